@@ -0,0 +1,51 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Fuzz target for the wire frame around every message on the daemon socket.
+//!
+//! Before `salusd` decodes a request it finds where the frame ends with
+//! `frame_len`, from a header the client wrote, then checks the frame's CRC-32
+//! and opens its envelope with `decode_frame_with_meta`
+//! (`salusd/src/runtime/mod.rs`). Both run over bytes straight off the socket,
+//! as the client's `decode_frame_with_meta::<Response>` runs over the daemon's.
+//!
+//! The corpus is seeded with valid frames, so the fuzzer starts from inputs
+//! that get past the checksum and into the envelope.
+//!
+//! Invariants verified:
+//! - No panic regardless of input, from `frame_len` or either decode.
+//! - A decoded frame re-encodes to a frame whose `frame_len` is its length, and
+//!   which decodes to the same meta and re-encodes to the same bytes.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libsalus::{Action, Response, decode_frame_with_meta, encode_frame_with, frame_len};
+
+/// Decode `data` as a frame of `$message`, and check a decoded one reframes.
+macro_rules! reframes {
+    ($message:ty, $data:expr) => {
+        if let Ok((meta, message)) = decode_frame_with_meta::<$message>($data)
+            && let Ok(reframed) = encode_frame_with(meta, &message)
+        {
+            let len = frame_len(&reframed).expect("a frame must have a length");
+            assert_eq!(len, Some(reframed.len()), "frame_len must span the frame");
+            let (meta2, message2) = decode_frame_with_meta::<$message>(&reframed)
+                .expect("a re-encoded frame must decode");
+            assert_eq!(meta, meta2, "the meta must survive a re-encode");
+            let reframed2 = encode_frame_with(meta2, &message2).expect("re-encode must succeed");
+            assert_eq!(reframed, reframed2, "encode/decode must be idempotent");
+        }
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _len = frame_len(data);
+    reframes!(Action, data);
+    reframes!(Response, data);
+});
@@ -0,0 +1,83 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Regression tests for `fuzz_decode_frame` crashes.
+//!
+//! Per the cargo-fuzz documentation, each crash is embedded here as a `&[u8]`
+//! constant so that `cargo test` permanently guards against regressions without
+//! requiring a nightly fuzzer run.
+//!
+//! To add a new crash:
+//! 1. Extract the bytes from the artifact downloaded from CI.
+//! 2. Run `xxd -i crash-<hash>` (or `hexdump -C`) to get the byte values.
+//! 3. Add a new test function following the pattern below.
+//! 4. Commit the raw crash file to `fuzz/artifacts/fuzz_decode_frame/crash-<hash>`
+//!    so `cargo +nightly fuzz run fuzz_decode_frame` also replays it.
+
+use libsalus::{
+    Action, FrameMeta, Response, Timing, decode_frame_with_meta, encode_frame, encode_frame_with,
+    frame_len,
+};
+
+macro_rules! reframes {
+    ($message:ty, $data:expr) => {
+        if let Ok((meta, message)) = decode_frame_with_meta::<$message>($data)
+            && let Ok(reframed) = encode_frame_with(meta, &message)
+        {
+            let len = frame_len(&reframed).expect("a frame must have a length");
+            assert_eq!(len, Some(reframed.len()));
+            let (meta2, message2) = decode_frame_with_meta::<$message>(&reframed)
+                .expect("a re-encoded frame must decode");
+            assert_eq!(meta, meta2);
+            let reframed2 = encode_frame_with(meta2, &message2).expect("re-encode must succeed");
+            assert_eq!(reframed, reframed2);
+        }
+    };
+}
+
+/// Mirrors the fuzz target body exactly. Any panic here is a confirmed bug.
+fn run_fuzz_decode_frame(data: &[u8]) {
+    let _len = frame_len(data);
+    reframes!(Action, data);
+    reframes!(Response, data);
+}
+
+#[test]
+fn regression_empty_and_short() {
+    run_fuzz_decode_frame(&[]);
+    run_fuzz_decode_frame(&[0]);
+    run_fuzz_decode_frame(&[0xff]);
+    run_fuzz_decode_frame(&[0, 0, 0, 0]);
+}
+
+#[test]
+fn regression_valid_frames_reframe() {
+    let timing = Timing::builder().build();
+    let meta = FrameMeta::builder().id(7).verbose(true).timing(timing).build();
+    run_fuzz_decode_frame(&encode_frame(Action::Read("github".into())).expect("encode"));
+    run_fuzz_decode_frame(&encode_frame_with(meta, Response::Pong).expect("encode"));
+}
+
+#[test]
+fn regression_bad_checksum_is_refused() {
+    let mut frame = encode_frame(Action::Lock).expect("encode");
+    if let Some(last) = frame.last_mut() {
+        *last ^= 0xff;
+    }
+    assert!(decode_frame_with_meta::<Action>(&frame).is_err());
+    run_fuzz_decode_frame(&frame);
+}
+
+#[test]
+fn regression_forged_message_length_is_bounded() {
+    // A header claiming a message of u64::MAX bytes must be refused, not
+    // overflow the frame's length.
+    let header = [0x03, 0x00, 0x00, 0x00, 0x00, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    assert!(frame_len(&header).is_err());
+    run_fuzz_decode_frame(&header);
+}
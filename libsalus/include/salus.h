@@ -0,0 +1,136 @@
+/*
+ * salus.h - the C interface to salusd, from libsalus built with the `ffi`
+ * feature. Generated by `cargo xtask header`; do not edit.
+ */
+
+#ifndef SALUS_H
+#define SALUS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The outcome of a call.
+ */
+typedef enum {
+  /**
+   * The call succeeded
+   */
+  SALUS_STATUS_OK = 0,
+  /**
+   * No value is stored under the key
+   */
+  SALUS_STATUS_NOT_FOUND = 1,
+  /**
+   * A value is already stored under the key, and `force` was not set
+   */
+  SALUS_STATUS_EXISTS = 2,
+  /**
+   * A pointer was null, or a string was not UTF-8
+   */
+  SALUS_STATUS_INVALID_ARGUMENT = 3,
+  /**
+   * The daemon could not be reached
+   */
+  SALUS_STATUS_CONNECTION = 4,
+  /**
+   * The daemon refused the request, as it does while the store is locked
+   */
+  SALUS_STATUS_REFUSED = 5,
+  /**
+   * The daemon is read-only and refuses changes
+   */
+  SALUS_STATUS_READ_ONLY = 6,
+  /**
+   * The daemon answered with something this library does not expect
+   */
+  SALUS_STATUS_UNEXPECTED = 7,
+} SalusStatus;
+
+/**
+ * A connection to `salusd`, opened by [`salus_connect`].
+ */
+typedef struct SalusClient SalusClient;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open a client for the daemon at `socket`, or at the default socket (or
+ * `SALUS_SOCKET`) when `socket` is null, and check that the daemon answers.
+ *
+ * Returns null when the daemon cannot be reached; [`salus_last_error`] says
+ * why.
+ *
+ * # Safety
+ *
+ * `socket` must be null or a NUL-terminated string.
+ */
+SalusClient *salus_connect(const char *socket);
+
+/**
+ * Close `client`. A null `client` is ignored.
+ *
+ * # Safety
+ *
+ * `client` must be null or a client from [`salus_connect`] that has not
+ * been closed.
+ */
+void salus_disconnect(SalusClient *client);
+
+/**
+ * Read the value stored under `key` into `*value` and `*len`.
+ *
+ * On [`SalusStatus::Ok`] the caller owns `*value` and must hand it, with
+ * `*len`, to [`salus_free_secret`]. Otherwise `*value` is null and `*len`
+ * is zero.
+ *
+ * # Safety
+ *
+ * `client` must be a client from [`salus_connect`], `key` a NUL-terminated
+ * string, and `value` and `len` valid places to write to.
+ */
+SalusStatus salus_read(const SalusClient *client, const char *key, uint8_t **value, size_t *len);
+
+/**
+ * Store the `len` bytes at `value`, which must be UTF-8, under `key`,
+ * replacing any value already there only if `force` is set.
+ *
+ * # Safety
+ *
+ * `client` must be a client from [`salus_connect`], `key` a NUL-terminated
+ * string, and `value` readable for `len` bytes.
+ */
+SalusStatus salus_store(const SalusClient *client,
+                        const char *key,
+                        const uint8_t *value,
+                        size_t len,
+                        bool force);
+
+/**
+ * Zero and free a secret returned by [`salus_read`]. A null `value` is
+ * ignored.
+ *
+ * # Safety
+ *
+ * `value` and `len` must be as [`salus_read`] returned them, and the secret
+ * not yet freed.
+ */
+void salus_free_secret(uint8_t *value, size_t len);
+
+/**
+ * Why the last call on this thread failed, or null if none has.
+ *
+ * The message stays valid until the next call on this thread.
+ */
+const char *salus_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SALUS_H */
@@ -0,0 +1,290 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Clients for `salusd`: the [`ClientApi`] trait an application codes
+//! against, and, with the `client` feature, `Client`, which blocks the
+//! calling thread on each request for callers with no async runtime of their
+//! own, such as the C interface and the Python bindings.
+
+use std::fmt;
+
+use anyhow::Result;
+#[cfg(feature = "client")]
+use anyhow::{anyhow, bail};
+#[cfg(feature = "client")]
+use interprocess::local_socket::tokio::{Stream, prelude::*};
+#[cfg(feature = "client")]
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    runtime::{Builder, Runtime},
+};
+#[cfg(feature = "client")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "client")]
+use crate::{
+    message::frame::{
+        FrameMeta, Timing, decode_frame_with_id, decode_frame_with_meta, encode_frame_with,
+        encode_frame_with_id, frame_len,
+    },
+    socket_name,
+    transport::{Reader, Writer, client_transport_key, initiate},
+};
+use crate::{
+    message::{Action, Response, Store},
+    secret::Secret,
+};
+
+/// Something that answers [`Action`]s the way `salusd` does: `Client`, or
+/// `testing::MockClient` in an application's tests.
+///
+/// Only [`send`](ClientApi::send) is required; the rest wrap the common
+/// requests, failing with a [`Refusal`] when the daemon refuses one.
+pub trait ClientApi {
+    /// Send `action` and wait for the daemon's response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, or its response
+    /// cannot be decoded.
+    fn send(&self, action: Action) -> Result<Response>;
+
+    /// The value stored under `key`, or `None` when nothing is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, or a [`Refusal`] if
+    /// it refuses the read.
+    fn read(&self, key: &str) -> Result<Option<Secret<Vec<u8>>>> {
+        match self.send(Action::Read(key.to_string()))? {
+            Response::Value(value) => Ok(value.map(Secret::new)),
+            Response::KeyNotFound => Ok(None),
+            other => Err(Refusal::from(other).into()),
+        }
+    }
+
+    /// Store `value` under `key`, replacing any value already there only if
+    /// `force` is set. Returns whether the value was stored: `false` when
+    /// one was already there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, or a [`Refusal`] if
+    /// it refuses the change.
+    fn store(&self, key: &str, value: &str, force: bool) -> Result<bool> {
+        let store = Store::builder().key(key).value(value).force(force).build();
+        match self.send(Action::Store(store))? {
+            Response::Success => Ok(true),
+            Response::KeyExists => Ok(false),
+            other => Err(Refusal::from(other).into()),
+        }
+    }
+
+    /// Delete the value stored under `key`. Returns whether there was one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, or a [`Refusal`] if
+    /// it refuses the change.
+    fn delete(&self, key: &str) -> Result<bool> {
+        match self.send(Action::Delete(key.to_string()))? {
+            Response::Success => Ok(true),
+            Response::KeyNotFound => Ok(false),
+            other => Err(Refusal::from(other).into()),
+        }
+    }
+}
+
+/// Why the daemon refused a request made through a [`ClientApi`] helper,
+/// found on the error with `downcast_ref::<Refusal>()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Refusal {
+    /// The daemon refused, saying why (as it does while the store is locked)
+    Error(String),
+    /// The daemon is read-only and refuses changes
+    ReadOnly,
+    /// The request is longer than the daemon accepts; carries its limit in
+    /// bytes
+    MessageTooLarge(u32),
+    /// The daemon does not know the action, most likely because it is older;
+    /// carries the action's tag
+    UnknownAction(u32),
+    /// The daemon answered with something the request does not expect
+    Unexpected,
+}
+
+impl From<Response> for Refusal {
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Error(error) => Refusal::Error(error),
+            Response::ReadOnly => Refusal::ReadOnly,
+            Response::MessageTooLarge(limit) => Refusal::MessageTooLarge(limit),
+            Response::UnknownAction(tag) => Refusal::UnknownAction(tag),
+            _ => Refusal::Unexpected,
+        }
+    }
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Error(error) => f.write_str(error),
+            Refusal::ReadOnly => f.write_str("salusd is read-only and refuses changes"),
+            Refusal::MessageTooLarge(limit) => {
+                write!(f, "salusd accepts requests of at most {limit} bytes")
+            }
+            Refusal::UnknownAction(tag) => {
+                write!(
+                    f,
+                    "salusd does not know action {tag}; it may be out of date"
+                )
+            }
+            Refusal::Unexpected => f.write_str("unexpected response from salusd"),
+        }
+    }
+}
+
+impl std::error::Error for Refusal {}
+
+/// A connection to `salusd` that blocks the calling thread on each request.
+///
+/// Like the async client in `salusc`, every request opens its own connection,
+/// so one `Client` can be shared between threads.
+#[cfg(feature = "client")]
+#[derive(Debug)]
+pub struct Client {
+    /// The socket override the client was opened with.
+    socket: Option<String>,
+    /// Drives each request to completion on the calling thread.
+    runtime: Runtime,
+}
+
+#[cfg(feature = "client")]
+impl Client {
+    /// Open a client for the daemon at `socket`, or at the default socket (or
+    /// `SALUS_SOCKET`) when it is `None`, and check that the daemon answers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached.
+    pub fn connect(socket: Option<&str>) -> Result<Self> {
+        let client = Self {
+            socket: socket.map(String::from),
+            runtime: Builder::new_current_thread().enable_all().build()?,
+        };
+        match client.send(Action::Status)? {
+            Response::Status(_) => Ok(client),
+            _ => bail!("unexpected response from salusd"),
+        }
+    }
+
+    /// Send every one of `actions` on one connection and wait for all their
+    /// responses, returned in the order of `actions`.
+    ///
+    /// The requests are pipelined: each carries its own id, the daemon
+    /// answers them concurrently, and each response is matched to its request
+    /// by that id. Because they run concurrently, a request that depends on
+    /// another's outcome belongs in a later call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, a response cannot be
+    /// decoded or matched to a request, or a [`Refusal`] if the daemon refuses
+    /// the connection's requests as a whole (as it does when one is too long).
+    pub fn pipeline(&self, actions: Vec<Action>) -> Result<Vec<Response>> {
+        let mut responses: Vec<Option<Response>> = actions.iter().map(|_| None).collect();
+        let stream = self.runtime.block_on(async move {
+            let (mut recver, mut sender) = open(self.socket.as_deref()).await?;
+            for (id, action) in (1u64..).zip(actions) {
+                sender.write_all(&encode_frame_with_id(id, action)?).await?;
+            }
+            sender.flush().await?;
+            // The daemon answers until it has read the last request.
+            drop(sender);
+            let mut stream = Zeroizing::new(Vec::new());
+            let _len = recver.read_to_end(&mut stream).await?;
+            Ok::<_, anyhow::Error>(stream)
+        })?;
+        let mut rest = stream.as_slice();
+        while !rest.is_empty() {
+            let Some((frame, tail)) = frame_len(rest)?.and_then(|len| rest.split_at_checked(len))
+            else {
+                bail!("salusd closed the connection in the middle of a response");
+            };
+            rest = tail;
+            let (id, response) = decode_frame_with_id::<Response>(frame)?;
+            // Id 0 answers no request of ours: the daemon refused them all.
+            if id == 0 {
+                return Err(Refusal::from(response).into());
+            }
+            let slot = usize::try_from(id)
+                .ok()
+                .and_then(|id| id.checked_sub(1))
+                .and_then(|index| responses.get_mut(index));
+            match slot {
+                Some(slot @ None) => *slot = Some(response),
+                _ => bail!("salusd answered request {id}, which was not pending"),
+            }
+        }
+        (1u64..)
+            .zip(responses)
+            .map(|(id, response)| {
+                response.ok_or_else(|| anyhow!("salusd did not answer request {id}"))
+            })
+            .collect()
+    }
+
+    /// Send `action`, asking the daemon to time it, and wait for its response
+    /// and the [`Timing`] the daemon reports with it.
+    ///
+    /// The timing is `None` if the daemon did not report one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached, or its response
+    /// cannot be decoded.
+    pub fn send_timed(&self, action: Action) -> Result<(Response, Option<Timing>)> {
+        let meta = FrameMeta::builder().verbose(true).build();
+        self.exchange(meta, action)
+            .map(|(meta, response)| (response, meta.timing()))
+    }
+
+    /// Send `action` in a frame carrying `meta` on a connection of its own.
+    fn exchange(&self, meta: FrameMeta, action: Action) -> Result<(FrameMeta, Response)> {
+        self.runtime.block_on(async move {
+            let (mut recver, mut sender) = open(self.socket.as_deref()).await?;
+            sender.write_all(&encode_frame_with(meta, action)?).await?;
+            sender.flush().await?;
+            // The daemon reads the request to its end before answering.
+            drop(sender);
+            let mut response = Zeroizing::new(Vec::new());
+            let _len = recver.read_to_end(&mut response).await?;
+            if response.is_empty() {
+                bail!("salusd closed the connection without responding");
+            }
+            decode_frame_with_meta(&response)
+        })
+    }
+}
+
+/// Connect to the daemon at `socket`, encrypting the connection when the
+/// socket is shared.
+#[cfg(feature = "client")]
+async fn open(socket: Option<&str>) -> Result<(Reader, Writer)> {
+    let key = client_transport_key(socket, None)?;
+    let (recver, sender) = Stream::connect(socket_name(socket)?).await?.split();
+    initiate(recver, sender, key.as_ref()).await
+}
+
+#[cfg(feature = "client")]
+impl ClientApi for Client {
+    fn send(&self, action: Action) -> Result<Response> {
+        self.exchange(FrameMeta::default(), action)
+            .map(|(_meta, response)| response)
+    }
+}
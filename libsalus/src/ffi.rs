@@ -0,0 +1,452 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A C interface to `salusd`, built into the `cdylib` when the `ffi` feature
+//! is on; `include/salus.h` declares it.
+//!
+//! A client is opened with [`salus_connect`] and closed with
+//! [`salus_disconnect`]. Every call blocks until the daemon answers and
+//! returns a [`SalusStatus`]; when it is not [`SalusStatus::Ok`],
+//! [`salus_last_error`] says why. A secret from [`salus_read`] belongs to the
+//! caller, who hands it back to [`salus_free_secret`] to be zeroed and freed.
+
+// A C interface is only reachable through raw pointers.
+#![allow(unsafe_code)]
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    ptr::{self, null, null_mut},
+    slice,
+};
+
+use anyhow::Result;
+use zeroize::{Zeroize as _, Zeroizing};
+
+use crate::{
+    client::{Client, ClientApi as _},
+    message::{Action, Response, Store},
+};
+
+thread_local! {
+    /// Why the last call on this thread failed.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The outcome of a call.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub enum SalusStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// No value is stored under the key
+    NotFound = 1,
+    /// A value is already stored under the key, and `force` was not set
+    Exists = 2,
+    /// A pointer was null, or a string was not UTF-8
+    InvalidArgument = 3,
+    /// The daemon could not be reached
+    Connection = 4,
+    /// The daemon refused the request, as it does while the store is locked
+    Refused = 5,
+    /// The daemon is read-only and refuses changes
+    ReadOnly = 6,
+    /// The daemon answered with something this library does not expect
+    Unexpected = 7,
+}
+
+/// A connection to `salusd`, opened by [`salus_connect`].
+#[derive(Debug)]
+pub struct SalusClient(Client);
+
+/// Open a client for the daemon at `socket`, or at the default socket (or
+/// `SALUS_SOCKET`) when `socket` is null, and check that the daemon answers.
+///
+/// Returns null when the daemon cannot be reached; [`salus_last_error`] says
+/// why.
+///
+/// # Safety
+///
+/// `socket` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn salus_connect(socket: *const c_char) -> *mut SalusClient {
+    // SAFETY: the caller passes null or a NUL-terminated string.
+    let socket = match unsafe { text(socket, "socket") } {
+        Ok(socket) => socket,
+        Err(_status) => return null_mut(),
+    };
+    match Client::connect(socket) {
+        Ok(client) => Box::into_raw(Box::new(SalusClient(client))),
+        Err(e) => {
+            let _status = fail(SalusStatus::Connection, format!("{e:#}"));
+            null_mut()
+        }
+    }
+}
+
+/// Close `client`. A null `client` is ignored.
+///
+/// # Safety
+///
+/// `client` must be null or a client from [`salus_connect`] that has not
+/// been closed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn salus_disconnect(client: *mut SalusClient) {
+    if !client.is_null() {
+        // SAFETY: the client came from `Box::into_raw` in `salus_connect` and
+        // is dropped only here.
+        drop(unsafe { Box::from_raw(client) });
+    }
+}
+
+/// Read the value stored under `key` into `*value` and `*len`.
+///
+/// On [`SalusStatus::Ok`] the caller owns `*value` and must hand it, with
+/// `*len`, to [`salus_free_secret`]. Otherwise `*value` is null and `*len`
+/// is zero.
+///
+/// # Safety
+///
+/// `client` must be a client from [`salus_connect`], `key` a NUL-terminated
+/// string, and `value` and `len` valid places to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn salus_read(
+    client: *const SalusClient,
+    key: *const c_char,
+    value: *mut *mut u8,
+    len: *mut usize,
+) -> SalusStatus {
+    if value.is_null() || len.is_null() {
+        return fail(
+            SalusStatus::InvalidArgument,
+            "value and len must not be null",
+        );
+    }
+    // SAFETY: both were just checked to be non-null, and the caller passes
+    // valid places to write to.
+    unsafe {
+        value.write(null_mut());
+        len.write(0);
+    }
+    // SAFETY: the caller passes a client from `salus_connect`.
+    let Some(client) = (unsafe { client.as_ref() }) else {
+        return fail(SalusStatus::InvalidArgument, "client must not be null");
+    };
+    // SAFETY: the caller passes a NUL-terminated string.
+    let key = match unsafe { text(key, "key") } {
+        Ok(Some(key)) => key.to_string(),
+        Ok(None) => return fail(SalusStatus::InvalidArgument, "key must not be null"),
+        Err(status) => return status,
+    };
+    match client.0.send(Action::Read(key)) {
+        Ok(Response::Value(Some(secret))) => {
+            // Copied into an allocation of its exact length, so the one it
+            // was decoded into can be zeroed.
+            let secret = Zeroizing::new(secret);
+            let secret = Box::into_raw(Box::<[u8]>::from(secret.as_slice()));
+            // SAFETY: checked non-null above; `salus_free_secret` rebuilds
+            // the box from this pointer and length.
+            unsafe {
+                value.write(secret.cast());
+                len.write(secret.len());
+            }
+            SalusStatus::Ok
+        }
+        Ok(Response::Value(None) | Response::KeyNotFound) => {
+            fail(SalusStatus::NotFound, "no value is stored under the key")
+        }
+        other => failure(other),
+    }
+}
+
+/// Store the `len` bytes at `value`, which must be UTF-8, under `key`,
+/// replacing any value already there only if `force` is set.
+///
+/// # Safety
+///
+/// `client` must be a client from [`salus_connect`], `key` a NUL-terminated
+/// string, and `value` readable for `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn salus_store(
+    client: *const SalusClient,
+    key: *const c_char,
+    value: *const u8,
+    len: usize,
+    force: bool,
+) -> SalusStatus {
+    // SAFETY: the caller passes a client from `salus_connect`.
+    let Some(client) = (unsafe { client.as_ref() }) else {
+        return fail(SalusStatus::InvalidArgument, "client must not be null");
+    };
+    // SAFETY: the caller passes a NUL-terminated string.
+    let key = match unsafe { text(key, "key") } {
+        Ok(Some(key)) => key.to_string(),
+        Ok(None) => return fail(SalusStatus::InvalidArgument, "key must not be null"),
+        Err(status) => return status,
+    };
+    let bytes = if len == 0 {
+        &[][..]
+    } else if value.is_null() {
+        return fail(SalusStatus::InvalidArgument, "value must not be null");
+    } else {
+        // SAFETY: the caller passes `len` readable bytes at `value`.
+        unsafe { slice::from_raw_parts(value, len) }
+    };
+    let Ok(secret) = std::str::from_utf8(bytes) else {
+        return fail(SalusStatus::InvalidArgument, "value must be UTF-8");
+    };
+    let store = Store::builder().key(key).value(secret).force(force).build();
+    match client.0.send(Action::Store(store)) {
+        Ok(Response::Success) => SalusStatus::Ok,
+        Ok(Response::KeyExists) => fail(
+            SalusStatus::Exists,
+            "a value is already stored under the key",
+        ),
+        other => failure(other),
+    }
+}
+
+/// Zero and free a secret returned by [`salus_read`]. A null `value` is
+/// ignored.
+///
+/// # Safety
+///
+/// `value` and `len` must be as [`salus_read`] returned them, and the secret
+/// not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn salus_free_secret(value: *mut u8, len: usize) {
+    if value.is_null() {
+        return;
+    }
+    // SAFETY: the pointer and length are those of the boxed slice handed out
+    // by `salus_read`, which is rebuilt and dropped only here.
+    let mut secret = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(value, len)) };
+    secret.zeroize();
+}
+
+/// Why the last call on this thread failed, or null if none has.
+///
+/// The message stays valid until the next call on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn salus_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ref().map_or(null(), |error| error.as_ptr()))
+}
+
+/// Record `message` as the reason for `status`.
+fn fail(status: SalusStatus, message: impl Into<String>) -> SalusStatus {
+    let message = message.into().replace('\0', " ");
+    LAST_ERROR.set(CString::new(message).ok());
+    status
+}
+
+/// The status of a failed request, or of a response no call expects.
+fn failure(response: Result<Response>) -> SalusStatus {
+    match response {
+        Ok(Response::ReadOnly) => fail(
+            SalusStatus::ReadOnly,
+            "salusd is read-only and refuses changes",
+        ),
+        Ok(Response::Error(error)) => fail(SalusStatus::Refused, error),
+        Ok(Response::MessageTooLarge(limit)) => fail(
+            SalusStatus::Refused,
+            format!("salusd accepts requests of at most {limit} bytes"),
+        ),
+        Ok(Response::UnknownAction(tag)) => fail(
+            SalusStatus::Refused,
+            format!("salusd does not know action {tag}; it may be out of date"),
+        ),
+        Ok(_) => fail(SalusStatus::Unexpected, "unexpected response from salusd"),
+        Err(e) => fail(SalusStatus::Connection, format!("{e:#}")),
+    }
+}
+
+/// The string at `text`, or nothing for a null pointer.
+///
+/// # Safety
+///
+/// `text` must be null or a NUL-terminated string that outlives `'a`.
+unsafe fn text<'a>(text: *const c_char, name: &str) -> Result<Option<&'a str>, SalusStatus> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: the caller passes a NUL-terminated string.
+    unsafe { CStr::from_ptr(text) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| {
+            fail(
+                SalusStatus::InvalidArgument,
+                format!("{name} must be UTF-8"),
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ffi::{CStr, CString},
+        io::{Read as _, Write as _},
+        ptr::{null, null_mut},
+        thread::{self, JoinHandle},
+    };
+
+    use anyhow::{Context as _, Result, bail};
+    use interprocess::local_socket::{ListenerOptions, prelude::*};
+
+    use super::{
+        SalusStatus, salus_connect, salus_disconnect, salus_free_secret, salus_last_error,
+        salus_read, salus_store,
+    };
+    use crate::{
+        message::{
+            Action, Response, StoreStatus,
+            frame::{decode_frame, encode_frame},
+        },
+        socket_name,
+    };
+
+    /// Answer one request per response on `path`, returning the requests.
+    fn mock_daemon(
+        path: &str,
+        responses: Vec<Response>,
+    ) -> Result<JoinHandle<Result<Vec<Action>>>> {
+        let listener = ListenerOptions::new()
+            .name(socket_name(Some(path))?)
+            .create_sync()?;
+        Ok(thread::spawn(move || {
+            let mut actions = vec![];
+            for response in responses {
+                let mut conn = listener.accept()?;
+                let mut request = vec![];
+                let _len = conn.read_to_end(&mut request)?;
+                actions.push(decode_frame(&request)?);
+                conn.write_all(&encode_frame(response)?)?;
+            }
+            Ok(actions)
+        }))
+    }
+
+    fn socket_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("salus-ffi-{name}-{}.sock", std::process::id()))
+            .display()
+            .to_string()
+    }
+
+    fn last_error() -> String {
+        // SAFETY: the message is NUL-terminated and this thread makes no call
+        // before it is copied.
+        unsafe { CStr::from_ptr(salus_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn reads_and_stores_through_the_c_interface() -> Result<()> {
+        let path = socket_path("round-trip");
+        let _old = std::fs::remove_file(&path);
+        let daemon = mock_daemon(
+            &path,
+            vec![
+                Response::Status(
+                    StoreStatus::builder()
+                        .initialized(true)
+                        .sealed(false)
+                        .threshold(3)
+                        .num_shares(5)
+                        .shares_collected(0)
+                        .daemon_version("test")
+                        .build(),
+                ),
+                Response::Value(Some(b"s3cret".to_vec())),
+                Response::KeyNotFound,
+                Response::KeyExists,
+                Response::ReadOnly,
+            ],
+        )?;
+        let socket = CString::new(path.clone())?;
+        let key = CString::new("db/password")?;
+        // SAFETY: every pointer passed is valid for the call, and the client
+        // and secret are freed once, after their last use.
+        unsafe {
+            let client = salus_connect(socket.as_ptr());
+            if client.is_null() {
+                bail!("connect failed: {}", last_error());
+            }
+            let mut value = null_mut();
+            let mut len = 0;
+            assert_eq!(
+                salus_read(client, key.as_ptr(), &raw mut value, &raw mut len),
+                SalusStatus::Ok
+            );
+            let secret = std::slice::from_raw_parts(value, len).to_vec();
+            salus_free_secret(value, len);
+            assert_eq!(secret, b"s3cret");
+
+            assert_eq!(
+                salus_read(client, key.as_ptr(), &raw mut value, &raw mut len),
+                SalusStatus::NotFound
+            );
+            assert!(value.is_null());
+
+            let new = b"n3w";
+            assert_eq!(
+                salus_store(client, key.as_ptr(), new.as_ptr(), new.len(), false),
+                SalusStatus::Exists
+            );
+            assert_eq!(
+                salus_store(client, key.as_ptr(), new.as_ptr(), new.len(), true),
+                SalusStatus::ReadOnly
+            );
+            assert!(last_error().contains("read-only"));
+            salus_disconnect(client);
+        }
+        let actions = daemon.join().ok().context("mock daemon panicked")??;
+        let _removed = std::fs::remove_file(&path);
+        let Some(Action::Store(store)) = actions.last() else {
+            bail!("expected a store, got {actions:?}");
+        };
+        assert_eq!((store.key(), store.value()), ("db/password", "n3w"));
+        assert!(store.force());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_arguments_are_refused_without_a_request() {
+        let value = [0xff_u8, 0xfe];
+        // SAFETY: null pointers are what is being checked, and `value` is
+        // readable for its length.
+        unsafe {
+            assert_eq!(
+                salus_read(null(), c"k".as_ptr(), null_mut(), null_mut()),
+                SalusStatus::InvalidArgument
+            );
+            let mut secret = null_mut();
+            let mut len = 0;
+            assert_eq!(
+                salus_read(null(), c"k".as_ptr(), &raw mut secret, &raw mut len),
+                SalusStatus::InvalidArgument
+            );
+            assert_eq!(
+                salus_store(null(), c"k".as_ptr(), value.as_ptr(), value.len(), false),
+                SalusStatus::InvalidArgument
+            );
+            salus_free_secret(null_mut(), 0);
+            salus_disconnect(null_mut());
+        }
+        assert_eq!(last_error(), "client must not be null");
+    }
+
+    #[test]
+    fn connect_fails_without_a_daemon() {
+        let socket = CString::new(socket_path("absent")).unwrap_or_default();
+        // SAFETY: `socket` is NUL-terminated.
+        assert!(unsafe { salus_connect(socket.as_ptr()) }.is_null());
+        assert!(!last_error().is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Random secret generation.
+//!
+//! The daemon uses this to answer [`Action::Generate`](crate::Action::Generate)
+//! so a generated secret can be stored without ever reaching the client. The
+//! caller supplies the random bytes, which keeps this crate free of an RNG
+//! dependency; indices are drawn by rejection sampling so every character or
+//! word is equally likely.
+//!
+//! The bundled passphrase word list is the EFF "large" word list, which is
+//! distributed by the Electronic Frontier Foundation under the Creative
+//! Commons Attribution 3.0 United States license (CC BY 3.0 US). See
+//! <https://www.eff.org/dice> and `eff_large_wordlist.txt`.
+
+use anyhow::{Result, bail};
+use bincode_next::{Decode, Encode};
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The EFF "large" word list (7776 words, one per line), embedded at build time.
+const WORDLIST: &str = include_str!("eff_large_wordlist.txt");
+
+/// The longest character secret that may be requested.
+pub const MAX_SECRET_LENGTH: u32 = 4096;
+/// The most words a passphrase may have.
+pub const MAX_PASSPHRASE_WORDS: u32 = 64;
+
+/// The words passphrases are drawn from.
+pub fn passphrase_words() -> impl Iterator<Item = &'static str> {
+    WORDLIST.lines().filter(|line| !line.is_empty())
+}
+
+/// The characters a generated secret is drawn from.
+#[derive(Clone, Copy, Debug, Decode, Default, Encode, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(Deserialize, Serialize))]
+pub enum Charset {
+    /// ASCII letters and digits
+    Alnum,
+    /// ASCII letters, digits, and symbols
+    #[default]
+    AlnumSymbols,
+    /// Lowercase hexadecimal digits
+    Hex,
+    /// Decimal digits
+    Digits,
+}
+
+impl Charset {
+    /// The characters in this set.
+    #[must_use]
+    pub fn alphabet(self) -> &'static str {
+        match self {
+            Charset::Alnum => "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+            Charset::AlnumSymbols => {
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*()-_=+[]{};:,.<>?"
+            }
+            Charset::Hex => "0123456789abcdef",
+            Charset::Digits => "0123456789",
+        }
+    }
+}
+
+/// What kind of secret to generate.
+#[derive(Clone, Debug, Decode, Encode, Eq, PartialEq)]
+#[cfg_attr(feature = "json", derive(Deserialize, Serialize))]
+pub enum SecretSpec {
+    /// `length` characters drawn uniformly from `charset`
+    Chars {
+        /// The number of characters (1 to [`MAX_SECRET_LENGTH`])
+        length: u32,
+        /// The characters to draw from
+        charset: Charset,
+    },
+    /// A diceware-style passphrase
+    Passphrase {
+        /// The number of words (1 to [`MAX_PASSPHRASE_WORDS`])
+        words: u32,
+        /// Placed between consecutive words
+        separator: String,
+    },
+}
+
+/// Generate a secret as described by `spec`, taking randomness from `fill`.
+///
+/// `fill` must fill its argument with cryptographically secure random bytes.
+///
+/// # Errors
+///
+/// Returns an error if the length or word count is out of range, or `fill`
+/// fails.
+pub fn generate_secret<F>(spec: &SecretSpec, mut fill: F) -> Result<String>
+where
+    F: FnMut(&mut [u8]) -> Result<()>,
+{
+    match spec {
+        SecretSpec::Chars { length, charset } => {
+            if !(1..=MAX_SECRET_LENGTH).contains(length) {
+                bail!("length must be between 1 and {MAX_SECRET_LENGTH}");
+            }
+            let alphabet: Vec<char> = charset.alphabet().chars().collect();
+            let mut secret = String::new();
+            for _ in 0..*length {
+                let idx = uniform_index(alphabet.len(), &mut fill)?;
+                if let Some(c) = alphabet.get(idx) {
+                    secret.push(*c);
+                }
+            }
+            Ok(secret)
+        }
+        SecretSpec::Passphrase { words, separator } => {
+            if !(1..=MAX_PASSPHRASE_WORDS).contains(words) {
+                bail!("words must be between 1 and {MAX_PASSPHRASE_WORDS}");
+            }
+            let list: Vec<&str> = passphrase_words().collect();
+            let mut phrase = String::new();
+            for n in 0..*words {
+                if n > 0 {
+                    phrase.push_str(separator);
+                }
+                let idx = uniform_index(list.len(), &mut fill)?;
+                if let Some(word) = list.get(idx) {
+                    phrase.push_str(word);
+                }
+            }
+            Ok(phrase)
+        }
+    }
+}
+
+/// Draw an index in `0..bound` without modulo bias.
+fn uniform_index<F>(bound: usize, fill: &mut F) -> Result<usize>
+where
+    F: FnMut(&mut [u8]) -> Result<()>,
+{
+    let Ok(bound) = u32::try_from(bound) else {
+        bail!("alphabet too large");
+    };
+    let Some(excess) = u32::MAX.checked_rem(bound) else {
+        bail!("cannot draw from an empty set");
+    };
+    // The largest multiple of `bound` that fits in a u32; draws at or above it
+    // would favour the low indices and are thrown away.
+    let zone = u32::MAX.saturating_sub(excess);
+    let mut buf = [0u8; 4];
+    loop {
+        fill(&mut buf)?;
+        let draw = u32::from_le_bytes(buf);
+        buf.zeroize();
+        if draw < zone
+            && let Some(idx) = draw.checked_rem(bound)
+        {
+            return Ok(usize::try_from(idx)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::{Charset, MAX_SECRET_LENGTH, SecretSpec, generate_secret, passphrase_words};
+
+    fn rng_fill(buf: &mut [u8]) -> Result<()> {
+        Ok(aws_lc_rs::rand::fill(buf)?)
+    }
+
+    #[test]
+    fn chars_use_only_the_charset() -> Result<()> {
+        for charset in [
+            Charset::Alnum,
+            Charset::AlnumSymbols,
+            Charset::Hex,
+            Charset::Digits,
+        ] {
+            let spec = SecretSpec::Chars {
+                length: 64,
+                charset,
+            };
+            let secret = generate_secret(&spec, rng_fill)?;
+            assert_eq!(secret.chars().count(), 64);
+            assert!(secret.chars().all(|c| charset.alphabet().contains(c)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn passphrase_joins_list_words() -> Result<()> {
+        let spec = SecretSpec::Passphrase {
+            words: 6,
+            separator: "-".to_string(),
+        };
+        let phrase = generate_secret(&spec, rng_fill)?;
+        let words: Vec<&str> = phrase.split('-').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| passphrase_words().any(|l| l == *w)));
+        assert_eq!(passphrase_words().count(), 7776);
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_range_sizes_and_failed_fills_are_errors() {
+        let too_long = SecretSpec::Chars {
+            length: MAX_SECRET_LENGTH + 1,
+            charset: Charset::Hex,
+        };
+        assert!(generate_secret(&too_long, rng_fill).is_err());
+        let empty = SecretSpec::Passphrase {
+            words: 0,
+            separator: " ".to_string(),
+        };
+        assert!(generate_secret(&empty, rng_fill).is_err());
+        let spec = SecretSpec::Chars {
+            length: 8,
+            charset: Charset::Hex,
+        };
+        assert!(generate_secret(&spec, |_| anyhow::bail!("no entropy")).is_err());
+    }
+}
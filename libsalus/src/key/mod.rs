@@ -11,14 +11,14 @@ use ssss::{SsssConfig, unlock};
 use tracing::trace;
 use zeroize::Zeroizing;
 
-/// Generate a new key and split it into shares using Shamir's Secret Sharing Scheme.
+/// Split a key into shares using Shamir's Secret Sharing Scheme.
 ///
 /// # Errors
 ///
 /// * If the random number generation fails, an error is returned.
 /// * If the share generation fails, an error is returned.
 ///
-pub fn gen_shares(config: &SsssConfig, key: &[u8; 32]) -> Result<Vec<String>> {
+pub fn gen_shares(config: &SsssConfig, key: &[u8]) -> Result<Vec<String>> {
     trace!("Generating shares from key");
     ssss::gen_shares(config, key)
 }
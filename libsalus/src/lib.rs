@@ -253,31 +253,164 @@ use interprocess::local_socket::GenericFilePath;
 use interprocess::local_socket::Name;
 use interprocess::local_socket::ToFsName;
 
+mod client;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod generate;
+#[cfg(feature = "keys")]
 mod key;
 mod message;
 mod search;
+mod secret;
+mod share;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "noise")]
+mod transport;
+#[cfg(feature = "keys")]
+mod wrap;
 
+#[cfg(feature = "client")]
+pub use crate::client::Client;
+pub use crate::client::ClientApi;
+pub use crate::client::Refusal;
+pub use crate::generate::Charset;
+pub use crate::generate::MAX_PASSPHRASE_WORDS;
+pub use crate::generate::MAX_SECRET_LENGTH;
+pub use crate::generate::SecretSpec;
+pub use crate::generate::generate_secret;
+pub use crate::generate::passphrase_words;
+#[cfg(feature = "keys")]
 pub use crate::key::gen_shares;
+#[cfg(feature = "keys")]
 pub use crate::key::unlock_key;
 pub use crate::message::Action;
+pub use crate::message::ApprovalInfo;
+pub use crate::message::Backup;
+pub use crate::message::BackupInfo;
+pub use crate::message::BatchOutcome;
+pub use crate::message::BeginUpload;
+pub use crate::message::CHUNK_SIZE;
+pub use crate::message::CertInfo;
+pub use crate::message::ConfigReload;
+pub use crate::message::Credential;
+pub use crate::message::DataKey;
+pub use crate::message::DecryptRequest;
+pub use crate::message::DeletePrefix;
+pub use crate::message::DiffRequest;
+pub use crate::message::EncryptRequest;
+pub use crate::message::ExportSync;
+pub use crate::message::ExportWrapped;
+pub use crate::message::FieldChange;
+pub use crate::message::FieldChangeKind;
+pub use crate::message::GenerateSecret;
+pub use crate::message::ImportCa;
+pub use crate::message::ImportSync;
+pub use crate::message::ImportWrapped;
 pub use crate::message::Init;
+pub use crate::message::IntegrityProblem;
+pub use crate::message::IntegrityReport;
+pub use crate::message::IssueCert;
+pub use crate::message::IssuedCert;
+pub use crate::message::KeyAlgorithm;
+pub use crate::message::KeyStat;
+pub use crate::message::LeaseInfo;
+pub use crate::message::Link;
+pub use crate::message::MAX_DATA_KEY_BITS;
+pub use crate::message::MAX_KEY_NAME_LEN;
 pub use crate::message::MAX_MESSAGE_SIZE;
 pub use crate::message::MAX_UNLOCK_SECONDS;
+pub use crate::message::MIN_DATA_KEY_BITS;
+pub use crate::message::MintCredential;
+pub use crate::message::NamedKeyInfo;
+pub use crate::message::NewCa;
+pub use crate::message::NewNamedKey;
+pub use crate::message::NewSigningKey;
+pub use crate::message::Patch;
+pub use crate::message::PluginInfo;
+pub use crate::message::ReadChunk;
+pub use crate::message::ReadField;
+pub use crate::message::RenewLease;
 pub use crate::message::Response;
+pub use crate::message::RewrapProgress;
+pub use crate::message::RotationWarning;
 pub use crate::message::SearchQuery;
+pub use crate::message::SetRotation;
 pub use crate::message::Share;
 pub use crate::message::Shares;
+pub use crate::message::SignRequest;
+pub use crate::message::SignSshKey;
+pub use crate::message::SigningAlgorithm;
+pub use crate::message::SshCertificate;
 pub use crate::message::Store;
+pub use crate::message::StoreBatch;
+pub use crate::message::StoreStatus;
+pub use crate::message::StreamedValue;
+pub use crate::message::SyncBundle;
+pub use crate::message::SyncEntry;
+pub use crate::message::SyncOutcome;
+pub use crate::message::SyncStrategy;
 pub use crate::message::UnlockTimeout;
+pub use crate::message::UploadChunk;
+pub use crate::message::ValidationFailure;
+pub use crate::message::ValueVersion;
+pub use crate::message::VerifyRequest;
 pub use crate::message::agent::AgentAction;
 pub use crate::message::agent::AgentResponse;
 pub use crate::message::agent::SetInfo;
+pub use crate::message::chunk_count;
+pub use crate::message::chunk_len;
 pub use crate::message::decode;
+#[cfg(feature = "json")]
+pub use crate::message::decode_json;
 pub use crate::message::encode;
+#[cfg(feature = "json")]
+pub use crate::message::encode_json;
+pub use crate::message::frame::FrameMeta;
+pub use crate::message::frame::PROTOCOL_VERSION;
+pub use crate::message::frame::Timing;
+pub use crate::message::frame::UnknownMessage;
+pub use crate::message::frame::decode_frame;
+pub use crate::message::frame::decode_frame_with_id;
+pub use crate::message::frame::decode_frame_with_meta;
+pub use crate::message::frame::encode_frame;
+pub use crate::message::frame::encode_frame_with;
+pub use crate::message::frame::encode_frame_with_id;
+pub use crate::message::frame::frame_len;
 pub use crate::search::fuzzy_rank;
+pub use crate::secret::Secret;
+pub use crate::share::mnemonic_to_share;
+pub use crate::share::normalize_share;
+pub use crate::share::share_parts;
+pub use crate::share::share_to_mnemonic;
+pub use crate::share::unwrap_share;
+pub use crate::share::wrap_share;
+#[cfg(feature = "noise")]
+pub use crate::transport::Reader;
+#[cfg(feature = "noise")]
+pub use crate::transport::TRANSPORT_KEY_LEN;
+#[cfg(feature = "noise")]
+pub use crate::transport::TransportKey;
+#[cfg(feature = "noise")]
+pub use crate::transport::Writer;
+#[cfg(feature = "noise")]
+pub use crate::transport::client_transport_key;
+#[cfg(feature = "noise")]
+pub use crate::transport::initiate;
+#[cfg(feature = "noise")]
+pub use crate::transport::respond;
+#[cfg(feature = "noise")]
+pub use crate::transport::transport_key_path;
+#[cfg(feature = "keys")]
+pub use crate::wrap::WRAP_PUBLIC_KEY_LEN;
+#[cfg(feature = "keys")]
+pub use crate::wrap::WrappingKey;
+#[cfg(feature = "keys")]
+pub use crate::wrap::wrap_key;
 use interprocess::local_socket::GenericNamespaced;
 use interprocess::local_socket::NameType;
 use interprocess::local_socket::ToNsName;
+#[cfg(feature = "keys")]
 pub use ssss::SsssConfig;
 
 /// The base file name used for the daemon IPC socket.
@@ -304,13 +437,17 @@ enum SocketTarget {
     /// A filesystem socket addressed by path, used for explicit overrides and
     /// as the fallback on platforms without namespaced socket support.
     File(PathBuf),
+    /// A filesystem socket in the shared temp directory, the last fallback
+    /// when there is no per-user runtime directory either. Anyone can reach
+    /// it, so connections on it are encrypted.
+    Shared(PathBuf),
 }
 
 /// Resolve where an IPC socket should live.
 ///
 /// Precedence: an explicit per-side override wins, then the shared environment
 /// value, then a platform default (a namespaced name where supported, otherwise
-/// a file under the runtime/temp directory). `default_name` is the base socket
+/// a file under the runtime directory, or else the shared temp directory). `default_name` is the base socket
 /// file name used for both the namespaced name and the file fallback.
 fn socket_target(
     override_path: Option<&str>,
@@ -321,9 +458,10 @@ fn socket_target(
         SocketTarget::File(PathBuf::from(path))
     } else if GenericNamespaced::is_supported() {
         SocketTarget::Namespaced(default_name.to_string())
-    } else {
-        let dir = dirs2::runtime_dir().unwrap_or_else(std::env::temp_dir);
+    } else if let Some(dir) = dirs2::runtime_dir() {
         SocketTarget::File(dir.join(default_name))
+    } else {
+        SocketTarget::Shared(std::env::temp_dir().join(default_name))
     }
 }
 
@@ -331,7 +469,9 @@ fn socket_target(
 fn target_to_name<'a>(target: SocketTarget) -> Result<Name<'a>> {
     let name = match target {
         SocketTarget::Namespaced(name) => name.to_ns_name::<GenericNamespaced>()?,
-        SocketTarget::File(path) => path.to_fs_name::<GenericFilePath>()?,
+        SocketTarget::File(path) | SocketTarget::Shared(path) => {
+            path.to_fs_name::<GenericFilePath>()?
+        }
     };
     Ok(name)
 }
@@ -355,6 +495,20 @@ pub fn socket_name<'a>(override_path: Option<&str>) -> Result<Name<'a>> {
     ))
 }
 
+/// Whether the daemon socket [`socket_name`] resolves for `override_path` is
+/// a file in the shared temp directory, which the daemon and its clients then
+/// encrypt their connections on.
+///
+/// An explicit path is never taken for shared: its location was chosen.
+#[must_use]
+pub fn socket_is_shared(override_path: Option<&str>) -> bool {
+    let env_socket = std::env::var(SOCKET_ENV).ok();
+    matches!(
+        socket_target(override_path, env_socket.as_deref(), SOCKET_FILE_NAME),
+        SocketTarget::Shared(_)
+    )
+}
+
 /// Get the socket name used to talk to the `salus-agent`.
 ///
 /// Mirrors [`socket_name`] but resolves the agent-specific override, the shared
@@ -381,8 +535,8 @@ mod test {
     use anyhow::{Context, Result};
 
     use super::{
-        AGENT_SOCKET_FILE_NAME, SOCKET_FILE_NAME, SocketTarget, agent_socket_name, socket_name,
-        socket_target, target_to_name,
+        AGENT_SOCKET_FILE_NAME, SOCKET_FILE_NAME, SocketTarget, agent_socket_name,
+        socket_is_shared, socket_name, socket_target, target_to_name,
     };
 
     #[test]
@@ -411,7 +565,7 @@ mod test {
         // runtime/temp directory. Either way it must end with the base name.
         match socket_target(None, None, SOCKET_FILE_NAME) {
             SocketTarget::Namespaced(name) => assert_eq!(name, SOCKET_FILE_NAME),
-            SocketTarget::File(path) => {
+            SocketTarget::File(path) | SocketTarget::Shared(path) => {
                 let file_name = path.file_name().context("socket path has no file name")?;
                 assert_eq!(file_name, SOCKET_FILE_NAME);
             }
@@ -425,7 +579,7 @@ mod test {
         // daemon's, so the two sockets never collide.
         match socket_target(None, None, AGENT_SOCKET_FILE_NAME) {
             SocketTarget::Namespaced(name) => assert_eq!(name, AGENT_SOCKET_FILE_NAME),
-            SocketTarget::File(path) => {
+            SocketTarget::File(path) | SocketTarget::Shared(path) => {
                 let file_name = path.file_name().context("socket path has no file name")?;
                 assert_eq!(file_name, AGENT_SOCKET_FILE_NAME);
             }
@@ -444,4 +598,9 @@ mod test {
         assert!(socket_name(Some("/tmp/override.sock")).is_ok());
         assert!(agent_socket_name(Some("/tmp/agent-override.sock")).is_ok());
     }
+
+    #[test]
+    fn an_explicit_socket_is_never_shared() {
+        assert!(!socket_is_shared(Some("/tmp/override.sock")));
+    }
 }
@@ -0,0 +1,413 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Wire frames: the envelope every message on the daemon and agent sockets
+//! travels in.
+//!
+//! A frame is an envelope followed by a little-endian CRC-32 of it. The
+//! envelope carries the sender's [`PROTOCOL_VERSION`], the message's tag (the
+//! position of its variant in `Action`, `Response` and the agent enums, which
+//! is why variants are only ever appended), the request's correlation id and
+//! the encoded message itself. A peer that does not know the variant can still
+//! read the envelope, so it answers or reports an [`UnknownMessage`] rather
+//! than failing to parse the whole frame.
+//!
+//! Frames delimit themselves, so several can follow one another on a
+//! connection: [`frame_len`] finds where the first one ends. The daemon echoes
+//! a request's id in its response, which lets a client pipeline requests over
+//! one connection and match the responses as they complete, in any order.
+//!
+//! A request can also ask, through its [`FrameMeta`], for the daemon to time
+//! it; the response's envelope then carries a [`Timing`] beside the message.
+
+use std::{fmt, time::Duration};
+
+use anyhow::{Context as _, Result, bail};
+use bincode_next::{
+    Decode, Encode, config::standard, decode_from_slice, encode_to_vec, error::DecodeError,
+};
+use bon::Builder;
+use getset::CopyGetters;
+
+use super::{MAX_MESSAGE_SIZE, decode, encode};
+
+/// The version of the framed protocol this build speaks.
+///
+/// Sent in every envelope, so a peer can say which version it could not
+/// follow.
+pub const PROTOCOL_VERSION: u16 = 3;
+
+/// The length, in bytes, of the CRC-32 trailer closing a wire frame.
+const FRAME_CHECKSUM_LEN: usize = 4;
+
+/// The largest envelope: a message of [`MAX_MESSAGE_SIZE`] and the few bytes
+/// of version, tag, id, timing and length around it.
+const MAX_ENVELOPE_SIZE: usize = MAX_MESSAGE_SIZE + 96;
+
+/// A message, with what a peer needs to name it without decoding it.
+#[derive(Decode, Encode)]
+struct Envelope {
+    version: u16,
+    tag: u32,
+    id: u64,
+    verbose: bool,
+    timing: Option<Timing>,
+    message: Vec<u8>,
+}
+
+/// The start of an [`Envelope`], up to the length of its message.
+#[derive(Decode)]
+struct Header {
+    _version: u16,
+    _tag: u32,
+    _id: u64,
+    _verbose: bool,
+    _timing: Option<Timing>,
+    message_len: u64,
+}
+
+/// What a frame carries beside its message.
+#[derive(Builder, Clone, Copy, CopyGetters, Debug, Default, Eq, PartialEq)]
+#[getset(get_copy = "pub")]
+pub struct FrameMeta {
+    /// The id of the request, echoed in its response; 0 for a request alone on
+    /// its connection
+    #[builder(default)]
+    id: u64,
+    /// Whether a request asks the daemon to time it
+    #[builder(default)]
+    verbose: bool,
+    /// How the daemon spent its time on the request, in the response to a
+    /// verbose one
+    timing: Option<Timing>,
+}
+
+/// Where the daemon's time on a request went, sent in the response to a
+/// request whose [`FrameMeta`] is verbose.
+///
+/// Whatever a client measures beyond `total` was spent in the client and on
+/// the socket.
+#[derive(Builder, Clone, Copy, CopyGetters, Debug, Decode, Default, Encode, Eq, PartialEq)]
+#[getset(get_copy = "pub")]
+pub struct Timing {
+    /// From the request arriving to the daemon starting on it: waiting behind
+    /// other requests for a worker and for the store
+    #[builder(default)]
+    queued: Duration,
+    /// Reading and writing the database or object store
+    #[builder(default)]
+    storage: Duration,
+    /// Everything else the store did: sealing, opening and deriving keys
+    #[builder(default)]
+    crypto: Duration,
+    /// From the request arriving to its response being sent
+    #[builder(default)]
+    total: Duration,
+}
+
+/// A frame that checked out, carrying a message this side does not know;
+/// found on the error from [`decode_frame`] with
+/// `downcast_ref::<UnknownMessage>()`.
+///
+/// This is what a peer newer than this one sends when it uses a variant added
+/// since.
+#[derive(Clone, Copy, CopyGetters, Debug, Eq, PartialEq)]
+#[getset(get_copy = "pub")]
+pub struct UnknownMessage {
+    /// The protocol version the peer speaks
+    version: u16,
+    /// The message's tag, the position of its variant
+    tag: u32,
+    /// The id of the request the message belongs to
+    id: u64,
+}
+
+impl fmt::Display for UnknownMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown message {} from a peer speaking protocol version {}",
+            self.tag, self.version
+        )
+    }
+}
+
+impl std::error::Error for UnknownMessage {}
+
+/// Encode a protocol message as a wire frame: an envelope holding the
+/// [`encode`]d message, followed by a little-endian CRC-32 of it.
+///
+/// Everything written to the daemon or agent socket goes through this, so
+/// [`decode_frame`] can tell a truncated or corrupted request from a valid one
+/// before acting on it. The frame carries id 0, for a request that is alone on
+/// its connection; see [`encode_frame_with_id`] to pipeline several.
+///
+/// # Errors
+///
+/// Returns an error if the message cannot be [`encode`]d.
+pub fn encode_frame<E: Encode>(message: E) -> Result<Vec<u8>> {
+    encode_frame_with_id(0, message)
+}
+
+/// Encode a protocol message as a wire frame, like [`encode_frame`], carrying
+/// the correlation `id`.
+///
+/// The daemon answers a request with a response carrying the same id, so a
+/// client that writes several requests on one connection gives each its own.
+///
+/// # Errors
+///
+/// Returns an error if the message cannot be [`encode`]d.
+pub fn encode_frame_with_id<E: Encode>(id: u64, message: E) -> Result<Vec<u8>> {
+    encode_frame_with(FrameMeta::builder().id(id).build(), message)
+}
+
+/// Encode a protocol message as a wire frame, like [`encode_frame`], carrying
+/// `meta`.
+///
+/// # Errors
+///
+/// Returns an error if the message cannot be [`encode`]d.
+pub fn encode_frame_with<E: Encode>(meta: FrameMeta, message: E) -> Result<Vec<u8>> {
+    let message = encode(message)?;
+    // An enum is encoded as its variant's position, then its fields, so the
+    // tag is the number the message starts with.
+    let (tag, _len) = decode_from_slice::<u32, _>(&message, standard())?;
+    let envelope = Envelope {
+        version: PROTOCOL_VERSION,
+        tag,
+        id: meta.id,
+        verbose: meta.verbose,
+        timing: meta.timing,
+        message,
+    };
+    let mut frame = encode_to_vec(envelope, standard().with_limit::<MAX_ENVELOPE_SIZE>())?;
+    let checksum = crc32fast::hash(&frame);
+    frame.extend_from_slice(&checksum.to_le_bytes());
+    Ok(frame)
+}
+
+/// Decode a wire frame written by [`encode_frame`], checking its CRC-32 trailer
+/// before the envelope is decoded.
+///
+/// # Errors
+///
+/// Returns a "corrupt frame" error if the frame is too short to carry a
+/// checksum, the checksum does not match, or there is no envelope inside, and
+/// an [`UnknownMessage`] if the envelope holds a message that does not decode
+/// as a `D`.
+pub fn decode_frame<D: Decode<()>>(frame: &[u8]) -> Result<D> {
+    decode_frame_with_id(frame).map(|(_id, message)| message)
+}
+
+/// Decode a wire frame, like [`decode_frame`], with the correlation id it
+/// carries.
+///
+/// # Errors
+///
+/// Fails as [`decode_frame`] does.
+pub fn decode_frame_with_id<D: Decode<()>>(frame: &[u8]) -> Result<(u64, D)> {
+    decode_frame_with_meta(frame).map(|(meta, message)| (meta.id, message))
+}
+
+/// Decode a wire frame, like [`decode_frame`], with everything it carries
+/// beside its message.
+///
+/// # Errors
+///
+/// Fails as [`decode_frame`] does.
+pub fn decode_frame_with_meta<D: Decode<()>>(frame: &[u8]) -> Result<(FrameMeta, D)> {
+    let Some((envelope, checksum)) = frame.split_last_chunk::<FRAME_CHECKSUM_LEN>() else {
+        bail!(
+            "corrupt frame: {} bytes is too short to be a frame",
+            frame.len()
+        );
+    };
+    if crc32fast::hash(envelope) != u32::from_le_bytes(*checksum) {
+        bail!("corrupt frame: the checksum does not match");
+    }
+    let (envelope, _len) =
+        decode_from_slice::<Envelope, _>(envelope, standard().with_limit::<MAX_ENVELOPE_SIZE>())
+            .context("corrupt frame: there is no envelope inside")?;
+    let message = decode(&envelope.message).context(UnknownMessage {
+        version: envelope.version,
+        tag: envelope.tag,
+        id: envelope.id,
+    })?;
+    let meta = FrameMeta {
+        id: envelope.id,
+        verbose: envelope.verbose,
+        timing: envelope.timing,
+    };
+    Ok((meta, message))
+}
+
+/// The length of the frame `buf` starts with, or `None` when `buf` does not
+/// yet hold enough of it to tell.
+///
+/// Only the envelope's header is read, so the length may be past the end of
+/// `buf` while the rest of the frame is still to arrive, and the frame's
+/// checksum is left to [`decode_frame`] once it has.
+///
+/// # Errors
+///
+/// Returns a "corrupt frame" error if `buf` does not start with an envelope
+/// header.
+pub fn frame_len(buf: &[u8]) -> Result<Option<usize>> {
+    let decoded = decode_from_slice::<Header, _>(buf, standard());
+    // Running out of bytes means the header is still arriving; any other
+    // failure means it will never decode.
+    if let Err(DecodeError::UnexpectedEnd { .. }) = decoded {
+        return Ok(None);
+    }
+    let (Header { message_len, .. }, header_len) =
+        decoded.context("corrupt frame: there is no envelope header")?;
+    usize::try_from(message_len)
+        .ok()
+        .and_then(|message_len| header_len.checked_add(message_len))
+        .and_then(|len| len.checked_add(FRAME_CHECKSUM_LEN))
+        .map(Some)
+        .context("corrupt frame: the message length is out of range")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use anyhow::{Result, bail};
+    use bincode_next::{Decode, Encode};
+
+    use super::{
+        FrameMeta, PROTOCOL_VERSION, Timing, UnknownMessage, decode_frame, decode_frame_with_id,
+        decode_frame_with_meta, encode_frame, encode_frame_with, encode_frame_with_id, frame_len,
+    };
+    use crate::message::{Action, Response, UnlockTimeout};
+
+    /// `Action`, as a peer that predates every variant after `Lock` sees it.
+    #[derive(Debug, Decode, Encode)]
+    enum Older {
+        Unlock(u8),
+        Lock,
+    }
+
+    #[test]
+    fn frames_round_trip() -> Result<()> {
+        let frame = encode_frame(Action::Read("github".into()))?;
+        match decode_frame::<Action>(&frame)? {
+            Action::Read(key) => assert_eq!(key, "github"),
+            other => bail!("expected Action::Read, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_frames_are_refused() -> Result<()> {
+        let frame = encode_frame(Action::Read("github".into()))?;
+        let mut flipped = frame.clone();
+        if let Some(byte) = flipped.get_mut(1) {
+            *byte ^= 0x01;
+        }
+        let truncated = frame.split_last().map(|(_, rest)| rest).unwrap_or_default();
+        for corrupt in [flipped.as_slice(), truncated, &[]] {
+            match decode_frame::<Action>(corrupt) {
+                Ok(action) => bail!("expected a corrupt frame, got {action:?}"),
+                Err(e) => assert!(e.to_string().contains("corrupt frame")),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_variants_are_named_by_their_tag() -> Result<()> {
+        assert!(matches!(
+            decode_frame::<Older>(&encode_frame(Action::Lock)?)?,
+            Older::Lock
+        ));
+        let frame = encode_frame_with_id(7, Action::Read("github".into()))?;
+        let Err(e) = decode_frame::<Older>(&frame) else {
+            bail!("an older peer decoded a variant it predates");
+        };
+        assert_eq!(
+            e.downcast_ref::<UnknownMessage>(),
+            Some(&UnknownMessage {
+                version: PROTOCOL_VERSION,
+                tag: 5,
+                id: 7,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ids_round_trip() -> Result<()> {
+        let frame = encode_frame_with_id(u64::MAX, Action::Lock)?;
+        assert!(matches!(
+            decode_frame_with_id::<Action>(&frame)?,
+            (u64::MAX, Action::Lock)
+        ));
+        assert!(matches!(
+            decode_frame_with_id::<Action>(&encode_frame(Action::Lock)?)?,
+            (0, Action::Lock)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn timings_ride_in_the_envelope() -> Result<()> {
+        let timing = Timing::builder()
+            .queued(Duration::from_micros(1))
+            .storage(Duration::from_micros(20))
+            .crypto(Duration::from_micros(300))
+            .total(Duration::from_millis(4))
+            .build();
+        let meta = FrameMeta::builder().id(9).timing(timing).build();
+        let frame = encode_frame_with(meta, Response::Pong)?;
+        let (decoded, response) = decode_frame_with_meta::<Response>(&frame)?;
+        assert!(matches!(response, Response::Pong));
+        assert_eq!(decoded, meta);
+        assert_eq!(frame_len(&frame)?, Some(frame.len()));
+        let request = FrameMeta::builder().verbose(true).build();
+        let (decoded, _action) =
+            decode_frame_with_meta::<Action>(&encode_frame_with(request, Action::Lock)?)?;
+        assert!(decoded.verbose());
+        assert!(decoded.timing().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn frames_delimit_themselves() -> Result<()> {
+        let first = encode_frame_with_id(1, Action::Read("github".into()))?;
+        let second = encode_frame_with_id(2, Action::Lock)?;
+        let stream = [first.as_slice(), second.as_slice()].concat();
+        assert_eq!(frame_len(&stream)?, Some(first.len()));
+        let (head, tail) = stream.split_at(first.len());
+        assert!(matches!(
+            decode_frame_with_id::<Action>(head)?,
+            (1, Action::Read(_))
+        ));
+        assert_eq!(frame_len(tail)?, Some(second.len()));
+        assert_eq!(frame_len(&[])?, None);
+        assert_eq!(frame_len(first.get(..2).unwrap_or_default())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn tags_are_variant_positions() -> Result<()> {
+        // A variant's position is its tag on the wire: these must never move.
+        for (action, tag) in [
+            (Action::Unlock(UnlockTimeout::Default), 0),
+            (Action::Lock, 1),
+        ] {
+            let frame = encode_frame(action)?;
+            assert_eq!(frame.get(1), Some(&tag));
+        }
+        let frame = encode_frame(Response::Success)?;
+        assert_eq!(frame.get(1), Some(&1));
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A secret value handed to an application by the client API.
+//!
+//! [`Secret`] zeroes what it holds when dropped, prints as `[REDACTED]` through
+//! both `Debug` and `Display`, and gives up its value only through
+//! [`Secret::expose`], so plaintext does not reach a log line by accident and
+//! no copy is left behind that the caller did not make on purpose.
+
+use std::{fmt, str::Utf8Error};
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// What a [`Secret`] prints instead of its value.
+const REDACTED: &str = "[REDACTED]";
+
+/// A value that is zeroed when dropped and never printed.
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    /// Hold `value` as a secret.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// The value itself. Anything made from it is the caller's to zero.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl Secret<Vec<u8>> {
+    /// The value as text, when it is UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not UTF-8.
+    pub fn expose_str(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(&self.0)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> ZeroizeOnDrop for Secret<T> {}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Secret")
+            .field(&format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::Secret;
+
+    #[test]
+    fn a_secret_is_redacted_unless_exposed() -> Result<()> {
+        let secret = Secret::new(b"s3cret".to_vec());
+        assert_eq!(format!("{secret}"), "[REDACTED]");
+        assert_eq!(format!("{secret:?}"), "Secret([REDACTED])");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some(Secret([REDACTED]))");
+        assert_eq!(secret.expose().as_slice(), b"s3cret");
+        assert_eq!(secret.expose_str()?, "s3cret");
+        assert!(Secret::new(vec![0xff]).expose_str().is_err());
+        assert_eq!(Secret::from(String::from("pw")).expose(), "pw");
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The checksummed share envelope.
+//!
+//! An envelope reads `salus1-<index>-<group>-...-<group>-<crc>`:
+//!
+//! * `salus` and the format version, so a future layout is recognised rather
+//!   than misread;
+//! * the share index in decimal;
+//! * the share bytes in Crockford base32, four characters per group, each
+//!   group followed by its own check character;
+//! * a CRC-32 over the version, index, and bytes.
+//!
+//! The per-group check characters let a typo be reported by position
+//! ("group 11 of 13, near the end"); the CRC catches what they cannot, such
+//! as a dropped or repeated group.
+
+use anyhow::{Result, bail};
+use zeroize::Zeroizing;
+
+use super::{crc32, decode_ssss, encode_ssss, from_bits, to_bits};
+
+/// The text every envelope starts with, ahead of the version.
+pub(super) const PREFIX: &str = "salus";
+/// The envelope layout written by this version.
+const VERSION: u8 = 1;
+/// Crockford base32 digits, lowest value first.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Data characters per group, ahead of the group's check character.
+const GROUP_LEN: usize = 4;
+
+/// Wrap an `ssss` share in an envelope.
+pub(super) fn wrap(share: &str) -> Result<String> {
+    let (index, data) = decode_ssss(share)?;
+    let encoded = Zeroizing::new(base32_encode(&data));
+    let mut out = format!("{PREFIX}{VERSION}-{index}");
+    for (group, chunk) in encoded.as_bytes().chunks(GROUP_LEN).enumerate() {
+        out.push('-');
+        out.extend(chunk.iter().map(|c| char::from(*c)));
+        out.push(check_char(group, chunk));
+    }
+    out.push('-');
+    out.push_str(&crc_text(index, &data));
+    Ok(out)
+}
+
+/// Open an envelope, returning the `ssss` share inside.
+pub(super) fn unwrap(envelope: &str) -> Result<String> {
+    let compact: Zeroizing<String> = Zeroizing::new(
+        envelope
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    );
+    let parts: Vec<&str> = compact.split('-').collect();
+    let Some((&header, rest)) = parts.split_first() else {
+        bail!("the share is empty");
+    };
+    let Some(version) = header
+        .strip_prefix(&PREFIX.to_ascii_uppercase())
+        .and_then(|version| version.parse::<u8>().ok())
+    else {
+        bail!("the share does not start with '{PREFIX}{VERSION}-'");
+    };
+    if version != VERSION {
+        bail!("the share uses format version {version}; this salusc reads version {VERSION}");
+    }
+    let (Some((&index, groups)), Some(&crc)) = (rest.split_first(), rest.last()) else {
+        bail!("the share is missing its index or checksum");
+    };
+    let Ok(index) = index.parse::<u8>() else {
+        bail!("the share index '{index}' is not a number from 1 to 255");
+    };
+    let groups = groups
+        .get(..groups.len().saturating_sub(1))
+        .unwrap_or_default();
+    if groups.is_empty() {
+        bail!("the share has no data");
+    }
+
+    let total = groups.len();
+    let mut encoded = Zeroizing::new(String::new());
+    for (group, text) in groups.iter().enumerate() {
+        let digits: Vec<u8> = text.bytes().map(normalize_digit).collect();
+        let Some((&check, chunk)) = digits.split_last() else {
+            bail!("{}", typo_at(group, total));
+        };
+        let last = group.saturating_add(1) == total;
+        let complete = chunk.len() == GROUP_LEN || (last && (1..GROUP_LEN).contains(&chunk.len()));
+        if !complete
+            || !chunk.iter().all(|c| CROCKFORD.contains(c))
+            || check_char(group, chunk) != char::from(check)
+        {
+            bail!("{}", typo_at(group, total));
+        }
+        encoded.extend(chunk.iter().map(|c| char::from(*c)));
+    }
+    let Some(data) = base32_decode(&encoded) else {
+        bail!("the share data is malformed");
+    };
+    let crc: String = crc
+        .bytes()
+        .map(|c| char::from(normalize_digit(c)))
+        .collect();
+    if index == 0 || crc != crc_text(index, &data) {
+        bail!(
+            "the share checksum does not match; a group may be missing, repeated, or out of order"
+        );
+    }
+    Ok(encode_ssss(index, &data))
+}
+
+/// Describe a typo in group `group` (zero-based) of `total`.
+fn typo_at(group: usize, total: usize) -> String {
+    let number = group.saturating_add(1);
+    let third = total.div_ceil(3);
+    let place = if number <= third {
+        "near the start"
+    } else if number > total.saturating_sub(third) {
+        "near the end"
+    } else {
+        "in the middle"
+    };
+    format!("typo in group {number} of {total} ({place})")
+}
+
+/// Map the characters Crockford base32 treats as look-alikes onto their digit.
+fn normalize_digit(c: u8) -> u8 {
+    match c {
+        b'O' => b'0',
+        b'I' | b'L' => b'1',
+        other => other,
+    }
+}
+
+/// The check character for a group: a weighted sum of its digits and its
+/// position, modulo 32.
+///
+/// The odd weights mean any single mistyped character changes the sum, and
+/// mixing in the position catches a group pasted in the wrong place.
+fn check_char(group: usize, chunk: &[u8]) -> char {
+    let sum =
+        chunk
+            .iter()
+            .zip((1usize..).step_by(2))
+            .fold(group.wrapping_mul(5), |sum, (c, weight)| {
+                let value = CROCKFORD.iter().position(|d| d == c).unwrap_or(0);
+                sum.wrapping_add(value.wrapping_mul(weight))
+            });
+    CROCKFORD
+        .get(sum % 32)
+        .map_or('0', |digit| char::from(*digit))
+}
+
+fn crc_text(index: u8, data: &[u8]) -> String {
+    let mut covered = Zeroizing::new(vec![VERSION, index]);
+    covered.extend_from_slice(data);
+    // Always seven characters: 32 bits plus three bits of padding.
+    base32_encode(&crc32(&covered).to_be_bytes())
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut bits = Zeroizing::new(to_bits(data));
+    while !bits.len().is_multiple_of(5) {
+        bits.push(false);
+    }
+    bits.chunks(5)
+        .filter_map(|chunk| {
+            let value = chunk.iter().fold(0usize, |value, &bit| {
+                value.wrapping_shl(1) | usize::from(bit)
+            });
+            CROCKFORD.get(value).map(|digit| char::from(*digit))
+        })
+        .collect()
+}
+
+fn base32_decode(text: &str) -> Option<Zeroizing<Vec<u8>>> {
+    let mut bits = Zeroizing::new(Vec::with_capacity(text.len().saturating_mul(5)));
+    for c in text.bytes() {
+        let value = CROCKFORD.iter().position(|digit| *digit == c)?;
+        for shift in (0..5).rev() {
+            bits.push(value.wrapping_shr(shift) & 1 == 1);
+        }
+    }
+    let bytes = Zeroizing::new(from_bits(&bits));
+    // Only the zero padding of the final character may be left over.
+    let padding = bits.len().saturating_sub(bytes.len().saturating_mul(8));
+    let tail = bits
+        .get(bytes.len().saturating_mul(8)..)
+        .unwrap_or_default();
+    (padding < 5 && tail.iter().all(|bit| !bit)).then_some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::{super::encode_ssss, unwrap, wrap};
+
+    #[test]
+    fn envelope_round_trips() -> Result<()> {
+        let share = encode_ssss(3, &[0xA5; 32]);
+        let envelope = wrap(&share)?;
+        assert!(envelope.starts_with("salus1-3-"));
+        // 52 data characters in 13 groups, plus the header, index, and CRC.
+        assert_eq!(envelope.split('-').count(), 16);
+        assert_eq!(unwrap(&envelope)?, share);
+        // Case, spacing, and look-alike letters are forgiven.
+        let relaxed = envelope
+            .to_lowercase()
+            .replace('-', " - ")
+            .replace('0', "o");
+        assert_eq!(unwrap(&relaxed)?, share);
+        Ok(())
+    }
+
+    #[test]
+    fn typos_are_located() -> Result<()> {
+        let envelope = wrap(&encode_ssss(2, &[0x3C; 32]))?;
+        let mut groups: Vec<String> = envelope.split('-').map(str::to_string).collect();
+        // Group 12 of 13 sits just before the last data group and the CRC.
+        let Some(group) = groups.get_mut(13) else {
+            bail!("too few groups");
+        };
+        let replacement = if group.starts_with('X') { "Y" } else { "X" };
+        group.replace_range(..1, replacement);
+        let Err(e) = unwrap(&groups.join("-")) else {
+            bail!("a typo was accepted");
+        };
+        assert_eq!(e.to_string(), "typo in group 12 of 13 (near the end)");
+
+        let mut swapped = envelope.split('-').collect::<Vec<_>>();
+        swapped.swap(3, 4);
+        assert!(unwrap(&swapped.join("-")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn other_versions_are_named() -> Result<()> {
+        let envelope = wrap(&encode_ssss(1, &[1; 32]))?;
+        let Err(e) = unwrap(&envelope.replacen("salus1", "salus2", 1)) else {
+            bail!("a future version was accepted");
+        };
+        assert!(e.to_string().contains("version 2"));
+        assert!(unwrap("salus1-1").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Human-friendly share representations.
+//!
+//! The daemon always works with the `ssss` string form of a share
+//! (`<index>:<share>`, both halves base62). People get one of two checked
+//! forms instead: the envelope (see [`wrap_share`]), whose per-group check
+//! characters locate a typo, or a BIP39-style mnemonic: whitespace-separated words from the first 2048 entries
+//! of the passphrase word list, eleven bits per word. The words carry the share
+//! index, the share length, and a final checksum word, so a mistyped or
+//! misheard word is caught when the share is entered rather than when the
+//! unlock fails.
+
+use anyhow::{Result, bail};
+use zeroize::Zeroizing;
+
+use crate::generate::passphrase_words;
+
+mod envelope;
+
+/// The number of words a mnemonic draws from (2^11).
+const MNEMONIC_WORDS: usize = 2048;
+/// The bits carried by each mnemonic word.
+const WORD_BITS: usize = 11;
+/// The base62 digits used by `ssss`, lowest value first.
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// The length of the nonce `ssss` prefixes to each base62 half.
+const NONCE_LEN: usize = 10;
+
+/// Convert an `ssss` share string into its mnemonic words.
+///
+/// # Errors
+///
+/// Returns an error if `share` is not a valid `ssss` share.
+pub fn share_to_mnemonic(share: &str) -> Result<String> {
+    let (index, data) = decode_ssss(share)?;
+    let Ok(len) = u8::try_from(data.len()) else {
+        bail!("the share is too long to write as a mnemonic");
+    };
+    let mut payload = Zeroizing::new(Vec::with_capacity(data.len().saturating_add(2)));
+    payload.push(index);
+    payload.push(len);
+    payload.extend_from_slice(&data);
+
+    let mut bits = Zeroizing::new(to_bits(&payload));
+    while !bits.len().is_multiple_of(WORD_BITS) {
+        bits.push(false);
+    }
+    let list = mnemonic_words();
+    let mut words: Vec<&str> = bits
+        .chunks(WORD_BITS)
+        .filter_map(|chunk| list.get(word_index(chunk)).copied())
+        .collect();
+    if let Some(check) = list.get(checksum(&payload)) {
+        words.push(check);
+    }
+    Ok(words.join(" "))
+}
+
+/// Convert mnemonic words back into the `ssss` share string.
+///
+/// Words are matched case-insensitively and may be separated by any
+/// whitespace.
+///
+/// # Errors
+///
+/// Returns an error naming the first unknown word, or if the words do not
+/// form a share or fail the checksum.
+pub fn mnemonic_to_share(mnemonic: &str) -> Result<String> {
+    let list = mnemonic_words();
+    let mut indices = Zeroizing::new(vec![]);
+    for (position, word) in mnemonic.split_whitespace().enumerate() {
+        let word = word.to_lowercase();
+        let Some(idx) = list.iter().position(|candidate| *candidate == word) else {
+            bail!(
+                "word {} ('{word}') is not a share word",
+                position.saturating_add(1)
+            );
+        };
+        indices.push(idx);
+    }
+    let Some((&check, data_words)) = indices.split_last() else {
+        bail!("the share mnemonic is empty");
+    };
+
+    let mut bits = Zeroizing::new(Vec::with_capacity(
+        data_words.len().saturating_mul(WORD_BITS),
+    ));
+    for idx in data_words {
+        for shift in (0..WORD_BITS).rev() {
+            bits.push(idx.checked_shr(u32::try_from(shift)?).unwrap_or(0) & 1 == 1);
+        }
+    }
+    let bytes = Zeroizing::new(from_bits(&bits));
+    let (Some(&index), Some(&len)) = (bytes.first(), bytes.get(1)) else {
+        bail!("the share mnemonic is too short");
+    };
+    let end = usize::from(len).saturating_add(2);
+    let (Some(payload), Some(rest)) = (bytes.get(..end), bytes.get(end..)) else {
+        bail!("the share mnemonic is too short");
+    };
+    // Padding must be zero and shorter than a word.
+    let padding = bits.len().saturating_sub(end.saturating_mul(8));
+    if padding >= WORD_BITS || rest.iter().any(|byte| *byte != 0) {
+        bail!("the share mnemonic has extra words");
+    }
+    if checksum(payload) != check {
+        bail!("the share mnemonic checksum does not match; check the words for typos");
+    }
+    if index == 0 {
+        bail!("the share mnemonic has an invalid share index");
+    }
+    Ok(encode_ssss(index, payload.get(2..).unwrap_or_default()))
+}
+
+/// Wrap an `ssss` share in the versioned, checksummed envelope,
+/// `salus1-<index>-<group>-...-<crc>`.
+///
+/// # Errors
+///
+/// Returns an error if `share` is not a valid `ssss` share.
+pub fn wrap_share(share: &str) -> Result<String> {
+    envelope::wrap(share)
+}
+
+/// Open a share envelope, returning the `ssss` share inside.
+///
+/// Case, whitespace, and the look-alike letters `O`, `I`, and `L` are
+/// forgiven.
+///
+/// # Errors
+///
+/// Returns an error that locates the group holding a typo, names an unknown
+/// format version, or reports a checksum mismatch.
+pub fn unwrap_share(envelope: &str) -> Result<String> {
+    envelope::unwrap(envelope)
+}
+
+/// Accept a share in any form, returning the `ssss` string the daemon
+/// expects.
+///
+/// Input starting with `salus` is opened as an envelope and input containing
+/// a `:` is taken to be a bare `ssss` share (as printed before envelopes
+/// existed); anything else is decoded as a mnemonic.
+///
+/// # Errors
+///
+/// Returns an error if the input is none of these forms.
+pub fn normalize_share(input: &str) -> Result<String> {
+    let input = input.trim();
+    let is_envelope = input
+        .get(..envelope::PREFIX.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(envelope::PREFIX));
+    if is_envelope {
+        unwrap_share(input)
+    } else if input.contains(':') {
+        Ok(input.to_string())
+    } else {
+        mnemonic_to_share(input)
+    }
+}
+
+/// Split a share, in any form [`normalize_share`] accepts, into its index and
+/// share bytes.
+///
+/// The bytes are what identify a share: the `ssss` string form carries a
+/// random nonce, so two strings for the same share need not be equal.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a valid share.
+pub fn share_parts(share: &str) -> Result<(u8, Zeroizing<Vec<u8>>)> {
+    decode_ssss(&normalize_share(share)?)
+}
+
+fn mnemonic_words() -> Vec<&'static str> {
+    passphrase_words().take(MNEMONIC_WORDS).collect()
+}
+
+/// The top eleven bits of the CRC-32 of `payload`.
+fn checksum(payload: &[u8]) -> usize {
+    let top = crc32(payload).checked_shr(21).unwrap_or(0);
+    usize::try_from(top).unwrap_or(0)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc.wrapping_shr(1) ^ 0xEDB8_8320
+            } else {
+                crc.wrapping_shr(1)
+            };
+        }
+    }
+    !crc
+}
+
+fn to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            (0..8)
+                .rev()
+                .map(move |shift| byte.wrapping_shr(shift) & 1 == 1)
+        })
+        .collect()
+}
+
+/// Pack `bits` into bytes, dropping a trailing partial byte.
+fn from_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks_exact(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold(0u8, |byte, &bit| byte.wrapping_shl(1) | u8::from(bit))
+        })
+        .collect()
+}
+
+fn word_index(bits: &[bool]) -> usize {
+    bits.iter()
+        .fold(0usize, |idx, &bit| idx.wrapping_shl(1) | usize::from(bit))
+}
+
+/// Split an `ssss` share into its index and share bytes.
+fn decode_ssss(share: &str) -> Result<(u8, Zeroizing<Vec<u8>>)> {
+    let Some((index, data)) = share.trim().split_once(':') else {
+        bail!("a share has the form '<index>:<share>'");
+    };
+    let index = base62_decode(index)?;
+    let data = base62_decode(data)?;
+    match (index.first(), index.len()) {
+        (Some(&index), 1) if index != 0 && !data.is_empty() => Ok((index, data)),
+        _ => bail!("the share is malformed"),
+    }
+}
+
+/// The `ssss` string for share `index` with the given bytes.
+fn encode_ssss(index: u8, data: &[u8]) -> String {
+    format!("{}:{}", base62_encode(&[index]), base62_encode(data))
+}
+
+/// Decode one base62 half of an `ssss` share, dropping its nonce.
+///
+/// `ssss` writes the least significant digit first.
+fn base62_decode(input: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let mut value = Zeroizing::new(vec![]);
+    for c in input.chars().rev() {
+        let Some(digit) = BASE62.iter().position(|d| char::from(*d) == c) else {
+            bail!("'{c}' is not a valid share character");
+        };
+        let mut carry = u32::try_from(digit)?;
+        for byte in value.iter_mut().rev() {
+            let acc = u32::from(*byte).saturating_mul(62).saturating_add(carry);
+            *byte = u8::try_from(acc & 0xFF)?;
+            carry = acc.wrapping_shr(8);
+        }
+        while carry > 0 {
+            value.insert(0, u8::try_from(carry & 0xFF)?);
+            carry = carry.wrapping_shr(8);
+        }
+    }
+    match value.get(NONCE_LEN..) {
+        Some(data) => Ok(Zeroizing::new(data.to_vec())),
+        None => bail!("the share is malformed"),
+    }
+}
+
+/// Encode `data` as one base62 half of an `ssss` share.
+///
+/// `ssss` only requires a non-zero leading nonce byte, so a fixed nonce is
+/// used: the result decodes to the same share as the original string.
+fn base62_encode(data: &[u8]) -> String {
+    let mut value = Zeroizing::new(vec![0u8; NONCE_LEN]);
+    if let Some(first) = value.first_mut() {
+        *first = 1;
+    }
+    value.extend_from_slice(data);
+
+    let mut out = String::new();
+    while value.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in value.iter_mut() {
+            let acc = remainder.wrapping_shl(8) | u32::from(*byte);
+            *byte = u8::try_from(acc / 62).unwrap_or(0);
+            remainder = acc % 62;
+        }
+        let digit = usize::try_from(remainder).unwrap_or(0);
+        if let Some(d) = BASE62.get(digit) {
+            out.push(char::from(*d));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use ssss::{SsssConfig, gen_shares, unlock};
+
+    use super::{
+        decode_ssss, encode_ssss, mnemonic_to_share, normalize_share, share_parts,
+        share_to_mnemonic, wrap_share,
+    };
+
+    fn shares() -> Result<(Vec<String>, [u8; 32])> {
+        let mut key = [0u8; 32];
+        rand::fill(&mut key);
+        Ok((gen_shares(&SsssConfig::default(), &key)?, key))
+    }
+
+    #[test]
+    fn mnemonic_round_trips_to_an_equivalent_share() -> Result<()> {
+        let (shares, key) = shares()?;
+        let mut converted = vec![];
+        for share in &shares {
+            let mnemonic = share_to_mnemonic(share)?;
+            // Index and length byte plus 32 share bytes fill 25 words, plus
+            // the checksum word.
+            assert_eq!(mnemonic.split_whitespace().count(), 26);
+            let back = mnemonic_to_share(&mnemonic.to_uppercase())?;
+            let (Ok(original), Ok(decoded)) = (decode_ssss(share), decode_ssss(&back)) else {
+                bail!("share did not decode");
+            };
+            assert_eq!(original, decoded);
+            converted.push(back);
+        }
+        assert_eq!(unlock(&converted)?, key);
+        Ok(())
+    }
+
+    #[test]
+    fn typos_and_unknown_words_are_rejected() -> Result<()> {
+        let mnemonic = share_to_mnemonic(&encode_ssss(2, &[0x5A; 32]))?;
+        let mut words: Vec<&str> = mnemonic.split_whitespace().collect();
+        let Some(third) = words.get_mut(2) else {
+            bail!("too few words");
+        };
+        *third = if *third == "abacus" {
+            "abdomen"
+        } else {
+            "abacus"
+        };
+        assert!(mnemonic_to_share(&words.join(" ")).is_err());
+        let Some(first) = words.first_mut() else {
+            bail!("too few words");
+        };
+        *first = "salus";
+        let Err(e) = mnemonic_to_share(&words.join(" ")) else {
+            bail!("an unknown word was accepted");
+        };
+        assert!(e.to_string().contains("word 1 ('salus')"));
+        assert!(mnemonic_to_share("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn normalize_detects_the_format() -> Result<()> {
+        let (shares, _) = shares()?;
+        let Some(share) = shares.first() else {
+            bail!("no shares");
+        };
+        assert_eq!(normalize_share(&format!("  {share}\n"))?, *share);
+        let opened = normalize_share(&wrap_share(share)?)?;
+        let (Ok(original), Ok(decoded)) = (decode_ssss(share), decode_ssss(&opened)) else {
+            bail!("share did not decode");
+        };
+        assert_eq!(original, decoded);
+        let from_words = normalize_share(&share_to_mnemonic(share)?)?;
+        let (Ok(original), Ok(decoded)) = (decode_ssss(share), decode_ssss(&from_words)) else {
+            bail!("share did not decode");
+        };
+        assert_eq!(original, decoded);
+        assert!(normalize_share("not a share").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn share_parts_agree_across_forms() -> Result<()> {
+        let share = encode_ssss(4, &[0x17; 32]);
+        let (index, data) = share_parts(&share)?;
+        assert_eq!(index, 4);
+        assert_eq!(*data, [0x17; 32]);
+        assert_eq!(share_parts(&wrap_share(&share)?)?, (index, data.clone()));
+        assert_eq!(share_parts(&share_to_mnemonic(&share)?)?, (index, data));
+        assert!(share_parts("4:").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! An in-memory stand-in for `salusd`, built with the `testing` feature, so an
+//! application that codes against [`ClientApi`] can be unit tested without a
+//! daemon.
+//!
+//! [`MockClient`] keeps values in a map and answers reads, stores, deletes,
+//! prefix reads, status and lock requests as the daemon would, including
+//! refusing them while locked or read-only. Any request can instead be made to
+//! fail with [`MockClient::fail_next`], and every request is recorded for
+//! [`MockClient::calls`] to return.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::Result;
+use zeroize::Zeroizing;
+
+use crate::{
+    client::ClientApi,
+    message::{Action, Response, StoreStatus},
+};
+
+/// What the daemon says to a request while the store is locked.
+const NOT_UNLOCKED: &str = "Store not unlocked";
+
+/// A failure for [`MockClient::fail_next`] to inject.
+#[derive(Clone, Debug)]
+pub enum Failure {
+    /// Answer with this response instead, as a daemon refusing the request
+    /// would
+    Respond(Response),
+    /// Fail as if no daemon were listening, with a connection-refused
+    /// [`io::Error`]
+    Unreachable,
+}
+
+/// A [`ClientApi`] over an in-memory map, for tests.
+///
+/// It starts unlocked, writable and empty. Interior mutability lets it be
+/// shared by reference, as a real client is.
+#[derive(Debug, Default)]
+pub struct MockClient {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    values: BTreeMap<String, Zeroizing<Vec<u8>>>,
+    failures: VecDeque<Failure>,
+    calls: Vec<Action>,
+    locked: bool,
+    read_only: bool,
+}
+
+impl MockClient {
+    /// An empty, unlocked, writable mock.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// This mock, holding `value` under `key`.
+    #[must_use]
+    pub fn with_value(self, key: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Hold `value` under `key`, without recording a call.
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<Vec<u8>>) {
+        let _old = self
+            .state()
+            .values
+            .insert(key.into(), Zeroizing::new(value.into()));
+    }
+
+    /// The value held under `key`.
+    #[must_use]
+    pub fn value(&self, key: &str) -> Option<Vec<u8>> {
+        self.state().values.get(key).map(|value| value.to_vec())
+    }
+
+    /// Lock or unlock the store: while locked, every request for a value is
+    /// refused with the daemon's "Store not unlocked".
+    pub fn set_locked(&self, locked: bool) {
+        self.state().locked = locked;
+    }
+
+    /// Make the store read-only, refusing stores and deletes with
+    /// [`Response::ReadOnly`], or writable again.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.state().read_only = read_only;
+    }
+
+    /// Fail the next request not yet given a failure with `failure`.
+    pub fn fail_next(&self, failure: Failure) {
+        self.state().failures.push_back(failure);
+    }
+
+    /// Every request sent so far, oldest first.
+    #[must_use]
+    pub fn calls(&self) -> Vec<Action> {
+        self.state().calls.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ClientApi for MockClient {
+    fn send(&self, action: Action) -> Result<Response> {
+        let mut state = self.state();
+        state.calls.push(action.clone());
+        match state.failures.pop_front() {
+            Some(Failure::Respond(response)) => Ok(response),
+            Some(Failure::Unreachable) => {
+                Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+            }
+            None => Ok(state.answer(action)),
+        }
+    }
+}
+
+impl State {
+    fn answer(&mut self, action: Action) -> Response {
+        match action {
+            Action::Status => Response::Status(self.status()),
+            Action::Ping => Response::Pong,
+            Action::Lock => {
+                self.locked = true;
+                Response::Success
+            }
+            Action::SetReadOnly(read_only) => {
+                self.read_only = read_only;
+                Response::Success
+            }
+            Action::Store(_) | Action::Delete(_) if self.read_only => Response::ReadOnly,
+            Action::Read(_) | Action::ReadPrefix(_) | Action::Store(_) | Action::Delete(_)
+                if self.locked =>
+            {
+                Response::Error(NOT_UNLOCKED.to_string())
+            }
+            Action::Read(key) => Response::Value(self.values.get(&key).map(|value| value.to_vec())),
+            Action::ReadPrefix(prefix) => Response::Values(
+                self.values
+                    .range(prefix.clone()..)
+                    .take_while(|(key, _)| key.starts_with(&prefix))
+                    .map(|(key, value)| (key.clone(), value.to_vec()))
+                    .collect(),
+            ),
+            Action::Store(store) => {
+                let (key, value, force) = store.into_parts();
+                let value = Zeroizing::new(value);
+                if !force && self.values.contains_key(&key) {
+                    Response::KeyExists
+                } else {
+                    let _old = self
+                        .values
+                        .insert(key, Zeroizing::new(value.as_bytes().to_vec()));
+                    Response::Success
+                }
+            }
+            Action::Delete(key) => match self.values.remove(&key) {
+                Some(_) => Response::Success,
+                None => Response::KeyNotFound,
+            },
+            _ => Response::Error("MockClient does not handle this request".to_string()),
+        }
+    }
+
+    fn status(&self) -> StoreStatus {
+        StoreStatus::builder()
+            .initialized(true)
+            .sealed(self.locked)
+            .threshold(3)
+            .num_shares(5)
+            .shares_collected(0)
+            .daemon_version("mock")
+            .read_only(self.read_only)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use anyhow::{Context as _, Result, bail};
+
+    use super::{Failure, MockClient};
+    use crate::{
+        client::{ClientApi as _, Refusal},
+        message::{Action, Response},
+    };
+
+    #[test]
+    fn values_are_read_stored_and_deleted_in_memory() -> Result<()> {
+        let client = MockClient::new().with_value("db/password", "s3cret");
+        assert_eq!(
+            client
+                .read("db/password")?
+                .as_ref()
+                .map(|secret| secret.expose().as_slice()),
+            Some(&b"s3cret"[..])
+        );
+        assert!(client.read("missing")?.is_none());
+        assert!(!client.store("db/password", "other", false)?);
+        assert!(client.store("db/password", "n3w", true)?);
+        assert!(client.store("db/user", "app", false)?);
+        assert_eq!(client.value("db/password").as_deref(), Some(&b"n3w"[..]));
+        let Response::Values(values) = client.send(Action::ReadPrefix("db/".to_string()))? else {
+            bail!("expected values");
+        };
+        assert_eq!(values.len(), 2);
+        assert!(client.delete("db/user")?);
+        assert!(!client.delete("db/user")?);
+        assert_eq!(client.calls().len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn locked_read_only_and_injected_failures_are_refused() -> Result<()> {
+        let client = MockClient::new().with_value("k", "v");
+        client.set_read_only(true);
+        let refusal = client
+            .store("k", "x", true)
+            .err()
+            .context("stored while read-only")?;
+        assert_eq!(refusal.downcast_ref::<Refusal>(), Some(&Refusal::ReadOnly));
+        assert_eq!(
+            client
+                .read("k")?
+                .as_ref()
+                .map(|secret| secret.expose().as_slice()),
+            Some(&b"v"[..])
+        );
+
+        let _locked = client.send(Action::Lock)?;
+        let refusal = client.read("k").err().context("read while locked")?;
+        assert_eq!(refusal.to_string(), "Store not unlocked");
+        client.set_locked(false);
+
+        client.fail_next(Failure::Unreachable);
+        client.fail_next(Failure::Respond(Response::Error("boom".to_string())));
+        let unreachable = client.read("k").err().context("read while unreachable")?;
+        assert_eq!(
+            unreachable.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::ConnectionRefused)
+        );
+        let refusal = client
+            .read("k")
+            .err()
+            .context("read with an injected error")?;
+        assert_eq!(
+            refusal.downcast_ref::<Refusal>(),
+            Some(&Refusal::Error("boom".to_string()))
+        );
+        assert!(client.read("k")?.is_some());
+        Ok(())
+    }
+}
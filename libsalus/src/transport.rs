@@ -0,0 +1,478 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Encryption for a daemon socket that fell back to the shared temp directory.
+//!
+//! Without a namespaced socket or a per-user runtime directory, the daemon's
+//! socket is a file in the temp directory, where anyone who can open it can
+//! connect. Every connection on such a socket opens with a Noise `NNpsk0`
+//! handshake keyed by the daemon's transport key, a file only the daemon's
+//! user can read, so a peer without the key is refused before it sends a
+//! request. The frames then travel in Noise transport messages, each a
+//! big-endian `u16` length and the ciphertext, under keys fresh for the
+//! connection.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use anyhow::{Context as _, Result, anyhow};
+use snow::{Builder, StatelessTransportState, params::NoiseParams};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, ReadBuf};
+use zeroize::Zeroizing;
+
+use crate::socket_is_shared;
+
+/// The bytes in a transport key.
+pub const TRANSPORT_KEY_LEN: usize = 32;
+
+/// The environment variable, shared by the daemon and the client, that
+/// overrides where the transport key is kept.
+const TRANSPORT_KEY_ENV: &str = "SALUS_TRANSPORT_KEY";
+
+/// The transport key's file name, in the daemon's config directory.
+const TRANSPORT_KEY_FILE_NAME: &str = "transport.key";
+
+/// The daemon's name, which is its config directory's too.
+const DAEMON_NAME: &str = "salusd";
+
+/// The Noise protocol an encrypted connection speaks.
+const NOISE_PATTERN: &str = "Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s";
+
+/// The longest Noise message.
+const MAX_NOISE_MESSAGE: usize = 65535;
+
+/// The bytes the authentication tag adds to each message.
+const TAG_LEN: usize = 16;
+
+/// The most plaintext one transport message carries.
+const MAX_PLAINTEXT: usize = MAX_NOISE_MESSAGE.saturating_sub(TAG_LEN);
+
+/// How much is read from the socket at a time.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// The read half of a daemon connection, decrypting it when it is encrypted.
+pub type Reader = Box<dyn AsyncRead + Send + Sync + Unpin>;
+
+/// The write half of a daemon connection, encrypting it when it is encrypted.
+pub type Writer = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+/// The pre-shared key that encrypts connections on a shared daemon socket.
+#[derive(Clone)]
+pub struct TransportKey(Zeroizing<[u8; TRANSPORT_KEY_LEN]>);
+
+impl TransportKey {
+    /// A key of `bytes`, which should be random.
+    #[must_use]
+    pub fn new(bytes: [u8; TRANSPORT_KEY_LEN]) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// Read the key the daemon keeps at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not hold exactly
+    /// [`TRANSPORT_KEY_LEN`] bytes.
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = Zeroizing::new(fs::read(path).with_context(|| {
+            format!(
+                "unable to read the transport key at {}; salusd creates it when it starts",
+                path.display()
+            )
+        })?);
+        let key = <[u8; TRANSPORT_KEY_LEN]>::try_from(bytes.as_slice()).map_err(|_| {
+            anyhow!(
+                "the transport key at {} is not {TRANSPORT_KEY_LEN} bytes",
+                path.display()
+            )
+        })?;
+        Ok(Self::new(key))
+    }
+}
+
+impl fmt::Debug for TransportKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TransportKey(<redacted>)")
+    }
+}
+
+/// Where the transport key is kept.
+///
+/// `override_path`, when `Some`, wins over the shared `SALUS_TRANSPORT_KEY`
+/// environment variable, which wins over `transport.key` in the daemon's
+/// config directory.
+///
+/// # Errors
+///
+/// Returns an error if no path is given and there is no config directory.
+pub fn transport_key_path(override_path: Option<&str>) -> Result<PathBuf> {
+    let env_path = std::env::var(TRANSPORT_KEY_ENV).ok();
+    if let Some(path) = override_path.or(env_path.as_deref()) {
+        Ok(PathBuf::from(path))
+    } else {
+        let base = dirs2::config_dir().context("there is no valid config directory")?;
+        Ok(base.join(DAEMON_NAME).join(TRANSPORT_KEY_FILE_NAME))
+    }
+}
+
+/// The key a client encrypts its connection to the daemon at `socket` with:
+/// the one at `key_path`, as [`transport_key_path`] resolves it, when
+/// [`socket_is_shared`], or else none.
+///
+/// # Errors
+///
+/// Returns an error if the socket is shared and the key cannot be read.
+pub fn client_transport_key(
+    socket: Option<&str>,
+    key_path: Option<&str>,
+) -> Result<Option<TransportKey>> {
+    if !socket_is_shared(socket) {
+        return Ok(None);
+    }
+    TransportKey::read(&transport_key_path(key_path)?).map(Some)
+}
+
+/// Open the client side of a connection on `recver` and `sender`: encrypted
+/// under `key` when there is one, or else as it is.
+///
+/// # Errors
+///
+/// Returns an error if the handshake fails, as it does when the daemon holds
+/// another key.
+pub async fn initiate<R, W>(
+    recver: R,
+    sender: W,
+    key: Option<&TransportKey>,
+) -> Result<(Reader, Writer)>
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    match key {
+        Some(key) => handshake(recver, sender, key, true).await.context(
+            "salusd refused the transport handshake; check that both use the same transport key",
+        ),
+        None => Ok((Box::new(recver), Box::new(sender))),
+    }
+}
+
+/// Open the daemon side of a connection on `recver` and `sender`: encrypted
+/// under `key` when there is one, or else as it is.
+///
+/// # Errors
+///
+/// Returns an error if the handshake fails, as it does when the client holds
+/// another key.
+pub async fn respond<R, W>(
+    recver: R,
+    sender: W,
+    key: Option<&TransportKey>,
+) -> Result<(Reader, Writer)>
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    match key {
+        Some(key) => handshake(recver, sender, key, false).await,
+        None => Ok((Box::new(recver), Box::new(sender))),
+    }
+}
+
+/// Run the two messages of the `NNpsk0` handshake, as its initiator or its
+/// responder, and wrap the halves in the transport it agrees.
+async fn handshake<R, W>(
+    mut recver: R,
+    mut sender: W,
+    key: &TransportKey,
+    initiator: bool,
+) -> Result<(Reader, Writer)>
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let params: NoiseParams = NOISE_PATTERN.parse()?;
+    let builder = Builder::new(params).psk(0, key.0.as_slice());
+    let mut state = if initiator {
+        builder.build_initiator()?
+    } else {
+        builder.build_responder()?
+    };
+    let mut message = vec![0; MAX_NOISE_MESSAGE];
+    let mut payload = vec![0; MAX_NOISE_MESSAGE];
+    while !state.is_handshake_finished() {
+        if state.is_my_turn() {
+            let len = state.write_message(&[], &mut message)?;
+            send_message(&mut sender, message.get(..len).unwrap_or_default()).await?;
+        } else {
+            let received = recv_message(&mut recver).await?;
+            let _len = state.read_message(&received, &mut payload)?;
+        }
+    }
+    let state = Arc::new(state.into_stateless_transport_mode()?);
+    let reader = NoiseReader {
+        inner: recver,
+        state: Arc::clone(&state),
+        nonce: 0,
+        received: Vec::new(),
+        plain: Zeroizing::new(Vec::new()),
+        read: 0,
+    };
+    let writer = NoiseWriter {
+        inner: sender,
+        state,
+        nonce: 0,
+        pending: Vec::new(),
+        written: 0,
+    };
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// Write one length-prefixed handshake message.
+async fn send_message<W: AsyncWrite + Unpin>(sender: &mut W, message: &[u8]) -> Result<()> {
+    let len = u16::try_from(message.len())?;
+    sender.write_all(&len.to_be_bytes()).await?;
+    sender.write_all(message).await?;
+    sender.flush().await?;
+    Ok(())
+}
+
+/// Read one length-prefixed handshake message.
+async fn recv_message<R: AsyncRead + Unpin>(recver: &mut R) -> Result<Vec<u8>> {
+    let mut len = [0; 2];
+    let _read = recver.read_exact(&mut len).await?;
+    let mut message = vec![0; usize::from(u16::from_be_bytes(len))];
+    let _read = recver.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+/// The next nonce after `nonce`, or an error once they run out.
+fn next_nonce(nonce: u64) -> io::Result<u64> {
+    nonce
+        .checked_add(1)
+        .ok_or_else(|| io::Error::other("the connection has used up its nonces"))
+}
+
+/// Decrypts the transport messages read from `inner`.
+struct NoiseReader<R> {
+    inner: R,
+    state: Arc<StatelessTransportState>,
+    /// The nonce of the next message read.
+    nonce: u64,
+    /// What has been read and not yet decrypted.
+    received: Vec<u8>,
+    /// The last message decrypted.
+    plain: Zeroizing<Vec<u8>>,
+    /// How much of `plain` has been read.
+    read: usize,
+}
+
+impl<R> NoiseReader<R> {
+    /// Decrypt the next complete message in `received`, returning whether
+    /// there was one.
+    fn decrypt_next(&mut self) -> io::Result<bool> {
+        let Some((len, rest)) = self.received.split_first_chunk::<2>() else {
+            return Ok(false);
+        };
+        let len = usize::from(u16::from_be_bytes(*len));
+        let Some(message) = rest.get(..len) else {
+            return Ok(false);
+        };
+        let mut plain = Zeroizing::new(vec![0; len]);
+        let plain_len = self
+            .state
+            .read_message(self.nonce, message, &mut plain)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        plain.truncate(plain_len);
+        self.nonce = next_nonce(self.nonce)?;
+        let _decrypted = self.received.drain(..len.saturating_add(2));
+        self.plain = plain;
+        self.read = 0;
+        Ok(true)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for NoiseReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(unread) = this.plain.get(this.read..).filter(|u| !u.is_empty()) {
+                let take = unread.len().min(buf.remaining());
+                buf.put_slice(unread.get(..take).unwrap_or_default());
+                this.read = this.read.saturating_add(take);
+                return Poll::Ready(Ok(()));
+            }
+            if this.decrypt_next()? {
+                continue;
+            }
+            let mut chunk = [0; READ_CHUNK];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                // The peer closed its side, which is only clean between
+                // messages.
+                return Poll::Ready(if this.received.is_empty() {
+                    Ok(())
+                } else {
+                    Err(io::ErrorKind::UnexpectedEof.into())
+                });
+            }
+            this.received.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+/// Encrypts what is written into transport messages for `inner`.
+struct NoiseWriter<W> {
+    inner: W,
+    state: Arc<StatelessTransportState>,
+    /// The nonce of the next message written.
+    nonce: u64,
+    /// The last message encrypted, with its length.
+    pending: Vec<u8>,
+    /// How much of `pending` has been written to `inner`.
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> NoiseWriter<W> {
+    /// Write what is left of `pending` to `inner`.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(rest) = self.pending.get(self.written..).filter(|r| !r.is_empty()) {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, rest))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written = self.written.saturating_add(written);
+        }
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for NoiseWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let plain = buf.get(..MAX_PLAINTEXT).unwrap_or(buf);
+        if plain.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut message = vec![0; plain.len().saturating_add(TAG_LEN)];
+        let len = this
+            .state
+            .write_message(this.nonce, plain, &mut message)
+            .map_err(io::Error::other)?;
+        this.nonce = next_nonce(this.nonce)?;
+        let prefix = u16::try_from(len).map_err(io::Error::other)?;
+        this.pending.extend_from_slice(&prefix.to_be_bytes());
+        this.pending
+            .extend_from_slice(message.get(..len).unwrap_or_default());
+        Poll::Ready(Ok(plain.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _, duplex, split},
+        join,
+    };
+
+    use super::{MAX_PLAINTEXT, TRANSPORT_KEY_LEN, TransportKey, initiate, respond};
+
+    #[tokio::test]
+    async fn both_sides_with_the_key_talk_in_messages_of_any_size() -> Result<()> {
+        let key = TransportKey::new([7; TRANSPORT_KEY_LEN]);
+        let (client, daemon) = duplex(4096);
+        let (client_recver, client_sender) = split(client);
+        let (daemon_recver, daemon_sender) = split(daemon);
+        let (client, daemon) = join!(
+            initiate(client_recver, client_sender, Some(&key)),
+            respond(daemon_recver, daemon_sender, Some(&key)),
+        );
+        let ((mut client_recver, mut client_sender), (mut daemon_recver, mut daemon_sender)) =
+            (client?, daemon?);
+
+        let request: Vec<u8> = (0u8..=255)
+            .cycle()
+            .take(MAX_PLAINTEXT.saturating_mul(2).saturating_add(3))
+            .collect();
+        let sent = async {
+            client_sender.write_all(&request).await?;
+            client_sender.shutdown().await?;
+            Ok::<(), anyhow::Error>(())
+        };
+        let mut received = Vec::new();
+        let (sent, read) = join!(sent, daemon_recver.read_to_end(&mut received));
+        sent?;
+        let _read = read?;
+        assert_eq!(received, request);
+
+        daemon_sender.write_all(b"answer").await?;
+        daemon_sender.shutdown().await?;
+        let mut answer = Vec::new();
+        let _read = client_recver.read_to_end(&mut answer).await?;
+        assert_eq!(answer, b"answer");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_client_with_another_key_is_refused() -> Result<()> {
+        let (client, daemon) = duplex(4096);
+        let (client_recver, client_sender) = split(client);
+        let (daemon_recver, daemon_sender) = split(daemon);
+        let client_key = TransportKey::new([1; TRANSPORT_KEY_LEN]);
+        let daemon_key = TransportKey::new([2; TRANSPORT_KEY_LEN]);
+        let (client, daemon) = join!(
+            initiate(client_recver, client_sender, Some(&client_key)),
+            respond(daemon_recver, daemon_sender, Some(&daemon_key)),
+        );
+        if daemon.is_ok() {
+            bail!("the daemon accepted a client with another key");
+        }
+        assert!(client.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn a_key_file_of_the_wrong_length_is_refused() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("salus-transport-key-test-{}", std::process::id()));
+        std::fs::write(&path, [0u8; 16])?;
+        let read = TransportKey::read(&path);
+        std::fs::remove_file(&path)?;
+        assert!(read.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Key wrapping for custody transfers.
+//!
+//! A key is sealed to a recipient's X25519 public key, so it can move between
+//! systems without ever being in the clear outside either end. A wrapped key
+//! is:
+//!
+//! ```text
+//! version (1) || ephemeral public key (32) || nonce (12) || ciphertext || tag (16)
+//! ```
+//!
+//! The sender generates an ephemeral X25519 key pair, agrees a shared secret
+//! with the recipient's public key, and derives an AES-256-GCM key from it
+//! with HKDF-SHA256 (salt: the ephemeral public key then the recipient's;
+//! info: `salus key wrap v1`). The version byte and ephemeral public key are
+//! the AAD.
+
+use anyhow::{Result, anyhow, bail};
+use aws_lc_rs::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    agreement::{self, EphemeralPrivateKey, PrivateKey, UnparsedPublicKey, X25519},
+    hkdf, rand,
+};
+use zeroize::Zeroizing;
+
+/// The length of an X25519 public key, in bytes.
+pub const WRAP_PUBLIC_KEY_LEN: usize = 32;
+/// The layout written by this version.
+const WRAP_VERSION: u8 = 1;
+/// The HKDF info binding derived keys to this format.
+const WRAP_INFO: &[u8] = b"salus key wrap v1";
+/// The length of the derived AES-256-GCM key, in bytes.
+const WRAP_KEY_LEN: usize = 32;
+
+/// An X25519 key pair that wrapped keys can be sealed to.
+///
+/// salusd hands out the public half of one of these for `import-wrapped`; the
+/// private half never leaves the process that generated it.
+#[derive(Debug)]
+pub struct WrappingKey {
+    private: PrivateKey,
+}
+
+impl WrappingKey {
+    /// Generate a new wrapping key pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be generated.
+    pub fn generate() -> Result<Self> {
+        Ok(Self {
+            private: PrivateKey::generate(&X25519)?,
+        })
+    }
+
+    /// The public key to wrap to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the public key cannot be computed.
+    pub fn public_key(&self) -> Result<Vec<u8>> {
+        Ok(self.private.compute_public_key()?.as_ref().to_vec())
+    }
+
+    /// Unwrap a key sealed to this key pair by [`wrap_key`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `wrapped` is malformed, was sealed to another key,
+    /// or was tampered with.
+    pub fn unwrap_key(&self, wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let Some((&WRAP_VERSION, rest)) = wrapped.split_first() else {
+            bail!("the wrapped key is not in a format this version reads");
+        };
+        let Some((ephemeral, rest)) = rest.split_at_checked(WRAP_PUBLIC_KEY_LEN) else {
+            bail!("the wrapped key is truncated");
+        };
+        let Some((nonce, sealed)) = rest.split_at_checked(NONCE_LEN) else {
+            bail!("the wrapped key is truncated");
+        };
+        let recipient = self.public_key()?;
+        let key = agreement::agree(
+            &self.private,
+            UnparsedPublicKey::new(&X25519, ephemeral),
+            anyhow!("the wrapped key's ephemeral public key is invalid"),
+            |secret| derive(secret, ephemeral, &recipient),
+        )?;
+        let aad = header(ephemeral);
+        let mut plaintext = Zeroizing::new(sealed.to_vec());
+        let len = key
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce)?,
+                Aad::from(&aad),
+                &mut plaintext,
+            )
+            .map_err(|_| anyhow!("the wrapped key was sealed to another key or tampered with"))?
+            .len();
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+}
+
+/// Seal `plaintext` to `recipient`, an X25519 public key.
+///
+/// # Errors
+///
+/// Returns an error if `recipient` is not a valid X25519 public key.
+pub fn wrap_key(recipient: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    if recipient.len() != WRAP_PUBLIC_KEY_LEN {
+        bail!("an X25519 public key is {WRAP_PUBLIC_KEY_LEN} bytes");
+    }
+    let ephemeral = EphemeralPrivateKey::generate(&X25519, &rand::SystemRandom::new())?;
+    let ephemeral_public = ephemeral.compute_public_key()?;
+    let key = agreement::agree_ephemeral(
+        ephemeral,
+        UnparsedPublicKey::new(&X25519, recipient),
+        anyhow!("the recipient public key is invalid"),
+        |secret| derive(secret, ephemeral_public.as_ref(), recipient),
+    )?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::fill(&mut nonce)?;
+    let mut wrapped = header(ephemeral_public.as_ref());
+    let aad = wrapped.clone();
+    wrapped.extend_from_slice(&nonce);
+    let mut sealed = Zeroizing::new(plaintext.to_vec());
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(&aad),
+        &mut *sealed,
+    )?;
+    wrapped.extend_from_slice(&sealed);
+    Ok(wrapped)
+}
+
+/// The version byte and ephemeral public key, which every wrapped key starts
+/// with and authenticates.
+fn header(ephemeral: &[u8]) -> Vec<u8> {
+    let mut header = vec![WRAP_VERSION];
+    header.extend_from_slice(ephemeral);
+    header
+}
+
+/// The AES-256-GCM key for a shared secret.
+fn derive(secret: &[u8], ephemeral: &[u8], recipient: &[u8]) -> Result<LessSafeKey> {
+    let mut salt = ephemeral.to_vec();
+    salt.extend_from_slice(recipient);
+    let mut key = Zeroizing::new([0u8; WRAP_KEY_LEN]);
+    hkdf::Salt::new(hkdf::HKDF_SHA256, &salt)
+        .extract(secret)
+        .expand(&[WRAP_INFO], KeyLen)?
+        .fill(&mut *key)?;
+    Ok(LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &*key)?))
+}
+
+/// The HKDF output length for [`derive`].
+struct KeyLen;
+
+impl hkdf::KeyType for KeyLen {
+    fn len(&self) -> usize {
+        WRAP_KEY_LEN
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::{WrappingKey, wrap_key};
+
+    #[test]
+    fn keys_unwrap_only_for_their_recipient() -> Result<()> {
+        let recipient = WrappingKey::generate()?;
+        let wrapped = wrap_key(&recipient.public_key()?, b"hsm key material")?;
+        assert_eq!(
+            recipient.unwrap_key(&wrapped)?.as_slice(),
+            b"hsm key material"
+        );
+        assert!(WrappingKey::generate()?.unwrap_key(&wrapped).is_err());
+
+        let mut tampered = wrapped.clone();
+        if let Some(last) = tampered.last_mut() {
+            *last ^= 1;
+        }
+        assert!(recipient.unwrap_key(&tampered).is_err());
+        assert!(
+            recipient
+                .unwrap_key(wrapped.get(..20).unwrap_or_default())
+                .is_err()
+        );
+        assert!(wrap_key(b"short", b"x").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+pub fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(coverage_nightly)");
+    nightly();
+}
+
+#[rustversion::nightly]
+fn nightly() {
+    println!("cargo:rustc-check-cfg=cfg(nightly)");
+    println!("cargo:rustc-cfg=nightly");
+}
+
+#[rustversion::not(nightly)]
+fn nightly() {
+    println!("cargo:rustc-check-cfg=cfg(nightly)");
+}
@@ -0,0 +1,274 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Python bindings for salus.
+//!
+//! Built with the `python` feature, this crate is the `pysalus` extension
+//! module: a blocking `Client` for `salusd` that Python tooling can use in a
+//! `with` block instead of driving `salusc` and parsing what it prints.
+//!
+//! ```python
+//! import pysalus
+//!
+//! with pysalus.Client() as client:
+//!     password = client.read("db/password")  # bytes
+//!     client.store("db/user", "app", force=True)
+//! ```
+//!
+//! Secrets cross into Python as `bytes`, never decoded text, and the copies
+//! this crate makes on the way are zeroed once Python has its own. A missing
+//! key raises `KeyError`; everything else the daemon refuses raises
+//! `pysalus.SalusError` or one of its subclasses, `KeyExistsError` and
+//! `ReadOnlyError`.
+//!
+//! `maturin build --release` in this directory builds the wheel.
+
+// rustc lints
+#![cfg_attr(
+    all(feature = "unstable", nightly),
+    feature(
+        multiple_supertrait_upcastable,
+        must_not_suspend,
+        non_exhaustive_omitted_patterns_lint,
+        strict_provenance_lints,
+        unqualified_local_imports,
+    )
+)]
+#![cfg_attr(nightly, allow(single_use_lifetimes))]
+#![cfg_attr(
+    nightly,
+    deny(
+        absolute_paths_not_starting_with_crate,
+        ambiguous_glob_imports,
+        ambiguous_glob_reexports,
+        ambiguous_negative_literals,
+        ambiguous_wide_pointer_comparisons,
+        anonymous_parameters,
+        array_into_iter,
+        asm_sub_register,
+        async_fn_in_trait,
+        bad_asm_style,
+        bare_trait_objects,
+        boxed_slice_into_iter,
+        break_with_label_and_loop,
+        clashing_extern_declarations,
+        closure_returning_async_block,
+        coherence_leak_check,
+        confusable_idents,
+        const_evaluatable_unchecked,
+        const_item_mutation,
+        dangling_pointers_from_temporaries,
+        dead_code,
+        dependency_on_unit_never_type_fallback,
+        deprecated,
+        deprecated_in_future,
+        deprecated_safe_2024,
+        deprecated_where_clause_location,
+        deref_into_dyn_supertrait,
+        deref_nullptr,
+        double_negations,
+        drop_bounds,
+        dropping_copy_types,
+        dropping_references,
+        duplicate_macro_attributes,
+        dyn_drop,
+        edition_2024_expr_fragment_specifier,
+        elided_lifetimes_in_paths,
+        ellipsis_inclusive_range_patterns,
+        explicit_outlives_requirements,
+        exported_private_dependencies,
+        ffi_unwind_calls,
+        forbidden_lint_groups,
+        forgetting_copy_types,
+        forgetting_references,
+        for_loops_over_fallibles,
+        function_item_references,
+        hidden_glob_reexports,
+        if_let_rescope,
+        impl_trait_overcaptures,
+        impl_trait_redundant_captures,
+        improper_ctypes,
+        improper_ctypes_definitions,
+        inline_no_sanitize,
+        internal_features,
+        invalid_from_utf8,
+        invalid_macro_export_arguments,
+        invalid_nan_comparisons,
+        invalid_value,
+        irrefutable_let_patterns,
+        keyword_idents_2018,
+        keyword_idents_2024,
+        large_assignments,
+        late_bound_lifetime_arguments,
+        legacy_derive_helpers,
+        let_underscore_drop,
+        macro_use_extern_crate,
+        map_unit_fn,
+        meta_variable_misuse,
+        mismatched_lifetime_syntaxes,
+        missing_abi,
+        missing_copy_implementations,
+        missing_debug_implementations,
+        missing_docs,
+        missing_unsafe_on_extern,
+        mixed_script_confusables,
+        named_arguments_used_positionally,
+        never_type_fallback_flowing_into_unsafe,
+        no_mangle_generic_items,
+        non_ascii_idents,
+        non_camel_case_types,
+        non_contiguous_range_endpoints,
+        non_fmt_panics,
+        non_local_definitions,
+        non_shorthand_field_patterns,
+        non_snake_case,
+        non_upper_case_globals,
+        noop_method_call,
+        opaque_hidden_inferred_bound,
+        out_of_scope_macro_calls,
+        overlapping_range_endpoints,
+        path_statements,
+        private_bounds,
+        private_interfaces,
+        ptr_to_integer_transmute_in_consts,
+        redundant_imports,
+        redundant_lifetimes,
+        redundant_semicolons,
+        refining_impl_trait_internal,
+        refining_impl_trait_reachable,
+        renamed_and_removed_lints,
+        rust_2021_incompatible_closure_captures,
+        rust_2021_incompatible_or_patterns,
+        rust_2021_prefixes_incompatible_syntax,
+        rust_2021_prelude_collisions,
+        rust_2024_guarded_string_incompatible_syntax,
+        rust_2024_incompatible_pat,
+        rust_2024_prelude_collisions,
+        self_constructor_from_outer_item,
+        semicolon_in_expressions_from_macros,
+        single_use_lifetimes,
+        special_module_name,
+        stable_features,
+        static_mut_refs,
+        suspicious_double_ref_op,
+        tail_expr_drop_order,
+        trivial_bounds,
+        trivial_casts,
+        trivial_numeric_casts,
+        type_alias_bounds,
+        tyvar_behind_raw_pointer,
+        uncommon_codepoints,
+        unconditional_recursion,
+        uncovered_param_in_projection,
+        unexpected_cfgs,
+        unfulfilled_lint_expectations,
+        ungated_async_fn_track_caller,
+        uninhabited_static,
+        unit_bindings,
+        unknown_lints,
+        unknown_or_malformed_diagnostic_attributes,
+        unnameable_test_items,
+        unnameable_types,
+        unpredictable_function_pointer_comparisons,
+        unreachable_code,
+        unreachable_patterns,
+        unreachable_pub,
+        unsafe_attr_outside_unsafe,
+        unsafe_code,
+        unsafe_op_in_unsafe_fn,
+        unstable_name_collisions,
+        unstable_syntax_pre_expansion,
+        unused_allocation,
+        unused_assignments,
+        unused_associated_type_bounds,
+        unused_attributes,
+        unused_braces,
+        unused_comparisons,
+        unused_crate_dependencies,
+        unused_doc_comments,
+        unused_extern_crates,
+        unused_features,
+        unused_import_braces,
+        unused_imports,
+        unused_labels,
+        unused_lifetimes,
+        unused_macro_rules,
+        unused_macros,
+        unused_must_use,
+        unused_mut,
+        unused_parens,
+        unused_qualifications,
+        unused_results,
+        unused_unsafe,
+        unused_variables,
+        useless_ptr_null_checks,
+        uses_power_alignment,
+        variant_size_differences,
+        while_true,
+    )
+)]
+// If nightly and unstable, allow `incomplete_features` and `unstable_features`
+#![cfg_attr(
+    all(feature = "unstable", nightly),
+    allow(incomplete_features, unstable_features)
+)]
+// If nightly and not unstable, deny `incomplete_features` and `unstable_features`
+#![cfg_attr(
+    all(not(feature = "unstable"), nightly),
+    deny(incomplete_features, unstable_features)
+)]
+// The unstable lints
+#![cfg_attr(
+    all(feature = "unstable", nightly),
+    deny(
+        implicit_provenance_casts,
+        multiple_supertrait_upcastable,
+        must_not_suspend,
+        non_exhaustive_omitted_patterns,
+        unqualified_local_imports,
+    )
+)]
+// clippy lints
+#![cfg_attr(nightly, deny(clippy::all, clippy::pedantic))]
+// no-panic restriction lints: handle every error, never panic (see CLAUDE.md)
+#![cfg_attr(
+    nightly,
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing,
+        clippy::arithmetic_side_effects,
+        clippy::get_unwrap,
+        clippy::unwrap_in_result,
+    )
+)]
+// rustdoc lints
+#![cfg_attr(
+    nightly,
+    deny(
+        rustdoc::bare_urls,
+        rustdoc::broken_intra_doc_links,
+        rustdoc::invalid_codeblock_attributes,
+        rustdoc::invalid_html_tags,
+        rustdoc::missing_crate_level_docs,
+        rustdoc::private_doc_tests,
+        rustdoc::private_intra_doc_links,
+    )
+)]
+#![cfg_attr(all(docsrs), feature(doc_cfg))]
+
+// interprocess is only used by the `python` module's tests.
+#[cfg(all(test, not(feature = "python")))]
+use interprocess as _;
+
+#[cfg(feature = "python")]
+mod python;
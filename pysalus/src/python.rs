@@ -0,0 +1,322 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The `pysalus` module.
+
+use anyhow::Result;
+use libsalus::{Action, ClientApi as _, Response, Store};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyType},
+};
+use zeroize::Zeroizing;
+
+create_exception!(
+    pysalus,
+    SalusError,
+    PyException,
+    "salusd could not be reached, or refused a request."
+);
+create_exception!(
+    pysalus,
+    KeyExistsError,
+    SalusError,
+    "A value is already stored under the key, and `force` was not set."
+);
+create_exception!(
+    pysalus,
+    ReadOnlyError,
+    SalusError,
+    "salusd is read-only and refuses changes."
+);
+
+/// A value to store: text, or bytes holding UTF-8.
+#[derive(FromPyObject)]
+enum Value {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// The value as text, zeroed when dropped.
+    fn into_text(self) -> PyResult<Zeroizing<String>> {
+        match self {
+            Value::Text(text) => Ok(Zeroizing::new(text)),
+            Value::Bytes(bytes) => String::from_utf8(bytes).map(Zeroizing::new).map_err(|e| {
+                let _bytes = Zeroizing::new(e.into_bytes());
+                PyValueError::new_err("value must be UTF-8")
+            }),
+        }
+    }
+}
+
+/// A connection to salusd.
+///
+/// Opening one checks that the daemon answers. Every call blocks until the
+/// daemon replies, letting other Python threads run meanwhile. Use it in a
+/// `with` block, or call `close` when done.
+#[pyclass(module = "pysalus")]
+struct Client {
+    inner: Option<libsalus::Client>,
+}
+
+impl Client {
+    /// Send `action`, without holding the GIL while the daemon answers.
+    fn send(&self, py: Python<'_>, action: Action) -> PyResult<Result<Response>> {
+        let Some(client) = &self.inner else {
+            return Err(SalusError::new_err("the client is closed"));
+        };
+        Ok(py.detach(|| client.send(action)))
+    }
+}
+
+#[pymethods]
+impl Client {
+    /// Connect to the daemon at `socket`, or at the default socket (or
+    /// `SALUS_SOCKET`) when it is `None`.
+    #[new]
+    #[pyo3(signature = (socket = None))]
+    fn new(py: Python<'_>, socket: Option<&str>) -> PyResult<Self> {
+        let inner = py
+            .detach(|| libsalus::Client::connect(socket))
+            .map_err(|e| SalusError::new_err(format!("{e:#}")))?;
+        Ok(Self { inner: Some(inner) })
+    }
+
+    /// The value stored under `key`, as bytes.
+    ///
+    /// Raises `KeyError` when nothing is stored under it.
+    fn read<'py>(&self, py: Python<'py>, key: String) -> PyResult<Bound<'py, PyBytes>> {
+        match self.send(py, Action::Read(key.clone()))? {
+            Ok(Response::Value(Some(value))) => Ok(PyBytes::new(py, &Zeroizing::new(value))),
+            Ok(Response::Value(None) | Response::KeyNotFound) => Err(PyKeyError::new_err(key)),
+            other => Err(refused(other)),
+        }
+    }
+
+    /// The value stored under `key`, or `default` when nothing is.
+    #[pyo3(signature = (key, default = None))]
+    fn get<'py>(
+        &self,
+        py: Python<'py>,
+        key: String,
+        default: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<Option<Bound<'py, PyAny>>> {
+        match self.read(py, key) {
+            Ok(value) => Ok(Some(value.into_any())),
+            Err(e) if e.is_instance_of::<PyKeyError>(py) => Ok(default),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store `value` (text, or bytes holding UTF-8) under `key`.
+    ///
+    /// Raises `KeyExistsError` when a value is already stored there, unless
+    /// `force` is set.
+    #[pyo3(signature = (key, value, *, force = false))]
+    fn store(&self, py: Python<'_>, key: String, value: Value, force: bool) -> PyResult<()> {
+        let value = value.into_text()?;
+        let store = Store::builder()
+            .key(key)
+            .value(value.as_str())
+            .force(force)
+            .build();
+        match self.send(py, Action::Store(store))? {
+            Ok(Response::Success) => Ok(()),
+            Ok(Response::KeyExists) => Err(KeyExistsError::new_err(
+                "a value is already stored under the key",
+            )),
+            other => Err(refused(other)),
+        }
+    }
+
+    /// Delete the value stored under `key`.
+    ///
+    /// Raises `KeyError` when nothing is stored under it.
+    fn delete(&self, py: Python<'_>, key: String) -> PyResult<()> {
+        match self.send(py, Action::Delete(key.clone()))? {
+            Ok(Response::Success) => Ok(()),
+            Ok(Response::KeyNotFound) => Err(PyKeyError::new_err(key)),
+            other => Err(refused(other)),
+        }
+    }
+
+    /// Close the client; any later call raises `SalusError`.
+    fn close(&mut self) {
+        self.inner = None;
+    }
+
+    /// Whether the client has been closed.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<Bound<'_, PyType>>,
+        _exc_value: Option<Bound<'_, PyAny>>,
+        _traceback: Option<Bound<'_, PyAny>>,
+    ) {
+        self.close();
+    }
+}
+
+/// The exception for a request the daemon refused, or a response no call
+/// expects.
+fn refused(response: Result<Response>) -> PyErr {
+    match response {
+        Ok(Response::ReadOnly) => ReadOnlyError::new_err("salusd is read-only and refuses changes"),
+        Ok(Response::Error(error)) => SalusError::new_err(error),
+        Ok(Response::MessageTooLarge(limit)) => {
+            SalusError::new_err(format!("salusd accepts requests of at most {limit} bytes"))
+        }
+        Ok(Response::UnknownAction(tag)) => SalusError::new_err(format!(
+            "salusd does not know action {tag}; it may be out of date"
+        )),
+        Ok(_) => SalusError::new_err("unexpected response from salusd"),
+        Err(e) => SalusError::new_err(format!("{e:#}")),
+    }
+}
+
+/// Python bindings for the salus secret store.
+#[pymodule]
+fn pysalus(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_class::<Client>()?;
+    module.add("SalusError", py.get_type::<SalusError>())?;
+    module.add("KeyExistsError", py.get_type::<KeyExistsError>())?;
+    module.add("ReadOnlyError", py.get_type::<ReadOnlyError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read as _, Write as _},
+        thread::{self, JoinHandle},
+    };
+
+    use anyhow::{Context as _, Result, anyhow, bail};
+    use interprocess::local_socket::{ListenerOptions, prelude::*};
+    use libsalus::{Action, Response, StoreStatus, decode_frame, encode_frame, socket_name};
+    use pyo3::{prelude::*, types::PyDict, wrap_pymodule};
+
+    /// Answer one request per response on `path`, returning the requests.
+    fn mock_daemon(
+        path: &str,
+        responses: Vec<Response>,
+    ) -> Result<JoinHandle<Result<Vec<Action>>>> {
+        let listener = ListenerOptions::new()
+            .name(socket_name(Some(path))?)
+            .create_sync()?;
+        Ok(thread::spawn(move || {
+            let mut actions = vec![];
+            for response in responses {
+                let mut conn = listener.accept()?;
+                let mut request = vec![];
+                let _len = conn.read_to_end(&mut request)?;
+                actions.push(decode_frame(&request)?);
+                conn.write_all(&encode_frame(response)?)?;
+            }
+            Ok(actions)
+        }))
+    }
+
+    fn status() -> Response {
+        Response::Status(
+            StoreStatus::builder()
+                .initialized(true)
+                .sealed(false)
+                .threshold(3)
+                .num_shares(5)
+                .shares_collected(0)
+                .daemon_version("test")
+                .build(),
+        )
+    }
+
+    #[test]
+    fn the_client_reads_stores_and_closes_in_a_with_block() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("pysalus-{}.sock", std::process::id()))
+            .display()
+            .to_string();
+        let _old = std::fs::remove_file(&path);
+        let daemon = mock_daemon(
+            &path,
+            vec![
+                status(),
+                Response::Value(Some(vec![0x73, 0x00, 0xff])),
+                Response::KeyNotFound,
+                Response::KeyExists,
+                Response::Success,
+                Response::ReadOnly,
+                Response::Error("Store not unlocked".to_string()),
+            ],
+        )?;
+        Python::initialize();
+        Python::attach(|py| -> PyResult<()> {
+            let locals = PyDict::new(py);
+            locals.set_item("pysalus", wrap_pymodule!(super::pysalus)(py))?;
+            locals.set_item("socket", &path)?;
+            py.run(
+                cr#"
+with pysalus.Client(socket) as client:
+    assert client.read("k") == b"s\x00\xff"
+    assert client.get("missing", b"d") == b"d"
+    try:
+        client.store("k", "v")
+        raise AssertionError("stored over an existing value")
+    except pysalus.KeyExistsError:
+        pass
+    client.store("k", b"v2", force=True)
+    try:
+        client.delete("k")
+        raise AssertionError("deleted on a read-only daemon")
+    except pysalus.ReadOnlyError:
+        pass
+    try:
+        client.store("k", b"\xff")
+        raise AssertionError("stored bytes that are not UTF-8")
+    except ValueError:
+        pass
+    try:
+        client.read("k")
+        raise AssertionError("read from a locked store")
+    except pysalus.SalusError as e:
+        assert str(e) == "Store not unlocked"
+assert client.closed
+try:
+    client.read("k")
+    raise AssertionError("read from a closed client")
+except pysalus.SalusError:
+    pass
+"#,
+                None,
+                Some(&locals),
+            )
+        })
+        .map_err(|e| anyhow!("{e}"))?;
+        let actions = daemon.join().ok().context("mock daemon panicked")??;
+        let _removed = std::fs::remove_file(&path);
+        let Some(Action::Store(store)) = actions.get(4) else {
+            bail!("expected a store, got {actions:?}");
+        };
+        assert_eq!((store.key(), store.value()), ("k", "v2"));
+        assert!(store.force());
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use config::{Config, Environment, File, FileFormat, Source};
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
@@ -28,10 +29,53 @@ pub(crate) trait PathDefaults {
     fn app_name(&self) -> String;
     /// The absolute path to use for the config file
     fn config_absolute_path(&self) -> Option<String>;
+    /// The format the config file is written in, when not its extension's
+    fn config_format(&self) -> Option<ConfigFormat>;
     /// The absolute path to use for tracing output
     fn tracing_absolute_path(&self) -> Option<String>;
 }
 
+/// The formats a config file can be written in
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Every format, in the order a default config file is looked for in
+    const ALL: [Self; 3] = [Self::Toml, Self::Yaml, Self::Json];
+
+    /// The extensions a file in this format is known by
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Toml => &["toml"],
+            Self::Yaml => &["yaml", "yml"],
+            Self::Json => &["json"],
+        }
+    }
+
+    /// The format `path`'s extension names, if it names one.
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extensions().contains(&extension.as_str()))
+    }
+}
+
+impl From<ConfigFormat> for FileFormat {
+    fn from(format: ConfigFormat) -> Self {
+        match format {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Yaml => FileFormat::Yaml,
+            ConfigFormat::Json => FileFormat::Json,
+        }
+    }
+}
+
 /// The documented default for [`ConfigSalusAgent::passphrase_cache_timeout`].
 ///
 /// The agent caches an unsealed final share for this many seconds after a
@@ -137,12 +181,17 @@ where
     D: PathDefaults,
 {
     let config_file_path = config_file_path(defaults)?;
+    // The format set explicitly, else the one the extension names, else TOML.
+    let format = defaults
+        .config_format()
+        .or_else(|| ConfigFormat::from_path(&config_file_path))
+        .unwrap_or_default();
     let config = Config::builder()
         // Lowest precedence first; the `config` crate is last-wins, so the order
-        // is: TOML file -> environment -> explicitly-set CLI flags.
+        // is: config file -> environment -> explicitly-set CLI flags.
         .add_source(
             File::from(config_file_path)
-                .format(FileFormat::Toml)
+                .format(format.into())
                 .required(false),
         )
         .add_source(env_source(&defaults.env_prefix()))
@@ -185,24 +234,48 @@ where
     D: PathDefaults,
 {
     let base = dirs2::config_dir().ok_or(Error::ConfigDir)?;
-    Ok(config_file_in(&base, &defaults.app_name()))
+    Ok(config_file_in(
+        &base,
+        &defaults.app_name(),
+        defaults.config_format(),
+    ))
 }
 
-/// Compose the default config file path: `<base>/<app>/<app>.toml`.
-fn config_file_in(base: &Path, app: &str) -> PathBuf {
-    base.join(app).join(app).with_extension("toml")
+/// Compose the default config file path, `<base>/<app>/<app>.<ext>`: the
+/// first of `.toml`, `.yaml`, `.yml` and `.json` (only `format`'s, when it is
+/// set) that exists, or else the first of them.
+fn config_file_in(base: &Path, app: &str, format: Option<ConfigFormat>) -> PathBuf {
+    let stem = base.join(app).join(app);
+    let formats = format
+        .as_ref()
+        .map_or(ConfigFormat::ALL.as_slice(), std::slice::from_ref);
+    let candidates = formats
+        .iter()
+        .flat_map(|format| format.extensions())
+        .map(|extension| stem.with_extension(extension))
+        .collect::<Vec<_>>();
+    candidates
+        .iter()
+        .find(|candidate| candidate.exists())
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or_else(|| stem.with_extension("toml"))
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use std::{
+        env, fs,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    };
 
     use anyhow::Result;
     use config::{Config, ConfigError, Map, Source, Value, ValueKind};
 
     use super::{
-        ConfigSalusAgent, DEFAULT_PASSPHRASE_CACHE_TIMEOUT, PathDefaults, Tracing, config_file_in,
-        env_source, load,
+        ConfigFormat, ConfigSalusAgent, DEFAULT_PASSPHRASE_CACHE_TIMEOUT, PathDefaults, Tracing,
+        config_file_in, env_source, load,
     };
 
     /// A test double that is both a CLI [`Source`] and a [`PathDefaults`], so a
@@ -210,6 +283,7 @@ mod test {
     #[derive(Clone, Debug)]
     struct TestCli {
         config_path: Option<String>,
+        config_format: Option<ConfigFormat>,
         socket_path: Option<String>,
     }
 
@@ -241,6 +315,9 @@ mod test {
         fn config_absolute_path(&self) -> Option<String> {
             self.config_path.clone()
         }
+        fn config_format(&self) -> Option<ConfigFormat> {
+            self.config_format
+        }
         fn tracing_absolute_path(&self) -> Option<String> {
             None
         }
@@ -248,8 +325,49 @@ mod test {
 
     #[test]
     fn config_file_in_composes_app_dir_and_extension() {
-        let path = config_file_in(Path::new("/base"), "salus-agent");
+        let path = config_file_in(Path::new("/base"), "salus-agent", None);
         assert_eq!(path, Path::new("/base/salus-agent/salus-agent.toml"));
+        let path = config_file_in(Path::new("/base"), "salus-agent", Some(ConfigFormat::Yaml));
+        assert_eq!(path, Path::new("/base/salus-agent/salus-agent.yaml"));
+    }
+
+    #[test]
+    fn config_files_are_read_in_the_format_their_extension_names() -> Result<()> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let dir =
+            env::temp_dir().join(format!("salus-agent-config-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("agent.yaml"), "passphrase_cache_timeout: 60\n")?;
+        fs::write(
+            dir.join("agent.json"),
+            r#"{"passphrase_cache_timeout": 61}"#,
+        )?;
+        fs::write(dir.join("agent.conf"), "passphrase_cache_timeout: 62\n")?;
+        let load_from = |name: &str, config_format| -> Result<ConfigSalusAgent> {
+            let cli = TestCli {
+                config_path: Some(dir.join(name).to_string_lossy().into_owned()),
+                config_format,
+                socket_path: None,
+            };
+            load(&cli, &cli)
+        };
+        assert_eq!(
+            load_from("agent.yaml", None)?.passphrase_cache_timeout(),
+            60
+        );
+        assert_eq!(
+            load_from("agent.json", None)?.passphrase_cache_timeout(),
+            61
+        );
+        assert_eq!(
+            load_from("agent.conf", Some(ConfigFormat::Yaml))?.passphrase_cache_timeout(),
+            62
+        );
+        assert!(load_from("agent.conf", None).is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
     }
 
     #[test]
@@ -276,6 +394,7 @@ mod test {
         // skipped, then let the CLI source supply an explicitly-set flag.
         let cli = TestCli {
             config_path: Some("/nonexistent/salus-agent-test.toml".to_string()),
+            config_format: None,
             socket_path: Some("/tmp/agent-test.sock".to_string()),
         };
         let cfg: ConfigSalusAgent = load(&cli, &cli)?;
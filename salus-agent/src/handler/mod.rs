@@ -10,7 +10,7 @@ use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use bon::Builder;
-use libsalus::{AgentAction, AgentResponse, encode};
+use libsalus::{AgentAction, AgentResponse, UnknownMessage, encode_frame};
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
     spawn,
@@ -103,8 +103,17 @@ where
         }
     }
 
+    /// Answer a request this agent does not know, most likely from a newer
+    /// client.
+    pub(crate) async fn unknown(&mut self, unknown: UnknownMessage) -> Result<()> {
+        self.respond(AgentResponse::Error(format!(
+            "salus-agent does not know this request ({unknown}); it may be older than this client"
+        )))
+        .await
+    }
+
     async fn respond(&mut self, response: AgentResponse) -> Result<()> {
-        let message = encode(response)?;
+        let message = encode_frame(response)?;
         self.sender.write_all(&message).await?;
         self.sender.flush().await?;
         Ok(())
@@ -124,7 +133,7 @@ mod test {
     use std::sync::{Arc, Mutex};
 
     use anyhow::Result;
-    use libsalus::{AgentAction, AgentResponse, decode};
+    use libsalus::{AgentAction, AgentResponse, decode_frame};
 
     use super::AgentHandler;
     use crate::{keystore, store::AgentState, test_keyring::guard};
@@ -144,7 +153,7 @@ mod test {
     ) -> Result<AgentResponse> {
         let mut handler = handler(Arc::new(Mutex::new(state)), cache_timeout);
         handler.handle(action).await?;
-        decode::<AgentResponse>(&handler.sender)
+        decode_frame::<AgentResponse>(&handler.sender)
     }
 
     fn enroll_alpha() -> Result<()> {
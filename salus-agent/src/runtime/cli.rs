@@ -10,7 +10,7 @@ use clap::{ArgAction, Parser};
 use config::{ConfigError, Map, Source, Value, ValueKind};
 use getset::Getters;
 
-use crate::config::PathDefaults;
+use crate::config::{ConfigFormat, PathDefaults};
 
 #[derive(Clone, Debug, Getters, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -41,6 +41,13 @@ pub(crate) struct Cli {
     /// The absolute path to a non-standard config file
     #[clap(short, long, help = "Specify the absolute path to the config file")]
     config_absolute_path: Option<String>,
+    /// The config file's format, when its extension does not name it
+    #[clap(
+        long,
+        value_enum,
+        help = "Read the config file as toml, yaml or json, whatever its extension"
+    )]
+    config_format: Option<ConfigFormat>,
     /// The absolute path to a non-standard tracing output file
     #[clap(
         short,
@@ -110,6 +117,10 @@ impl PathDefaults for Cli {
         self.config_absolute_path.clone()
     }
 
+    fn config_format(&self) -> Option<ConfigFormat> {
+        self.config_format
+    }
+
     fn tracing_absolute_path(&self) -> Option<String> {
         self.tracing_absolute_path.clone()
     }
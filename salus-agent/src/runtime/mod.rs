@@ -18,7 +18,7 @@ use interprocess::local_socket::{
     ListenerOptions,
     traits::tokio::{Listener, Stream as _},
 };
-use libsalus::{AgentAction, agent_socket_name, decode};
+use libsalus::{AgentAction, UnknownMessage, agent_socket_name, decode_frame};
 use tokio::{io::AsyncReadExt, spawn};
 use tracing::{error, info, trace};
 
@@ -96,16 +96,23 @@ where
                 return;
             }
             // A forged length prefix cannot trigger an unbounded allocation here:
-            // `decode` enforces `MAX_MESSAGE_SIZE`. Malformed input is dropped.
-            if let Ok(message) = decode::<AgentAction>(&msg_buf) {
-                let mut handler = AgentHandler::builder()
-                    .sender(sender)
-                    .store(store_c)
-                    .cache_timeout(cache_timeout)
-                    .build();
-                if let Err(e) = handler.handle(message).await {
-                    error!("Error handling agent request: {e}");
-                }
+            // `decode` enforces `MAX_MESSAGE_SIZE`. A request from a newer
+            // client is answered with an error naming it; malformed input, or a
+            // frame failing its checksum, is dropped.
+            let mut handler = AgentHandler::builder()
+                .sender(sender)
+                .store(store_c)
+                .cache_timeout(cache_timeout)
+                .build();
+            let outcome = match decode_frame::<AgentAction>(&msg_buf) {
+                Ok(message) => handler.handle(message).await,
+                Err(e) => match e.downcast_ref::<UnknownMessage>() {
+                    Some(unknown) => handler.unknown(*unknown).await,
+                    None => Ok(()),
+                },
+            };
+            if let Err(e) = outcome {
+                error!("Error handling agent request: {e}");
             }
         });
     }
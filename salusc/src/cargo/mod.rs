@@ -0,0 +1,257 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Cargo's credential-provider protocol, spoken by `salusc cargo-credential`
+//! so cargo reads registry tokens from the store rather than from
+//! `~/.cargo/credentials.toml`.
+//!
+//! The provider opens with a hello line naming the protocol versions it
+//! speaks; cargo then writes each request as one line of JSON on stdin and
+//! reads one line of JSON back from stdout. `get` answers with the token,
+//! `login` stores one (asking for it on the terminal when cargo has none),
+//! and `logout` deletes it. A registry's token is kept under
+//! `<prefix><name>`, with cargo's name for the registry (`crates-io` for
+//! crates.io), or under `<prefix><index-url>` for a registry that has none.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+};
+
+use anyhow::{Context as _, Result};
+use libsalus::{Action, Response, Store};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize as _, Zeroizing};
+
+use crate::{error::Error, inter::Inter};
+
+/// The one protocol version spoken.
+const VERSION: u32 = 1;
+
+/// The terminal a token is asked for on, since stdin and stdout carry the
+/// protocol.
+#[cfg(unix)]
+const TERMINAL: &str = "/dev/tty";
+#[cfg(windows)]
+const TERMINAL: &str = "CONIN$";
+
+/// A request from cargo.
+#[derive(Debug, Deserialize)]
+struct Request {
+    v: u32,
+    registry: Registry,
+    #[serde(flatten)]
+    kind: Kind,
+}
+
+/// The registry a request is for.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Registry {
+    index_url: String,
+    name: Option<String>,
+}
+
+impl Registry {
+    /// How the registry is named, to cargo and in the store.
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.index_url)
+    }
+}
+
+/// What cargo asks for.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Kind {
+    /// The registry's token, for any operation
+    Get,
+    /// Store the registry's token
+    Login { token: Option<String> },
+    /// Forget the registry's token
+    Logout,
+    /// Anything a later protocol adds
+    #[serde(other)]
+    Unknown,
+}
+
+/// The answer to a request.
+#[derive(Debug, Serialize)]
+enum Reply {
+    Ok(Success),
+    Err(Failure),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Success {
+    Get {
+        token: String,
+        cache: &'static str,
+        operation_independent: bool,
+    },
+    Login,
+    Logout,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Failure {
+    NotFound,
+    OperationNotSupported,
+    Other { message: String },
+}
+
+impl Failure {
+    fn other(message: impl Into<String>) -> Reply {
+        Reply::Err(Failure::Other {
+            message: message.into(),
+        })
+    }
+}
+
+/// Answer cargo's requests on `input` with `inter`'s daemon, keeping tokens
+/// under `prefix`, until cargo closes `input`.
+///
+/// # Errors
+///
+/// Returns an error only if `input` or `output` fails; a request that cannot
+/// be served is answered with an error for cargo to report.
+pub(crate) async fn serve(
+    inter: &Inter,
+    prefix: &str,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    writeln!(output, "{}", serde_json::json!({ "v": [VERSION] }))?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = Zeroizing::new(line?);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if request.v != VERSION => Failure::other(format!(
+                "salusc speaks version {VERSION} of the credential protocol, not {}",
+                request.v
+            )),
+            Ok(request) => answer(inter, prefix, request).await,
+            Err(e) => Failure::other(format!("unreadable request: {e}")),
+        };
+        let reply = Zeroizing::new(serde_json::to_string(&reply)?);
+        writeln!(output, "{}", reply.as_str())?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+async fn answer(inter: &Inter, prefix: &str, request: Request) -> Reply {
+    let key = format!("{prefix}{}", request.registry.name());
+    let answered = match request.kind {
+        Kind::Get => get(inter, key).await,
+        Kind::Login { token } => login(inter, key, token, &request.registry).await,
+        Kind::Logout => logout(inter, key).await,
+        Kind::Unknown => Ok(Reply::Err(Failure::OperationNotSupported)),
+    };
+    answered.unwrap_or_else(|e| match e.downcast_ref::<Error>() {
+        // `send` has already said why on stderr.
+        Some(Error::Exit(_)) => Failure::other("salusd refused the request"),
+        None => Failure::other(format!("{e:#}")),
+    })
+}
+
+async fn get(inter: &Inter, key: String) -> Result<Reply> {
+    Ok(match inter.send(Action::Read(key)).await? {
+        Response::Value(Some(token)) => match String::from_utf8(token) {
+            Ok(token) => Reply::Ok(Success::Get {
+                token,
+                cache: "session",
+                operation_independent: true,
+            }),
+            Err(e) => {
+                e.into_bytes().zeroize();
+                Failure::other("the stored token is not text")
+            }
+        },
+        Response::Value(None) | Response::KeyNotFound => Reply::Err(Failure::NotFound),
+        Response::Error(error) => Failure::other(error),
+        _ => Failure::other("unexpected response from salusd"),
+    })
+}
+
+async fn login(
+    inter: &Inter,
+    key: String,
+    token: Option<String>,
+    registry: &Registry,
+) -> Result<Reply> {
+    let token = match token {
+        Some(token) => Zeroizing::new(token),
+        None => read_token(registry)?,
+    };
+    let store = Store::builder()
+        .key(key)
+        .value(token.trim())
+        .force(true)
+        .build();
+    Ok(match inter.send(Action::Store(store)).await? {
+        Response::Success => Reply::Ok(Success::Login),
+        Response::Error(error) => Failure::other(error),
+        _ => Failure::other("unexpected response from salusd"),
+    })
+}
+
+async fn logout(inter: &Inter, key: String) -> Result<Reply> {
+    Ok(match inter.send(Action::Delete(key)).await? {
+        Response::Success => Reply::Ok(Success::Logout),
+        Response::KeyNotFound => Reply::Err(Failure::NotFound),
+        Response::Error(error) => Failure::other(error),
+        _ => Failure::other("unexpected response from salusd"),
+    })
+}
+
+/// Ask for `registry`'s token on the terminal.
+fn read_token(registry: &Registry) -> Result<Zeroizing<String>> {
+    let terminal = File::open(TERMINAL)
+        .context("no terminal to ask for the token on; pass it to `cargo login`")?;
+    eprintln!("please paste the token for {} below", registry.name());
+    let mut token = Zeroizing::new(String::new());
+    let _read = BufReader::new(terminal).read_line(&mut token)?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::{Kind, Request};
+
+    #[test]
+    fn requests_of_every_kind_parse() -> Result<()> {
+        let get: Request = serde_json::from_str(
+            r#"{"v":1,"registry":{"index-url":"sparse+https://r/index/","name":"my-reg","headers":[]},
+                "kind":"get","operation":"publish","name":"sample","vers":"0.1.0","cksum":"abc","args":[]}"#,
+        )?;
+        assert!(matches!(get.kind, Kind::Get));
+        assert_eq!(get.registry.name(), "my-reg");
+
+        let login: Request = serde_json::from_str(
+            r#"{"v":1,"registry":{"index-url":"https://r/index"},"kind":"login","token":"t","login-url":null,"args":[]}"#,
+        )?;
+        let Kind::Login { token } = login.kind else {
+            bail!("expected login");
+        };
+        assert_eq!(token.as_deref(), Some("t"));
+        assert_eq!(login.registry.name(), "https://r/index");
+
+        let later: Request = serde_json::from_str(
+            r#"{"v":1,"registry":{"index-url":"https://r"},"kind":"refresh","args":[]}"#,
+        )?;
+        assert!(matches!(later.kind, Kind::Unknown));
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Copying secrets to the system clipboard with an automatic clear.
+//!
+//! On X11 and Wayland the clipboard contents are served by the process that set
+//! them, so they vanish as soon as that process exits. `read --clip` therefore
+//! hands the value to a detached copy of `salusc` running the hidden
+//! `clip-hold` subcommand. That process owns the clipboard for the timeout and
+//! then clears it, unless something else has been copied in the meantime.
+
+use std::{
+    env::current_exe,
+    io::{Read as _, Write as _, stdin},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result};
+use arboard::Clipboard;
+use zeroize::Zeroize as _;
+
+/// The name of the hidden subcommand that holds and then clears the clipboard.
+pub(crate) const HOLD_SUBCOMMAND: &str = "clip-hold";
+
+/// Seconds a copied secret stays on the clipboard when no timeout is configured.
+pub(crate) const DEFAULT_CLIP_TIMEOUT: u64 = 45;
+
+/// Place `value` on the clipboard and clear it after `seconds`.
+///
+/// The value is written to the stdin of a detached `salusc clip-hold` process
+/// rather than passed as an argument, so it never shows up in the process list.
+///
+/// # Errors
+///
+/// Returns an error if the current executable cannot be located or the helper
+/// process cannot be started or fed the value.
+pub(crate) fn copy(value: &str, seconds: u64) -> Result<()> {
+    let exe = current_exe().context("unable to locate the salusc executable")?;
+    let mut child = Command::new(exe)
+        .arg(HOLD_SUBCOMMAND)
+        .arg("--seconds")
+        .arg(seconds.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("unable to start the clipboard helper")?;
+    let mut pipe = child
+        .stdin
+        .take()
+        .context("unable to open the clipboard helper's stdin")?;
+    pipe.write_all(value.as_bytes())?;
+    // Closing stdin signals end-of-value; the helper outlives this process.
+    drop(pipe);
+    Ok(())
+}
+
+/// Read a value from stdin, own the clipboard with it for `seconds`, then clear
+/// the clipboard if it still holds that value.
+///
+/// This is the body of the hidden `clip-hold` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if stdin cannot be read or the clipboard is unavailable.
+pub(crate) fn hold(seconds: u64) -> Result<()> {
+    let mut value = String::new();
+    let _read = stdin().read_to_string(&mut value)?;
+    let result = hold_value(&value, seconds);
+    value.zeroize();
+    result
+}
+
+fn hold_value(value: &str, seconds: u64) -> Result<()> {
+    let mut clipboard = Clipboard::new().context("unable to access the clipboard")?;
+    let deadline = Instant::now()
+        .checked_add(Duration::from_secs(seconds))
+        .context("clipboard timeout is too large")?;
+    set_until(&mut clipboard, value, deadline)?;
+
+    // Someone else copying something in the meantime wins: only clear what we put
+    // there.
+    let mut current = clipboard.get_text().unwrap_or_default();
+    if current == value {
+        clipboard.clear()?;
+    }
+    current.zeroize();
+    Ok(())
+}
+
+/// Set the clipboard text and block until `deadline`.
+///
+/// On Linux this keeps serving the selection until the deadline (returning early
+/// if another application takes ownership) and asks clipboard managers not to
+/// record the secret in their history.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+fn set_until(clipboard: &mut Clipboard, value: &str, deadline: Instant) -> Result<()> {
+    use arboard::SetExtLinux as _;
+
+    clipboard
+        .set()
+        .exclude_from_history()
+        .wait_until(deadline)
+        .text(value)?;
+    Ok(())
+}
+
+/// Set the clipboard text and block until `deadline`.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+fn set_until(clipboard: &mut Clipboard, value: &str, deadline: Instant) -> Result<()> {
+    clipboard.set_text(value)?;
+    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    Ok(())
+}
@@ -9,15 +9,59 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use config::{Config, Environment, File, FileFormat, Source};
 use serde::{Deserialize, Serialize};
 
+use crate::{exec::NameTransform, output::OutputFormat};
+
 /// The application name, used as the env prefix, per-user directory, and file
 /// stem for the client's configuration.
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 
-/// The client configuration, layered (lowest to highest precedence) from a TOML
-/// file, `SALUSC_` environment variables, and explicitly-set CLI flags.
+/// The formats a config file can be written in
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Every format, in the order a default config file is looked for in
+    const ALL: [Self; 3] = [Self::Toml, Self::Yaml, Self::Json];
+
+    /// The extensions a file in this format is known by
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Toml => &["toml"],
+            Self::Yaml => &["yaml", "yml"],
+            Self::Json => &["json"],
+        }
+    }
+
+    /// The format `path`'s extension names, if it names one.
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extensions().contains(&extension.as_str()))
+    }
+}
+
+impl From<ConfigFormat> for FileFormat {
+    fn from(format: ConfigFormat) -> Self {
+        match format {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Yaml => FileFormat::Yaml,
+            ConfigFormat::Json => FileFormat::Json,
+        }
+    }
+}
+
+/// The client configuration, layered (lowest to highest precedence) from a
+/// TOML, YAML or JSON file, `SALUSC_` environment variables, and explicitly-set CLI flags.
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(default)]
 pub(crate) struct ConfigSalusc {
@@ -27,10 +71,48 @@ pub(crate) struct ConfigSalusc {
     /// Optional override for the `salus-agent` IPC socket path. Falls back to the
     /// shared `SALUS_AGENT_SOCKET` env var and then the platform default.
     agent_socket_path: Option<String>,
+    /// Optional override for where the key that encrypts connections to a
+    /// daemon socket in the shared temp directory is kept. Falls back to the
+    /// shared `SALUS_TRANSPORT_KEY` env var and then the daemon's default.
+    transport_key_path: Option<String>,
     /// Optional maximum bytes to read from stdin for the `store` subcommand.
     /// When `None`, the default of 65536 (64 KiB) is used. Can be overridden
     /// per-invocation with the `--max-value-bytes` flag.
     store_max_value_bytes: Option<usize>,
+    /// Optional number of seconds a value copied with `read --clip` stays on
+    /// the clipboard. When `None`, the default of 45 seconds is used. Can be
+    /// overridden per-invocation with the `--clip-timeout` flag.
+    clip_timeout: Option<u64>,
+    /// How `exec` turns key names into environment variable names: `upper`
+    /// (default), `lower`, or `preserve`. Overridden with `--transform`.
+    exec_transform: Option<NameTransform>,
+    /// Prepended to every variable name `exec` sets. Overridden with
+    /// `--env-prefix`.
+    exec_env_prefix: Option<String>,
+    /// Prepended, with a `/`, to the key names and prefixes commands are
+    /// given. Overridden with `--namespace`; an empty one turns it off.
+    namespace: Option<String>,
+    /// How results and errors are rendered: `plain` (default), `json`, or
+    /// `yaml`. Overridden per-invocation with `-o/--output`.
+    output: OutputFormat,
+    /// Optional number of seconds to wait for the daemon to accept a
+    /// connection. When `None`, the default of 5 seconds is used. Overridden
+    /// with `--connect-timeout`.
+    connect_timeout: Option<u64>,
+    /// Optional number of seconds to wait for the daemon to answer a request,
+    /// `0` to wait for as long as it takes. When `None`, the default of 120
+    /// seconds is used. Overridden with `--request-timeout`.
+    request_timeout: Option<u64>,
+    /// Optional number of times a connection that fails for a transient
+    /// reason is retried, with backoff. When `None`, the default of 3 is used.
+    /// Overridden with `--retries`.
+    retries: Option<u32>,
+    /// Start `salusd` when no daemon is listening on the socket. Set with
+    /// `--auto-start`.
+    auto_start: bool,
+    /// Ask the daemon to time each request, and print where the time went to
+    /// stderr. Set with `--timing`.
+    timing: bool,
 }
 
 impl ConfigSalusc {
@@ -42,30 +124,92 @@ impl ConfigSalusc {
         self.agent_socket_path.as_deref()
     }
 
+    pub(crate) fn transport_key_path(&self) -> Option<&str> {
+        self.transport_key_path.as_deref()
+    }
+
     pub(crate) fn store_max_value_bytes(&self) -> Option<usize> {
         self.store_max_value_bytes
     }
+
+    pub(crate) fn clip_timeout(&self) -> Option<u64> {
+        self.clip_timeout
+    }
+
+    pub(crate) fn exec_transform(&self) -> Option<NameTransform> {
+        self.exec_transform
+    }
+
+    pub(crate) fn exec_env_prefix(&self) -> Option<&str> {
+        self.exec_env_prefix.as_deref()
+    }
+
+    /// `key` in the configured namespace, or `key` itself without one.
+    pub(crate) fn in_namespace(&self, key: String) -> String {
+        match self
+            .namespace
+            .as_deref()
+            .map(|namespace| namespace.trim_end_matches('/'))
+            .filter(|namespace| !namespace.is_empty())
+        {
+            Some(namespace) => format!("{namespace}/{key}"),
+            None => key,
+        }
+    }
+
+    pub(crate) fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    pub(crate) fn connect_timeout(&self) -> Option<u64> {
+        self.connect_timeout
+    }
+
+    pub(crate) fn request_timeout(&self) -> Option<u64> {
+        self.request_timeout
+    }
+
+    pub(crate) fn retries(&self) -> Option<u32> {
+        self.retries
+    }
+
+    pub(crate) fn auto_start(&self) -> bool {
+        self.auto_start
+    }
+
+    pub(crate) fn timing(&self) -> bool {
+        self.timing
+    }
 }
 
 /// Load the client configuration.
 ///
 /// `config_absolute_path`, when `Some`, is an explicit config file path (from
-/// the `--config-path` flag) used instead of the per-user default.
+/// the `--config-path` flag) used instead of the per-user default. The file is
+/// read in `config_format` (from `--config-format`) when it is set, and
+/// otherwise in the format its extension names, or TOML.
 ///
 /// # Errors
 ///
 /// * Returns an error if no valid config directory can be found, or if the
 ///   configuration cannot be built or deserialized.
-pub(crate) fn load<S>(cli: &S, config_absolute_path: Option<&str>) -> Result<ConfigSalusc>
+pub(crate) fn load<S>(
+    cli: &S,
+    config_absolute_path: Option<&str>,
+    config_format: Option<ConfigFormat>,
+) -> Result<ConfigSalusc>
 where
     S: Source + Clone + Send + Sync + 'static,
 {
-    let config_file_path = config_file_path(config_absolute_path)?;
+    let config_file_path = config_file_path(config_absolute_path, config_format)?;
+    let format = config_format
+        .or_else(|| ConfigFormat::from_path(&config_file_path))
+        .unwrap_or_default();
     let config = Config::builder()
         // Lowest precedence first; the `config` crate is last-wins.
         .add_source(
             File::from(config_file_path)
-                .format(FileFormat::Toml)
+                .format(format.into())
                 .required(false),
         )
         .add_source(env_source(&APP_NAME.to_ascii_uppercase()))
@@ -89,34 +233,88 @@ fn env_source(prefix: &str) -> Environment {
         .try_parsing(true)
 }
 
-fn config_file_path(config_absolute_path: Option<&str>) -> Result<PathBuf> {
+fn config_file_path(
+    config_absolute_path: Option<&str>,
+    config_format: Option<ConfigFormat>,
+) -> Result<PathBuf> {
     config_absolute_path.map_or_else(
         || {
             let base = dirs2::config_dir().context("there is no valid config directory")?;
-            Ok(config_file_in(&base, APP_NAME))
+            Ok(config_file_in(&base, APP_NAME, config_format))
         },
         |path| Ok(PathBuf::from(path)),
     )
 }
 
-/// Compose the default config file path: `<base>/<app>/<app>.toml`.
-fn config_file_in(base: &Path, app: &str) -> PathBuf {
-    base.join(app).join(app).with_extension("toml")
+/// Compose the default config file path, `<base>/<app>/<app>.<ext>`: the
+/// first of `.toml`, `.yaml`, `.yml` and `.json` (only `format`'s, when it is
+/// set) that exists, or else the first of them.
+fn config_file_in(base: &Path, app: &str, format: Option<ConfigFormat>) -> PathBuf {
+    let stem = base.join(app).join(app);
+    let formats = format
+        .as_ref()
+        .map_or(ConfigFormat::ALL.as_slice(), std::slice::from_ref);
+    let candidates = formats
+        .iter()
+        .flat_map(|format| format.extensions())
+        .map(|extension| stem.with_extension(extension))
+        .collect::<Vec<_>>();
+    candidates
+        .iter()
+        .find(|candidate| candidate.exists())
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or_else(|| stem.with_extension("toml"))
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use std::{
+        env, fs,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    };
 
     use anyhow::Result;
     use config::{Config, Map};
 
-    use super::{ConfigSalusc, config_file_in, env_source};
+    use super::{ConfigFormat, ConfigSalusc, config_file_in, env_source, load};
+    use crate::{exec::NameTransform, output::OutputFormat};
 
     #[test]
     fn config_file_in_composes_app_dir_and_extension() {
-        let path = config_file_in(Path::new("/base"), "salusc");
+        let path = config_file_in(Path::new("/base"), "salusc", None);
         assert_eq!(path, Path::new("/base/salusc/salusc.toml"));
+        let path = config_file_in(Path::new("/base"), "salusc", Some(ConfigFormat::Json));
+        assert_eq!(path, Path::new("/base/salusc/salusc.json"));
+    }
+
+    #[test]
+    fn config_files_are_read_in_the_format_their_extension_names() -> Result<()> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let dir = env::temp_dir().join(format!("salusc-config-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("salusc.yml"), "retries: 7\noutput: json\n")?;
+        fs::write(dir.join("salusc.json"), r#"{"retries": 8}"#)?;
+        fs::write(dir.join("salusc.conf"), "retries: 9\n")?;
+        let no_flags = env_source("SALUSC_TEST_NO_FLAGS").source(Some(Map::new()));
+        let load_from = |name: &str, format| {
+            let path = dir.join(name);
+            load(&no_flags, path.to_str(), format)
+        };
+        let cfg = load_from("salusc.yml", None)?;
+        assert_eq!(cfg.retries(), Some(7));
+        assert_eq!(cfg.output(), OutputFormat::Json);
+        assert_eq!(load_from("salusc.json", None)?.retries(), Some(8));
+        assert_eq!(
+            load_from("salusc.conf", Some(ConfigFormat::Yaml))?.retries(),
+            Some(9)
+        );
+        assert!(load_from("salusc.conf", None).is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
     }
 
     #[test]
@@ -139,6 +337,71 @@ mod test {
         let config = Config::builder().build()?;
         let cfg: ConfigSalusc = config.try_deserialize()?;
         assert!(cfg.socket_path().is_none());
+        assert_eq!(cfg.output(), OutputFormat::Plain);
+        Ok(())
+    }
+
+    #[test]
+    fn output_from_env() -> Result<()> {
+        let mut env = Map::new();
+        let _old = env.insert("SALUSC_OUTPUT".to_string(), "yaml".to_string());
+        let config = Config::builder()
+            .add_source(env_source("SALUSC").source(Some(env)))
+            .build()?;
+        let cfg: ConfigSalusc = config.try_deserialize()?;
+        assert_eq!(cfg.output(), OutputFormat::Yaml);
+        Ok(())
+    }
+
+    #[test]
+    fn connection_settings_from_env() -> Result<()> {
+        let mut env = Map::new();
+        let _old = env.insert("SALUSC_REQUEST_TIMEOUT".to_string(), "0".to_string());
+        let _old = env.insert("SALUSC_RETRIES".to_string(), "5".to_string());
+        let _old = env.insert("SALUSC_AUTO_START".to_string(), "true".to_string());
+        let _old = env.insert("SALUSC_TIMING".to_string(), "true".to_string());
+        let config = Config::builder()
+            .add_source(env_source("SALUSC").source(Some(env)))
+            .build()?;
+        let cfg: ConfigSalusc = config.try_deserialize()?;
+        assert_eq!(cfg.connect_timeout(), None);
+        assert_eq!(cfg.request_timeout(), Some(0));
+        assert_eq!(cfg.retries(), Some(5));
+        assert!(cfg.auto_start());
+        assert!(cfg.timing());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_are_put_in_the_namespace() -> Result<()> {
+        let in_namespace = |namespace: &str| -> Result<String> {
+            let mut env = Map::new();
+            let _old = env.insert("SALUSC_NAMESPACE".to_string(), namespace.to_string());
+            let config = Config::builder()
+                .add_source(env_source("SALUSC").source(Some(env)))
+                .build()?;
+            let cfg: ConfigSalusc = config.try_deserialize()?;
+            Ok(cfg.in_namespace("db/password".to_string()))
+        };
+        assert_eq!(in_namespace("prod")?, "prod/db/password");
+        assert_eq!(in_namespace("prod/")?, "prod/db/password");
+        assert_eq!(in_namespace("")?, "db/password");
+        assert_eq!(
+            ConfigSalusc::default().in_namespace("db/password".to_string()),
+            "db/password"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn exec_transform_from_env() -> Result<()> {
+        let mut env = Map::new();
+        let _old = env.insert("SALUSC_EXEC_TRANSFORM".to_string(), "preserve".to_string());
+        let config = Config::builder()
+            .add_source(env_source("SALUSC").source(Some(env)))
+            .build()?;
+        let cfg: ConfigSalusc = config.try_deserialize()?;
+        assert_eq!(cfg.exec_transform(), Some(NameTransform::Preserve));
         Ok(())
     }
 }
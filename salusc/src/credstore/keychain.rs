@@ -0,0 +1,98 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The macOS Keychain's generic passwords, through the Security framework.
+//!
+//! The Keychain may ask the user to allow each password `salusc` reads that
+//! another application saved.
+
+use anyhow::Result;
+use security_framework::{
+    item::{ItemClass, ItemSearchOptions, Limit},
+    passwords::{PasswordOptions, generic_password, set_generic_password},
+};
+use zeroize::Zeroizing;
+
+use super::Credential;
+
+/// The credential store, as the user knows it.
+pub(crate) const NAME: &str = "the Keychain";
+
+/// `errSecItemNotFound`: the search matched nothing.
+const ITEM_NOT_FOUND: i32 = -25_300;
+
+/// Every generic password, or those of `service`.
+///
+/// # Errors
+///
+/// Returns an error if the Keychain cannot be searched, or refuses a password.
+pub(crate) fn read(service: Option<&str>) -> Result<Vec<Credential>> {
+    let mut search = ItemSearchOptions::new();
+    let _ = search
+        .class(ItemClass::generic_password())
+        .load_attributes(true)
+        .limit(Limit::All);
+    if let Some(service) = service {
+        let _ = search.service(service);
+    }
+    let found = match search.search() {
+        Ok(found) => found,
+        Err(e) if e.code() == ITEM_NOT_FOUND => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut credentials = vec![];
+    for item in found {
+        let Some(attributes) = item.simplify_dict() else {
+            continue;
+        };
+        let service = attributes.get("svce").cloned().unwrap_or_default();
+        let account = attributes.get("acct").cloned().unwrap_or_default();
+        let secret = Zeroizing::new(generic_password(PasswordOptions::new_generic_password(
+            &service, &account,
+        ))?);
+        credentials.push(Credential {
+            service,
+            account,
+            secret,
+        });
+    }
+    Ok(credentials)
+}
+
+/// Whether the Keychain already holds `credential`.
+///
+/// # Errors
+///
+/// Returns an error if the Keychain cannot be searched.
+pub(crate) fn exists(credential: &Credential) -> Result<bool> {
+    let mut search = ItemSearchOptions::new();
+    let _ = search
+        .class(ItemClass::generic_password())
+        .service(&credential.service)
+        .account(&credential.account)
+        .load_attributes(true)
+        .limit(1);
+    match search.search() {
+        Ok(found) => Ok(!found.is_empty()),
+        Err(e) if e.code() == ITEM_NOT_FOUND => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save `credential`, replacing the password it had.
+///
+/// # Errors
+///
+/// Returns an error if the Keychain refuses the password.
+pub(crate) fn write(credential: &Credential) -> Result<()> {
+    Ok(set_generic_password(
+        &credential.service,
+        &credential.account,
+        &credential.secret,
+    )?)
+}
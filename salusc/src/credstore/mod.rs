@@ -0,0 +1,110 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The operating system's own credential store: the macOS Keychain or the
+//! Windows Credential Manager.
+//!
+//! A credential is a secret filed under a service (a Windows target name)
+//! and an account (a Windows user name). It is kept in the store as
+//! `<service>/<account>`, or as `<service>` alone when it has no account, and
+//! a key goes back the same way: everything before its last `/` is the
+//! service. Only generic passwords are read and written.
+
+use std::collections::BTreeMap;
+
+use zeroize::Zeroizing;
+
+#[cfg(target_os = "macos")]
+pub(crate) use self::keychain::{NAME, exists, read, write};
+#[cfg(windows)]
+pub(crate) use self::wincred::{NAME, check_distinct, exists, read, write};
+
+#[cfg(target_os = "macos")]
+mod keychain;
+#[cfg(windows)]
+mod wincred;
+
+/// A secret in the credential store.
+#[derive(Debug)]
+pub(crate) struct Credential {
+    service: String,
+    account: String,
+    secret: Zeroizing<Vec<u8>>,
+}
+
+impl Credential {
+    /// The credential stored under `key`, holding `secret`.
+    pub(crate) fn for_key(key: &str, secret: Vec<u8>) -> Self {
+        let (service, account) = match key.rsplit_once('/') {
+            Some((service, account)) if !service.is_empty() => (service, account),
+            _ => (key, ""),
+        };
+        Self {
+            service: service.to_string(),
+            account: account.to_string(),
+            secret: Zeroizing::new(secret),
+        }
+    }
+
+    /// The key this credential is stored under.
+    pub(crate) fn key(&self) -> String {
+        if self.account.is_empty() {
+            self.service.clone()
+        } else {
+            format!("{}/{}", self.service, self.account)
+        }
+    }
+}
+
+/// The text credentials of `credentials` by key, and the keys of those whose
+/// secret is not UTF-8 and so cannot be stored as a value.
+pub(crate) fn entries(credentials: Vec<Credential>) -> (BTreeMap<String, String>, Vec<String>) {
+    let mut entries = BTreeMap::new();
+    let mut binary = vec![];
+    for credential in credentials {
+        let key = credential.key();
+        match std::str::from_utf8(&credential.secret) {
+            Ok(secret) => {
+                let _old = entries.insert(key, secret.to_string());
+            }
+            Err(_) => binary.push(key),
+        }
+    }
+    (entries, binary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Credential, entries};
+
+    #[test]
+    fn keys_split_at_the_last_slash() {
+        for (key, service, account) in [
+            ("git:https://github.com/me", "git:https://github.com", "me"),
+            ("wifi", "wifi", ""),
+            ("/odd", "/odd", ""),
+        ] {
+            let credential = Credential::for_key(key, vec![]);
+            assert_eq!(
+                (credential.service.as_str(), credential.account.as_str()),
+                (service, account)
+            );
+            assert_eq!(credential.key(), key);
+        }
+    }
+
+    #[test]
+    fn binary_secrets_are_left_out() {
+        let (text, binary) = entries(vec![
+            Credential::for_key("a/me", b"pw".to_vec()),
+            Credential::for_key("b/me", vec![0xff, 0xfe]),
+        ]);
+        assert_eq!(text.get("a/me").map(String::as_str), Some("pw"));
+        assert_eq!(binary, ["b/me"]);
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The Windows Credential Manager's generic credentials, through the Win32
+//! credential API.
+//!
+//! A credential's blob holding NUL bytes is read as UTF-16 when it decodes as
+//! that, which is what `cmdkey` and most Windows tools write; any other blob
+//! is read as it is. A value is written back as its UTF-8 bytes.
+
+// The credential API is only reachable through FFI.
+#![allow(unsafe_code)]
+
+use std::{
+    collections::BTreeMap,
+    io,
+    iter::once,
+    ptr::{null, null_mut},
+    slice,
+};
+
+use anyhow::{Result, bail};
+use windows_sys::{
+    Win32::{
+        Foundation::ERROR_NOT_FOUND,
+        Security::Credentials::{
+            CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC, CREDENTIALW, CredEnumerateW, CredFree,
+            CredReadW, CredWriteW,
+        },
+    },
+    core::PWSTR,
+};
+use zeroize::Zeroizing;
+
+use super::Credential;
+
+/// The credential store, as the user knows it.
+pub(crate) const NAME: &str = "Credential Manager";
+
+/// Every generic credential, or those whose target name matches `filter`
+/// (which may end in `*`).
+///
+/// # Errors
+///
+/// Returns an error if the credentials cannot be listed.
+pub(crate) fn read(filter: Option<&str>) -> Result<Vec<Credential>> {
+    let filter = filter.map(wide);
+    let mut count = 0u32;
+    let mut list: *mut *mut CREDENTIALW = null_mut();
+    // SAFETY: the filter is a NUL-terminated UTF-16 string that outlives the
+    // call, and `count` and `list` are valid places for its results.
+    let listed = unsafe {
+        CredEnumerateW(
+            filter.as_ref().map_or(null(), Vec::as_ptr),
+            0,
+            &raw mut count,
+            &raw mut list,
+        )
+    };
+    if listed == 0 {
+        return match last_error() {
+            e if not_found(&e) => Ok(vec![]),
+            e => Err(e.into()),
+        };
+    }
+    // SAFETY: on success `list` holds `count` pointers to credentials, which
+    // stay valid until `CredFree` below; nothing in between can return early.
+    let credentials = unsafe {
+        slice::from_raw_parts(list, usize::try_from(count).unwrap_or_default())
+            .iter()
+            .filter_map(|credential| credential.as_ref())
+            .filter(|credential| credential.Type == CRED_TYPE_GENERIC)
+            .map(|credential| Credential {
+                service: text(credential.TargetName),
+                account: text(credential.UserName),
+                secret: Zeroizing::new(decode(blob(credential))),
+            })
+            .collect()
+    };
+    // SAFETY: `list` was allocated by `CredEnumerateW` and is freed only here.
+    unsafe { CredFree(list.cast_const().cast()) };
+    Ok(credentials)
+}
+
+/// Whether the Credential Manager already holds a generic credential under
+/// the target name of `credential`.
+///
+/// # Errors
+///
+/// Returns an error if the credential cannot be looked up.
+pub(crate) fn exists(credential: &Credential) -> Result<bool> {
+    let target = wide(&credential.service);
+    let mut found: *mut CREDENTIALW = null_mut();
+    // SAFETY: `target` is NUL-terminated and outlives the call, and `found`
+    // is a valid place for its result.
+    let read = unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &raw mut found) };
+    if read == 0 {
+        return match last_error() {
+            e if not_found(&e) => Ok(false),
+            e => Err(e.into()),
+        };
+    }
+    // SAFETY: `found` was allocated by `CredReadW` and is freed only here.
+    unsafe { CredFree(found.cast_const().cast()) };
+    Ok(true)
+}
+
+/// Save `credential`, replacing the one under its target name.
+///
+/// # Errors
+///
+/// Returns an error if the Credential Manager refuses it, as it does a secret
+/// over 2560 bytes.
+pub(crate) fn write(credential: &Credential) -> Result<()> {
+    let mut target = wide(&credential.service);
+    let mut account = wide(&credential.account);
+    let saved = CREDENTIALW {
+        Type: CRED_TYPE_GENERIC,
+        TargetName: target.as_mut_ptr(),
+        UserName: if credential.account.is_empty() {
+            null_mut()
+        } else {
+            account.as_mut_ptr()
+        },
+        CredentialBlobSize: u32::try_from(credential.secret.len())?,
+        CredentialBlob: credential.secret.as_ptr().cast_mut(),
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        ..CREDENTIALW::default()
+    };
+    // SAFETY: every pointer in `saved` points into a buffer that outlives the
+    // call, and `CredWriteW` only reads through them.
+    if unsafe { CredWriteW(&raw const saved, 0) } == 0 {
+        return Err(last_error().into());
+    }
+    Ok(())
+}
+
+/// Check that no two of `credentials` would land on one credential: the
+/// Credential Manager files a credential under its target name alone, so
+/// `svc/a` and `svc/b` would overwrite each other.
+///
+/// # Errors
+///
+/// Returns an error naming the first two keys that share a target name.
+pub(crate) fn check_distinct(credentials: &[Credential]) -> Result<()> {
+    let mut seen = BTreeMap::new();
+    for credential in credentials {
+        if let Some(other) = seen.insert(credential.service.to_lowercase(), credential.key()) {
+            bail!(
+                "'{other}' and '{}' would both be saved as the credential '{}'",
+                credential.key(),
+                credential.service
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The bytes of `credential`'s blob.
+///
+/// # Safety
+///
+/// `credential` must be one the credential API returned, and not yet freed.
+unsafe fn blob(credential: &CREDENTIALW) -> Vec<u8> {
+    if credential.CredentialBlob.is_null() {
+        return vec![];
+    }
+    let len = usize::try_from(credential.CredentialBlobSize).unwrap_or_default();
+    // SAFETY: the API sizes the blob by `CredentialBlobSize`.
+    unsafe { slice::from_raw_parts(credential.CredentialBlob, len) }.to_vec()
+}
+
+/// A NUL-terminated UTF-16 string as text, or nothing for a null pointer.
+///
+/// # Safety
+///
+/// `text` must be null or point to a NUL-terminated UTF-16 string.
+unsafe fn text(text: PWSTR) -> String {
+    if text.is_null() {
+        return String::new();
+    }
+    let mut len = 0usize;
+    // SAFETY: the string is NUL-terminated, so every unit up to the NUL is
+    // readable.
+    while unsafe { *text.add(len) } != 0 {
+        len = len.saturating_add(1);
+    }
+    // SAFETY: the `len` units before the NUL were just read.
+    String::from_utf16_lossy(unsafe { slice::from_raw_parts(text, len) })
+}
+
+/// A blob as UTF-8 when it is UTF-16 text, or left as it is: UTF-16 text of
+/// ASCII holds a NUL in every other byte, where UTF-8 text holds none.
+fn decode(blob: Vec<u8>) -> Vec<u8> {
+    if !blob.contains(&0) || !blob.len().is_multiple_of(2) {
+        return blob;
+    }
+    let blob = Zeroizing::new(blob);
+    let units = Zeroizing::new(
+        blob.chunks_exact(2)
+            .filter_map(|pair| <[u8; 2]>::try_from(pair).ok())
+            .map(u16::from_le_bytes)
+            .collect::<Vec<_>>(),
+    );
+    String::from_utf16(&units).map_or_else(|_| blob.to_vec(), String::into_bytes)
+}
+
+/// `text` as a NUL-terminated UTF-16 string.
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(once(0)).collect()
+}
+
+fn last_error() -> io::Error {
+    io::Error::last_os_error()
+}
+
+fn not_found(e: &io::Error) -> bool {
+    e.raw_os_error().and_then(|code| u32::try_from(code).ok()) == Some(ERROR_NOT_FOUND)
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Editing a decrypted value in the user's `$EDITOR` for the `edit` subcommand.
+//!
+//! The plaintext only ever touches disk in a [`SecureTempFile`]: created
+//! exclusively with mode `0600`, on a tmpfs (`/dev/shm`) where one is available,
+//! and overwritten with zeros before it is removed.
+
+use std::{
+    env::{temp_dir, var},
+    fs::{OpenOptions, read, remove_file},
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use anyhow::{Context as _, Result, bail};
+use zeroize::Zeroize as _;
+
+/// The editor used when neither `$VISUAL` nor `$EDITOR` is set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// What happened to a value opened in the editor.
+#[derive(Debug)]
+pub(crate) enum EditOutcome {
+    /// The editor saved different content.
+    Changed(String),
+    /// The editor exited successfully but the content is the same.
+    Unchanged,
+    /// The editor exited non-zero; the edit is abandoned.
+    EditorFailed(ExitStatus),
+}
+
+/// The editor command line: `$VISUAL`, then `$EDITOR`, then `vi`, split on
+/// whitespace so values such as `code --wait` work.
+pub(crate) fn editor_command() -> Vec<String> {
+    let editor = var("VISUAL")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or_else(|| var("EDITOR").ok().filter(|v| !v.trim().is_empty()))
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    editor.split_whitespace().map(String::from).collect()
+}
+
+/// Open `initial` in `editor` and report how it changed.
+///
+/// One trailing newline (which most editors add on save) is stripped from the
+/// result, mirroring how `store` treats a value piped on stdin.
+///
+/// # Errors
+///
+/// Returns an error if the editor command is empty or cannot be started, the
+/// temporary file cannot be written or read back, or the result is not UTF-8.
+pub(crate) fn edit_with(editor: &[String], initial: &str) -> Result<EditOutcome> {
+    let Some((program, args)) = editor.split_first() else {
+        bail!("no editor is configured; set $EDITOR");
+    };
+    let file = SecureTempFile::create(initial.as_bytes())?;
+    let status = Command::new(program)
+        .args(args)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("unable to start editor '{program}'"))?;
+    if !status.success() {
+        return Ok(EditOutcome::EditorFailed(status));
+    }
+
+    let mut edited = match String::from_utf8(file.read()?) {
+        Ok(edited) => edited,
+        Err(e) => {
+            e.into_bytes().zeroize();
+            bail!("the edited value is not valid UTF-8");
+        }
+    };
+    if edited.ends_with('\n') {
+        let _ = edited.pop();
+        if edited.ends_with('\r') {
+            let _ = edited.pop();
+        }
+    }
+    if edited == initial {
+        edited.zeroize();
+        Ok(EditOutcome::Unchanged)
+    } else {
+        Ok(EditOutcome::Changed(edited))
+    }
+}
+
+/// A private temporary file that is shredded and removed on drop.
+#[derive(Debug)]
+pub(crate) struct SecureTempFile {
+    path: PathBuf,
+}
+
+impl SecureTempFile {
+    /// Exclusively create a new `0600` file holding `contents`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written.
+    pub(crate) fn create(contents: &[u8]) -> Result<Self> {
+        let path = secure_dir().join(format!(
+            "salusc-edit-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let mut options = OpenOptions::new();
+        let _ = options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt as _;
+            let _ = options.mode(0o600);
+        }
+        let mut file = options
+            .open(&path)
+            .with_context(|| format!("unable to create {}", path.display()))?;
+        // Construct the guard first so a failed write still cleans up.
+        let temp = Self { path };
+        file.write_all(contents)?;
+        file.sync_all()?;
+        Ok(temp)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the file's current contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub(crate) fn read(&self) -> Result<Vec<u8>> {
+        read(&self.path).with_context(|| format!("unable to read {}", self.path.display()))
+    }
+}
+
+impl Drop for SecureTempFile {
+    fn drop(&mut self) {
+        // Best-effort shred: overwrite the plaintext with zeros before unlinking.
+        if let Ok(len) = self.path.metadata().map(|m| m.len())
+            && let Ok(mut file) = OpenOptions::new().write(true).open(&self.path)
+        {
+            let zeros = vec![0u8; usize::try_from(len).unwrap_or(0)];
+            drop(file.write_all(&zeros));
+            drop(file.sync_all());
+        }
+        drop(remove_file(&self.path));
+    }
+}
+
+/// Prefer a RAM-backed tmpfs so the plaintext never reaches persistent storage.
+fn secure_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if cfg!(target_os = "linux") && shm.is_dir() {
+        shm.to_path_buf()
+    } else {
+        temp_dir()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::{EditOutcome, SecureTempFile, edit_with};
+
+    fn cmd(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn temp_file_is_private_and_removed_on_drop() -> Result<()> {
+        let file = SecureTempFile::create(b"secret")?;
+        let path = file.path().to_path_buf();
+        assert_eq!(file.read()?, b"secret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+        }
+        drop(file);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn successful_editor_without_changes_is_unchanged() -> Result<()> {
+        assert!(matches!(
+            edit_with(&cmd(&["true"]), "value")?,
+            EditOutcome::Unchanged
+        ));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_editor_is_reported() -> Result<()> {
+        assert!(matches!(
+            edit_with(&cmd(&["false"]), "value")?,
+            EditOutcome::EditorFailed(_)
+        ));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_changes_drop_one_trailing_newline() -> Result<()> {
+        let editor = cmd(&["sh", "-c", "printf 'new value\\n' > \"$0\""]);
+        match edit_with(&editor, "old")? {
+            EditOutcome::Changed(value) => assert_eq!(value, "new value"),
+            other => bail!("expected a change, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn empty_editor_command_is_an_error() {
+        assert!(edit_with(&[], "value").is_err());
+    }
+}
@@ -9,12 +9,23 @@
 use clap::error::ErrorKind;
 use tracing::error;
 
+#[derive(thiserror::Error, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Error {
+    /// The failure has already been reported (e.g. as a structured error
+    /// object), so exit with this status without printing it again.
+    #[error("Exiting with status {0}")]
+    Exit(i32),
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub(crate) fn clap_or_error(err: anyhow::Error) -> i32 {
     let disp_err = || {
         eprint!("{err:?}");
         1
     };
+    if let Some(Error::Exit(code)) = err.downcast_ref::<Error>() {
+        return *code;
+    }
     match err.downcast_ref::<clap::Error>() {
         Some(e) => match e.kind() {
             ErrorKind::DisplayHelp => {
@@ -53,6 +64,7 @@ pub(crate) fn success((): ()) -> i32 {
 #[cfg(test)]
 mod test {
     use super::{clap_or_error, success};
+    use crate::error::Error as ClientError;
     use anyhow::{Error, anyhow};
     use clap::{
         Command,
@@ -69,6 +81,11 @@ mod test {
         assert_eq!(1, clap_or_error(anyhow!("test")));
     }
 
+    #[test]
+    fn clap_or_error_honors_exit_status() {
+        assert_eq!(2, clap_or_error(Error::new(ClientError::Exit(2))));
+    }
+
     #[test]
     fn clap_or_error_is_help() {
         let mut cmd = Command::new(env!("CARGO_PKG_NAME"));
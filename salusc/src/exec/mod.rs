@@ -0,0 +1,220 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Running a command with secrets injected as environment variables, for the
+//! `exec` subcommand.
+//!
+//! The secrets only ever live in this process's memory and the child's
+//! environment; nothing is written to disk.
+
+use std::{collections::BTreeMap, process::Command};
+
+use anyhow::{Context as _, Result, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize as _;
+
+/// How key names are turned into environment variable names.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NameTransform {
+    /// `db/password` becomes `DB_PASSWORD` (the default)
+    #[default]
+    Upper,
+    /// `db/Password` becomes `db_password`
+    Lower,
+    /// `db/Password` becomes `db_Password`
+    Preserve,
+}
+
+/// The mapping from key names (with the prefix already stripped) to
+/// environment variable names.
+///
+/// After the case transform, every character other than an ASCII letter,
+/// digit, or `_` becomes `_`, and `env_prefix` is prepended.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EnvNames {
+    transform: NameTransform,
+    env_prefix: String,
+}
+
+impl EnvNames {
+    pub(crate) fn new(transform: NameTransform, env_prefix: String) -> Self {
+        Self {
+            transform,
+            env_prefix,
+        }
+    }
+
+    /// The environment variable name for `key`.
+    pub(crate) fn name(&self, key: &str) -> String {
+        let cased = match self.transform {
+            NameTransform::Upper => key.to_uppercase(),
+            NameTransform::Lower => key.to_lowercase(),
+            NameTransform::Preserve => key.to_string(),
+        };
+        let mut name = self.env_prefix.clone();
+        name.extend(cased.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        }));
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name.insert(0, '_');
+        }
+        name
+    }
+
+    /// Map every entry to its environment variable name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error (after scrubbing the values) if a name comes out empty
+    /// or two keys map to the same name.
+    pub(crate) fn map(&self, values: BTreeMap<String, String>) -> Result<Vec<(String, String)>> {
+        let mut env: BTreeMap<String, (String, String)> = BTreeMap::new();
+        let mut failure = None;
+        for (key, mut value) in values {
+            let name = self.name(&key);
+            if failure.is_some() {
+                value.zeroize();
+            } else if name.is_empty() {
+                value.zeroize();
+                failure = Some(format!("key '{key}' maps to an empty variable name"));
+            } else if let Some((other, _)) = env.get(&name) {
+                failure = Some(format!(
+                    "keys '{other}' and '{key}' both map to the variable {name}"
+                ));
+                value.zeroize();
+            } else {
+                let _old = env.insert(name, (key, value));
+            }
+        }
+        if let Some(failure) = failure {
+            for (_, value) in env.values_mut() {
+                value.zeroize();
+            }
+            bail!(failure);
+        }
+        Ok(env
+            .into_iter()
+            .map(|(name, (_, value))| (name, value))
+            .collect())
+    }
+}
+
+/// Replace this process with `command`, adding `env` to its environment.
+///
+/// # Errors
+///
+/// Returns an error if `command` is empty or cannot be executed.
+#[cfg(unix)]
+pub(crate) fn run(command: &[String], mut env: Vec<(String, String)>) -> Result<()> {
+    use std::os::unix::process::CommandExt as _;
+
+    let mut child = build(command, &env)?;
+    // `exec` only returns on failure.
+    let error = child.exec();
+    for (_, value) in &mut env {
+        value.zeroize();
+    }
+    Err(error).with_context(|| format!("unable to run '{}'", command.join(" ")))
+}
+
+/// Run `command` with `env` added to its environment and exit with its status.
+///
+/// # Errors
+///
+/// Returns an error if `command` is empty or cannot be started, and
+/// [`Error::Exit`](crate::error::Error::Exit) with the command's exit code when
+/// it fails.
+#[cfg(not(unix))]
+pub(crate) fn run(command: &[String], mut env: Vec<(String, String)>) -> Result<()> {
+    let mut child = build(command, &env)?;
+    let status = child.status();
+    for (_, value) in &mut env {
+        value.zeroize();
+    }
+    let status = status.with_context(|| format!("unable to run '{}'", command.join(" ")))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(crate::error::Error::Exit(status.code().unwrap_or(1)).into())
+    }
+}
+
+fn build(command: &[String], env: &[(String, String)]) -> Result<Command> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("no command given; usage: salusc exec [OPTIONS] -- COMMAND [ARGS]...");
+    };
+    let mut child = Command::new(program);
+    let _ = child
+        .args(args)
+        .envs(env.iter().map(|(name, value)| (name, value)));
+    Ok(child)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+
+    use super::{EnvNames, NameTransform, run};
+
+    fn names(transform: NameTransform, env_prefix: &str) -> EnvNames {
+        EnvNames::new(transform, env_prefix.to_string())
+    }
+
+    #[test]
+    fn key_names_become_valid_variable_names() {
+        let upper = names(NameTransform::Upper, "");
+        assert_eq!(upper.name("db/password"), "DB_PASSWORD");
+        assert_eq!(upper.name("api-key.v2"), "API_KEY_V2");
+        assert_eq!(upper.name("1st"), "_1ST");
+        assert_eq!(names(NameTransform::Lower, "").name("Db/Pass"), "db_pass");
+        assert_eq!(
+            names(NameTransform::Preserve, "APP_").name("Db/Pass"),
+            "APP_Db_Pass"
+        );
+    }
+
+    #[test]
+    fn colliding_names_are_rejected() {
+        let values = BTreeMap::from([
+            ("db-pass".to_string(), "a".to_string()),
+            ("db_pass".to_string(), "b".to_string()),
+        ]);
+        let err = names(NameTransform::Upper, "").map(values).err();
+        assert!(err.is_some_and(|e| e.to_string().contains("DB_PASS")));
+    }
+
+    #[test]
+    fn mapped_variables_are_sorted_by_name() -> Result<()> {
+        let values = BTreeMap::from([
+            ("b".to_string(), "2".to_string()),
+            ("a".to_string(), "1".to_string()),
+        ]);
+        let env = names(NameTransform::Upper, "").map(values)?;
+        assert_eq!(
+            env,
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn empty_command_is_an_error() {
+        assert!(run(&[], vec![]).is_err());
+    }
+}
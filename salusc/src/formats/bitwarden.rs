@@ -0,0 +1,225 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Bitwarden's unencrypted exports, JSON or CSV.
+//!
+//! A login's `username`, `password`, `totp` and `url` (then `url-2`, ...), a
+//! card's, identity's or SSH key's own fields (`cardholderName` becomes
+//! `cardholder-name`), the `notes`, and every custom field become fields of
+//! the item. An item sits in its folder, or in an organization export, in its
+//! first collection.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+use super::{csv, items::Item};
+
+/// The items of a Bitwarden export, told apart from a CSV one by its leading
+/// `{`.
+///
+/// # Errors
+///
+/// Returns an error if the export is encrypted or malformed.
+pub(super) fn items(text: &str) -> Result<Vec<Item>> {
+    if text.trim_start().starts_with('{') {
+        json_items(&serde_json::from_str(text)?)
+    } else {
+        csv_items(text)
+    }
+}
+
+fn json_items(export: &Value) -> Result<Vec<Item>> {
+    if export.get("encrypted").and_then(Value::as_bool) == Some(true) {
+        bail!("the Bitwarden export is encrypted; export it again as unencrypted JSON");
+    }
+    let Some(listed) = export.get("items").and_then(Value::as_array) else {
+        bail!("a Bitwarden JSON export lists its items under \"items\"");
+    };
+    let folders = names(export.get("folders"));
+    let collections = names(export.get("collections"));
+    let mut items = vec![];
+    for listed in listed {
+        let folder = listed
+            .get("folderId")
+            .and_then(Value::as_str)
+            .and_then(|id| folders.get(id))
+            .or_else(|| {
+                listed
+                    .get("collectionIds")
+                    .and_then(Value::as_array)
+                    .and_then(|ids| ids.first())
+                    .and_then(Value::as_str)
+                    .and_then(|id| collections.get(id))
+            });
+        let mut item = Item {
+            folder: folder.cloned(),
+            name: text_of(listed, "name").to_string(),
+            fields: vec![],
+        };
+        if let Some(login) = listed.get("login") {
+            for field in ["username", "password", "totp"] {
+                item.field(field, text_of(login, field));
+            }
+            for uri in login
+                .get("uris")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                item.field("url", text_of(uri, "uri"));
+            }
+        }
+        for kind in ["card", "identity", "sshKey"] {
+            if let Some(Value::Object(fields)) = listed.get(kind) {
+                for (name, value) in fields {
+                    item.field(&words(name), value.as_str().unwrap_or_default());
+                }
+            }
+        }
+        item.field("notes", text_of(listed, "notes"));
+        for custom in listed
+            .get("fields")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            item.field(text_of(custom, "name"), text_of(custom, "value"));
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn csv_items(text: &str) -> Result<Vec<Item>> {
+    let mut items = vec![];
+    for record in csv::records(text)? {
+        let column = |name: &str| record.get(name).map_or("", String::as_str);
+        let folder = Some(column("folder"))
+            .filter(|folder| !folder.is_empty())
+            .or_else(|| column("collections").split(',').next())
+            .filter(|folder| !folder.is_empty());
+        let mut item = Item {
+            folder: folder.map(String::from),
+            name: column("name").to_string(),
+            fields: vec![],
+        };
+        item.field("username", column("login_username"));
+        item.field("password", column("login_password"));
+        item.field("totp", column("login_totp"));
+        for uri in column("login_uri").split(',') {
+            item.field("url", uri.trim());
+        }
+        item.field("notes", column("notes"));
+        for line in column("fields").lines() {
+            if let Some((name, value)) = line.split_once(": ") {
+                item.field(name, value);
+            }
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Each `{"id": ..., "name": ...}` of `list` as id to name.
+fn names(list: Option<&Value>) -> BTreeMap<String, String> {
+    list.and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.get("id")?.as_str()?;
+            Some((id.to_string(), text_of(entry, "name").to_string()))
+        })
+        .collect()
+}
+
+/// The string `name` of `value`, or nothing.
+fn text_of<'a>(value: &'a Value, name: &str) -> &'a str {
+    value.get(name).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// A camel case name as words joined by `-`.
+fn words(name: &str) -> String {
+    let mut words = String::new();
+    for c in name.chars() {
+        if c.is_uppercase() && !words.is_empty() {
+            words.push('-');
+        }
+        words.extend(c.to_lowercase());
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+
+    use super::items;
+    use crate::formats::items::{ItemRules, entries};
+
+    #[test]
+    fn json_exports_map_logins_cards_and_custom_fields() -> Result<()> {
+        let export = serde_json::json!({
+            "encrypted": false,
+            "folders": [{"id": "f1", "name": "Work/Servers"}],
+            "items": [
+                {
+                    "type": 1, "name": "Prod DB", "folderId": "f1", "notes": null,
+                    "login": {
+                        "username": "admin", "password": "hunter2", "totp": null,
+                        "uris": [{"uri": "https://a"}, {"uri": "https://b"}]
+                    },
+                    "fields": [{"name": "API key", "value": "k", "type": 1}]
+                },
+                {
+                    "type": 3, "name": "Visa", "folderId": null, "notes": "n",
+                    "card": {"cardholderName": "Me", "number": "4111", "code": null}
+                }
+            ]
+        });
+        let entries = entries(items(&export.to_string())?, &ItemRules::default());
+        let expected = [
+            ("visa/cardholder-name", "Me"),
+            ("visa/notes", "n"),
+            ("visa/number", "4111"),
+            ("work/servers/prod-db/api-key", "k"),
+            ("work/servers/prod-db/password", "hunter2"),
+            ("work/servers/prod-db/url", "https://a"),
+            ("work/servers/prod-db/url-2", "https://b"),
+            ("work/servers/prod-db/username", "admin"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<BTreeMap<_, _>>();
+        assert_eq!(entries, expected);
+        assert!(items(r#"{"encrypted": true, "items": []}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn csv_exports_map_the_login_columns_and_fields() -> Result<()> {
+        let text = "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+                    Work,,login,Mail,,\"pin: 1234\nrecovery: r\",0,https://mail,me,pw,\n";
+        let entries = entries(items(text)?, &ItemRules::default());
+        let expected = [
+            ("work/mail/password", "pw"),
+            ("work/mail/pin", "1234"),
+            ("work/mail/recovery", "r"),
+            ("work/mail/url", "https://mail"),
+            ("work/mail/username", "me"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<BTreeMap<_, _>>();
+        assert_eq!(entries, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Comma-separated exports, as RFC 4180 describes them: fields may be quoted,
+//! a quoted field may hold commas, line breaks and `""` for a quote, and the
+//! first row names the columns.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+
+/// The rows of `text` after its header, each as column name to value. A
+/// leading byte order mark is ignored, as are blank lines; column names are
+/// trimmed and lowercased.
+///
+/// # Errors
+///
+/// Returns an error if a quoted field is never closed, or a row has more
+/// fields than the header.
+pub(super) fn records(text: &str) -> Result<Vec<BTreeMap<String, String>>> {
+    let mut rows = rows(text.strip_prefix('\u{feff}').unwrap_or(text))?.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(vec![]);
+    };
+    let header = header
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    let mut records = vec![];
+    for (idx, row) in rows.enumerate() {
+        if row.len() > header.len() {
+            bail!(
+                "row {} has {} fields, but the header names {}",
+                idx.saturating_add(2),
+                row.len(),
+                header.len()
+            );
+        }
+        records.push(header.iter().cloned().zip(row).collect());
+    }
+    Ok(records)
+}
+
+fn rows(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                let _quote = chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|field| !field.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("a quoted field is never closed");
+    }
+    row.push(field);
+    if row.iter().any(|field| !field.is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::records;
+
+    #[test]
+    fn quoted_fields_keep_commas_quotes_and_line_breaks() -> Result<()> {
+        let text = "\u{feff}Name,Notes\r\nplain,\"a, \"\"b\"\"\nc\"\r\n\r\nshort\n";
+        let records = records(text)?;
+        assert_eq!(records.len(), 2);
+        let first = records.first();
+        assert_eq!(
+            first.and_then(|r| r.get("notes")).map(String::as_str),
+            Some("a, \"b\"\nc")
+        );
+        let second = records.get(1);
+        assert_eq!(
+            second.and_then(|r| r.get("name")).map(String::as_str),
+            Some("short")
+        );
+        assert!(second.is_some_and(|r| !r.contains_key("notes")));
+        assert!(super::records("a\n\"open").is_err());
+        assert!(super::records("a\n1,2").is_err());
+        Ok(())
+    }
+}
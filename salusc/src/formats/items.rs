@@ -0,0 +1,218 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Password manager items, and how they become key names.
+//!
+//! An item in folder `Work/Servers` named `Prod DB` with a `password` field is
+//! stored as `work/servers/prod-db/password`: every folder level and the item
+//! name are lowercased, and runs of anything but letters, digits, `-`, `_`, and
+//! `.` become one `-`. A second item that would land on the same name gets
+//! `-2`, then `-3`, in the order the export lists them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// One item of a password manager's export: its folder, its name, and its
+/// non-empty fields in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(super) struct Item {
+    pub(super) folder: Option<String>,
+    pub(super) name: String,
+    pub(super) fields: Vec<(String, String)>,
+}
+
+impl Item {
+    /// Add the field `name` unless `value` is empty; a name already taken
+    /// gets `-2`, then `-3`.
+    pub(super) fn field(&mut self, name: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let name = slug(name, "field");
+        let mut unique = name.clone();
+        let mut n = 1u32;
+        while self.fields.iter().any(|(taken, _)| *taken == unique) {
+            n = n.saturating_add(1);
+            unique = format!("{name}-{n}");
+        }
+        self.fields.push((unique, value.to_string()));
+    }
+}
+
+/// How items become key names: field renames and folder prefixes.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ItemRules {
+    /// Field name to the name it is stored under; an empty name drops it
+    fields: BTreeMap<String, String>,
+    /// Folder to the prefix its items are stored under
+    folders: BTreeMap<String, String>,
+}
+
+impl ItemRules {
+    /// Rules renaming each `(field, name)` in `fields` and placing the items
+    /// of each `(folder, prefix)` in `folders` under that prefix.
+    pub(crate) fn new(fields: Vec<(String, String)>, folders: Vec<(String, String)>) -> Self {
+        Self {
+            fields: fields
+                .into_iter()
+                .map(|(field, name)| (slug(&field, "field"), name))
+                .collect(),
+            folders: folders
+                .into_iter()
+                .map(|(folder, prefix)| (folder.trim_matches('/').to_string(), prefix))
+                .collect(),
+        }
+    }
+
+    /// Whether any rule was given.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.folders.is_empty()
+    }
+
+    /// The prefix the items of `folder` are stored under: the longest folder
+    /// rule covering it, followed by the rest of its levels, or every level
+    /// made into a name.
+    fn prefix(&self, folder: Option<&str>) -> String {
+        let Some(folder) = folder.map(|folder| folder.trim_matches('/')) else {
+            return String::new();
+        };
+        let ruled = self
+            .folders
+            .iter()
+            .filter(|(ruled, _)| {
+                folder == ruled.as_str()
+                    || folder
+                        .strip_prefix(ruled.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|(ruled, _)| ruled.len());
+        let (mut prefix, rest) = match ruled {
+            Some((ruled, prefix)) => (
+                prefix.clone(),
+                folder.get(ruled.len()..).unwrap_or_default(),
+            ),
+            None => (String::new(), folder),
+        };
+        for level in rest.split('/').filter(|level| !level.is_empty()) {
+            prefix.push_str(&slug(level, "folder"));
+            prefix.push('/');
+        }
+        prefix
+    }
+}
+
+/// The keys and values storing `items` under `rules`.
+pub(super) fn entries(items: Vec<Item>, rules: &ItemRules) -> BTreeMap<String, String> {
+    let mut taken = BTreeSet::new();
+    let mut entries = BTreeMap::new();
+    for item in items {
+        let base = format!(
+            "{}{}",
+            rules.prefix(item.folder.as_deref()),
+            slug(&item.name, "untitled")
+        );
+        let mut at = base.clone();
+        let mut n = 1u32;
+        while taken.contains(&at) {
+            n = n.saturating_add(1);
+            at = format!("{base}-{n}");
+        }
+        for (field, value) in item.fields {
+            let name = rules.fields.get(&field).unwrap_or(&field);
+            if !name.is_empty() {
+                let _old = entries.insert(format!("{at}/{name}"), value);
+            }
+        }
+        let _new = taken.insert(at);
+    }
+    entries
+}
+
+/// `text` as one level of a key name, or `empty` when nothing of it is left.
+pub(super) fn slug(text: &str, empty: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() || slug.chars().all(|c| c == '.') {
+        empty.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{Item, ItemRules, entries, slug};
+
+    fn item(folder: Option<&str>, name: &str, fields: &[(&str, &str)]) -> Item {
+        let mut item = Item {
+            folder: folder.map(String::from),
+            name: name.to_string(),
+            fields: vec![],
+        };
+        for (field, value) in fields {
+            item.field(field, value);
+        }
+        item
+    }
+
+    #[test]
+    fn names_are_slugged_and_collisions_numbered() {
+        assert_eq!(slug(" Prod DB (EU) ", "x"), "prod-db-eu");
+        assert_eq!(slug("..", "untitled"), "untitled");
+        let items = vec![
+            item(Some("Work/Servers"), "Prod DB", &[("Password", "a")]),
+            item(Some("Work/Servers"), "prod db", &[("password", "b")]),
+            item(None, "Bank", &[("username", "me"), ("notes", "")]),
+        ];
+        assert_eq!(
+            entries(items, &ItemRules::default()),
+            BTreeMap::from([
+                ("bank/username".to_string(), "me".to_string()),
+                ("work/servers/prod-db/password".to_string(), "a".to_string()),
+                (
+                    "work/servers/prod-db-2/password".to_string(),
+                    "b".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn rules_rename_drop_and_place_under_prefixes() {
+        let rules = ItemRules::new(
+            vec![
+                ("Password".to_string(), "secret".to_string()),
+                ("notes".to_string(), String::new()),
+            ],
+            vec![("Work".to_string(), "prod/".to_string())],
+        );
+        let items = vec![
+            item(
+                Some("Work/Servers"),
+                "db",
+                &[("password", "p"), ("notes", "n")],
+            ),
+            item(Some("Workshop"), "saw", &[("password", "s")]),
+        ];
+        assert_eq!(
+            entries(items, &rules),
+            BTreeMap::from([
+                ("prod/servers/db/secret".to_string(), "p".to_string()),
+                ("workshop/saw/secret".to_string(), "s".to_string()),
+            ])
+        );
+    }
+}
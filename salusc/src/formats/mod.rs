@@ -0,0 +1,329 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The secret file formats understood by `import` and written by `export`.
+//!
+//! Every format is reduced to a flat, sorted map of key name to string value.
+//! Nested JSON/YAML objects are flattened with `/` between levels, so
+//! `{"db": {"pass": "x"}}` becomes `db/pass`. Other non-string values (numbers,
+//! booleans, arrays) are stored as their compact JSON text. [`render`] writes
+//! the flat map back out, so an export can be imported again unchanged.
+//!
+//! `import` also reads the exports of Bitwarden and 1Password: each item
+//! becomes one key per field under the item's folder and name, as [`items`]
+//! describes and [`ItemRules`] adjusts.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use serde_json::Value;
+
+pub(crate) use self::items::ItemRules;
+
+mod bitwarden;
+mod csv;
+mod items;
+mod onepassword;
+
+/// A file format for secrets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum FileFormat {
+    /// `KEY=value` lines, as read by most `.env` loaders
+    Dotenv,
+    /// A JSON object
+    Json,
+    /// A YAML mapping
+    Yaml,
+}
+
+impl FileFormat {
+    /// Guess the format from a file name: `.json`, `.yaml`/`.yml`, or anything
+    /// ending in `.env` (including a bare `.env`).
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Some(FileFormat::Json),
+            Some("yaml" | "yml") => Some(FileFormat::Yaml),
+            Some("env") => Some(FileFormat::Dotenv),
+            _ if name == ".env" || name.starts_with(".env.") => Some(FileFormat::Dotenv),
+            _ => None,
+        }
+    }
+}
+
+/// A file format `import` reads: one of the [`FileFormat`]s, or a password
+/// manager's export.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ImportFormat {
+    /// `KEY=value` lines, as read by most `.env` loaders
+    Dotenv,
+    /// A JSON object
+    Json,
+    /// A YAML mapping
+    Yaml,
+    /// A Bitwarden export, unencrypted JSON or CSV
+    Bitwarden,
+    /// A 1Password export, CSV or the `export.data` inside a `.1pux`
+    #[value(name = "1password", alias = "onepassword")]
+    OnePassword,
+}
+
+impl From<FileFormat> for ImportFormat {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Dotenv => ImportFormat::Dotenv,
+            FileFormat::Json => ImportFormat::Json,
+            FileFormat::Yaml => ImportFormat::Yaml,
+        }
+    }
+}
+
+/// Parse `text` in `format` into key/value pairs, naming the items of a
+/// password manager's export by `rules`.
+///
+/// # Errors
+///
+/// Returns an error if the text is not valid in `format`, or `rules` are
+/// given for a format that has no items.
+pub(crate) fn parse_import(
+    format: ImportFormat,
+    text: &str,
+    rules: &ItemRules,
+) -> Result<BTreeMap<String, String>> {
+    let items = match format {
+        ImportFormat::Bitwarden => bitwarden::items(text)?,
+        ImportFormat::OnePassword => onepassword::items(text)?,
+        ImportFormat::Dotenv | ImportFormat::Json | ImportFormat::Yaml if !rules.is_empty() => {
+            bail!("--map and --folder only apply to --format bitwarden|1password")
+        }
+        ImportFormat::Dotenv => return parse(FileFormat::Dotenv, text),
+        ImportFormat::Json => return parse(FileFormat::Json, text),
+        ImportFormat::Yaml => return parse(FileFormat::Yaml, text),
+    };
+    Ok(items::entries(items, rules))
+}
+
+/// Parse `text` in `format` into key/value pairs.
+///
+/// # Errors
+///
+/// Returns an error if the text is not valid in `format`, the top level is not
+/// an object/mapping, or it contains a `null` value.
+pub(crate) fn parse(format: FileFormat, text: &str) -> Result<BTreeMap<String, String>> {
+    match format {
+        FileFormat::Dotenv => parse_dotenv(text),
+        FileFormat::Json => flatten_document(serde_json::from_str(text)?),
+        FileFormat::Yaml => flatten_document(serde_yaml_ng::from_str(text)?),
+    }
+}
+
+/// Render key/value pairs in `format`.
+///
+/// Dotenv values are always double-quoted (escaping `\`, `"`, and control
+/// characters) so that [`parse`] reads back exactly the same value.
+///
+/// # Errors
+///
+/// Returns an error if the JSON or YAML serializer fails.
+pub(crate) fn render(format: FileFormat, entries: &BTreeMap<String, String>) -> Result<String> {
+    match format {
+        FileFormat::Dotenv => {
+            let mut out = String::new();
+            for (key, value) in entries {
+                out.push_str(key);
+                out.push_str("=\"");
+                for c in value.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c => out.push(c),
+                    }
+                }
+                out.push_str("\"\n");
+            }
+            Ok(out)
+        }
+        FileFormat::Json => {
+            let mut out = serde_json::to_string_pretty(entries)?;
+            out.push('\n');
+            Ok(out)
+        }
+        FileFormat::Yaml => Ok(serde_yaml_ng::to_string(entries)?),
+    }
+}
+
+pub(crate) fn flatten_document(document: Value) -> Result<BTreeMap<String, String>> {
+    let Value::Object(_) = document else {
+        bail!("the top level of the file must be an object of key/value pairs");
+    };
+    let mut entries = BTreeMap::new();
+    flatten_into(&mut entries, "", document)?;
+    Ok(entries)
+}
+
+fn flatten_into(entries: &mut BTreeMap<String, String>, path: &str, value: Value) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                let child = if path.is_empty() {
+                    name
+                } else {
+                    format!("{path}/{name}")
+                };
+                flatten_into(entries, &child, value)?;
+            }
+        }
+        Value::Null => bail!("'{path}' is null; remove it or give it a value"),
+        Value::String(text) => {
+            let _old = entries.insert(path.to_string(), text);
+        }
+        other @ (Value::Bool(_) | Value::Number(_) | Value::Array(_)) => {
+            let _old = entries.insert(path.to_string(), other.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Parse `.env` text: `KEY=value` per line, blank lines and `#` comments
+/// ignored, an optional leading `export`, and single- or double-quoted values.
+/// Double quotes understand `\n`, `\t`, `\"`, and `\\`; single quotes are
+/// literal. A later definition of the same key wins.
+fn parse_dotenv(text: &str) -> Result<BTreeMap<String, String>> {
+    let mut entries = BTreeMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        let lineno = idx.saturating_add(1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, raw)) = line.split_once('=') else {
+            bail!("line {lineno}: expected KEY=value");
+        };
+        let key = key.trim();
+        if key.is_empty() || key.chars().any(char::is_whitespace) {
+            bail!("line {lineno}: invalid key '{key}'");
+        }
+        let value = dotenv_value(raw.trim())
+            .ok_or_else(|| anyhow!("line {lineno}: unterminated quoted value"))?;
+        let _old = entries.insert(key.to_string(), value);
+    }
+    Ok(entries)
+}
+
+fn dotenv_value(raw: &str) -> Option<String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest.find('\'')?;
+        return rest.get(..end).map(ToString::to_string);
+    }
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(value),
+                '\\' => match chars.next()? {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    other => value.push(other),
+                },
+                c => value.push(c),
+            }
+        }
+        return None;
+    }
+    // Unquoted: an inline comment starts at a `#` preceded by whitespace.
+    let value = raw
+        .find(" #")
+        .and_then(|idx| raw.get(..idx))
+        .unwrap_or(raw)
+        .trim_end();
+    Some(value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, path::Path};
+
+    use anyhow::Result;
+
+    use super::{FileFormat, parse, render};
+
+    #[test]
+    fn dotenv_handles_quotes_comments_and_export() -> Result<()> {
+        let text =
+            "# comment\n\nexport A=1\nB='single # kept'\nC=\"line\\nbreak\"\nD=bare # note\nA=2\n";
+        let entries = parse(FileFormat::Dotenv, text)?;
+        assert_eq!(entries.get("A").map(String::as_str), Some("2"));
+        assert_eq!(entries.get("B").map(String::as_str), Some("single # kept"));
+        assert_eq!(entries.get("C").map(String::as_str), Some("line\nbreak"));
+        assert_eq!(entries.get("D").map(String::as_str), Some("bare"));
+        assert_eq!(entries.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn dotenv_rejects_malformed_lines() {
+        assert!(parse(FileFormat::Dotenv, "NOEQUALS\n").is_err());
+        assert!(parse(FileFormat::Dotenv, "A=\"open\n").is_err());
+    }
+
+    #[test]
+    fn json_and_yaml_flatten_nested_objects() -> Result<()> {
+        let json = parse(
+            FileFormat::Json,
+            r#"{"db": {"user": "u", "port": 5432}, "tags": ["a"]}"#,
+        )?;
+        assert_eq!(json.get("db/user").map(String::as_str), Some("u"));
+        assert_eq!(json.get("db/port").map(String::as_str), Some("5432"));
+        assert_eq!(json.get("tags").map(String::as_str), Some(r#"["a"]"#));
+
+        let yaml = parse(FileFormat::Yaml, "db:\n  user: u\ntoken: t\n")?;
+        assert_eq!(yaml.get("db/user").map(String::as_str), Some("u"));
+        assert_eq!(yaml.get("token").map(String::as_str), Some("t"));
+        Ok(())
+    }
+
+    #[test]
+    fn rendered_output_parses_back_unchanged() -> Result<()> {
+        let entries = BTreeMap::from([
+            ("A".to_string(), "plain".to_string()),
+            ("B".to_string(), "quote \" slash \\ # hash".to_string()),
+            ("C".to_string(), "multi\nline\ttab".to_string()),
+        ]);
+        for format in [FileFormat::Dotenv, FileFormat::Json, FileFormat::Yaml] {
+            assert_eq!(parse(format, &render(format, &entries)?)?, entries);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn format_is_inferred_from_the_file_name() {
+        for (name, format) in [
+            (".env", Some(FileFormat::Dotenv)),
+            (".env.production", Some(FileFormat::Dotenv)),
+            ("prod.env", Some(FileFormat::Dotenv)),
+            ("secrets.json", Some(FileFormat::Json)),
+            ("secrets.yml", Some(FileFormat::Yaml)),
+            ("secrets.txt", None),
+        ] {
+            assert_eq!(FileFormat::from_path(Path::new(name)), format);
+        }
+    }
+
+    #[test]
+    fn non_object_documents_and_nulls_are_rejected() {
+        assert!(parse(FileFormat::Json, "[1, 2]").is_err());
+        assert!(parse(FileFormat::Yaml, "a: ~\n").is_err());
+    }
+}
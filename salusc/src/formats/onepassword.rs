@@ -0,0 +1,217 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! 1Password's exports: the CSV one, or the `export.data` file inside a
+//! `.1pux` archive.
+//!
+//! A CSV row's `Url`, `Username`, `Password`, `OTPAuth` (as `totp`) and
+//! `Notes` become fields of the item named by its `Title`, as does any other
+//! column but `Favorite`, `Archived`, `Tags` and `Type`. A `.1pux` item sits
+//! in a folder named for its vault, and its login fields, password, notes and
+//! the fields of its sections, named by their titles, become its fields.
+//! Archived items are left out of both.
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+use super::{csv, items::Item};
+
+/// CSV columns that describe the row rather than hold a secret.
+const SKIPPED_COLUMNS: [&str; 5] = ["title", "favorite", "archived", "tags", "type"];
+
+/// The items of a 1Password export, told apart from a CSV one by its leading
+/// `{`.
+///
+/// # Errors
+///
+/// Returns an error if the export is malformed.
+pub(super) fn items(text: &str) -> Result<Vec<Item>> {
+    if text.trim_start().starts_with('{') {
+        pux_items(&serde_json::from_str(text)?)
+    } else {
+        csv_items(text)
+    }
+}
+
+fn csv_items(text: &str) -> Result<Vec<Item>> {
+    let mut items = vec![];
+    for record in csv::records(text)? {
+        if record
+            .get("archived")
+            .is_some_and(|archived| archived.eq_ignore_ascii_case("true"))
+        {
+            continue;
+        }
+        let mut item = Item {
+            folder: record
+                .get("vault")
+                .cloned()
+                .filter(|vault| !vault.is_empty()),
+            name: record.get("title").cloned().unwrap_or_default(),
+            fields: vec![],
+        };
+        for (column, value) in &record {
+            let name = match column.as_str() {
+                "otpauth" => "totp",
+                "vault" => continue,
+                skipped if SKIPPED_COLUMNS.contains(&skipped) => continue,
+                other => other,
+            };
+            item.field(name, value);
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn pux_items(export: &Value) -> Result<Vec<Item>> {
+    let Some(accounts) = export.get("accounts").and_then(Value::as_array) else {
+        bail!("a 1Password export.data lists its vaults under \"accounts\"");
+    };
+    let mut items = vec![];
+    for vault in accounts.iter().flat_map(|account| list(account, "vaults")) {
+        let folder = vault
+            .pointer("/attrs/name")
+            .and_then(Value::as_str)
+            .map(String::from);
+        for listed in list(vault, "items") {
+            if listed.get("state").and_then(Value::as_str) == Some("archived") {
+                continue;
+            }
+            let mut item = Item {
+                folder: folder.clone(),
+                name: text_at(listed, "/overview/title").to_string(),
+                fields: vec![],
+            };
+            let details = listed.get("details").unwrap_or(&Value::Null);
+            for field in list(details, "loginFields") {
+                let name = Some(text_at(field, "/designation"))
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| text_at(field, "/name"));
+                item.field(name, text_at(field, "/value"));
+            }
+            item.field("password", text_at(details, "/password"));
+            item.field("url", text_at(listed, "/overview/url"));
+            for field in list(details, "sections").flat_map(|section| list(section, "fields")) {
+                let name = Some(text_at(field, "/title"))
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| text_at(field, "/id"));
+                if let Some(value) = section_value(field.get("value")) {
+                    item.field(name, &value);
+                }
+            }
+            item.field("notes", text_at(details, "/notesPlain"));
+            items.push(item);
+        }
+    }
+    Ok(items)
+}
+
+/// A section field's value, kept as `{"<kind>": <value>}`: the text of a
+/// string or number, or nothing for the kinds holding more than one value.
+fn section_value(value: Option<&Value>) -> Option<String> {
+    let Some(Value::Object(kinds)) = value else {
+        return None;
+    };
+    match kinds.values().next()? {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// The entries of the array `name` of `value`, or none.
+fn list<'a>(value: &'a Value, name: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(name)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// The string at `pointer` in `value`, or nothing.
+fn text_at<'a>(value: &'a Value, pointer: &str) -> &'a str {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+
+    use super::items;
+    use crate::formats::items::{ItemRules, entries};
+
+    fn expected(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn csv_exports_map_known_and_extra_columns() -> Result<()> {
+        let text = "Title,Url,Username,Password,OTPAuth,Favorite,Archived,Tags,Notes,Recovery Code\n\
+                    GitHub,https://github.com,me,pw,otpauth://x,true,false,dev,,r1\n\
+                    Old,,,gone,,false,true,,,\n";
+        assert_eq!(
+            entries(items(text)?, &ItemRules::default()),
+            expected(&[
+                ("github/password", "pw"),
+                ("github/recovery-code", "r1"),
+                ("github/totp", "otpauth://x"),
+                ("github/url", "https://github.com"),
+                ("github/username", "me"),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pux_exports_map_login_and_section_fields_under_the_vault() -> Result<()> {
+        let export = serde_json::json!({
+            "accounts": [{"vaults": [{
+                "attrs": {"name": "Private"},
+                "items": [
+                    {
+                        "state": "active",
+                        "overview": {"title": "Router", "url": "http://10.0.0.1"},
+                        "details": {
+                            "loginFields": [
+                                {"name": "user", "designation": "username", "value": "admin"},
+                                {"name": "pass", "designation": "password", "value": "pw"}
+                            ],
+                            "notesPlain": "",
+                            "sections": [{"fields": [
+                                {"title": "WiFi Key", "id": "a", "value": {"concealed": "w"}},
+                                {"title": "Port", "id": "b", "value": {"string": "22"}},
+                                {"title": "Address", "id": "c", "value": {"address": {"city": "x"}}}
+                            ]}]
+                        }
+                    },
+                    {"state": "archived", "overview": {"title": "Gone"}, "details": {"password": "x"}}
+                ]
+            }]}]
+        });
+        assert_eq!(
+            entries(items(&export.to_string())?, &ItemRules::default()),
+            expected(&[
+                ("private/router/password", "pw"),
+                ("private/router/port", "22"),
+                ("private/router/url", "http://10.0.0.1"),
+                ("private/router/username", "admin"),
+                ("private/router/wifi-key", "w"),
+            ])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Ctrl-C: cancels the request `salusc` is waiting on, or ends `salusc` when
+//! it is waiting on none.
+//!
+//! Listening for Ctrl-C replaces the default handler for the rest of the
+//! process, so [`watch`] keeps that behaviour outside a request: `salusc` exits
+//! with status 130, as a shell reports for an interrupted command.
+
+use std::{
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::{signal::ctrl_c, spawn, sync::Notify};
+
+/// The exit status of a command ended by Ctrl-C.
+pub(crate) const INTERRUPTED_STATUS: i32 = 130;
+
+/// How many requests `salusc` is waiting on.
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+/// Woken at each Ctrl-C while a request is waited on.
+static INTERRUPTED: Notify = Notify::const_new();
+
+/// Start listening for Ctrl-C.
+pub(crate) fn watch() {
+    let _watcher = spawn(async {
+        while ctrl_c().await.is_ok() {
+            if WAITING.load(Ordering::Acquire) == 0 {
+                process::exit(INTERRUPTED_STATUS);
+            }
+            INTERRUPTED.notify_waiters();
+        }
+    });
+}
+
+/// Marks `salusc` as waiting on a request, so Ctrl-C cancels it, for as long
+/// as it lives.
+pub(crate) struct Waiting(());
+
+impl Waiting {
+    pub(crate) fn start() -> Self {
+        let _waiting = WAITING.fetch_add(1, Ordering::AcqRel);
+        Self(())
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let _waiting = WAITING.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Resolves at the next Ctrl-C pressed while a request is waited on.
+pub(crate) async fn interrupted() {
+    INTERRUPTED.notified().await;
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+
+    use super::{WAITING, Waiting};
+
+    #[test]
+    fn waiting_is_counted_while_it_lives() {
+        let waiting = Waiting::start();
+        assert!(WAITING.load(Ordering::Acquire) >= 1);
+        drop(waiting);
+    }
+}
@@ -250,10 +250,27 @@ use crate::error::{clap_or_error, success};
 use anyhow::Result;
 use std::process;
 
+mod cargo;
+mod clipboard;
 mod config;
+#[cfg(any(target_os = "macos", windows))]
+mod credstore;
+mod editor;
 mod error;
+mod exec;
+mod formats;
 mod inter;
+mod interrupt;
+mod output;
+mod paper;
+mod qr;
 mod runtime;
+mod stream;
+mod template;
+mod token;
+mod utils;
+#[cfg(feature = "vault")]
+mod vault;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -0,0 +1,1205 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Machine-readable rendering of client results.
+//!
+//! The default `plain` format is the styled, human-oriented output every
+//! subcommand has always produced. `json` and `yaml` instead write a single
+//! structured document to stdout per invocation, using the record types below so
+//! field names stay stable for scripts and CI. A failure in a structured mode is
+//! rendered as an [`ErrorRecord`] (`{"error": {"kind": ..., "message": ...}}`)
+//! and the process exits non-zero.
+
+use std::{
+    collections::BTreeMap,
+    io::{Write as _, stdout},
+    path::Path,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// How the client renders its results.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    /// Styled, human-readable output (the default)
+    #[default]
+    Plain,
+    /// A single JSON document on stdout
+    Json,
+    /// A single YAML document on stdout
+    Yaml,
+}
+
+impl OutputFormat {
+    /// Whether this is the human-readable `plain` format.
+    pub(crate) fn is_plain(self) -> bool {
+        self == OutputFormat::Plain
+    }
+
+    /// The lowercase name used on the command line and in config files.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            OutputFormat::Plain => "plain",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Serialize `value` in this format.
+    ///
+    /// `plain` has no structured representation, so it renders as JSON; callers
+    /// only reach this in a structured mode.
+    pub(crate) fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        match self {
+            OutputFormat::Plain | OutputFormat::Json => Ok(serde_json::to_string_pretty(value)?),
+            OutputFormat::Yaml => Ok(serde_yaml_ng::to_string(value)?),
+        }
+    }
+
+    /// Write `value` to stdout as a single structured document.
+    pub(crate) fn emit<T: Serialize>(self, value: &T) -> Result<()> {
+        let rendered = self.render(value)?;
+        let mut out = stdout();
+        if rendered.ends_with('\n') {
+            write!(out, "{rendered}")?;
+        } else {
+            writeln!(out, "{rendered}")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write an [`ErrorRecord`] to stdout and return the error that makes the
+    /// process exit with status 1 without printing the failure a second time.
+    pub(crate) fn fail(self, kind: &str, message: &str) -> Result<()> {
+        self.emit(&ErrorRecord::new(kind, message))?;
+        Err(Error::Exit(1).into())
+    }
+}
+
+/// A failure, rendered as `{"error": {"kind": ..., "message": ...}}`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ErrorRecord {
+    error: ErrorBody,
+}
+
+/// The body of an [`ErrorRecord`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct ErrorBody {
+    /// A short, stable classification in `snake_case` (e.g. `key_not_found`).
+    kind: String,
+    /// A human-readable description of the failure.
+    message: String,
+}
+
+impl ErrorRecord {
+    pub(crate) fn new(kind: &str, message: &str) -> Self {
+        Self {
+            error: ErrorBody {
+                kind: kind.to_string(),
+                message: message.to_string(),
+            },
+        }
+    }
+}
+
+/// The outcome of a command that only succeeds or fails (store, delete, lock,
+/// unlock, ...).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct StatusRecord<'a> {
+    /// Always `true`; failures are rendered as an [`ErrorRecord`] instead.
+    ok: bool,
+    /// The subcommand that produced this record.
+    action: &'a str,
+    /// The key the command acted on, when there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<&'a str>,
+    /// Whether anything was written, for commands that may decide not to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed: Option<bool>,
+}
+
+impl<'a> StatusRecord<'a> {
+    pub(crate) fn new(action: &'a str, key: Option<&'a str>) -> Self {
+        Self {
+            ok: true,
+            action,
+            key,
+            changed: None,
+        }
+    }
+
+    /// Record whether the command changed anything.
+    pub(crate) fn with_changed(mut self, changed: bool) -> Self {
+        self.changed = Some(changed);
+        self
+    }
+}
+
+/// The result of `delete --prefix`: the keys deleted or, for a dry run, the
+/// keys that would be.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DeletedRecord<'a> {
+    prefix: &'a str,
+    dry_run: bool,
+    keys: &'a [String],
+}
+
+impl<'a> DeletedRecord<'a> {
+    pub(crate) fn new(prefix: &'a str, dry_run: bool, keys: &'a [String]) -> Self {
+        Self {
+            prefix,
+            dry_run,
+            keys,
+        }
+    }
+}
+
+/// The result of `read`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ValueRecord<'a> {
+    key: &'a str,
+    /// The UTF-8 value, or `null` when the stored bytes are not valid UTF-8.
+    value: Option<&'a str>,
+    /// The length of the stored value in bytes.
+    bytes: usize,
+}
+
+impl<'a> ValueRecord<'a> {
+    pub(crate) fn new(key: &'a str, raw: &'a [u8]) -> Self {
+        Self {
+            key,
+            value: str::from_utf8(raw).ok(),
+            bytes: raw.len(),
+        }
+    }
+}
+
+/// The result of `exists`: whether the key holds a value and, when it does,
+/// what the daemon records of it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct KeyStatRecord<'a> {
+    key: &'a str,
+    exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stored_under: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stored_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    versions: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    written: Option<u64>,
+}
+
+impl<'a> KeyStatRecord<'a> {
+    pub(crate) fn new(key: &'a str, stat: Option<&'a libsalus::KeyStat>) -> Self {
+        Self {
+            key,
+            exists: stat.is_some(),
+            stored_under: stat.map(|stat| stat.key().as_str()),
+            stored_bytes: stat.map(libsalus::KeyStat::stored_bytes),
+            chunked: stat.map(libsalus::KeyStat::chunked),
+            versions: stat.map(libsalus::KeyStat::versions),
+            written: stat.and_then(libsalus::KeyStat::written),
+        }
+    }
+}
+
+/// One version of a value, as `history` lists it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct VersionRecord {
+    version: u64,
+    current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    written: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<u32>,
+}
+
+/// The result of `history`: the versions of a value, oldest first.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct HistoryRecord<'a> {
+    key: &'a str,
+    versions: Vec<VersionRecord>,
+}
+
+impl<'a> HistoryRecord<'a> {
+    pub(crate) fn new(key: &'a str, versions: &[libsalus::ValueVersion]) -> Self {
+        Self {
+            key,
+            versions: versions
+                .iter()
+                .map(|version| VersionRecord {
+                    version: version.version(),
+                    current: version.current(),
+                    written: version.written(),
+                    author: version.author(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One changed field, as `diff` reports it; its values only with
+/// `--show-values`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct FieldChangeRecord<'a> {
+    path: &'a str,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<&'a str>,
+}
+
+/// The result of `diff`: the fields that changed between two versions.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DiffRecord<'a> {
+    key: &'a str,
+    from: u64,
+    to: u64,
+    changes: Vec<FieldChangeRecord<'a>>,
+}
+
+impl<'a> DiffRecord<'a> {
+    pub(crate) fn new(
+        key: &'a str,
+        from: u64,
+        to: u64,
+        changes: &'a [libsalus::FieldChange],
+    ) -> Self {
+        Self {
+            key,
+            from,
+            to,
+            changes: changes
+                .iter()
+                .map(|change| FieldChangeRecord {
+                    path: change.path(),
+                    kind: change_kind(change.kind()),
+                    before: change.before().as_deref(),
+                    after: change.after().as_deref(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How a field changed, as `diff` names it.
+fn change_kind(kind: libsalus::FieldChangeKind) -> &'static str {
+    match kind {
+        libsalus::FieldChangeKind::Added => "added",
+        libsalus::FieldChangeKind::Removed => "removed",
+        libsalus::FieldChangeKind::Changed => "changed",
+    }
+}
+
+/// The result of `find` and `search`: the matching key names, best match first.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct KeysRecord<'a> {
+    keys: &'a [String],
+}
+
+impl<'a> KeysRecord<'a> {
+    pub(crate) fn new(keys: &'a [String]) -> Self {
+        Self { keys }
+    }
+}
+
+/// One named key, as reported by `key list`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct NamedKeyRecord<'a> {
+    name: &'a str,
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotate_after: Option<u64>,
+    rotated_at: u64,
+    rotation_due: bool,
+}
+
+/// The result of `key list`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct NamedKeysRecord<'a> {
+    keys: Vec<NamedKeyRecord<'a>>,
+}
+
+impl<'a> NamedKeysRecord<'a> {
+    pub(crate) fn new(keys: &'a [libsalus::NamedKeyInfo]) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|info| NamedKeyRecord {
+                    name: info.name(),
+                    version: info.version(),
+                    rotate_after: info.rotate_after(),
+                    rotated_at: info.rotated_at(),
+                    rotation_due: info.rotation_due(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One plugin, as reported by `plugin list`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct PluginRecord<'a> {
+    name: &'a str,
+    healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    problem: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'a str>,
+    roles: &'a [String],
+    max_ttl: u64,
+}
+
+/// The result of `plugin list`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct PluginsRecord<'a> {
+    plugins: Vec<PluginRecord<'a>>,
+}
+
+impl<'a> PluginsRecord<'a> {
+    pub(crate) fn new(plugins: &'a [libsalus::PluginInfo]) -> Self {
+        Self {
+            plugins: plugins
+                .iter()
+                .map(|info| PluginRecord {
+                    name: info.name(),
+                    healthy: info.healthy(),
+                    problem: info.problem().as_deref(),
+                    version: info.version().as_deref(),
+                    roles: info.roles(),
+                    max_ttl: info.max_ttl(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The result of `plugin mint`: the credential's fields, by name.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CredentialRecord<'a> {
+    plugin: &'a str,
+    role: &'a str,
+    fields: BTreeMap<&'a str, &'a str>,
+    ttl: u64,
+    expires_at: u64,
+}
+
+impl<'a> CredentialRecord<'a> {
+    pub(crate) fn new(credential: &'a libsalus::Credential) -> Self {
+        Self {
+            plugin: credential.plugin(),
+            role: credential.role(),
+            fields: credential
+                .fields()
+                .iter()
+                .map(|(field, value)| (field.as_str(), value.as_str()))
+                .collect(),
+            ttl: credential.ttl(),
+            expires_at: credential.expires_at(),
+        }
+    }
+}
+
+/// The result of `lease renew`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct LeaseRecord<'a> {
+    lease_id: &'a str,
+    role: &'a str,
+    username: &'a str,
+    expires_at: u64,
+}
+
+impl<'a> LeaseRecord<'a> {
+    pub(crate) fn new(lease: &'a libsalus::LeaseInfo) -> Self {
+        Self {
+            lease_id: lease.lease_id(),
+            role: lease.role(),
+            username: lease.username(),
+            expires_at: lease.expires_at(),
+        }
+    }
+}
+
+/// A held read, as `approvals` lists it and `approvals approve` reports it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ApprovalRecord<'a> {
+    id: &'a str,
+    key: &'a str,
+    requester: u32,
+    approved_by: &'a [u32],
+    required: u32,
+    granted: bool,
+    expires_at: u64,
+}
+
+impl<'a> ApprovalRecord<'a> {
+    pub(crate) fn new(approval: &'a libsalus::ApprovalInfo) -> Self {
+        Self {
+            id: approval.id(),
+            key: approval.key(),
+            requester: approval.requester(),
+            approved_by: approval.approved_by(),
+            required: approval.required(),
+            granted: approval.granted(),
+            expires_at: approval.expires_at(),
+        }
+    }
+}
+
+/// The result of `approvals`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ApprovalsRecord<'a> {
+    approvals: Vec<ApprovalRecord<'a>>,
+}
+
+impl<'a> ApprovalsRecord<'a> {
+    pub(crate) fn new(approvals: &'a [libsalus::ApprovalInfo]) -> Self {
+        Self {
+            approvals: approvals.iter().map(ApprovalRecord::new).collect(),
+        }
+    }
+}
+
+/// The result of `ssh sign`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SshCertificateRecord<'a> {
+    certificate: &'a str,
+    serial: u64,
+    principals: &'a [String],
+    valid_after: u64,
+    valid_before: u64,
+    ca_public_key: &'a str,
+}
+
+impl<'a> SshCertificateRecord<'a> {
+    pub(crate) fn new(certificate: &'a libsalus::SshCertificate) -> Self {
+        Self {
+            certificate: certificate.certificate(),
+            serial: certificate.serial(),
+            principals: certificate.principals(),
+            valid_after: certificate.valid_after(),
+            valid_before: certificate.valid_before(),
+            ca_public_key: certificate.ca_public_key(),
+        }
+    }
+}
+
+/// The result of `pki init` or `pki import-ca`: the CA's certificate, then
+/// any above it, in PEM.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CaRecord<'a> {
+    certificate: &'a str,
+}
+
+impl<'a> CaRecord<'a> {
+    pub(crate) fn new(certificate: &'a str) -> Self {
+        Self { certificate }
+    }
+}
+
+/// The result of `pki issue`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct IssuedCertRecord<'a> {
+    serial: &'a str,
+    certificate: &'a str,
+    ca_chain: &'a str,
+    private_key: &'a str,
+    not_after: u64,
+}
+
+impl<'a> IssuedCertRecord<'a> {
+    pub(crate) fn new(issued: &'a libsalus::IssuedCert) -> Self {
+        Self {
+            serial: issued.serial(),
+            certificate: issued.certificate(),
+            ca_chain: issued.ca_chain(),
+            private_key: issued.private_key(),
+            not_after: issued.not_after(),
+        }
+    }
+}
+
+/// One certificate, as reported by `pki list` and `pki revoke`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CertRecord<'a> {
+    serial: &'a str,
+    common_name: &'a str,
+    sans: &'a [String],
+    not_before: u64,
+    not_after: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<u64>,
+}
+
+impl<'a> CertRecord<'a> {
+    pub(crate) fn new(info: &'a libsalus::CertInfo) -> Self {
+        Self {
+            serial: info.serial(),
+            common_name: info.common_name(),
+            sans: info.sans(),
+            not_before: info.not_before(),
+            not_after: info.not_after(),
+            revoked_at: info.revoked_at(),
+        }
+    }
+}
+
+/// The result of `pki list`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CertsRecord<'a> {
+    certs: Vec<CertRecord<'a>>,
+}
+
+impl<'a> CertsRecord<'a> {
+    pub(crate) fn new(certs: &'a [libsalus::CertInfo]) -> Self {
+        Self {
+            certs: certs.iter().map(CertRecord::new).collect(),
+        }
+    }
+}
+
+/// The result of `pki crl`: the CRL, in PEM.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CrlRecord<'a> {
+    crl: &'a str,
+}
+
+impl<'a> CrlRecord<'a> {
+    pub(crate) fn new(crl: &'a str) -> Self {
+        Self { crl }
+    }
+}
+
+/// The result of `backup`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct BackupRecord<'a> {
+    path: &'a str,
+    manifest: &'a str,
+    sha256: &'a str,
+    bytes: u64,
+    rows: u64,
+    encrypted: bool,
+}
+
+impl<'a> BackupRecord<'a> {
+    pub(crate) fn new(info: &'a libsalus::BackupInfo) -> Self {
+        Self {
+            path: info.path(),
+            manifest: info.manifest(),
+            sha256: info.sha256(),
+            bytes: info.bytes(),
+            rows: info.rows(),
+            encrypted: info.encrypted(),
+        }
+    }
+}
+
+/// The result of `fsck`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct IntegrityRecord<'a> {
+    checked: u64,
+    problems: Vec<IntegrityProblemRecord<'a>>,
+}
+
+/// One row `fsck` found a problem with.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct IntegrityProblemRecord<'a> {
+    table: &'a str,
+    key: &'a str,
+    reason: &'a str,
+}
+
+impl<'a> IntegrityRecord<'a> {
+    pub(crate) fn new(report: &'a libsalus::IntegrityReport) -> Self {
+        Self {
+            checked: report.checked(),
+            problems: report
+                .problems()
+                .iter()
+                .map(|problem| IntegrityProblemRecord {
+                    table: problem.table(),
+                    key: problem.key(),
+                    reason: problem.reason(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The result of `key rotate`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct KeyRotatedRecord<'a> {
+    name: &'a str,
+    version: u32,
+}
+
+impl<'a> KeyRotatedRecord<'a> {
+    pub(crate) fn new(name: &'a str, version: u32) -> Self {
+        Self { name, version }
+    }
+}
+
+/// How far a rewrap has got, as `key rewrap` and `key rewraps` report it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RewrapRecord<'a> {
+    prefix: &'a str,
+    stale: u64,
+    checked: u64,
+    rewrapped: u64,
+    batches: u64,
+    finished: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl<'a> RewrapRecord<'a> {
+    pub(crate) fn new(progress: &'a libsalus::RewrapProgress) -> Self {
+        Self {
+            prefix: progress.prefix(),
+            stale: progress.stale(),
+            checked: progress.checked(),
+            rewrapped: progress.rewrapped(),
+            batches: progress.batches(),
+            finished: progress.finished(),
+            error: progress.error().as_deref(),
+        }
+    }
+}
+
+/// The result of `key rewraps`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RewrapsRecord<'a> {
+    rewraps: Vec<RewrapRecord<'a>>,
+}
+
+impl<'a> RewrapsRecord<'a> {
+    pub(crate) fn new(rewraps: &'a [libsalus::RewrapProgress]) -> Self {
+        Self {
+            rewraps: rewraps.iter().map(RewrapRecord::new).collect(),
+        }
+    }
+}
+
+/// The result of `shares`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SharesRecord<'a> {
+    /// Empty when the shares went only to files.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    shares: &'a [String],
+    /// The files the shares were also saved to, if any.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    files: &'a [String],
+    /// How many shares were written to hardware tokens, when any were.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens: Option<usize>,
+}
+
+impl<'a> SharesRecord<'a> {
+    pub(crate) fn new(shares: &'a [String]) -> Self {
+        Self {
+            shares,
+            files: &[],
+            tokens: None,
+        }
+    }
+
+    /// Record the files the shares were saved to.
+    pub(crate) fn with_files(mut self, files: &'a [String]) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Record how many shares were written to tokens.
+    pub(crate) fn with_tokens(mut self, tokens: usize) -> Self {
+        self.tokens = (tokens > 0).then_some(tokens);
+        self
+    }
+}
+
+/// The result of `verify-share` for a share in the current set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct VerifiedShareRecord {
+    /// Always `true`; an unrecognized share is rendered as an [`ErrorRecord`].
+    valid: bool,
+    /// The share's index within its set.
+    index: u8,
+}
+
+impl VerifiedShareRecord {
+    pub(crate) fn new(index: u8) -> Self {
+        Self { valid: true, index }
+    }
+}
+
+/// One enrolled set, as reported by `enroll-status`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SetRecord<'a> {
+    name: &'a str,
+    auto_shares: u8,
+}
+
+/// The result of `enroll-status`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct EnrollStatusRecord<'a> {
+    sets: Vec<SetRecord<'a>>,
+    agent_reachable: bool,
+}
+
+impl<'a> EnrollStatusRecord<'a> {
+    pub(crate) fn new(sets: &'a [libsalus::SetInfo], agent_reachable: bool) -> Self {
+        Self {
+            sets: sets
+                .iter()
+                .map(|info| SetRecord {
+                    name: &info.name,
+                    auto_shares: info.auto_count,
+                })
+                .collect(),
+            agent_reachable,
+        }
+    }
+}
+
+/// The result of `status`.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DaemonStatusRecord<'a> {
+    initialized: bool,
+    sealed: bool,
+    threshold: u8,
+    num_shares: u8,
+    shares_collected: usize,
+    /// `null` while sealed or when the key is held until an explicit lock.
+    key_timeout_remaining_secs: Option<u64>,
+    daemon_version: &'a str,
+    database_path: Option<&'a str>,
+    /// `null` until the shares are generated.
+    fingerprint: Option<&'a str>,
+    /// How many times the shares have been refreshed.
+    share_epoch: u64,
+    /// The store key's cipher, e.g. `AES-256-GCM`.
+    key_algorithm: String,
+    /// Whether the store key is derived from the shares through HKDF.
+    kdf: bool,
+    /// Whether the daemon refuses changes to the store.
+    read_only: bool,
+    /// The values due to be rotated, with `--warnings`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warnings: Option<Vec<WarningRecord<'a>>>,
+}
+
+/// A value due to be rotated, as `status --warnings` reports it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct WarningRecord<'a> {
+    key: &'a str,
+    rotate_after_secs: u64,
+    /// `null` for a value written before write times were recorded.
+    written: Option<u64>,
+    /// `null` when it is not known when the value was written.
+    due_at: Option<u64>,
+}
+
+impl<'a> WarningRecord<'a> {
+    pub(crate) fn new(warning: &'a libsalus::RotationWarning) -> Self {
+        Self {
+            key: warning.key(),
+            rotate_after_secs: warning.rotate_after(),
+            written: warning.written(),
+            due_at: warning.due_at(),
+        }
+    }
+}
+
+impl<'a> DaemonStatusRecord<'a> {
+    pub(crate) fn new(status: &'a libsalus::StoreStatus) -> Self {
+        Self {
+            initialized: status.initialized(),
+            sealed: status.sealed(),
+            threshold: status.threshold(),
+            num_shares: status.num_shares(),
+            shares_collected: status.shares_collected(),
+            key_timeout_remaining_secs: status.key_timeout_remaining(),
+            daemon_version: status.daemon_version(),
+            database_path: status.database_path().as_deref(),
+            fingerprint: status.fingerprint().as_deref(),
+            share_epoch: status.share_epoch(),
+            key_algorithm: status.key_algorithm().to_string(),
+            kdf: status.kdf(),
+            read_only: status.read_only(),
+            warnings: None,
+        }
+    }
+
+    /// Add the values due to be rotated, when they were asked for.
+    pub(crate) fn with_warnings(
+        mut self,
+        warnings: Option<&'a [libsalus::RotationWarning]>,
+    ) -> Self {
+        self.warnings = warnings.map(|warnings| warnings.iter().map(WarningRecord::new).collect());
+        self
+    }
+}
+
+/// The result of `read-only`: the mode the daemon is now in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ReadOnlyRecord {
+    read_only: bool,
+}
+
+impl ReadOnlyRecord {
+    pub(crate) fn new(read_only: bool) -> Self {
+        Self { read_only }
+    }
+}
+
+/// The result of `reload-config`: the settings the daemon took up.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ReloadRecord<'a> {
+    applied: &'a [String],
+}
+
+impl<'a> ReloadRecord<'a> {
+    pub(crate) fn new(applied: &'a [String]) -> Self {
+        Self { applied }
+    }
+}
+
+/// The result of `import`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ImportRecord<'a> {
+    dry_run: bool,
+    /// Whether the entries were written.
+    applied: bool,
+    /// Every key that was (or would be) written.
+    keys: &'a [String],
+    /// The subset of `keys` that already existed.
+    overwritten: &'a [String],
+    /// Existing keys that blocked the import because `--force` was not given.
+    conflicts: &'a [String],
+}
+
+impl<'a> ImportRecord<'a> {
+    pub(crate) fn new(outcome: &'a libsalus::BatchOutcome, dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            applied: outcome.applied(),
+            keys: outcome.keys(),
+            overwritten: outcome.overwritten(),
+            conflicts: outcome.conflicts(),
+        }
+    }
+}
+
+/// The result of `export-keychain` / `export-wincred`.
+#[cfg(any(target_os = "macos", windows))]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CredentialExportRecord<'a> {
+    /// The credential store written to.
+    store: &'a str,
+    /// Every key saved as a credential, with the prefix stripped.
+    keys: &'a [String],
+}
+
+#[cfg(any(target_os = "macos", windows))]
+impl<'a> CredentialExportRecord<'a> {
+    pub(crate) fn new(store: &'a str, keys: &'a [String]) -> Self {
+        Self { store, keys }
+    }
+}
+
+/// The result of `import-vault`: what became of each secret.
+#[cfg(feature = "vault")]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct VaultImportRecord<'a> {
+    dry_run: bool,
+    secrets: &'a [crate::vault::Mapping],
+}
+
+#[cfg(feature = "vault")]
+impl<'a> VaultImportRecord<'a> {
+    pub(crate) fn new(secrets: &'a [crate::vault::Mapping], dry_run: bool) -> Self {
+        Self { dry_run, secrets }
+    }
+}
+
+/// The result of `sync`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SyncRecord<'a> {
+    dry_run: bool,
+    /// Whether the values were written.
+    applied: bool,
+    /// Keys the destination did not hold.
+    copied: &'a [String],
+    /// Keys whose values at the destination were replaced.
+    overwritten: &'a [String],
+    /// Keys the destination held and kept.
+    skipped: &'a [String],
+    /// Keys stored in chunks at the source, which were not copied.
+    streamed: &'a [String],
+}
+
+impl<'a> SyncRecord<'a> {
+    pub(crate) fn new(
+        outcome: &'a libsalus::SyncOutcome,
+        streamed: &'a [String],
+        dry_run: bool,
+    ) -> Self {
+        Self {
+            dry_run,
+            applied: outcome.applied(),
+            copied: outcome.copied(),
+            overwritten: outcome.overwritten(),
+            skipped: outcome.skipped(),
+            streamed,
+        }
+    }
+}
+
+/// The result of `signing-key`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SigningKeyRecord<'a> {
+    name: &'a str,
+    algorithm: String,
+    /// The public key in hex; `null` for an HMAC key.
+    public_key: Option<String>,
+}
+
+impl<'a> SigningKeyRecord<'a> {
+    pub(crate) fn new(name: &'a str, algorithm: String, public_key: Option<String>) -> Self {
+        Self {
+            name,
+            algorithm,
+            public_key,
+        }
+    }
+}
+
+/// The result of `sign` and `hmac`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SignatureRecord<'a> {
+    key: &'a str,
+    /// The signature or tag in hex.
+    signature: String,
+}
+
+impl<'a> SignatureRecord<'a> {
+    pub(crate) fn new(key: &'a str, signature: String) -> Self {
+        Self { key, signature }
+    }
+}
+
+/// The result of `verify` for a matching signature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SignatureCheckRecord {
+    /// Always `true`; a mismatch is rendered as an [`ErrorRecord`].
+    valid: bool,
+}
+
+impl SignatureCheckRecord {
+    pub(crate) fn new() -> Self {
+        Self { valid: true }
+    }
+}
+
+/// The result of `data-key` and `data-key decrypt`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DataKeyRecord {
+    /// The data key in hex.
+    plaintext: String,
+    /// The wrapped data key in hex; omitted by `data-key decrypt`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ciphertext: Option<String>,
+}
+
+impl DataKeyRecord {
+    pub(crate) fn new(plaintext: String, ciphertext: Option<String>) -> Self {
+        Self {
+            plaintext,
+            ciphertext,
+        }
+    }
+}
+
+/// The result of `encrypt`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct CiphertextRecord {
+    /// The ciphertext, in hex.
+    ciphertext: String,
+    /// Whether equal values give equal ciphertexts.
+    deterministic: bool,
+}
+
+impl CiphertextRecord {
+    pub(crate) fn new(ciphertext: String, deterministic: bool) -> Self {
+        Self {
+            ciphertext,
+            deterministic,
+        }
+    }
+}
+
+/// The result of `decrypt`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct PlaintextRecord<'a> {
+    /// The UTF-8 value, or `null` when the bytes are not valid UTF-8.
+    plaintext: Option<&'a str>,
+    /// The value, in hex.
+    hex: String,
+}
+
+impl<'a> PlaintextRecord<'a> {
+    pub(crate) fn new(raw: &'a [u8], hex: String) -> Self {
+        Self {
+            plaintext: str::from_utf8(raw).ok(),
+            hex,
+        }
+    }
+}
+
+/// The result of `wrapping-key`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct WrappingKeyRecord {
+    /// The X25519 public key to wrap to, in hex.
+    public_key: String,
+}
+
+impl WrappingKeyRecord {
+    pub(crate) fn new(public_key: String) -> Self {
+        Self { public_key }
+    }
+}
+
+/// The result of `export-wrapped` and `wrap`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct WrappedRecord {
+    /// The wrapped value, in hex.
+    wrapped: String,
+}
+
+impl WrappedRecord {
+    pub(crate) fn new(wrapped: String) -> Self {
+        Self { wrapped }
+    }
+}
+
+/// The result of `random`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RandomRecord<'a> {
+    /// The random bytes in the requested encoding, or the UUID.
+    value: &'a str,
+}
+
+impl<'a> RandomRecord<'a> {
+    pub(crate) fn new(value: &'a str) -> Self {
+        Self { value }
+    }
+}
+
+/// The result of `encrypt-file` and `decrypt-file`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct FileRecord {
+    /// The file written.
+    output: String,
+    /// The plaintext bytes encrypted or decrypted.
+    bytes: u64,
+}
+
+impl FileRecord {
+    pub(crate) fn new(output: &Path, bytes: u64) -> Self {
+        Self {
+            output: output.display().to_string(),
+            bytes,
+        }
+    }
+}
+
+/// The result of `gen` and `generate`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct GeneratedRecord<'a> {
+    /// The generated value; omitted by `generate` unless `--show` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a str>,
+    /// The key the value was stored under, when it was stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stored_as: Option<&'a str>,
+}
+
+impl<'a> GeneratedRecord<'a> {
+    pub(crate) fn new(value: Option<&'a str>, stored_as: Option<&'a str>) -> Self {
+        Self { value, stored_as }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use serde_json::{Value, json};
+
+    use super::{ErrorRecord, KeysRecord, OutputFormat, StatusRecord, ValueRecord};
+    use crate::error::Error;
+
+    #[test]
+    fn value_record_json_field_names_are_stable() -> Result<()> {
+        let json = OutputFormat::Json.render(&ValueRecord::new("db/pass", b"hunter2"))?;
+        let parsed: Value = serde_json::from_str(&json)?;
+        assert_eq!(parsed.pointer("/key"), Some(&json!("db/pass")));
+        assert_eq!(parsed.pointer("/value"), Some(&json!("hunter2")));
+        assert_eq!(parsed.pointer("/bytes"), Some(&json!(7)));
+        Ok(())
+    }
+
+    #[test]
+    fn non_utf8_value_renders_null() -> Result<()> {
+        let json = OutputFormat::Json.render(&ValueRecord::new("bin", &[0xff, 0xfe]))?;
+        let parsed: Value = serde_json::from_str(&json)?;
+        assert_eq!(parsed.pointer("/value"), Some(&Value::Null));
+        assert_eq!(parsed.pointer("/bytes"), Some(&json!(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn error_record_nests_kind_and_message() -> Result<()> {
+        let json = OutputFormat::Json.render(&ErrorRecord::new("key_not_found", "nope"))?;
+        let parsed: Value = serde_json::from_str(&json)?;
+        assert_eq!(parsed.pointer("/error/kind"), Some(&json!("key_not_found")));
+        assert_eq!(parsed.pointer("/error/message"), Some(&json!("nope")));
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_renders_keys_and_status() -> Result<()> {
+        let keys = vec!["a".to_string(), "b".to_string()];
+        let yaml = OutputFormat::Yaml.render(&KeysRecord::new(&keys))?;
+        assert!(yaml.contains("keys:"));
+        assert!(yaml.contains("- a"));
+
+        let yaml = OutputFormat::Yaml.render(&StatusRecord::new("lock", None))?;
+        assert!(yaml.contains("ok: true"));
+        assert!(!yaml.contains("key:"));
+        Ok(())
+    }
+
+    #[test]
+    fn fail_returns_exit_error() {
+        let err = OutputFormat::Json.fail("daemon_error", "boom").err();
+        assert!(matches!(
+            err.as_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::Exit(1))
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Printable "paper key" pages, for `shares --paper`.
+//!
+//! Each share gets one self-contained HTML page (inline CSS and an inline SVG
+//! QR code, nothing fetched) laid out for a single printed sheet, so every
+//! share in a safe looks the same. Use the browser's "Print to PDF" for a PDF.
+
+use bon::Builder;
+
+use crate::qr::{self, QrCode};
+
+/// One share's page.
+#[derive(Builder, Clone, Copy, Debug)]
+pub(crate) struct PaperPage<'a> {
+    /// This share's position, from 1.
+    number: usize,
+    /// How many shares the set has.
+    total: usize,
+    /// How many shares unlock the store.
+    threshold: u8,
+    /// The share as the custodian should type it.
+    share: &'a str,
+    /// The QR code of the share's envelope.
+    qr: &'a QrCode,
+    /// When the shares were generated.
+    created: &'a str,
+    /// The store fingerprint, when the daemon reported one.
+    fingerprint: Option<&'a str>,
+}
+
+impl PaperPage<'_> {
+    /// The page as an HTML document.
+    pub(crate) fn render(&self) -> String {
+        let Self {
+            number,
+            total,
+            threshold,
+            share,
+            qr,
+            created,
+            fingerprint,
+        } = *self;
+        let fingerprint = escape(fingerprint.unwrap_or("unknown"));
+        let share = escape(share);
+        let created = escape(created);
+        let svg = qr::to_svg(qr);
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>salus share {number} of {total}</title>
+<style>
+  @page {{ size: auto; margin: 18mm; }}
+  body {{ font-family: sans-serif; color: #000; max-width: 170mm; margin: 0 auto; }}
+  h1 {{ font-size: 20pt; margin-bottom: 2mm; }}
+  table {{ border-collapse: collapse; margin: 4mm 0; }}
+  th {{ text-align: left; padding-right: 6mm; }}
+  .share {{ font-family: monospace; font-size: 14pt; border: 1px solid #000; padding: 4mm; word-break: break-all; }}
+  .qr {{ width: 60mm; height: 60mm; margin: 6mm 0; }}
+  li {{ margin-bottom: 2mm; }}
+</style>
+</head>
+<body>
+<h1>salus key share {number} of {total}</h1>
+<table>
+<tr><th>Shares needed to unlock</th><td>{threshold} of {total}</td></tr>
+<tr><th>Store fingerprint</th><td><code>{fingerprint}</code></td></tr>
+<tr><th>Created</th><td>{created}</td></tr>
+</table>
+<div class="share">{share}</div>
+<div class="qr">{svg}</div>
+<h2>Instructions</h2>
+<ol>
+<li>This page is one share of the key protecting a salus store. Any {threshold} shares unlock the store; fewer reveal nothing about the key.</li>
+<li>Keep it in a secure, access-controlled place, apart from the other shares. Do not photograph it, copy it to shared storage, or send it by email or chat.</li>
+<li>Before using it, check that <code>salusc status</code> reports the fingerprint above.</li>
+<li>To unlock, run <code>salusc unlock</code> and type the share above (or paste it from the QR code) when prompted. A typo is reported straight away, with the group it is in.</li>
+<li>If this page is lost or may have been seen by someone else, tell the store's operators so the shares can be reissued.</li>
+</ol>
+</body>
+</html>
+"#
+        )
+    }
+}
+
+/// Escape `text` for an HTML text node or attribute.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::{PaperPage, escape};
+    use crate::qr::QrCode;
+
+    #[test]
+    fn page_holds_the_share_and_its_details() -> Result<()> {
+        let qr = QrCode::encode(b"salus1-2-ABCD")?;
+        let page = PaperPage::builder()
+            .number(2)
+            .total(5)
+            .threshold(3)
+            .share("salus1-2-ABCD")
+            .qr(&qr)
+            .created("2025-01-02 03:04 UTC")
+            .fingerprint("0a1b-2c3d-4e5f-6071")
+            .build()
+            .render();
+        assert!(page.contains("<title>salus share 2 of 5</title>"));
+        assert!(page.contains("<td>3 of 5</td>"));
+        assert!(page.contains("<div class=\"share\">salus1-2-ABCD</div>"));
+        assert!(page.contains("0a1b-2c3d-4e5f-6071"));
+        assert!(page.contains("2025-01-02 03:04 UTC"));
+        assert!(page.contains("<svg "));
+        Ok(())
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(
+            escape("<a href=\"x\">&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
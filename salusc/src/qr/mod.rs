@@ -0,0 +1,146 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! QR codes for `shares --qr`, `--qr-png`, and `--paper`.
+//!
+//! The symbol is encoded by the `qrcode` crate, at error-correction level M
+//! and at most version 10 (up to 213 bytes), which comfortably fits a share
+//! and keeps a code on paper large enough to scan. This module draws it, for a
+//! terminal here, as SVG in `svg` and as PNG in `png`.
+
+use anyhow::{Result, bail};
+use qrcode::{Color, EcLevel};
+
+mod png;
+mod svg;
+
+pub(crate) use self::{png::to_png, svg::to_svg};
+
+/// Light modules around the symbol, as the spec requires.
+const QUIET_ZONE: usize = 4;
+
+/// The width in modules of a version 10 symbol, the largest encoded.
+const MAX_SIZE: usize = 57;
+
+/// An encoded QR symbol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data` in the smallest version that holds it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` does not fit in a version 10 symbol.
+    pub(crate) fn encode(data: &[u8]) -> Result<Self> {
+        let too_long = || format!("{} bytes is too long to fit in a QR code", data.len());
+        let Ok(code) = qrcode::QrCode::with_error_correction_level(data, EcLevel::M) else {
+            bail!(too_long());
+        };
+        let size = code.width();
+        if size > MAX_SIZE {
+            bail!(too_long());
+        }
+        let modules = code
+            .into_colors()
+            .into_iter()
+            .map(|color| color == Color::Dark)
+            .collect();
+        Ok(Self { size, modules })
+    }
+
+    /// The width (and height) in modules, without the quiet zone.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the module at column `x`, row `y` is dark; anything outside the
+    /// symbol (the quiet zone) is light.
+    pub(crate) fn dark(&self, x: usize, y: usize) -> bool {
+        self.index(x, y)
+            .and_then(|idx| self.modules.get(idx))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Render for a terminal, two rows of modules per line.
+    ///
+    /// Light modules are drawn as block characters and dark ones as spaces, so
+    /// the code reads correctly on the usual light-text-on-dark terminal.
+    pub(crate) fn to_terminal(&self) -> String {
+        let span = self.size.saturating_add(QUIET_ZONE.saturating_mul(2));
+        let light = |x: usize, y: usize| {
+            !(x.checked_sub(QUIET_ZONE)
+                .zip(y.checked_sub(QUIET_ZONE))
+                .is_some_and(|(x, y)| self.dark(x, y)))
+        };
+        let mut out = String::new();
+        for y in (0..span).step_by(2) {
+            for x in 0..span {
+                let top = light(x, y);
+                let bottom = y.saturating_add(1) < span && light(x, y.saturating_add(1));
+                out.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.size && y < self.size {
+            y.checked_mul(self.size)?.checked_add(x)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::QrCode;
+
+    #[test]
+    fn version_grows_with_the_data() -> Result<()> {
+        assert_eq!(QrCode::encode(b"short")?.size(), 21);
+        let share = "AQ==:3q2+7wABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhs=";
+        let qr = QrCode::encode(share.as_bytes())?;
+        assert_eq!(qr.size(), 33);
+        // Finder pattern corners are dark, their separators light.
+        assert!(qr.dark(0, 0) && qr.dark(32, 0) && qr.dark(0, 32));
+        assert!(!qr.dark(7, 7) && !qr.dark(25, 7));
+        assert!(QrCode::encode(&[b'x'; 214]).is_err());
+        assert_eq!(QrCode::encode(&[b'x'; 213])?.size(), 57);
+        Ok(())
+    }
+
+    #[test]
+    fn terminal_rendering_includes_the_quiet_zone() -> Result<()> {
+        let qr = QrCode::encode(b"salus")?;
+        let rendered = qr.to_terminal();
+        let lines: Vec<&str> = rendered.lines().collect();
+        // 29 rows, two per line.
+        assert_eq!(lines.len(), 15);
+        assert!(lines.iter().all(|line| line.chars().count() == 29));
+        assert!(
+            lines
+                .first()
+                .is_some_and(|line| line.chars().all(|c| c == '█'))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! PNG images of QR codes, 8-bit grayscale, written by the `png` crate.
+
+use ::png::{BitDepth, ColorType, Encoder};
+use anyhow::{Result, bail};
+
+use super::{QUIET_ZONE, QrCode};
+
+/// Render `qr` as a PNG with each module `scale` pixels square, quiet zone
+/// included.
+///
+/// # Errors
+///
+/// Returns an error if `scale` is zero or the image would be too large.
+pub(crate) fn to_png(qr: &QrCode, scale: usize) -> Result<Vec<u8>> {
+    if scale == 0 {
+        bail!("the PNG scale must be at least 1");
+    }
+    let modules = qr.size().saturating_add(QUIET_ZONE.saturating_mul(2));
+    let Some(pixels) = modules.checked_mul(scale) else {
+        bail!("QR code image is too large");
+    };
+    let Ok(dimension) = u32::try_from(pixels) else {
+        bail!("QR code image is too large");
+    };
+
+    let mut raw = Vec::with_capacity(pixels.saturating_mul(pixels));
+    for py in 0..pixels {
+        for px in 0..pixels {
+            let dark = px
+                .checked_div(scale)
+                .and_then(|x| x.checked_sub(QUIET_ZONE))
+                .zip(
+                    py.checked_div(scale)
+                        .and_then(|y| y.checked_sub(QUIET_ZONE)),
+                )
+                .is_some_and(|(x, y)| qr.dark(x, y));
+            raw.push(if dark { 0x00 } else { 0xFF });
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = Encoder::new(&mut png, dimension, dimension);
+    encoder.set_color(ColorType::Grayscale);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&raw)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use ::png::{BitDepth, ColorType, Decoder};
+    use anyhow::Result;
+
+    use super::{QUIET_ZONE, QrCode, to_png};
+
+    #[test]
+    fn png_decodes_to_the_symbol_and_quiet_zone() -> Result<()> {
+        let qr = QrCode::encode(b"salus")?;
+        let png = to_png(&qr, 4)?;
+        let mut reader = Decoder::new(Cursor::new(png)).read_info()?;
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let frame = reader.next_frame(&mut pixels)?;
+        // (21 + 8) * 4 pixels square.
+        assert_eq!((frame.width, frame.height), (116, 116));
+        assert_eq!(
+            (frame.color_type, frame.bit_depth),
+            (ColorType::Grayscale, BitDepth::Eight)
+        );
+        for y in 0..116 {
+            for x in 0..116 {
+                let module = |at: usize| (at / 4).checked_sub(QUIET_ZONE);
+                let dark = module(x).zip(module(y)).is_some_and(|(x, y)| qr.dark(x, y));
+                let pixel = pixels.get(y * 116 + x).copied();
+                assert_eq!(pixel, Some(if dark { 0x00 } else { 0xFF }), "({x}, {y})");
+            }
+        }
+        assert!(to_png(&qr, 0).is_err());
+        Ok(())
+    }
+}
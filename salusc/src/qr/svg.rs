@@ -0,0 +1,54 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Inline SVG for QR codes, so a paper page is a single self-contained file.
+
+use std::fmt::Write as _;
+
+use super::{QUIET_ZONE, QrCode};
+
+/// Render `qr` as an SVG document one unit per module, quiet zone included;
+/// it scales to whatever size the page gives it.
+pub(crate) fn to_svg(qr: &QrCode) -> String {
+    let size = qr.size();
+    let span = size.saturating_add(QUIET_ZONE.saturating_mul(2));
+    let mut path = String::new();
+    for y in 0..size {
+        for x in 0..size {
+            if qr.dark(x, y) {
+                // Writing to a `String` cannot fail.
+                let _ = write!(
+                    path,
+                    "M{},{}h1v1h-1z",
+                    x.saturating_add(QUIET_ZONE),
+                    y.saturating_add(QUIET_ZONE)
+                );
+            }
+        }
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {span} {span}\" shape-rendering=\"crispEdges\"><rect width=\"{span}\" height=\"{span}\" fill=\"#fff\"/><path d=\"{path}\" fill=\"#000\"/></svg>"
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::{QrCode, to_svg};
+
+    #[test]
+    fn svg_covers_the_symbol_and_quiet_zone() -> Result<()> {
+        let svg = to_svg(&QrCode::encode(b"salus")?);
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>"));
+        assert!(svg.contains("viewBox=\"0 0 29 29\""));
+        // The top-left finder pattern starts just inside the quiet zone.
+        assert!(svg.contains("M4,4h1v1h-1z"));
+        Ok(())
+    }
+}
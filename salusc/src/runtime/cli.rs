@@ -6,8 +6,21 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap::{
+    ArgAction, Parser, Subcommand, ValueEnum, builder::NonEmptyStringValueParser, value_parser,
+};
+use clap_complete::Shell;
 use config::{ConfigError, Map, Source, Value, ValueKind};
+use libsalus::{Charset, GenerateSecret, KeyAlgorithm, SecretSpec, SigningAlgorithm, SyncStrategy};
+
+use std::path::PathBuf;
+
+use crate::{
+    config::ConfigFormat,
+    exec::NameTransform,
+    formats::{FileFormat, ImportFormat},
+    output::OutputFormat,
+};
 
 /// Command-line client for the salus secret store.
 ///
@@ -40,6 +53,13 @@ pub(crate) struct Cli {
     /// Config file path
     #[clap(short, long, help = "Specify a path to the config file")]
     config_path: Option<String>,
+    /// The config file's format, when its extension does not name it
+    #[clap(
+        long,
+        value_enum,
+        help = "Read the config file as toml, yaml or json, whatever its extension"
+    )]
+    config_format: Option<ConfigFormat>,
     /// Override the IPC socket path (otherwise the shared `SALUS_SOCKET` env var
     /// or the platform default is used)
     #[clap(short, long, help = "Specify the path to the IPC socket")]
@@ -52,6 +72,55 @@ pub(crate) struct Cli {
         help = "Specify the path to the salus-agent IPC socket"
     )]
     agent_socket_path: Option<String>,
+    /// Prepended, with a `/`, to the key names and prefixes commands are given
+    #[clap(
+        long,
+        global = true,
+        value_name = "NAMESPACE",
+        help = "Namespace the keys and prefixes given to commands live under"
+    )]
+    namespace: Option<String>,
+    /// Render results as styled text (the default), JSON, or YAML
+    #[clap(
+        short,
+        long,
+        global = true,
+        value_enum,
+        help = "Output format for results and errors"
+    )]
+    output: Option<OutputFormat>,
+    /// Seconds to wait for the daemon to accept a connection (default 5)
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds to wait for the daemon to accept a connection"
+    )]
+    connect_timeout: Option<u64>,
+    /// Seconds to wait for the daemon to answer, 0 to wait for as long as it
+    /// takes (default 120)
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds to wait for the daemon to answer (0 waits forever)"
+    )]
+    request_timeout: Option<u64>,
+    /// Times to retry a connection that fails for a transient reason, such as
+    /// a daemon still starting up (default 3)
+    #[clap(
+        long,
+        value_name = "COUNT",
+        help = "Times to retry a connection that fails for a transient reason"
+    )]
+    retries: Option<u32>,
+    /// Start salusd when no daemon is listening on the socket
+    #[clap(long, help = "Start salusd when no daemon is listening")]
+    auto_start: bool,
+    /// Ask the daemon to time each request and print where the time went
+    #[clap(
+        long,
+        help = "Print how long the daemon spent queueing, in storage, and in crypto on each request"
+    )]
+    timing: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -64,6 +133,10 @@ impl Cli {
     pub(crate) fn config_path(&self) -> Option<&str> {
         self.config_path.as_deref()
     }
+
+    pub(crate) fn config_format(&self) -> Option<ConfigFormat> {
+        self.config_format
+    }
 }
 
 impl Source for Cli {
@@ -76,7 +149,8 @@ impl Source for Cli {
         let origin = String::from("command line");
         // Only emit flags the user actually set, so CLI defaults do not clobber
         // values from the lower-precedence env/file sources. The `config_path`
-        // override is consumed directly to locate the file, not layered here.
+        // and `config_format` overrides are consumed directly to read the
+        // file, not layered here.
         if self.verbose > 0 {
             let _old = map.insert(
                 "verbose".to_string(),
@@ -101,6 +175,51 @@ impl Source for Cli {
                 Value::new(Some(&origin), ValueKind::String(agent_socket_path.clone())),
             );
         }
+        if let Some(namespace) = &self.namespace {
+            let _old = map.insert(
+                "namespace".to_string(),
+                Value::new(Some(&origin), ValueKind::String(namespace.clone())),
+            );
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            let _old = map.insert(
+                "connect_timeout".to_string(),
+                Value::new(Some(&origin), ValueKind::U64(connect_timeout)),
+            );
+        }
+        if let Some(request_timeout) = self.request_timeout {
+            let _old = map.insert(
+                "request_timeout".to_string(),
+                Value::new(Some(&origin), ValueKind::U64(request_timeout)),
+            );
+        }
+        if let Some(retries) = self.retries {
+            let _old = map.insert(
+                "retries".to_string(),
+                Value::new(Some(&origin), ValueKind::U64(u32::into(retries))),
+            );
+        }
+        if self.auto_start {
+            let _old = map.insert(
+                "auto_start".to_string(),
+                Value::new(Some(&origin), ValueKind::Boolean(true)),
+            );
+        }
+        if self.timing {
+            let _old = map.insert(
+                "timing".to_string(),
+                Value::new(Some(&origin), ValueKind::Boolean(true)),
+            );
+        }
+        if let Some(output) = self.output {
+            let _old = map.insert(
+                "output".to_string(),
+                Value::new(
+                    Some(&origin),
+                    ValueKind::String(output.as_str().to_string()),
+                ),
+            );
+        }
         Ok(map)
     }
 }
@@ -111,14 +230,62 @@ pub(crate) enum Commands {
     ///
     /// Generates a fresh master key, splits it into Shamir shares, and prints
     /// them a single time. Record them somewhere safe — they are required to
-    /// `unlock` the store and are never shown again.
+    /// `unlock` the store and are never shown again. `shares refresh` reissues
+    /// them later.
     Shares {
-        /// The number of shares to create
-        #[arg(short, long, default_value = "5", value_name = "COUNT")]
-        num_shares: u8,
-        /// The number of shares required to reconstruct the key
-        #[arg(short, long, default_value = "3", value_name = "COUNT")]
-        threshold: u8,
+        #[command(subcommand)]
+        action: Option<SharesAction>,
+        /// The number of shares to create (default: the daemon's `[shares]`
+        /// setting, 5 unless configured)
+        #[arg(short, long, value_name = "COUNT", value_parser = value_parser!(u8).range(2..))]
+        num_shares: Option<u8>,
+        /// The number of shares required to reconstruct the key (default: the
+        /// daemon's `[shares]` setting, 3 unless configured)
+        #[arg(short, long, value_name = "COUNT", value_parser = value_parser!(u8).range(2..))]
+        threshold: Option<u8>,
+        /// The AES-GCM key size for the new store
+        #[arg(long, value_enum, value_name = "BITS", default_value_t = KeyBits::Bits256)]
+        key_bits: KeyBits,
+        /// Derive the store key from the reconstructed secret with HKDF-SHA256
+        /// and a stored salt, rather than using the secret directly
+        #[arg(long)]
+        kdf: bool,
+        /// Show each share as mnemonic words instead of a string; `unlock` and
+        /// `enroll` accept either form
+        #[arg(long, global = true)]
+        mnemonic: bool,
+        /// Also draw each share as a QR code in the terminal
+        #[arg(long, global = true, conflicts_with_all = ["out_dir", "paper"])]
+        qr: bool,
+        /// Also save each share as a QR code PNG (`share-1-of-5.png`, `0600`) in
+        /// this directory
+        #[arg(long, global = true, value_name = "DIR")]
+        qr_png: Option<PathBuf>,
+        /// Write each share to its own file (`share-1-of-5.txt`, `0600`) in
+        /// this directory instead of printing them, for handing to separate
+        /// custodians
+        #[arg(long, global = true, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+        /// Write a printable page per share (`share-1-of-5.html`, `0600`) in
+        /// this directory instead of printing them: the share, its QR code,
+        /// the creation date, the store fingerprint, and instructions
+        #[arg(long, global = true, value_name = "DIR")]
+        paper: Option<PathBuf>,
+        /// Write each share onto its own hardware token instead of printing
+        /// them: prompts for each token in turn and runs CMD (through the
+        /// shell) with the share on stdin and `SALUS_SHARE_NUMBER` /
+        /// `SALUS_SHARE_TOTAL` set
+        #[arg(
+            long,
+            global = true,
+            value_name = "CMD",
+            conflicts_with_all = ["qr", "qr_png", "out_dir", "paper"]
+        )]
+        token_cmd: Option<String>,
+        /// Write each share into a PIV data object on its own `YubiKey`, with
+        /// `ykman`; shorthand for a `--token-cmd`
+        #[arg(long, global = true, conflicts_with_all = ["token_cmd", "qr", "qr_png", "out_dir", "paper"])]
+        yubikey_piv: bool,
     },
     /// Reconstruct the key in the daemon's memory from `threshold` shares
     ///
@@ -137,6 +304,42 @@ pub(crate) enum Commands {
     },
     /// Clear the daemon's unlocked key and cancel any pending auto-clear timer
     Lock,
+    /// Show whether the store is initialized and sealed, unlock progress, and
+    /// daemon details
+    ///
+    /// Exits with status 2 when the store is sealed, so scripts can branch on
+    /// `salusc status` without parsing its output.
+    Status {
+        /// Also list the values due to be rotated (see `rotate-after`), once
+        /// the store is unlocked
+        #[arg(short, long)]
+        warnings: bool,
+    },
+    /// Have the daemon refuse, or accept again, changes to the store
+    ///
+    /// While read-only the daemon still serves reads, so it can stay up
+    /// during a backup or a migration. Turning it off needs the store
+    /// unlocked.
+    ReadOnly {
+        /// Whether changes are refused
+        #[arg(value_enum)]
+        mode: Switch,
+    },
+    /// Have the daemon load its configuration again, without a restart
+    ///
+    /// Timeouts, limits, keepalive and logging settings are taken up, for
+    /// connections opened from then on. A change to any other setting needs
+    /// a restart: the daemon then keeps its configuration as it was, and this
+    /// exits with status 1 naming the settings. Sending the daemon `SIGHUP`
+    /// does the same.
+    ReloadConfig,
+    /// Check that a share belongs to the store's current share set, without
+    /// unlocking
+    ///
+    /// Prompts (without echo) for one share in any form. Lets a custodian
+    /// confirm their share is still good without convening a quorum. Exits
+    /// with status 1 when the share is not one of the current shares.
+    VerifyShare,
     /// Encrypt and store a value under a key
     ///
     /// Provide the value as the second argument, or omit it to read the value
@@ -150,29 +353,415 @@ pub(crate) enum Commands {
         /// The value to store; if omitted, it is read from stdin
         #[arg(value_name = "VALUE")]
         value: Option<String>,
-        /// Maximum bytes to read from stdin (default: 65536)
+        /// The longest value taken, from stdin or the command line (default:
+        /// 65536)
         #[arg(long, value_name = "BYTES")]
         max_value_bytes: Option<usize>,
         /// Overwrite an existing value without prompting for confirmation
         #[arg(short, long)]
         force: bool,
+        /// Never replace an existing value: fail, with status 1, when the key
+        /// already holds one
+        #[arg(long, conflicts_with = "force")]
+        no_overwrite: bool,
+        /// Seal the value under this named key instead of the store key
+        #[arg(long, value_name = "NAME")]
+        with_key: Option<String>,
     },
     /// Read and decrypt the value stored under a key
     ///
-    /// The store must be unlocked first.
+    /// The store must be unlocked first. With `--clip` the value is copied to
+    /// the clipboard instead of printed, and cleared again after the clip
+    /// timeout (default 45 seconds).
     Read {
         /// The key to read the value from
         #[arg(value_name = "KEY")]
         key: String,
+        /// Copy the value to the clipboard instead of printing it
+        #[arg(short, long)]
+        clip: bool,
+        /// Seconds before the clipboard is cleared (default: 45)
+        #[arg(long, value_name = "SECONDS", requires = "clip")]
+        clip_timeout: Option<u64>,
+        /// Read only this field of a JSON value: a top-level name, or a JSON
+        /// pointer such as `/tls/cert`
+        #[arg(long, value_name = "FIELD")]
+        field: Option<String>,
+    },
+    /// Store a file of any size under a key
+    ///
+    /// The file is sent in 512 KiB chunks, each sealed on its own by the
+    /// daemon, so neither side holds it whole. The daemon refuses files above
+    /// its `[streaming] max_bytes` (default 1 GiB). Read it back with
+    /// `read-file`. The store must be unlocked first.
+    StoreFile {
+        /// The key to store the file under
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The file to store
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Overwrite an existing key without prompting
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Read a value stored by `store-file`
+    ///
+    /// The value is fetched a chunk at a time and written to stdout byte for
+    /// byte, or with `--out` atomically to a `0600` file. Values stored by
+    /// `store` read the same way. The store must be unlocked first.
+    ReadFile {
+        /// The key to read
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// Write the value here instead of stdout
+        ///
+        /// (`-o` is taken by the global `--output` format flag.)
+        #[arg(short = 'O', long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Overwrite the output file if it exists
+        #[arg(short, long, requires = "out")]
+        force: bool,
+    },
+    /// Change fields of a JSON value in place with a JSON merge patch
+    ///
+    /// The patch (RFC 7396) is applied by the daemon in one write: its members
+    /// replace the stored document's, `null` removes one, and nested objects
+    /// are merged in turn. The store must be unlocked first.
+    Patch {
+        /// The key whose JSON value to patch
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The merge patch, e.g. '{"password":"new"}'; if omitted, it is read
+        /// from stdin
+        #[arg(value_name = "PATCH")]
+        patch: Option<String>,
+        /// Maximum bytes to read from stdin (default: 65536)
+        #[arg(long, value_name = "BYTES")]
+        max_value_bytes: Option<usize>,
+    },
+    /// Make a key an alias of another, so reading it reads the other's value
+    ///
+    /// Use it to move a value to a new path without breaking readers of the
+    /// old one: `salusc link old/path new/path`. Aliases are followed only by
+    /// reads, as far as 8 deep, and a link that would loop is refused. Delete
+    /// the alias with `salusc delete` once nothing reads it.
+    Link {
+        /// The key to read through, e.g. the old path
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+        /// The key whose value reads of the alias return
+        #[arg(value_name = "TARGET")]
+        target: String,
+    },
+    /// Have the value under a key fall due to be rotated a while after each
+    /// write
+    ///
+    /// Once the value has gone unwritten for longer, the daemon logs an audit
+    /// event and runs the hooks that take `overdue`, and `salusc status
+    /// --warnings` lists it, until it is written again. Works on the key as
+    /// stored, not through an alias.
+    RotateAfter {
+        /// The key whose value to track
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// Seconds after each write the value is due again
+        #[arg(
+            value_name = "SECONDS",
+            required_unless_present = "clear",
+            conflicts_with = "clear"
+        )]
+        seconds: Option<u64>,
+        /// Stop tracking the value
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Check whether a key holds a value, without reading it
+    ///
+    /// Exits 0 when it does, 1 when it does not, and 2 when the daemon cannot
+    /// say, so scripts can test with `if salusc exists db/creds; then`.
+    /// Aliases are followed. Reports only what the daemon records outside the
+    /// sealed value: the key it is stored under, its size as stored, and when
+    /// it was last written. Works while the store is sealed.
+    Exists {
+        /// The key to check
+        #[arg(value_name = "KEY")]
+        key: String,
+    },
+    /// List the versions of a value kept for history
+    ///
+    /// Shows each version's number, when it was written and the uid of the
+    /// user who wrote it, oldest first and the current one last. Earlier
+    /// versions are only kept when the daemon sets `[history] keep`. Aliases
+    /// are followed. Works while the store is sealed.
+    History {
+        /// The key whose versions to list
+        #[arg(value_name = "KEY")]
+        key: String,
+    },
+    /// Show which fields changed between two versions of a value
+    ///
+    /// JSON values are compared field by field, naming each changed field by
+    /// its JSON pointer; other values are compared as a whole. The values are
+    /// masked unless `--show-values` is given. Versions are numbers as listed
+    /// by `history`, with or without a leading `v`. The store must be unlocked
+    /// first.
+    Diff {
+        /// The key whose versions to compare
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The earlier version, e.g. `v2`
+        #[arg(value_name = "FROM", value_parser = version)]
+        from: u64,
+        /// The later version, e.g. `v3`
+        #[arg(value_name = "TO", value_parser = version)]
+        to: u64,
+        /// Print the changed fields' values, not only their names
+        #[arg(long)]
+        show_values: bool,
+    },
+    /// Edit the value stored under a key in `$EDITOR`
+    ///
+    /// The decrypted value is written to a private temporary file (on a tmpfs
+    /// where available), opened in `$VISUAL`/`$EDITOR`, and stored again when
+    /// the editor exits successfully with changed content. The temporary file
+    /// is overwritten and removed afterwards. The store must be unlocked first.
+    Edit {
+        /// The key whose value to edit
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// Start from an empty value when the key does not exist yet
+        #[arg(long)]
+        create: bool,
+    },
+    /// Store every entry of a `.env`, JSON, or YAML file, or of a Bitwarden or
+    /// 1Password export, in one atomic write
+    ///
+    /// Nested JSON/YAML objects become `/`-separated key names, and `--prefix`
+    /// is prepended to every key (e.g. `--prefix app/`). Each field of a
+    /// password manager's item becomes `<folder>/<item>/<field>`, adjusted by
+    /// `--map` and `--folder`. If any key already exists nothing is written
+    /// unless `--force` is given; `--dry-run` lists what would be written and
+    /// overwritten. The store must be unlocked first.
+    Import {
+        /// The file to import, or `-` for stdin
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// The file format (default: inferred from the file name)
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Prepended to every imported key name
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Store an item's FIELD as NAME, or drop it when NAME is empty
+        /// (repeatable; Bitwarden and 1Password only)
+        #[arg(long = "map", value_name = "FIELD=NAME", value_parser = rule)]
+        maps: Vec<(String, String)>,
+        /// Store the items of FOLDER, and of the folders within it, under
+        /// PREFIX rather than the folder's own name (repeatable; Bitwarden and
+        /// 1Password only)
+        #[arg(long = "folder", value_name = "FOLDER=PREFIX", value_parser = rule)]
+        folders: Vec<(String, String)>,
+        /// Show what would be written without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Overwrite keys that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Copy the secrets under a Vault KV path into the store
+    ///
+    /// Walks the mount through Vault's HTTP API (KV version 1 or 2) and writes
+    /// each secret in one atomic batch. Every field becomes its own key,
+    /// `<prefix><path>/<field>`, with the path relative to `--path`; with
+    /// `--all-versions` older versions land beside it as
+    /// `<prefix><path>@<version>/<field>`. Secrets whose keys already exist are
+    /// left alone unless `--force` is given. With `--state`, an interrupted
+    /// import picks up where it stopped when rerun. The store must be unlocked
+    /// first.
+    #[cfg(feature = "vault")]
+    ImportVault {
+        /// The Vault path to import: a KV mount or a folder under one (e.g.
+        /// `secret/` or `secret/app/`)
+        #[arg(long, value_name = "PATH")]
+        path: String,
+        /// The Vault server (default: `VAULT_ADDR`)
+        #[arg(long, value_name = "URL")]
+        addr: Option<String>,
+        /// A token able to list and read the path (default: `VAULT_TOKEN`,
+        /// which keeps it out of the process list)
+        #[arg(long, value_name = "TOKEN")]
+        token: Option<String>,
+        /// Prepended to every imported key name
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Also import the older versions of each secret still readable
+        #[arg(long)]
+        all_versions: bool,
+        /// Record each secret imported here, and skip those already recorded
+        #[arg(long, value_name = "FILE")]
+        state: Option<PathBuf>,
+        /// Write a JSON report mapping each Vault path, with its version and
+        /// metadata, to the keys it became
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+        /// Show what would be written without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Overwrite keys that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Store the Keychain's generic passwords in one atomic write
+    ///
+    /// Each password becomes `<prefix><service>/<account>`, or
+    /// `<prefix><service>` when it has no account. The Keychain may ask to
+    /// allow each password another application saved. If any key already
+    /// exists nothing is written unless `--force` is given. The store must be
+    /// unlocked first.
+    #[cfg(target_os = "macos")]
+    ImportKeychain {
+        /// Only import the passwords of this service
+        #[arg(long, value_name = "SERVICE")]
+        service: Option<String>,
+        /// Prepended to every imported key name
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Show what would be written without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Overwrite keys that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Save every secret under a prefix to the Keychain as a generic password
+    ///
+    /// The prefix is stripped, and everything before the last `/` of the rest
+    /// is the service and everything after it the account, so
+    /// `import-keychain --prefix mac/` and `export-keychain --prefix mac/`
+    /// round-trip. Nothing is saved if any password already exists unless
+    /// `--force` is given. The store must be unlocked first.
+    #[cfg(target_os = "macos")]
+    ExportKeychain {
+        /// Only export keys starting with this prefix
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Replace passwords that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Store the Credential Manager's generic credentials in one atomic write
+    ///
+    /// Each credential becomes `<prefix><target>/<user>`, or `<prefix><target>`
+    /// when it has no user name. If any key already exists nothing is written
+    /// unless `--force` is given. The store must be unlocked first.
+    #[cfg(windows)]
+    ImportWincred {
+        /// Only import credentials whose target name matches this, which may
+        /// end in `*` (e.g. `git:*`)
+        #[arg(long, value_name = "PATTERN")]
+        filter: Option<String>,
+        /// Prepended to every imported key name
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Show what would be written without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Overwrite keys that already exist
+        #[arg(short, long)]
+        force: bool,
     },
-    /// Permanently delete the value stored under a key
+    /// Save every secret under a prefix to the Credential Manager as a generic
+    /// credential
     ///
-    /// Prompts for confirmation unless `--force` is given. The store must be
+    /// The prefix is stripped, and everything before the last `/` of the rest
+    /// is the target name and everything after it the user name, so
+    /// `import-wincred --prefix win/` and `export-wincred --prefix win/`
+    /// round-trip. Nothing is saved if any credential already exists unless
+    /// `--force` is given. The store must be unlocked first.
+    #[cfg(windows)]
+    ExportWincred {
+        /// Only export keys starting with this prefix
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// Replace credentials that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Write every secret under a prefix to stdout as `.env`, JSON, or YAML
+    ///
+    /// The prefix is stripped from the exported key names, so
+    /// `salusc export --prefix app/ > .env` and `salusc import --prefix app/ .env`
+    /// round-trip. The output is plaintext: unless `--redact` is given, this
+    /// prompts for confirmation (on stderr) or requires `--force` when stdin is
+    /// not a terminal. The store must be unlocked first.
+    Export {
+        /// Only export keys starting with this prefix
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = FileFormat::Dotenv)]
+        format: FileFormat,
+        /// Replace every value with a placeholder (lists the keys only)
+        #[arg(long)]
+        redact: bool,
+        /// Skip the plaintext confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Run a command with the secrets under a prefix as environment variables
+    ///
+    /// Each key has the prefix stripped and is mapped to a variable name (by
+    /// default upper-cased, with every character other than a letter, digit, or
+    /// `_` replaced by `_`, so `app/db/password` with `--prefix app/` becomes
+    /// `DB_PASSWORD`). The secrets are never written to disk. For example:
+    /// `salusc exec --prefix app/ -- ./server --port 8080`. The store must be
+    /// unlocked first.
+    Exec {
+        /// Only inject keys starting with this prefix
+        #[arg(short, long, value_name = "PREFIX", default_value = "")]
+        prefix: String,
+        /// How key names become variable names (default: upper)
+        #[arg(long, value_enum)]
+        transform: Option<NameTransform>,
+        /// Prepended to every variable name
+        #[arg(long, value_name = "ENV_PREFIX")]
+        env_prefix: Option<String>,
+        /// The command to run, and its arguments
+        #[arg(
+            value_name = "COMMAND",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+    /// Render config files with embedded secrets
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+    /// Permanently delete the value stored under a key, or every key under a
+    /// prefix
+    ///
+    /// Prompts for confirmation unless `--force` is given; a prefix's keys are
+    /// listed first, and deleted together in one write. The store must be
     /// unlocked first.
     Delete {
         /// The key to delete from the store
-        #[arg(value_name = "KEY")]
-        key: String,
+        #[arg(value_name = "KEY", required_unless_present = "prefix")]
+        key: Option<String>,
+        /// Delete every key starting with this prefix instead, e.g. `tmp/`
+        #[arg(
+            long,
+            value_name = "PREFIX",
+            conflicts_with = "key",
+            value_parser = NonEmptyStringValueParser::new()
+        )]
+        prefix: Option<String>,
+        /// Only list the keys under the prefix that would be deleted
+        #[arg(long, conflicts_with = "key")]
+        dry_run: bool,
         /// Skip the confirmation prompt
         #[arg(short, long)]
         force: bool,
@@ -228,6 +817,31 @@ pub(crate) enum Commands {
     },
     /// List the enrolled sets and whether the agent is reachable
     EnrollStatus,
+    /// Hold a value (read from stdin) on the clipboard, then clear it
+    ///
+    /// Internal helper spawned by `read --clip`; not meant to be run directly.
+    #[command(name = "clip-hold", hide = true)]
+    ClipHold {
+        /// Seconds to hold the value before clearing the clipboard
+        #[arg(long, value_name = "SECONDS")]
+        seconds: u64,
+    },
+    /// Print a shell completion script
+    ///
+    /// For bash, zsh, and fish the script also completes key names for `read`,
+    /// `edit`, `delete`, and `store` by asking the daemon (the store must be
+    /// unlocked). For example: `salusc completions bash > ~/.local/share/bash-completion/completions/salusc`.
+    Completions {
+        /// The shell to generate the script for
+        #[arg(value_enum, value_name = "SHELL")]
+        shell: Shell,
+    },
+    /// Dynamic completion hook used by the generated completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[command(subcommand)]
+        target: CompleteTarget,
+    },
     /// Generate a random password or passphrase
     ///
     /// By default produces a 30-character password drawn from lowercase letters
@@ -300,30 +914,833 @@ pub(crate) enum Commands {
         #[arg(short, long, value_name = "KEY")]
         key: Option<String>,
     },
+    /// Have the daemon generate a secret and store it directly
+    ///
+    /// Unlike `gen`, the secret is produced by the daemon's RNG and written
+    /// straight into the store, so it never reaches this terminal unless
+    /// `--show` is given. The store must be unlocked.
+    Generate {
+        #[command(subcommand)]
+        action: GenerateAction,
+    },
+    /// Have the daemon generate a named signing key
+    ///
+    /// The private key never leaves the daemon; `sign`, `hmac`, and `verify`
+    /// use it by name. Prints the public key (hex) for an Ed25519 key. The
+    /// store must be unlocked.
+    SigningKey {
+        /// The signing key's name
+        #[arg(value_name = "NAME")]
+        name: String,
+        /// The kind of key to generate
+        #[arg(short, long, value_enum, default_value_t = SigningKind::Ed25519)]
+        algorithm: SigningKind,
+        /// Replace an existing key of the same name without prompting
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Sign a message with a named Ed25519 key, printing the signature (hex)
+    ///
+    /// The message is read from FILE, or from stdin when omitted, exactly as
+    /// given (no trailing newline is dropped).
+    Sign {
+        /// The signing key's name
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The file holding the message (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Compute an HMAC-SHA256 tag with a named HMAC key, printing it (hex)
+    ///
+    /// The message is read as for `sign`.
+    Hmac {
+        /// The HMAC key's name
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The file holding the message (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Check a signature or HMAC tag (hex) against a named key
+    ///
+    /// The message is read as for `sign`. Exits with status 1 when the
+    /// signature does not match.
+    Verify {
+        /// The signing key's name
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The signature or tag, in hex
+        #[arg(value_name = "SIGNATURE")]
+        signature: String,
+        /// The file holding the message (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Have the daemon generate a data key for envelope encryption
+    ///
+    /// Prints the data key and the same key wrapped by the store key, both in
+    /// hex: encrypt locally with the first, discard it, and keep the second
+    /// beside the data. `data-key decrypt` unwraps it again. The store must be
+    /// unlocked.
+    DataKey {
+        #[command(subcommand)]
+        action: Option<DataKeyAction>,
+        /// The data key size, a multiple of 8 from 128 to 512
+        #[arg(
+            short,
+            long,
+            value_name = "BITS",
+            default_value_t = 256,
+            value_parser = value_parser!(u16).range(128..=512)
+        )]
+        bits: u16,
+    },
+    /// Encrypt a file of any size under a fresh data key
+    ///
+    /// The file is sealed locally in 64 KiB AES-256-GCM chunks, so it is never
+    /// held in memory and never crosses the socket; only the data key, wrapped
+    /// by the store key, is kept in the output's header. Writes
+    /// `<FILE>.enc` unless `--out` is given. The store must be unlocked.
+    EncryptFile {
+        /// The file to encrypt
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+        /// Write the encrypted file here
+        ///
+        /// (`-o` is taken by the global `--output` format flag.)
+        #[arg(short = 'O', long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Overwrite the output file if it exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Draw random bytes from the daemon's CSPRNG
+    ///
+    /// Prints `BYTES` random bytes in hex (the default) or base64, or a random
+    /// version 4 UUID with `--uuid`. Useful on hosts whose own entropy source
+    /// is in doubt. Works while the store is sealed; the daemon caps each
+    /// request at its `max_random_bytes` (default 4096).
+    Random {
+        /// How many bytes to draw
+        #[arg(value_name = "BYTES", default_value_t = 32, conflicts_with = "uuid")]
+        bytes: u32,
+        /// Print the bytes in hex (the default)
+        #[arg(long, group = "encoding")]
+        hex: bool,
+        /// Print the bytes in standard base64
+        #[arg(long, group = "encoding")]
+        base64: bool,
+        /// Print a random UUID
+        #[arg(long, group = "encoding")]
+        uuid: bool,
+    },
+    /// Encrypt a value under a context's key without storing it
+    ///
+    /// Reads the value from the file or stdin byte for byte and prints the
+    /// ciphertext in hex. Each context has its own key, derived from the store
+    /// key. With `--deterministic` equal values give equal ciphertexts, so
+    /// encrypted identifiers can be matched without decrypting them, at the
+    /// cost of revealing which values are equal. The store must be unlocked.
+    Encrypt {
+        /// The context whose key to use (e.g. `users/email`)
+        #[arg(short, long, value_name = "CONTEXT")]
+        context: String,
+        /// Encrypt equal values to equal ciphertexts
+        #[arg(short, long)]
+        deterministic: bool,
+        /// Encrypt under this named key instead of the store key
+        #[arg(short, long, value_name = "NAME")]
+        key: Option<String>,
+        /// The file holding the value (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Decrypt a ciphertext printed by `encrypt`
+    ///
+    /// Writes the value to stdout byte for byte. The context must be the one
+    /// the value was encrypted under. The store must be unlocked.
+    Decrypt {
+        /// The context the value was encrypted under
+        #[arg(short, long, value_name = "CONTEXT")]
+        context: String,
+        /// The ciphertext, in hex
+        #[arg(value_name = "CIPHERTEXT")]
+        ciphertext: String,
+    },
+    /// Manage named keys: versioned keys that values and `encrypt` can use in
+    /// place of the store key
+    ///
+    /// `store --with-key NAME` and `encrypt --key NAME` seal under a named
+    /// key's newest version. Rotating adds a version for later writes, while
+    /// everything sealed earlier keeps opening under the version it records. A
+    /// key with a rotation period rotates itself on its first write once the
+    /// period has passed. The store must be unlocked.
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+    /// Back the store up to a file while the daemon runs
+    ///
+    /// Writes a redb database with every row of the store, as of one moment,
+    /// and a `<FILE>.manifest.json` beside it with the file's SHA-256 and the
+    /// row counts. The values stay sealed, so the store need not be unlocked,
+    /// and restoring one still needs the shares. With `--recipient` the file
+    /// is also encrypted to an age recipient. The path is resolved on this
+    /// side; the daemon writes it, and never over an existing file.
+    Backup {
+        /// The file to write
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+        /// Encrypt the backup to this age recipient (`age1...`)
+        #[arg(short, long, value_name = "RECIPIENT")]
+        recipient: Option<String>,
+    },
+    /// Check every sealed row of the store for damage
+    ///
+    /// Opens each value, signing key, and named key under the key it should
+    /// be sealed under, so a row that was damaged, truncated, or copied under
+    /// another key's name fails. Prints one `TABLE<TAB>KEY<TAB>REASON` line per
+    /// bad row and exits with status 1 when there are any. Named `fsck` since
+    /// `verify` checks signatures. The store must be unlocked.
+    Fsck,
+    /// Have the daemon issue a wrapping key for `import-wrapped`
+    ///
+    /// Prints an X25519 public key in hex. Seal the key to import to it (with
+    /// `salusc wrap`, or on the system the key comes from), then pass the
+    /// result to `import-wrapped`. Each wrapping key imports one value and is
+    /// forgotten when the store locks. The store must be unlocked.
+    WrappingKey,
+    /// Unwrap a key sealed to the daemon's wrapping key and store it
+    ///
+    /// The wrapped key is read, in hex, from the file or stdin. If the key
+    /// already exists, prompts for confirmation before overwriting unless
+    /// `--force` is given. The store must be unlocked.
+    ImportWrapped {
+        /// The key to store the value under
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The file holding the wrapped key (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// Overwrite an existing value without prompting for confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Seal a stored value to a recipient's public key
+    ///
+    /// Prints the wrapped value in hex; only the holder of the recipient's
+    /// X25519 private key can open it. The store must be unlocked.
+    ExportWrapped {
+        /// The key whose value to export
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The recipient's X25519 public key, in hex
+        #[arg(value_name = "PUBLIC_KEY")]
+        recipient: String,
+    },
+    /// Serve registry tokens to cargo as a credential provider
+    ///
+    /// Speaks cargo's credential-provider protocol on stdin and stdout, so
+    /// `get`, `cargo login`, and `cargo logout` read, store, and delete tokens
+    /// in the store rather than in `~/.cargo/credentials.toml`. Configure it in
+    /// `~/.cargo/config.toml`:
+    /// `[registry] global-credential-providers = ["salusc cargo-credential"]`.
+    /// The store must be unlocked.
+    CargoCredential {
+        /// Prepended to each registry's name to make the key of its token
+        #[arg(short, long, value_name = "PREFIX", default_value = "cargo/")]
+        prefix: String,
+        /// Added by cargo to every provider it starts
+        #[arg(long, hide = true)]
+        cargo_plugin: bool,
+    },
+    /// Copy the values under some prefixes from one daemon to another
+    ///
+    /// The destination issues a wrapping key, the source seals each value to
+    /// it, and the destination unwraps the values and stores them under its
+    /// own key, so they are never in the clear here. `--strategy` decides what
+    /// happens to keys the destination already holds; `newest-wins` keeps
+    /// whichever value was written last. Values stored in chunks are listed
+    /// but not copied. Both daemons log the sync to their audit logs, and
+    /// both stores must be unlocked.
+    Sync {
+        /// The prefixes whose values to copy (`""` for every value)
+        #[arg(value_name = "PREFIX", required = true)]
+        prefixes: Vec<String>,
+        /// The source daemon's socket (default: the configured daemon)
+        #[arg(long, value_name = "SOCKET")]
+        from: Option<String>,
+        /// The destination daemon's socket
+        #[arg(long, value_name = "SOCKET")]
+        to: String,
+        /// What to do with keys the destination already holds
+        #[arg(long, value_enum, default_value_t = SyncConflict::Skip)]
+        strategy: SyncConflict,
+        /// Show what would be copied without copying anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Seal a key to an X25519 public key locally, without the daemon
+    ///
+    /// Reads the key from the file or stdin byte for byte and prints it
+    /// wrapped, in hex, in the format `import-wrapped` reads.
+    Wrap {
+        /// The X25519 public key to seal to, in hex
+        #[arg(value_name = "PUBLIC_KEY")]
+        recipient: String,
+        /// The file holding the key to wrap (default: stdin)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Decrypt a file written by `encrypt-file`
+    ///
+    /// The daemon unwraps the file's data key. Every chunk is authenticated,
+    /// and a damaged or truncated file leaves no output behind. Writes
+    /// `<FILE>` without its `.enc` suffix unless `--out` is given. The store
+    /// must be unlocked.
+    DecryptFile {
+        /// The file to decrypt
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+        /// Write the decrypted file here
+        ///
+        /// (`-o` is taken by the global `--output` format flag.)
+        #[arg(short = 'O', long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Overwrite the output file if it exists
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Mint short-lived credentials with the daemon's plugins
+    ///
+    /// A plugin is a program, set up in the daemon's `[plugins.<name>]`
+    /// config, that the daemon runs to mint a credential, such as a database
+    /// login, on demand. Minting needs the store unlocked.
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+    /// Renew or revoke the leases on database users
+    ///
+    /// A read of `database/creds/<role>` answers with a `lease_id`; the user
+    /// is dropped once its lease is up unless the lease is renewed. Both need
+    /// the store unlocked.
+    Lease {
+        #[command(subcommand)]
+        action: LeaseAction,
+    },
+    /// List, approve or refuse reads waiting on approval
+    ///
+    /// A namespace with `approvals` set in the daemon's config holds each
+    /// read of its keys until that many other users approve it; the read is
+    /// answered with an approval id, and read again once granted. With no
+    /// subcommand, lists the reads waiting and those granted.
+    Approvals {
+        #[command(subcommand)]
+        action: Option<ApprovalAction>,
+    },
+    /// Sign short-lived SSH certificates with a CA key held by the daemon
+    ///
+    /// The CA is an Ed25519 key made with `signing-key`; servers trust it
+    /// with `TrustedUserCAKeys` (or `@cert-authority` for host
+    /// certificates) in place of `authorized_keys` entries. Signing needs
+    /// the store unlocked.
+    Ssh {
+        #[command(subcommand)]
+        action: SshAction,
+    },
+    /// Issue X.509 certificates for internal TLS from a CA held by the daemon
+    ///
+    /// The CA is made with `pki init`, or brought in (an intermediate, say)
+    /// with `pki import-ca`; its key never leaves the daemon. Each
+    /// certificate gets a new ECDSA P-256 key, printed once and not kept.
+    /// Everything but `pki list` needs the store unlocked.
+    Pki {
+        #[command(subcommand)]
+        action: PkiAction,
+    },
 }
 
-/// How the words of a generated passphrase are joined together.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
-pub(crate) enum GenKind {
-    /// Space-separated lowercase words: `correct horse battery staple`
-    Space,
-    /// Hyphen-separated lowercase words: `correct-horse-battery-staple`
-    Hyphen,
-    /// Dot-separated lowercase words: `correct.horse.battery.staple`
-    Dot,
-    /// Capitalized words with no separator: `CorrectHorseBatteryStaple`
-    Camel,
+/// `data-key` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum DataKeyAction {
+    /// Unwrap a data key printed by `data-key`, printing it in hex
+    Decrypt {
+        /// The wrapped data key, in hex
+        #[arg(value_name = "CIPHERTEXT")]
+        ciphertext: String,
+    },
 }
 
-#[cfg(test)]
-mod test {
-    use anyhow::Result;
-    use clap::Parser;
-    use config::Source;
+/// `key` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum KeyAction {
+    /// Create a named key
+    Create {
+        /// The key's name: letters, digits, `.`, `_`, or `-`
+        #[arg(value_name = "NAME")]
+        name: String,
+        /// Rotate the key on its first write this many seconds after its
+        /// newest version was created
+        #[arg(short, long, value_name = "SECONDS")]
+        rotate_after: Option<u64>,
+    },
+    /// Add a new version to a named key, for later writes to use
+    Rotate {
+        /// The key's name
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Seal the values under a prefix again under the newest version of
+    /// their named key, so older versions seal nothing stored. The daemon
+    /// does it in batches in the background; `key rewraps` shows how far it
+    /// has got
+    Rewrap {
+        /// The prefix whose values are sealed again
+        #[arg(value_name = "PREFIX")]
+        prefix: String,
+        /// Follow the rewrap until it finishes, reporting each batch
+        #[arg(short, long)]
+        wait: bool,
+    },
+    /// Show how far each rewrap has got
+    Rewraps,
+    /// List the named keys with their versions and rotation state
+    List,
+}
 
-    use super::Cli;
+/// `plugin` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum PluginAction {
+    /// List the plugins, checking each one's health
+    List,
+    /// Mint a credential for a role, printing its fields
+    Mint {
+        /// The plugin's name
+        #[arg(value_name = "PLUGIN")]
+        plugin: String,
+        /// The role to mint the credential for
+        #[arg(value_name = "ROLE")]
+        role: String,
+        /// Seconds the credential is good for (default: the plugin's
+        /// `max_ttl`)
+        #[arg(short, long, value_name = "SECONDS")]
+        ttl: Option<u64>,
+    },
+}
 
-    #[test]
+/// `lease` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum LeaseAction {
+    /// Keep a lease's user longer, printing when it is now dropped
+    Renew {
+        /// The lease's id
+        #[arg(value_name = "LEASE_ID")]
+        lease_id: String,
+        /// Seconds from now to keep the user (default: its role's `ttl`),
+        /// held to the plugin's `max_ttl` after the user was made
+        #[arg(short, long, value_name = "SECONDS")]
+        increment: Option<u64>,
+    },
+    /// Drop a lease's user now
+    Revoke {
+        /// The lease's id
+        #[arg(value_name = "LEASE_ID")]
+        lease_id: String,
+    },
+}
+
+/// `approvals` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum ApprovalAction {
+    /// Approve a held read, as the user running salusc
+    Approve {
+        /// The approval's id
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+    /// Refuse a held read, or withdraw one of your own
+    Deny {
+        /// The approval's id
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+}
+
+/// `ssh` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum SshAction {
+    /// Certify a public key, printing the certificate
+    ///
+    /// The key is read from an OpenSSH `.pub` file, or from stdin when
+    /// omitted. The certificate is printed as a line for the key's
+    /// `-cert.pub` file, and when it is good until to stderr.
+    Sign {
+        /// The file holding the public key (default: stdin)
+        #[arg(value_name = "PUBLIC_KEY")]
+        file: Option<PathBuf>,
+        /// The users (or host names, with `--host`) the certificate is good
+        /// for, comma-separated
+        #[arg(
+            short = 'n',
+            long,
+            value_name = "PRINCIPALS",
+            value_delimiter = ',',
+            required = true
+        )]
+        principals: Vec<String>,
+        /// Seconds the certificate is good for (default: the daemon's
+        /// `[ssh] max_ttl`)
+        #[arg(short, long, value_name = "SECONDS")]
+        ttl: Option<u64>,
+        /// The signing key that is the CA
+        #[arg(long, value_name = "NAME", default_value = "ssh-ca")]
+        ca: String,
+        /// Certify a host key rather than a user's
+        #[arg(long)]
+        host: bool,
+    },
+}
+
+/// `pki` subcommands.
+#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum PkiAction {
+    /// Make a self-signed root CA, printing its certificate
+    Init {
+        /// The CA's common name
+        #[arg(long, value_name = "NAME", default_value = "salus CA")]
+        cn: String,
+        /// Seconds the CA is good for
+        #[arg(short, long, value_name = "SECONDS", default_value_t = 315_360_000)]
+        ttl: u64,
+        /// Replace the CA there is, forgetting the certificates it issued
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Bring in a CA made elsewhere, root or intermediate
+    ImportCa {
+        /// The PEM file holding the CA's certificate, then any above it
+        #[arg(value_name = "CERT")]
+        certificate: PathBuf,
+        /// The PEM file holding the CA's ECDSA P-256 private key
+        #[arg(value_name = "KEY")]
+        key: PathBuf,
+        /// Replace the CA there is, forgetting the certificates it issued
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Issue a certificate, printing it, the CA chain, and its private key
+    ///
+    /// The common name is also a subject alternative name when it is a host
+    /// name or IP address. The PEM blocks go to stdout, and the serial and
+    /// when it is good until to stderr.
+    Issue {
+        /// The certificate's common name
+        #[arg(value_name = "CN")]
+        cn: String,
+        /// More host names (`*.` wildcards allowed) or IP addresses it is
+        /// good for, comma-separated
+        #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+        san: Vec<String>,
+        /// Seconds the certificate is good for (default: the daemon's
+        /// `[pki] max_ttl`)
+        #[arg(short, long, value_name = "SECONDS")]
+        ttl: Option<u64>,
+    },
+    /// Revoke a certificate, so the CRL lists it
+    Revoke {
+        /// The certificate's serial number, in hex (colons allowed)
+        #[arg(value_name = "SERIAL")]
+        serial: String,
+    },
+    /// List the certificates issued, soonest to expire first
+    List,
+    /// Print a freshly signed CRL, in PEM
+    Crl,
+}
+
+/// The kinds of key `signing-key` can generate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum SigningKind {
+    /// Ed25519 signatures
+    Ed25519,
+    /// HMAC-SHA256 tags
+    HmacSha256,
+}
+
+impl From<SigningKind> for SigningAlgorithm {
+    fn from(kind: SigningKind) -> Self {
+        match kind {
+            SigningKind::Ed25519 => SigningAlgorithm::Ed25519,
+            SigningKind::HmacSha256 => SigningAlgorithm::HmacSha256,
+        }
+    }
+}
+
+/// What `sync` does with a key the destination already holds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum SyncConflict {
+    /// Keep the destination's value
+    Skip,
+    /// Replace it with the source's
+    Overwrite,
+    /// Keep whichever was written last
+    NewestWins,
+}
+
+impl From<SyncConflict> for SyncStrategy {
+    fn from(conflict: SyncConflict) -> Self {
+        match conflict {
+            SyncConflict::Skip => SyncStrategy::Skip,
+            SyncConflict::Overwrite => SyncStrategy::Overwrite,
+            SyncConflict::NewestWins => SyncStrategy::NewestWins,
+        }
+    }
+}
+
+/// Turns a daemon mode on or off.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum Switch {
+    /// Turn the mode on
+    On,
+    /// Turn it off
+    Off,
+}
+
+/// `shares` subcommands.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Subcommand)]
+pub(crate) enum SharesAction {
+    /// Reissue the shares for the unlocked store, retiring the current ones
+    ///
+    /// The store key and every stored value stay the same; the new shares
+    /// (same count and threshold) replace the old, which no longer unlock the
+    /// store. Takes the same output options as `shares`. The store must be
+    /// unlocked first.
+    Refresh,
+}
+
+/// `generate` subcommands.
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum GenerateAction {
+    /// A random string of characters
+    Key {
+        /// Number of characters (1-4096)
+        #[arg(
+            short,
+            long,
+            default_value_t = 32,
+            value_parser = clap::value_parser!(u32).range(1..=4096),
+            value_name = "N"
+        )]
+        length: u32,
+        /// The characters to draw from
+        #[arg(long, value_enum, default_value_t = GenCharset::AlnumSymbols)]
+        charset: GenCharset,
+        /// The key to store the secret under
+        #[arg(long, value_name = "KEY")]
+        store: String,
+        /// Also print the generated secret
+        #[arg(long)]
+        show: bool,
+        /// Overwrite an existing value without prompting
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// A diceware passphrase from the EFF word list
+    Passphrase {
+        /// Number of words (1-64)
+        #[arg(
+            short,
+            long,
+            default_value_t = 6,
+            value_parser = clap::value_parser!(u32).range(1..=64),
+            value_name = "N"
+        )]
+        words: u32,
+        /// Placed between words
+        #[arg(long, default_value = " ", value_name = "SEP")]
+        separator: String,
+        /// The key to store the secret under
+        #[arg(long, value_name = "KEY")]
+        store: String,
+        /// Also print the generated secret
+        #[arg(long)]
+        show: bool,
+        /// Overwrite an existing value without prompting
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+impl GenerateAction {
+    /// The request to send to the daemon.
+    pub(crate) fn into_request(self) -> GenerateSecret {
+        let (spec, store, show, force) = match self {
+            GenerateAction::Key {
+                length,
+                charset,
+                store,
+                show,
+                force,
+            } => (
+                SecretSpec::Chars {
+                    length,
+                    charset: charset.into(),
+                },
+                store,
+                show,
+                force,
+            ),
+            GenerateAction::Passphrase {
+                words,
+                separator,
+                store,
+                show,
+                force,
+            } => (
+                SecretSpec::Passphrase { words, separator },
+                store,
+                show,
+                force,
+            ),
+        };
+        GenerateSecret::builder()
+            .key(store)
+            .spec(spec)
+            .show(show)
+            .force(force)
+            .build()
+    }
+}
+
+/// The key sizes `shares` can create a store with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum KeyBits {
+    /// AES-128-GCM
+    #[value(name = "128")]
+    Bits128,
+    /// AES-256-GCM
+    #[value(name = "256")]
+    Bits256,
+}
+
+impl From<KeyBits> for KeyAlgorithm {
+    fn from(bits: KeyBits) -> Self {
+        match bits {
+            KeyBits::Bits128 => KeyAlgorithm::Aes128Gcm,
+            KeyBits::Bits256 => KeyAlgorithm::Aes256Gcm,
+        }
+    }
+}
+
+/// The character sets `generate key` can draw from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum GenCharset {
+    /// ASCII letters and digits
+    Alnum,
+    /// ASCII letters, digits, and symbols
+    AlnumSymbols,
+    /// Lowercase hexadecimal
+    Hex,
+    /// Decimal digits
+    Digits,
+}
+
+impl From<GenCharset> for Charset {
+    fn from(charset: GenCharset) -> Self {
+        match charset {
+            GenCharset::Alnum => Charset::Alnum,
+            GenCharset::AlnumSymbols => Charset::AlnumSymbols,
+            GenCharset::Hex => Charset::Hex,
+            GenCharset::Digits => Charset::Digits,
+        }
+    }
+}
+
+/// `template` subcommands.
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum TemplateAction {
+    /// Substitute `{{ secret "key" }}` placeholders in a template file
+    ///
+    /// Writes to stdout, or with `--out` atomically replaces that file (created
+    /// `0600`). With `--watch` the referenced keys are re-read every
+    /// `--interval` seconds and the file is rewritten whenever the result
+    /// changes. The store must be unlocked first.
+    Render {
+        /// The template file
+        #[arg(value_name = "TEMPLATE")]
+        input: PathBuf,
+        /// Write the result to this file instead of stdout
+        ///
+        /// (`-o` is taken by the global `--output` format flag.)
+        #[arg(short = 'O', long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Keep running and re-render when referenced values change
+        #[arg(short, long, requires = "out")]
+        watch: bool,
+        /// Seconds between checks in watch mode
+        #[arg(long, value_name = "SECONDS", default_value_t = 30, requires = "watch")]
+        interval: u64,
+    },
+}
+
+/// What the hidden `__complete` hook completes.
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum CompleteTarget {
+    /// Print the stored key names starting with PREFIX, one per line
+    Keys {
+        /// The partial key typed so far
+        #[arg(value_name = "PREFIX", default_value = "")]
+        prefix: String,
+    },
+}
+
+/// How the words of a generated passphrase are joined together.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum GenKind {
+    /// Space-separated lowercase words: `correct horse battery staple`
+    Space,
+    /// Hyphen-separated lowercase words: `correct-horse-battery-staple`
+    Hyphen,
+    /// Dot-separated lowercase words: `correct.horse.battery.staple`
+    Dot,
+    /// Capitalized words with no separator: `CorrectHorseBatteryStaple`
+    Camel,
+}
+
+/// Parse a `--map` or `--folder` rule: `FROM=TO`.
+fn rule(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((from, to)) if !from.is_empty() => Ok((from.to_string(), to.to_string())),
+        _ => Err(format!("expected FROM=TO, got '{text}'")),
+    }
+}
+
+/// Parse a value version: `3` or `v3`.
+fn version(text: &str) -> Result<u64, String> {
+    let digits = text.strip_prefix('v').unwrap_or(text);
+    digits
+        .parse()
+        .map_err(|_e| format!("expected a version like 'v3', got '{text}'"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use anyhow::{Result, bail};
+    use clap::Parser;
+    use config::Source;
+    use libsalus::{Charset, KeyAlgorithm, SecretSpec};
+
+    use super::{
+        ApprovalAction, Cli, Commands, DataKeyAction, KeyAction, KeyBits, LeaseAction, PkiAction,
+        PluginAction, SharesAction, SigningKind, SshAction, SyncConflict,
+    };
+    use crate::{formats::ImportFormat, inter::RandomEncoding};
+
+    #[test]
     fn collect_omits_unset_flags() -> Result<()> {
         let cli = Cli::try_parse_from(["salusc", "unlock"])?;
         let map = cli.collect()?;
@@ -351,4 +1768,700 @@ mod test {
         assert!(!map.contains_key("socket_path"));
         Ok(())
     }
+
+    #[test]
+    fn collect_includes_namespace_given_after_the_command() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "read", "db/password", "--namespace", "prod"])?;
+        let map = cli.collect()?;
+        assert!(map.contains_key("namespace"));
+        Ok(())
+    }
+
+    #[test]
+    fn collect_includes_connection_settings() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "--request-timeout",
+            "0",
+            "--retries",
+            "5",
+            "--auto-start",
+            "--timing",
+            "status",
+        ])?;
+        let map = cli.collect()?;
+        assert!(map.contains_key("request_timeout"));
+        assert!(map.contains_key("retries"));
+        assert!(map.contains_key("auto_start"));
+        assert!(map.contains_key("timing"));
+        assert!(!map.contains_key("connect_timeout"));
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_after_takes_seconds_or_clear() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "rotate-after", "app/db", "7776000"])?;
+        let Commands::RotateAfter {
+            key,
+            seconds,
+            clear,
+        } = cli.command()
+        else {
+            bail!("expected the rotate-after command");
+        };
+        assert_eq!(key, "app/db");
+        assert_eq!(seconds, Some(7_776_000));
+        assert!(!clear);
+        let cli = Cli::try_parse_from(["salusc", "rotate-after", "app/db", "--clear"])?;
+        assert!(matches!(
+            cli.command(),
+            Commands::RotateAfter {
+                seconds: None,
+                clear: true,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["salusc", "rotate-after", "app/db"]).is_err());
+        assert!(
+            Cli::try_parse_from(["salusc", "rotate-after", "app/db", "60", "--clear"]).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lease_renew_takes_an_id_and_an_increment() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "lease", "renew", "0f3a", "--increment", "600"])?;
+        let Commands::Lease {
+            action:
+                LeaseAction::Renew {
+                    lease_id,
+                    increment,
+                },
+        } = cli.command()
+        else {
+            bail!("expected the lease renew command");
+        };
+        assert_eq!(lease_id, "0f3a");
+        assert_eq!(increment, Some(600));
+        assert!(Cli::try_parse_from(["salusc", "lease", "revoke"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn approvals_lists_with_no_subcommand() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "approvals"])?;
+        let Commands::Approvals { action: None } = cli.command() else {
+            bail!("expected the approvals list");
+        };
+        let cli = Cli::try_parse_from(["salusc", "approvals", "approve", "5c1e"])?;
+        let Commands::Approvals {
+            action: Some(ApprovalAction::Approve { id }),
+        } = cli.command()
+        else {
+            bail!("expected the approvals approve command");
+        };
+        assert_eq!(id, "5c1e");
+        assert!(Cli::try_parse_from(["salusc", "approvals", "deny"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn clip_timeout_requires_clip() {
+        assert!(Cli::try_parse_from(["salusc", "read", "k", "--clip-timeout", "10"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "read", "k", "-c", "--clip-timeout", "10"]).is_ok());
+    }
+
+    #[test]
+    fn exec_passes_the_command_through_verbatim() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc", "exec", "--prefix", "app/", "--", "./server", "--port", "8080",
+        ])?;
+        match cli.command() {
+            Commands::Exec {
+                prefix, command, ..
+            } => {
+                assert_eq!(prefix, "app/");
+                assert_eq!(command, ["./server", "--port", "8080"]);
+            }
+            other => bail!("expected exec, got {other:?}"),
+        }
+        assert!(Cli::try_parse_from(["salusc", "exec", "--prefix", "app/"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn template_watch_requires_an_output_file() {
+        assert!(Cli::try_parse_from(["salusc", "template", "render", "in.tmpl", "-w"]).is_err());
+        assert!(
+            Cli::try_parse_from(["salusc", "template", "render", "in.tmpl", "-O", "out", "-w"])
+                .is_ok()
+        );
+        assert!(
+            Cli::try_parse_from(["salusc", "template", "render", "in.tmpl", "--interval", "5"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn shares_out_dir_excludes_terminal_qr() {
+        assert!(Cli::try_parse_from(["salusc", "shares", "--out-dir", "d", "--qr"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "shares", "--paper", "d", "--qr"]).is_err());
+        assert!(
+            Cli::try_parse_from(["salusc", "shares", "--out-dir", "d", "--qr-png", "d"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn shares_counts_below_two_are_rejected() {
+        assert!(Cli::try_parse_from(["salusc", "shares", "-t", "1"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "shares", "-n", "0"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "shares", "-n", "2", "-t", "2"]).is_ok());
+    }
+
+    #[test]
+    fn shares_takes_the_key_options() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "shares", "--key-bits", "128", "--kdf"])?;
+        let Commands::Shares { key_bits, kdf, .. } = cli.command() else {
+            bail!("expected shares");
+        };
+        assert_eq!(KeyAlgorithm::from(key_bits), KeyAlgorithm::Aes128Gcm);
+        assert!(kdf);
+        let cli = Cli::try_parse_from(["salusc", "shares"])?;
+        let Commands::Shares { key_bits, kdf, .. } = cli.command() else {
+            bail!("expected shares");
+        };
+        assert_eq!(key_bits, KeyBits::Bits256);
+        assert!(!kdf);
+        assert!(Cli::try_parse_from(["salusc", "shares", "--key-bits", "192"]).is_err());
+        // The key is fixed once the store exists.
+        assert!(Cli::try_parse_from(["salusc", "shares", "refresh", "--kdf"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shares_token_output_stands_alone() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "shares", "--token-cmd", "write-token"])?;
+        let Commands::Shares { token_cmd, .. } = cli.command() else {
+            bail!("expected shares");
+        };
+        assert_eq!(token_cmd.as_deref(), Some("write-token"));
+        for conflicting in ["--qr", "--yubikey-piv"] {
+            assert!(
+                Cli::try_parse_from(["salusc", "shares", "--token-cmd", "x", conflicting]).is_err()
+            );
+        }
+        assert!(
+            Cli::try_parse_from(["salusc", "shares", "--yubikey-piv", "--paper", "d"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["salusc", "shares", "refresh", "--yubikey-piv"]).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn verify_takes_a_signature_and_an_optional_file() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "verify", "release", "abcd", "msg.txt"])?;
+        let Commands::Verify {
+            key,
+            signature,
+            file,
+        } = cli.command()
+        else {
+            bail!("expected verify");
+        };
+        assert_eq!((key.as_str(), signature.as_str()), ("release", "abcd"));
+        assert!(file.is_some());
+        let cli = Cli::try_parse_from(["salusc", "signing-key", "hooks", "-a", "hmac-sha256"])?;
+        let Commands::SigningKey { algorithm, .. } = cli.command() else {
+            bail!("expected signing-key");
+        };
+        assert_eq!(algorithm, SigningKind::HmacSha256);
+        Ok(())
+    }
+
+    #[test]
+    fn data_key_defaults_to_256_bits_and_bounds_the_size() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "data-key"])?;
+        let Commands::DataKey { action, bits } = cli.command() else {
+            bail!("expected data-key");
+        };
+        assert_eq!((action, bits), (None, 256));
+        assert!(Cli::try_parse_from(["salusc", "data-key", "-b", "1024"]).is_err());
+        let cli = Cli::try_parse_from(["salusc", "data-key", "decrypt", "01ab"])?;
+        let Commands::DataKey { action, .. } = cli.command() else {
+            bail!("expected data-key");
+        };
+        assert_eq!(
+            action,
+            Some(DataKeyAction::Decrypt {
+                ciphertext: "01ab".to_string()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn random_takes_one_encoding() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "random", "--base64"])?;
+        let Commands::Random {
+            bytes,
+            base64,
+            uuid,
+            ..
+        } = cli.command()
+        else {
+            bail!("expected random");
+        };
+        assert_eq!(bytes, 32);
+        assert_eq!(
+            RandomEncoding::from_flags(base64, uuid),
+            RandomEncoding::Base64
+        );
+        assert!(Cli::try_parse_from(["salusc", "random", "--hex", "--base64"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "random", "16", "--uuid"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "random", "--uuid"]).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_needs_a_context() -> Result<()> {
+        assert!(Cli::try_parse_from(["salusc", "encrypt"]).is_err());
+        let cli = Cli::try_parse_from(["salusc", "encrypt", "-c", "users/email", "-d"])?;
+        let Commands::Encrypt {
+            context,
+            deterministic,
+            key,
+            file,
+        } = cli.command()
+        else {
+            bail!("expected encrypt");
+        };
+        assert_eq!(
+            (context.as_str(), deterministic, key, file),
+            ("users/email", true, None, None)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn key_subcommands_parse() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "key", "create", "app-a", "-r", "86400"])?;
+        let Commands::Key { action } = cli.command() else {
+            bail!("expected key");
+        };
+        assert_eq!(
+            action,
+            KeyAction::Create {
+                name: "app-a".to_string(),
+                rotate_after: Some(86_400)
+            }
+        );
+        let cli = Cli::try_parse_from(["salusc", "store", "db/pass", "x", "--with-key", "app-a"])?;
+        let Commands::Store { with_key, .. } = cli.command() else {
+            bail!("expected store");
+        };
+        assert_eq!(with_key.as_deref(), Some("app-a"));
+        assert!(Cli::try_parse_from(["salusc", "key", "rotate"]).is_err());
+        let cli = Cli::try_parse_from(["salusc", "key", "rewrap", "db/", "--wait"])?;
+        let Commands::Key { action } = cli.command() else {
+            bail!("expected key");
+        };
+        assert_eq!(
+            action,
+            KeyAction::Rewrap {
+                prefix: "db/".to_string(),
+                wait: true,
+            }
+        );
+        let cli = Cli::try_parse_from(["salusc", "key", "rewraps"])?;
+        let Commands::Key { action } = cli.command() else {
+            bail!("expected key");
+        };
+        assert_eq!(action, KeyAction::Rewraps);
+        Ok(())
+    }
+
+    #[test]
+    fn no_overwrite_conflicts_with_force() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "store", "k", "v", "--no-overwrite"])?;
+        let Commands::Store { no_overwrite, .. } = cli.command() else {
+            bail!("expected store");
+        };
+        assert!(no_overwrite);
+        assert!(
+            Cli::try_parse_from(["salusc", "store", "k", "v", "--no-overwrite", "--force"])
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn patch_takes_a_key_and_an_optional_patch() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "patch", "db/creds", r#"{"password":"new"}"#])?;
+        let Commands::Patch { key, patch, .. } = cli.command() else {
+            bail!("expected patch");
+        };
+        assert_eq!(key, "db/creds");
+        assert_eq!(patch.as_deref(), Some(r#"{"password":"new"}"#));
+        let cli = Cli::try_parse_from(["salusc", "patch", "db/creds"])?;
+        let Commands::Patch { patch, .. } = cli.command() else {
+            bail!("expected patch");
+        };
+        assert!(patch.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn link_takes_an_alias_and_a_target() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "link", "old/db", "new/db"])?;
+        let Commands::Link { alias, target } = cli.command() else {
+            bail!("expected link");
+        };
+        assert_eq!((alias.as_str(), target.as_str()), ("old/db", "new/db"));
+        assert!(Cli::try_parse_from(["salusc", "link", "old/db"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn delete_takes_a_key_or_a_prefix() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "delete", "--prefix", "tmp/", "--dry-run"])?;
+        let Commands::Delete {
+            key,
+            prefix,
+            dry_run,
+            ..
+        } = cli.command()
+        else {
+            bail!("expected delete");
+        };
+        assert!(key.is_none());
+        assert_eq!(prefix.as_deref(), Some("tmp/"));
+        assert!(dry_run);
+        assert!(Cli::try_parse_from(["salusc", "delete"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "delete", "k", "--prefix", "tmp/"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "delete", "k", "--dry-run"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "delete", "--prefix", ""]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn exists_takes_a_key() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "exists", "db/creds"])?;
+        let Commands::Exists { key } = cli.command() else {
+            bail!("expected exists");
+        };
+        assert_eq!(key, "db/creds");
+        assert!(Cli::try_parse_from(["salusc", "exists"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn diff_takes_two_versions_with_or_without_a_v() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "diff", "db/creds", "v2", "3"])?;
+        let Commands::Diff {
+            key,
+            from,
+            to,
+            show_values,
+        } = cli.command()
+        else {
+            bail!("expected diff");
+        };
+        assert_eq!(
+            (key.as_str(), from, to, show_values),
+            ("db/creds", 2, 3, false)
+        );
+        let cli = Cli::try_parse_from(["salusc", "diff", "k", "1", "2", "--show-values"])?;
+        assert!(matches!(
+            cli.command(),
+            Commands::Diff {
+                show_values: true,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["salusc", "diff", "k", "v", "2"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "diff", "k", "1"]).is_err());
+        let cli = Cli::try_parse_from(["salusc", "history", "db/creds"])?;
+        assert!(matches!(cli.command(), Commands::History { key } if key == "db/creds"));
+        Ok(())
+    }
+
+    #[test]
+    fn plugin_mint_takes_a_plugin_a_role_and_a_ttl() -> Result<()> {
+        let cli =
+            Cli::try_parse_from(["salusc", "plugin", "mint", "pg", "readonly", "--ttl", "60"])?;
+        let Commands::Plugin {
+            action: PluginAction::Mint { plugin, role, ttl },
+        } = cli.command()
+        else {
+            bail!("expected plugin mint");
+        };
+        assert_eq!(plugin, "pg");
+        assert_eq!(role, "readonly");
+        assert_eq!(ttl, Some(60));
+        let cli = Cli::try_parse_from(["salusc", "plugin", "list"])?;
+        assert!(matches!(
+            cli.command(),
+            Commands::Plugin {
+                action: PluginAction::List
+            }
+        ));
+        assert!(Cli::try_parse_from(["salusc", "plugin", "mint", "pg"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ssh_sign_takes_a_key_file_and_principals() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "ssh",
+            "sign",
+            "id_ed25519.pub",
+            "-n",
+            "alice,deploy",
+            "-t",
+            "3600",
+        ])?;
+        let Commands::Ssh {
+            action:
+                SshAction::Sign {
+                    file,
+                    principals,
+                    ttl,
+                    ca,
+                    host,
+                },
+        } = cli.command()
+        else {
+            bail!("expected ssh sign");
+        };
+        assert_eq!(file.as_deref(), Some(Path::new("id_ed25519.pub")));
+        assert_eq!(principals, &["alice", "deploy"]);
+        assert_eq!(ttl, Some(3600));
+        assert_eq!(ca, "ssh-ca");
+        assert!(!host);
+        assert!(Cli::try_parse_from(["salusc", "ssh", "sign", "id_ed25519.pub"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pki_issue_takes_a_name_and_alt_names() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "pki",
+            "issue",
+            "api.internal",
+            "--san",
+            "10.0.0.7,*.svc.internal",
+            "-t",
+            "3600",
+        ])?;
+        let Commands::Pki {
+            action: PkiAction::Issue { cn, san, ttl },
+        } = cli.command()
+        else {
+            bail!("expected pki issue");
+        };
+        assert_eq!(cn, "api.internal");
+        assert_eq!(san, &["10.0.0.7", "*.svc.internal"]);
+        assert_eq!(ttl, Some(3600));
+        let cli = Cli::try_parse_from(["salusc", "pki", "init"])?;
+        assert!(matches!(
+            cli.command(),
+            Commands::Pki {
+                action: PkiAction::Init { cn, ttl: 315_360_000, force: false },
+            } if cn == "salus CA"
+        ));
+        assert!(Cli::try_parse_from(["salusc", "pki", "issue"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "pki", "import-ca", "ca.pem"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backup_takes_a_file_and_an_optional_recipient() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "backup", "out.redb", "-r", "age1abc"])?;
+        let Commands::Backup { path, recipient } = cli.command() else {
+            bail!("expected backup");
+        };
+        assert_eq!(path, PathBuf::from("out.redb"));
+        assert_eq!(recipient.as_deref(), Some("age1abc"));
+        assert!(Cli::try_parse_from(["salusc", "backup"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fsck_takes_no_arguments() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "fsck"])?;
+        assert!(matches!(cli.command(), Commands::Fsck));
+        assert!(Cli::try_parse_from(["salusc", "fsck", "extra"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_needs_a_destination_and_a_prefix() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "sync",
+            "app/",
+            "web/",
+            "--to",
+            "/tmp/other.sock",
+            "--strategy",
+            "newest-wins",
+        ])?;
+        let Commands::Sync {
+            prefixes,
+            from,
+            to,
+            strategy,
+            dry_run,
+        } = cli.command()
+        else {
+            bail!("expected sync");
+        };
+        assert_eq!(prefixes, ["app/", "web/"]);
+        assert_eq!((from, to.as_str()), (None, "/tmp/other.sock"));
+        assert_eq!((strategy, dry_run), (SyncConflict::NewestWins, false));
+        assert!(Cli::try_parse_from(["salusc", "sync", "app/"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "sync", "--to", "/tmp/other.sock"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn import_takes_password_manager_formats_and_rules() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "import",
+            "export.csv",
+            "--format",
+            "1password",
+            "--map",
+            "otpauth=",
+            "--map",
+            "password=secret",
+            "--folder",
+            "Work/Servers=prod/",
+        ])?;
+        let Commands::Import {
+            format,
+            maps,
+            folders,
+            ..
+        } = cli.command()
+        else {
+            bail!("expected import");
+        };
+        assert_eq!(format, Some(ImportFormat::OnePassword));
+        assert_eq!(
+            maps,
+            [
+                ("otpauth".to_string(), String::new()),
+                ("password".to_string(), "secret".to_string())
+            ]
+        );
+        assert_eq!(folders, [("Work/Servers".to_string(), "prod/".to_string())]);
+        assert!(Cli::try_parse_from(["salusc", "import", "x.csv", "--map", "=a"]).is_err());
+        assert!(Cli::try_parse_from(["salusc", "import", "x.csv", "--folder", "a"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn import_wrapped_reads_stdin_by_default() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "import-wrapped", "hsm/key", "-f"])?;
+        let Commands::ImportWrapped { key, file, force } = cli.command() else {
+            bail!("expected import-wrapped");
+        };
+        assert_eq!((key.as_str(), file, force), ("hsm/key", None, true));
+        assert!(Cli::try_parse_from(["salusc", "export-wrapped", "hsm/key"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn file_commands_take_out_and_force() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "encrypt-file", "a.tar", "-O", "b.enc", "-f"])?;
+        let Commands::EncryptFile { input, out, force } = cli.command() else {
+            bail!("expected encrypt-file");
+        };
+        assert_eq!(input, PathBuf::from("a.tar"));
+        assert_eq!(out, Some(PathBuf::from("b.enc")));
+        assert!(force);
+        let cli = Cli::try_parse_from(["salusc", "-o", "json", "decrypt-file", "b.enc"])?;
+        let Commands::DecryptFile { out, force, .. } = cli.command() else {
+            bail!("expected decrypt-file");
+        };
+        assert_eq!((out, force), (None, false));
+        Ok(())
+    }
+
+    #[test]
+    fn streamed_file_commands_take_a_key_and_a_file() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "store-file", "db/dump", "dump.sql", "-f"])?;
+        let Commands::StoreFile { key, file, force } = cli.command() else {
+            bail!("expected store-file");
+        };
+        assert_eq!(key, "db/dump");
+        assert_eq!(file, PathBuf::from("dump.sql"));
+        assert!(force);
+        let cli = Cli::try_parse_from(["salusc", "read-file", "db/dump", "-O", "out.sql"])?;
+        let Commands::ReadFile { out, force, .. } = cli.command() else {
+            bail!("expected read-file");
+        };
+        assert_eq!((out, force), (Some(PathBuf::from("out.sql")), false));
+        assert!(Cli::try_parse_from(["salusc", "read-file", "db/dump", "-f"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shares_refresh_takes_the_output_options() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "shares", "refresh", "--paper", "d"])?;
+        let Commands::Shares { action, paper, .. } = cli.command() else {
+            bail!("expected shares");
+        };
+        assert_eq!(action, Some(SharesAction::Refresh));
+        assert!(paper.is_some());
+        assert!(
+            Cli::try_parse_from(["salusc", "shares", "refresh", "--paper", "d", "--qr"]).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn generate_key_builds_a_chars_request() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusc",
+            "generate",
+            "key",
+            "--length",
+            "32",
+            "--charset",
+            "alnum-symbols",
+            "--store",
+            "db/password",
+        ])?;
+        let Commands::Generate { action } = cli.command() else {
+            bail!("expected generate");
+        };
+        let request = action.into_request();
+        assert_eq!(request.key(), "db/password");
+        assert!(!request.show());
+        assert_eq!(
+            request.spec(),
+            &SecretSpec::Chars {
+                length: 32,
+                charset: Charset::AlnumSymbols
+            }
+        );
+        assert!(Cli::try_parse_from(["salusc", "generate", "passphrase"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn collect_includes_output_after_subcommand() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusc", "read", "k", "--output", "json"])?;
+        let map = cli.collect()?;
+        assert_eq!(
+            map.get("output").map(ToString::to_string),
+            Some("json".to_string())
+        );
+        Ok(())
+    }
 }
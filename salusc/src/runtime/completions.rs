@@ -0,0 +1,152 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Shell completion scripts for the `completions` subcommand.
+//!
+//! The static part (subcommands, flags, value enums) is generated by
+//! `clap_complete` from the real [`Cli`] definition, so it never drifts. For
+//! bash, zsh, and fish a small hook is appended that completes the `KEY`
+//! argument of `read`, `edit`, `delete`, `store`, `store-file`, and `read-file`
+//! with actual key names by calling the hidden `salusc __complete keys
+//! <prefix>` subcommand.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::CommandFactory;
+use clap_complete::{Shell, generate};
+
+use super::cli::Cli;
+
+/// The binary name the completions are registered for.
+const BIN_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Wraps the generated `_salusc` function and adds key names for the first
+/// positional argument of the key-taking subcommands.
+const BASH_KEYS_HOOK: &str = r#"
+_salusc_with_keys() {
+    _salusc "$@"
+    local i word sub="" positional=0
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        word="${COMP_WORDS[i]}"
+        case "$word" in
+            -c|--config-path|-s|--socket-path|-a|--agent-socket-path|-o|--output)
+                [[ -z "$sub" ]] && (( i++ )) ;;
+            -*) ;;
+            *)
+                if [[ -z "$sub" ]]; then sub="$word"; else (( positional++ )); fi ;;
+        esac
+    done
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    case "$sub" in
+        read|edit|delete|store|store-file|read-file)
+            if [[ $positional -eq 0 && "$cur" != -* ]]; then
+                COMPREPLY+=( $(salusc __complete keys "$cur" 2>/dev/null) )
+            fi ;;
+    esac
+}
+complete -F _salusc_with_keys -o nosort -o bashdefault -o default salusc
+"#;
+
+/// Completion function substituted for the `KEY` arguments' default action.
+const ZSH_KEYS_FUNCTION: &str = r#"
+(( $+functions[_salusc_keys] )) ||
+_salusc_keys() {
+    local -a keys
+    keys=(${(f)"$(salusc __complete keys "$PREFIX" 2>/dev/null)"})
+    compadd -a keys
+}
+"#;
+
+/// Adds key names to the first argument of the key-taking subcommands.
+const FISH_KEYS_HOOK: &str = r#"
+complete -c salusc -n "__fish_seen_subcommand_from read edit delete store store-file read-file; and test (count (commandline -opc | string match -v -- '-*')) -le 2" -f -a "(salusc __complete keys (commandline -ct) 2>/dev/null)"
+"#;
+
+/// Write the completion script for `shell` to `out`.
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be written or is not valid UTF-8.
+pub(crate) fn write_completions<W: Write>(shell: Shell, out: &mut W) -> Result<()> {
+    let mut cmd = <Cli as CommandFactory>::command();
+    let mut script = Vec::new();
+    generate(shell, &mut cmd, BIN_NAME, &mut script);
+    let script = String::from_utf8(script)?;
+
+    // `Shell` is non-exhaustive: the shells without a key hook get the
+    // generated script as it is, so they are not matched on.
+    if shell == Shell::Zsh {
+        out.write_all(zsh_with_keys(&script).as_bytes())?;
+        return Ok(());
+    }
+    out.write_all(script.as_bytes())?;
+    if shell == Shell::Bash {
+        out.write_all(BASH_KEYS_HOOK.as_bytes())?;
+    } else if shell == Shell::Fish {
+        out.write_all(FISH_KEYS_HOOK.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Define `_salusc_keys` right after the `#compdef` line and point every `KEY`
+/// positional at it instead of the default (file) completion.
+fn zsh_with_keys(script: &str) -> String {
+    let mut out = String::with_capacity(script.len().saturating_add(ZSH_KEYS_FUNCTION.len()));
+    for (idx, line) in script.lines().enumerate() {
+        if line.trim_start().starts_with("':key -- ") {
+            out.push_str(&line.replacen(":_default'", ":_salusc_keys'", 1));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+        if idx == 0 {
+            out.push_str(ZSH_KEYS_FUNCTION);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use clap_complete::Shell;
+
+    use super::write_completions;
+
+    fn script(shell: Shell) -> Result<String> {
+        let mut out = Vec::new();
+        write_completions(shell, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn every_shell_generates_a_script() -> Result<()> {
+        for shell in [
+            Shell::Bash,
+            Shell::Zsh,
+            Shell::Fish,
+            Shell::PowerShell,
+            Shell::Elvish,
+        ] {
+            assert!(script(shell)?.contains("salusc"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bash_fish_and_zsh_complete_keys_dynamically() -> Result<()> {
+        assert!(script(Shell::Bash)?.contains("__complete keys"));
+        assert!(script(Shell::Fish)?.contains("__complete keys"));
+        let zsh = script(Shell::Zsh)?;
+        assert!(zsh.starts_with("#compdef salusc\n"));
+        assert!(zsh.contains("__complete keys"));
+        assert!(zsh.contains(":_salusc_keys'"));
+        Ok(())
+    }
+}
@@ -10,12 +10,8 @@
 //!
 //! Generation is performed entirely on the client with a cryptographically
 //! secure RNG (`rand::rng()`); no key material or daemon round-trip is
-//! involved unless the caller chooses to store the result.
-//!
-//! The bundled passphrase word list is the EFF "large" word list, which is
-//! distributed by the Electronic Frontier Foundation under the Creative
-//! Commons Attribution 3.0 United States license (CC BY 3.0 US). See
-//! <https://www.eff.org/dice> and `eff_large_wordlist.txt`.
+//! involved unless the caller chooses to store the result. Passphrase words
+//! come from the EFF word list bundled with `libsalus`.
 
 use anyhow::{Result, anyhow};
 use crossterm::style::{Color, Stylize, style};
@@ -33,9 +29,6 @@ const DIGITS: &str = "0123456789";
 /// Symbol characters, added when `special` is enabled.
 const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>?";
 
-/// The EFF "large" word list (7776 words, one per line), embedded at build time.
-const WORDLIST: &str = include_str!("eff_large_wordlist.txt");
-
 /// Generate a password or passphrase.
 ///
 /// When `passphrase` is `Some(n)` an `n`-word passphrase is produced using
@@ -110,7 +103,7 @@ fn gen_password(length: u32, caps: bool, numbers: bool, special: bool) -> Result
 
 /// Build a random passphrase of `words` words formatted according to `kind`.
 fn gen_passphrase(words: u32, kind: GenKind) -> Result<String> {
-    let list: Vec<&str> = WORDLIST.lines().filter(|line| !line.is_empty()).collect();
+    let list: Vec<&str> = libsalus::passphrase_words().collect();
     let mut rng = rand::rng();
 
     let count = usize::try_from(words).unwrap_or(usize::MAX);
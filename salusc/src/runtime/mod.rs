@@ -6,22 +6,46 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
+use std::collections::BTreeMap;
 use std::ffi::OsString;
-use std::io::IsTerminal as _;
+use std::io::{IsTerminal as _, Read as _};
+use std::path::Path;
+use std::time::Duration;
 
-use anyhow::{Result, bail};
+use anyhow::{Context as _, Result, bail};
 use clap::Parser;
+use libsalus::{DiffRequest, ImportCa, IssueCert, MAX_MESSAGE_SIZE, NewCa, SignSshKey};
 use tokio::io::AsyncReadExt;
+use zeroize::{Zeroize as _, Zeroizing};
 
+#[cfg(any(target_os = "macos", windows))]
+use crate::credstore;
 use crate::{
-    config::load,
-    inter::Inter,
-    runtime::cli::{Cli, Commands},
+    cargo,
+    clipboard::{self, DEFAULT_CLIP_TIMEOUT},
+    config::{ConfigSalusc, load},
+    error::Error,
+    exec::EnvNames,
+    formats::{self, FileFormat, ImportFormat, ItemRules},
+    inter::{Inter, RandomEncoding, ShareDelivery},
+    interrupt,
+    output::GeneratedRecord,
+    runtime::cli::{
+        ApprovalAction, Cli, Commands, CompleteTarget, DataKeyAction, KeyAction, LeaseAction,
+        PkiAction, PluginAction, SharesAction, SshAction, Switch, TemplateAction,
+    },
+    token,
 };
+#[cfg(feature = "vault")]
+use crate::{inter::VaultImport, vault::Vault};
 
 mod cli;
+mod completions;
 mod generate;
 
+/// The most bytes of a value or patch read from stdin, unless configured.
+const DEFAULT_MAX_VALUE_BYTES: usize = 65_536; // 64 KiB
+
 pub(crate) async fn run<I, T>(args: Option<I>) -> Result<()>
 where
     I: IntoIterator<Item = T>,
@@ -34,62 +58,290 @@ where
         Cli::try_parse()?
     };
 
-    // Load the layered configuration (TOML file, SALUSC_ env vars, CLI flags).
-    let config = load(&cli, cli.config_path())?;
+    // Load the layered configuration (config file, SALUSC_ env vars, CLI flags).
+    let config = load(&cli, cli.config_path(), cli.config_format())?;
 
+    let output = config.output();
     let inter = Inter::builder()
         .maybe_name(config.socket_path().map(String::from))
         .maybe_agent_name(config.agent_socket_path().map(String::from))
+        .maybe_transport_key(config.transport_key_path().map(String::from))
+        .output(output)
+        .maybe_connect_timeout(config.connect_timeout().map(Duration::from_secs))
+        .maybe_request_timeout(config.request_timeout().map(Duration::from_secs))
+        .maybe_retries(config.retries())
+        .auto_start(config.auto_start())
+        .timing(config.timing())
         .build();
+    interrupt::watch();
 
-    match cli.command() {
+    match dispatch(cli.command(), &config, &inter).await {
+        // In a structured mode every failure becomes an error object on stdout,
+        // including ones that never reached the daemon (e.g. no connection).
+        Err(e) if !output.is_plain() && e.downcast_ref::<Error>().is_none() => {
+            output.fail("client_error", &format!("{e:#}"))
+        }
+        result => result,
+    }
+}
+
+#[allow(
+    clippy::too_many_lines,
+    reason = "one match arm per subcommand; splitting it would only scatter them"
+)]
+async fn dispatch(command: Commands, config: &ConfigSalusc, inter: &Inter) -> Result<()> {
+    match command {
         Commands::Shares {
+            action,
             num_shares,
             threshold,
-        } => inter.shares(num_shares, threshold).await?,
+            key_bits,
+            kdf,
+            mnemonic,
+            qr,
+            qr_png,
+            out_dir,
+            paper,
+            token_cmd,
+            yubikey_piv,
+        } => {
+            let token_cmd = token_cmd
+                .as_deref()
+                .or(yubikey_piv.then_some(token::YUBIKEY_PIV_CMD));
+            let delivery = ShareDelivery::builder()
+                .mnemonic(mnemonic)
+                .qr(qr)
+                .maybe_qr_png(qr_png.as_deref())
+                .maybe_out_dir(out_dir.as_deref())
+                .maybe_paper(paper.as_deref())
+                .maybe_token_cmd(token_cmd)
+                .build();
+            match action {
+                Some(SharesAction::Refresh) => inter.refresh_shares(delivery).await?,
+                None => {
+                    inter
+                        .shares(num_shares, threshold, key_bits.into(), kdf, delivery)
+                        .await?;
+                }
+            }
+        }
         Commands::Unlock { set, duration } => inter.unlock(set, duration).await?,
         Commands::Lock => inter.lock().await?,
+        Commands::Status { warnings } => inter.status(warnings).await?,
+        Commands::ReadOnly { mode } => inter.read_only(mode == Switch::On).await?,
+        Commands::ReloadConfig => inter.reload_config().await?,
+        Commands::VerifyShare => inter.verify_share().await?,
         Commands::Store {
             key,
             value,
             max_value_bytes,
             force,
+            no_overwrite,
+            with_key,
         } => {
-            const DEFAULT_MAX: usize = 65_536; // 64 KiB
             let max_bytes = max_value_bytes
                 .or_else(|| config.store_max_value_bytes())
-                .unwrap_or(DEFAULT_MAX);
+                .unwrap_or(DEFAULT_MAX_VALUE_BYTES);
 
-            let value = if let Some(v) = value {
-                v
-            } else {
-                if std::io::stdin().is_terminal() {
-                    eprint!("Value: ");
-                }
-                let mut buf = String::new();
-                let _ = tokio::io::stdin()
-                    .take((max_bytes as u64).saturating_add(1))
-                    .read_to_string(&mut buf)
-                    .await?;
-                if buf.len() > max_bytes {
-                    bail!(
-                        "stdin input exceeds {max_bytes} bytes; \
-                         increase with --max-value-bytes or SALUSC_STORE_MAX_VALUE_BYTES"
-                    );
-                }
-                if buf.ends_with('\n') {
-                    let _ = buf.pop();
-                    if buf.ends_with('\r') {
-                        let _ = buf.pop();
-                    }
-                }
-                buf
+            let value = match value {
+                Some(v) => check_value_len(v, max_bytes)?,
+                None => read_stdin_value(max_bytes).await?,
             };
-            inter.store(key, value, force).await?;
+            let key = config.in_namespace(key);
+            inter
+                .store(key, value, force, no_overwrite, with_key.as_deref())
+                .await?;
         }
 
-        Commands::Read { key } => inter.read(key).await?,
-        Commands::Delete { key, force } => inter.delete(key, force).await?,
+        Commands::Read {
+            key,
+            clip,
+            clip_timeout,
+            field,
+        } => {
+            let key = config.in_namespace(key);
+            if clip {
+                let seconds = clip_timeout
+                    .or_else(|| config.clip_timeout())
+                    .unwrap_or(DEFAULT_CLIP_TIMEOUT);
+                inter.clip(key, field, seconds).await?;
+            } else {
+                inter.read(key, field).await?;
+            }
+        }
+        Commands::Patch {
+            key,
+            patch,
+            max_value_bytes,
+        } => {
+            let patch = if let Some(patch) = patch {
+                patch
+            } else {
+                let max_bytes = max_value_bytes
+                    .or_else(|| config.store_max_value_bytes())
+                    .unwrap_or(DEFAULT_MAX_VALUE_BYTES);
+                read_stdin_value(max_bytes).await?
+            };
+            inter.patch(config.in_namespace(key), patch).await?;
+        }
+        Commands::Link { alias, target } => {
+            inter
+                .link(config.in_namespace(alias), config.in_namespace(target))
+                .await?;
+        }
+        Commands::RotateAfter { key, seconds, .. } => {
+            inter
+                .rotate_after(config.in_namespace(key), seconds)
+                .await?;
+        }
+        Commands::Exists { key } => inter.exists(config.in_namespace(key)).await?,
+        Commands::History { key } => inter.history(config.in_namespace(key)).await?,
+        Commands::Diff {
+            key,
+            from,
+            to,
+            show_values,
+        } => {
+            let request = DiffRequest::builder()
+                .key(config.in_namespace(key))
+                .from(from)
+                .to(to)
+                .show_values(show_values)
+                .build();
+            inter.diff(request).await?;
+        }
+        Commands::Edit { key, create } => inter.edit(config.in_namespace(key), create).await?,
+        Commands::Import {
+            file,
+            format,
+            prefix,
+            maps,
+            folders,
+            dry_run,
+            force,
+        } => {
+            let entries = read_import_file(&file, format, &ItemRules::new(maps, folders))?;
+            let prefix = config.in_namespace(prefix);
+            inter.import(&prefix, entries, dry_run, force).await?;
+        }
+        #[cfg(feature = "vault")]
+        Commands::ImportVault {
+            path,
+            addr,
+            token,
+            prefix,
+            all_versions,
+            state,
+            report,
+            dry_run,
+            force,
+        } => {
+            let Some(addr) = addr.or_else(|| std::env::var("VAULT_ADDR").ok()) else {
+                bail!("Give the Vault server with --addr or VAULT_ADDR");
+            };
+            let Some(token) = token.or_else(|| std::env::var("VAULT_TOKEN").ok()) else {
+                bail!("Give a Vault token with VAULT_TOKEN or --token");
+            };
+            let vault = Vault::new(&addr, Zeroizing::new(token))?;
+            let import = VaultImport::builder()
+                .path(&path)
+                .prefix(&prefix)
+                .all_versions(all_versions)
+                .dry_run(dry_run)
+                .force(force)
+                .maybe_state(state.as_deref())
+                .maybe_report(report.as_deref())
+                .build();
+            inter.import_vault(&vault, import).await?;
+        }
+        #[cfg(target_os = "macos")]
+        Commands::ImportKeychain {
+            service,
+            prefix,
+            dry_run,
+            force,
+        } => {
+            let credentials = credstore::read(service.as_deref())?;
+            inter
+                .import_credentials(&prefix, credentials, dry_run, force)
+                .await?;
+        }
+        #[cfg(target_os = "macos")]
+        Commands::ExportKeychain { prefix, force } => {
+            inter.export_credentials(&prefix, force).await?;
+        }
+        #[cfg(windows)]
+        Commands::ImportWincred {
+            filter,
+            prefix,
+            dry_run,
+            force,
+        } => {
+            let credentials = credstore::read(filter.as_deref())?;
+            inter
+                .import_credentials(&prefix, credentials, dry_run, force)
+                .await?;
+        }
+        #[cfg(windows)]
+        Commands::ExportWincred { prefix, force } => {
+            inter.export_credentials(&prefix, force).await?;
+        }
+        Commands::Export {
+            prefix,
+            format,
+            redact,
+            force,
+        } => {
+            let prefix = config.in_namespace(prefix);
+            inter.export(&prefix, format, redact, force).await?;
+        }
+        Commands::Exec {
+            prefix,
+            transform,
+            env_prefix,
+            command,
+        } => {
+            let names = EnvNames::new(
+                transform
+                    .or_else(|| config.exec_transform())
+                    .unwrap_or_default(),
+                env_prefix
+                    .or_else(|| config.exec_env_prefix().map(String::from))
+                    .unwrap_or_default(),
+            );
+            inter
+                .exec(&config.in_namespace(prefix), &names, &command)
+                .await?;
+        }
+        Commands::Template {
+            action:
+                TemplateAction::Render {
+                    input,
+                    out,
+                    watch,
+                    interval,
+                },
+        } => {
+            inter
+                .render_template(&input, out.as_deref(), watch, interval)
+                .await?;
+        }
+        Commands::Delete {
+            key,
+            prefix,
+            dry_run,
+            force,
+        } => {
+            if let Some(prefix) = prefix {
+                inter
+                    .delete_prefix(config.in_namespace(prefix), dry_run, force)
+                    .await?;
+            } else {
+                // Clap requires a key when there is no prefix.
+                let key = key.unwrap_or_default();
+                inter.delete(config.in_namespace(key), force).await?;
+            }
+        }
         Commands::Find { regex } => inter.find(regex).await?,
         Commands::Search { query, limit } => inter.search(query, limit).await?,
         Commands::Enroll {
@@ -99,6 +351,13 @@ where
         } => inter.enroll(name, force, independent_auto).await?,
         Commands::Forget { name, all, force } => inter.forget(name.as_deref(), all, force).await?,
         Commands::EnrollStatus => inter.enroll_status().await?,
+        Commands::ClipHold { seconds } => clipboard::hold(seconds)?,
+        Commands::Completions { shell } => {
+            completions::write_completions(shell, &mut std::io::stdout())?;
+        }
+        Commands::Complete {
+            target: CompleteTarget::Keys { prefix },
+        } => inter.complete_keys(&prefix).await,
         Commands::Gen {
             length,
             caps,
@@ -109,12 +368,292 @@ where
             key,
         } => {
             let secret = generate::generate(length, caps, numbers, special, passphrase, kind)?;
-            if let Some(key) = key {
-                inter.store(key, secret.clone(), false).await?;
+            let stored = match &key {
+                Some(key) => inter.store_value(key, secret.clone(), false).await?,
+                None => false,
+            };
+            if config.output().is_plain() {
+                generate::print_secret(&secret);
+            } else {
+                let stored_as = key.as_deref().filter(|_| stored);
+                config
+                    .output()
+                    .emit(&GeneratedRecord::new(Some(&secret), stored_as))?;
+            }
+        }
+        Commands::Generate { action } => inter.generate(action.into_request()).await?,
+        Commands::SigningKey {
+            name,
+            algorithm,
+            force,
+        } => inter.signing_key(name, algorithm.into(), force).await?,
+        Commands::Sign { key, file } => {
+            inter
+                .sign(key, read_message(file.as_deref())?, false)
+                .await?;
+        }
+        Commands::Hmac { key, file } => {
+            inter
+                .sign(key, read_message(file.as_deref())?, true)
+                .await?;
+        }
+        Commands::DataKey { action, bits } => match action {
+            Some(DataKeyAction::Decrypt { ciphertext }) => {
+                inter.decrypt_data_key(&ciphertext).await?;
             }
-            generate::print_secret(&secret);
+            None => inter.generate_data_key(bits).await?,
+        },
+        Commands::Random {
+            bytes,
+            base64,
+            uuid,
+            ..
+        } => {
+            inter
+                .random(bytes, RandomEncoding::from_flags(base64, uuid))
+                .await?;
+        }
+        Commands::EncryptFile { input, out, force } => {
+            inter.encrypt_file(&input, out, force).await?;
+        }
+        Commands::DecryptFile { input, out, force } => {
+            inter.decrypt_file(&input, out, force).await?;
+        }
+        Commands::StoreFile { key, file, force } => {
+            inter
+                .store_file(config.in_namespace(key), &file, force)
+                .await?;
+        }
+        Commands::ReadFile { key, out, force } => {
+            inter
+                .read_file(config.in_namespace(key), out, force)
+                .await?;
+        }
+        Commands::Encrypt {
+            context,
+            deterministic,
+            key,
+            file,
+        } => {
+            let plaintext = Zeroizing::new(read_message(file.as_deref())?);
+            inter
+                .encrypt(context, &plaintext, deterministic, key)
+                .await?;
+        }
+        Commands::Decrypt {
+            context,
+            ciphertext,
+        } => inter.decrypt(context, &ciphertext).await?,
+        Commands::Key { action } => match action {
+            KeyAction::Create { name, rotate_after } => {
+                inter.create_key(name, rotate_after).await?;
+            }
+            KeyAction::Rotate { name } => inter.rotate_key(name).await?,
+            KeyAction::Rewrap { prefix, wait } => inter.rewrap(prefix, wait).await?,
+            KeyAction::Rewraps => inter.rewraps().await?,
+            KeyAction::List => inter.list_keys().await?,
+        },
+        Commands::Plugin { action } => match action {
+            PluginAction::List => inter.list_plugins().await?,
+            PluginAction::Mint { plugin, role, ttl } => {
+                inter.mint_credential(plugin, role, ttl).await?;
+            }
+        },
+        Commands::Lease { action } => match action {
+            LeaseAction::Renew {
+                lease_id,
+                increment,
+            } => inter.renew_lease(lease_id, increment).await?,
+            LeaseAction::Revoke { lease_id } => inter.revoke_lease(lease_id).await?,
+        },
+        Commands::Approvals { action } => match action {
+            None => inter.list_approvals().await?,
+            Some(ApprovalAction::Approve { id }) => inter.approve(id).await?,
+            Some(ApprovalAction::Deny { id }) => inter.deny_approval(id).await?,
+        },
+        Commands::Ssh { action } => match action {
+            SshAction::Sign {
+                file,
+                principals,
+                ttl,
+                ca,
+                host,
+            } => {
+                let public_key = read_message(file.as_deref())?;
+                let request = SignSshKey::builder()
+                    .ca(ca)
+                    .public_key(String::from_utf8_lossy(&public_key).trim())
+                    .principals(principals)
+                    .maybe_ttl(ttl)
+                    .host(host)
+                    .build();
+                inter.sign_ssh_key(request).await?;
+            }
+        },
+        Commands::Pki { action } => match action {
+            PkiAction::Init { cn, ttl, force } => {
+                let request = NewCa::builder()
+                    .common_name(cn)
+                    .ttl(ttl)
+                    .force(force)
+                    .build();
+                inter.create_ca(request).await?;
+            }
+            PkiAction::ImportCa {
+                certificate,
+                key,
+                force,
+            } => {
+                let certificate = read_message(Some(&certificate))?;
+                let key = Zeroizing::new(read_message(Some(&key))?);
+                let request = ImportCa::builder()
+                    .certificate(String::from_utf8_lossy(&certificate))
+                    .private_key(String::from_utf8_lossy(&key))
+                    .force(force)
+                    .build();
+                inter.import_ca(request).await?;
+            }
+            PkiAction::Issue { cn, san, ttl } => {
+                let request = IssueCert::builder()
+                    .common_name(cn)
+                    .sans(san)
+                    .maybe_ttl(ttl)
+                    .build();
+                inter.issue_cert(request).await?;
+            }
+            PkiAction::Revoke { serial } => inter.revoke_cert(serial).await?,
+            PkiAction::List => inter.list_certs().await?,
+            PkiAction::Crl => inter.crl().await?,
+        },
+        Commands::Backup { path, recipient } => inter.backup(&path, recipient).await?,
+        Commands::Fsck => inter.fsck().await?,
+        Commands::WrappingKey => inter.wrapping_key().await?,
+        Commands::ImportWrapped { key, file, force } => {
+            let wrapped = read_message(file.as_deref())?;
+            inter
+                .import_wrapped(key, &String::from_utf8_lossy(&wrapped), force)
+                .await?;
+        }
+        Commands::ExportWrapped { key, recipient } => {
+            inter.export_wrapped(key, &recipient).await?;
+        }
+        Commands::CargoCredential { prefix, .. } => {
+            cargo::serve(
+                inter,
+                &prefix,
+                std::io::BufReader::new(std::io::stdin()),
+                std::io::stdout(),
+            )
+            .await?;
+        }
+        Commands::Sync {
+            prefixes,
+            from,
+            to,
+            strategy,
+            dry_run,
+        } => {
+            let source = from.map_or_else(|| inter.clone(), |from| inter.for_socket(from));
+            source
+                .sync(&inter.for_socket(to), prefixes, strategy.into(), dry_run)
+                .await?;
+        }
+        Commands::Wrap { recipient, file } => {
+            inter.wrap(&recipient, &Zeroizing::new(read_message(file.as_deref())?))?;
+        }
+        Commands::Verify {
+            key,
+            signature,
+            file,
+        } => {
+            inter
+                .verify_signature(key, &signature, read_message(file.as_deref())?)
+                .await?;
         }
     }
 
     Ok(())
 }
+
+/// Read a message to sign from `file`, or stdin when there is none, byte for
+/// byte.
+fn read_message(file: Option<&Path>) -> Result<Vec<u8>> {
+    let mut message = Vec::new();
+    let limit = u64::try_from(MAX_MESSAGE_SIZE).unwrap_or(u64::MAX);
+    let _ = match file {
+        Some(file) => std::fs::File::open(file)
+            .and_then(|f| f.take(limit.saturating_add(1)).read_to_end(&mut message))
+            .with_context(|| format!("unable to read {}", file.display()))?,
+        None => std::io::stdin()
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut message)?,
+    };
+    if message.len() > MAX_MESSAGE_SIZE {
+        bail!("the message exceeds {MAX_MESSAGE_SIZE} bytes");
+    }
+    Ok(message)
+}
+
+/// Read and parse an `import` file (`-` is stdin).
+fn read_import_file(
+    file: &Path,
+    format: Option<ImportFormat>,
+    rules: &ItemRules,
+) -> Result<BTreeMap<String, String>> {
+    let Some(format) = format.or_else(|| FileFormat::from_path(file).map(ImportFormat::from))
+    else {
+        bail!(
+            "cannot tell the format of '{}'; pass --format dotenv|json|yaml|bitwarden|1password",
+            file.display()
+        );
+    };
+    let mut text = String::new();
+    if file == Path::new("-") {
+        let _ = std::io::stdin().read_to_string(&mut text)?;
+    } else {
+        text = std::fs::read_to_string(file)
+            .with_context(|| format!("unable to read {}", file.display()))?;
+    }
+    let entries = formats::parse_import(format, &text, rules);
+    text.zeroize();
+    entries.with_context(|| format!("unable to parse {}", file.display()))
+}
+
+/// Read a `store` value from stdin, capped at `max_bytes`, dropping one
+/// trailing newline.
+/// `value`, when it is no longer than `max_bytes`, so an oversized value
+/// given on the command line is refused before it is sent.
+fn check_value_len(value: String, max_bytes: usize) -> Result<String> {
+    if value.len() > max_bytes {
+        bail!(
+            "the value is {} bytes, over the limit of {max_bytes}; \
+             increase with --max-value-bytes or SALUSC_STORE_MAX_VALUE_BYTES",
+            value.len()
+        );
+    }
+    Ok(value)
+}
+
+async fn read_stdin_value(max_bytes: usize) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        eprint!("Value: ");
+    }
+    let mut buf = String::new();
+    let _ = tokio::io::stdin()
+        .take((max_bytes as u64).saturating_add(1))
+        .read_to_string(&mut buf)
+        .await?;
+    if buf.len() > max_bytes {
+        bail!(
+            "stdin input exceeds {max_bytes} bytes; \
+             increase with --max-value-bytes or SALUSC_STORE_MAX_VALUE_BYTES"
+        );
+    }
+    if buf.ends_with('\n') {
+        let _ = buf.pop();
+        if buf.ends_with('\r') {
+            let _ = buf.pop();
+        }
+    }
+    Ok(buf)
+}
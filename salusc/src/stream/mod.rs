@@ -0,0 +1,327 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Chunked file encryption, for `encrypt-file` and `decrypt-file`.
+//!
+//! A file is encrypted locally under a fresh data key from the daemon, so only
+//! the key crosses the socket and a file of any size is handled in constant
+//! memory. The layout is:
+//!
+//! * a header: [`MAGIC`], the format version, the chunk size, the wrapped data
+//!   key, and a random nonce prefix;
+//! * the chunks: each up to the chunk size of plaintext, sealed with
+//!   AES-256-GCM under a nonce of the prefix, the chunk counter, and a flag
+//!   marking the final chunk, with the header as AAD.
+//!
+//! Every chunk but the last is full, and the last is flagged (and may be
+//! empty), so truncating, reordering, or splicing chunks fails to decrypt.
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result, bail};
+use aws_lc_rs::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, MAX_TAG_LEN, NONCE_LEN, Nonce, UnboundKey},
+    rand,
+};
+use zeroize::Zeroizing;
+
+/// The bytes every encrypted file starts with.
+pub(crate) const MAGIC: &[u8; 8] = b"SALUSENC";
+/// The layout written by this version.
+const VERSION: u8 = 1;
+/// The plaintext bytes per chunk written by this version.
+pub(crate) const CHUNK_SIZE: u32 = 64 * 1024;
+/// The largest chunk size accepted when reading, so a corrupt header cannot
+/// demand a huge buffer.
+const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+/// The random part of each chunk's nonce; the counter and final flag fill the
+/// rest.
+const PREFIX_LEN: usize = NONCE_LEN.saturating_sub(5);
+/// The data key size, in bits, for AES-256-GCM.
+pub(crate) const DATA_KEY_BITS: u16 = 256;
+
+/// The suffix `encrypt-file` adds and `decrypt-file` strips.
+const SUFFIX: &str = "enc";
+
+/// Where `encrypt-file` writes `input` by default: `input` with `.enc` added.
+pub(crate) fn encrypted_path(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".");
+    path.push(SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Where `decrypt-file` writes `input` by default: `input` without its `.enc`
+/// suffix, if it has one.
+pub(crate) fn decrypted_path(input: &Path) -> Option<PathBuf> {
+    (input.extension().is_some_and(|ext| ext == SUFFIX) && input.file_stem().is_some())
+        .then(|| input.with_extension(""))
+}
+
+/// A parsed file header.
+#[derive(Clone, Debug)]
+pub(crate) struct Header {
+    chunk_size: u32,
+    wrapped_key: Vec<u8>,
+    prefix: [u8; PREFIX_LEN],
+    /// The header exactly as written, which every chunk authenticates.
+    raw: Vec<u8>,
+}
+
+impl Header {
+    /// A header for a new file, with a fresh nonce prefix.
+    pub(crate) fn new(wrapped_key: &[u8]) -> Result<Self> {
+        let mut prefix = [0u8; PREFIX_LEN];
+        rand::fill(&mut prefix)?;
+        let key_len = u16::try_from(wrapped_key.len()).context("the wrapped key is too long")?;
+        let mut raw = MAGIC.to_vec();
+        raw.push(VERSION);
+        raw.extend_from_slice(&CHUNK_SIZE.to_be_bytes());
+        raw.extend_from_slice(&key_len.to_be_bytes());
+        raw.extend_from_slice(wrapped_key);
+        raw.extend_from_slice(&prefix);
+        Ok(Self {
+            chunk_size: CHUNK_SIZE,
+            wrapped_key: wrapped_key.to_vec(),
+            prefix,
+            raw,
+        })
+    }
+
+    /// Read a header from the start of an encrypted file.
+    pub(crate) fn read_from(reader: &mut impl Read) -> Result<Self> {
+        let mut fixed = [0u8; 15];
+        reader
+            .read_exact(&mut fixed)
+            .context("the file is too short to be encrypted by salusc")?;
+        let (magic, rest) = fixed.split_at(MAGIC.len());
+        if magic != MAGIC {
+            bail!("the file was not encrypted by salusc");
+        }
+        let (&[version], rest) = rest.split_at(1) else {
+            bail!("the file header is truncated");
+        };
+        if version != VERSION {
+            bail!("the file uses format version {version}; this salusc reads version {VERSION}");
+        }
+        let (chunk_size, key_len) = rest.split_at(4);
+        let chunk_size = u32::from_be_bytes(chunk_size.try_into()?);
+        let key_len = u16::from_be_bytes(key_len.try_into()?);
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            bail!("the file header names an unusable chunk size ({chunk_size})");
+        }
+        let mut wrapped_key = vec![0u8; usize::from(key_len)];
+        let mut prefix = [0u8; PREFIX_LEN];
+        reader
+            .read_exact(&mut wrapped_key)
+            .and_then(|()| reader.read_exact(&mut prefix))
+            .context("the file header is truncated")?;
+        let mut raw = fixed.to_vec();
+        raw.extend_from_slice(&wrapped_key);
+        raw.extend_from_slice(&prefix);
+        Ok(Self {
+            chunk_size,
+            wrapped_key,
+            prefix,
+            raw,
+        })
+    }
+
+    /// The data key, wrapped by the store key.
+    pub(crate) fn wrapped_key(&self) -> &[u8] {
+        &self.wrapped_key
+    }
+
+    /// The nonce for chunk `counter`.
+    fn nonce(&self, counter: u32, last: bool) -> Nonce {
+        let mut nonce = [0u8; NONCE_LEN];
+        let (prefix, rest) = nonce.split_at_mut(PREFIX_LEN);
+        prefix.copy_from_slice(&self.prefix);
+        let (count, flag) = rest.split_at_mut(4);
+        count.copy_from_slice(&counter.to_be_bytes());
+        flag.fill(u8::from(last));
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    fn chunk_len(&self) -> usize {
+        usize::try_from(self.chunk_size).unwrap_or(usize::MAX)
+    }
+}
+
+/// Encrypt everything from `reader` into `writer` under `data_key`, writing
+/// `header` first. Returns the plaintext bytes encrypted.
+pub(crate) fn encrypt(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    header: &Header,
+    data_key: &[u8],
+) -> Result<u64> {
+    let key = aead_key(data_key)?;
+    writer.write_all(&header.raw)?;
+    let chunk_len = header.chunk_len();
+    let mut buf = Zeroizing::new(Vec::with_capacity(chunk_len.saturating_add(MAX_TAG_LEN)));
+    let mut counter = 0u32;
+    let mut total = 0u64;
+    loop {
+        buf.clear();
+        let read = read_up_to(reader, &mut buf, chunk_len)?;
+        total = total.saturating_add(u64::try_from(read)?);
+        let last = read < chunk_len;
+        key.seal_in_place_append_tag(
+            header.nonce(counter, last),
+            Aad::from(&header.raw),
+            &mut *buf,
+        )?;
+        writer.write_all(&buf)?;
+        if last {
+            break;
+        }
+        counter = next(counter)?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Decrypt the chunks following `header` in `reader` into `writer` under
+/// `data_key`. Returns the plaintext bytes written.
+///
+/// Plaintext reaches `writer` chunk by chunk as each one authenticates, so a
+/// caller writing a file should only keep it once this returns `Ok`.
+pub(crate) fn decrypt(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    header: &Header,
+    data_key: &[u8],
+) -> Result<u64> {
+    let key = aead_key(data_key)?;
+    let sealed_len = header.chunk_len().saturating_add(MAX_TAG_LEN);
+    let mut buf = Zeroizing::new(Vec::with_capacity(sealed_len));
+    let mut counter = 0u32;
+    let mut total = 0u64;
+    loop {
+        buf.clear();
+        let read = read_up_to(reader, &mut buf, sealed_len)?;
+        let last = read < sealed_len;
+        let plaintext = key
+            .open_in_place(
+                header.nonce(counter, last),
+                Aad::from(&header.raw),
+                &mut buf,
+            )
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "chunk {} failed to decrypt: the file is damaged, truncated, or was \
+                     encrypted by another store",
+                    counter.saturating_add(1)
+                )
+            })?;
+        writer.write_all(plaintext)?;
+        total = total.saturating_add(u64::try_from(plaintext.len())?);
+        if last {
+            break;
+        }
+        counter = next(counter)?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+fn aead_key(data_key: &[u8]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, data_key)
+        .map_err(|_| anyhow::anyhow!("the data key is not an AES-256 key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn next(counter: u32) -> Result<u32> {
+    counter
+        .checked_add(1)
+        .context("the file has too many chunks for one data key")
+}
+
+/// Append up to `len` bytes from `reader` to `buf`, stopping early only at
+/// end of input. Returns the bytes read.
+fn read_up_to(reader: &mut impl Read, buf: &mut Vec<u8>, len: usize) -> Result<usize> {
+    Ok(reader.take(u64::try_from(len)?).read_to_end(buf)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use anyhow::{Result, bail};
+
+    use super::{CHUNK_SIZE, Header, decrypt, encrypt};
+
+    fn round_trip(plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key = [9u8; 32];
+        let header = Header::new(b"wrapped")?;
+        let mut sealed = Vec::new();
+        let _ = encrypt(&mut Cursor::new(plaintext), &mut sealed, &header, &key)?;
+        let mut reader = Cursor::new(sealed);
+        let read = Header::read_from(&mut reader)?;
+        assert_eq!(read.wrapped_key(), b"wrapped");
+        let mut opened = Vec::new();
+        let _ = decrypt(&mut reader, &mut opened, &read, &key)?;
+        Ok(opened)
+    }
+
+    #[test]
+    fn files_of_any_length_round_trip() -> Result<()> {
+        let chunk = usize::try_from(CHUNK_SIZE)?;
+        for len in [
+            0,
+            1,
+            chunk.saturating_sub(1),
+            chunk,
+            chunk.saturating_mul(2).saturating_add(5),
+        ] {
+            let plaintext: Vec<u8> = (0..len)
+                .map(|i| u8::try_from(i % 251))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(round_trip(&plaintext)?, plaintext, "length {len}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn truncation_and_tampering_are_caught() -> Result<()> {
+        let key = [3u8; 32];
+        let header = Header::new(b"wrapped")?;
+        let chunk = usize::try_from(CHUNK_SIZE)?;
+        let plaintext = vec![0x5a; chunk.saturating_mul(2)];
+        let mut sealed = Vec::new();
+        let _ = encrypt(&mut Cursor::new(&plaintext), &mut sealed, &header, &key)?;
+
+        let open = |bytes: &[u8], key: &[u8]| -> Result<Vec<u8>> {
+            let mut reader = Cursor::new(bytes);
+            let header = Header::read_from(&mut reader)?;
+            let mut out = Vec::new();
+            let _ = decrypt(&mut reader, &mut out, &header, key)?;
+            Ok(out)
+        };
+        assert_eq!(open(&sealed, &key)?, plaintext);
+        // Dropping the empty final chunk leaves a full chunk that is not
+        // flagged as the last.
+        let truncated = sealed
+            .get(..sealed.len().saturating_sub(16))
+            .unwrap_or_default();
+        assert!(open(truncated, &key).is_err());
+        let mut tampered = sealed.clone();
+        let Some(byte) = tampered.get_mut(40) else {
+            bail!("too short");
+        };
+        *byte ^= 1;
+        assert!(open(&tampered, &key).is_err());
+        assert!(open(&sealed, &[4u8; 32]).is_err());
+        assert!(open(b"not encrypted at all", &key).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Config-file templates with embedded secrets, for `template render`.
+//!
+//! A placeholder is `{{ secret "key/name" }}` (whitespace inside the braces is
+//! optional; `\"` and `\\` escape a quote or backslash in the key name).
+//! Everything else, including other `{{ ... }}` constructs, is copied through
+//! verbatim, so templates for tools with their own brace syntax keep working.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+/// Matches `{{ secret "..." }}`, capturing the quoted key name.
+const PLACEHOLDER: &str = r#"\{\{\s*secret\s+"((?:[^"\\]|\\.)*)"\s*\}\}"#;
+
+/// One piece of a parsed template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Literal(String),
+    Secret(String),
+}
+
+/// A parsed template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Split `text` into literal text and secret placeholders.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a placeholder names an empty key.
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        let placeholder = Regex::new(PLACEHOLDER)?;
+        let mut segments = vec![];
+        let mut last = 0;
+        for caps in placeholder.captures_iter(text) {
+            let (Some(whole), Some(key)) = (caps.get(0), caps.get(1)) else {
+                continue;
+            };
+            if let Some(literal) = text.get(last..whole.start())
+                && !literal.is_empty()
+            {
+                segments.push(Segment::Literal(literal.to_string()));
+            }
+            let key = unescape(key.as_str());
+            if key.is_empty() {
+                bail!("empty key name in placeholder '{}'", whole.as_str());
+            }
+            segments.push(Segment::Secret(key));
+            last = whole.end();
+        }
+        if let Some(rest) = text.get(last..)
+            && !rest.is_empty()
+        {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Every key the template references, once each.
+    pub(crate) fn keys(&self) -> BTreeSet<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Secret(key) => Some(key.as_str()),
+                Segment::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// Substitute `values` into the template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a referenced key has no value.
+    pub(crate) fn render(&self, values: &BTreeMap<String, String>) -> Result<String> {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Secret(key) => match values.get(key) {
+                    Some(value) => out.push_str(value),
+                    None => bail!("no value for key '{key}'"),
+                },
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\'
+            && let Some(next) = chars.next()
+        {
+            out.push(next);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+
+    use super::Template;
+
+    #[test]
+    fn placeholders_are_found_and_substituted() -> Result<()> {
+        let template = Template::parse(
+            "{\"user\": \"{{secret \"db/user\"}}\", \"pass\": \"{{ secret \"db/pass\" }}\", \"again\": \"{{ secret \"db/user\" }}\"}",
+        )?;
+        assert_eq!(
+            template.keys().into_iter().collect::<Vec<_>>(),
+            ["db/pass", "db/user"]
+        );
+        let values = BTreeMap::from([
+            ("db/user".to_string(), "u".to_string()),
+            ("db/pass".to_string(), "p".to_string()),
+        ]);
+        assert_eq!(
+            template.render(&values)?,
+            "{\"user\": \"u\", \"pass\": \"p\", \"again\": \"u\"}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn other_braces_pass_through_and_escapes_are_honored() -> Result<()> {
+        let template = Template::parse("{{ .Values.x }} {{ secret \"a\\\"b\" }}")?;
+        assert_eq!(template.keys().into_iter().collect::<Vec<_>>(), ["a\"b"]);
+        let values = BTreeMap::from([("a\"b".to_string(), "v".to_string())]);
+        assert_eq!(template.render(&values)?, "{{ .Values.x }} v");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_values_and_empty_keys_are_errors() -> Result<()> {
+        assert!(Template::parse("{{ secret \"\" }}").is_err());
+        let template = Template::parse("{{ secret \"k\" }}")?;
+        assert!(template.render(&BTreeMap::new()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Writing shares onto hardware tokens, for `shares --token-cmd`.
+//!
+//! salusc does not talk to tokens itself: the vendor tools already handle PIN
+//! and management-key prompts, touch policies, and device selection. Instead
+//! each share is handed to a token-writing command on its stdin, one token at a
+//! time, so the share never appears on screen, in the process list, or on the
+//! filesystem.
+
+use std::{
+    io::{BufRead as _, Write as _, stderr, stdin},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context as _, Result, bail};
+
+/// The command `--yubikey-piv` runs: import stdin as a vendor-range PIV data
+/// object with `ykman`, which prompts for the management key itself.
+pub(crate) const YUBIKEY_PIV_CMD: &str = "ykman piv objects import 0x5fff01 -";
+
+/// Write each of `shares` onto its own token with `command`.
+///
+/// Before each share `ready` is called with the share's number and the total;
+/// it returns once the operator has the next token connected, or `false` to
+/// stop. A failed write asks again, so another token can be tried. Returns a
+/// description of each share written.
+///
+/// # Errors
+///
+/// Returns an error naming the first share that was not written, when the
+/// operator stops or the command cannot be started.
+pub(crate) fn write_share_tokens<F>(
+    command: &str,
+    shares: &[String],
+    mut ready: F,
+) -> Result<Vec<String>>
+where
+    F: FnMut(usize, usize, Option<&str>) -> Result<bool>,
+{
+    let total = shares.len();
+    let mut written = Vec::with_capacity(total);
+    for (idx, share) in shares.iter().enumerate() {
+        let number = idx.saturating_add(1);
+        let mut failure = None;
+        loop {
+            if !ready(number, total, failure.as_deref())? {
+                bail!("share {number} of {total} was not written to a token");
+            }
+            match run(command, share, number, total)? {
+                None => break,
+                Some(reason) => failure = Some(reason),
+            }
+        }
+        written.push(format!("share {number} of {total} to a token"));
+    }
+    Ok(written)
+}
+
+/// Prompt on the terminal for token `number` of `total`, after reporting why
+/// the previous attempt failed. An empty line continues; `q` stops.
+///
+/// # Errors
+///
+/// Returns an error if the terminal cannot be read or written.
+pub(crate) fn prompt_for_token(number: usize, total: usize, failure: Option<&str>) -> Result<bool> {
+    let mut err = stderr().lock();
+    if let Some(failure) = failure {
+        writeln!(err, "Writing share {number} of {total} failed: {failure}")?;
+        write!(
+            err,
+            "Connect a token and press Enter to retry, or type q to stop: "
+        )?;
+    } else {
+        write!(
+            err,
+            "Connect token {number} of {total} and press Enter (q to stop): "
+        )?;
+    }
+    err.flush()?;
+    let mut line = String::new();
+    let _read = stdin().lock().read_line(&mut line)?;
+    Ok(!line.trim().eq_ignore_ascii_case("q"))
+}
+
+/// Run `command` once with `share` on its stdin, returning why it failed, if
+/// it did.
+fn run(command: &str, share: &str, number: usize, total: usize) -> Result<Option<String>> {
+    let mut child = shell(command)
+        .env("SALUS_SHARE_NUMBER", number.to_string())
+        .env("SALUS_SHARE_TOTAL", total.to_string())
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("unable to start the token command '{command}'"))?;
+    let mut pipe = child
+        .stdin
+        .take()
+        .context("unable to open the token command's stdin")?;
+    // A command that exits without reading fails below rather than here.
+    let fed = pipe.write_all(share.as_bytes());
+    drop(pipe);
+    let status = child.wait()?;
+    Ok(match (status.success(), fed) {
+        (true, Ok(())) => None,
+        (true, Err(e)) => Some(format!("the command did not read the share ({e})")),
+        (false, _) => Some(format!("the command exited with {status}")),
+    })
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    let _ = shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    let _ = shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use anyhow::Result;
+
+    use super::write_share_tokens;
+
+    #[test]
+    fn each_share_reaches_the_command_on_stdin() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("salusc-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let command = format!(
+            "cat > '{}'/token-$SALUS_SHARE_NUMBER-of-$SALUS_SHARE_TOTAL",
+            dir.display()
+        );
+        let shares = vec!["salus1-1-AAAA".to_string(), "salus1-2-BBBB".to_string()];
+        let mut prompts = Vec::new();
+        let written = write_share_tokens(&command, &shares, |number, total, failure| {
+            prompts.push((number, total, failure.is_some()));
+            Ok(true)
+        })?;
+        assert_eq!(written.len(), 2);
+        assert_eq!(prompts, [(1, 2, false), (2, 2, false)]);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("token-2-of-2"))?,
+            "salus1-2-BBBB"
+        );
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn a_failed_write_asks_again_and_can_stop() {
+        let shares = vec!["salus1-1-AAAA".to_string()];
+        let mut attempts = 0usize;
+        let result = write_share_tokens("exit 3", &shares, |_, _, failure| {
+            attempts = attempts.saturating_add(1);
+            Ok(failure.is_none())
+        });
+        assert_eq!(attempts, 2);
+        assert!(result.is_err_and(|e| e.to_string().contains("share 1 of 1")));
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    fmt::Write as _,
+    fs::{DirBuilder, File, OpenOptions, rename},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context as _, Result, bail};
+
+/// Atomically replace `path` with `contents`, readable only by the owner.
+///
+/// Everything written this way (rendered templates, shares) holds secrets, so
+/// it goes to a `0600` sibling that is renamed into place: readers never see a
+/// half-written file and the contents are never world-readable, even briefly.
+///
+/// # Errors
+///
+/// Returns an error if the temporary file cannot be written or renamed.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    write_private_with(path, |file| {
+        file.write_all(contents)
+            .with_context(|| format!("unable to write {}", path.display()))
+    })
+}
+
+/// Atomically replace `path` with whatever `write` puts in a `0600` sibling,
+/// as [`write_private`] does; the sibling is removed instead if `write` fails.
+///
+/// # Errors
+///
+/// Returns `write`'s error, or an error if the temporary file cannot be
+/// created or renamed.
+pub(crate) fn write_private_with<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut File) -> Result<()>,
+{
+    let mut private = PrivateFile::create(path)?;
+    let written = write(private.file());
+    private.finish(written)
+}
+
+/// The `0600` sibling [`write_private_with`] writes, for writers that cannot
+/// run in one closure (e.g. ones that await between writes).
+#[derive(Debug)]
+pub(crate) struct PrivateFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: File,
+}
+
+impl PrivateFile {
+    /// Create the sibling that will replace `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` names no file or the sibling cannot be
+    /// created.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("'{}' is not a file path", path.display()))?;
+        let temp = path.with_file_name(format!(".{file_name}.salusc-{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        let _ = options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt as _;
+            let _ = options.mode(0o600);
+        }
+        let file = options
+            .open(&temp)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            temp,
+            file,
+        })
+    }
+
+    /// The sibling, to write to.
+    pub(crate) fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Rename the sibling into place if `written` is `Ok`, or remove it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `written`'s error, or an error if the sibling cannot be synced
+    /// or renamed.
+    pub(crate) fn finish(self, written: Result<()>) -> Result<()> {
+        let Self { path, temp, file } = self;
+        let context = || format!("unable to write {}", path.display());
+        let written = written.and_then(|()| file.sync_all().with_context(context));
+        drop(file);
+        let written = written.and_then(|()| rename(&temp, &path).with_context(context));
+        if written.is_err() {
+            drop(std::fs::remove_file(&temp));
+        }
+        written
+    }
+}
+
+/// Create `dir` (and its parents) if needed; a newly created directory is
+/// `0700`.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created.
+pub(crate) fn create_private_dir(dir: &Path) -> Result<()> {
+    let mut builder = DirBuilder::new();
+    let _ = builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt as _;
+        let _ = builder.mode(0o700);
+    }
+    builder
+        .create(dir)
+        .with_context(|| format!("unable to create {}", dir.display()))
+}
+
+/// Lowercase hex for `bytes`.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(
+        String::with_capacity(bytes.len().saturating_mul(2)),
+        |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        },
+    )
+}
+
+/// Decode hex (either case, surrounding whitespace ignored).
+///
+/// # Errors
+///
+/// Returns an error if `text` has an odd length or a non-hex character.
+pub(crate) fn from_hex(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        bail!("'{text}' is not hex: it has an odd number of digits");
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .with_context(|| format!("'{text}' is not hex"))
+        })
+        .collect()
+}
+
+/// Format `time` as `YYYY-MM-DD HH:MM UTC`.
+///
+/// A time before the Unix epoch is shown as the epoch.
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    let (days, of_day) = (secs / 86_400, secs % 86_400);
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm, in
+    // unsigned arithmetic since `days` is never negative here).
+    let z = days.saturating_add(719_468);
+    let era = z / 146_097;
+    let doe = z.saturating_sub(era.saturating_mul(146_097));
+    let yoe = doe
+        .saturating_sub(doe / 1_460)
+        .saturating_add(doe / 36_524)
+        .saturating_sub(doe / 146_096)
+        / 365;
+    let doy = doe.saturating_sub(
+        yoe.saturating_mul(365)
+            .saturating_add(yoe / 4)
+            .saturating_sub(yoe / 100),
+    );
+    let mp = doy.saturating_mul(5).saturating_add(2) / 153;
+    let day = doy
+        .saturating_sub(mp.saturating_mul(153).saturating_add(2) / 5)
+        .saturating_add(1);
+    let month = if mp < 10 {
+        mp.saturating_add(3)
+    } else {
+        mp.saturating_sub(9)
+    };
+    let year = yoe
+        .saturating_add(era.saturating_mul(400))
+        .saturating_add(u64::from(month <= 2));
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        of_day / 3_600,
+        of_day % 3_600 / 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use anyhow::Result;
+
+    use super::{from_hex, to_hex, utc_timestamp, write_private};
+
+    #[test]
+    fn timestamps_are_civil_utc() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01 00:00 UTC");
+        // 2024-02-29 13:45:10 UTC, a leap day.
+        let leap = UNIX_EPOCH + Duration::from_secs(1_709_214_310);
+        assert_eq!(utc_timestamp(leap), "2024-02-29 13:45 UTC");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_767_225_599);
+        assert_eq!(utc_timestamp(new_year), "2025-12-31 23:59 UTC");
+    }
+
+    #[test]
+    fn hex_round_trips_and_rejects_junk() -> Result<()> {
+        assert_eq!(to_hex(&[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(from_hex(" 00AB7f\n")?, [0x00, 0xab, 0x7f]);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn written_file_is_private() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("salusc-private-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("config.json");
+        write_private(&path, b"secret")?;
+        write_private(&path, b"replaced")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "replaced");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
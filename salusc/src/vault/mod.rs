@@ -0,0 +1,609 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Reading a Vault KV mount, for `import-vault`.
+//!
+//! The mount holding a path, and its KV version, are learned from
+//! `sys/internal/ui/mounts` as the `vault` CLI does. A version 2 mount is
+//! listed through `metadata/` and read through `data/`, a version at a time; a
+//! version 1 mount holds only the latest value of each secret.
+//!
+//! A secret is a map of fields, and each field becomes its own key,
+//! `<prefix><path>/<field>`, with nested fields joined by `/` as `import` joins
+//! them. Older versions land beside it as `<prefix><path>@<version>/<field>`.
+//! The store keeps no metadata per key, so a secret's version, creation time,
+//! and custom metadata go into the mapping report instead.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use anyhow::{Context as _, Result, bail};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::{formats, utils};
+
+/// A KV mount, and the folder under it an import starts from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Mount {
+    /// The mount's path, ending in `/` (e.g. `secret/`)
+    path: String,
+    /// The KV engine version, 1 or 2
+    version: u8,
+    /// The folder under the mount, empty or ending in `/`
+    start: String,
+}
+
+impl Mount {
+    /// The API path listing `folder`.
+    fn listing(&self, folder: &str) -> String {
+        match self.version {
+            2 => format!("{}metadata/{folder}", self.path),
+            _ => format!("{}{folder}", self.path),
+        }
+    }
+
+    /// The API path reading the secret at `path`.
+    fn data(&self, path: &str) -> String {
+        match self.version {
+            2 => format!("{}data/{path}", self.path),
+            _ => format!("{}{path}", self.path),
+        }
+    }
+
+    /// The Vault path of the secret `rel` names, as the `vault` CLI shows it.
+    pub(crate) fn full(&self, rel: &str) -> String {
+        format!("{}{}{rel}", self.path, self.start)
+    }
+}
+
+/// A client for one Vault server.
+pub(crate) struct Vault {
+    http: Client,
+    addr: String,
+    token: Zeroizing<String>,
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Vault")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Vault {
+    /// A client for the server at `addr`, acting with `token`.
+    pub(crate) fn new(addr: &str, token: Zeroizing<String>) -> Result<Self> {
+        Ok(Self {
+            http: Client::builder().build()?,
+            addr: addr.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// Where an import of `mount` reads from, as recorded in its progress.
+    pub(crate) fn source(&self, mount: &Mount) -> String {
+        format!("{}/{}{}", self.addr, mount.path, mount.start)
+    }
+
+    /// The body Vault answers `GET /v1/<path>` with, or `None` when it has
+    /// nothing there.
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<Value>> {
+        let response = self
+            .http
+            .get(format!("{}/v1/{path}", self.addr))
+            .header("X-Vault-Token", self.token.as_str())
+            .query(query)
+            .send()
+            .await
+            .with_context(|| format!("unable to reach Vault at {}", self.addr))?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if status.is_success() {
+            return Ok(Some(response.json().await.with_context(|| {
+                format!("Vault's answer for {path} is not JSON")
+            })?));
+        }
+        let body = response.json::<Value>().await.unwrap_or_default();
+        let errors = body
+            .get("errors")
+            .and_then(Value::as_array)
+            .map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+            .unwrap_or_default();
+        bail!("Vault refused {path} ({status}): {errors}")
+    }
+
+    /// The KV mount holding `path`, and where under it `path` is.
+    pub(crate) async fn mount(&self, path: &str) -> Result<Mount> {
+        let path = path.trim_matches('/');
+        let Some(body) = self
+            .get(&format!("sys/internal/ui/mounts/{path}"), &[])
+            .await?
+        else {
+            bail!("no Vault mount holds {path}");
+        };
+        let Some(mount) = body.pointer("/data/path").and_then(Value::as_str) else {
+            bail!("Vault did not say which mount holds {path}");
+        };
+        match body.pointer("/data/type").and_then(Value::as_str) {
+            Some("kv" | "generic") => {}
+            other => bail!(
+                "{mount} is not a KV mount (its type is {})",
+                other.unwrap_or("unknown")
+            ),
+        }
+        let version = match body
+            .pointer("/data/options/version")
+            .and_then(Value::as_str)
+        {
+            Some("2") => 2,
+            _ => 1,
+        };
+        let mount = format!("{}/", mount.trim_end_matches('/'));
+        let start = path
+            .strip_prefix(mount.trim_end_matches('/'))
+            .unwrap_or_default()
+            .trim_matches('/');
+        Ok(Mount {
+            path: mount,
+            version,
+            start: if start.is_empty() {
+                String::new()
+            } else {
+                format!("{start}/")
+            },
+        })
+    }
+
+    /// The path of every secret under the mount's folder, relative to it, in
+    /// order.
+    pub(crate) async fn list(&self, mount: &Mount) -> Result<Vec<String>> {
+        let mut secrets = vec![];
+        let mut folders = vec![String::new()];
+        while let Some(folder) = folders.pop() {
+            let listed = self
+                .get(
+                    &mount.listing(&format!("{}{folder}", mount.start)),
+                    &[("list", "true")],
+                )
+                .await?;
+            let keys = listed
+                .as_ref()
+                .and_then(|body| body.pointer("/data/keys"))
+                .and_then(Value::as_array);
+            for key in keys.into_iter().flatten().filter_map(Value::as_str) {
+                let child = format!("{folder}{key}");
+                if key.ends_with('/') {
+                    folders.push(child);
+                } else {
+                    secrets.push(child);
+                }
+            }
+        }
+        secrets.sort();
+        Ok(secrets)
+    }
+
+    /// The secret `rel` (from [`Vault::list`]) names, with its older versions
+    /// too when `all_versions`.
+    ///
+    /// Versions that were deleted or destroyed are left out.
+    pub(crate) async fn read(
+        &self,
+        mount: &Mount,
+        rel: &str,
+        all_versions: bool,
+    ) -> Result<Secret> {
+        let path = format!("{}{rel}", mount.start);
+        if mount.version != 2 {
+            let Some(body) = self.get(&mount.data(&path), &[]).await? else {
+                bail!("{} is gone", mount.full(rel));
+            };
+            let fields = fields(body.get("data"))?;
+            return Ok(Secret {
+                version: None,
+                created_time: None,
+                custom_metadata: None,
+                versions: BTreeMap::from([(0, fields)]),
+            });
+        }
+        let Some(metadata) = self
+            .get(&format!("{}metadata/{path}", mount.path), &[])
+            .await?
+        else {
+            bail!("{} is gone", mount.full(rel));
+        };
+        let current = metadata
+            .pointer("/data/current_version")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let about = |version: u64| metadata.pointer(&format!("/data/versions/{version}"));
+        let readable = |version: u64| {
+            about(version).is_some_and(|about| {
+                about.get("deletion_time").and_then(Value::as_str) == Some("")
+                    && about.get("destroyed").and_then(Value::as_bool) != Some(true)
+            })
+        };
+        let oldest = if all_versions { 1 } else { current };
+        let mut versions = BTreeMap::new();
+        for version in (oldest..=current).filter(|version| readable(*version)) {
+            let Some(body) = self
+                .get(&mount.data(&path), &[("version", &version.to_string())])
+                .await?
+            else {
+                continue;
+            };
+            let _old = versions.insert(version, fields(body.pointer("/data/data"))?);
+        }
+        Ok(Secret {
+            version: Some(current),
+            created_time: about(current)
+                .and_then(|about| about.get("created_time"))
+                .and_then(Value::as_str)
+                .map(String::from),
+            custom_metadata: metadata
+                .pointer("/data/custom_metadata")
+                .filter(|custom| !custom.is_null())
+                .cloned(),
+            versions,
+        })
+    }
+}
+
+/// A secret's fields, flattened to `/`-joined names.
+fn fields(data: Option<&Value>) -> Result<BTreeMap<String, String>> {
+    match data {
+        Some(data @ Value::Object(_)) => formats::flatten_document(data.clone()),
+        _ => bail!("the secret holds no fields"),
+    }
+}
+
+/// One secret as read from Vault.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Secret {
+    /// The newest version, on a version 2 mount
+    version: Option<u64>,
+    /// When the newest version was written
+    created_time: Option<String>,
+    /// The secret's custom metadata, if it has any
+    custom_metadata: Option<Value>,
+    /// The fields of each version read, by version (`0` on a version 1 mount)
+    versions: BTreeMap<u64, BTreeMap<String, String>>,
+}
+
+impl Secret {
+    /// The keys and values storing this secret, with `prefix` before its path
+    /// `rel`.
+    pub(crate) fn entries(&self, prefix: &str, rel: &str) -> BTreeMap<String, String> {
+        let mut entries = BTreeMap::new();
+        for (version, fields) in &self.versions {
+            let at = match self.version {
+                Some(newest) if newest != *version => format!("{prefix}{rel}@{version}"),
+                _ => format!("{prefix}{rel}"),
+            };
+            for (field, value) in fields {
+                let _old = entries.insert(format!("{at}/{field}"), value.clone());
+            }
+        }
+        entries
+    }
+
+    /// How the secret at `vault_path` maps to `keys`, with its metadata.
+    pub(crate) fn mapping(&self, vault_path: String, keys: Vec<String>) -> Mapping {
+        Mapping {
+            vault_path,
+            version: self.version,
+            created_time: self.created_time.clone(),
+            custom_metadata: self.custom_metadata.clone(),
+            keys,
+            status: MappingStatus::Imported,
+            reason: None,
+        }
+    }
+}
+
+/// What became of one secret.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MappingStatus {
+    /// Its keys were written
+    Imported,
+    /// Its keys would be written, in a dry run
+    WouldImport,
+    /// An earlier run imported it
+    AlreadyImported,
+    /// Some of its keys exist, and `--force` was not given
+    Conflict,
+    /// It has nothing to import: every version read was deleted
+    Skipped,
+    /// It could not be read
+    Failed,
+}
+
+/// One line of the mapping report: a Vault path and the keys it became.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct Mapping {
+    vault_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    custom_metadata: Option<Value>,
+    keys: Vec<String>,
+    status: MappingStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl Mapping {
+    /// A secret at `vault_path` that was not read, with why.
+    pub(crate) fn unread(
+        vault_path: String,
+        status: MappingStatus,
+        reason: Option<String>,
+    ) -> Self {
+        Self {
+            vault_path,
+            version: None,
+            created_time: None,
+            custom_metadata: None,
+            keys: vec![],
+            status,
+            reason,
+        }
+    }
+
+    /// The same mapping, with `status` for `reason`.
+    pub(crate) fn with_status(mut self, status: MappingStatus, reason: Option<String>) -> Self {
+        self.status = status;
+        self.reason = reason;
+        self
+    }
+
+    pub(crate) fn vault_path(&self) -> &str {
+        &self.vault_path
+    }
+
+    pub(crate) fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn status(&self) -> MappingStatus {
+        self.status
+    }
+
+    pub(crate) fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+/// Which secrets earlier runs of an import wrote, so a rerun picks up where
+/// one stopped.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Progress {
+    /// The server and path imported from; another import's file is refused
+    source: String,
+    /// The secrets written, by path relative to the source
+    done: BTreeSet<String>,
+}
+
+impl Progress {
+    /// The progress recorded in `path` for an import from `source`, or none
+    /// yet when there is no such file.
+    pub(crate) fn load(path: &Path, source: &str) -> Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Self {
+                    source: source.to_string(),
+                    done: BTreeSet::new(),
+                });
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("unable to read {}", path.display()));
+            }
+        };
+        let progress: Self = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not an import-vault state file", path.display()))?;
+        if progress.source != source {
+            bail!(
+                "{} records an import from {}, not {source}",
+                path.display(),
+                progress.source
+            );
+        }
+        Ok(progress)
+    }
+
+    /// Whether the secret `rel` was written by an earlier run.
+    pub(crate) fn is_done(&self, rel: &str) -> bool {
+        self.done.contains(rel)
+    }
+
+    /// Record that the secret `rel` was written, in `path` when there is one.
+    pub(crate) fn mark(&mut self, rel: &str, path: Option<&Path>) -> Result<()> {
+        let _new = self.done.insert(rel.to_string());
+        match path {
+            Some(path) => utils::write_private(path, &serde_json::to_vec_pretty(self)?),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A stand-in Vault server answering each `GET` whose path and query are in
+/// `routes` with its body, and anything else with `404`. Returns its address.
+#[cfg(test)]
+pub(crate) async fn fake_vault(routes: BTreeMap<String, Value>) -> Result<String> {
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = format!("http://{}", listener.local_addr()?);
+    let _server = tokio::spawn(async move {
+        while let Ok((mut conn, _peer)) = listener.accept().await {
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+                match conn.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend(buf.iter().take(read)),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let target = request.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = routes
+                .get(target)
+                .map_or(("404 Not Found", "{\"errors\":[]}".to_string()), |body| {
+                    ("200 OK", body.to_string())
+                });
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _written = conn.write_all(response.as_bytes()).await;
+        }
+    });
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+    use serde_json::{Value, json};
+    use zeroize::Zeroizing;
+
+    use super::{Vault, fake_vault};
+
+    /// A version 2 mount at `secret/` holding `app/db` (two versions, the first
+    /// deleted, then a third) and `app/web/api`.
+    fn kv2() -> BTreeMap<String, Value> {
+        let version = |deleted: &str| json!({"created_time": "2025-01-01T00:00:00Z", "deletion_time": deleted, "destroyed": false});
+        BTreeMap::from([
+            (
+                "/v1/sys/internal/ui/mounts/secret/app".to_string(),
+                json!({"data": {"path": "secret/", "type": "kv", "options": {"version": "2"}}}),
+            ),
+            (
+                "/v1/secret/metadata/app/?list=true".to_string(),
+                json!({"data": {"keys": ["db", "web/"]}}),
+            ),
+            (
+                "/v1/secret/metadata/app/web/?list=true".to_string(),
+                json!({"data": {"keys": ["api"]}}),
+            ),
+            (
+                "/v1/secret/metadata/app/db".to_string(),
+                json!({"data": {
+                    "current_version": 3,
+                    "custom_metadata": {"owner": "ops"},
+                    "versions": {
+                        "1": version("2025-01-02T00:00:00Z"),
+                        "2": version(""),
+                        "3": version(""),
+                    },
+                }}),
+            ),
+            (
+                "/v1/secret/data/app/db?version=2".to_string(),
+                json!({"data": {"data": {"password": "old"}}}),
+            ),
+            (
+                "/v1/secret/data/app/db?version=3".to_string(),
+                json!({"data": {"data": {"password": "new", "tls": {"cert": "pem"}}}}),
+            ),
+            (
+                "/v1/secret/metadata/app/web/api".to_string(),
+                json!({"data": {"current_version": 1, "custom_metadata": null, "versions": {"1": version("")}}}),
+            ),
+            (
+                "/v1/secret/data/app/web/api?version=1".to_string(),
+                json!({"data": {"data": {"token": "t"}}}),
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn a_kv2_folder_is_walked_with_its_readable_versions() -> Result<()> {
+        let vault = Vault::new(
+            &fake_vault(kv2()).await?,
+            Zeroizing::new("root".to_string()),
+        )?;
+        let mount = vault.mount("secret/app/").await?;
+        assert_eq!(mount.full("db"), "secret/app/db");
+        assert_eq!(vault.list(&mount).await?, ["db", "web/api"]);
+
+        let newest = vault.read(&mount, "db", false).await?;
+        assert_eq!(
+            newest.entries("vault/", "db"),
+            BTreeMap::from([
+                ("vault/db/password".to_string(), "new".to_string()),
+                ("vault/db/tls/cert".to_string(), "pem".to_string()),
+            ])
+        );
+        // The first version was deleted, so only the second lands beside it.
+        let every = vault.read(&mount, "db", true).await?;
+        let keys = every.entries("", "db").into_keys().collect::<Vec<_>>();
+        assert_eq!(keys, ["db/password", "db/tls/cert", "db@2/password"]);
+        let mapping = serde_json::to_value(every.mapping("secret/app/db".to_string(), keys))?;
+        assert_eq!(mapping.pointer("/version"), Some(&json!(3)));
+        assert_eq!(
+            mapping.pointer("/custom_metadata/owner"),
+            Some(&json!("ops"))
+        );
+        assert_eq!(mapping.pointer("/status"), Some(&json!("imported")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_kv1_mount_is_read_without_versions() -> Result<()> {
+        let routes = BTreeMap::from([
+            (
+                "/v1/sys/internal/ui/mounts/kv".to_string(),
+                json!({"data": {"path": "kv/", "type": "kv", "options": null}}),
+            ),
+            (
+                "/v1/kv/?list=true".to_string(),
+                json!({"data": {"keys": ["app"]}}),
+            ),
+            ("/v1/kv/app".to_string(), json!({"data": {"port": 5432}})),
+        ]);
+        let vault = Vault::new(
+            &fake_vault(routes).await?,
+            Zeroizing::new("root".to_string()),
+        )?;
+        let mount = vault.mount("kv").await?;
+        assert_eq!(vault.list(&mount).await?, ["app"]);
+        let secret = vault.read(&mount, "app", true).await?;
+        assert_eq!(
+            secret.entries("", "app"),
+            BTreeMap::from([("app/port".to_string(), "5432".to_string())])
+        );
+        assert!(vault.mount("cubbyhole").await.is_err());
+        Ok(())
+    }
+}
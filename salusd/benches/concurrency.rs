@@ -0,0 +1,62 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Concurrent reads through the store's lock: shared, as the daemon takes it,
+//! against exclusive, as a single mutex made every request.
+//!
+//! Run with `cargo bench -p salusd --features bench`.
+
+use std::{hint::black_box, thread};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use salusd::bench::SharedStore;
+
+/// Values in the store.
+const VALUES: usize = 64;
+/// Reads each thread makes per iteration.
+const READS: usize = 256;
+
+fn reads(c: &mut Criterion) {
+    let Ok(store) = SharedStore::unlocked(VALUES) else {
+        eprintln!("unable to set up the benchmark store");
+        return;
+    };
+    let keys = (0..VALUES).map(|i| format!("key-{i}")).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("concurrent_reads");
+    for threads in [1, 2, 4, 8] {
+        let total = u64::try_from(threads * READS).unwrap_or(u64::MAX);
+        let _group = group.throughput(Throughput::Elements(total));
+        for (name, read) in [
+            (
+                "shared",
+                SharedStore::read_shared as fn(&SharedStore, &str) -> _,
+            ),
+            ("exclusive", SharedStore::read_exclusive),
+        ] {
+            let _bench =
+                group.bench_with_input(BenchmarkId::new(name, threads), &threads, |b, &threads| {
+                    b.iter(|| {
+                        thread::scope(|scope| {
+                            for offset in 0..threads {
+                                let (store, keys) = (&store, &keys);
+                                let _reader = scope.spawn(move || {
+                                    for key in keys.iter().cycle().skip(offset).take(READS) {
+                                        black_box(read(store, key)).ok();
+                                    }
+                                });
+                            }
+                        });
+                    });
+                });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reads);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Finding keys in a database file: through the key index, as the daemon
+//! does, against reading every value row.
+//!
+//! Run with `cargo bench -p salusd --features bench --bench search`.
+
+use std::{env, fs, hint::black_box, process};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use salusd::bench::SharedStore;
+
+/// Patterns to find: anchored to a literal start, and not.
+const PATTERNS: [(&str, &str); 2] = [("prefix", "^key-999"), ("pattern", "999$")];
+
+fn find(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find");
+    let _group = group.sample_size(10);
+    for values in [10_000, 100_000] {
+        let path = env::temp_dir().join(format!(
+            "salusd-bench-search-{}-{values}.redb",
+            process::id()
+        ));
+        let Ok(store) = SharedStore::on_disk(&path, values) else {
+            eprintln!("unable to set up the benchmark store");
+            drop(fs::remove_file(&path));
+            return;
+        };
+        for (name, regex) in PATTERNS {
+            for (how, find) in [
+                ("indexed", SharedStore::find as fn(&SharedStore, &str) -> _),
+                ("scanning", SharedStore::find_scanning),
+            ] {
+                let _bench = group.bench_with_input(
+                    BenchmarkId::new(format!("{name}/{how}"), values),
+                    &regex,
+                    |b, regex| b.iter(|| black_box(find(&store, regex)).ok()),
+                );
+            }
+        }
+        drop(store);
+        drop(fs::remove_file(&path));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, find);
+criterion_main!(benches);
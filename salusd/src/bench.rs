@@ -0,0 +1,184 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Benchmark facade for the daemon's store locking and key search.
+//!
+//! `ShareStore` is crate-private, so this module exposes a small `pub` surface,
+//! gated behind the `bench` feature, for `benches/concurrency.rs` and
+//! `benches/search.rs`. It holds an unlocked store behind the same lock the
+//! daemon's connections share, and reads from it either the way the daemon
+//! does (a shared read lock) or holding the lock alone, as every request did
+//! when the store sat behind one mutex. Keys are found either the way the
+//! daemon finds them (through the key index) or by reading every value row,
+//! as before the index.
+
+use std::{
+    fmt,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Result, bail};
+use libsalus::{Response, Store};
+use regex::Regex;
+
+use crate::{
+    db::{
+        Backend, SALUS_VAL_TABLE_DEF, SharedBackend,
+        backend::{MemoryBackend, RedbBackend},
+        read_backend, scan_values,
+    },
+    store::ShareStore,
+};
+
+/// An unlocked store shared the way the daemon shares it.
+#[derive(Clone)]
+pub struct SharedStore {
+    store: Arc<RwLock<ShareStore>>,
+    backend: Backend,
+}
+
+impl SharedStore {
+    /// An unlocked in-memory store holding `values` values, under the keys
+    /// `key-0` to `key-<values - 1>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be initialized, unlocked, or
+    /// written.
+    pub fn unlocked(values: usize) -> Result<Self> {
+        let backend = Arc::new(SharedBackend::new(MemoryBackend::default()));
+        let store = unlock(&backend)?;
+        for i in 0..values {
+            let _stored = store.store(&format!("key-{i}"), vec![0x5a; 64], true)?;
+        }
+        Ok(Self {
+            store: Arc::new(RwLock::new(store)),
+            backend,
+        })
+    }
+
+    /// An unlocked store in a new database file at `path`, holding `values`
+    /// values under the keys `key-0` to `key-<values - 1>`, written in one
+    /// transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be created, or the store
+    /// initialized, unlocked, or written.
+    pub fn on_disk(path: &Path, values: usize) -> Result<Self> {
+        let backend = Arc::new(SharedBackend::new(RedbBackend::create(path)?));
+        let store = unlock(&backend)?;
+        let entries = (0..values)
+            .map(|i| {
+                Store::builder()
+                    .key(format!("key-{i}"))
+                    .value("x".repeat(64))
+                    .force(true)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let _stored = store.store_batch(&entries, false)?;
+        Ok(Self {
+            store: Arc::new(RwLock::new(store)),
+            backend,
+        })
+    }
+
+    /// Read `key` under a shared read lock, as the daemon does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is missing or cannot be read.
+    pub fn read_shared(&self, key: &str) -> Result<()> {
+        let store = match self.store.read() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        found(&store, key)
+    }
+
+    /// Read `key` holding the lock alone, as when one mutex guarded the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is missing or cannot be read.
+    pub fn read_exclusive(&self, key: &str) -> Result<()> {
+        let store = match self.store.write() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        found(&store, key)
+    }
+
+    /// How many keys match `regex`, found as the daemon finds them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid or the keys cannot be read.
+    pub fn find(&self, regex: &str) -> Result<usize> {
+        let store = match self.store.read() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match store.find(regex)? {
+            Response::Matches(matches) => Ok(matches.len()),
+            other => bail!("expected matches, got {other:?}"),
+        }
+    }
+
+    /// How many keys match `regex`, found by reading every value row.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the regex is invalid or the rows cannot be read.
+    pub fn find_scanning(&self, regex: &str) -> Result<usize> {
+        let re = Regex::new(regex)?;
+        let mut matches = 0;
+        read_backend(&self.backend, |db| -> Result<()> {
+            matches = scan_values(db, SALUS_VAL_TABLE_DEF, "")?
+                .iter()
+                .filter(|(key, _)| re.is_match(key))
+                .count();
+            Ok(())
+        })?;
+        Ok(matches)
+    }
+}
+
+impl fmt::Debug for SharedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedStore").finish_non_exhaustive()
+    }
+}
+
+/// A new store in `backend`, its shares generated and its key unlocked.
+fn unlock(backend: &Backend) -> Result<ShareStore> {
+    let mut store = ShareStore::builder().backend(Arc::clone(backend)).build();
+    let Response::Shares(shares) = store.gen_shares()? else {
+        bail!("expected shares");
+    };
+    for share in shares
+        .shares()
+        .iter()
+        .take(usize::from(store.get_threshold()))
+    {
+        store.add_share(share.clone());
+    }
+    if !matches!(store.unlock()?, Response::Success) {
+        bail!("expected the store to unlock");
+    }
+    Ok(store)
+}
+
+fn found(store: &ShareStore, key: &str) -> Result<()> {
+    match store.read(key)? {
+        Response::Value(Some(_)) => Ok(()),
+        other => bail!("expected a value under {key}, got {other:?}"),
+    }
+}
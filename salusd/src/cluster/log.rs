@@ -0,0 +1,287 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The Raft log, in a redb file of its own beside the database.
+//!
+//! Entries are kept by index; the vote, the committed and purged log ids, and
+//! what the state machine records (see [`super::state`]) are kept by name in a
+//! second table. Every change is its own durable redb transaction, so an entry
+//! is on disk before Raft is told it is.
+
+use std::{fmt::Debug, ops::RangeBounds, path::Path, sync::Arc};
+
+use anyhow::Result;
+use bincode_next::{
+    config::standard,
+    serde::{decode_from_slice, encode_to_vec},
+};
+use openraft::{
+    AnyError, Entry, LogId, LogState, OptionalSend, RaftLogReader, StorageError, StorageIOError,
+    Vote,
+    storage::{LogFlushed, RaftLogStorage},
+};
+use redb::{
+    Database, ReadableDatabase as _, ReadableTable as _, ReadableTableMetadata as _,
+    TableDefinition,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::TypeConfig;
+
+const LOG: TableDefinition<'_, u64, &[u8]> = TableDefinition::new("raft_log");
+const META: TableDefinition<'_, &str, &[u8]> = TableDefinition::new("raft_meta");
+
+const VOTE: &str = "vote";
+const COMMITTED: &str = "committed";
+const PURGED: &str = "purged";
+
+/// The Raft log of this node, and the named records kept beside it.
+#[derive(Clone, Debug)]
+pub(crate) struct LogStore {
+    db: Arc<Database>,
+}
+
+impl LogStore {
+    /// Open (creating if needed) the log at `path`.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let db = Database::create(path)?;
+        let txn = db.begin_write()?;
+        {
+            let _log = txn.open_table(LOG)?;
+            let _meta = txn.open_table(META)?;
+        }
+        txn.commit()?;
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Whether this node has never taken part in a cluster: it has neither
+    /// voted nor logged anything.
+    pub(crate) fn is_fresh(&self) -> Result<bool> {
+        let txn = self.db.begin_read()?;
+        let fresh = txn.open_table(META)?.is_empty()? && txn.open_table(LOG)?.is_empty()?;
+        Ok(fresh)
+    }
+
+    /// The record kept as `name`, if any.
+    pub(crate) fn meta<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(META)?;
+        table
+            .get(name)?
+            .map(|bytes| decode(bytes.value()))
+            .transpose()
+    }
+
+    /// Keep `value` as `name`.
+    pub(crate) fn set_meta<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(META)?;
+            let _old = table.insert(name, encode(value)?.as_slice())?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn entries<RB: RangeBounds<u64>>(&self, range: RB) -> Result<Vec<Entry<TypeConfig>>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(LOG)?;
+        table
+            .range(range)?
+            .map(|row| decode(row?.1.value()))
+            .collect()
+    }
+
+    fn last_log_id(&self) -> Result<Option<LogId<u64>>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(LOG)?;
+        table
+            .last()?
+            .map(|(_, entry)| decode::<Entry<TypeConfig>>(entry.value()).map(|entry| entry.log_id))
+            .transpose()
+    }
+
+    fn append_entries(&self, entries: impl IntoIterator<Item = Entry<TypeConfig>>) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(LOG)?;
+            for entry in entries {
+                let _old = table.insert(entry.log_id.index, encode(&entry)?.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Drop the entries in `range`, recording `purged` when purging.
+    fn remove(&self, range: impl RangeBounds<u64>, purged: Option<LogId<u64>>) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(LOG)?;
+            table.retain_in(range, |_, _| false)?;
+            if let Some(purged) = purged {
+                let mut meta = txn.open_table(META)?;
+                let _old = meta.insert(PURGED, encode(&purged)?.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+impl RaftLogReader<TypeConfig> for LogStore {
+    async fn try_get_log_entries<RB: RangeBounds<u64> + Clone + Debug + OptionalSend>(
+        &mut self,
+        range: RB,
+    ) -> Result<Vec<Entry<TypeConfig>>, StorageError<u64>> {
+        self.entries(range)
+            .map_err(|e| StorageIOError::read_logs(any(&e)).into())
+    }
+}
+
+impl RaftLogStorage<TypeConfig> for LogStore {
+    type LogReader = Self;
+
+    async fn get_log_state(&mut self) -> Result<LogState<TypeConfig>, StorageError<u64>> {
+        let read = || -> Result<LogState<TypeConfig>> {
+            let last_purged_log_id = self.meta(PURGED)?;
+            let last_log_id = self.last_log_id()?.or(last_purged_log_id);
+            Ok(LogState {
+                last_purged_log_id,
+                last_log_id,
+            })
+        };
+        read().map_err(|e| StorageIOError::read_logs(any(&e)).into())
+    }
+
+    async fn get_log_reader(&mut self) -> Self::LogReader {
+        self.clone()
+    }
+
+    async fn save_vote(&mut self, vote: &Vote<u64>) -> Result<(), StorageError<u64>> {
+        self.set_meta(VOTE, vote)
+            .map_err(|e| StorageIOError::write_vote(any(&e)).into())
+    }
+
+    async fn read_vote(&mut self) -> Result<Option<Vote<u64>>, StorageError<u64>> {
+        self.meta(VOTE)
+            .map_err(|e| StorageIOError::read_vote(any(&e)).into())
+    }
+
+    async fn save_committed(
+        &mut self,
+        committed: Option<LogId<u64>>,
+    ) -> Result<(), StorageError<u64>> {
+        self.set_meta(COMMITTED, &committed)
+            .map_err(|e| StorageIOError::write_logs(any(&e)).into())
+    }
+
+    async fn read_committed(&mut self) -> Result<Option<LogId<u64>>, StorageError<u64>> {
+        self.meta::<Option<LogId<u64>>>(COMMITTED)
+            .map(Option::flatten)
+            .map_err(|e| StorageIOError::read_logs(any(&e)).into())
+    }
+
+    async fn append<I>(
+        &mut self,
+        entries: I,
+        callback: LogFlushed<TypeConfig>,
+    ) -> Result<(), StorageError<u64>>
+    where
+        I: IntoIterator<Item = Entry<TypeConfig>> + OptionalSend,
+        I::IntoIter: OptionalSend,
+    {
+        self.append_entries(entries)
+            .map_err(|e| StorageIOError::write_logs(any(&e)))?;
+        callback.log_io_completed(Ok(()));
+        Ok(())
+    }
+
+    async fn truncate(&mut self, log_id: LogId<u64>) -> Result<(), StorageError<u64>> {
+        self.remove(log_id.index.., None)
+            .map_err(|e| StorageIOError::write_logs(any(&e)).into())
+    }
+
+    async fn purge(&mut self, log_id: LogId<u64>) -> Result<(), StorageError<u64>> {
+        self.remove(..=log_id.index, Some(log_id))
+            .map_err(|e| StorageIOError::write_logs(any(&e)).into())
+    }
+}
+
+/// The bytes `value` is kept as, in the log and on the wire.
+pub(crate) fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(encode_to_vec(value, standard())?)
+}
+
+/// Read back what [`encode`] wrote.
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(decode_from_slice(bytes, standard())?.0)
+}
+
+/// `e`, in the form Raft's storage errors carry.
+pub(crate) fn any(e: &anyhow::Error) -> AnyError {
+    AnyError::error(format!("{e:#}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process};
+
+    use anyhow::Result;
+    use openraft::{
+        CommittedLeaderId, Entry, EntryPayload, LogId, RaftLogReader as _, Vote,
+        storage::RaftLogStorage as _,
+    };
+
+    use super::LogStore;
+    use crate::{
+        cluster::TypeConfig,
+        db::backend::{Table, WriteOp},
+    };
+
+    fn entry(index: u64) -> Entry<TypeConfig> {
+        Entry {
+            log_id: LogId::new(CommittedLeaderId::new(1, 1), index),
+            payload: EntryPayload::Normal(vec![WriteOp::Delete {
+                table: Table::Values,
+                key: format!("key-{index}"),
+            }]),
+        }
+    }
+
+    #[tokio::test]
+    async fn entries_votes_and_purges_survive_reopening() -> Result<()> {
+        let path = env::temp_dir().join(format!("salusd-raft-log-{}.raft", process::id()));
+        let _old = fs::remove_file(&path);
+        {
+            let mut log = LogStore::open(&path)?;
+            assert!(log.is_fresh()?);
+            log.append_entries((1..=5).map(entry))?;
+            log.save_vote(&Vote::new(2, 1)).await?;
+            log.truncate(LogId::new(CommittedLeaderId::new(1, 1), 5))
+                .await?;
+            log.purge(LogId::new(CommittedLeaderId::new(1, 1), 2))
+                .await?;
+        }
+        let mut log = LogStore::open(&path)?;
+        assert!(!log.is_fresh()?);
+        assert_eq!(log.read_vote().await?, Some(Vote::new(2, 1)));
+        let state = log.get_log_state().await?;
+        assert_eq!(state.last_purged_log_id.map(|id| id.index), Some(2));
+        assert_eq!(state.last_log_id.map(|id| id.index), Some(4));
+        let kept = log.try_get_log_entries(0..10).await?;
+        assert_eq!(
+            kept.iter()
+                .map(|entry| entry.log_id.index)
+                .collect::<Vec<_>>(),
+            [3, 4]
+        );
+        let _removed = fs::remove_file(&path);
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Clustered mode: several salusd nodes keeping one store, replicated with
+//! Raft.
+//!
+//! Every change the store makes is already a batch of [`WriteOp`]s. A node in
+//! a cluster proposes each batch to the cluster's Raft log instead of
+//! committing it, and every node commits the batch to its own database file as
+//! the log applies it ([`state`]). Only the leader takes writes; any node
+//! serves reads from its own file, so a follower may trail the leader by the
+//! writes still on their way to it. Each node is unlocked with the shares on
+//! its own, as a single daemon is.
+//!
+//! The Raft log, and what the state machine records beside it, are kept in
+//! `<database>.raft` ([`log`]). Nodes talk over TCP ([`net`]), and a
+//! connection is only answered by a node holding the same `[cluster]
+//! key_file`.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    io::{Cursor, Write as _, stdout},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result, bail};
+use openraft::{BasicNode, Config, ServerState, declare_raft_types};
+use tokio::{net::TcpListener, spawn};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::{
+    config::ClusterSettings,
+    db::{
+        Backend, SharedBackend,
+        backend::{ClusterBackend, RedbBackend, StorageBackend as _, Table, WriteOp},
+        migrations::migrate,
+    },
+    error::Error,
+    utils::ensure_parent_dir,
+};
+
+use self::{
+    log::LogStore,
+    net::{Network, Reply, Request},
+    state::StateMachine,
+};
+
+mod log;
+mod net;
+mod state;
+
+declare_raft_types!(
+    /// The types the cluster's Raft log is made of: each entry is one batch of
+    /// writes to the store.
+    pub(crate) TypeConfig: D = Vec<WriteOp>, R = ()
+);
+
+/// A running Raft node of the cluster.
+pub(crate) type Raft = openraft::Raft<TypeConfig>;
+
+/// The fewest bytes a `[cluster] key_file` may hold.
+const MIN_KEY_LEN: usize = 32;
+/// The most entries sent to a follower in one message; a batch can carry a
+/// value up to `MAX_MESSAGE_SIZE`, so this bounds a message to tens of MiB.
+const MAX_PAYLOAD_ENTRIES: u64 = 32;
+/// How long bootstrapping waits for this node to lead and to snapshot.
+const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many times an admin request follows a node to its leader.
+const MAX_REDIRECTS: usize = 3;
+
+/// The key every node of the cluster holds.
+pub(crate) type ClusterKey = Arc<Zeroizing<Vec<u8>>>;
+
+/// Start this node of the cluster: open its database and Raft log, start Raft
+/// and the cluster listener on `listen`, and bootstrap the cluster when this
+/// node is configured to.
+///
+/// The store is migrated here, before anything is applied, so rows arriving
+/// from the log land in the layout this salusd writes. Every node should run
+/// the same salusd.
+///
+/// # Errors
+///
+/// Returns an error if the settings are unusable, if a node joining a cluster
+/// already holds a store ([`Error::ClusterNotEmpty`]), or if the database,
+/// the log or the listener cannot be opened.
+pub(crate) async fn start(
+    settings: &ClusterSettings,
+    listen: &str,
+    database: &Path,
+) -> Result<Backend> {
+    let id = node_id(settings)?;
+    let key = cluster_key(settings)?;
+    ensure_parent_dir(database)?;
+    let local = Arc::new(RedbBackend::create(database)?);
+    let _copy = migrate(
+        &Arc::new(SharedBackend::new(Arc::clone(&local))),
+        Some(database),
+    )?;
+    let log = LogStore::open(&raft_path(database))?;
+    if log.is_fresh()? && !settings.bootstrap() && !local.scan(Table::Config, "")?.is_empty() {
+        return Err(Error::ClusterNotEmpty(database.to_path_buf()).into());
+    }
+    let state = StateMachine::open(Arc::clone(&local), log.clone())?;
+    let config = Config {
+        cluster_name: "salus".to_string(),
+        heartbeat_interval: settings.heartbeat_ms(),
+        election_timeout_min: settings.election_timeout_ms(),
+        election_timeout_max: settings.election_timeout_ms().saturating_mul(2),
+        max_payload_entries: MAX_PAYLOAD_ENTRIES,
+        ..Config::default()
+    }
+    .validate()?;
+    let raft = Raft::new(
+        id,
+        Arc::new(config),
+        Network::new(Arc::clone(&key)),
+        log,
+        state,
+    )
+    .await?;
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Unable to listen for the cluster on {listen}"))?;
+    let _server = spawn(net::serve(listener, raft.clone(), key));
+    info!("Cluster node {id} is listening on {listen}");
+    if settings.bootstrap() && !raft.is_initialized().await? {
+        bootstrap(&raft, id, advertise(settings, listen)).await?;
+    }
+    Ok(Arc::new(SharedBackend::new(ClusterBackend::new(
+        local, raft,
+    ))))
+}
+
+/// Make this node the cluster's only member, then snapshot the store it
+/// starts with and drop the log behind the snapshot, so a node that joins is
+/// sent the store whole rather than a log that never held it.
+async fn bootstrap(raft: &Raft, id: u64, advertise: String) -> Result<()> {
+    raft.initialize(BTreeMap::from([(id, BasicNode::new(advertise))]))
+        .await?;
+    let _leader = raft
+        .wait(Some(BOOTSTRAP_TIMEOUT))
+        .state(ServerState::Leader, "bootstrap: lead")
+        .await?;
+    raft.trigger().snapshot().await?;
+    let snapshotted = raft
+        .wait(Some(BOOTSTRAP_TIMEOUT))
+        .metrics(|metrics| metrics.snapshot.is_some(), "bootstrap: snapshot")
+        .await?;
+    if let Some(snapshot) = snapshotted.snapshot {
+        raft.trigger().purge_log(snapshot.index).await?;
+    }
+    info!("Cluster bootstrapped with node {id} as its only member");
+    Ok(())
+}
+
+/// `salusd cluster join`: add this node, as `[cluster]` describes it, to the
+/// cluster the node at `addr` belongs to.
+///
+/// # Errors
+///
+/// Returns an error if no node can be reached, or the cluster refuses the
+/// change.
+pub(crate) async fn join(settings: &ClusterSettings, addr: &str) -> Result<()> {
+    let id = node_id(settings)?;
+    let listen = settings.listen().as_deref().ok_or(Error::ClusterAddress)?;
+    let request = Request::Join {
+        id,
+        addr: advertise(settings, listen),
+    };
+    expect_done(ask(addr, &cluster_key(settings)?, &request).await?)?;
+    writeln!(stdout(), "Node {id} joined the cluster")?;
+    Ok(())
+}
+
+/// `salusd cluster leave`: remove `node` (default: this node) from the cluster
+/// the node at `addr` (default: this node) belongs to.
+///
+/// # Errors
+///
+/// Returns an error if no node can be reached, or the cluster refuses the
+/// change.
+pub(crate) async fn leave(
+    settings: &ClusterSettings,
+    addr: Option<&str>,
+    node: Option<u64>,
+) -> Result<()> {
+    let id = match node {
+        Some(id) => id,
+        None => node_id(settings)?,
+    };
+    let addr = target(settings, addr)?;
+    expect_done(ask(addr, &cluster_key(settings)?, &Request::Leave { id }).await?)?;
+    writeln!(stdout(), "Node {id} left the cluster")?;
+    Ok(())
+}
+
+/// `salusd cluster status`: print the cluster as the node at `addr` (default:
+/// this node) sees it, as JSON.
+///
+/// # Errors
+///
+/// Returns an error if the node cannot be reached.
+pub(crate) async fn status(settings: &ClusterSettings, addr: Option<&str>) -> Result<()> {
+    let addr = target(settings, addr)?;
+    let Reply::Status(status) = ask(addr, &cluster_key(settings)?, &Request::Status).await? else {
+        bail!("{addr} did not answer with its status");
+    };
+    let mut out = stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &status)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Send `request` to the node at `addr`, following it to the leader when the
+/// node is not the one to change membership.
+async fn ask(addr: &str, key: &ClusterKey, request: &Request) -> Result<Reply> {
+    let mut addr = addr.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        match net::ask(&addr, key, request).await? {
+            Reply::NotLeader(Some(leader)) => addr = leader,
+            Reply::NotLeader(None) => bail!("The cluster has no leader yet; try again shortly"),
+            Reply::Failed(reason) => bail!("{addr} refused the request: {reason}"),
+            reply => return Ok(reply),
+        }
+    }
+    bail!("The cluster's leader kept moving; try again shortly")
+}
+
+fn expect_done(reply: Reply) -> Result<()> {
+    match reply {
+        Reply::Done => Ok(()),
+        other => bail!("Expected the change to be made, got {other:?}"),
+    }
+}
+
+/// The node an admin request goes to: `addr`, or this node.
+fn target<'a>(settings: &'a ClusterSettings, addr: Option<&'a str>) -> Result<&'a str> {
+    addr.or(settings.listen().as_deref())
+        .ok_or_else(|| Error::ClusterAddress.into())
+}
+
+/// How a node names the leader to a client it cannot serve.
+pub(crate) fn leader_name(id: Option<u64>, node: Option<&BasicNode>) -> String {
+    match (id, node) {
+        (Some(id), Some(node)) => format!("node {id} at {}", node.addr),
+        (Some(id), None) => format!("node {id}"),
+        (None, _) => "the leader, once one is elected".to_string(),
+    }
+}
+
+pub(crate) fn node_id(settings: &ClusterSettings) -> Result<u64> {
+    Some(settings.node_id())
+        .filter(|id| *id != 0)
+        .ok_or_else(|| Error::ClusterNodeId.into())
+}
+
+fn advertise(settings: &ClusterSettings, listen: &str) -> String {
+    settings
+        .advertise()
+        .clone()
+        .unwrap_or_else(|| listen.to_string())
+}
+
+/// Read the key from `[cluster] key_file`.
+pub(crate) fn cluster_key(settings: &ClusterSettings) -> Result<ClusterKey> {
+    let path = settings
+        .key_file()
+        .as_ref()
+        .ok_or(Error::ClusterKeyMissing)?;
+    let key = fs::read(path)
+        .ok()
+        .map(Zeroizing::new)
+        .filter(|key| key.len() >= MIN_KEY_LEN)
+        .ok_or_else(|| Error::ClusterKey(path.clone()))?;
+    Ok(Arc::new(key))
+}
+
+/// `<database>.raft`, beside the database.
+fn raft_path(database: &Path) -> PathBuf {
+    let mut path = OsString::from(database);
+    path.push(".raft");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path};
+
+    use anyhow::{Result, bail};
+
+    use super::{raft_path, start};
+    use crate::{
+        config::ClusterSettings,
+        db::{
+            SALUS_CONFIG_TABLE_DEF, SALUS_VAL_TABLE_DEF,
+            backend::{RedbBackend, StorageBackend as _, Table},
+            put, read_backend,
+            values::{config::ConfigVal, salus::SalusVal},
+            write_keys,
+        },
+        error::Error,
+        utils::test::temp_dir,
+    };
+
+    fn settings(dir: &Path, bootstrap: bool) -> Result<ClusterSettings> {
+        let key = dir.join("cluster.key");
+        fs::write(&key, [7u8; 32])?;
+        Ok(serde_json::from_value(serde_json::json!({
+            "node_id": 1,
+            "key_file": key,
+            "bootstrap": bootstrap,
+            "heartbeat_ms": 50,
+            "election_timeout_ms": 150,
+        }))?)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_single_node_cluster_commits_through_its_log() -> Result<()> {
+        let dir = temp_dir("cluster-single")?;
+        let database = dir.join("salusd.redb");
+        let backend = start(&settings(&dir, true)?, "127.0.0.1:0", &database).await?;
+        let row = SalusVal::from_parts([1; 12], b"sealed");
+        write_keys(&backend, [(Table::Values, "a")], |db| {
+            db.commit(vec![put(SALUS_VAL_TABLE_DEF, "a", &row)])
+        })?;
+        read_backend(&backend, |db| {
+            if db.get(Table::Values, "a")?.is_none() {
+                bail!("expected the write to be applied before the commit returned");
+            }
+            Ok(())
+        })?;
+        assert!(raft_path(&database).exists());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_joining_node_must_start_empty() -> Result<()> {
+        let dir = temp_dir("cluster-joiner")?;
+        let database = dir.join("salusd.redb");
+        RedbBackend::create(&database)?.commit(vec![put(
+            SALUS_CONFIG_TABLE_DEF,
+            "INITIALIZED",
+            &ConfigVal::from_value(true)?,
+        )])?;
+        match start(&settings(&dir, false)?, "127.0.0.1:0", &database).await {
+            Err(e) if matches!(e.downcast_ref(), Some(Error::ClusterNotEmpty(_))) => Ok(()),
+            Err(e) => Err(e),
+            Ok(_) => bail!("expected a node holding a store to be refused"),
+        }
+    }
+}
@@ -0,0 +1,552 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Cluster traffic: Raft's messages, and `salusd cluster` requests, over TCP.
+//!
+//! A connection opens with the Noise `NNpsk0` handshake the shared daemon
+//! socket speaks (see `libsalus::initiate`), keyed by the HMAC-SHA256, under
+//! the cluster key, of a label for cluster traffic. A peer without the cluster
+//! key is refused before it sends a request, and each connection agrees keys
+//! of its own. Each message is then a big-endian `u32` length and the encoded
+//! message, carried in Noise transport messages, so it is encrypted, and one
+//! that is forged, altered, replayed or reflected fails to decrypt.
+
+use std::{
+    collections::BTreeSet,
+    error::Error as StdError,
+    fmt,
+    io::{self, ErrorKind},
+    time::Duration,
+};
+
+use aws_lc_rs::hmac;
+use libsalus::{Reader, TRANSPORT_KEY_LEN, TransportKey, Writer, initiate, respond};
+use openraft::{
+    BasicNode, ChangeMembers, RaftMetrics,
+    error::{
+        ClientWriteError, InstallSnapshotError, NetworkError, RPCError, RaftError, RemoteError,
+        Unreachable,
+    },
+    network::{RPCOption, RaftNetwork, RaftNetworkFactory},
+    raft::{
+        AppendEntriesRequest, AppendEntriesResponse, ClientWriteResponse, InstallSnapshotRequest,
+        InstallSnapshotResponse, VoteRequest, VoteResponse,
+    },
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, split},
+    net::{TcpListener, TcpStream},
+    spawn,
+    time::timeout,
+};
+use tracing::{debug, error};
+
+use super::{
+    ClusterKey, Raft, TypeConfig,
+    log::{decode, encode},
+};
+
+/// The most bytes one message may carry.
+const MAX_FRAME: u32 = 64 * 1024 * 1024;
+/// Bound into the handshake key, so the cluster key means nothing elsewhere.
+const CONTEXT: &[u8] = b"salus cluster v2";
+/// How long a `salusd cluster` request waits for its answer.
+const ADMIN_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// What one node asks of another.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) enum Request {
+    Append(AppendEntriesRequest<TypeConfig>),
+    Vote(VoteRequest<u64>),
+    Snapshot(InstallSnapshotRequest<TypeConfig>),
+    /// Add node `id`, reachable at `addr`, as a voter.
+    Join {
+        id: u64,
+        addr: String,
+    },
+    /// Remove node `id`.
+    Leave {
+        id: u64,
+    },
+    Status,
+}
+
+/// The answer to a [`Request`].
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) enum Reply {
+    Append(Result<AppendEntriesResponse<u64>, RaftError<u64>>),
+    Vote(Result<VoteResponse<u64>, RaftError<u64>>),
+    Snapshot(Result<InstallSnapshotResponse<u64>, RaftError<u64, InstallSnapshotError>>),
+    /// The membership change was made.
+    Done,
+    /// Membership changes go to the leader, at this address if one is known.
+    NotLeader(Option<String>),
+    Status(Status),
+    /// The request could not be carried out.
+    Failed(String),
+}
+
+/// A node's view of its cluster, as `salusd cluster status` prints it.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Status {
+    node_id: u64,
+    state: String,
+    term: u64,
+    leader: Option<u64>,
+    last_log_index: Option<u64>,
+    last_applied_index: Option<u64>,
+    members: Vec<Member>,
+}
+
+/// One node of the cluster.
+#[derive(Debug, Deserialize, Serialize)]
+struct Member {
+    node_id: u64,
+    addr: String,
+    voter: bool,
+}
+
+impl Status {
+    fn of(metrics: &RaftMetrics<u64, BasicNode>) -> Self {
+        let membership = metrics.membership_config.membership();
+        let voters = membership.voter_ids().collect::<BTreeSet<_>>();
+        Self {
+            node_id: metrics.id,
+            state: format!("{:?}", metrics.state),
+            term: metrics.current_term,
+            leader: metrics.current_leader,
+            last_log_index: metrics.last_log_index,
+            last_applied_index: metrics.last_applied.map(|applied| applied.index),
+            members: membership
+                .nodes()
+                .map(|(id, node)| Member {
+                    node_id: *id,
+                    addr: node.addr.clone(),
+                    voter: voters.contains(id),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One end of an encrypted connection.
+pub(crate) struct Session {
+    reader: Reader,
+    writer: Writer,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session").finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    /// Open a session over `stream`, as the side that connected.
+    pub(crate) async fn client<S>(stream: S, cluster_key: &[u8]) -> io::Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        let (reader, writer) = split(stream);
+        let (reader, writer) = initiate(reader, writer, Some(&handshake_key(cluster_key)))
+            .await
+            .map_err(|e| refused(&e))?;
+        Ok(Self { reader, writer })
+    }
+
+    /// Open a session over `stream`, as the side that accepted.
+    pub(crate) async fn server<S>(stream: S, cluster_key: &[u8]) -> io::Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        let (reader, writer) = split(stream);
+        let (reader, writer) = respond(reader, writer, Some(&handshake_key(cluster_key)))
+            .await
+            .map_err(|e| refused(&e))?;
+        Ok(Self { reader, writer })
+    }
+
+    /// Send `message`.
+    pub(crate) async fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        let payload = encode(message).map_err(|e| invalid(&e))?;
+        let len = u32::try_from(payload.len())
+            .ok()
+            .filter(|len| *len <= MAX_FRAME)
+            .ok_or_else(|| invalid("the message is too large to send"))?;
+        let mut frame = Vec::with_capacity(payload.len().saturating_add(4));
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&payload);
+        self.writer.write_all(&frame).await?;
+        self.writer.flush().await
+    }
+
+    /// Receive the next message, refusing one that was not sent by the other
+    /// side of this session in turn.
+    pub(crate) async fn recv<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        let mut len = [0; 4];
+        let _read = self.reader.read_exact(&mut len).await?;
+        let len = u32::from_be_bytes(len);
+        if len > MAX_FRAME {
+            return Err(invalid("the message is too large to receive"));
+        }
+        let mut payload = vec![0; usize::try_from(len).map_err(|e| invalid(&e))?];
+        let _read = self.reader.read_exact(&mut payload).await?;
+        decode(&payload).map_err(|e| invalid(&e))
+    }
+
+    /// Send `request` and wait for the reply.
+    pub(crate) async fn call(&mut self, request: &Request) -> io::Result<Reply> {
+        self.send(request).await?;
+        self.recv().await
+    }
+}
+
+/// The key of the handshake between holders of `cluster_key`.
+fn handshake_key(cluster_key: &[u8]) -> TransportKey {
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, cluster_key), CONTEXT);
+    let mut key = [0; TRANSPORT_KEY_LEN];
+    key.copy_from_slice(tag.as_ref());
+    TransportKey::new(key)
+}
+
+fn refused(e: &anyhow::Error) -> io::Error {
+    io::Error::new(
+        ErrorKind::PermissionDenied,
+        format!(
+            "the handshake failed ({}); check that every node has the same cluster key",
+            e.root_cause()
+        ),
+    )
+}
+
+fn invalid<E: ToString + ?Sized>(e: &E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+async fn connect(addr: &str, key: &ClusterKey) -> io::Result<Session> {
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    Session::client(stream, key).await
+}
+
+/// Send one `salusd cluster` request to the node at `addr`.
+pub(crate) async fn ask(addr: &str, key: &ClusterKey, request: &Request) -> anyhow::Result<Reply> {
+    let asked = async { connect(addr, key).await?.call(request).await };
+    let reply = timeout(ADMIN_TIMEOUT, asked)
+        .await
+        .map_err(|_| anyhow::anyhow!("{addr} did not answer in time"))?
+        .map_err(|e| anyhow::anyhow!("Unable to ask {addr}: {e}"))?;
+    Ok(reply)
+}
+
+/// Connections to the other nodes, for Raft.
+#[derive(Clone, Debug)]
+pub(crate) struct Network {
+    key: ClusterKey,
+}
+
+impl Network {
+    pub(crate) fn new(key: ClusterKey) -> Self {
+        Self { key }
+    }
+}
+
+impl RaftNetworkFactory<TypeConfig> for Network {
+    type Network = Peer;
+
+    async fn new_client(&mut self, target: u64, node: &BasicNode) -> Peer {
+        Peer {
+            target,
+            addr: node.addr.clone(),
+            key: self.key.clone(),
+            session: None,
+        }
+    }
+}
+
+/// The connection to one other node, opened when first needed and again after
+/// it fails.
+#[derive(Debug)]
+pub(crate) struct Peer {
+    target: u64,
+    addr: String,
+    key: ClusterKey,
+    session: Option<Session>,
+}
+
+impl Peer {
+    async fn call<E: StdError>(
+        &mut self,
+        request: &Request,
+        ttl: Duration,
+    ) -> Result<Reply, RPCError<u64, BasicNode, E>> {
+        let session = if let Some(session) = &mut self.session {
+            session
+        } else {
+            let session = connect(&self.addr, &self.key)
+                .await
+                .map_err(|e| RPCError::Unreachable(Unreachable::new(&e)))?;
+            self.session.insert(session)
+        };
+        let reply = timeout(ttl, session.call(request))
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(ErrorKind::TimedOut, "no reply in time")));
+        reply.map_err(|e| {
+            self.session = None;
+            RPCError::Network(NetworkError::new(&e))
+        })
+    }
+}
+
+/// The error for a reply to some other request.
+fn unexpected<E: StdError>(reply: &Reply) -> RPCError<u64, BasicNode, E> {
+    RPCError::Network(NetworkError::new(&invalid(&format!(
+        "unexpected reply {reply:?}"
+    ))))
+}
+
+impl RaftNetwork<TypeConfig> for Peer {
+    async fn append_entries(
+        &mut self,
+        rpc: AppendEntriesRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<AppendEntriesResponse<u64>, RPCError<u64, BasicNode, RaftError<u64>>> {
+        match self.call(&Request::Append(rpc), option.hard_ttl()).await? {
+            Reply::Append(reply) => {
+                reply.map_err(|e| RPCError::RemoteError(RemoteError::new(self.target, e)))
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        rpc: InstallSnapshotRequest<TypeConfig>,
+        option: RPCOption,
+    ) -> Result<
+        InstallSnapshotResponse<u64>,
+        RPCError<u64, BasicNode, RaftError<u64, InstallSnapshotError>>,
+    > {
+        match self
+            .call(&Request::Snapshot(rpc), option.hard_ttl())
+            .await?
+        {
+            Reply::Snapshot(reply) => {
+                reply.map_err(|e| RPCError::RemoteError(RemoteError::new(self.target, e)))
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    async fn vote(
+        &mut self,
+        rpc: VoteRequest<u64>,
+        option: RPCOption,
+    ) -> Result<VoteResponse<u64>, RPCError<u64, BasicNode, RaftError<u64>>> {
+        match self.call(&Request::Vote(rpc), option.hard_ttl()).await? {
+            Reply::Vote(reply) => {
+                reply.map_err(|e| RPCError::RemoteError(RemoteError::new(self.target, e)))
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+}
+
+/// Answer cluster connections on `listener` for as long as the daemon runs.
+pub(crate) async fn serve(listener: TcpListener, raft: Raft, key: ClusterKey) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, from)) => {
+                let raft = raft.clone();
+                let key = key.clone();
+                let _handle = spawn(async move {
+                    if let Err(e) = answer(stream, &raft, &key).await {
+                        debug!("Cluster connection from {from} closed: {e}");
+                    }
+                });
+            }
+            Err(e) => error!("There was an error with an incoming cluster connection: {e}"),
+        }
+    }
+}
+
+async fn answer(stream: TcpStream, raft: &Raft, key: &ClusterKey) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut session = Session::server(stream, key).await?;
+    loop {
+        let request = session.recv::<Request>().await?;
+        session.send(&handle(raft, request).await).await?;
+    }
+}
+
+async fn handle(raft: &Raft, request: Request) -> Reply {
+    match request {
+        Request::Append(rpc) => Reply::Append(raft.append_entries(rpc).await),
+        Request::Vote(rpc) => Reply::Vote(raft.vote(rpc).await),
+        Request::Snapshot(rpc) => Reply::Snapshot(raft.install_snapshot(rpc).await),
+        Request::Join { id, addr } => {
+            let joined = match raft.add_learner(id, BasicNode::new(addr), true).await {
+                Ok(_) => {
+                    raft.change_membership(ChangeMembers::AddVoterIds(BTreeSet::from([id])), false)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            changed(joined)
+        }
+        Request::Leave { id } => changed(
+            raft.change_membership(ChangeMembers::RemoveVoters(BTreeSet::from([id])), false)
+                .await,
+        ),
+        Request::Status => Reply::Status(Status::of(&raft.metrics().borrow())),
+    }
+}
+
+/// The reply to a membership change.
+fn changed(
+    result: Result<
+        ClientWriteResponse<TypeConfig>,
+        RaftError<u64, ClientWriteError<u64, BasicNode>>,
+    >,
+) -> Reply {
+    match result {
+        Ok(_) => Reply::Done,
+        Err(RaftError::APIError(ClientWriteError::ForwardToLeader(forward))) => {
+            Reply::NotLeader(forward.leader_node.map(|node| node.addr))
+        }
+        Err(e) => Reply::Failed(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, ErrorKind};
+
+    use anyhow::{Result, bail};
+    use tokio::{
+        io::{
+            AsyncRead, AsyncReadExt as _, AsyncWriteExt as _, DuplexStream, WriteHalf, copy,
+            duplex, split,
+        },
+        join, spawn, try_join,
+    };
+
+    use super::{Reply, Request, Session};
+
+    /// The next Noise message the other side of `from` wrote, with its length.
+    async fn message<R: AsyncRead + Unpin>(from: &mut R) -> io::Result<Vec<u8>> {
+        let mut len = [0; 2];
+        let _read = from.read_exact(&mut len).await?;
+        let mut message = len.to_vec();
+        message.resize(usize::from(u16::from_be_bytes(len)).saturating_add(2), 0);
+        let _read = from
+            .read_exact(message.get_mut(2..).unwrap_or_default())
+            .await?;
+        Ok(message)
+    }
+
+    /// A session between holders of the same key, with what the client sends
+    /// after the handshake left to the test to carry to the server.
+    async fn intercepted() -> Result<(
+        Session,
+        Session,
+        impl AsyncRead + Unpin,
+        WriteHalf<DuplexStream>,
+    )> {
+        let (near, client_side) = duplex(4096);
+        let (server_side, far) = duplex(4096);
+        let (mut from_client, mut to_client) = split(client_side);
+        let (mut from_server, mut to_server) = split(server_side);
+        let _replies = spawn(async move { copy(&mut from_server, &mut to_client).await });
+        let (client, server, ()) = try_join!(
+            Session::client(near, &[1; 32]),
+            Session::server(far, &[1; 32]),
+            async {
+                let hello = message(&mut from_client).await?;
+                to_server.write_all(&hello).await
+            },
+        )?;
+        Ok((client, server, from_client, to_server))
+    }
+
+    #[tokio::test]
+    async fn sessions_carry_messages_between_holders_of_the_key() -> Result<()> {
+        let (near, far) = duplex(4096);
+        let (mut client, mut server) = try_join!(
+            Session::client(near, &[1; 32]),
+            Session::server(far, &[1; 32])
+        )?;
+        client.send(&Request::Leave { id: 3 }).await?;
+        let Request::Leave { id } = server.recv().await? else {
+            bail!("expected the request sent");
+        };
+        assert_eq!(id, 3);
+        server.send(&Reply::Done).await?;
+        assert!(matches!(client.recv().await?, Reply::Done));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_session_under_another_key_is_refused() -> Result<()> {
+        let (near, far) = duplex(4096);
+        let (client, server) = join!(
+            Session::client(near, &[1; 32]),
+            Session::server(far, &[2; 32])
+        );
+        assert!(server.is_err_and(|e| e.kind() == ErrorKind::PermissionDenied));
+        assert!(client.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn messages_cross_encrypted() -> Result<()> {
+        let (mut client, mut server, mut from_client, mut to_server) = intercepted().await?;
+        let request = Request::Join {
+            id: 3,
+            addr: "node-three.example:7000".to_string(),
+        };
+        client.send(&request).await?;
+        let sent = message(&mut from_client).await?;
+        assert!(!sent.windows(10).any(|window| window == b"node-three"));
+        to_server.write_all(&sent).await?;
+        let Request::Join { id, addr } = server.recv().await? else {
+            bail!("expected the request sent");
+        };
+        assert_eq!((id, addr.as_str()), (3, "node-three.example:7000"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn an_altered_message_is_refused() -> Result<()> {
+        let (mut client, mut server, mut from_client, mut to_server) = intercepted().await?;
+        client.send(&Request::Leave { id: 3 }).await?;
+        let mut sent = message(&mut from_client).await?;
+        if let Some(last) = sent.last_mut() {
+            *last ^= 1;
+        }
+        to_server.write_all(&sent).await?;
+        assert!(server.recv::<Request>().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_replayed_message_is_refused() -> Result<()> {
+        let (mut client, mut server, mut from_client, mut to_server) = intercepted().await?;
+        client.send(&Request::Leave { id: 3 }).await?;
+        let sent = message(&mut from_client).await?;
+        to_server.write_all(&sent).await?;
+        assert!(matches!(
+            server.recv::<Request>().await?,
+            Request::Leave { id: 3 }
+        ));
+        to_server.write_all(&sent).await?;
+        assert!(server.recv::<Request>().await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The state machine: this node's database file, as the log applies to it.
+//!
+//! Each entry's batch is committed to the database in one transaction. The
+//! last applied log id and the membership are recorded in the log's file
+//! after the batch lands, so a crash between the two replays the batch; a
+//! batch only puts and deletes whole rows, so applying it again changes
+//! nothing.
+//!
+//! A snapshot is every row of the store, as a batch of puts. It is copied when
+//! asked for, and installing one replaces every row in one transaction. The
+//! latest snapshot is kept in the log's file, for followers too far behind
+//! for the log.
+
+use std::{
+    io::Cursor,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use openraft::{
+    BasicNode, Entry, EntryPayload, LogId, OptionalSend, RaftSnapshotBuilder, Snapshot,
+    SnapshotMeta, StorageError, StorageIOError, StoredMembership, storage::RaftStateMachine,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    TypeConfig,
+    log::{LogStore, any, decode, encode},
+};
+use crate::db::backend::{RedbBackend, StorageBackend as _, Table, WriteOp};
+
+/// Where the state machine's progress is kept in the log's file.
+const APPLIED: &str = "applied";
+/// Where the latest snapshot is kept in the log's file.
+const SNAPSHOT: &str = "snapshot";
+
+/// How far the log has been applied, and the membership as of there.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct Applied {
+    last: Option<LogId<u64>>,
+    membership: StoredMembership<u64, BasicNode>,
+}
+
+/// A snapshot as it is kept: its description and its rows.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Stored {
+    meta: SnapshotMeta<u64, BasicNode>,
+    data: Vec<u8>,
+}
+
+impl Stored {
+    fn snapshot(self) -> Snapshot<TypeConfig> {
+        Snapshot {
+            meta: self.meta,
+            snapshot: Box::new(Cursor::new(self.data)),
+        }
+    }
+}
+
+/// This node's database, driven by the log.
+#[derive(Debug)]
+pub(crate) struct StateMachine {
+    local: Arc<RedbBackend>,
+    log: LogStore,
+    applied: Applied,
+}
+
+impl StateMachine {
+    /// The state machine over `local`, picking up where it left off.
+    pub(crate) fn open(local: Arc<RedbBackend>, log: LogStore) -> Result<Self> {
+        let applied = log.meta(APPLIED)?.unwrap_or_default();
+        Ok(Self {
+            local,
+            log,
+            applied,
+        })
+    }
+
+    fn record(&self) -> Result<()> {
+        self.log.set_meta(APPLIED, &self.applied)
+    }
+
+    /// Every row of the store, as the puts that would write it.
+    fn rows(&self) -> Result<Vec<WriteOp>> {
+        let mut rows = vec![];
+        for table in Table::ALL {
+            rows.extend(
+                self.local
+                    .scan(table, "")?
+                    .into_iter()
+                    .map(|(key, value)| WriteOp::Put { table, key, value }),
+            );
+        }
+        Ok(rows)
+    }
+
+    /// Replace every row of the store with `rows`.
+    fn replace(&self, rows: Vec<WriteOp>) -> Result<()> {
+        let mut ops = vec![];
+        for table in Table::ALL {
+            ops.extend(
+                self.local
+                    .keys(table, "")?
+                    .into_iter()
+                    .map(|key| WriteOp::Delete { table, key }),
+            );
+        }
+        ops.extend(rows);
+        self.local.commit(ops)
+    }
+}
+
+/// A snapshot copied from the store, waiting to be handed to Raft.
+#[derive(Debug)]
+pub(crate) struct Snapshotter {
+    copied: Result<Stored, String>,
+    log: LogStore,
+}
+
+impl RaftSnapshotBuilder<TypeConfig> for Snapshotter {
+    async fn build_snapshot(&mut self) -> Result<Snapshot<TypeConfig>, StorageError<u64>> {
+        let stored = self
+            .copied
+            .clone()
+            .map_err(|e| StorageIOError::read_state_machine(openraft::AnyError::error(e)))?;
+        self.log
+            .set_meta(SNAPSHOT, &stored)
+            .map_err(|e| StorageIOError::write_snapshot(Some(stored.meta.signature()), any(&e)))?;
+        Ok(stored.snapshot())
+    }
+}
+
+impl RaftStateMachine<TypeConfig> for StateMachine {
+    type SnapshotBuilder = Snapshotter;
+
+    async fn applied_state(
+        &mut self,
+    ) -> Result<(Option<LogId<u64>>, StoredMembership<u64, BasicNode>), StorageError<u64>> {
+        Ok((self.applied.last, self.applied.membership.clone()))
+    }
+
+    async fn apply<I>(&mut self, entries: I) -> Result<Vec<()>, StorageError<u64>>
+    where
+        I: IntoIterator<Item = Entry<TypeConfig>> + OptionalSend,
+        I::IntoIter: OptionalSend,
+    {
+        let mut applied = vec![];
+        for entry in entries {
+            match entry.payload {
+                EntryPayload::Blank => {}
+                EntryPayload::Normal(ops) => self
+                    .local
+                    .commit(ops)
+                    .map_err(|e| StorageIOError::apply(entry.log_id, any(&e)))?,
+                EntryPayload::Membership(membership) => {
+                    self.applied.membership = StoredMembership::new(Some(entry.log_id), membership);
+                }
+            }
+            self.applied.last = Some(entry.log_id);
+            applied.push(());
+        }
+        self.record()
+            .map_err(|e| StorageIOError::write_state_machine(any(&e)))?;
+        Ok(applied)
+    }
+
+    async fn get_snapshot_builder(&mut self) -> Self::SnapshotBuilder {
+        // The rows are copied here, while nothing is being applied, so the
+        // snapshot holds exactly the log up to `applied.last`.
+        let copied = self
+            .rows()
+            .and_then(|rows| encode(&rows))
+            .map(|data| Stored {
+                meta: SnapshotMeta {
+                    last_log_id: self.applied.last,
+                    last_membership: self.applied.membership.clone(),
+                    snapshot_id: snapshot_id(self.applied.last),
+                },
+                data,
+            })
+            .map_err(|e| format!("{e:#}"));
+        Snapshotter {
+            copied,
+            log: self.log.clone(),
+        }
+    }
+
+    async fn begin_receiving_snapshot(
+        &mut self,
+    ) -> Result<Box<Cursor<Vec<u8>>>, StorageError<u64>> {
+        Ok(Box::new(Cursor::new(vec![])))
+    }
+
+    async fn install_snapshot(
+        &mut self,
+        meta: &SnapshotMeta<u64, BasicNode>,
+        snapshot: Box<Cursor<Vec<u8>>>,
+    ) -> Result<(), StorageError<u64>> {
+        let stored = Stored {
+            meta: meta.clone(),
+            data: snapshot.into_inner(),
+        };
+        let installed = decode::<Vec<WriteOp>>(&stored.data)
+            .and_then(|rows| self.replace(rows))
+            .and_then(|()| {
+                self.applied = Applied {
+                    last: meta.last_log_id,
+                    membership: meta.last_membership.clone(),
+                };
+                self.record()
+            })
+            .and_then(|()| self.log.set_meta(SNAPSHOT, &stored));
+        installed
+            .map_err(|e| StorageIOError::write_snapshot(Some(meta.signature()), any(&e)).into())
+    }
+
+    async fn get_current_snapshot(
+        &mut self,
+    ) -> Result<Option<Snapshot<TypeConfig>>, StorageError<u64>> {
+        self.log
+            .meta::<Stored>(SNAPSHOT)
+            .map(|stored| stored.map(Stored::snapshot))
+            .map_err(|e| StorageIOError::read_snapshot(None, any(&e)).into())
+    }
+}
+
+/// A snapshot id no other snapshot of this node shares.
+fn snapshot_id(last: Option<LogId<u64>>) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    match last {
+        Some(last) => format!("{}-{}-{nanos}", last.leader_id, last.index),
+        None => format!("empty-{nanos}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process, sync::Arc};
+
+    use anyhow::Result;
+    use openraft::{
+        CommittedLeaderId, Entry, EntryPayload, LogId, RaftSnapshotBuilder as _,
+        storage::RaftStateMachine as _,
+    };
+
+    use super::StateMachine;
+    use crate::{
+        cluster::log::LogStore,
+        db::backend::{RedbBackend, StorageBackend as _, Table, WriteOp},
+    };
+
+    fn put(key: &str) -> WriteOp {
+        WriteOp::Put {
+            table: Table::SigningUses,
+            key: key.to_string(),
+            value: 1u64.to_le_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_snapshot_replaces_every_row_of_another_node() -> Result<()> {
+        let dir = env::temp_dir().join(format!("salusd-raft-state-{}", process::id()));
+        let _old = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let open = |name: &str| -> Result<StateMachine> {
+            let local = Arc::new(RedbBackend::create(&dir.join(format!("{name}.redb")))?);
+            StateMachine::open(local, LogStore::open(&dir.join(format!("{name}.raft")))?)
+        };
+        let mut leader = open("leader")?;
+        let log_id = LogId::new(CommittedLeaderId::new(1, 1), 1);
+        let _applied = leader
+            .apply([Entry {
+                log_id,
+                payload: EntryPayload::Normal(vec![put("kept")]),
+            }])
+            .await?;
+        let snapshot = leader.get_snapshot_builder().await.build_snapshot().await?;
+
+        let mut follower = open("follower")?;
+        follower.local.commit(vec![put("stale")])?;
+        follower
+            .install_snapshot(&snapshot.meta, snapshot.snapshot)
+            .await?;
+        assert_eq!(follower.local.keys(Table::SigningUses, "")?, ["kept"]);
+        assert_eq!(follower.applied_state().await?.0, Some(log_id));
+        assert!(follower.get_current_snapshot().await?.is_some());
+        drop(follower);
+        assert_eq!(open("follower")?.applied.last, Some(log_id));
+        let _removed = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -6,15 +6,29 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use config::{Config, Environment, File, FileFormat, Source};
 use getset::{CopyGetters, Getters};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de};
 use tracing_subscriber_init::TracingConfig;
 
-use crate::{error::Error, utils::to_path_buf};
+use crate::{
+    error::Error,
+    handler::Wire,
+    store::{
+        blob::{DEFAULT_MAX_STREAM_BYTES, DEFAULT_MAX_UPLOADS, DEFAULT_UPLOAD_TIMEOUT},
+        compress::DEFAULT_LEVEL,
+    },
+    utils::{expand, to_path_buf},
+};
+
+pub(crate) mod reload;
 
 /// Trait to allow default paths to be supplied to [`load`]
 ///
@@ -28,14 +42,96 @@ pub(crate) trait PathDefaults {
     fn app_name(&self) -> String;
     /// The absolute path to use for the config file
     fn config_absolute_path(&self) -> Option<String>;
+    /// The format the config file is written in, when not its extension's
+    fn config_format(&self) -> Option<ConfigFormat>;
     /// The abolute path to use for tracing output
     fn tracing_absolute_path(&self) -> Option<String>;
     /// The absolute path to use for the database
     fn database_absolute_path(&self) -> Option<String>;
 }
 
+/// The formats a config file can be written in
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Every format, in the order a default config file is looked for in
+    const ALL: [Self; 3] = [Self::Toml, Self::Yaml, Self::Json];
+
+    /// The extensions a file in this format is known by
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Toml => &["toml"],
+            Self::Yaml => &["yaml", "yml"],
+            Self::Json => &["json"],
+        }
+    }
+
+    /// The format `path`'s extension names, if it names one.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extensions().contains(&extension.as_str()))
+    }
+}
+
+impl From<ConfigFormat> for FileFormat {
+    fn from(format: ConfigFormat) -> Self {
+        match format {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Yaml => FileFormat::Yaml,
+            ConfigFormat::Json => FileFormat::Json,
+        }
+    }
+}
+
 /// The documented default for [`ConfigSalusd::key_timeout`].
-const DEFAULT_KEY_TIMEOUT: u64 = 20;
+pub(crate) const DEFAULT_KEY_TIMEOUT: u64 = 20;
+/// The documented default for [`ConfigSalusd::max_random_bytes`].
+pub(crate) const DEFAULT_MAX_RANDOM_BYTES: u32 = 4096;
+/// The documented default for [`ConfigSalusd::max_message_bytes`], matching
+/// `MAX_MESSAGE_SIZE`.
+pub(crate) const DEFAULT_MAX_MESSAGE_BYTES: u32 = 1024 * 1024;
+/// The documented default for [`ConfigSalusd::max_value_bytes`].
+pub(crate) const DEFAULT_MAX_VALUE_BYTES: u64 = 1024 * 1024;
+/// The documented default for [`SharesDefaults::num_shares`].
+pub(crate) const DEFAULT_NUM_SHARES: u8 = 5;
+/// The documented default for [`SharesDefaults::threshold`].
+pub(crate) const DEFAULT_THRESHOLD: u8 = 3;
+/// The documented default for [`KeepaliveSettings::interval`].
+const DEFAULT_KEEPALIVE_INTERVAL: u64 = 30;
+/// The documented default for [`KeepaliveSettings::timeout`].
+const DEFAULT_KEEPALIVE_TIMEOUT: u64 = 120;
+/// The documented default for [`ReadCacheSettings::ttl`].
+const DEFAULT_READ_CACHE_TTL: u64 = 30;
+/// The documented default for [`PluginSettings::timeout_ms`].
+const DEFAULT_PLUGIN_TIMEOUT_MS: u64 = 5000;
+/// The documented default for [`PluginSettings::max_ttl`].
+const DEFAULT_PLUGIN_MAX_TTL: u64 = 3600;
+/// The documented default for [`PluginSettings::fuel`].
+const DEFAULT_PLUGIN_FUEL: u64 = 10_000_000;
+/// The documented default for [`PluginSettings::max_memory_bytes`].
+const DEFAULT_PLUGIN_MAX_MEMORY_BYTES: u64 = 16 * 1024 * 1024;
+/// The documented default for [`NamespaceSettings::approval_window`].
+const DEFAULT_APPROVAL_WINDOW: u64 = 900;
+/// The documented default for [`DatabaseRole::ttl`].
+const DEFAULT_DATABASE_TTL: u64 = 3600;
+/// The documented default for [`SshSettings::max_ttl`].
+const DEFAULT_SSH_MAX_TTL: u64 = 86_400;
+/// The documented default for [`PkiSettings::max_ttl`].
+const DEFAULT_PKI_MAX_TTL: u64 = 2_592_000;
+/// The documented default for [`HookSettings::timeout_ms`].
+const DEFAULT_HOOK_TIMEOUT_MS: u64 = 5000;
+/// The documented default for [`ClusterSettings::heartbeat_ms`].
+const DEFAULT_HEARTBEAT_MS: u64 = 250;
+/// The documented default for [`ClusterSettings::election_timeout_ms`].
+const DEFAULT_ELECTION_TIMEOUT_MS: u64 = 1000;
 
 // `#[serde(default)]` fills any field absent from all config sources from
 // `Default`, making the built-in defaults the lowest-precedence layer (a config
@@ -51,12 +147,93 @@ pub(crate) struct ConfigSalusd {
     enable_std_output: bool,
     #[getset(get_copy = "pub(crate)")]
     key_timeout: u64,
+    /// The most bytes a single `random` request may draw
+    #[getset(get_copy = "pub(crate)")]
+    max_random_bytes: u32,
+    /// The longest request, in bytes, read from a client before it is refused
+    /// and its connection closed
+    #[getset(get_copy = "pub(crate)")]
+    max_message_bytes: u32,
+    /// The longest value, in bytes, stored by one `store` request or batch
+    /// entry; values stored in chunks are held to `streaming.max_bytes`
+    #[getset(get_copy = "pub(crate)")]
+    max_value_bytes: u64,
+    /// Start read-only, refusing changes to the store until the mode is lifted
+    #[getset(get_copy = "pub(crate)")]
+    read_only: bool,
+    /// Keep the daemon's memory out of core files and, on Linux, away from
+    /// other processes of its user that would attach to it. On Windows only
+    /// crash reports leave it out: a debugger of its user can still attach
+    #[getset(get_copy = "pub(crate)")]
+    harden_process: bool,
     /// Optional override for the IPC socket path. Falls back to the shared
     /// `SALUS_SOCKET` env var and then the platform default in libsalus.
     #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
     socket_path: Option<String>,
+    /// A second socket, on which the daemon also speaks newline-delimited
+    /// JSON. Off unless set.
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    json_socket_path: Option<String>,
+    /// Where the key that encrypts connections on a socket in the shared
+    /// temp directory is kept. Falls back to the shared `SALUS_TRANSPORT_KEY`
+    /// env var and then `transport.key` in the default config directory.
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    transport_key_path: Option<String>,
     #[getset(get = "pub(crate)")]
     tracing: Tracing,
+    /// The share count and threshold used when a client does not choose them
+    #[getset(get = "pub(crate)")]
+    shares: SharesDefaults,
+    /// Where the store is kept, when not in the local database file
+    #[getset(get = "pub(crate)")]
+    storage: Storage,
+    /// How idle client connections are checked on and given up on
+    #[getset(get = "pub(crate)")]
+    keepalive: KeepaliveSettings,
+    /// Whether, and for how long, decrypted values are cached between reads
+    #[getset(get = "pub(crate)")]
+    read_cache: ReadCacheSettings,
+    /// The limits on values streamed in chunks
+    #[getset(get = "pub(crate)")]
+    streaming: StreamingSettings,
+    /// Whether values are compressed before they are sealed
+    #[getset(get = "pub(crate)")]
+    compression: CompressionSettings,
+    /// How many earlier versions of each value are kept
+    #[getset(get = "pub(crate)")]
+    history: HistorySettings,
+    /// Whether this node is one of a Raft-replicated cluster
+    #[getset(get = "pub(crate)")]
+    cluster: ClusterSettings,
+    /// More places to take requests on, beside `socket_path` and
+    /// `json_socket_path`
+    #[getset(get = "pub(crate)")]
+    listeners: Vec<ListenerSettings>,
+    /// Stricter rules for the keys under some namespaces, by namespace
+    #[getset(get = "pub(crate)")]
+    namespace: BTreeMap<String, NamespaceSettings>,
+    /// The programs that mint short-lived credentials, by name
+    #[getset(get = "pub(crate)")]
+    plugins: BTreeMap<String, PluginSettings>,
+    /// The database users minted on a read of `database/creds/<role>`, by
+    /// role
+    #[getset(get = "pub(crate)")]
+    database_roles: BTreeMap<String, DatabaseRole>,
+    /// How SSH certificates are signed
+    #[getset(get = "pub(crate)")]
+    ssh: SshSettings,
+    /// How X.509 certificates are issued
+    #[getset(get = "pub(crate)")]
+    pki: PkiSettings,
+    /// The commands and webhooks run after values change, by name
+    #[getset(get = "pub(crate)")]
+    hooks: BTreeMap<String, HookSettings>,
+    /// The rules values are checked against before they are stored, by name
+    #[getset(get = "pub(crate)")]
+    validation: BTreeMap<String, ValidationSettings>,
 }
 
 impl Default for ConfigSalusd {
@@ -66,8 +243,31 @@ impl Default for ConfigSalusd {
             quiet: 0,
             enable_std_output: false,
             key_timeout: DEFAULT_KEY_TIMEOUT,
+            max_random_bytes: DEFAULT_MAX_RANDOM_BYTES,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            max_value_bytes: DEFAULT_MAX_VALUE_BYTES,
+            read_only: false,
+            harden_process: true,
             socket_path: None,
+            json_socket_path: None,
+            transport_key_path: None,
             tracing: Tracing::default(),
+            shares: SharesDefaults::default(),
+            storage: Storage::default(),
+            keepalive: KeepaliveSettings::default(),
+            read_cache: ReadCacheSettings::default(),
+            streaming: StreamingSettings::default(),
+            compression: CompressionSettings::default(),
+            history: HistorySettings::default(),
+            cluster: ClusterSettings::default(),
+            listeners: Vec::new(),
+            namespace: BTreeMap::new(),
+            plugins: BTreeMap::new(),
+            database_roles: BTreeMap::new(),
+            ssh: SshSettings::default(),
+            pki: PkiSettings::default(),
+            hooks: BTreeMap::new(),
+            validation: BTreeMap::new(),
         }
     }
 }
@@ -102,7 +302,549 @@ impl TracingConfig for ConfigSalusd {
     }
 }
 
-/// Tracing configuration
+/// The `[shares]` table: defaults for a new share set
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct SharesDefaults {
+    /// The number of shares to create
+    #[getset(get_copy = "pub(crate)")]
+    num_shares: u8,
+    /// The number of shares needed to reconstruct the key
+    #[getset(get_copy = "pub(crate)")]
+    threshold: u8,
+}
+
+impl Default for SharesDefaults {
+    fn default() -> Self {
+        Self {
+            num_shares: DEFAULT_NUM_SHARES,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+}
+
+/// The `[keepalive]` table: pings on idle long-lived connections, and how long
+/// a silent client is waited for
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct KeepaliveSettings {
+    /// How long a long-lived connection may sit idle before the daemon pings
+    /// it, in seconds; 0 turns pings off
+    #[getset(get_copy = "pub(crate)")]
+    interval: u64,
+    /// How long a connection may go without a byte from its client, with none
+    /// of its requests in flight, before it is closed, in seconds; 0 waits
+    /// forever
+    #[getset(get_copy = "pub(crate)")]
+    timeout: u64,
+}
+
+impl Default for KeepaliveSettings {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_KEEPALIVE_INTERVAL,
+            timeout: DEFAULT_KEEPALIVE_TIMEOUT,
+        }
+    }
+}
+
+/// The `[read_cache]` table: the cache of decrypted values, off by default
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct ReadCacheSettings {
+    /// The most values to keep decrypted; 0 turns the cache off
+    #[getset(get_copy = "pub(crate)")]
+    capacity: usize,
+    /// How long a decrypted value is kept, in seconds
+    #[getset(get_copy = "pub(crate)")]
+    ttl: u64,
+}
+
+impl Default for ReadCacheSettings {
+    fn default() -> Self {
+        Self {
+            capacity: 0,
+            ttl: DEFAULT_READ_CACHE_TTL,
+        }
+    }
+}
+
+/// The `[streaming]` table: limits on values uploaded in chunks
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct StreamingSettings {
+    /// The largest value that may be uploaded, in bytes
+    #[getset(get_copy = "pub(crate)")]
+    max_bytes: u64,
+    /// How many uploads may be in progress at once
+    #[getset(get_copy = "pub(crate)")]
+    max_uploads: usize,
+    /// How long an upload may go without a chunk before it is dropped, in
+    /// seconds
+    #[getset(get_copy = "pub(crate)")]
+    timeout: u64,
+    /// Whether equal values share one set of chunks
+    #[getset(get_copy = "pub(crate)")]
+    dedup: bool,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_STREAM_BYTES,
+            max_uploads: DEFAULT_MAX_UPLOADS,
+            timeout: DEFAULT_UPLOAD_TIMEOUT,
+            dedup: true,
+        }
+    }
+}
+
+/// The `[compression]` table: zstd compression before sealing, off by default
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct CompressionSettings {
+    /// The shortest value to compress, in bytes; 0 turns compression off
+    #[getset(get_copy = "pub(crate)")]
+    threshold: usize,
+    /// The zstd level to compress at
+    #[getset(get_copy = "pub(crate)")]
+    level: i32,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0,
+            level: DEFAULT_LEVEL,
+        }
+    }
+}
+
+/// The `[history]` table: earlier versions of values kept for `salusc history`
+/// and `salusc diff`, none by default
+#[derive(Clone, Copy, CopyGetters, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct HistorySettings {
+    /// How many versions before its current one each value keeps; 0 keeps
+    /// none
+    #[getset(get_copy = "pub(crate)")]
+    keep: u32,
+}
+
+/// The `[cluster]` table: clustered mode, off unless `listen` is set
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct ClusterSettings {
+    /// This node's id, nonzero and unique within the cluster
+    #[getset(get_copy = "pub(crate)")]
+    node_id: u64,
+    /// The address to take cluster traffic on, `<host>:<port>`
+    #[getset(get = "pub(crate)")]
+    listen: Option<String>,
+    /// The address other nodes reach this one at (default: `listen`)
+    #[getset(get = "pub(crate)")]
+    advertise: Option<String>,
+    /// A file holding the key every node of the cluster shares
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    key_file: Option<PathBuf>,
+    /// Whether this node starts the cluster, as its first member
+    #[getset(get_copy = "pub(crate)")]
+    bootstrap: bool,
+    /// How often the leader heartbeats, in milliseconds
+    #[getset(get_copy = "pub(crate)")]
+    heartbeat_ms: u64,
+    /// How long a follower waits to hear from a leader before calling an
+    /// election, in milliseconds
+    #[getset(get_copy = "pub(crate)")]
+    election_timeout_ms: u64,
+}
+
+impl Default for ClusterSettings {
+    fn default() -> Self {
+        Self {
+            node_id: 0,
+            listen: None,
+            advertise: None,
+            key_file: None,
+            bootstrap: false,
+            heartbeat_ms: DEFAULT_HEARTBEAT_MS,
+            election_timeout_ms: DEFAULT_ELECTION_TIMEOUT_MS,
+        }
+    }
+}
+
+/// What a `[[listeners]]` entry listens on
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ListenerKind {
+    /// A local socket, at `path`
+    #[default]
+    Socket,
+    /// A TCP address, with mutual TLS
+    Tcp,
+}
+
+/// A `[[listeners]]` entry: one more place the daemon takes requests on, with
+/// who may connect to it and what they may do
+#[derive(Clone, CopyGetters, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct ListenerSettings {
+    /// What is listened on: `socket` (default) or `tcp`
+    #[getset(get_copy = "pub(crate)")]
+    kind: ListenerKind,
+    /// The socket's path, for a `socket` listener
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    path: Option<String>,
+    /// The address to listen on, `<host>:<port>`, for a `tcp` listener
+    #[getset(get = "pub(crate)")]
+    address: Option<String>,
+    /// The protocol spoken: `bincode` (default) or `json`
+    #[getset(get_copy = "pub(crate)")]
+    wire: Wire,
+    /// The group the socket file is given, whose members may then connect
+    #[getset(get_copy = "pub(crate)")]
+    gid: Option<u32>,
+    /// The users, by uid, that may connect to the socket; with `allow_gids`
+    /// empty too, anyone who can reach it may
+    #[getset(get = "pub(crate)")]
+    allow_uids: Vec<u32>,
+    /// The groups, by gid, whose members may connect to the socket
+    #[getset(get = "pub(crate)")]
+    allow_gids: Vec<u32>,
+    /// Refuse changes to the store made through this listener
+    #[getset(get_copy = "pub(crate)")]
+    read_only: bool,
+    /// The certificate chain a `tcp` listener presents, PEM
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    cert: Option<PathBuf>,
+    /// The private key for `cert`, PEM
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    key: Option<PathBuf>,
+    /// The certificate authorities a client's certificate must chain to, PEM
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    client_ca: Option<PathBuf>,
+}
+
+/// A `[namespace.<name>]` table: rules for the keys under `<name>/`, stricter
+/// than the daemon's own
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct NamespaceSettings {
+    /// For how many seconds after the store is unlocked the namespace's keys
+    /// are served; the store's own `key_timeout` still locks it for all
+    #[getset(get_copy = "pub(crate)")]
+    key_timeout: Option<u64>,
+    /// The longest value, in bytes, stored in the namespace
+    #[getset(get_copy = "pub(crate)")]
+    max_value_bytes: Option<u64>,
+    /// What is refused in the namespace
+    #[getset(get = "pub(crate)")]
+    policies: Vec<NamespacePolicy>,
+    /// Which requests for the namespace's keys are written to the audit log
+    #[getset(get_copy = "pub(crate)")]
+    audit: Option<NamespaceAudit>,
+    /// How many other users must approve a read of one of the namespace's
+    /// keys before its value is released; reads are not held when unset
+    #[getset(get_copy = "pub(crate)")]
+    approvals: Option<u32>,
+    /// The users, by uid, who may approve; any user but the requester when
+    /// empty
+    #[getset(get = "pub(crate)")]
+    approvers: Vec<u32>,
+    /// For how many seconds a held read waits for its approvals and, once
+    /// granted, the requester may read the key
+    #[getset(get_copy = "pub(crate)")]
+    approval_window: u64,
+}
+
+impl Default for NamespaceSettings {
+    fn default() -> Self {
+        Self {
+            key_timeout: None,
+            max_value_bytes: None,
+            policies: Vec::new(),
+            audit: None,
+            approvals: None,
+            approvers: Vec::new(),
+            approval_window: DEFAULT_APPROVAL_WINDOW,
+        }
+    }
+}
+
+/// Something a namespace refuses
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum NamespacePolicy {
+    /// Any change to its values
+    ReadOnly,
+    /// Replacing a value already stored
+    NoOverwrite,
+    /// Deleting a value
+    NoDelete,
+}
+
+/// Which requests for a namespace's keys are audited
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NamespaceAudit {
+    /// Every change
+    Writes,
+    /// Every change and every read
+    All,
+}
+
+/// A `[plugins.<name>]` table: a program the daemon runs, or a WebAssembly
+/// module it runs in itself, to mint short-lived credentials
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct PluginSettings {
+    /// The program to run, best given as an absolute path
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    command: Option<PathBuf>,
+    /// The arguments it is run with
+    #[getset(get = "pub(crate)")]
+    args: Vec<String>,
+    /// A WebAssembly module run in the daemon instead of a program, best
+    /// given as an absolute path; needs the `wasm-plugins` feature
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    module: Option<PathBuf>,
+    /// The host functions a module may import
+    #[getset(get = "pub(crate)")]
+    capabilities: Vec<PluginCapability>,
+    /// The most instructions, roughly, one call to a module may run
+    #[getset(get_copy = "pub(crate)")]
+    fuel: u64,
+    /// The most memory a module may have, in bytes
+    #[getset(get_copy = "pub(crate)")]
+    max_memory_bytes: u64,
+    /// How long one call may take before the program is killed, in
+    /// milliseconds
+    #[getset(get_copy = "pub(crate)")]
+    timeout_ms: u64,
+    /// The longest a credential it mints may be good for, in seconds
+    #[getset(get_copy = "pub(crate)")]
+    max_ttl: u64,
+    /// The roles credentials may be minted for; any role when empty
+    #[getset(get = "pub(crate)")]
+    roles: Vec<String>,
+    /// A stored key holding a JSON document sent to the program with each
+    /// mint, such as the admin login it mints credentials with
+    #[getset(get = "pub(crate)")]
+    config_key: Option<String>,
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        Self {
+            command: None,
+            args: Vec::new(),
+            module: None,
+            capabilities: Vec::new(),
+            fuel: DEFAULT_PLUGIN_FUEL,
+            max_memory_bytes: DEFAULT_PLUGIN_MAX_MEMORY_BYTES,
+            timeout_ms: DEFAULT_PLUGIN_TIMEOUT_MS,
+            max_ttl: DEFAULT_PLUGIN_MAX_TTL,
+            roles: Vec::new(),
+            config_key: None,
+        }
+    }
+}
+
+/// A host function a WebAssembly plugin may import from `salus`
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PluginCapability {
+    /// `log(ptr, len)`: write a line to the daemon's log
+    Log,
+    /// `random(ptr, len) -> i32`: fill a buffer with random bytes
+    Random,
+    /// `now() -> i64`: the time, in seconds since the Unix epoch
+    Clock,
+}
+
+/// A `[hooks.<name>]` table: a command or webhook run after a value changes
+/// or a named key is rotated
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct HookSettings {
+    /// The keys it is run for: a key, or every key under a prefix ending in
+    /// `/`; every key when empty
+    #[getset(get = "pub(crate)")]
+    keys: Vec<String>,
+    /// The changes it is run for; every change when empty
+    #[getset(get = "pub(crate)")]
+    events: Vec<HookEvent>,
+    /// The program to run, best given as an absolute path
+    #[getset(get = "pub(crate)")]
+    #[serde(deserialize_with = "expanded")]
+    command: Option<PathBuf>,
+    /// The arguments it is run with
+    #[getset(get = "pub(crate)")]
+    args: Vec<String>,
+    /// The URL the change is sent to in a POST, after any command has run
+    #[getset(get = "pub(crate)")]
+    url: Option<String>,
+    /// How long one run may take before it is given up on, in milliseconds
+    #[getset(get_copy = "pub(crate)")]
+    timeout_ms: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            events: Vec::new(),
+            command: None,
+            args: Vec::new(),
+            url: None,
+            timeout_ms: DEFAULT_HOOK_TIMEOUT_MS,
+        }
+    }
+}
+
+/// A change a hook is run after
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HookEvent {
+    /// A value was stored, patched, generated or imported
+    Written,
+    /// A value was deleted
+    Deleted,
+    /// A named key was given a new version
+    Rotated,
+    /// A value went unwritten for longer than its `rotate_after`
+    Overdue,
+}
+
+impl HookEvent {
+    /// The name a hook is told.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Written => "written",
+            Self::Deleted => "deleted",
+            Self::Rotated => "rotated",
+            Self::Overdue => "overdue",
+        }
+    }
+}
+
+/// A `[validation.<name>]` table: what the values stored under some keys
+/// must look like
+#[derive(Clone, CopyGetters, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct ValidationSettings {
+    /// The keys it holds for: a key, or every key under a prefix ending in
+    /// `/`; every key when empty
+    #[getset(get = "pub(crate)")]
+    keys: Vec<String>,
+    /// A regular expression the value must match
+    #[getset(get = "pub(crate)")]
+    pattern: Option<String>,
+    /// A JSON schema the value, a JSON document, must meet
+    #[getset(get = "pub(crate)")]
+    schema: Option<String>,
+    /// The shortest value, in bytes
+    #[getset(get_copy = "pub(crate)")]
+    min_bytes: Option<u64>,
+    /// The longest value, in bytes
+    #[getset(get_copy = "pub(crate)")]
+    max_bytes: Option<u64>,
+    /// The fewest bits of entropy the value may have, estimated from how
+    /// often each of its characters appears
+    #[getset(get_copy = "pub(crate)")]
+    min_entropy: Option<u64>,
+}
+
+/// A `[database_roles.<role>]` table: how a database user for the role is
+/// created, and dropped once its lease is up
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct DatabaseRole {
+    /// The plugin that runs the statements against the database
+    #[getset(get = "pub(crate)")]
+    plugin: String,
+    /// Run to create the user, with `{{name}}`, `{{password}}` and
+    /// `{{expiration}}` filled in
+    #[getset(get = "pub(crate)")]
+    creation: Vec<String>,
+    /// Run to drop the user, with `{{name}}` filled in
+    #[getset(get = "pub(crate)")]
+    revocation: Vec<String>,
+    /// Run when the user's lease is renewed, with `{{name}}` and
+    /// `{{expiration}}` filled in; nothing is run when empty
+    #[getset(get = "pub(crate)")]
+    renewal: Vec<String>,
+    /// How long the user is kept, in seconds
+    #[getset(get_copy = "pub(crate)")]
+    ttl: u64,
+}
+
+impl Default for DatabaseRole {
+    fn default() -> Self {
+        Self {
+            plugin: String::new(),
+            creation: Vec::new(),
+            revocation: Vec::new(),
+            renewal: Vec::new(),
+            ttl: DEFAULT_DATABASE_TTL,
+        }
+    }
+}
+
+/// The `[ssh]` table: the SSH certificates the daemon signs
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct SshSettings {
+    /// The longest a certificate may be good for, in seconds
+    #[getset(get_copy = "pub(crate)")]
+    max_ttl: u64,
+}
+
+impl Default for SshSettings {
+    fn default() -> Self {
+        Self {
+            max_ttl: DEFAULT_SSH_MAX_TTL,
+        }
+    }
+}
+
+/// The `[pki]` table: the X.509 certificates the daemon's CA issues
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct PkiSettings {
+    /// The longest a leaf certificate may be good for, in seconds
+    #[getset(get_copy = "pub(crate)")]
+    max_ttl: u64,
+}
+
+impl Default for PkiSettings {
+    fn default() -> Self {
+        Self {
+            max_ttl: DEFAULT_PKI_MAX_TTL,
+        }
+    }
+}
+
+/// Storage configuration
+#[derive(Clone, CopyGetters, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Storage {
+    /// An S3-compatible bucket to keep the store in, `s3://<bucket>[/<prefix>]`
+    #[getset(get = "pub(crate)")]
+    url: Option<String>,
+    /// How long, in milliseconds, a write to the database file waits for
+    /// others to share its transaction; 0 commits each write on its own
+    #[getset(get_copy = "pub(crate)")]
+    commit_window_ms: u64,
+}
+
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Clone, CopyGetters, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
 #[serde(default)]
@@ -127,29 +869,51 @@ pub(crate) struct Tracing {
     directives: Option<String>,
 }
 
+/// Deserialize an optional path, with a leading `~` and any `${VAR}` in it
+/// expanded.
+fn expanded<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|path| expand(&path).map(T::from).map_err(de::Error::custom))
+        .transpose()
+}
+
 /// Load the configuration
 pub(crate) fn load<'a, S, T, D>(cli: &S, defaults: &D) -> Result<T>
 where
     T: Deserialize<'a>,
     S: Source + Clone + Send + Sync + 'static,
     D: PathDefaults,
+{
+    layered(cli, defaults)?
+        .try_deserialize::<T>()
+        .with_context(|| Error::ConfigDeserialize)
+}
+
+/// The configuration's sources, merged but not yet deserialized, each value
+/// still carrying where it came from.
+pub(crate) fn layered<S, D>(cli: &S, defaults: &D) -> Result<Config>
+where
+    S: Source + Clone + Send + Sync + 'static,
+    D: PathDefaults,
 {
     let config_file_path = config_file_path(defaults)?;
-    let config = Config::builder()
+    let format = config_format(defaults, &config_file_path);
+    Config::builder()
         // Lowest precedence first; the `config` crate is last-wins, so the order
-        // is: TOML file -> environment -> explicitly-set CLI flags.
+        // is: config file -> environment -> explicitly-set CLI flags.
         .add_source(
             File::from(config_file_path)
-                .format(FileFormat::Toml)
+                .format(format.into())
                 .required(false),
         )
         .add_source(env_source(&defaults.env_prefix()))
         .add_source(cli.clone())
         .build()
-        .with_context(|| Error::ConfigBuild)?;
-    config
-        .try_deserialize::<T>()
-        .with_context(|| Error::ConfigDeserialize)
+        .with_context(|| Error::ConfigBuild)
 }
 
 /// Build the environment-variable config source for `prefix`.
@@ -166,43 +930,174 @@ pub(crate) fn env_source(prefix: &str) -> Environment {
         .try_parsing(true)
 }
 
-fn config_file_path<D>(defaults: &D) -> Result<PathBuf>
+pub(crate) fn config_file_path<D>(defaults: &D) -> Result<PathBuf>
 where
     D: PathDefaults,
 {
     let default_fn = || -> Result<PathBuf> { default_config_file_path(defaults) };
     defaults
         .config_absolute_path()
-        .as_ref()
+        .as_deref()
         .map_or_else(default_fn, to_path_buf)
 }
 
+/// The format the config file at `path` is read in: the one set explicitly,
+/// else the one its extension names, else TOML.
+pub(crate) fn config_format<D>(defaults: &D, path: &Path) -> ConfigFormat
+where
+    D: PathDefaults,
+{
+    defaults
+        .config_format()
+        .or_else(|| ConfigFormat::from_path(path))
+        .unwrap_or_default()
+}
+
 fn default_config_file_path<D>(defaults: &D) -> Result<PathBuf>
 where
     D: PathDefaults,
 {
     let base = dirs2::config_dir().ok_or(Error::ConfigDir)?;
-    Ok(config_file_in(&base, &defaults.app_name()))
+    Ok(config_file_in(
+        &base,
+        &defaults.app_name(),
+        defaults.config_format(),
+    ))
 }
 
-/// Compose the default config file path: `<base>/<app>/<app>.toml`.
-fn config_file_in(base: &Path, app: &str) -> PathBuf {
-    base.join(app).join(app).with_extension("toml")
+/// Compose the default config file path, `<base>/<app>/<app>.<ext>`: the
+/// first of `.toml`, `.yaml`, `.yml` and `.json` (only `format`'s, when it is
+/// set) that exists, or else the first of them.
+fn config_file_in(base: &Path, app: &str, format: Option<ConfigFormat>) -> PathBuf {
+    let stem = base.join(app).join(app);
+    let formats = format
+        .as_ref()
+        .map_or(ConfigFormat::ALL.as_slice(), std::slice::from_ref);
+    let candidates = formats
+        .iter()
+        .flat_map(|format| format.extensions())
+        .map(|extension| stem.with_extension(extension))
+        .collect::<Vec<_>>();
+    candidates
+        .iter()
+        .find(|candidate| candidate.exists())
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or_else(|| stem.with_extension("toml"))
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use std::{fs, path::Path};
 
     use anyhow::Result;
     use config::{Config, Map};
 
-    use super::{ConfigSalusd, DEFAULT_KEY_TIMEOUT, config_file_in, env_source};
+    use super::{
+        ConfigFormat, ConfigSalusd, DEFAULT_ELECTION_TIMEOUT_MS, DEFAULT_HEARTBEAT_MS,
+        DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_KEEPALIVE_TIMEOUT, DEFAULT_KEY_TIMEOUT, DEFAULT_LEVEL,
+        DEFAULT_MAX_MESSAGE_BYTES, DEFAULT_MAX_RANDOM_BYTES, DEFAULT_MAX_STREAM_BYTES,
+        DEFAULT_MAX_UPLOADS, DEFAULT_MAX_VALUE_BYTES, DEFAULT_NUM_SHARES, DEFAULT_PKI_MAX_TTL,
+        DEFAULT_READ_CACHE_TTL, DEFAULT_SSH_MAX_TTL, DEFAULT_THRESHOLD, DEFAULT_UPLOAD_TIMEOUT,
+        PathDefaults, config_file_in, env_source, load,
+    };
+    use crate::utils::test::temp_dir;
+
+    /// Defaults that name a config file, and maybe its format.
+    struct TestDefaults {
+        config: String,
+        format: Option<ConfigFormat>,
+    }
+
+    impl PathDefaults for TestDefaults {
+        fn env_prefix(&self) -> String {
+            "SALUSD_TEST".to_string()
+        }
+
+        fn app_name(&self) -> String {
+            "salusd".to_string()
+        }
+
+        fn config_absolute_path(&self) -> Option<String> {
+            Some(self.config.clone())
+        }
+
+        fn config_format(&self) -> Option<ConfigFormat> {
+            self.format
+        }
+
+        fn tracing_absolute_path(&self) -> Option<String> {
+            None
+        }
+
+        fn database_absolute_path(&self) -> Option<String> {
+            None
+        }
+    }
 
     #[test]
     fn config_file_in_composes_app_dir_and_extension() {
-        let path = config_file_in(Path::new("/base"), "salusd");
+        let path = config_file_in(Path::new("/base"), "salusd", None);
         assert_eq!(path, Path::new("/base/salusd/salusd.toml"));
+        let path = config_file_in(Path::new("/base"), "salusd", Some(ConfigFormat::Yaml));
+        assert_eq!(path, Path::new("/base/salusd/salusd.yaml"));
+    }
+
+    #[test]
+    fn the_default_config_file_is_whichever_format_exists() -> Result<()> {
+        let base = temp_dir("formats")?;
+        fs::create_dir_all(base.join("salusd"))?;
+        fs::write(base.join("salusd").join("salusd.yml"), "key_timeout: 45\n")?;
+        assert_eq!(
+            config_file_in(&base, "salusd", None),
+            base.join("salusd").join("salusd.yml")
+        );
+        assert_eq!(
+            config_file_in(&base, "salusd", Some(ConfigFormat::Json)),
+            base.join("salusd").join("salusd.json")
+        );
+        fs::remove_dir_all(&base)?;
+        Ok(())
+    }
+
+    #[test]
+    fn config_files_are_read_in_the_format_their_extension_names() -> Result<()> {
+        let dir = temp_dir("config")?;
+        let files = [
+            ("salusd.toml", "key_timeout = 41\n[shares]\nthreshold = 2\n"),
+            (
+                "salusd.yaml",
+                "key_timeout: 42\nshares:\n  threshold: 2\nlisteners:\n  - path: /run/ops.sock\n",
+            ),
+            (
+                "salusd.json",
+                r#"{"key_timeout": 43, "shares": {"threshold": 2}}"#,
+            ),
+            ("salusd.conf", "key_timeout: 44\nshares:\n  threshold: 2\n"),
+        ];
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents)?;
+        }
+        let load_from = |name: &str, format: Option<ConfigFormat>| -> Result<ConfigSalusd> {
+            let defaults = TestDefaults {
+                config: dir.join(name).to_string_lossy().into_owned(),
+                format,
+            };
+            let no_flags = env_source("SALUSD_TEST_NO_FLAGS").source(Some(Map::new()));
+            load::<_, ConfigSalusd, _>(&no_flags, &defaults)
+        };
+        assert_eq!(load_from("salusd.toml", None)?.key_timeout(), 41);
+        let cfg = load_from("salusd.yaml", None)?;
+        assert_eq!(cfg.key_timeout(), 42);
+        assert_eq!(cfg.listeners().len(), 1);
+        assert_eq!(load_from("salusd.json", None)?.key_timeout(), 43);
+        let cfg = load_from("salusd.conf", Some(ConfigFormat::Yaml))?;
+        assert_eq!(cfg.key_timeout(), 44);
+        assert_eq!(cfg.shares().threshold(), 2);
+        // An extension that names no format is read as TOML.
+        assert!(load_from("salusd.conf", None).is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
     }
 
     #[test]
@@ -211,9 +1106,39 @@ mod test {
         let config = Config::builder().build()?;
         let cfg: ConfigSalusd = config.try_deserialize()?;
         assert_eq!(cfg.key_timeout(), DEFAULT_KEY_TIMEOUT);
+        assert_eq!(cfg.max_random_bytes(), DEFAULT_MAX_RANDOM_BYTES);
+        assert_eq!(cfg.max_message_bytes(), DEFAULT_MAX_MESSAGE_BYTES);
+        assert_eq!(cfg.max_value_bytes(), DEFAULT_MAX_VALUE_BYTES);
+        assert!(!cfg.read_only());
+        assert!(cfg.harden_process());
         assert_eq!(cfg.verbose(), 0);
         assert!(!cfg.enable_std_output());
         assert!(cfg.socket_path().is_none());
+        assert!(cfg.json_socket_path().is_none());
+        assert_eq!(cfg.shares().num_shares(), DEFAULT_NUM_SHARES);
+        assert_eq!(cfg.shares().threshold(), DEFAULT_THRESHOLD);
+        assert_eq!(cfg.keepalive().interval(), DEFAULT_KEEPALIVE_INTERVAL);
+        assert_eq!(cfg.keepalive().timeout(), DEFAULT_KEEPALIVE_TIMEOUT);
+        assert_eq!(cfg.ssh().max_ttl(), DEFAULT_SSH_MAX_TTL);
+        assert_eq!(cfg.pki().max_ttl(), DEFAULT_PKI_MAX_TTL);
+        assert!(cfg.hooks().is_empty());
+        assert!(cfg.validation().is_empty());
+        assert_eq!(cfg.read_cache().capacity(), 0);
+        assert_eq!(cfg.read_cache().ttl(), DEFAULT_READ_CACHE_TTL);
+        assert_eq!(cfg.storage().commit_window_ms(), 0);
+        assert_eq!(cfg.streaming().max_bytes(), DEFAULT_MAX_STREAM_BYTES);
+        assert_eq!(cfg.streaming().max_uploads(), DEFAULT_MAX_UPLOADS);
+        assert_eq!(cfg.streaming().timeout(), DEFAULT_UPLOAD_TIMEOUT);
+        assert!(cfg.streaming().dedup());
+        assert_eq!(cfg.compression().threshold(), 0);
+        assert_eq!(cfg.compression().level(), DEFAULT_LEVEL);
+        assert_eq!(cfg.history().keep(), 0);
+        assert!(cfg.cluster().listen().is_none());
+        assert_eq!(cfg.cluster().heartbeat_ms(), DEFAULT_HEARTBEAT_MS);
+        assert_eq!(
+            cfg.cluster().election_timeout_ms(),
+            DEFAULT_ELECTION_TIMEOUT_MS
+        );
         Ok(())
     }
 
@@ -227,12 +1152,50 @@ mod test {
             "SALUSD_TRACING__WITH_TARGET".to_string(),
             "true".to_string(),
         );
+        let _old = map.insert("SALUSD_SHARES__THRESHOLD".to_string(), "4".to_string());
+        let _old = map.insert("SALUSD_HISTORY__KEEP".to_string(), "5".to_string());
         let config = Config::builder()
             .add_source(env_source("SALUSD").source(Some(map)))
             .build()?;
         let cfg: ConfigSalusd = config.try_deserialize()?;
         assert_eq!(cfg.key_timeout(), 99);
         assert!(cfg.tracing().with_target());
+        assert_eq!(cfg.shares().threshold(), 4);
+        assert_eq!(cfg.shares().num_shares(), DEFAULT_NUM_SHARES);
+        assert_eq!(cfg.history().keep(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn paths_are_expanded_as_they_are_loaded() -> Result<()> {
+        let load = |name: &str, value: &str| -> Result<ConfigSalusd> {
+            let mut map = Map::new();
+            let _old = map.insert(name.to_string(), value.to_string());
+            let config = Config::builder()
+                .add_source(env_source("SALUSD").source(Some(map)))
+                .build()?;
+            Ok(config.try_deserialize()?)
+        };
+        let cfg = load("SALUSD_TRANSPORT_KEY_PATH", "~/salusd/transport.key")?;
+        if let Some(home) = dirs2::home_dir() {
+            let expected = home.join("salusd").join("transport.key");
+            assert_eq!(
+                cfg.transport_key_path().as_deref().map(Path::new),
+                Some(expected.as_path())
+            );
+        }
+        assert!(
+            load(
+                "SALUSD_SOCKET_PATH",
+                "/run/${SALUSD_TEST_UNSET_VAR}/salusd.sock"
+            )
+            .is_err()
+        );
+        let cfg = load("SALUSD_JSON_SOCKET_PATH", "/run/salusd/json.sock")?;
+        assert_eq!(
+            cfg.json_socket_path().as_deref(),
+            Some("/run/salusd/json.sock")
+        );
         Ok(())
     }
 }
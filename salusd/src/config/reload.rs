@@ -0,0 +1,344 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Taking up a changed configuration without restarting the daemon.
+//!
+//! [`Reloader::reload`] loads the configuration again, from the same file,
+//! environment and flags the daemon started with, and compares it with the
+//! one the daemon is running. The limits a connection is served with, the
+//! keepalive settings, the namespace rules, the plugins and database roles,
+//! the SSH and PKI settings, the hooks, the validation rules, and the log
+//! verbosity and directives are taken up at once: connections opened from
+//! then on get the new limits, the next change runs the new hooks, and the
+//! next write is checked against the new rules. Every other
+//! setting is only read as the daemon starts, so a reload that changes any of
+//! them is refused whole, naming them, and the daemon keeps running as it was.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+use getset::{CopyGetters, Getters};
+use libsalus::ConfigReload;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{
+    config::{ConfigSalusd, KeepaliveSettings, PkiSettings, SshSettings, Tracing},
+    handler::Namespaces,
+    hook::Hooks,
+    logging::TracingReload,
+    plugin::{Plugins, database::DatabaseRoles},
+    store::rules::Rules,
+};
+
+/// Loads the configuration again.
+pub(crate) type Load = Box<dyn Fn() -> Result<ConfigSalusd> + Send + Sync>;
+
+/// The limits a connection is served with, which a reload can change
+#[derive(Clone, CopyGetters, Debug, Eq, Getters, PartialEq)]
+pub(crate) struct Limits {
+    /// The default number of seconds an unlocked key is held
+    #[getset(get_copy = "pub(crate)")]
+    key_timeout: u64,
+    /// The most bytes a single `random` request may draw
+    #[getset(get_copy = "pub(crate)")]
+    max_random_bytes: u32,
+    /// The longest request read from a client
+    #[getset(get_copy = "pub(crate)")]
+    max_message_bytes: u32,
+    /// The longest value stored by one request
+    #[getset(get_copy = "pub(crate)")]
+    max_value_bytes: u64,
+    /// How idle connections are checked on and given up on
+    #[getset(get_copy = "pub(crate)")]
+    keepalive: KeepaliveSettings,
+    /// The rules for the keys under some namespaces
+    #[getset(get = "pub(crate)")]
+    namespaces: Arc<Namespaces>,
+    /// The plugins credentials are minted with
+    #[getset(get = "pub(crate)")]
+    plugins: Arc<Plugins>,
+    /// The database users minted on a read of `database/creds/<role>`
+    #[getset(get = "pub(crate)")]
+    database: Arc<DatabaseRoles>,
+    /// How SSH certificates are signed
+    #[getset(get_copy = "pub(crate)")]
+    ssh: SshSettings,
+    /// How X.509 certificates are issued
+    #[getset(get_copy = "pub(crate)")]
+    pki: PkiSettings,
+    /// The commands and webhooks run after values change
+    #[getset(get = "pub(crate)")]
+    hooks: Arc<Hooks>,
+    /// What values must look like before they are stored
+    #[getset(get = "pub(crate)")]
+    rules: Arc<Rules>,
+}
+
+impl From<&ConfigSalusd> for Limits {
+    fn from(config: &ConfigSalusd) -> Self {
+        Self {
+            key_timeout: config.key_timeout(),
+            max_random_bytes: config.max_random_bytes(),
+            max_message_bytes: config.max_message_bytes(),
+            max_value_bytes: config.max_value_bytes(),
+            keepalive: *config.keepalive(),
+            namespaces: Arc::new(Namespaces::from(config)),
+            plugins: Arc::new(Plugins::from(config)),
+            database: Arc::new(DatabaseRoles::from(config)),
+            ssh: *config.ssh(),
+            pki: *config.pki(),
+            hooks: Arc::new(Hooks::from(config)),
+            rules: Arc::new(Rules::from(config)),
+        }
+    }
+}
+
+/// Reloads the daemon's configuration, on `SIGHUP` or `Action::ReloadConfig`
+pub(crate) struct Reloader {
+    load: Load,
+    /// The configuration the daemon is running
+    running: Mutex<ConfigSalusd>,
+    limits: watch::Sender<Limits>,
+    /// The log filters, when tracing was set up by the daemon
+    tracing: Option<TracingReload>,
+}
+
+impl Reloader {
+    /// A reloader for a daemon running `config`, which `load` reads again.
+    pub(crate) fn new(config: ConfigSalusd, load: Load, tracing: Option<TracingReload>) -> Self {
+        let (limits, _unwatched) = watch::channel(Limits::from(&config));
+        Self {
+            load,
+            running: Mutex::new(config),
+            limits,
+            tracing,
+        }
+    }
+
+    /// The limits new connections are served with, kept current across
+    /// reloads.
+    pub(crate) fn limits(&self) -> watch::Receiver<Limits> {
+        self.limits.subscribe()
+    }
+
+    /// Load the configuration again and take up what changed, or nothing at
+    /// all when a change needs a restart.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the configuration cannot be loaded, or its
+    ///   tracing directives cannot be applied.
+    pub(crate) fn reload(&self) -> Result<ConfigReload> {
+        let config = (self.load)()?;
+        let mut running = lock(&self.running);
+        let needs_restart = needs_restart(&running, &config);
+        if !needs_restart.is_empty() {
+            warn!(
+                ?needs_restart,
+                "Kept the running configuration: these settings only change on a restart"
+            );
+            return Ok(ConfigReload::builder()
+                .needs_restart(names(&needs_restart))
+                .build());
+        }
+        let applied = reloadable(&running, &config);
+        if applied.iter().any(|name| LOGGING.contains(name))
+            && let Some(tracing) = &self.tracing
+        {
+            tracing.reload(&config)?;
+        }
+        let _old = self.limits.send_replace(Limits::from(&config));
+        *running = config;
+        info!(?applied, "Reloaded the configuration");
+        Ok(ConfigReload::builder().applied(names(&applied)).build())
+    }
+}
+
+/// The reloadable settings that change how the daemon logs.
+const LOGGING: [&str; 3] = ["verbose", "quiet", "tracing.directives"];
+
+/// The settings a reload takes up that differ between `running` and
+/// `config`.
+fn reloadable(running: &ConfigSalusd, config: &ConfigSalusd) -> Vec<&'static str> {
+    differing([
+        ("key_timeout", running.key_timeout != config.key_timeout),
+        (
+            "max_random_bytes",
+            running.max_random_bytes != config.max_random_bytes,
+        ),
+        (
+            "max_message_bytes",
+            running.max_message_bytes != config.max_message_bytes,
+        ),
+        (
+            "max_value_bytes",
+            running.max_value_bytes != config.max_value_bytes,
+        ),
+        ("keepalive", running.keepalive != config.keepalive),
+        ("namespace", running.namespace != config.namespace),
+        ("plugins", running.plugins != config.plugins),
+        (
+            "database_roles",
+            running.database_roles != config.database_roles,
+        ),
+        ("ssh", running.ssh != config.ssh),
+        ("pki", running.pki != config.pki),
+        ("hooks", running.hooks != config.hooks),
+        ("validation", running.validation != config.validation),
+        ("verbose", running.verbose != config.verbose),
+        ("quiet", running.quiet != config.quiet),
+        (
+            "tracing.directives",
+            running.tracing.directives != config.tracing.directives,
+        ),
+    ])
+}
+
+/// The settings only read as the daemon starts that differ between `running`
+/// and `config`.
+fn needs_restart(running: &ConfigSalusd, config: &ConfigSalusd) -> Vec<&'static str> {
+    differing([
+        (
+            "enable_std_output",
+            running.enable_std_output != config.enable_std_output,
+        ),
+        ("read_only", running.read_only != config.read_only),
+        (
+            "harden_process",
+            running.harden_process != config.harden_process,
+        ),
+        ("socket_path", running.socket_path != config.socket_path),
+        (
+            "json_socket_path",
+            running.json_socket_path != config.json_socket_path,
+        ),
+        (
+            "transport_key_path",
+            running.transport_key_path != config.transport_key_path,
+        ),
+        (
+            "tracing",
+            format_only(&running.tracing) != format_only(&config.tracing),
+        ),
+        ("shares", running.shares != config.shares),
+        ("storage", running.storage != config.storage),
+        ("read_cache", running.read_cache != config.read_cache),
+        ("streaming", running.streaming != config.streaming),
+        ("compression", running.compression != config.compression),
+        ("history", running.history != config.history),
+        ("cluster", running.cluster != config.cluster),
+        ("listeners", running.listeners != config.listeners),
+    ])
+}
+
+/// `tracing` without its directives, which can change while the daemon runs.
+fn format_only(tracing: &Tracing) -> Tracing {
+    Tracing {
+        directives: None,
+        ..tracing.clone()
+    }
+}
+
+fn differing<const N: usize>(settings: [(&'static str, bool); N]) -> Vec<&'static str> {
+    settings
+        .into_iter()
+        .filter_map(|(name, differs)| differs.then_some(name))
+        .collect()
+}
+
+fn names(settings: &[&str]) -> Vec<String> {
+    settings.iter().map(ToString::to_string).collect()
+}
+
+fn lock(running: &Mutex<ConfigSalusd>) -> MutexGuard<'_, ConfigSalusd> {
+    match running.lock() {
+        Ok(running) => running,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::{Result, anyhow};
+
+    use super::{Limits, Reloader, lock};
+    use crate::config::{ConfigSalusd, Storage, Tracing};
+
+    /// A reloader whose configuration "file" is `on_disk`.
+    fn reloader(on_disk: &Arc<Mutex<ConfigSalusd>>) -> Reloader {
+        let source = on_disk.clone();
+        Reloader::new(
+            ConfigSalusd::default(),
+            Box::new(move || Ok(lock(&source).clone())),
+            None,
+        )
+    }
+
+    #[test]
+    fn a_reload_takes_up_changed_limits() -> Result<()> {
+        let on_disk = Arc::new(Mutex::new(ConfigSalusd::default()));
+        let reloader = reloader(&on_disk);
+        let limits = reloader.limits();
+        *lock(&on_disk) = ConfigSalusd {
+            key_timeout: 60,
+            max_random_bytes: 16,
+            tracing: Tracing {
+                directives: Some("salusd=debug".to_string()),
+                ..Tracing::default()
+            },
+            ..ConfigSalusd::default()
+        };
+        let report = reloader.reload()?;
+        assert_eq!(
+            report.applied(),
+            &["key_timeout", "max_random_bytes", "tracing.directives"]
+        );
+        assert!(report.needs_restart().is_empty());
+        assert_eq!(limits.borrow().key_timeout(), 60);
+        assert_eq!(limits.borrow().max_random_bytes(), 16);
+        assert_eq!(*limits.borrow(), Limits::from(&*lock(&on_disk)));
+        assert!(reloader.reload()?.applied().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn a_change_that_needs_a_restart_refuses_the_whole_reload() -> Result<()> {
+        let on_disk = Arc::new(Mutex::new(ConfigSalusd::default()));
+        let reloader = reloader(&on_disk);
+        let limits = reloader.limits();
+        *lock(&on_disk) = ConfigSalusd {
+            key_timeout: 60,
+            socket_path: Some("/tmp/elsewhere.sock".to_string()),
+            storage: Storage {
+                commit_window_ms: 5,
+                ..Storage::default()
+            },
+            ..ConfigSalusd::default()
+        };
+        let report = reloader.reload()?;
+        assert!(report.applied().is_empty());
+        assert_eq!(report.needs_restart(), &["socket_path", "storage"]);
+        assert_eq!(*limits.borrow(), Limits::from(&ConfigSalusd::default()));
+        Ok(())
+    }
+
+    #[test]
+    fn a_configuration_that_fails_to_load_changes_nothing() {
+        let reloader = Reloader::new(
+            ConfigSalusd::default(),
+            Box::new(|| Err(anyhow!("unable to deserialize configuration"))),
+            None,
+        );
+        let limits = reloader.limits();
+        assert!(reloader.reload().is_err());
+        assert_eq!(*limits.borrow(), Limits::from(&ConfigSalusd::default()));
+    }
+}
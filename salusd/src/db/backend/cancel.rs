@@ -0,0 +1,126 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Cancelling a store call part way through.
+//!
+//! A store call runs on one blocking thread from start to finish, so the
+//! request it serves can be cancelled by raising a flag that every backend
+//! call on that thread checks first, while [`cancellable`] is watching it. The
+//! call then fails with [`Error::Cancelled`] at its next read or write, and a
+//! commit it had not reached is never applied: each commit lands whole or not
+//! at all.
+
+use std::{
+    cell::RefCell,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::Result;
+
+use super::{StorageBackend, Table, WriteOp};
+use crate::error::Error;
+
+/// Raised to cancel the request whose store calls watch it.
+pub(crate) type CancelFlag = Arc<AtomicBool>;
+
+thread_local! {
+    /// The flag of the request this thread is running a store call for.
+    static WATCHED: RefCell<Option<CancelFlag>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, failing its backend calls once `flag` is raised.
+pub(crate) fn cancellable<T>(flag: &CancelFlag, f: impl FnOnce() -> T) -> T {
+    let outer = WATCHED.replace(Some(flag.clone()));
+    let value = f();
+    let _flag = WATCHED.replace(outer);
+    value
+}
+
+/// A backend whose calls fail once the calling thread's request is cancelled.
+pub(crate) struct Cancellable<'a>(pub(crate) &'a dyn StorageBackend);
+
+impl Cancellable<'_> {
+    fn check() -> Result<()> {
+        let cancelled = WATCHED.with_borrow(|flag| {
+            flag.as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Acquire))
+        });
+        if cancelled {
+            Err(Error::Cancelled.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl StorageBackend for Cancellable<'_> {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        Self::check()?;
+        self.0.get(table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        Self::check()?;
+        self.0.scan(table, prefix)
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        Self::check()?;
+        self.0.keys(table, prefix)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        Self::check()?;
+        self.0.commit(ops)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+
+    use anyhow::{Result, bail};
+
+    use super::{CancelFlag, Cancellable, cancellable};
+    use crate::{
+        db::backend::{MemoryBackend, StorageBackend as _, Table, WriteOp},
+        error::Error,
+    };
+
+    fn put(key: &str) -> Vec<WriteOp> {
+        vec![WriteOp::Put {
+            table: Table::Values,
+            key: key.to_string(),
+            value: vec![1],
+        }]
+    }
+
+    #[test]
+    fn a_raised_flag_stops_the_next_commit() -> Result<()> {
+        let backend = MemoryBackend::default();
+        let cancellable_backend = Cancellable(&backend);
+        let flag = CancelFlag::default();
+        cancellable(&flag, || -> Result<()> {
+            cancellable_backend.commit(put("before"))?;
+            flag.store(true, Ordering::Release);
+            match cancellable_backend.commit(put("after")) {
+                Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::Cancelled)) => Ok(()),
+                other => bail!("expected the commit to be cancelled, got {other:?}"),
+            }
+        })?;
+        assert!(backend.get(Table::Values, "before")?.is_some());
+        assert!(backend.get(Table::Values, "after")?.is_none());
+        // Outside `cancellable`, nothing is watched.
+        cancellable_backend.commit(put("after"))?;
+        assert!(backend.get(Table::Values, "after")?.is_some());
+        Ok(())
+    }
+}
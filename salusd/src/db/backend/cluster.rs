@@ -0,0 +1,92 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+use openraft::error::{ClientWriteError, RaftError};
+use tokio::{runtime::Handle, task::block_in_place};
+
+use super::{RedbBackend, StorageBackend, Table, WriteOp};
+use crate::{
+    cluster::{Raft, leader_name},
+    error::Error,
+};
+
+/// A node of a cluster: reads come from its own database file, and commits go
+/// through the cluster's Raft log.
+///
+/// A commit returns once the leader has applied it, which is after a majority
+/// of the cluster has logged it. Only the leader can commit; elsewhere a
+/// commit fails with [`Error::NotClusterLeader`], naming the leader.
+pub(crate) struct ClusterBackend {
+    local: Arc<RedbBackend>,
+    raft: Raft,
+    runtime: Handle,
+}
+
+impl ClusterBackend {
+    /// The node running `raft`, whose state machine applies to `local`. Must
+    /// be called from within the daemon's runtime.
+    pub(crate) fn new(local: Arc<RedbBackend>, raft: Raft) -> Self {
+        Self {
+            local,
+            raft,
+            runtime: Handle::current(),
+        }
+    }
+}
+
+impl fmt::Debug for ClusterBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterBackend")
+            .field("local", &self.local)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StorageBackend for ClusterBackend {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        self.local.get(table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.local.scan(table, prefix)
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        self.local.keys(table, prefix)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        // The store is synchronous, and is called from the runtime's worker
+        // threads; the worker steps aside while the write is replicated.
+        let write = self.raft.client_write(ops);
+        let written = if Handle::try_current().is_ok() {
+            block_in_place(|| self.runtime.block_on(write).map_err(Box::new))
+        } else {
+            self.runtime.block_on(write).map_err(Box::new)
+        };
+        let Err(e) = written else {
+            return Ok(());
+        };
+        match *e {
+            RaftError::APIError(ClientWriteError::ForwardToLeader(forward)) => {
+                Err(Error::NotClusterLeader(leader_name(
+                    forward.leader_id,
+                    forward.leader_node.as_ref(),
+                ))
+                .into())
+            }
+            e => Err(e.into()),
+        }
+    }
+}
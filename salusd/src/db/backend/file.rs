@@ -0,0 +1,539 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{borrow::Borrow, path::Path};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use redb::{
+    Database, DatabaseError, Key, ReadTransaction, ReadableDatabase as _, ReadableTable as _,
+    ReadableTableMetadata as _, TableDefinition, TableError, Value, WriteTransaction,
+};
+use tracing::info;
+
+use super::{StorageBackend, Table, WriteOp};
+use crate::{
+    db::values::{config::ConfigVal, salus::SalusVal},
+    error::Error,
+};
+
+const CONFIG: TableDefinition<'_, &str, ConfigVal> = TableDefinition::new(Table::Config.name());
+const VALUES: TableDefinition<'_, String, SalusVal> = TableDefinition::new(Table::Values.name());
+const SIGNING_KEYS: TableDefinition<'_, String, SalusVal> =
+    TableDefinition::new(Table::SigningKeys.name());
+const SIGNING_USES: TableDefinition<'_, String, u64> =
+    TableDefinition::new(Table::SigningUses.name());
+const NAMED_KEYS: TableDefinition<'_, String, SalusVal> =
+    TableDefinition::new(Table::NamedKeys.name());
+const BLOBS: TableDefinition<'_, String, SalusVal> = TableDefinition::new(Table::Blobs.name());
+const BLOB_REFS: TableDefinition<'_, String, [u8; 24]> =
+    TableDefinition::new(Table::BlobRefs.name());
+const WRITTEN: TableDefinition<'_, String, u64> = TableDefinition::new(Table::Written.name());
+const ALIASES: TableDefinition<'_, String, String> = TableDefinition::new(Table::Aliases.name());
+const LEASES: TableDefinition<'_, String, String> = TableDefinition::new(Table::Leases.name());
+const PKI_CA: TableDefinition<'_, String, SalusVal> = TableDefinition::new(Table::PkiCa.name());
+const PKI_CERTS: TableDefinition<'_, String, String> = TableDefinition::new(Table::PkiCerts.name());
+const VERSIONS: TableDefinition<'_, String, u64> = TableDefinition::new(Table::Versions.name());
+const ROTATE_AFTER: TableDefinition<'_, String, u64> =
+    TableDefinition::new(Table::RotateAfter.name());
+const APPROVALS: TableDefinition<'_, String, String> =
+    TableDefinition::new(Table::Approvals.name());
+const HISTORY: TableDefinition<'_, String, &[u8]> = TableDefinition::new(Table::History.name());
+const AUTHORS: TableDefinition<'_, String, u32> = TableDefinition::new(Table::Authors.name());
+/// The keys of `salus_store`, without their values, so listing and searching
+/// keys reads only keys. Not a [`Table`]: it is rebuilt from `salus_store`
+/// rather than copied.
+const KEY_INDEX: TableDefinition<'_, &str, ()> = TableDefinition::new("salus_key_index");
+
+/// The default backend: a redb database file.
+///
+/// Each [`Table`] is a redb table of the same name, keyed and typed as it
+/// always has been, so an existing database file reads unchanged. Beside them,
+/// a key index of `salus_store` is kept in step within every write
+/// transaction.
+#[derive(Debug)]
+pub(crate) struct RedbBackend {
+    db: Database,
+}
+
+impl RedbBackend {
+    /// Open (creating if needed) the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DatabaseLocked`] when another process (most likely
+    /// another `salusd`) holds the database open.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let db = lock_contention(path, Database::create(path))?;
+        let backend = Self { db };
+        backend.index_keys()?;
+        Ok(backend)
+    }
+
+    /// The keys of `table` starting with `prefix`, read from the table itself.
+    fn scan_keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .scan(table, prefix)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Rebuild the key index if it does not match `salus_store`: a database
+    /// from before the index, or one a salusd without it has since written.
+    fn index_keys(&self) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        let (stored, indexed) = {
+            let values = txn.open_table(VALUES)?;
+            let index = txn.open_table(KEY_INDEX)?;
+            let stored = values
+                .iter()?
+                .map(|row| Ok(row?.0.value()))
+                .collect::<Result<Vec<_>>>()?;
+            let indexed = index
+                .iter()?
+                .map(|row| Ok(row?.0.value().to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            (stored, indexed)
+        };
+        if stored == indexed {
+            txn.abort()?;
+            return Ok(());
+        }
+        {
+            let mut index = txn.open_table(KEY_INDEX)?;
+            index.retain(|_, ()| false)?;
+            for key in &stored {
+                let _old = index.insert(key.as_str(), ())?;
+            }
+        }
+        txn.commit()?;
+        info!("Indexed {} stored keys", stored.len());
+        Ok(())
+    }
+
+    /// Open the existing database at `path` without creating one, for offline
+    /// reads.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DatabaseOpen`] when there is no usable database at
+    /// `path`.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let db = lock_contention(path, Database::open(path))
+            .with_context(|| Error::DatabaseOpen(path.to_path_buf()))?;
+        Ok(Self { db })
+    }
+}
+
+impl StorageBackend for RedbBackend {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        let txn = self.db.begin_read()?;
+        match table {
+            Table::Config => get_row(&txn, CONFIG, key),
+            Table::Values => get_row(&txn, VALUES, key.to_string()),
+            Table::SigningKeys => get_row(&txn, SIGNING_KEYS, key.to_string()),
+            Table::SigningUses => get_row(&txn, SIGNING_USES, key.to_string()),
+            Table::NamedKeys => get_row(&txn, NAMED_KEYS, key.to_string()),
+            Table::Blobs => get_row(&txn, BLOBS, key.to_string()),
+            Table::BlobRefs => get_row(&txn, BLOB_REFS, key.to_string()),
+            Table::Written => get_row(&txn, WRITTEN, key.to_string()),
+            Table::Aliases => get_row(&txn, ALIASES, key.to_string()),
+            Table::Leases => get_row(&txn, LEASES, key.to_string()),
+            Table::PkiCa => get_row(&txn, PKI_CA, key.to_string()),
+            Table::PkiCerts => get_row(&txn, PKI_CERTS, key.to_string()),
+            Table::Versions => get_row(&txn, VERSIONS, key.to_string()),
+            Table::RotateAfter => get_row(&txn, ROTATE_AFTER, key.to_string()),
+            Table::Approvals => get_row(&txn, APPROVALS, key.to_string()),
+            Table::History => get_row(&txn, HISTORY, key.to_string()),
+            Table::Authors => get_row(&txn, AUTHORS, key.to_string()),
+        }
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let txn = self.db.begin_read()?;
+        match table {
+            Table::Config => scan_rows(&txn, CONFIG, prefix, prefix),
+            Table::Values => scan_rows(&txn, VALUES, prefix.to_string(), prefix),
+            Table::SigningKeys => scan_rows(&txn, SIGNING_KEYS, prefix.to_string(), prefix),
+            Table::SigningUses => scan_rows(&txn, SIGNING_USES, prefix.to_string(), prefix),
+            Table::NamedKeys => scan_rows(&txn, NAMED_KEYS, prefix.to_string(), prefix),
+            Table::Blobs => scan_rows(&txn, BLOBS, prefix.to_string(), prefix),
+            Table::BlobRefs => scan_rows(&txn, BLOB_REFS, prefix.to_string(), prefix),
+            Table::Written => scan_rows(&txn, WRITTEN, prefix.to_string(), prefix),
+            Table::Aliases => scan_rows(&txn, ALIASES, prefix.to_string(), prefix),
+            Table::Leases => scan_rows(&txn, LEASES, prefix.to_string(), prefix),
+            Table::PkiCa => scan_rows(&txn, PKI_CA, prefix.to_string(), prefix),
+            Table::PkiCerts => scan_rows(&txn, PKI_CERTS, prefix.to_string(), prefix),
+            Table::Versions => scan_rows(&txn, VERSIONS, prefix.to_string(), prefix),
+            Table::RotateAfter => scan_rows(&txn, ROTATE_AFTER, prefix.to_string(), prefix),
+            Table::Approvals => scan_rows(&txn, APPROVALS, prefix.to_string(), prefix),
+            Table::History => scan_rows(&txn, HISTORY, prefix.to_string(), prefix),
+            Table::Authors => scan_rows(&txn, AUTHORS, prefix.to_string(), prefix),
+        }
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        if table != Table::Values {
+            return self.scan_keys(table, prefix);
+        }
+        let txn = self.db.begin_read()?;
+        let (index, values) = match (txn.open_table(KEY_INDEX), txn.open_table(VALUES)) {
+            (Ok(index), Ok(values)) => (index, values),
+            (_, Err(e)) => return missing_table(e),
+            // Opened without `create`, before the index was built.
+            (Err(_), Ok(_)) => return self.scan_keys(table, prefix),
+        };
+        if index.len()? != values.len()? {
+            return self.scan_keys(table, prefix);
+        }
+        let mut keys = vec![];
+        for row in index.range(prefix..)? {
+            let (key, _) = row.with_context(|| Error::TableIterRead)?;
+            let key = key.value();
+            if !key.starts_with(prefix) {
+                break;
+            }
+            keys.push(key.to_string());
+        }
+        Ok(keys)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        // redb runs one write transaction at a time, and read transactions
+        // alongside it.
+        let txn = self.db.begin_write()?;
+        for op in ops {
+            index_key(&txn, &op)?;
+            match op {
+                WriteOp::Put { table, key, value } => match table {
+                    Table::Config => put_row(&txn, CONFIG, key.as_str(), &value)?,
+                    Table::Values => put_row(&txn, VALUES, key, &value)?,
+                    Table::SigningKeys => put_row(&txn, SIGNING_KEYS, key, &value)?,
+                    Table::SigningUses => put_row(&txn, SIGNING_USES, key, &value)?,
+                    Table::NamedKeys => put_row(&txn, NAMED_KEYS, key, &value)?,
+                    Table::Blobs => put_row(&txn, BLOBS, key, &value)?,
+                    Table::BlobRefs => put_row(&txn, BLOB_REFS, key, &value)?,
+                    Table::Written => put_row(&txn, WRITTEN, key, &value)?,
+                    Table::Aliases => put_row(&txn, ALIASES, key, &value)?,
+                    Table::Leases => put_row(&txn, LEASES, key, &value)?,
+                    Table::PkiCa => put_row(&txn, PKI_CA, key, &value)?,
+                    Table::PkiCerts => put_row(&txn, PKI_CERTS, key, &value)?,
+                    Table::Versions => put_row(&txn, VERSIONS, key, &value)?,
+                    Table::RotateAfter => put_row(&txn, ROTATE_AFTER, key, &value)?,
+                    Table::Approvals => put_row(&txn, APPROVALS, key, &value)?,
+                    Table::History => put_row(&txn, HISTORY, key, &value)?,
+                    Table::Authors => put_row(&txn, AUTHORS, key, &value)?,
+                },
+                WriteOp::Delete { table, key } => match table {
+                    Table::Config => delete_row(&txn, CONFIG, key.as_str())?,
+                    Table::Values => delete_row(&txn, VALUES, key)?,
+                    Table::SigningKeys => delete_row(&txn, SIGNING_KEYS, key)?,
+                    Table::SigningUses => delete_row(&txn, SIGNING_USES, key)?,
+                    Table::NamedKeys => delete_row(&txn, NAMED_KEYS, key)?,
+                    Table::Blobs => delete_row(&txn, BLOBS, key)?,
+                    Table::BlobRefs => delete_row(&txn, BLOB_REFS, key)?,
+                    Table::Written => delete_row(&txn, WRITTEN, key)?,
+                    Table::Aliases => delete_row(&txn, ALIASES, key)?,
+                    Table::Leases => delete_row(&txn, LEASES, key)?,
+                    Table::PkiCa => delete_row(&txn, PKI_CA, key)?,
+                    Table::PkiCerts => delete_row(&txn, PKI_CERTS, key)?,
+                    Table::Versions => delete_row(&txn, VERSIONS, key)?,
+                    Table::RotateAfter => delete_row(&txn, ROTATE_AFTER, key)?,
+                    Table::Approvals => delete_row(&txn, APPROVALS, key)?,
+                    Table::History => delete_row(&txn, HISTORY, key)?,
+                    Table::Authors => delete_row(&txn, AUTHORS, key)?,
+                },
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Keep the key index in step with `op`, in the same transaction.
+fn index_key(txn: &WriteTransaction, op: &WriteOp) -> Result<()> {
+    match op {
+        WriteOp::Put {
+            table: Table::Values,
+            key,
+            ..
+        } => {
+            let mut index = txn.open_table(KEY_INDEX)?;
+            let _old = index.insert(key.as_str(), ())?;
+        }
+        WriteOp::Delete {
+            table: Table::Values,
+            key,
+        } => {
+            let mut index = txn.open_table(KEY_INDEX)?;
+            let _old = index.remove(key.as_str())?;
+        }
+        WriteOp::Put { .. } | WriteOp::Delete { .. } => {}
+    }
+    Ok(())
+}
+
+/// The bytes of the row under `key`; a table never written has no rows.
+fn get_row<'k, K, V>(
+    txn: &ReadTransaction,
+    def: TableDefinition<'_, K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+) -> Result<Option<Vec<u8>>>
+where
+    K: Key + 'static,
+    V: Value + 'static,
+{
+    let table = match txn.open_table(def) {
+        Ok(table) => table,
+        Err(e) => return missing_table(e),
+    };
+    Ok(table
+        .get(key)?
+        .map(|row| V::as_bytes(&row.value()).as_ref().to_vec()))
+}
+
+/// Every row from `start` on whose key starts with `prefix`.
+fn scan_rows<'k, K, V, KR>(
+    txn: &ReadTransaction,
+    def: TableDefinition<'_, K, V>,
+    start: KR,
+    prefix: &str,
+) -> Result<Vec<(String, Vec<u8>)>>
+where
+    K: Key + 'static,
+    V: Value + 'static,
+    KR: Borrow<K::SelfType<'k>> + 'k,
+{
+    let table = match txn.open_table(def) {
+        Ok(table) => table,
+        Err(e) => return missing_table(e),
+    };
+    let mut rows = vec![];
+    for row in table.range(start..)? {
+        let (key, value) = row.with_context(|| Error::TableIterRead)?;
+        let key = String::from_utf8(K::as_bytes(&key.value()).as_ref().to_vec())?;
+        if !key.starts_with(prefix) {
+            break;
+        }
+        rows.push((key, V::as_bytes(&value.value()).as_ref().to_vec()));
+    }
+    Ok(rows)
+}
+
+/// Insert `value`'s bytes as a `V` under `key`.
+fn put_row<'k, K, V>(
+    txn: &WriteTransaction,
+    def: TableDefinition<'_, K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: &[u8],
+) -> Result<()>
+where
+    K: Key + 'static,
+    V: Value + 'static,
+{
+    // redb decodes fixed-width values without checking their length.
+    if V::fixed_width().is_some_and(|width| width != value.len()) {
+        bail!(
+            "a {} row is {} bytes, not {}",
+            V::type_name().name(),
+            V::fixed_width().unwrap_or_default(),
+            value.len()
+        );
+    }
+    let mut table = txn.open_table(def)?;
+    let _old = table.insert(key, V::from_bytes(value))?;
+    Ok(())
+}
+
+/// Remove the row under `key`, if there is one.
+fn delete_row<'k, K, V>(
+    txn: &WriteTransaction,
+    def: TableDefinition<'_, K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+) -> Result<()>
+where
+    K: Key + 'static,
+    V: Value + 'static,
+{
+    let mut table = txn.open_table(def)?;
+    let _old = table.remove(key)?;
+    Ok(())
+}
+
+/// A table that was never written reads as empty; any other failure to open
+/// one is an error.
+fn missing_table<T: Default>(error: TableError) -> Result<T> {
+    if let TableError::TableDoesNotExist(_) = error {
+        Ok(T::default())
+    } else {
+        Err(anyhow!(error))
+    }
+}
+
+/// Map redb's lock-contention error from opening `path` into
+/// [`Error::DatabaseLocked`].
+fn lock_contention(path: &Path, opened: Result<Database, DatabaseError>) -> Result<Database> {
+    match opened {
+        Ok(db) => Ok(db),
+        Err(DatabaseError::DatabaseAlreadyOpen) => {
+            Err(anyhow::Error::new(DatabaseError::DatabaseAlreadyOpen))
+                .with_context(|| Error::DatabaseLocked(path.to_path_buf()))
+        }
+        // Known variants listed explicitly to satisfy `non_exhaustive_omitted_patterns`;
+        // the trailing `Err(other)` forwards any future `#[non_exhaustive]` variant.
+        Err(
+            other @ (DatabaseError::RepairAborted
+            | DatabaseError::UpgradeRequired(_)
+            | DatabaseError::TransactionInProgress
+            | DatabaseError::Storage(_))
+            | other,
+        ) => Err(anyhow::Error::new(other)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::PathBuf,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use anyhow::{Result, bail};
+    use redb::{ReadableDatabase as _, ReadableTableMetadata as _};
+
+    use super::{KEY_INDEX, RedbBackend};
+    use crate::{
+        db::backend::{StorageBackend as _, Table, WriteOp},
+        error::Error,
+    };
+
+    fn unique_db_path() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let name = format!("salusd-test-{}-{nanos}.redb", std::process::id());
+        std::env::temp_dir().join(name)
+    }
+
+    #[test]
+    fn create_reports_lock_contention() -> Result<()> {
+        let path = unique_db_path();
+        // Hold the database open so the second open contends for the lock.
+        let _first = RedbBackend::create(&path)?;
+
+        let result = RedbBackend::create(&path);
+        let cleanup = || {
+            drop(std::fs::remove_file(&path));
+        };
+
+        let Err(err) = result else {
+            cleanup();
+            bail!(
+                "expected lock contention error opening {} twice",
+                path.display()
+            );
+        };
+
+        let matched =
+            matches!(err.downcast_ref::<Error>(), Some(Error::DatabaseLocked(p)) if p == &path);
+        cleanup();
+        if !matched {
+            bail!(
+                "expected Error::DatabaseLocked({}), got: {err:?}",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rows_round_trip_through_typed_tables() -> Result<()> {
+        let path = unique_db_path();
+        let result = (|| -> Result<()> {
+            let backend = RedbBackend::create(&path)?;
+            assert!(backend.get(Table::Values, "db/pass")?.is_none());
+            assert!(backend.scan(Table::NamedKeys, "")?.is_empty());
+            let put = |table, key: &str, value: &[u8]| WriteOp::Put {
+                table,
+                key: key.to_string(),
+                value: value.to_vec(),
+            };
+            backend.commit(vec![
+                put(Table::Values, "db/pass", b"sealed"),
+                put(Table::Values, "db/user", b"sealed too"),
+                put(Table::Values, "web", b"other"),
+                put(Table::SigningUses, "release", &7u64.to_le_bytes()),
+            ])?;
+            let scanned = backend.scan(Table::Values, "db/")?;
+            assert_eq!(
+                scanned
+                    .iter()
+                    .map(|(key, _)| key.as_str())
+                    .collect::<Vec<_>>(),
+                ["db/pass", "db/user"]
+            );
+            assert_eq!(
+                backend.get(Table::SigningUses, "release")?,
+                Some(7u64.to_le_bytes().to_vec())
+            );
+            // A malformed fixed-width row is refused, and nothing in its batch
+            // lands.
+            assert!(
+                backend
+                    .commit(vec![
+                        put(Table::Values, "late", b"x"),
+                        put(Table::SigningUses, "release", b"short"),
+                    ])
+                    .is_err()
+            );
+            assert!(backend.get(Table::Values, "late")?.is_none());
+            Ok(())
+        })();
+        drop(std::fs::remove_file(&path));
+        result
+    }
+
+    #[test]
+    fn the_key_index_follows_writes_and_is_rebuilt_when_stale() -> Result<()> {
+        let path = unique_db_path();
+        let result = (|| -> Result<()> {
+            let put = |key: &str| WriteOp::Put {
+                table: Table::Values,
+                key: key.to_string(),
+                value: b"sealed".to_vec(),
+            };
+            {
+                let backend = RedbBackend::create(&path)?;
+                backend.commit(vec![put("app/a"), put("app/b"), put("web")])?;
+                backend.commit(vec![WriteOp::Delete {
+                    table: Table::Values,
+                    key: "app/a".to_string(),
+                }])?;
+                assert_eq!(backend.keys(Table::Values, "app/")?, ["app/b"]);
+                assert_eq!(backend.keys(Table::Values, "")?, ["app/b", "web"]);
+
+                // A write that skipped the index, as a salusd without one
+                // makes: the index no longer matches and is not trusted.
+                let txn = backend.db.begin_write()?;
+                {
+                    let mut index = txn.open_table(KEY_INDEX)?;
+                    let _old = index.remove("web")?;
+                }
+                txn.commit()?;
+                assert_eq!(backend.keys(Table::Values, "")?, ["app/b", "web"]);
+            }
+            let reopened = RedbBackend::create(&path)?;
+            let txn = reopened.db.begin_read()?;
+            assert_eq!(txn.open_table(KEY_INDEX)?.len()?, 2);
+            assert_eq!(reopened.keys(Table::Values, "w")?, ["web"]);
+            Ok(())
+        })();
+        drop(std::fs::remove_file(&path));
+        result
+    }
+}
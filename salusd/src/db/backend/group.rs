@@ -0,0 +1,271 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Group commit: writes that arrive close together share one transaction.
+//!
+//! The first commit to arrive leads. It waits out the window, takes every
+//! commit queued behind it, and applies them all at once, in arrival order.
+//! Each caller still returns only once its own writes have landed. If the
+//! shared transaction fails, each caller applies its own writes alone, so a
+//! bad write fails only the caller that made it.
+//!
+//! Callers never share a key within one group: a write holds its keys' locks
+//! (see [`crate::db::write_keys`]) until its commit returns.
+
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use super::{StorageBackend, Table, WriteOp};
+
+/// A backend whose commits are batched across callers.
+pub(crate) struct GroupCommit {
+    inner: Box<dyn StorageBackend>,
+    /// How long a leader waits for more commits before applying its group.
+    window: Duration,
+    queue: Mutex<Queue>,
+    /// Signalled when a group has been applied.
+    applied: Condvar,
+}
+
+/// The commits waiting for a leader, and the outcomes of applied groups.
+#[derive(Default)]
+struct Queue {
+    next_ticket: u64,
+    /// Whether a leader is waiting out its window.
+    leading: bool,
+    pending: Vec<(u64, Vec<WriteOp>)>,
+    /// Outcomes not yet collected by their callers, by ticket.
+    outcomes: BTreeMap<u64, Outcome>,
+}
+
+enum Outcome {
+    Committed,
+    /// The group failed: apply these writes alone.
+    Alone(Vec<WriteOp>),
+}
+
+impl GroupCommit {
+    /// Batch the commits to `inner` that arrive within `window` of each other.
+    pub(crate) fn new(inner: impl StorageBackend + 'static, window: Duration) -> Self {
+        Self {
+            inner: Box::new(inner),
+            window,
+            queue: Mutex::default(),
+            applied: Condvar::new(),
+        }
+    }
+
+    fn queue(&self) -> MutexGuard<'_, Queue> {
+        match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Wait for the group holding `ticket` to be applied.
+    fn follow(&self, mut queue: MutexGuard<'_, Queue>, ticket: u64) -> Result<()> {
+        loop {
+            if let Some(outcome) = queue.outcomes.remove(&ticket) {
+                drop(queue);
+                return match outcome {
+                    Outcome::Committed => Ok(()),
+                    Outcome::Alone(ops) => self.inner.commit(ops),
+                };
+            }
+            queue = match self.applied.wait(queue) {
+                Ok(queue) => queue,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+    }
+
+    /// Wait out the window, then apply everything queued so far.
+    fn lead(&self, ticket: u64) -> Result<()> {
+        thread::sleep(self.window);
+        let group = {
+            let mut queue = self.queue();
+            queue.leading = false;
+            mem::take(&mut queue.pending)
+        };
+        if let [(only, _)] = group.as_slice()
+            && *only == ticket
+        {
+            return self
+                .inner
+                .commit(group.into_iter().flat_map(|(_, ops)| ops).collect());
+        }
+        let combined = group
+            .iter()
+            .flat_map(|(_, ops)| ops.iter().cloned())
+            .collect();
+        let committed = match self.inner.commit(combined) {
+            Ok(()) => {
+                debug!("Applied {} commits in one transaction", group.len());
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "A group of {} commits failed, applying each alone: {e}",
+                    group.len()
+                );
+                false
+            }
+        };
+        let mut own = Vec::new();
+        {
+            let mut queue = self.queue();
+            for (queued, ops) in group {
+                if queued == ticket {
+                    own = ops;
+                } else {
+                    let outcome = if committed {
+                        Outcome::Committed
+                    } else {
+                        Outcome::Alone(ops)
+                    };
+                    let _prev = queue.outcomes.insert(queued, outcome);
+                }
+            }
+        }
+        self.applied.notify_all();
+        if committed {
+            Ok(())
+        } else {
+            self.inner.commit(own)
+        }
+    }
+}
+
+impl StorageBackend for GroupCommit {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.get(table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.inner.scan(table, prefix)
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        self.inner.keys(table, prefix)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        if self.window.is_zero() {
+            return self.inner.commit(ops);
+        }
+        let mut queue = self.queue();
+        let ticket = queue.next_ticket;
+        queue.next_ticket = ticket.wrapping_add(1);
+        queue.pending.push((ticket, ops));
+        if queue.leading {
+            return self.follow(queue, ticket);
+        }
+        queue.leading = true;
+        drop(queue);
+        self.lead(ticket)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{Result, anyhow, bail};
+
+    use super::GroupCommit;
+    use crate::db::backend::{MemoryBackend, StorageBackend, Table, WriteOp};
+
+    /// A memory backend that counts its commits and refuses any touching
+    /// the key `bad`.
+    #[derive(Default)]
+    struct Counting {
+        rows: MemoryBackend,
+        commits: Arc<AtomicUsize>,
+    }
+
+    impl StorageBackend for Counting {
+        fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+            self.rows.get(table, key)
+        }
+
+        fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+            self.rows.scan(table, prefix)
+        }
+
+        fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+            let _prev = self.commits.fetch_add(1, Ordering::SeqCst);
+            if ops
+                .iter()
+                .any(|op| matches!(op, WriteOp::Put { key, .. } if key == "bad"))
+            {
+                bail!("refused");
+            }
+            self.rows.commit(ops)
+        }
+    }
+
+    fn put(key: &str) -> Vec<WriteOp> {
+        vec![WriteOp::Put {
+            table: Table::Values,
+            key: key.to_string(),
+            value: key.as_bytes().to_vec(),
+        }]
+    }
+
+    /// Commit `keys` from a thread each, returning each commit's result.
+    fn commit_all(backend: &GroupCommit, keys: &[&str]) -> Result<Vec<bool>> {
+        thread::scope(|scope| {
+            let handles = keys
+                .iter()
+                .map(|key| scope.spawn(move || backend.commit(put(key)).is_ok()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().map_err(|_| anyhow!("a writer panicked")))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn writes_in_one_window_share_a_transaction() -> Result<()> {
+        let inner = Counting::default();
+        let commits = Arc::clone(&inner.commits);
+        let backend = GroupCommit::new(inner, Duration::from_millis(200));
+        let keys = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        assert!(commit_all(&backend, &keys)?.into_iter().all(|ok| ok));
+        assert!(commits.load(Ordering::SeqCst) < keys.len());
+        assert_eq!(backend.scan(Table::Values, "")?.len(), keys.len());
+        Ok(())
+    }
+
+    #[test]
+    fn a_bad_write_fails_only_its_own_commit() -> Result<()> {
+        let backend = GroupCommit::new(Counting::default(), Duration::from_millis(200));
+        let results = commit_all(&backend, &["a", "bad", "c"])?;
+        assert_eq!(results, [true, false, true]);
+        assert!(backend.get(Table::Values, "a")?.is_some());
+        assert!(backend.get(Table::Values, "bad")?.is_none());
+        assert!(backend.get(Table::Values, "c")?.is_some());
+        Ok(())
+    }
+}
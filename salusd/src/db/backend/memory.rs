@@ -0,0 +1,120 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    collections::BTreeMap,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use anyhow::Result;
+
+use super::{StorageBackend, Table, WriteOp};
+
+/// Every row, by table and key.
+type Rows = BTreeMap<(Table, String), Vec<u8>>;
+
+/// A backend that keeps every row in memory, so a store can be exercised, or
+/// read without changing it, without touching the filesystem.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryBackend {
+    rows: RwLock<Rows>,
+}
+
+impl MemoryBackend {
+    fn rows(&self) -> RwLockReadGuard<'_, Rows> {
+        match self.rows.read() {
+            Ok(rows) => rows,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn rows_mut(&self) -> RwLockWriteGuard<'_, Rows> {
+        match self.rows.write() {
+            Ok(rows) => rows,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Clone for MemoryBackend {
+    fn clone(&self) -> Self {
+        Self {
+            rows: RwLock::new(self.rows().clone()),
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.rows().get(&(table, key.to_string())).cloned())
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        Ok(self
+            .rows()
+            .range((table, prefix.to_string())..)
+            .take_while(|((row_table, key), _)| *row_table == table && key.starts_with(prefix))
+            .map(|((_, key), value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        let mut rows = self.rows_mut();
+        for op in ops {
+            match op {
+                WriteOp::Put { table, key, value } => {
+                    let _old = rows.insert((table, key), value);
+                }
+                WriteOp::Delete { table, key } => {
+                    let _old = rows.remove(&(table, key));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+
+    use super::MemoryBackend;
+    use crate::db::backend::{StorageBackend as _, Table, WriteOp};
+
+    #[test]
+    fn scans_stay_inside_their_table_and_prefix() -> Result<()> {
+        let backend = MemoryBackend::default();
+        let put = |table, key: &str| WriteOp::Put {
+            table,
+            key: key.to_string(),
+            value: key.as_bytes().to_vec(),
+        };
+        backend.commit(vec![
+            put(Table::Values, "db/user"),
+            put(Table::Values, "db/pass"),
+            put(Table::Values, "dc"),
+            put(Table::NamedKeys, "db/other"),
+        ])?;
+        let keys = |rows: Vec<(String, Vec<u8>)>| rows.into_iter().map(|(key, _)| key).collect();
+        let scanned: Vec<String> = keys(backend.scan(Table::Values, "db/")?);
+        assert_eq!(scanned, ["db/pass", "db/user"]);
+        assert_eq!(backend.scan(Table::Values, "")?.len(), 3);
+        assert!(backend.scan(Table::Config, "")?.is_empty());
+
+        backend.commit(vec![WriteOp::Delete {
+            table: Table::Values,
+            key: "db/pass".to_string(),
+        }])?;
+        assert!(backend.get(Table::Values, "db/pass")?.is_none());
+        assert_eq!(
+            backend.get(Table::Values, "db/user")?.as_deref(),
+            Some(&b"db/user"[..])
+        );
+        Ok(())
+    }
+}
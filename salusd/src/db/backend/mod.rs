@@ -0,0 +1,217 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Where the store's rows live.
+//!
+//! The store sees a handful of tables of string-keyed byte rows, read one at a
+//! time or by key prefix, and changed in atomic batches. Backends are shared:
+//! reads run alongside each other and alongside a commit, which each backend
+//! serializes itself. [`StorageBackend`] is
+//! that surface; [`RedbBackend`] (a redb database file) is the default,
+//! `ObjectStoreBackend` keeps the store in an S3-compatible bucket (with the
+//! `s3` feature), and [`MemoryBackend`] keeps everything in memory, for tests
+//! and for offline reads. [`GroupCommit`] wraps the database file's backend
+//! when `[storage] commit_window_ms` is set, so bursts of writes share
+//! transactions. `ClusterBackend` (with the `cluster` feature) reads a node's
+//! own database file and commits through the cluster's Raft log. [`Timed`]
+//! wraps whichever of them the store uses, timing its calls, and
+//! [`Cancellable`] wraps that, failing the calls of a cancelled request.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+#[cfg(feature = "cluster")]
+use serde::{Deserialize, Serialize};
+
+pub(crate) use self::cancel::{CancelFlag, Cancellable, cancellable};
+#[cfg(feature = "cluster")]
+pub(crate) use self::cluster::ClusterBackend;
+pub(crate) use self::file::RedbBackend;
+pub(crate) use self::group::GroupCommit;
+pub(crate) use self::memory::MemoryBackend;
+#[cfg(feature = "s3")]
+pub(crate) use self::object::ObjectStoreBackend;
+pub(crate) use self::timed::{Timed, storage_time};
+
+mod cancel;
+#[cfg(feature = "cluster")]
+mod cluster;
+mod file;
+mod group;
+mod memory;
+#[cfg(feature = "s3")]
+mod object;
+mod timed;
+
+/// A table of rows.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "cluster", derive(Deserialize, Serialize))]
+pub(crate) enum Table {
+    /// Store configuration: share parameters, digests, the wrapped key.
+    Config,
+    /// Sealed values, and the `CHECK_KEY` sentinel.
+    Values,
+    /// Sealed signing keys, by name.
+    SigningKeys,
+    /// How many times each signing key has been used.
+    SigningUses,
+    /// Sealed named-key keyrings, by name.
+    NamedKeys,
+    /// Sealed chunks of values stored in chunks, by chunk id and position.
+    Blobs,
+    /// The chunks each distinct streamed value is kept in, and how many values
+    /// share them, by content digest.
+    BlobRefs,
+    /// When each value was last written, by key.
+    Written,
+    /// The key each alias points at, by alias.
+    Aliases,
+    /// The credentials minted for a while, by lease id.
+    Leases,
+    /// The sealed X.509 CA.
+    PkiCa,
+    /// The certificates the CA issued, by serial number.
+    PkiCerts,
+    /// How many times each value has been written, by key.
+    Versions,
+    /// Seconds after each write a value is due to be rotated, by key.
+    RotateAfter,
+    /// The reads waiting on, or granted, approval, by approval id.
+    Approvals,
+    /// Earlier versions of values, kept for history, by key and version.
+    History,
+    /// Who wrote each value, by key.
+    Authors,
+}
+
+impl Table {
+    /// Every table.
+    pub(crate) const ALL: [Table; 17] = [
+        Table::Config,
+        Table::Values,
+        Table::SigningKeys,
+        Table::SigningUses,
+        Table::NamedKeys,
+        Table::Blobs,
+        Table::BlobRefs,
+        Table::Written,
+        Table::Aliases,
+        Table::Leases,
+        Table::PkiCa,
+        Table::PkiCerts,
+        Table::Versions,
+        Table::RotateAfter,
+        Table::Approvals,
+        Table::History,
+        Table::Authors,
+    ];
+
+    /// The table recorded as `name`.
+    #[cfg(feature = "s3")]
+    pub(crate) fn named(name: &str) -> Option<Table> {
+        Table::ALL.into_iter().find(|table| table.name() == name)
+    }
+
+    /// The table's name, as recorded in the database.
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Table::Config => "salus_config",
+            Table::Values => "salus_store",
+            Table::SigningKeys => "salus_signing_keys",
+            Table::SigningUses => "salus_signing_uses",
+            Table::NamedKeys => "salus_named_keys",
+            Table::Blobs => "salus_blobs",
+            Table::BlobRefs => "salus_blob_refs",
+            Table::Written => "salus_written",
+            Table::Aliases => "salus_aliases",
+            Table::Leases => "salus_leases",
+            Table::PkiCa => "salus_pki_ca",
+            Table::PkiCerts => "salus_pki_certs",
+            Table::Versions => "salus_versions",
+            Table::RotateAfter => "salus_rotate_after",
+            Table::Approvals => "salus_approvals",
+            Table::History => "salus_history",
+            Table::Authors => "salus_authors",
+        }
+    }
+}
+
+/// One change in a [`StorageBackend::commit`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "cluster", derive(Deserialize, Serialize))]
+pub(crate) enum WriteOp {
+    /// Insert or replace the row under `key`.
+    Put {
+        table: Table,
+        key: String,
+        value: Vec<u8>,
+    },
+    /// Remove the row under `key`, if there is one.
+    Delete { table: Table, key: String },
+}
+
+/// The persistence layer under a [`ShareStore`](crate::store::ShareStore).
+///
+/// A table that has never been written reads as empty rather than as an
+/// error. Rows are opaque bytes: sealing happens before they get here.
+pub(crate) trait StorageBackend: Send + Sync {
+    /// The row under `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be read.
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Every row whose key starts with `prefix`, in key order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be read.
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>>;
+
+    /// Every key starting with `prefix`, in key order, without the rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be read.
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .scan(table, prefix)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Apply `ops` in order, atomically: either all of them land or none do,
+    /// and a read sees all of them or none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot be written, in which case
+    /// nothing was.
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()>;
+}
+
+/// A backend shared with something else that reads or writes it directly.
+impl<B: StorageBackend + ?Sized> StorageBackend for Arc<B> {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        (**self).get(table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        (**self).scan(table, prefix)
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        (**self).keys(table, prefix)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        (**self).commit(ops)
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! An S3-compatible object-store backend, for stateless deployments.
+//!
+//! The whole store is one snapshot object under `<prefix>/snapshots/`, and
+//! `<prefix>/manifest` names the current one and its generation. Every row is
+//! already sealed before it gets here, so the bucket only ever holds
+//! ciphertext.
+//!
+//! Reads are served from a local copy loaded at open. A commit writes through:
+//! it uploads the next snapshot under a fresh name, then moves the manifest
+//! with a conditional put on the version it last saw. If another daemon moved
+//! the manifest first, the commit fails with [`Error::StorageConflict`] and the
+//! local copy is reloaded, so a retry applies on top of the other writer's
+//! changes. Once a commit lands, the snapshot two generations back is removed.
+
+use std::{
+    mem,
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard},
+    thread,
+};
+
+use anyhow::{Context as _, Result, anyhow};
+use aws_lc_rs::rand;
+use bincode_next::{config::standard, decode_from_slice, encode_to_vec};
+use object_store::{
+    ObjectStore, PutMode, PutPayload, UpdateVersion, aws::AmazonS3Builder, path::Path,
+};
+use tokio::runtime::{Builder, Runtime};
+use tracing::{info, warn};
+
+use super::{MemoryBackend, StorageBackend, Table, WriteOp};
+use crate::error::Error;
+
+/// The manifest object, under the prefix.
+const MANIFEST: &str = "manifest";
+/// The directory of snapshot objects, under the prefix.
+const SNAPSHOTS: &str = "snapshots";
+
+/// A snapshot's rows: table name, key, and sealed value.
+type Rows = Vec<(String, String, Vec<u8>)>;
+/// The manifest: the current generation and the name of its snapshot.
+type Manifest = (u64, String);
+
+/// A store kept in an object-store bucket.
+pub(crate) struct ObjectStoreBackend {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    /// Drives the object store's futures; see [`ObjectStoreBackend::block_on`].
+    runtime: Runtime,
+    /// The rows as of the current generation.
+    cache: RwLock<MemoryBackend>,
+    /// Where the local copy stands against the bucket, held for the whole of
+    /// a commit, so commits run one at a time while reads carry on.
+    state: Mutex<Generation>,
+}
+
+/// The bucket state the local copy was loaded from or written to.
+#[derive(Default)]
+struct Generation {
+    /// The generation the manifest named when it was last read or written; 0
+    /// before the first commit.
+    number: u64,
+    /// The snapshot `number` was loaded from or written to.
+    snapshot: Option<String>,
+    /// The snapshot before it, kept for readers that loaded the manifest just
+    /// before the last commit.
+    previous: Option<String>,
+    /// The manifest version a commit must find to succeed; `None` while there
+    /// is no manifest.
+    manifest: Option<UpdateVersion>,
+}
+
+impl ObjectStoreBackend {
+    /// Open the store at `url`, `s3://<bucket>[/<prefix>]`.
+    ///
+    /// Credentials, the region, and the endpoint of an S3-compatible service
+    /// come from the usual `AWS_*` environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` is not an `s3://` URL, or the bucket cannot
+    /// be read.
+    pub(crate) fn from_url(url: &str) -> Result<Self> {
+        let prefix = url
+            .strip_prefix("s3://")
+            .filter(|rest| !rest.is_empty())
+            .ok_or_else(|| Error::InvalidStorageUrl(url.to_string()))?
+            .split_once('/')
+            .map_or("", |(_bucket, prefix)| prefix);
+        let store = AmazonS3Builder::from_env()
+            .with_url(url)
+            .build()
+            .with_context(|| Error::InvalidStorageUrl(url.to_string()))?;
+        Self::new(Arc::new(store), Path::parse(prefix)?)
+    }
+
+    /// Open the store under `prefix` in `store`, loading its current snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest or snapshot cannot be read.
+    pub(crate) fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let backend = Self {
+            store,
+            prefix,
+            runtime,
+            cache: RwLock::default(),
+            state: Mutex::default(),
+        };
+        backend.reload(&mut backend.state())?;
+        Ok(backend)
+    }
+
+    /// Run `future` to completion on the backend's runtime.
+    ///
+    /// The store is called from the daemon's own runtime threads, where
+    /// blocking on another runtime is refused, so the future runs on a thread
+    /// of its own.
+    fn block_on<F>(&self, future: F) -> Result<F::Output>
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        thread::scope(|scope| scope.spawn(|| self.runtime.block_on(future)).join())
+            .map_err(|_| anyhow!("the object store request panicked"))
+    }
+
+    fn cache(&self) -> RwLockReadGuard<'_, MemoryBackend> {
+        match self.cache.read() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Swap in `rows` as the local copy.
+    fn replace_cache(&self, rows: MemoryBackend) {
+        let mut cache = match self.cache.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *cache = rows;
+    }
+
+    fn state(&self) -> MutexGuard<'_, Generation> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Replace the local copy with the bucket's current snapshot.
+    fn reload(&self, state: &mut Generation) -> Result<()> {
+        let manifest = self.prefix.child(MANIFEST);
+        let found = self.block_on(async {
+            let result = self.store.get(&manifest).await;
+            if let Err(object_store::Error::NotFound { .. }) = result {
+                return Ok(None);
+            }
+            let result = result?;
+            let version = UpdateVersion {
+                e_tag: result.meta.e_tag.clone(),
+                version: result.meta.version.clone(),
+            };
+            Ok::<_, object_store::Error>(Some((version, result.bytes().await?)))
+        })??;
+        let Some((version, bytes)) = found else {
+            self.replace_cache(MemoryBackend::default());
+            *state = Generation::default();
+            return Ok(());
+        };
+        let ((generation, snapshot), _): (Manifest, usize) = decode_from_slice(&bytes, standard())?;
+        let bytes = self.block_on(async {
+            self.store
+                .get(&self.snapshot_path(&snapshot))
+                .await?
+                .bytes()
+                .await
+        })??;
+        let (rows, _): (Rows, usize) = decode_from_slice(&bytes, standard())?;
+        let cache = MemoryBackend::default();
+        cache.commit(
+            rows.into_iter()
+                .map(|(table, key, value)| {
+                    let table = Table::named(&table)
+                        .ok_or_else(|| anyhow!("the snapshot has an unknown table '{table}'"))?;
+                    Ok(WriteOp::Put { table, key, value })
+                })
+                .collect::<Result<_>>()?,
+        )?;
+        self.replace_cache(cache);
+        *state = Generation {
+            number: generation,
+            snapshot: Some(snapshot),
+            previous: None,
+            manifest: Some(version),
+        };
+        Ok(())
+    }
+
+    fn snapshot_path(&self, snapshot: &str) -> Path {
+        self.prefix.child(SNAPSHOTS).child(snapshot)
+    }
+}
+
+impl StorageBackend for ObjectStoreBackend {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        self.cache().get(table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.cache().scan(table, prefix)
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        let mut state = self.state();
+        let next = self.cache().clone();
+        next.commit(ops)?;
+        let mut rows = Rows::new();
+        for table in Table::ALL {
+            rows.extend(
+                next.scan(table, "")?
+                    .into_iter()
+                    .map(|(key, value)| (table.name().to_string(), key, value)),
+            );
+        }
+        let generation = state.number.wrapping_add(1);
+        // A fresh name, so a racing writer's snapshot is never overwritten:
+        // only the manifest decides which one is current.
+        let mut nonce = [0u8; 8];
+        rand::fill(&mut nonce)?;
+        let snapshot = format!("{generation}-{:016x}", u64::from_be_bytes(nonce));
+        let manifest = encode_to_vec((generation, snapshot.as_str()), standard())?;
+        let mode = state
+            .manifest
+            .clone()
+            .map_or(PutMode::Create, PutMode::Update);
+        let snapshot_path = self.snapshot_path(&snapshot);
+        let manifest_path = self.prefix.child(MANIFEST);
+        let rows = encode_to_vec(&rows, standard())?;
+        let written = self.block_on(async {
+            let _snapshot = self
+                .store
+                .put(&snapshot_path, PutPayload::from(rows))
+                .await?;
+            self.store
+                .put_opts(&manifest_path, PutPayload::from(manifest), mode.into())
+                .await
+        })?;
+        if let Err(
+            object_store::Error::Precondition { .. } | object_store::Error::AlreadyExists { .. },
+        ) = written
+        {
+            warn!("Another writer changed the store first; reloading it");
+            self.reload(&mut state)?;
+            return Err(Error::StorageConflict.into());
+        }
+        let written = written?;
+        let current = state.snapshot.replace(snapshot);
+        let retired = mem::replace(&mut state.previous, current);
+        self.replace_cache(next);
+        state.number = generation;
+        state.manifest = Some(UpdateVersion {
+            e_tag: written.e_tag,
+            version: written.version,
+        });
+        if let Some(retired) = retired {
+            let retired = self.snapshot_path(&retired);
+            if let Err(e) = self.block_on(self.store.delete(&retired))? {
+                info!("Unable to remove the retired snapshot {retired}: {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use object_store::{ObjectStore, memory::InMemory, path::Path};
+
+    use super::ObjectStoreBackend;
+    use crate::{
+        db::backend::{StorageBackend as _, Table, WriteOp},
+        error::Error,
+    };
+
+    fn put(key: &str, value: &[u8]) -> Vec<WriteOp> {
+        vec![WriteOp::Put {
+            table: Table::Values,
+            key: key.to_string(),
+            value: value.to_vec(),
+        }]
+    }
+
+    #[test]
+    fn commits_persist_and_reopen() -> Result<()> {
+        let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let backend = ObjectStoreBackend::new(bucket.clone(), Path::from("salus"))?;
+        assert!(backend.get(Table::Values, "db/pass")?.is_none());
+        backend.commit(put("db/pass", b"sealed"))?;
+        backend.commit(put("db/user", b"sealed too"))?;
+        backend.commit(put("web", b"other"))?;
+
+        let reopened = ObjectStoreBackend::new(bucket, Path::from("salus"))?;
+        assert_eq!(reopened.scan(Table::Values, "db/")?.len(), 2);
+        assert_eq!(
+            reopened.get(Table::Values, "web")?.as_deref(),
+            Some(&b"other"[..])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_stale_writer_conflicts_then_catches_up() -> Result<()> {
+        let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let first = ObjectStoreBackend::new(bucket.clone(), Path::from("salus"))?;
+        let second = ObjectStoreBackend::new(bucket, Path::from("salus"))?;
+        first.commit(put("alpha", b"1"))?;
+
+        let conflict = second.commit(put("beta", b"2"));
+        assert!(matches!(
+            conflict.as_ref().map_err(|e| e.downcast_ref::<Error>()),
+            Err(Some(Error::StorageConflict))
+        ));
+        // The conflict reloaded the first writer's change, so a retry keeps it.
+        assert!(second.get(Table::Values, "alpha")?.is_some());
+        second.commit(put("beta", b"2"))?;
+        assert!(second.get(Table::Values, "alpha")?.is_some());
+        assert!(second.get(Table::Values, "beta")?.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The time a store call spends in its backend, for requests that ask the
+//! daemon to time them.
+//!
+//! A store call runs on one blocking thread from start to finish, so the time
+//! each backend call takes is added up per thread, while [`storage_time`] is
+//! counting.
+
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use super::{StorageBackend, Table, WriteOp};
+
+thread_local! {
+    /// The time this thread has spent in backends since [`storage_time`]
+    /// started counting, or `None` when it is not counting.
+    static SPENT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Run `f`, returning its result and the time it spent in backends.
+pub(crate) fn storage_time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let outer = SPENT.replace(Some(Duration::ZERO));
+    let value = f();
+    let spent = SPENT.get().unwrap_or_default();
+    // A call timed inside another counts toward both.
+    SPENT.set(outer.map(|outer| outer.saturating_add(spent)));
+    (value, spent)
+}
+
+/// A backend whose calls count toward the calling thread's [`storage_time`].
+pub(crate) struct Timed<'a>(pub(crate) &'a dyn StorageBackend);
+
+impl Timed<'_> {
+    fn time<T>(call: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let value = call();
+        if let Some(spent) = SPENT.get() {
+            SPENT.set(Some(spent.saturating_add(started.elapsed())));
+        }
+        value
+    }
+}
+
+impl StorageBackend for Timed<'_> {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        Self::time(|| self.0.get(table, key))
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        Self::time(|| self.0.scan(table, prefix))
+    }
+
+    fn keys(&self, table: Table, prefix: &str) -> Result<Vec<String>> {
+        Self::time(|| self.0.keys(table, prefix))
+    }
+
+    fn commit(&self, ops: Vec<WriteOp>) -> Result<()> {
+        Self::time(|| self.0.commit(ops))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread::sleep, time::Duration};
+
+    use anyhow::Result;
+
+    use super::{Timed, storage_time};
+    use crate::db::backend::{MemoryBackend, StorageBackend as _, Table};
+
+    #[test]
+    fn only_backend_calls_count() -> Result<()> {
+        let backend = MemoryBackend::default();
+        let timed = Timed(&backend);
+        let (read, spent) = storage_time(|| {
+            sleep(Duration::from_millis(20));
+            timed.get(Table::Values, "missing")
+        });
+        assert!(read?.is_none());
+        assert!(spent < Duration::from_millis(20));
+        let (inner, outer) = storage_time(|| {
+            let (_read, inner) = storage_time(|| timed.get(Table::Values, "missing"));
+            inner
+        });
+        assert!(outer >= inner);
+        Ok(())
+    }
+}
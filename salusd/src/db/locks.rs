@@ -0,0 +1,162 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Striped write locks over a backend's keys.
+//!
+//! Reads never take these: a backend serves reads alongside its commits. A
+//! write that depends on what it read holds the lock of every key it touches
+//! from the read through the commit, so changes to one key land one at a time
+//! while writes to other keys go on beside them. Keys hash onto a fixed set of
+//! stripes, so two keys can share a lock; all that costs is that their writes
+//! wait for each other.
+
+use std::{
+    array,
+    collections::BTreeSet,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    sync::{Mutex, MutexGuard},
+};
+
+use super::backend::Table;
+
+/// How many locks the keys share.
+const STRIPES: usize = 64;
+
+/// The write locks of a backend's keys.
+#[derive(Debug)]
+pub(crate) struct KeyLocks {
+    stripes: [Mutex<()>; STRIPES],
+}
+
+impl Default for KeyLocks {
+    fn default() -> Self {
+        Self {
+            stripes: array::from_fn(|_| Mutex::default()),
+        }
+    }
+}
+
+impl KeyLocks {
+    /// Hold the locks of `keys` until the guards are dropped.
+    ///
+    /// The locks are taken in one order whatever the keys, so two holders
+    /// never each wait on a lock the other has.
+    pub(crate) fn hold<'k>(
+        &self,
+        keys: impl IntoIterator<Item = (Table, &'k str)>,
+    ) -> Vec<MutexGuard<'_, ()>> {
+        let wanted = keys
+            .into_iter()
+            .map(|(table, key)| stripe(table, key))
+            .collect::<BTreeSet<_>>();
+        self.stripes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| wanted.contains(index))
+            .map(|(_, stripe)| lock(stripe))
+            .collect()
+    }
+
+    /// Hold every lock, so no write lands until the guards are dropped.
+    pub(crate) fn hold_all(&self) -> Vec<MutexGuard<'_, ()>> {
+        self.stripes.iter().map(lock).collect()
+    }
+}
+
+/// The stripe `key` of `table` hashes onto.
+fn stripe(table: Table, key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    table.hash(&mut hasher);
+    key.hash(&mut hasher);
+    usize::try_from(hasher.finish())
+        .unwrap_or_default()
+        .checked_rem(STRIPES)
+        .unwrap_or_default()
+}
+
+fn lock(stripe: &Mutex<()>) -> MutexGuard<'_, ()> {
+    match stripe.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        sync::{Arc, mpsc},
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{Result, anyhow};
+
+    use super::{KeyLocks, STRIPES, stripe};
+    use crate::db::backend::Table;
+
+    /// A key of `table` on a different stripe from `key`.
+    fn other_key(table: Table, key: &str) -> Result<String> {
+        (0..)
+            .map(|i| format!("other-{i}"))
+            .take(STRIPES.saturating_mul(8))
+            .find(|other| stripe(table, other) != stripe(table, key))
+            .ok_or_else(|| anyhow!("every key shared a stripe"))
+    }
+
+    /// A key of `table`, starting with `prefix`, on the same stripe as `key`
+    /// of `with`.
+    pub(crate) fn key_sharing_stripe(
+        table: Table,
+        prefix: &str,
+        (with, key): (Table, &str),
+    ) -> Result<String> {
+        (0..)
+            .map(|i| format!("{prefix}{i}"))
+            .take(STRIPES.saturating_mul(64))
+            .find(|candidate| stripe(table, candidate) == stripe(with, key))
+            .ok_or_else(|| anyhow!("no key shared the stripe"))
+    }
+
+    #[test]
+    fn holding_a_key_leaves_other_keys_free() -> Result<()> {
+        let locks = Arc::new(KeyLocks::default());
+        let other = other_key(Table::Values, "db/pass")?;
+        let held = locks.hold([(Table::Values, "db/pass")]);
+
+        let (tx, rx) = mpsc::channel();
+        let waiting = {
+            let locks = Arc::clone(&locks);
+            thread::spawn(move || {
+                let _guards = locks.hold([(Table::Values, "db/pass")]);
+                tx.send("same key")
+            })
+        };
+        {
+            let _guards = locks.hold([(Table::Values, other.as_str())]);
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(held);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5))?, "same key");
+        waiting
+            .join()
+            .map_err(|_| anyhow!("the waiting thread panicked"))??;
+        Ok(())
+    }
+
+    #[test]
+    fn a_key_given_twice_is_locked_once() {
+        let locks = KeyLocks::default();
+        assert_eq!(
+            locks
+                .hold([(Table::Values, "a"), (Table::Values, "a")])
+                .len(),
+            1
+        );
+        assert_eq!(locks.hold_all().len(), STRIPES);
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Schema versions, and the steps that bring an older database up to date.
+//!
+//! The version is a `salus_config` row written when the store is initialized.
+//! A database with rows but no version predates versioning and is version 0.
+//! Whatever opens a store migrates it first, one step at a time, each step
+//! committed together with the version it reaches, after copying every row to
+//! a redb file beside the database. A database from a newer salusd is refused
+//! rather than read with the wrong layout.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tracing::info;
+
+use super::{
+    Backend, SALUS_CONFIG_TABLE_DEF, SCHEMA_VERSION_KEY,
+    backend::{RedbBackend, StorageBackend, Table, WriteOp},
+    put, read_value, unlock_backend,
+    values::config::ConfigVal,
+};
+use crate::{error::Error, store::backup::create_private, utils::ensure_parent_dir};
+
+/// The layout this daemon reads and writes.
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+/// One upgrade step, from `from` to the version after it.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    /// The writes the step makes, committed with the new version.
+    apply: fn(&dyn StorageBackend) -> Result<Vec<WriteOp>>,
+}
+
+/// Every step, in order; step `n` upgrades version `n`.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "record the schema version",
+    // The layout before versioning is version 1's.
+    apply: |_| Ok(Vec::new()),
+}];
+
+/// The schema version of the store in `db`, or `None` for an empty store.
+pub(crate) fn schema_version(db: &dyn StorageBackend) -> Result<Option<u32>> {
+    if let Some(version) = read_value(db, SALUS_CONFIG_TABLE_DEF, SCHEMA_VERSION_KEY)? {
+        return Ok(Some(version.to_value()?));
+    }
+    for table in Table::ALL {
+        if !db.scan(table, "")?.is_empty() {
+            return Ok(Some(0));
+        }
+    }
+    Ok(None)
+}
+
+/// Refuse the store in `db` if a newer salusd wrote it.
+///
+/// # Errors
+///
+/// Returns [`Error::SchemaTooNew`] when the store's version is past
+/// [`SCHEMA_VERSION`].
+pub(crate) fn check_supported(db: &dyn StorageBackend) -> Result<Option<u32>> {
+    let version = schema_version(db)?;
+    match version {
+        Some(found) if found > SCHEMA_VERSION => {
+            Err(Error::SchemaTooNew(found, SCHEMA_VERSION).into())
+        }
+        _ => Ok(version),
+    }
+}
+
+/// Bring the store in `backend` up to [`SCHEMA_VERSION`], first copying it to
+/// a redb file beside `database` when there is one. Returns that copy, or
+/// `None` when there was nothing to migrate or nowhere to copy it.
+///
+/// Without `database`, the store must be one nothing else reads, such as an
+/// in-memory copy.
+///
+/// # Errors
+///
+/// Returns [`Error::SchemaTooNew`] for a store from a newer salusd, or an
+/// error if the copy or a step fails; the steps before it stay applied.
+pub(crate) fn migrate(backend: &Backend, database: Option<&Path>) -> Result<Option<PathBuf>> {
+    let mut copy = None;
+    unlock_backend(backend, |db: &dyn StorageBackend| -> Result<()> {
+        let Some(found) = check_supported(db)?.filter(|found| *found < SCHEMA_VERSION) else {
+            return Ok(());
+        };
+        if let Some(database) = database {
+            let path = copy_path(database, found);
+            write_copy(db, &path)?;
+            info!(
+                "Copied the version {found} database to {} before migrating it",
+                path.display()
+            );
+            copy = Some(path);
+        }
+        for step in MIGRATIONS.iter().skip_while(|step| step.from < found) {
+            let to = step.from.saturating_add(1);
+            let mut ops = (step.apply)(db)?;
+            ops.push(put(
+                SALUS_CONFIG_TABLE_DEF,
+                SCHEMA_VERSION_KEY,
+                &ConfigVal::from_value(to)?,
+            ));
+            db.commit(ops)?;
+            info!(
+                target: "salusd::audit",
+                from = step.from, to, "Database migrated: {}", step.description
+            );
+        }
+        Ok(())
+    })?;
+    Ok(copy)
+}
+
+/// `<database>.pre-migration-v<found>-<seconds>`, beside the database.
+fn copy_path(database: &Path, found: u32) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut name = database.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".pre-migration-v{found}-{seconds}"));
+    database.with_file_name(name)
+}
+
+/// Copy every row of `db` to a new redb database at `path`.
+fn write_copy(db: &dyn StorageBackend, path: &Path) -> Result<()> {
+    let mut ops = Vec::new();
+    for table in Table::ALL {
+        ops.extend(
+            db.scan(table, "")?
+                .into_iter()
+                .map(|(key, value)| WriteOp::Put { table, key, value }),
+        );
+    }
+    ensure_parent_dir(path)?;
+    // Created first so the copy is private, as the database is.
+    drop(create_private(path)?);
+    let written = RedbBackend::create(path).and_then(|copy| copy.commit(ops));
+    if written.is_err() {
+        drop(fs::remove_file(path));
+    }
+    written
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, sync::Arc};
+
+    use anyhow::{Result, bail};
+
+    use super::{MIGRATIONS, SCHEMA_VERSION, migrate, schema_version};
+    use crate::{
+        db::{
+            Backend, SALUS_CONFIG_TABLE_DEF, SCHEMA_VERSION_KEY, SharedBackend,
+            backend::{MemoryBackend, RedbBackend, StorageBackend, Table, WriteOp},
+            unlock_backend,
+            values::config::ConfigVal,
+            write_value,
+        },
+        error::Error,
+        utils::test::temp_dir,
+    };
+
+    fn version(backend: &Backend) -> Result<Option<u32>> {
+        let mut version = None;
+        unlock_backend(backend, |db: &dyn StorageBackend| -> Result<()> {
+            version = schema_version(db)?;
+            Ok(())
+        })?;
+        Ok(version)
+    }
+
+    #[test]
+    fn every_version_has_one_step() {
+        assert_eq!(
+            MIGRATIONS.len(),
+            usize::try_from(SCHEMA_VERSION).unwrap_or(0)
+        );
+        assert!(
+            MIGRATIONS
+                .iter()
+                .zip(0..)
+                .all(|(step, from)| step.from == from)
+        );
+    }
+
+    #[test]
+    fn an_unversioned_store_is_copied_then_migrated() -> Result<()> {
+        let dir = temp_dir("migrate-legacy")?;
+        let database = dir.join("salusd.redb");
+        let backend: Backend = Arc::new(SharedBackend::new(MemoryBackend::default()));
+        assert_eq!(migrate(&backend, Some(&database))?, None);
+        assert_eq!(version(&backend)?, None);
+
+        unlock_backend(&backend, |db: &dyn StorageBackend| -> Result<()> {
+            db.commit(vec![WriteOp::Put {
+                table: Table::Values,
+                key: "db/pass".to_string(),
+                value: b"sealed".to_vec(),
+            }])
+        })?;
+        assert_eq!(version(&backend)?, Some(0));
+        let Some(copy) = migrate(&backend, Some(&database))? else {
+            bail!("expected a pre-migration copy");
+        };
+        assert!(copy.starts_with(&dir));
+        assert!(
+            RedbBackend::open(&copy)?
+                .get(Table::Values, "db/pass")?
+                .is_some()
+        );
+        assert_eq!(version(&backend)?, Some(SCHEMA_VERSION));
+        assert_eq!(migrate(&backend, Some(&database))?, None);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn a_newer_store_is_refused() -> Result<()> {
+        let backend: Backend = Arc::new(SharedBackend::new(MemoryBackend::default()));
+        unlock_backend(&backend, |db: &dyn StorageBackend| -> Result<()> {
+            write_value(
+                db,
+                SALUS_CONFIG_TABLE_DEF,
+                SCHEMA_VERSION_KEY,
+                &ConfigVal::from_value(SCHEMA_VERSION.saturating_add(1))?,
+            )
+        })?;
+        let refused = migrate(&backend, None);
+        assert!(matches!(
+            refused.as_ref().map_err(|e| e.downcast_ref::<Error>()),
+            Err(Some(Error::SchemaTooNew(..)))
+        ));
+        Ok(())
+    }
+}
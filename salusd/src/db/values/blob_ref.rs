@@ -0,0 +1,42 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use anyhow::{Result, anyhow};
+
+use crate::db::Row;
+
+/// A `salus_blob_refs` row: the chunks holding one distinct streamed value,
+/// and how many stored values share them.
+///
+/// Stored as the 16-byte chunk id followed by the count as a little-endian
+/// `u64`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct BlobRef {
+    /// The id the shared chunks are stored under.
+    pub(crate) id: [u8; 16],
+    /// How many stored values name `id`.
+    pub(crate) count: u64,
+}
+
+impl Row for BlobRef {
+    fn to_row(&self) -> Vec<u8> {
+        let mut row = self.id.to_vec();
+        row.extend_from_slice(&self.count.to_le_bytes());
+        row
+    }
+
+    fn from_row(bytes: &[u8]) -> Result<Self> {
+        let malformed = || anyhow!("a blob ref row is 24 bytes, not {}", bytes.len());
+        let (id, count) = bytes.split_first_chunk::<16>().ok_or_else(malformed)?;
+        let count = <[u8; 8]>::try_from(count).map_err(|_| malformed())?;
+        Ok(Self {
+            id: *id,
+            count: u64::from_le_bytes(count),
+        })
+    }
+}
@@ -12,6 +12,8 @@ use bon::Builder;
 use libsalus::MAX_MESSAGE_SIZE;
 use redb::{TypeName, Value};
 
+use crate::db::Row;
+
 /// A `salus_config` row.
 ///
 /// `ConfigVal` is a thin newtype over the bincode-encoded bytes of a single
@@ -48,6 +50,16 @@ impl ConfigVal {
     }
 }
 
+impl Row for ConfigVal {
+    fn to_row(&self) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    fn from_row(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_raw_bytes(bytes))
+    }
+}
+
 impl Value for ConfigVal {
     type SelfType<'a>
         = ConfigVal
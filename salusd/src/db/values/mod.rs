@@ -6,5 +6,7 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
+pub(crate) mod blob_ref;
 pub(crate) mod config;
+pub(crate) mod revision;
 pub(crate) mod salus;
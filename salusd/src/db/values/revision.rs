@@ -0,0 +1,62 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use anyhow::{Result, anyhow};
+
+use crate::db::{Row, values::salus::SalusVal};
+
+/// The bytes before the sealed value: the version, then the write time and
+/// the author, each after a byte saying whether it is known.
+const HEADER_LEN: usize = 22;
+
+/// A `salus_history` row: an earlier version of a value, sealed as it was
+/// stored, with when and by whom it was written.
+///
+/// Stored as the version as a little-endian `u64`, the write time as a flag
+/// byte and a little-endian `u64`, the author's uid as a flag byte and a
+/// little-endian `u32`, then the sealed row.
+#[derive(Clone, Debug)]
+pub(crate) struct Revision {
+    /// The version's number.
+    pub(crate) version: u64,
+    /// When it was written, in seconds since the Unix epoch, if recorded.
+    pub(crate) written: Option<u64>,
+    /// Who wrote it, by uid, if known.
+    pub(crate) author: Option<u32>,
+    /// The value, sealed to its key as it was stored.
+    pub(crate) sealed: SalusVal,
+}
+
+impl Row for Revision {
+    fn to_row(&self) -> Vec<u8> {
+        let sealed = self.sealed.to_row();
+        let mut row = Vec::with_capacity(HEADER_LEN.saturating_add(sealed.len()));
+        row.extend_from_slice(&self.version.to_le_bytes());
+        row.push(u8::from(self.written.is_some()));
+        row.extend_from_slice(&self.written.unwrap_or_default().to_le_bytes());
+        row.push(u8::from(self.author.is_some()));
+        row.extend_from_slice(&self.author.unwrap_or_default().to_le_bytes());
+        row.extend_from_slice(&sealed);
+        row
+    }
+
+    fn from_row(bytes: &[u8]) -> Result<Self> {
+        let malformed = || anyhow!("a revision row is at least {HEADER_LEN} bytes");
+        let (version, rest) = bytes.split_first_chunk::<8>().ok_or_else(malformed)?;
+        let (written_known, rest) = rest.split_first().ok_or_else(malformed)?;
+        let (written, rest) = rest.split_first_chunk::<8>().ok_or_else(malformed)?;
+        let (author_known, rest) = rest.split_first().ok_or_else(malformed)?;
+        let (author, sealed) = rest.split_first_chunk::<4>().ok_or_else(malformed)?;
+        Ok(Self {
+            version: u64::from_le_bytes(*version),
+            written: (*written_known != 0).then(|| u64::from_le_bytes(*written)),
+            author: (*author_known != 0).then(|| u32::from_le_bytes(*author)),
+            sealed: SalusVal::from_row(sealed)?,
+        })
+    }
+}
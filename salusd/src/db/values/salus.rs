@@ -9,8 +9,31 @@
 use anyhow::{Result, anyhow};
 use redb::{TypeName, Value};
 
+use crate::db::Row;
+
 /// A `salus_store` row: an AES-256-GCM nonce followed by its ciphertext.
 ///
+/// A value sealed under a named key rather than the store key records which
+/// key and version sealed it ahead of the nonce:
+///
+/// ```text
+/// NAMED_KEY_MARKER (8) || name length (1) || name || version (4, big-endian) || nonce || ciphertext
+/// ```
+///
+/// A value stored in chunks records the id of its chunks in `salus_blobs`, and
+/// seals only its manifest:
+///
+/// ```text
+/// BLOB_MARKER (8) || id (16) || nonce || ciphertext
+/// ```
+///
+/// A value compressed before it was sealed carries one more marker in front of
+/// either layout:
+///
+/// ```text
+/// COMPRESSED_MARKER (8) || ...
+/// ```
+///
 /// `SalusVal` is a thin newtype over the raw `nonce || ciphertext` bytes, stored
 /// in `redb` verbatim. The infallible [`Value::from_bytes`] / [`Value::as_bytes`]
 /// hooks are therefore genuine no-op wraps/unwraps that can never panic on a
@@ -26,6 +49,22 @@ pub(crate) struct SalusVal {
 /// Length of the AES-256-GCM nonce that prefixes every stored value.
 const NONCE_LEN: usize = 12;
 
+/// Starts a row sealed under a named key. A store-key row starts with its
+/// random nonce, which matches this only with probability 2^-64, and such a
+/// row would then fail to open rather than open wrongly.
+const NAMED_KEY_MARKER: [u8; 8] = *b"\0salusnk";
+
+/// Starts a row whose value is stored in chunks; see [`NAMED_KEY_MARKER`] for
+/// why a store-key row never reads as one.
+const BLOB_MARKER: [u8; 8] = *b"\0salusbl";
+
+/// Starts a row whose value was compressed before it was sealed; see
+/// [`NAMED_KEY_MARKER`] for why a store-key row never reads as one.
+const COMPRESSED_MARKER: [u8; 8] = *b"\0saluszs";
+
+/// Length of the id naming a value's chunks.
+pub(crate) const BLOB_ID_LEN: usize = 16;
+
 impl SalusVal {
     /// Build a `SalusVal` from a freshly-sealed nonce and ciphertext.
     pub(crate) fn from_parts(nonce: [u8; NONCE_LEN], ciphertext: &[u8]) -> Self {
@@ -35,6 +74,39 @@ impl SalusVal {
         Self { raw }
     }
 
+    /// Build a `SalusVal` for a value sealed under version `version` of the
+    /// named key `name`, erroring if the name is longer than 255 bytes.
+    pub(crate) fn from_named_parts(
+        name: &str,
+        version: u32,
+        nonce: [u8; NONCE_LEN],
+        ciphertext: &[u8],
+    ) -> Result<Self> {
+        let name_len = u8::try_from(name.len())
+            .map_err(|_| anyhow!("the key name '{name}' is too long to record"))?;
+        let mut raw = NAMED_KEY_MARKER.to_vec();
+        raw.push(name_len);
+        raw.extend_from_slice(name.as_bytes());
+        raw.extend_from_slice(&version.to_be_bytes());
+        raw.extend_from_slice(&nonce);
+        raw.extend_from_slice(ciphertext);
+        Ok(Self { raw })
+    }
+
+    /// Build a `SalusVal` for the manifest of a value stored in chunks under
+    /// `id`.
+    pub(crate) fn from_blob_parts(
+        id: [u8; BLOB_ID_LEN],
+        nonce: [u8; NONCE_LEN],
+        ciphertext: &[u8],
+    ) -> Self {
+        let mut raw = BLOB_MARKER.to_vec();
+        raw.extend_from_slice(&id);
+        raw.extend_from_slice(&nonce);
+        raw.extend_from_slice(ciphertext);
+        Self { raw }
+    }
+
     /// Wrap raw stored bytes as a `SalusVal` without validating them.
     ///
     /// Infallible: validation (the 12-byte nonce split) is deferred to
@@ -43,13 +115,84 @@ impl SalusVal {
         Self { raw: data.to_vec() }
     }
 
+    /// Flag the value as compressed before it was sealed.
+    pub(crate) fn into_compressed(self) -> Self {
+        let mut raw = COMPRESSED_MARKER.to_vec();
+        raw.extend_from_slice(&self.raw);
+        Self { raw }
+    }
+
+    /// Whether the value was compressed before it was sealed.
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.raw.starts_with(&COMPRESSED_MARKER)
+    }
+
+    /// The row without its compression flag.
+    fn body(&self) -> &[u8] {
+        self.raw
+            .strip_prefix(&COMPRESSED_MARKER)
+            .unwrap_or(&self.raw)
+    }
+
+    /// Split off the named key reference, if the row has one, from the nonce
+    /// and ciphertext.
+    fn named_split(&self) -> Result<NamedSplit<'_>> {
+        let body = self.body();
+        let Some(rest) = body.strip_prefix(&NAMED_KEY_MARKER) else {
+            return Ok((None, body));
+        };
+        let malformed = || anyhow!("SalusVal is malformed (truncated named key reference)");
+        let (&name_len, rest) = rest.split_first().ok_or_else(malformed)?;
+        let (name, rest) = rest
+            .split_at_checked(usize::from(name_len))
+            .ok_or_else(malformed)?;
+        let (version, rest) = rest.split_first_chunk::<4>().ok_or_else(malformed)?;
+        Ok((
+            Some((str::from_utf8(name)?, u32::from_be_bytes(*version))),
+            rest,
+        ))
+    }
+
+    /// Split off the id of the value's chunks, if it is stored in chunks, from
+    /// the nonce and ciphertext.
+    fn blob_split(&self) -> Result<Option<(&[u8; BLOB_ID_LEN], &[u8])>> {
+        let Some(rest) = self.body().strip_prefix(&BLOB_MARKER) else {
+            return Ok(None);
+        };
+        rest.split_first_chunk::<BLOB_ID_LEN>()
+            .map(Some)
+            .ok_or_else(|| anyhow!("SalusVal is malformed (truncated chunk id)"))
+    }
+
     /// Split the row into its nonce and ciphertext, erroring on a truncated row.
     fn split(&self) -> Result<(&[u8; NONCE_LEN], &[u8])> {
-        self.raw
-            .split_first_chunk::<NONCE_LEN>()
+        let rest = match self.blob_split()? {
+            Some((_, rest)) => rest,
+            None => self.named_split()?.1,
+        };
+        rest.split_first_chunk::<NONCE_LEN>()
             .ok_or_else(|| anyhow!("SalusVal is malformed (need at least {NONCE_LEN} nonce bytes)"))
     }
 
+    /// The named key and version the value was sealed under; `None` for a value
+    /// sealed under the store key.
+    pub(crate) fn named_key(&self) -> Result<Option<(String, u32)>> {
+        Ok(self
+            .named_split()?
+            .0
+            .map(|(name, version)| (name.to_string(), version)))
+    }
+
+    /// The id of the value's chunks; `None` for a value stored whole.
+    pub(crate) fn blob_id(&self) -> Result<Option<[u8; BLOB_ID_LEN]>> {
+        Ok(self.blob_split()?.map(|(id, _)| *id))
+    }
+
+    /// The length of the row as stored.
+    pub(crate) fn stored_len(&self) -> usize {
+        self.raw.len()
+    }
+
     /// The 12-byte AES-256-GCM nonce.
     pub(crate) fn nonce(&self) -> Result<[u8; NONCE_LEN]> {
         Ok(*self.split()?.0)
@@ -61,6 +204,19 @@ impl SalusVal {
     }
 }
 
+/// A row's named key reference, if any, and the nonce and ciphertext after it.
+type NamedSplit<'a> = (Option<(&'a str, u32)>, &'a [u8]);
+
+impl Row for SalusVal {
+    fn to_row(&self) -> Vec<u8> {
+        self.raw.clone()
+    }
+
+    fn from_row(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_raw_bytes(bytes))
+    }
+}
+
 impl Value for SalusVal {
     type SelfType<'a>
         = SalusVal
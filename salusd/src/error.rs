@@ -20,14 +20,24 @@ pub(crate) enum Error {
     LogDir,
     #[error("Unable to create a parent directory")]
     CreateDir,
+    #[error("There is no home directory to expand ~ in {0}")]
+    HomeDir(String),
+    #[error("{1} names ${{{0}}}, which is not set")]
+    UnsetPathVar(String, String),
+    #[error("{0} opens a ${{ that is never closed")]
+    UnclosedPathVar(String),
     #[error("Unable to build a valid configuration")]
     ConfigBuild,
     #[error("Unable to deserialize config")]
     ConfigDeserialize,
     #[error("Unable to load a valid configuration")]
     ConfigLoad,
+    #[error("The [shares] defaults in the configuration are not usable")]
+    InvalidShareDefaults,
     #[error("Unable to initialize tracing")]
     TracingInit,
+    #[error("Unable to turn off core dumps and debugger attachment (harden_process)")]
+    Harden,
     #[error("Unable to initialize the database")]
     DatabaseInit,
     #[error(
@@ -35,6 +45,65 @@ pub(crate) enum Error {
          stop the other instance before starting salusd"
     )]
     DatabaseLocked(PathBuf),
+    #[error("Unable to open the database at {0}")]
+    DatabaseOpen(PathBuf),
+    #[error(
+        "The database has schema version {0}, but this salusd reads up to version {1}; \
+         upgrade salusd to open it"
+    )]
+    SchemaTooNew(u32, u32),
+    #[cfg(not(feature = "s3"))]
+    #[error("storage.url is set to {0}, but this salusd was built without the s3 feature")]
+    ObjectStoreUnsupported(String),
+    #[cfg(feature = "s3")]
+    #[error("{0} is not a usable object store; expected s3://<bucket>[/<prefix>]")]
+    InvalidStorageUrl(String),
+    #[cfg(feature = "s3")]
+    #[error("Another salusd changed the store first; the write was not applied, retry it")]
+    StorageConflict,
+    #[cfg(not(feature = "cluster"))]
+    #[error(
+        "This salusd was built without the cluster feature; rebuild it with --features cluster"
+    )]
+    ClusterUnsupported,
+    #[error("[cluster] and storage.url cannot both be set; each node keeps its own database file")]
+    ClusterObjectStore,
+    #[cfg(feature = "cluster")]
+    #[error("[cluster] node_id must be set to a nonzero id no other node uses")]
+    ClusterNodeId,
+    #[cfg(feature = "cluster")]
+    #[error("[cluster] key_file must name a file holding the key every node shares")]
+    ClusterKeyMissing,
+    #[cfg(feature = "cluster")]
+    #[error("Unable to read a cluster key of at least 32 bytes from {0}")]
+    ClusterKey(PathBuf),
+    #[cfg(feature = "cluster")]
+    #[error(
+        "{0} already holds a store; a node joining a cluster must start empty, \
+         only the bootstrap node may bring one in"
+    )]
+    ClusterNotEmpty(PathBuf),
+    #[cfg(feature = "cluster")]
+    #[error("Give --addr, or set [cluster] listen, to name the node to ask")]
+    ClusterAddress,
+    #[cfg(feature = "cluster")]
+    #[error("This node is not the cluster leader; send writes to {0}")]
+    NotClusterLeader(String),
+    #[error("{0} is not a usable backup path; give an absolute path to a file")]
+    BackupPath(PathBuf),
+    #[error("{0} or its manifest already exists; backups never overwrite")]
+    BackupExists(PathBuf),
+    #[error("Unable to read the backup manifest {0}")]
+    BackupManifest(PathBuf),
+    #[error("{0} does not match its manifest: {1}")]
+    BackupMismatch(PathBuf, String),
+    #[error("The backup is encrypted; pass --identity with the age identity it was encrypted to")]
+    BackupIdentityRequired,
+    #[error(
+        "The backup was taken under a different share set than the database it would replace, \
+         so the current shares will not unlock it; pass --force to restore it anyway"
+    )]
+    BackupShareSet,
     #[error("Unable to generate a nonce key")]
     NonceKeyGen,
     #[allow(dead_code)]
@@ -42,12 +111,126 @@ pub(crate) enum Error {
     CheckKeyNotFound,
     #[error("Error generating shares")]
     ShareGeneration,
+    #[error(
+        "The current shares cannot be verified; they were generated before salusd \
+         recorded share digests"
+    )]
+    ShareDigestsMissing,
+    #[error("The supplied shares did not reconstruct the key")]
+    UnlockFailed,
+    #[error("No value is stored under '{0}'")]
+    KeyNotFound(String),
+    #[error("The named key '{0}' has no version {1}")]
+    NamedKeyMissing(String, u32),
     #[error("Store not unlocked")]
     StoreNotUnlocked,
     #[error("Invalid regex")]
     InvalidRegex,
     #[error("Unable to read next item from table iterator")]
     TableIterRead,
+    #[error("The value under '{0}' is stored in chunks; read it on its own")]
+    StreamedValue(String),
+    #[error("The value under '{0}' is not a JSON document")]
+    NotJson(String),
+    #[error("The value under '{0}' has no field at '{1}'")]
+    NoSuchField(String, String),
+    #[error("The patch is not a JSON document")]
+    PatchNotJson,
+    #[error("The alias '{0}' leads back to itself")]
+    AliasLoop(String),
+    #[error("The alias '{0}' goes more than {1} aliases deep")]
+    AliasTooDeep(String, usize),
+    #[error("{0} uploads are already in progress; finish one or wait for it to time out")]
+    TooManyUploads(usize),
+    #[error("Chunk {0} of the upload is {1} bytes, not {2}")]
+    ChunkSize(u32, usize, usize),
+    #[error("The upload is missing chunk {0}")]
+    ChunkMissing(u64),
+    #[error("Compression level {0} is outside zstd's range of {1} to {2}")]
+    CompressionLevel(i32, i32, i32),
+    #[error("The request was cancelled")]
+    Cancelled,
+    #[error("The configuration has {0} problem(s)")]
+    InvalidConfig(usize),
+    #[error("{0} already exists; pass --force to replace it")]
+    ConfigExists(String),
+    #[error("init-config writes TOML; name a .toml file or pass --config-format toml")]
+    ConfigTemplateFormat,
+    #[error("A {0} listener needs {1} set")]
+    ListenerMissing(&'static str, &'static str),
+    #[error("{0} can only be set on a socket listener")]
+    ListenerSocketOnly(&'static str),
+    #[error("A socket is only given a group when it is at an absolute path, on unix")]
+    SocketGroup,
+    #[cfg(not(feature = "tls"))]
+    #[error("This salusd was built without the tls feature; rebuild it with --features tls")]
+    TlsUnsupported,
+    #[cfg(feature = "tls")]
+    #[error("{0} holds no certificate")]
+    NoCertificate(String),
+    #[error(
+        "Namespace '{0}' is only served for {1} seconds after the store is unlocked; unlock it \
+         again"
+    )]
+    NamespaceUnlockExpired(String, u64),
+    #[error("Namespace '{0}' is read-only")]
+    NamespaceReadOnly(String),
+    #[error("Values in namespace '{0}' are never overwritten")]
+    NamespaceNoOverwrite(String),
+    #[error("Values in namespace '{0}' are never deleted")]
+    NamespaceNoDelete(String),
+    #[error("Values in namespace '{0}' are at most {1} bytes")]
+    NamespaceValueTooLarge(String, u64),
+    #[error("A namespace is named without a leading or trailing '/', and not empty")]
+    NamespaceName,
+    #[error("Values in namespace '{0}' are only read one key at a time, once approved")]
+    NamespaceNeedsApproval(String),
+    #[error(
+        "Reads in namespace '{0}' wait on approval, which needs a local client whose user the \
+         platform reports"
+    )]
+    ApprovalNeedsUser(String),
+    #[error("Only the approvers of namespace '{0}' may approve its reads")]
+    NotAnApprover(String),
+    #[error("A read cannot be approved by the user who asked for it")]
+    SelfApproval,
+    #[error("There is no plugin named '{0}'")]
+    PluginNotFound(String),
+    #[error("Plugin '{0}' does not mint credentials for role '{1}'")]
+    PluginRole(String, String),
+    #[error("Plugin '{0}' mints credentials good for 1 to {1} seconds")]
+    PluginTtl(String, u64),
+    #[error("Plugin '{0}' has no command set")]
+    PluginCommand(String),
+    #[error("Plugin '{0}' did not answer within {1} ms")]
+    PluginTimeout(String, u64),
+    #[error("Plugin '{0}' speaks protocol {1}, not protocol {2}")]
+    PluginProtocol(String, u32, u32),
+    #[error("Plugin '{0}' failed: {1}")]
+    PluginFailed(String, String),
+    #[error("The SSH public key is not usable: {0}")]
+    SshPublicKey(String),
+    #[error("An SSH certificate needs at least one principal, and no empty ones")]
+    SshPrincipals,
+    #[error("SSH certificates are good for 1 to {0} seconds")]
+    SshTtl(u64),
+    #[error("No CA is set up; make one with `salusc pki init` or `salusc pki import-ca`")]
+    PkiNoCa,
+    #[error("The CA is not usable: {0}")]
+    PkiCa(String),
+    #[error("'{0}' is not a host name or IP address")]
+    PkiName(String),
+    #[error("Certificates are good for 1 to {0} seconds, and not past the CA's own expiry")]
+    PkiTtl(u64),
+    #[error("No certificate has serial number '{0}'")]
+    CertNotFound(String),
+    #[error("Hook '{0}' failed: {1}")]
+    HookFailed(String, String),
+    #[error("Hook '{0}' did not finish within {1} ms")]
+    HookTimeout(String, u64),
+    #[cfg(not(feature = "webhooks"))]
+    #[error("Hook '{0}' posts to a URL, but this salusd was built without the webhooks feature")]
+    WebhooksUnsupported(String),
 }
 
 #[allow(clippy::needless_pass_by_value)]
@@ -15,16 +15,18 @@
 //! AES-256-GCM seal/open round-trip and the `find` regex path without standing
 //! up a daemon, a socket, or an on-disk database.
 
-use std::sync::{Arc, Mutex};
-
-use anyhow::{Result, anyhow, bail};
-use libsalus::Response;
-use redb::{Database, backends::InMemoryBackend};
+use std::sync::Arc;
 
 use crate::{
-    db::values::{config::ConfigVal, salus::SalusVal},
+    db::{
+        SharedBackend,
+        backend::MemoryBackend,
+        values::{config::ConfigVal, salus::SalusVal},
+    },
     store::ShareStore,
 };
+use anyhow::{Result, anyhow, bail};
+use libsalus::Response;
 
 /// Keys seeded into the cached store so `find` has something to match against.
 const SEED_KEYS: &[&str] = &[
@@ -40,8 +42,9 @@ const SEED_KEYS: &[&str] = &[
 /// and the `CHECK_KEY` sentinel written) but is **not** yet unlocked.
 #[cfg_attr(coverage_nightly, coverage(off))]
 fn build_initialized_store() -> Result<(ShareStore, Vec<String>)> {
-    let db = Database::builder().create_with_backend(InMemoryBackend::new())?;
-    let mut store = ShareStore::builder().redb(Arc::new(Mutex::new(db))).build();
+    let mut store = ShareStore::builder()
+        .backend(Arc::new(SharedBackend::new(MemoryBackend::default())))
+        .build();
     let shares = match store.gen_shares()? {
         Response::Shares(shares) => shares.shares().to_vec(),
         other => bail!("expected shares from gen_shares, got {other:?}"),
@@ -77,7 +80,7 @@ thread_local! {
 /// Seal `value` under `key`, read it back, and return the decrypted plaintext.
 ///
 /// Exercises the full `store` → `read` path: AES-256-GCM sealing with the key
-/// name bound as additional authenticated data, the `redb` write/read, and the
+/// name bound as additional authenticated data, the backend write/read, and the
 /// authenticated open. A fuzz target should assert the result round-trips back
 /// to `value`.
 ///
@@ -122,7 +125,7 @@ pub fn find_regex(pattern: &str) -> Result<Vec<String>> {
 
 /// Parse arbitrary bytes as a stored `salus_config` value.
 ///
-/// `ConfigVal` wraps the raw redb bytes infallibly; the real fallible decode is
+/// `ConfigVal` wraps the raw stored bytes infallibly; the real fallible decode is
 /// [`ConfigVal::to_value`], which bincode-decodes the wrapped payload. Config
 /// rows hold a `bool`, so this drives that decode. A fuzz target should assert
 /// that arbitrary (corrupted) bytes yield an `Err`, never a panic.
@@ -138,7 +141,7 @@ pub fn decode_config_val(data: &[u8]) -> Result<()> {
 
 /// Parse arbitrary bytes as a stored `salus_store` value (`nonce || ciphertext`).
 ///
-/// `SalusVal` wraps the raw redb bytes infallibly; the real fallible path is
+/// `SalusVal` wraps the raw stored bytes infallibly; the real fallible path is
 /// splitting off the 12-byte nonce (a truncated row is too short). A fuzz target
 /// should assert that short or corrupted bytes yield an `Err`, never a panic on
 /// the nonce slice.
@@ -6,21 +6,64 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, atomic::Ordering},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Error, Result};
+use aws_lc_rs::rand;
 use bon::Builder;
 use libsalus::{
-    Action, Init, MAX_UNLOCK_SECONDS, Response, SearchQuery, Store, UnlockTimeout, encode,
+    Action, Backup, BeginUpload, Credential, DecryptRequest, DeletePrefix, DiffRequest,
+    EncryptRequest, ExportSync, ExportWrapped, FrameMeta, GenerateSecret, ImportCa, ImportSync,
+    ImportWrapped, Init, IssueCert, Link, MAX_UNLOCK_SECONDS, MintCredential, NewCa, NewNamedKey,
+    NewSigningKey, Patch, ReadChunk, ReadField, RenewLease, Response, SearchQuery, SetRotation,
+    SignRequest, SignSshKey, Store, StoreBatch, UnlockTimeout, UploadChunk, VerifyRequest,
+    encode_frame_with, encode_json,
 };
+use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
     spawn,
+    task::spawn_blocking,
     time::{Duration, sleep},
 };
-use tracing::warn;
+use tracing::{debug, info, warn};
+
+use self::namespace::ApprovalRule;
+pub(crate) use self::namespace::Namespaces;
+use self::stopwatch::{Spent, Stopwatch};
+use crate::{
+    config::{
+        DEFAULT_MAX_RANDOM_BYTES, DEFAULT_MAX_VALUE_BYTES, PkiSettings, SshSettings,
+        reload::Reloader,
+    },
+    db::backend::{CancelFlag, cancellable, storage_time},
+    error::Error as SalusdError,
+    plugin::{
+        self, OnStore, Plugins,
+        database::{self, DatabaseRoles},
+    },
+    store::{ShareStore, approval::Approval, history::written_by},
+};
+
+mod namespace;
+mod stopwatch;
 
-use crate::store::ShareStore;
+/// How a connection's requests and responses are written.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Wire {
+    /// Bincode frames (`encode_frame`/`decode_frame`), each response
+    /// carrying its request's id
+    #[default]
+    Bincode,
+    /// Newline-delimited JSON (`encode_json`/`decode_json`), answered in
+    /// order
+    Json,
+}
 
 #[derive(Builder)]
 pub(crate) struct ActionHandler<T>
@@ -28,28 +71,107 @@ where
     T: AsyncWrite + Unpin,
 {
     sender: T,
-    store: Arc<Mutex<ShareStore>>,
+    store: Arc<RwLock<ShareStore>>,
     #[builder(into, default = 20u64)]
     key_timeout: u64,
+    #[builder(default = DEFAULT_MAX_RANDOM_BYTES)]
+    max_random_bytes: u32,
+    /// The longest value one `store` request or batch entry may hold
+    #[builder(default = DEFAULT_MAX_VALUE_BYTES)]
+    max_value_bytes: u64,
+    #[builder(default)]
+    wire: Wire,
+    /// Reloads the daemon's configuration, for `Action::ReloadConfig`
+    reloader: Option<Arc<Reloader>>,
+    /// Refuse changes whatever the store's mode, for a connection on a
+    /// read-only listener
+    #[builder(default)]
+    read_only_listener: bool,
+    /// The user the connection's client runs as, when the platform reports
+    /// it: who asks for, and gives, approvals
+    uid: Option<u32>,
+    /// The `[namespace.<name>]` rules each request is checked against
+    #[builder(default)]
+    namespaces: Arc<Namespaces>,
+    /// The `[plugins.<name>]` credentials are minted with
+    #[builder(default)]
+    plugins: Arc<Plugins>,
+    /// The `[database_roles.<role>]` users are minted for
+    #[builder(default)]
+    database: Arc<DatabaseRoles>,
+    /// How SSH certificates are signed
+    #[builder(default)]
+    ssh: SshSettings,
+    /// How leaf certificates are issued
+    #[builder(default)]
+    pki: PkiSettings,
+    /// The id of the request being answered, echoed in its response
+    #[builder(default)]
+    id: u64,
+    /// Times the request being answered, when it asked to be
+    #[builder(skip)]
+    stopwatch: Option<Stopwatch>,
+    /// Raised to cancel the request being answered
+    #[builder(skip)]
+    cancel: CancelFlag,
+    /// The cancel flags of the connection's requests still in progress, by id
+    #[builder(skip)]
+    in_flight: Arc<Mutex<HashMap<u64, CancelFlag>>>,
 }
 
 impl<T> ActionHandler<T>
 where
     T: AsyncWrite + Unpin,
 {
+    #[allow(clippy::too_many_lines)]
     pub(crate) async fn action_handler(&mut self, message: Action) -> Result<()> {
+        let refused = if self.read_only_listener {
+            mutates(&message) || matches!(message, Action::SetReadOnly(_))
+        } else {
+            mutates(&message) && self.read_only().await?
+        };
+        if refused {
+            return self.response(Response::ReadOnly).await;
+        }
+        let stored = match self.namespaces.followed(&message).map(str::to_string) {
+            Some(key) => match self
+                .read_store_value(move |store| store.resolve(&key))
+                .await
+            {
+                Ok(stored) => stored,
+                Err(e) => return self.error(e).await,
+            },
+            None => None,
+        };
+        let unlocked_for = if self.namespaces.timed(&message, stored.as_deref()) {
+            self.unlocked_for().await?
+        } else {
+            None
+        };
+        if let Err(refusal) = self
+            .namespaces
+            .admit(&message, stored.as_deref(), unlocked_for)
+        {
+            return self.error(refusal.into()).await;
+        }
+        match self.namespaces.approval(&message, stored.as_deref()) {
+            Ok(Some((key, rule))) => {
+                if !self.approved(key.to_string(), rule).await? {
+                    return Ok(());
+                }
+            }
+            Ok(None) => {}
+            Err(refusal) => return self.error(refusal.into()).await,
+        }
         match message {
             Action::GenShares(num_shares, threshold) => {
                 let init = Init::builder()
                     .num_shares(num_shares)
                     .threshold(threshold)
                     .build();
-                match self.initialize(init).await {
-                    Ok(()) => self.gen_key().await?,
-                    Err(e) => self.error(e).await?,
-                }
+                self.gen_shares(init).await?;
             }
-            Action::Share(share) => self.add_share(share.share()).await?,
+            Action::Share(share) => self.add_share(share.share().to_string()).await?,
             Action::Unlock(timeout) => self.unlock(timeout).await?,
             Action::Lock => self.lock().await?,
             Action::Store(store) => self.store(store).await?,
@@ -58,14 +180,131 @@ where
             Action::GetThreshold => self.get_threshold().await?,
             Action::FindKey(key) => self.find(key).await?,
             Action::Search(query) => self.search(query).await?,
+            Action::Status => self.status().await?,
+            Action::StoreBatch(batch) => self.store_batch(batch).await?,
+            Action::ReadPrefix(prefix) => self.read_prefix(prefix).await?,
+            Action::Generate(request) => self.generate(request).await?,
+            Action::VerifyShare(share) => self.verify_share(share.share().to_string()).await?,
+            Action::RefreshShares => self.refresh_shares().await?,
+            Action::InitStore(init) => self.gen_shares(init).await?,
+            Action::CreateSigningKey(request) => self.create_signing_key(request).await?,
+            Action::Sign(request) => self.sign(request).await?,
+            Action::Verify(request) => self.verify_signature(request).await?,
+            Action::Hmac(request) => self.hmac(request).await?,
+            Action::GenerateDataKey(bits) => self.generate_data_key(bits).await?,
+            Action::DecryptDataKey(ciphertext) => self.decrypt_data_key(ciphertext).await?,
+            Action::Random(bytes) => self.random(bytes).await?,
+            Action::WrappingKey => self.wrapping_key().await?,
+            Action::ImportWrapped(request) => self.import_wrapped(request).await?,
+            Action::ExportWrapped(request) => self.export_wrapped(request).await?,
+            Action::Encrypt(request) => self.encrypt(request).await?,
+            Action::Decrypt(request) => self.decrypt(request).await?,
+            Action::CreateKey(request) => self.create_named_key(request).await?,
+            Action::RotateKey(name) => self.rotate_named_key(name).await?,
+            Action::Rewrap(prefix) => self.rewrap(prefix).await?,
+            Action::RewrapStatus => self.rewrap_status().await?,
+            Action::History(key) => self.history(key).await?,
+            Action::Diff(request) => self.diff(request).await?,
+            Action::ListKeys => self.list_named_keys().await?,
+            Action::StoreWithKey(name, request) => self.store_with_key(name, request).await?,
+            Action::Backup(request) => self.backup(request).await?,
+            Action::CheckStore => self.check_store().await?,
+            Action::BeginUpload(request) => self.begin_upload(request).await?,
+            Action::UploadChunk(chunk) => self.upload_chunk(chunk).await?,
+            Action::FinishUpload(upload) => self.finish_upload(upload).await?,
+            Action::ReadChunk(request) => self.read_chunk(request).await?,
+            Action::ExportSync(request) => self.export_sync(request).await?,
+            Action::ImportSync(request) => self.import_sync(request).await?,
+            Action::SetReadOnly(read_only) => self.set_read_only(read_only).await?,
+            Action::Ping => self.response(Response::Pong).await?,
+            Action::Cancel(id) => self.cancel_request(id).await?,
+            Action::ReloadConfig => self.reload_config().await?,
+            Action::ReadField(request) => self.read_field(request).await?,
+            Action::Patch(request) => self.patch(request).await?,
+            Action::Link(request) => self.link(request).await?,
+            Action::Exists(key) => self.exists(key).await?,
+            Action::DeletePrefix(request) => self.delete_prefix(request).await?,
+            Action::MintCredential(request) => self.mint_credential(request).await?,
+            Action::ListPlugins => self.list_plugins().await?,
+            Action::SignSshKey(request) => self.sign_ssh_key(request).await?,
+            Action::CreateCa(request) => self.create_ca(request).await?,
+            Action::ImportCa(request) => self.import_ca(request).await?,
+            Action::IssueCert(request) => self.issue_cert(request).await?,
+            Action::RevokeCert(serial) => self.revoke_cert(serial).await?,
+            Action::ListCerts => self.list_certs().await?,
+            Action::GetCrl => self.crl().await?,
+            Action::SetRotation(request) => self.set_rotation(request).await?,
+            Action::Warnings => self.warnings().await?,
+            Action::RenewLease(request) => self.renew_lease(request).await?,
+            Action::RevokeLease(id) => self.revoke_lease(id).await?,
+            Action::ListApprovals => self.list_approvals().await?,
+            Action::Approve(id) => self.approve(id).await?,
+            Action::DenyApproval(id) => self.deny_approval(id).await?,
         }
         Ok(())
     }
 
-    async fn initialize(&mut self, init: Init) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> { store.initialize(init) }) {
-            Ok(_response) => {
-                // self.response(response).await?;
+    /// Raise the cancel flag of request `id`, if it is still in progress on
+    /// this connection; its store call stops at its next read or write.
+    async fn cancel_request(&mut self, id: u64) -> Result<()> {
+        let flag = lock(&self.in_flight).get(&id).cloned();
+        match flag {
+            Some(flag) => {
+                flag.store(true, Ordering::Release);
+                self.response(Response::Success).await
+            }
+            None => {
+                self.response(Response::Error(format!(
+                    "no request {id} is in progress on this connection"
+                )))
+                .await
+            }
+        }
+    }
+
+    /// Load the daemon's configuration again, reporting what was taken up or
+    /// what needs a restart.
+    async fn reload_config(&mut self) -> Result<()> {
+        let Some(reloader) = self.reloader.clone() else {
+            return self
+                .response(Response::Error(
+                    "this daemon cannot reload its configuration".to_string(),
+                ))
+                .await;
+        };
+        match spawn_blocking(move || reloader.reload()).await? {
+            Ok(report) => self.response(Response::ConfigReloaded(report)).await,
+            Err(e) => self.error(e).await,
+        }
+    }
+
+    /// Whether the daemon is refusing changes to the store.
+    async fn read_only(&self) -> Result<bool> {
+        let store = self.store.clone();
+        Ok(spawn_blocking(move || match store.read() {
+            Ok(share_store) => share_store.read_only(),
+            Err(poisoned) => poisoned.into_inner().read_only(),
+        })
+        .await?)
+    }
+
+    /// How long ago the store was unlocked; `None` while it is sealed.
+    async fn unlocked_for(&self) -> Result<Option<Duration>> {
+        let store = self.store.clone();
+        Ok(spawn_blocking(move || match store.read() {
+            Ok(share_store) => share_store.unlocked_for(),
+            Err(poisoned) => poisoned.into_inner().unlocked_for(),
+        })
+        .await?)
+    }
+
+    async fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        match self
+            .write_store(move |store| -> Result<Response> { store.set_read_only(read_only) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
             }
             Err(e) => {
                 self.error(e).await?;
@@ -74,8 +313,16 @@ where
         Ok(())
     }
 
-    async fn gen_key(&mut self) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> { store.gen_shares() }) {
+    async fn gen_shares(&mut self, init: Init) -> Result<()> {
+        match self
+            .write_store(move |store| -> Result<Response> {
+                match store.initialize(init)? {
+                    Response::Success => store.gen_shares(),
+                    refused => Ok(refused),
+                }
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -86,11 +333,14 @@ where
         Ok(())
     }
 
-    async fn add_share(&mut self, share: &str) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> {
-            store.add_share(share);
-            Ok(Response::Success)
-        }) {
+    async fn add_share(&mut self, share: String) -> Result<()> {
+        match self
+            .write_store(move |store| -> Result<Response> {
+                store.add_share(share);
+                Ok(Response::Success)
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -102,10 +352,13 @@ where
     }
 
     async fn get_threshold(&mut self) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> {
-            let threshold = store.get_threshold();
-            Ok(Response::Threshold(threshold))
-        }) {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                let threshold = store.get_threshold();
+                Ok(Response::Threshold(threshold))
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -126,34 +379,41 @@ where
             UnlockTimeout::Seconds(secs) => Some(secs.min(MAX_UNLOCK_SECONDS)),
             UnlockTimeout::Forever => None,
         };
-        match self.unlock_store(|store| -> Result<Response> {
-            let response = store.unlock()?;
-
-            if matches!(response, Response::Success) {
-                if let Some(hold_secs) = hold_secs {
-                    // We successfully unlocked the key, so set a timer to clear it
-                    // from memory after `hold_secs` seconds. The timer captures the
-                    // current unlock generation; a later unlock or lock bumps the
-                    // generation, so this timer firing becomes a no-op and cannot
-                    // clear a fresher key.
-                    let generation = store.key_generation();
-                    let interval = sleep(Duration::from_secs(hold_secs));
-                    let store_c = store_c.clone();
-                    let _blah = spawn(async move {
-                        interval.await;
-                        warn!("Clearing unlocked key from memory");
-                        let mut store = match store_c.lock() {
-                            Ok(store) => store,
-                            Err(poisoned) => poisoned.into_inner(),
-                        };
-                        store.clear_key_if_generation(generation);
-                    });
-                } else {
-                    warn!("Key unlocked with no auto-clear timer (forever)");
+        match self
+            .write_store(move |store| -> Result<Response> {
+                let response = store.unlock()?;
+
+                if matches!(response, Response::Success) {
+                    store.set_key_expiry(hold_secs.map(Duration::from_secs));
+                    if let Some(hold_secs) = hold_secs {
+                        // We successfully unlocked the key, so set a timer to clear it
+                        // from memory after `hold_secs` seconds. The timer captures the
+                        // current unlock generation; a later unlock or lock bumps the
+                        // generation, so this timer firing becomes a no-op and cannot
+                        // clear a fresher key.
+                        let generation = store.key_generation();
+                        let interval = sleep(Duration::from_secs(hold_secs));
+                        let store_c = store_c.clone();
+                        let _blah = spawn(async move {
+                            interval.await;
+                            warn!("Clearing unlocked key from memory");
+                            let _cleared = spawn_blocking(move || {
+                                let mut store = match store_c.write() {
+                                    Ok(store) => store,
+                                    Err(poisoned) => poisoned.into_inner(),
+                                };
+                                store.clear_key_if_generation(generation);
+                            })
+                            .await;
+                        });
+                    } else {
+                        warn!("Key unlocked with no auto-clear timer (forever)");
+                    }
                 }
-            }
-            Ok(response)
-        }) {
+                Ok(response)
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -165,11 +425,14 @@ where
     }
 
     async fn lock(&mut self) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> {
-            store.lock();
-            warn!("Store locked; unlocked key cleared from memory");
-            Ok(Response::Success)
-        }) {
+        match self
+            .write_store(move |store| -> Result<Response> {
+                store.lock();
+                warn!("Store locked; unlocked key cleared from memory");
+                Ok(Response::Success)
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -181,10 +444,16 @@ where
     }
 
     async fn store(&mut self, value: Store) -> Result<()> {
+        if self.oversized([&value]) {
+            return self.value_too_large().await;
+        }
         let (key, value, force) = value.into_parts();
-        match self.unlock_store(|store| -> Result<Response> {
-            store.store(&key, value.as_bytes().to_vec(), force)
-        }) {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.store(&key, value.as_bytes().to_vec(), force)
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -195,8 +464,15 @@ where
         Ok(())
     }
 
-    async fn read(&mut self, key: String) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> { store.read(&key) }) {
+    async fn store_batch(&mut self, batch: StoreBatch) -> Result<()> {
+        if self.oversized(batch.entries()) {
+            return self.value_too_large().await;
+        }
+        let (entries, dry_run) = batch.into_parts();
+        match self
+            .read_store(move |store| -> Result<Response> { store.store_batch(&entries, dry_run) })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -207,8 +483,11 @@ where
         Ok(())
     }
 
-    async fn delete(&mut self, key: String) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> { store.delete(&key) }) {
+    async fn generate(&mut self, request: GenerateSecret) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.generate(&request) })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -219,8 +498,15 @@ where
         Ok(())
     }
 
-    async fn find(&mut self, regex: String) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> { store.find(&regex) }) {
+    async fn read(&mut self, key: String) -> Result<()> {
+        if let Some(role) = self.database.role_for(&key) {
+            let role = role.to_string();
+            return self.database_user(&role).await;
+        }
+        match self
+            .read_store(move |store| -> Result<Response> { store.read(&key) })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -231,10 +517,33 @@ where
         Ok(())
     }
 
-    async fn search(&mut self, query: SearchQuery) -> Result<()> {
-        match self.unlock_store(|store| -> Result<Response> {
-            store.search(query.query(), query.limit())
-        }) {
+    /// Create a database user for `role`, answering with its name and
+    /// password. Creating one is a change, refused while the daemon is
+    /// read-only.
+    async fn database_user(&mut self, role: &str) -> Result<()> {
+        if self.read_only_listener || self.read_only().await? {
+            return self.response(Response::ReadOnly).await;
+        }
+        let (plugins, roles) = (self.plugins.clone(), self.database.clone());
+        match database::mint(self, &plugins, &roles, role).await {
+            Ok(minted) => {
+                self.response(Response::Value(Some(minted.to_vec())))
+                    .await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_field(&mut self, request: ReadField) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.read_field(request.key(), request.pointer())
+            })
+            .await
+        {
             Ok(response) => {
                 self.response(response).await?;
             }
@@ -245,162 +554,1990 @@ where
         Ok(())
     }
 
-    /// Answer a request the daemon could not decode with a clear error.
-    ///
-    /// Sent when the incoming bytes do not decode to a known `Action` (for
-    /// example, the client is newer than this daemon), so the client receives an
-    /// actionable message instead of an empty response.
-    pub(crate) async fn decode_error(&mut self) -> Result<()> {
-        self.response(Response::Error(
-            "salusd could not decode the request; the client may be newer than this daemon".into(),
-        ))
-        .await
+    async fn exists(&mut self, key: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.exists(&key) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
     }
 
-    async fn response(&mut self, message: Response) -> Result<()> {
-        let message = encode(message)?;
-        self.sender.write_all(&message).await?;
-        self.sender.flush().await?;
+    async fn delete_prefix(&mut self, request: DeletePrefix) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.delete_prefix(request.prefix(), request.dry_run())
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    async fn error(&mut self, err: Error) -> Result<()> {
-        self.response(Response::Error(err.to_string())).await
+    async fn mint_credential(&mut self, request: MintCredential) -> Result<()> {
+        match self.minted(&request).await {
+            Ok(credential) => {
+                self.response(Response::Credential(credential)).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
     }
 
-    fn unlock_store(
-        &mut self,
-        mut store_fn: impl FnMut(&mut ShareStore) -> Result<Response>,
-    ) -> Result<Response> {
-        let mut store = match self.store.lock() {
-            Ok(share_store) => share_store,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        store_fn(&mut store)
+    /// Have the plugin `request` names mint a credential, once its settings
+    /// admit the request. Credentials are only minted while the store is
+    /// unlocked, whether or not the plugin is handed a document from it.
+    async fn minted(&mut self, request: &MintCredential) -> Result<Credential> {
+        let plugins = self.plugins.clone();
+        let (settings, ttl) = plugins.admit(request.plugin(), request.role(), request.ttl())?;
+        let stored = settings.clone();
+        let config = self
+            .read_store_value(move |store| plugin::stored_config(store, &stored))
+            .await?;
+        plugin::mint(
+            request.plugin(),
+            settings,
+            request.role(),
+            ttl,
+            config.as_ref(),
+        )
+        .await
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::sync::{Arc, Mutex};
+    async fn renew_lease(&mut self, request: RenewLease) -> Result<()> {
+        let (plugins, roles) = (self.plugins.clone(), self.database.clone());
+        let renewed = database::renew(
+            self,
+            &plugins,
+            &roles,
+            request.lease_id(),
+            request.increment(),
+            now(),
+        )
+        .await;
+        match renewed {
+            Ok(Some(lease)) => self.response(Response::LeaseRenewed(lease.info())).await,
+            Ok(None) => self.response(Response::LeaseNotFound).await,
+            Err(e) => self.error(e).await,
+        }
+    }
 
-    use anyhow::{Result, bail};
-    use libsalus::{Action, Response, SearchQuery, Share, Store, UnlockTimeout, decode};
-    use redb::Database;
+    async fn revoke_lease(&mut self, id: String) -> Result<()> {
+        let plugins = self.plugins.clone();
+        match database::revoke(self, &plugins, &id).await {
+            Ok(true) => self.response(Response::Success).await,
+            Ok(false) => self.response(Response::LeaseNotFound).await,
+            Err(e) => self.error(e).await,
+        }
+    }
 
-    use super::ActionHandler;
-    use crate::store::ShareStore;
+    /// Whether the connection's user may read `key` now, under `rule`.
+    /// When they may not, the read is held as an approval, and answered with
+    /// it.
+    async fn approved(&mut self, key: String, rule: ApprovalRule) -> Result<bool> {
+        let Some(uid) = self.uid else {
+            self.error(SalusdError::ApprovalNeedsUser(rule.namespace).into())
+                .await?;
+            return Ok(false);
+        };
+        let now = now();
+        let wanted = key.clone();
+        let requested = self
+            .read_store_value(move |store| {
+                store.request_approval(&wanted, uid, rule.required, rule.window, now)
+            })
+            .await;
+        match requested {
+            Ok(approval) if approval.granted() => {
+                info!(
+                    target: "salusd::audit",
+                    key,
+                    approval = approval.id(),
+                    requester = uid,
+                    "Approved read released"
+                );
+                Ok(true)
+            }
+            Ok(approval) => {
+                self.response(Response::ApprovalPending(approval.info()))
+                    .await?;
+                Ok(false)
+            }
+            Err(e) => {
+                self.error(e).await?;
+                Ok(false)
+            }
+        }
+    }
 
-    fn temp_store() -> Result<Arc<Mutex<ShareStore>>> {
-        let db = Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?;
-        Ok(Arc::new(Mutex::new(
-            ShareStore::builder().redb(Arc::new(Mutex::new(db))).build(),
-        )))
+    async fn list_approvals(&mut self) -> Result<()> {
+        let now = now();
+        match self
+            .read_store_value(move |store| store.approvals(now))
+            .await
+        {
+            Ok(approvals) => {
+                let approvals = approvals.iter().map(Approval::info).collect();
+                self.response(Response::Approvals(approvals)).await
+            }
+            Err(e) => self.error(e).await,
+        }
     }
 
-    fn handler(store: Arc<Mutex<ShareStore>>) -> ActionHandler<Vec<u8>> {
-        ActionHandler::builder()
-            .sender(Vec::<u8>::new())
-            .store(store)
-            .key_timeout(0u64)
-            .build()
+    /// Approve the held read `id` as the connection's user, who must be one
+    /// of its namespace's approvers.
+    async fn approve(&mut self, id: String) -> Result<()> {
+        let now = now();
+        let approver = match self.pending(&id, now, false).await {
+            Ok(Some((uid, _))) => uid,
+            Ok(None) => return self.response(Response::ApprovalNotFound).await,
+            Err(e) => return self.error(e).await,
+        };
+        match self
+            .read_store_value(move |store| store.approve(&id, approver, now))
+            .await
+        {
+            Ok(Some(approval)) => self.response(Response::Approved(approval.info())).await,
+            Ok(None) => self.response(Response::ApprovalNotFound).await,
+            Err(e) => self.error(e).await,
+        }
     }
 
-    async fn run(action: Action) -> Result<Response> {
-        let mut handler = handler(temp_store()?);
-        handler.action_handler(action).await?;
-        decode::<Response>(&handler.sender)
+    /// Refuse the held read `id`, as one of its namespace's approvers or the
+    /// user who asked for it.
+    async fn deny_approval(&mut self, id: String) -> Result<()> {
+        let now = now();
+        let (uid, approval) = match self.pending(&id, now, true).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return self.response(Response::ApprovalNotFound).await,
+            Err(e) => return self.error(e).await,
+        };
+        let wanted = id.clone();
+        match self
+            .read_store_value(move |store| store.remove_approval(&wanted))
+            .await
+        {
+            Ok(()) => {
+                info!(
+                    target: "salusd::audit",
+                    key = approval.key(),
+                    approval = id.as_str(),
+                    requester = approval.requester(),
+                    by = uid,
+                    "Read refused"
+                );
+                self.response(Response::Success).await
+            }
+            Err(e) => self.error(e).await,
+        }
     }
 
-    /// Run one action on a persistent handler, returning the decoded response.
-    ///
-    /// Unlike [`run`], the handler (and its store) survives across calls so a
-    /// test can drive a full gen/unlock/store/search sequence.
-    async fn run_on(handler: &mut ActionHandler<Vec<u8>>, action: Action) -> Result<Response> {
-        handler.sender.clear();
-        handler.action_handler(action).await?;
-        decode::<Response>(&handler.sender)
+    /// The approval `id`, with the connection's user, when that user may act
+    /// on it: one of its namespace's approvers or, when `requester` is set,
+    /// the user who asked for it. `None` when it lapsed or is unknown.
+    async fn pending(
+        &mut self,
+        id: &str,
+        now: u64,
+        requester: bool,
+    ) -> Result<Option<(u32, Approval)>> {
+        let wanted = id.to_string();
+        let Some(approval) = self
+            .read_store_value(move |store| store.approval(&wanted, now))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let rule = self.namespaces.approval_rule(approval.key());
+        let Some(uid) = self.uid else {
+            let namespace = rule.map_or_else(|| approval.key().to_string(), |rule| rule.namespace);
+            return Err(SalusdError::ApprovalNeedsUser(namespace).into());
+        };
+        let withdrawn = requester && approval.requester() == uid;
+        match rule {
+            Some(rule) if !withdrawn && !rule.admits(uid) => {
+                Err(SalusdError::NotAnApprover(rule.namespace).into())
+            }
+            _ => Ok(Some((uid, approval))),
+        }
     }
 
-    #[tokio::test]
-    async fn gen_shares_responds_with_shares() -> Result<()> {
-        match run(Action::GenShares(5, 3)).await? {
-            Response::Shares(shares) => assert_eq!(shares.shares().len(), 5),
-            other => bail!("expected shares, got {other:?}"),
+    async fn list_plugins(&mut self) -> Result<()> {
+        let plugins = self.plugins.health().await;
+        self.response(Response::Plugins(plugins)).await
+    }
+
+    async fn patch(&mut self, request: Patch) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.patch(request.key(), request.json_merge_patch())
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
         }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn get_threshold_responds_with_default() -> Result<()> {
-        assert!(matches!(
-            run(Action::GetThreshold).await?,
-            Response::Threshold(3)
-        ));
+    async fn link(&mut self, request: Link) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.link(request.alias(), request.target())
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn read_before_unlock_errors() -> Result<()> {
-        assert!(matches!(
-            run(Action::Read("k".to_string())).await?,
-            Response::Error(_)
-        ));
+    async fn begin_upload(&mut self, request: BeginUpload) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.begin_upload(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn store_before_unlock_errors() -> Result<()> {
-        let store = Store::builder().key("k").value("v").build();
-        assert!(matches!(
-            run(Action::Store(store)).await?,
-            Response::Error(_)
-        ));
+    async fn upload_chunk(&mut self, chunk: UploadChunk) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.upload_chunk(&chunk) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn delete_before_unlock_errors() -> Result<()> {
-        assert!(matches!(
-            run(Action::Delete("k".to_string())).await?,
-            Response::Error(_)
-        ));
+    async fn finish_upload(&mut self, upload: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.finish_upload(&upload) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn lock_responds_success() -> Result<()> {
-        assert!(matches!(run(Action::Lock).await?, Response::Success));
+    async fn read_chunk(&mut self, request: ReadChunk) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.read_chunk(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn search_before_unlock_errors() -> Result<()> {
-        let action = Action::Search(SearchQuery::builder().query("x").build());
-        assert!(matches!(run(action).await?, Response::Error(_)));
+    async fn read_prefix(&mut self, prefix: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.read_prefix(&prefix) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn decode_error_responds_with_error() -> Result<()> {
-        // An undecodable request must produce a `Response::Error` the client can
-        // render, not an empty response that decodes to an opaque error.
-        let mut handler = handler(temp_store()?);
-        handler.decode_error().await?;
-        match decode::<Response>(&handler.sender)? {
-            Response::Error(msg) => assert!(msg.contains("could not decode")),
-            other => bail!("expected an error response, got {other:?}"),
+    async fn delete(&mut self, key: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.delete(&key) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
         }
         Ok(())
     }
 
-    #[tokio::test]
-    async fn unlock_then_store_and_search_succeeds() -> Result<()> {
+    async fn find(&mut self, regex: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.find(&regex) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn search(&mut self, query: SearchQuery) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.search(query.query(), query.limit())
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn refresh_shares(&mut self) -> Result<()> {
+        match self.write_store(ShareStore::refresh_shares).await {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_signing_key(&mut self, request: NewSigningKey) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.create_signing_key(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn sign(&mut self, request: SignRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.sign(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn hmac(&mut self, request: SignRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.hmac(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn verify_signature(&mut self, request: VerifyRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.verify_signature(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn sign_ssh_key(&mut self, request: SignSshKey) -> Result<()> {
+        let max_ttl = self.ssh.max_ttl();
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.sign_ssh_key(&request, max_ttl, now)
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_ca(&mut self, request: NewCa) -> Result<()> {
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> { store.create_ca(&request, now) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_ca(&mut self, request: ImportCa) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.import_ca(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn issue_cert(&mut self, request: IssueCert) -> Result<()> {
+        let max_ttl = self.pki.max_ttl();
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.issue_cert(&request, max_ttl, now)
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn revoke_cert(&mut self, serial: String) -> Result<()> {
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> { store.revoke_cert(&serial, now) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_certs(&mut self) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.list_certs() })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_rotation(&mut self, request: SetRotation) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> {
+                store.set_rotation(request.key(), request.rotate_after())
+            })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn warnings(&mut self) -> Result<()> {
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> { store.warnings(now) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn crl(&mut self) -> Result<()> {
+        let now = now();
+        match self
+            .read_store(move |store| -> Result<Response> { store.crl(now) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn generate_data_key(&mut self, bits: u16) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.generate_data_key(bits) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn decrypt_data_key(&mut self, ciphertext: Vec<u8>) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.decrypt_data_key(&ciphertext) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn verify_share(&mut self, share: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.verify_share(&share) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn wrapping_key(&mut self) -> Result<()> {
+        match self.write_store(ShareStore::new_wrapping_key).await {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_wrapped(&mut self, request: ImportWrapped) -> Result<()> {
+        match self
+            .write_store(move |store| -> Result<Response> { store.import_wrapped(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn export_wrapped(&mut self, request: ExportWrapped) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.export_wrapped(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn export_sync(&mut self, request: ExportSync) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.export_sync(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_sync(&mut self, request: ImportSync) -> Result<()> {
+        match self
+            .write_store(move |store| -> Result<Response> { store.import_sync(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn encrypt(&mut self, request: EncryptRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.encrypt(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn decrypt(&mut self, request: DecryptRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.decrypt(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_named_key(&mut self, request: NewNamedKey) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.create_named_key(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rotate_named_key(&mut self, name: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.rotate_named_key(&name) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Begin a rewrap of `prefix`, and answer with its progress while its
+    /// batches are written in the background, one blocking call each, so a
+    /// long rewrap holds no request open and `Action::RewrapStatus` can follow
+    /// it.
+    async fn rewrap(&mut self, prefix: String) -> Result<()> {
+        let begin = prefix.clone();
+        match self
+            .read_store_value(move |store| store.begin_rewrap(&begin))
+            .await
+        {
+            Ok((progress, batches)) => {
+                let mut store = self.store.clone();
+                let _handle = spawn(async move {
+                    for batch in batches {
+                        let prefix = prefix.clone();
+                        match store
+                            .on_store(move |store| Ok(store.rewrap_batch(&prefix, &batch)))
+                            .await
+                        {
+                            Ok(progress) if !progress.finished() => {}
+                            Ok(_) => break,
+                            Err(e) => {
+                                warn!("Rewrap batch did not run: {e:#}");
+                                break;
+                            }
+                        }
+                    }
+                });
+                self.response(Response::Rewrapping(progress)).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn history(&mut self, key: String) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.history(&key) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn diff(&mut self, request: DiffRequest) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.diff(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rewrap_status(&mut self) -> Result<()> {
+        match self.read_store(ShareStore::rewraps).await {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_named_keys(&mut self) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.list_named_keys() })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn store_with_key(&mut self, name: String, request: Store) -> Result<()> {
+        if self.oversized([&request]) {
+            return self.value_too_large().await;
+        }
+        match self
+            .read_store(move |store| -> Result<Response> { store.store_with_key(&name, &request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any of `entries` holds a value longer than `max_value_bytes`.
+    fn oversized<'a>(&self, entries: impl IntoIterator<Item = &'a Store>) -> bool {
+        entries.into_iter().any(|entry| {
+            u64::try_from(entry.value().len()).map_or(true, |len| len > self.max_value_bytes)
+        })
+    }
+
+    /// Refuse a value longer than `max_value_bytes`, before it reaches the
+    /// store.
+    async fn value_too_large(&mut self) -> Result<()> {
+        info!(
+            "Refusing a value over max_value_bytes ({})",
+            self.max_value_bytes
+        );
+        self.response(Response::ValueTooLarge(self.max_value_bytes))
+            .await
+    }
+
+    /// Answer with `bytes` bytes from the CSPRNG; the store is not involved,
+    /// so this works while sealed.
+    async fn random(&mut self, bytes: u32) -> Result<()> {
+        if bytes > self.max_random_bytes {
+            return self
+                .response(Response::RandomTooLarge(self.max_random_bytes))
+                .await;
+        }
+        let mut random = vec![0u8; usize::try_from(bytes)?];
+        match rand::fill(&mut random) {
+            Ok(()) => {
+                debug!(bytes, "Random bytes drawn");
+                self.response(Response::Random(random)).await?;
+            }
+            Err(e) => {
+                self.error(e.into()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn backup(&mut self, request: Backup) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.backup(&request) })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_store(&mut self) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.check_integrity() })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn status(&mut self) -> Result<()> {
+        match self
+            .read_store(move |store| -> Result<Response> { store.status() })
+            .await
+        {
+            Ok(response) => {
+                self.response(response).await?;
+            }
+            Err(e) => {
+                self.error(e).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a request the daemon could not decode with a clear error.
+    ///
+    /// Sent when the incoming bytes do not decode to a known `Action` (for
+    /// example, the client is newer than this daemon), so the client receives an
+    /// actionable message instead of an empty response.
+    pub(crate) async fn decode_error(&mut self, reason: &str) -> Result<()> {
+        self.response(Response::Error(format!(
+            "salusd could not decode the request ({reason}); the client may be newer than this daemon"
+        )))
+        .await
+    }
+
+    /// Check that an idle long-lived connection's client is still there; the
+    /// write fails once it is gone.
+    pub(crate) async fn ping(&mut self) -> Result<()> {
+        self.response(Response::Ping).await
+    }
+
+    /// Refuse a request whose action this daemon does not know, most likely
+    /// from a newer client.
+    pub(crate) async fn unknown_action(&mut self, tag: u32) -> Result<()> {
+        self.response(Response::UnknownAction(tag)).await
+    }
+
+    /// Refuse a request longer than `limit` bytes.
+    pub(crate) async fn too_large(&mut self, limit: u32) -> Result<()> {
+        self.response(Response::MessageTooLarge(limit)).await
+    }
+
+    /// A handler answering request `id` into a buffer of its own, so it can
+    /// run alongside the connection's other requests and have its response
+    /// [`forward`](Self::forward)ed once it completes. A request that asked to
+    /// be timed passes when it `received`, and its response carries the
+    /// timing.
+    pub(crate) fn for_request(&self, id: u64, received: Option<Instant>) -> ActionHandler<Vec<u8>> {
+        let cancel = CancelFlag::default();
+        let _replaced = lock(&self.in_flight).insert(id, cancel.clone());
+        ActionHandler {
+            sender: Vec::new(),
+            store: self.store.clone(),
+            key_timeout: self.key_timeout,
+            max_random_bytes: self.max_random_bytes,
+            max_value_bytes: self.max_value_bytes,
+            wire: self.wire,
+            reloader: self.reloader.clone(),
+            read_only_listener: self.read_only_listener,
+            uid: self.uid,
+            namespaces: self.namespaces.clone(),
+            plugins: self.plugins.clone(),
+            database: self.database.clone(),
+            ssh: self.ssh,
+            pki: self.pki,
+            id,
+            stopwatch: received.map(Stopwatch::new),
+            cancel,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Write a response already encoded by a handler from
+    /// [`for_request`](Self::for_request).
+    pub(crate) async fn forward(&mut self, response: &[u8]) -> Result<()> {
+        self.sender.write_all(response).await?;
+        self.sender.flush().await?;
+        Ok(())
+    }
+
+    async fn response(&mut self, message: Response) -> Result<()> {
+        let message = match self.wire {
+            Wire::Bincode => {
+                let meta = FrameMeta::builder()
+                    .id(self.id)
+                    .maybe_timing(self.stopwatch.map(|stopwatch| stopwatch.timing()))
+                    .build();
+                encode_frame_with(meta, message)?
+            }
+            Wire::Json => encode_json(&message)?,
+        };
+        self.sender.write_all(&message).await?;
+        self.sender.flush().await?;
+        Ok(())
+    }
+
+    async fn error(&mut self, err: Error) -> Result<()> {
+        if matches!(err.downcast_ref(), Some(SalusdError::Cancelled)) {
+            return self.response(Response::Cancelled).await;
+        }
+        self.response(Response::Error(err.to_string())).await
+    }
+
+    /// Run `store_fn` on the store from the blocking pool, alongside other
+    /// readers.
+    ///
+    /// Store calls hold the store's lock across database reads and writes, and
+    /// key derivation, so they never run on an executor thread where a slow disk
+    /// would stall every other connection. Anything that only uses the unlocked
+    /// key, including writes to the backend (which has its own key locks), reads the
+    /// store.
+    async fn read_store(
+        &mut self,
+        store_fn: impl FnOnce(&ShareStore) -> Result<Response> + Send + 'static,
+    ) -> Result<Response> {
+        self.read_store_value(store_fn).await
+    }
+
+    /// [`read_store`](Self::read_store), for a store call that answers with
+    /// a value rather than a response.
+    async fn read_store_value<V>(
+        &mut self,
+        store_fn: impl FnOnce(&ShareStore) -> Result<V> + Send + 'static,
+    ) -> Result<V>
+    where
+        V: Send + 'static,
+    {
+        let store = self.store.clone();
+        let (cancel, uid) = (self.cancel.clone(), self.uid);
+        let (response, spent) = spawn_blocking(move || {
+            let store = match store.read() {
+                Ok(share_store) => share_store,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            written_by(uid, || timed(&cancel, || store_fn(&store)))
+        })
+        .await?;
+        self.record(spent);
+        response
+    }
+
+    /// Run `store_fn` on the store from the blocking pool, holding it alone:
+    /// for changes to the shares, the unlocked key, or the wrapping key.
+    async fn write_store(
+        &mut self,
+        store_fn: impl FnOnce(&mut ShareStore) -> Result<Response> + Send + 'static,
+    ) -> Result<Response> {
+        let store = self.store.clone();
+        let (cancel, uid) = (self.cancel.clone(), self.uid);
+        let (response, spent) = spawn_blocking(move || {
+            let mut store = match store.write() {
+                Ok(share_store) => share_store,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            written_by(uid, || timed(&cancel, || store_fn(&mut store)))
+        })
+        .await?;
+        self.record(spent);
+        response
+    }
+
+    /// Count a store call toward the request's timing, if it is timed.
+    fn record(&mut self, spent: Spent) {
+        if let Some(stopwatch) = self.stopwatch.as_mut() {
+            stopwatch.record(spent);
+        }
+    }
+}
+
+impl<T> OnStore for ActionHandler<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    async fn on_store<V>(
+        &mut self,
+        store_fn: impl FnOnce(&ShareStore) -> Result<V> + Send + 'static,
+    ) -> Result<V>
+    where
+        V: Send + 'static,
+    {
+        self.read_store_value(store_fn).await
+    }
+}
+
+/// Run a store call, noting the time it took once it held the store, and
+/// stopping it once `cancel` is raised.
+fn timed<V>(cancel: &CancelFlag, store_fn: impl FnOnce() -> Result<V>) -> (Result<V>, Spent) {
+    let started = Instant::now();
+    let (response, storage) = cancellable(cancel, || storage_time(store_fn));
+    let spent = Spent {
+        started,
+        took: started.elapsed(),
+        storage,
+    };
+    (response, spent)
+}
+
+impl ActionHandler<Vec<u8>> {
+    /// Everything the handler has written: the encoded response. The request
+    /// can no longer be cancelled.
+    pub(crate) fn into_written(self) -> Vec<u8> {
+        let _done = lock(&self.in_flight).remove(&self.id);
+        self.sender
+    }
+}
+
+/// The connection's requests in progress, even if a handler panicked while
+/// holding them.
+fn lock(
+    in_flight: &Mutex<HashMap<u64, CancelFlag>>,
+) -> std::sync::MutexGuard<'_, HashMap<u64, CancelFlag>> {
+    match in_flight.lock() {
+        Ok(in_flight) => in_flight,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Whether `action` may change the store, and so is refused while the daemon
+/// is read-only.
+///
+/// Signing, SSH certificates included, counts a use of the key, and encrypting under a named key may
+/// rotate it first, so both are changes, as is issuing or revoking a
+/// certificate, which records it. Rewrapping seals stored values again in
+/// place. Unlocking and locking only touch the key held in memory.
+fn mutates(action: &Action) -> bool {
+    match action {
+        Action::GenShares(..)
+        | Action::InitStore(_)
+        | Action::RefreshShares
+        | Action::Store(_)
+        | Action::Delete(_)
+        | Action::StoreBatch(_)
+        | Action::Generate(_)
+        | Action::CreateSigningKey(_)
+        | Action::Sign(_)
+        | Action::Hmac(_)
+        | Action::SignSshKey(_)
+        | Action::CreateCa(_)
+        | Action::ImportCa(_)
+        | Action::IssueCert(_)
+        | Action::RevokeCert(_)
+        | Action::ImportWrapped(_)
+        | Action::CreateKey(_)
+        | Action::RotateKey(_)
+        | Action::Rewrap(_)
+        | Action::StoreWithKey(..)
+        | Action::BeginUpload(_)
+        | Action::UploadChunk(_)
+        | Action::FinishUpload(_)
+        | Action::ImportSync(_)
+        | Action::Patch(_)
+        | Action::Link(_)
+        | Action::SetRotation(_)
+        | Action::RenewLease(_)
+        | Action::RevokeLease(_) => true,
+        Action::Encrypt(request) => request.key().is_some(),
+        Action::DeletePrefix(request) => !request.dry_run(),
+        Action::Share(_)
+        | Action::Unlock(_)
+        | Action::Lock
+        | Action::Read(_)
+        | Action::ReadField(_)
+        | Action::Exists(_)
+        | Action::GetThreshold
+        | Action::FindKey(_)
+        | Action::Search(_)
+        | Action::Status
+        | Action::ReadPrefix(_)
+        | Action::VerifyShare(_)
+        | Action::Verify(_)
+        | Action::GenerateDataKey(_)
+        | Action::DecryptDataKey(_)
+        | Action::Random(_)
+        | Action::WrappingKey
+        | Action::ExportWrapped(_)
+        | Action::Decrypt(_)
+        | Action::ListKeys
+        | Action::Backup(_)
+        | Action::CheckStore
+        | Action::ReadChunk(_)
+        | Action::ExportSync(_)
+        | Action::SetReadOnly(_)
+        | Action::Ping
+        | Action::Cancel(_)
+        | Action::ReloadConfig
+        | Action::MintCredential(_)
+        | Action::ListPlugins
+        | Action::ListCerts
+        | Action::GetCrl
+        | Action::Warnings
+        | Action::RewrapStatus
+        | Action::History(_)
+        | Action::Diff(_)
+        | Action::ListApprovals
+        | Action::Approve(_)
+        | Action::DenyApproval(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, RwLock, mpsc},
+        thread,
+        time::Instant,
+    };
+
+    use anyhow::{Result, anyhow, bail};
+    use libsalus::{
+        Action, Link, MintCredential, NewNamedKey, Response, SearchQuery, Share, SignRequest,
+        Store, StoreBatch, UnlockTimeout, decode_frame, decode_frame_with_meta, decode_json,
+        encode_frame,
+    };
+    use tokio::{
+        spawn,
+        time::{Duration, sleep},
+    };
+
+    use super::{ActionHandler, Namespaces, Wire};
+    use crate::{
+        config::{ConfigSalusd, reload::Reloader},
+        db::{SharedBackend, backend::MemoryBackend},
+        plugin::Plugins,
+        store::{ShareStore, test::unlocked_store},
+    };
+
+    fn temp_store() -> Arc<RwLock<ShareStore>> {
+        Arc::new(RwLock::new(
+            ShareStore::builder()
+                .backend(Arc::new(SharedBackend::new(MemoryBackend::default())))
+                .build(),
+        ))
+    }
+
+    fn handler(store: Arc<RwLock<ShareStore>>) -> ActionHandler<Vec<u8>> {
+        ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(store)
+            .key_timeout(0u64)
+            .build()
+    }
+
+    async fn run(action: Action) -> Result<Response> {
+        let mut handler = handler(temp_store());
+        handler.action_handler(action).await?;
+        decode_frame::<Response>(&handler.sender)
+    }
+
+    /// Run one action on a persistent handler, returning the decoded response.
+    ///
+    /// Unlike [`run`], the handler (and its store) survives across calls so a
+    /// test can drive a full gen/unlock/store/search sequence.
+    async fn run_on(handler: &mut ActionHandler<Vec<u8>>, action: Action) -> Result<Response> {
+        handler.sender.clear();
+        handler.action_handler(action).await?;
+        decode_frame::<Response>(&handler.sender)
+    }
+
+    #[tokio::test]
+    async fn gen_shares_responds_with_shares() -> Result<()> {
+        match run(Action::GenShares(5, 3)).await? {
+            Response::Shares(shares) => assert_eq!(shares.shares().len(), 5),
+            other => bail!("expected shares, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gen_shares_refuses_unusable_parameters_with_one_response() -> Result<()> {
+        let mut handler = handler(temp_store());
+        handler.action_handler(Action::GenShares(2, 3)).await?;
+        let response = decode_frame::<Response>(&handler.sender)?;
+        let Response::InvalidShareParameters(reason) = &response else {
+            bail!("expected refused parameters, got {response:?}");
+        };
+        assert!(reason.contains("cannot exceed"));
+        assert_eq!(handler.sender.len(), encode_frame(response)?.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_threshold_responds_with_default() -> Result<()> {
+        assert!(matches!(
+            run(Action::GetThreshold).await?,
+            Response::Threshold(3)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_before_unlock_errors() -> Result<()> {
+        assert!(matches!(
+            run(Action::Read("k".to_string())).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn store_before_unlock_errors() -> Result<()> {
+        let store = Store::builder().key("k").value("v").build();
+        assert!(matches!(
+            run(Action::Store(store)).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_before_unlock_errors() -> Result<()> {
+        assert!(matches!(
+            run(Action::Delete("k".to_string())).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_share_works_while_sealed() -> Result<()> {
+        let mut handler = handler(temp_store());
+        let shares = match run_on(&mut handler, Action::GenShares(5, 3)).await? {
+            Response::Shares(shares) => shares.shares().to_vec(),
+            other => bail!("expected shares, got {other:?}"),
+        };
+        let Some(last) = shares.last() else {
+            bail!("no shares");
+        };
+        let action = Action::VerifyShare(Share::builder().share(last.clone()).build());
+        assert!(matches!(
+            run_on(&mut handler, action).await?,
+            Response::ShareVerified(5)
+        ));
+        let action = Action::VerifyShare(Share::builder().share("garbage").build());
+        assert!(matches!(
+            run_on(&mut handler, action).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_read_only_daemon_refuses_changes_but_serves_reads() -> Result<()> {
+        let mut handler = handler(temp_store());
+        assert!(matches!(
+            run_on(&mut handler, Action::SetReadOnly(true)).await?,
+            Response::Success
+        ));
+        let store = Store::builder().key("k").value("v").build();
+        assert!(matches!(
+            run_on(&mut handler, Action::Store(store)).await?,
+            Response::ReadOnly
+        ));
+        let Response::Status(status) = run_on(&mut handler, Action::Status).await? else {
+            bail!("expected status");
+        };
+        assert!(status.read_only());
+        // The read is answered (the store is sealed), not refused.
+        assert!(matches!(
+            run_on(&mut handler, Action::Read("k".to_string())).await?,
+            Response::Error(_)
+        ));
+        // Only an unlocked store can be reopened to writes.
+        assert!(matches!(
+            run_on(&mut handler, Action::SetReadOnly(false)).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_read_only_listener_refuses_changes_and_mode_switches() -> Result<()> {
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .read_only_listener(true)
+            .build();
+        assert!(matches!(
+            run_on(&mut handler, Action::GenShares(5, 3)).await?,
+            Response::ReadOnly
+        ));
+        assert!(matches!(
+            run_on(&mut handler, Action::SetReadOnly(false)).await?,
+            Response::ReadOnly
+        ));
+        let Response::Status(status) = run_on(&mut handler, Action::Status).await? else {
+            bail!("expected status");
+        };
+        // The store itself is left writable, for the other listeners.
+        assert!(!status.read_only());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn namespace_rules_are_checked_before_the_store() -> Result<()> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "[namespace.archive]\npolicies = [\"read-only\"]\n",
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .namespaces(Arc::new(Namespaces::from(&config)))
+            .build();
+        let Response::Error(refusal) =
+            run_on(&mut handler, Action::Delete("archive/2024".to_string())).await?
+        else {
+            bail!("expected the namespace to refuse the delete");
+        };
+        assert_eq!(refusal, "Namespace 'archive' is read-only");
+        // Elsewhere, the store answers: it is not unlocked.
+        let Response::Error(refusal) =
+            run_on(&mut handler, Action::Delete("web/2024".to_string())).await?
+        else {
+            bail!("expected the locked store to refuse the delete");
+        };
+        assert_eq!(refusal, "Store not unlocked");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reads_wait_on_approval_from_other_users() -> Result<()> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "[namespace.payments]\napprovals = 1\napprovers = [1001, 1002]\n",
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let store = Arc::new(RwLock::new(unlocked_store()?));
+        let namespaces = Arc::new(Namespaces::from(&config));
+        let as_user = |uid: Option<u32>| {
+            ActionHandler::builder()
+                .sender(Vec::<u8>::new())
+                .store(store.clone())
+                .namespaces(namespaces.clone())
+                .maybe_uid(uid)
+                .build()
+        };
+        let (mut requester, mut approver, mut other) = (
+            as_user(Some(1000)),
+            as_user(Some(1001)),
+            as_user(Some(1003)),
+        );
+        let read = || Action::Read("payments/card".to_string());
+
+        let Response::ApprovalPending(held) = run_on(&mut requester, read()).await? else {
+            bail!("expected the read to be held");
+        };
+        assert!(!held.granted());
+        let Response::Error(refusal) = run_on(&mut as_user(None), read()).await? else {
+            bail!("expected a read by an unknown user to be refused");
+        };
+        assert!(refusal.contains("'payments'"));
+        let Response::Error(refusal) =
+            run_on(&mut requester, Action::ReadPrefix("pay".to_string())).await?
+        else {
+            bail!("expected a prefix read to be refused");
+        };
+        assert!(refusal.contains("one key at a time"));
+        let Response::Approvals(listed) = run_on(&mut other, Action::ListApprovals).await? else {
+            bail!("expected the held read to be listed");
+        };
+        assert_eq!(listed, vec![held.clone()]);
+
+        let approve = || Action::Approve(held.id().clone());
+        let Response::Error(refusal) = run_on(&mut other, approve()).await? else {
+            bail!("expected a user who is no approver to be refused");
+        };
+        assert_eq!(
+            refusal,
+            "Only the approvers of namespace 'payments' may approve its reads"
+        );
+        let Response::Approved(granted) = run_on(&mut approver, approve()).await? else {
+            bail!("expected the read to be approved");
+        };
+        assert!(granted.granted());
+        // Released: the store answers now.
+        assert!(matches!(
+            run_on(&mut requester, read()).await?,
+            Response::Value(None)
+        ));
+
+        // The requester may withdraw a read, and then it is gone.
+        let deny = Action::DenyApproval(held.id().clone());
+        assert!(matches!(
+            run_on(&mut requester, deny.clone()).await?,
+            Response::Success
+        ));
+        assert!(matches!(
+            run_on(&mut approver, deny).await?,
+            Response::ApprovalNotFound
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_read_through_an_older_alias_waits_on_approval() -> Result<()> {
+        let store = Arc::new(RwLock::new(unlocked_store()?));
+        let mut before = handler(store.clone());
+        let stored = Store::builder().key("payments/card").value("1234").build();
+        assert!(matches!(
+            run_on(&mut before, Action::Store(stored)).await?,
+            Response::Success
+        ));
+        let link = Link::builder()
+            .alias("web/card")
+            .target("payments/card")
+            .build();
+        assert!(matches!(
+            run_on(&mut before, Action::Link(link)).await?,
+            Response::Success
+        ));
+
+        // A reload then asks for approvals on the target's namespace.
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "[namespace.payments]\napprovals = 1\n",
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let mut after = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(store)
+            .namespaces(Arc::new(Namespaces::from(&config)))
+            .uid(1000)
+            .build();
+        let Response::ApprovalPending(held) =
+            run_on(&mut after, Action::Read("web/card".to_string())).await?
+        else {
+            bail!("expected the read through the alias to be held");
+        };
+        assert_eq!(held.key(), "payments/card");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_rewrap_runs_in_the_background_and_reports_progress() -> Result<()> {
+        let store = unlocked_store()?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app-a").build())?;
+        for index in 0..70 {
+            let value = Store::builder()
+                .key(format!("db/{index:02}"))
+                .value("secret")
+                .build();
+            let _stored = store.store_with_key("app-a", &value)?;
+        }
+        let _rotated = store.rotate_named_key("app-a")?;
+        let mut handler = handler(Arc::new(RwLock::new(store)));
+
+        let Response::Rewrapping(begun) =
+            run_on(&mut handler, Action::Rewrap("db/".to_string())).await?
+        else {
+            bail!("expected the rewrap to begin");
+        };
+        assert_eq!(begun.stale(), 70);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let finished = loop {
+            let Response::Rewraps(rewraps) = run_on(&mut handler, Action::RewrapStatus).await?
+            else {
+                bail!("expected the rewraps to be listed");
+            };
+            let Some(progress) = rewraps.into_iter().find(|p| p.prefix() == "db/") else {
+                bail!("expected the rewrap to be listed");
+            };
+            if progress.finished() {
+                break progress;
+            }
+            if Instant::now() > deadline {
+                bail!("the rewrap did not finish: {progress:?}");
+            }
+            sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(finished.batches(), 2);
+        assert_eq!(finished.rewrapped(), 70);
+        assert!(finished.error().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_names_are_only_listed_while_unlocked() -> Result<()> {
+        let mut handler = handler(temp_store());
+        for action in [
+            Action::ListApprovals,
+            Action::Warnings,
+            Action::RewrapStatus,
+        ] {
+            let Response::Error(refusal) = run_on(&mut handler, action).await? else {
+                bail!("expected the locked store to refuse the listing");
+            };
+            assert_eq!(refusal, "Store not unlocked");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn credentials_are_only_minted_while_unlocked() -> Result<()> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "[plugins.pg]\ncommand = \"/bin/true\"\nroles = [\"ro\"]\n",
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .plugins(Arc::new(Plugins::from(&config)))
+            .build();
+        let mint = |role: &str| {
+            Action::MintCredential(MintCredential::builder().plugin("pg").role(role).build())
+        };
+        let Response::Error(refusal) = run_on(&mut handler, mint("ro")).await? else {
+            bail!("expected the locked store to refuse the mint");
+        };
+        assert_eq!(refusal, "Store not unlocked");
+        let Response::Error(refusal) = run_on(&mut handler, mint("rw")).await? else {
+            bail!("expected the plugin's roles to refuse the mint");
+        };
+        assert_eq!(
+            refusal,
+            "Plugin 'pg' does not mint credentials for role 'rw'"
+        );
+        // Minting changes nothing in the store, so a read-only daemon does it.
+        assert!(!super::mutates(&mint("ro")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_config_reports_what_changed() -> Result<()> {
+        // A daemon without a reloader (as in these tests) cannot reload.
+        assert!(matches!(
+            run(Action::ReloadConfig).await?,
+            Response::Error(_)
+        ));
+        let config = ConfigSalusd::default();
+        let reloader = Reloader::new(config.clone(), Box::new(move || Ok(config.clone())), None);
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .reloader(Arc::new(reloader))
+            .build();
+        // Reloading is not a change to the store, so a read-only daemon does it.
+        assert!(matches!(
+            run_on(&mut handler, Action::SetReadOnly(true)).await?,
+            Response::Success
+        ));
+        match run_on(&mut handler, Action::ReloadConfig).await? {
+            Response::ConfigReloaded(report) => {
+                assert!(report.applied().is_empty());
+                assert!(report.needs_restart().is_empty());
+            }
+            other => bail!("expected a reload report, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lock_responds_success() -> Result<()> {
+        assert!(matches!(run(Action::Lock).await?, Response::Success));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_held_store_does_not_stall_other_connections() -> Result<()> {
+        // The test runtime has one thread, so a store call made on it would
+        // block everything until the lock is released.
+        let store = temp_store();
+        let (held_tx, held_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let holder = {
+            let store = store.clone();
+            thread::spawn(move || {
+                let _guard = store.write();
+                let _sent = held_tx.send(());
+                let _released = release_rx.recv();
+            })
+        };
+        held_rx.recv()?;
+
+        let mut waiting = handler(store);
+        let pending = spawn(async move {
+            waiting
+                .action_handler(Action::Status)
+                .await
+                .map(|()| waiting.sender)
+        });
+        sleep(Duration::from_millis(20)).await;
+        assert!(matches!(run(Action::Random(8)).await?, Response::Random(_)));
+
+        release_tx.send(())?;
+        holder
+            .join()
+            .map_err(|_| anyhow!("the lock holder panicked"))?;
+        assert!(matches!(
+            decode_frame::<Response>(&pending.await??)?,
+            Response::Status(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn random_works_sealed_up_to_the_cap() -> Result<()> {
+        assert!(matches!(
+            run(Action::Random(32)).await?,
+            Response::Random(ref bytes) if bytes.len() == 32
+        ));
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .max_random_bytes(16)
+            .build();
+        assert!(matches!(
+            run_on(&mut handler, Action::Random(17)).await?,
+            Response::RandomTooLarge(16)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn values_over_the_cap_are_refused_before_the_store() -> Result<()> {
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .max_value_bytes(4)
+            .build();
+        let store = |value: &str| Store::builder().key("k").value(value).build();
+        assert!(matches!(
+            run_on(&mut handler, Action::Store(store("12345"))).await?,
+            Response::ValueTooLarge(4)
+        ));
+        assert!(matches!(
+            run_on(
+                &mut handler,
+                Action::StoreBatch(
+                    StoreBatch::builder()
+                        .entries(vec![store("1"), store("12345")])
+                        .build()
+                )
+            )
+            .await?,
+            Response::ValueTooLarge(4)
+        ));
+        // Within the cap, the request reaches the (sealed) store.
+        assert!(matches!(
+            run_on(&mut handler, Action::Store(store("1234"))).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_shares_before_unlock_errors() -> Result<()> {
+        assert!(matches!(
+            run(Action::RefreshShares).await?,
+            Response::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signing_before_unlock_errors() -> Result<()> {
+        let action = Action::Sign(SignRequest::builder().key("k").message(vec![]).build());
+        assert!(matches!(run(action).await?, Response::Error(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_before_unlock_errors() -> Result<()> {
+        let action = Action::Search(SearchQuery::builder().query("x").build());
+        assert!(matches!(run(action).await?, Response::Error(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decode_error_responds_with_error() -> Result<()> {
+        // An undecodable request must produce a `Response::Error` the client can
+        // render, not an empty response that decodes to an opaque error.
+        let mut handler = handler(temp_store());
+        handler.decode_error("unexpected variant").await?;
+        match decode_frame::<Response>(&handler.sender)? {
+            Response::Error(msg) => {
+                assert!(msg.contains("could not decode"));
+                assert!(msg.contains("unexpected variant"));
+            }
+            other => bail!("expected an error response, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_handler_answers_with_a_json_line() -> Result<()> {
+        let mut handler = ActionHandler::builder()
+            .sender(Vec::<u8>::new())
+            .store(temp_store())
+            .wire(Wire::Json)
+            .build();
+        handler.action_handler(Action::GetThreshold).await?;
+        handler.action_handler(Action::GetThreshold).await?;
+        let mut lines = handler.sender.split_inclusive(|byte| *byte == b'\n');
+        for _ in 0..2 {
+            let Some(line) = lines.next() else {
+                bail!("expected a line per request");
+            };
+            match decode_json::<Response>(line)? {
+                Response::Threshold(threshold) => assert_eq!(threshold, 3),
+                other => bail!("expected the threshold, got {other:?}"),
+            }
+        }
+        assert!(lines.next().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn timed_requests_carry_their_timing() -> Result<()> {
+        let handler = handler(temp_store());
+        let mut timed = handler.for_request(7, Some(Instant::now()));
+        timed.action_handler(Action::Status).await?;
+        let (meta, response) = decode_frame_with_meta::<Response>(&timed.into_written())?;
+        assert!(matches!(response, Response::Status(_)));
+        assert_eq!(meta.id(), 7);
+        let Some(timing) = meta.timing() else {
+            bail!("expected a timing");
+        };
+        assert!(timing.total() >= timing.queued());
+        let mut untimed = handler.for_request(8, None);
+        untimed.action_handler(Action::Status).await?;
+        let (meta, _response) = decode_frame_with_meta::<Response>(&untimed.into_written())?;
+        assert!(meta.timing().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_request_leaves_the_store_untouched() -> Result<()> {
+        let mut handler = handler(temp_store());
+        let mut doomed = handler.for_request(3, None);
+        let mut canceller = handler.for_request(4, None);
+        canceller.action_handler(Action::Cancel(3)).await?;
+        assert!(matches!(
+            decode_frame::<Response>(&canceller.into_written())?,
+            Response::Success
+        ));
+        doomed.action_handler(Action::GenShares(5, 3)).await?;
+        assert!(matches!(
+            decode_frame::<Response>(&doomed.into_written())?,
+            Response::Cancelled
+        ));
+        assert!(matches!(
+            run_on(&mut handler, Action::GenShares(5, 3)).await?,
+            Response::Shares(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_finished_request_is_an_error() -> Result<()> {
+        let handler = handler(temp_store());
+        let mut finished = handler.for_request(3, None);
+        finished.action_handler(Action::Status).await?;
+        let _written = finished.into_written();
+        let mut canceller = handler.for_request(4, None);
+        canceller.action_handler(Action::Cancel(3)).await?;
+        match decode_frame::<Response>(&canceller.into_written())? {
+            Response::Error(message) => assert!(message.contains("no request 3"), "{message}"),
+            other => bail!("expected an error, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pings_go_both_ways() -> Result<()> {
+        assert!(matches!(run(Action::Ping).await?, Response::Pong));
+        let mut handler = handler(temp_store());
+        handler.ping().await?;
+        assert!(matches!(
+            decode_frame::<Response>(&handler.sender)?,
+            Response::Ping
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_action_responds_with_its_tag() -> Result<()> {
+        let mut handler = handler(temp_store());
+        handler.unknown_action(200).await?;
+        match decode_frame::<Response>(&handler.sender)? {
+            Response::UnknownAction(tag) => assert_eq!(tag, 200),
+            other => bail!("expected an unknown action, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn too_large_responds_with_the_limit() -> Result<()> {
+        let mut handler = handler(temp_store());
+        handler.too_large(1024).await?;
+        match decode_frame::<Response>(&handler.sender)? {
+            Response::MessageTooLarge(limit) => assert_eq!(limit, 1024),
+            other => bail!("expected message too large, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unlock_then_store_and_search_succeeds() -> Result<()> {
         // A non-zero key timeout exercises the unlock arm that arms the
         // auto-clear timer; the timer never fires within the test.
         let mut handler = ActionHandler::builder()
             .sender(Vec::<u8>::new())
-            .store(temp_store()?)
+            .store(temp_store())
             .key_timeout(3600u64)
             .build();
 
@@ -436,6 +2573,44 @@ mod test {
             Response::Matches(matches) => assert!(matches.iter().any(|k| k == "aws-prod-key")),
             other => bail!("expected matches, got {other:?}"),
         }
+
+        match run_on(&mut handler, Action::Status).await? {
+            Response::Status(status) => {
+                assert!(status.initialized());
+                assert!(!status.sealed());
+                assert_eq!(status.shares_collected(), 0);
+                assert!(
+                    status
+                        .key_timeout_remaining()
+                        .is_some_and(|secs| secs > 3500)
+                );
+                // Four dash-separated groups of four hex digits.
+                assert!(
+                    status
+                        .fingerprint()
+                        .as_deref()
+                        .is_some_and(|fp| fp.len() == 19 && fp.split('-').count() == 4)
+                );
+            }
+            other => bail!("expected status, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn status_on_fresh_store_is_sealed_and_uninitialized() -> Result<()> {
+        match run(Action::Status).await? {
+            Response::Status(status) => {
+                assert!(!status.initialized());
+                assert!(status.sealed());
+                assert_eq!(status.threshold(), 3);
+                assert_eq!(status.num_shares(), 5);
+                assert_eq!(status.key_timeout_remaining(), None);
+                assert_eq!(status.daemon_version(), env!("CARGO_PKG_VERSION"));
+                assert_eq!(status.fingerprint(), &None);
+            }
+            other => bail!("expected status, got {other:?}"),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,699 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The `[namespace.<name>]` rules, checked on each request against the keys
+//! it names, and the approvals a read of some of them waits on.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use libsalus::{Action, Store, SyncStrategy};
+use tracing::info;
+
+use crate::{
+    config::{ConfigSalusd, NamespaceAudit, NamespacePolicy, NamespaceSettings},
+    error::Error,
+};
+
+/// The namespaces with rules of their own, by name
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Namespaces(BTreeMap<String, NamespaceSettings>);
+
+impl From<&ConfigSalusd> for Namespaces {
+    fn from(config: &ConfigSalusd) -> Self {
+        Self(config.namespace().clone())
+    }
+}
+
+/// How a request uses a key
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Use {
+    Read,
+    /// Looks a key up without its value
+    Stat,
+    /// Stores a value of `bytes`, when the request says, replacing any value
+    /// already there when `overwrite` is set
+    Write {
+        bytes: Option<u64>,
+        overwrite: bool,
+    },
+    Delete,
+}
+
+/// A key a request uses or, for a `prefix`, every key under it
+#[derive(Clone, Copy, Debug)]
+struct Touch<'a> {
+    /// The request, as the audit log names it
+    operation: &'static str,
+    key: &'a str,
+    prefix: bool,
+    usage: Use,
+}
+
+impl<'a> Touch<'a> {
+    fn key(operation: &'static str, key: &'a str, usage: Use) -> Self {
+        Self {
+            operation,
+            key,
+            prefix: false,
+            usage,
+        }
+    }
+
+    fn prefix(operation: &'static str, prefix: &'a str, usage: Use) -> Self {
+        Self {
+            operation,
+            key: prefix,
+            prefix: true,
+            usage,
+        }
+    }
+
+    fn stored(operation: &'static str, store: &'a Store) -> Self {
+        let usage = Use::Write {
+            bytes: u64::try_from(store.value().len()).ok(),
+            overwrite: store.force(),
+        };
+        Self::key(operation, store.key(), usage)
+    }
+}
+
+/// What releases the value under a key whose reads are held: the rule of the
+/// innermost namespace holding it that asks for approvals
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ApprovalRule {
+    /// The namespace the rule is from
+    pub(crate) namespace: String,
+    /// How many users must approve
+    pub(crate) required: u32,
+    /// Who may approve, by uid; anyone but the requester when empty
+    pub(crate) approvers: Vec<u32>,
+    /// How long a held read waits, and a granted one lasts, in seconds
+    pub(crate) window: u64,
+}
+
+impl ApprovalRule {
+    /// Whether the user `uid` may approve under this rule.
+    pub(crate) fn admits(&self, uid: u32) -> bool {
+        self.approvers.is_empty() || self.approvers.contains(&uid)
+    }
+}
+
+impl Namespaces {
+    /// The key `action` reads following any aliases, when there are rules to
+    /// check the key it reaches against.
+    pub(crate) fn followed<'a>(&self, action: &'a Action) -> Option<&'a str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        match action {
+            Action::Read(key) | Action::Exists(key) | Action::History(key) => Some(key),
+            Action::Diff(request) => Some(request.key()),
+            Action::ReadField(request) => Some(request.key()),
+            Action::ExportWrapped(request) => Some(request.key()),
+            _ => None,
+        }
+    }
+
+    /// Whether a namespace `action` uses is only served for a while after an
+    /// unlock, so the time since the store was unlocked is needed to
+    /// [`admit`](Self::admit) it. `stored` is the key the
+    /// [`followed`](Self::followed) key's aliases lead to.
+    pub(crate) fn timed(&self, action: &Action, stored: Option<&str>) -> bool {
+        !self.0.is_empty()
+            && touches_through(action, stored).iter().any(|touch| {
+                self.covering(touch)
+                    .any(|(_, settings)| settings.key_timeout().is_some())
+            })
+    }
+
+    /// Check `action` against the rules of every namespace it uses, the store
+    /// having been unlocked `unlocked_for` ago, and write it to the audit log
+    /// where a namespace asks for that.
+    ///
+    /// A read through an alias meets the rules of the key it reaches,
+    /// `stored`, as well as those of the key it names: the link was checked
+    /// against the rules of its day, which may since have changed.
+    ///
+    /// # Errors
+    ///
+    /// * Returns the first rule `action` breaks.
+    pub(crate) fn admit(
+        &self,
+        action: &Action,
+        stored: Option<&str>,
+        unlocked_for: Option<Duration>,
+    ) -> Result<(), Error> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let touches = touches_through(action, stored);
+        for touch in &touches {
+            for (name, settings) in self.covering(touch) {
+                check(name, settings, touch, unlocked_for)?;
+            }
+        }
+        for touch in &touches {
+            for (name, settings) in self.covering(touch) {
+                if audited(settings.audit(), touch.usage) {
+                    info!(
+                        target: "salusd::audit",
+                        namespace = name,
+                        operation = touch.operation,
+                        key = touch.key,
+                        prefix = touch.prefix,
+                        "Namespace used"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The key `action` reads and the rule releasing it, when it reads one
+    /// whose reads are held for approval.
+    ///
+    /// A read through an alias is held by the rule of the key it reaches,
+    /// `stored`, which is the key answered, before that of the key it names,
+    /// so an alias made before the rule cannot read around it.
+    ///
+    /// Only a read of one key is held. A prefix read, a diff or a sync
+    /// reaching into a namespace that asks for approvals is refused, as is a
+    /// link to one of its keys, which would let the key be read under another
+    /// name.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`Error::NamespaceNeedsApproval`] for a read that cannot be
+    ///   held.
+    pub(crate) fn approval<'a>(
+        &self,
+        action: &'a Action,
+        stored: Option<&'a str>,
+    ) -> Result<Option<(&'a str, ApprovalRule)>, Error> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let held = match action {
+            Action::Read(key) => Some(key.as_str()),
+            Action::ReadField(request) => Some(request.key().as_str()),
+            Action::ExportWrapped(request) => Some(request.key().as_str()),
+            _ => None,
+        };
+        if held.is_none() {
+            for touch in touches_through(action, stored) {
+                if touch.usage != Use::Read {
+                    continue;
+                }
+                if let Some((name, _)) = self
+                    .covering(&touch)
+                    .find(|(_, settings)| settings.approvals().is_some())
+                {
+                    return Err(Error::NamespaceNeedsApproval(name.to_string()));
+                }
+            }
+        }
+        Ok(held.and_then(|key| {
+            stored
+                .into_iter()
+                .chain([key])
+                .find_map(|key| Some((key, self.approval_rule(key)?)))
+        }))
+    }
+
+    /// The rule releasing the value under `key`, when its reads are held for
+    /// approval.
+    pub(crate) fn approval_rule(&self, key: &str) -> Option<ApprovalRule> {
+        let touch = Touch::key("read", key, Use::Read);
+        self.covering(&touch)
+            .filter_map(|(name, settings)| Some((name, settings.approvals()?, settings)))
+            .max_by_key(|(name, ..)| name.len())
+            .map(|(name, required, settings)| ApprovalRule {
+                namespace: name.to_string(),
+                required,
+                approvers: settings.approvers().clone(),
+                window: settings.approval_window(),
+            })
+    }
+
+    /// The namespaces `touch` reaches into, with their rules.
+    fn covering<'a>(
+        &'a self,
+        touch: &Touch<'_>,
+    ) -> impl Iterator<Item = (&'a str, &'a NamespaceSettings)> {
+        let (key, prefix) = (touch.key, touch.prefix);
+        self.0
+            .iter()
+            .filter(move |(name, _)| covers(name, key, prefix))
+            .map(|(name, settings)| (name.as_str(), settings))
+    }
+}
+
+/// Whether namespace `name` holds `key` or, for a `prefix`, any key under it.
+///
+/// Namespaces nest, so a key under `payments/eu/` is held by both `payments`
+/// and `payments/eu`, and meets the rules of each.
+fn covers(name: &str, key: &str, prefix: bool) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    let within = key
+        .strip_prefix(name)
+        .is_some_and(|rest| rest.starts_with('/'));
+    within || (prefix && format!("{name}/").starts_with(key))
+}
+
+/// Refuse `touch` if it breaks a rule of namespace `name`.
+fn check(
+    name: &str,
+    settings: &NamespaceSettings,
+    touch: &Touch<'_>,
+    unlocked_for: Option<Duration>,
+) -> Result<(), Error> {
+    if let (Some(timeout), Some(unlocked_for)) = (settings.key_timeout(), unlocked_for)
+        && unlocked_for > Duration::from_secs(timeout)
+    {
+        return Err(Error::NamespaceUnlockExpired(name.to_string(), timeout));
+    }
+    let refuses = |policy| settings.policies().contains(&policy);
+    match touch.usage {
+        Use::Write { .. } | Use::Delete if refuses(NamespacePolicy::ReadOnly) => {
+            Err(Error::NamespaceReadOnly(name.to_string()))
+        }
+        Use::Delete if refuses(NamespacePolicy::NoDelete) => {
+            Err(Error::NamespaceNoDelete(name.to_string()))
+        }
+        Use::Write {
+            overwrite: true, ..
+        } if refuses(NamespacePolicy::NoOverwrite) => {
+            Err(Error::NamespaceNoOverwrite(name.to_string()))
+        }
+        Use::Write {
+            bytes: Some(bytes), ..
+        } => match settings.max_value_bytes() {
+            Some(limit) if bytes > limit => {
+                Err(Error::NamespaceValueTooLarge(name.to_string(), limit))
+            }
+            _ => Ok(()),
+        },
+        Use::Read | Use::Stat | Use::Write { .. } | Use::Delete => Ok(()),
+    }
+}
+
+/// Whether a namespace audited at `audit` logs a request that uses its key
+/// this way.
+fn audited(audit: Option<NamespaceAudit>, usage: Use) -> bool {
+    match audit {
+        None => false,
+        Some(NamespaceAudit::Writes) => matches!(usage, Use::Write { .. } | Use::Delete),
+        Some(NamespaceAudit::All) => true,
+    }
+}
+
+/// The keys and prefixes `action` uses, and how, with `stored`, the key a
+/// read of one key reaches through its aliases, used as that key is.
+fn touches_through<'a>(action: &'a Action, stored: Option<&'a str>) -> Vec<Touch<'a>> {
+    let mut touches = touches(action);
+    if let Some(stored) = stored {
+        let through = touches
+            .iter()
+            .filter(|touch| !touch.prefix && touch.key != stored)
+            .map(|touch| Touch {
+                key: stored,
+                ..*touch
+            })
+            .collect::<Vec<_>>();
+        touches.extend(through);
+    }
+    touches
+}
+
+/// The keys and prefixes `action` uses, and how.
+///
+/// A chunk or an upload being finished is only known by its id; the key it
+/// belongs to was checked when the read or upload began.
+#[allow(clippy::too_many_lines)]
+fn touches(action: &Action) -> Vec<Touch<'_>> {
+    match action {
+        Action::Store(store) | Action::StoreWithKey(_, store) => {
+            vec![Touch::stored("store", store)]
+        }
+        Action::StoreBatch(batch) => batch
+            .entries()
+            .iter()
+            .map(|store| Touch::stored("store_batch", store))
+            .collect(),
+        Action::Generate(request) => vec![Touch::key(
+            "generate",
+            request.key(),
+            Use::Write {
+                bytes: None,
+                overwrite: request.force(),
+            },
+        )],
+        Action::BeginUpload(request) => vec![Touch::key(
+            "upload",
+            request.key(),
+            Use::Write {
+                bytes: Some(request.size()),
+                overwrite: request.force(),
+            },
+        )],
+        Action::ImportWrapped(request) => vec![Touch::key(
+            "import_wrapped",
+            request.key(),
+            Use::Write {
+                bytes: None,
+                overwrite: request.force(),
+            },
+        )],
+        Action::Patch(request) => vec![Touch::key(
+            "patch",
+            request.key(),
+            Use::Write {
+                bytes: None,
+                overwrite: true,
+            },
+        )],
+        // Reading the alias reads the target, so the target's rules are
+        // checked when the link is made, and again on each read through it,
+        // as they may have changed since.
+        Action::Link(request) => vec![
+            Touch::key(
+                "link",
+                request.alias(),
+                Use::Write {
+                    bytes: None,
+                    overwrite: false,
+                },
+            ),
+            Touch::key("link", request.target(), Use::Read),
+        ],
+        Action::ImportSync(request) => {
+            let usage = Use::Write {
+                bytes: None,
+                overwrite: request.strategy() != SyncStrategy::Skip,
+            };
+            request
+                .entries()
+                .iter()
+                .map(|entry| Touch::key("import_sync", entry.key(), usage))
+                .collect()
+        }
+        Action::Read(key) => vec![Touch::key("read", key, Use::Read)],
+        Action::ReadField(request) => vec![Touch::key("read_field", request.key(), Use::Read)],
+        Action::Exists(key) => vec![Touch::key("exists", key, Use::Stat)],
+        Action::History(key) => vec![Touch::key("history", key, Use::Stat)],
+        // Even with its values masked, a diff tells which fields changed.
+        Action::Diff(request) => vec![Touch::key("diff", request.key(), Use::Read)],
+        Action::ExportWrapped(request) => {
+            vec![Touch::key("export_wrapped", request.key(), Use::Read)]
+        }
+        Action::ReadPrefix(prefix) => vec![Touch::prefix("read_prefix", prefix, Use::Read)],
+        Action::ExportSync(request) => request
+            .prefixes()
+            .iter()
+            .map(|prefix| Touch::prefix("export_sync", prefix, Use::Read))
+            .collect(),
+        Action::Delete(key) => vec![Touch::key("delete", key, Use::Delete)],
+        Action::SetRotation(request) => vec![Touch::key(
+            "set_rotation",
+            request.key(),
+            Use::Write {
+                bytes: None,
+                overwrite: true,
+            },
+        )],
+        // Rewrapping re-seals the prefix's values in place, so it is a write
+        // over values that already exist.
+        Action::Rewrap(prefix) => vec![Touch::prefix(
+            "rewrap",
+            prefix,
+            Use::Write {
+                bytes: None,
+                overwrite: true,
+            },
+        )],
+        Action::DeletePrefix(request) => {
+            let usage = if request.dry_run() {
+                Use::Stat
+            } else {
+                Use::Delete
+            };
+            vec![Touch::prefix("delete_prefix", request.prefix(), usage)]
+        }
+        Action::Unlock(_)
+        | Action::Lock
+        | Action::Share(_)
+        | Action::GenShares(..)
+        | Action::GetThreshold
+        | Action::FindKey(_)
+        | Action::Search(_)
+        | Action::Status
+        | Action::VerifyShare(_)
+        | Action::RefreshShares
+        | Action::InitStore(_)
+        | Action::CreateSigningKey(_)
+        | Action::Sign(_)
+        | Action::Verify(_)
+        | Action::Hmac(_)
+        | Action::GenerateDataKey(_)
+        | Action::DecryptDataKey(_)
+        | Action::Random(_)
+        | Action::WrappingKey
+        | Action::Encrypt(_)
+        | Action::Decrypt(_)
+        | Action::CreateKey(_)
+        | Action::RotateKey(_)
+        | Action::ListKeys
+        | Action::Backup(_)
+        | Action::CheckStore
+        | Action::UploadChunk(_)
+        | Action::FinishUpload(_)
+        | Action::ReadChunk(_)
+        | Action::SetReadOnly(_)
+        | Action::Ping
+        | Action::Cancel(_)
+        | Action::ReloadConfig
+        | Action::MintCredential(_)
+        | Action::ListPlugins
+        | Action::SignSshKey(_)
+        | Action::CreateCa(_)
+        | Action::ImportCa(_)
+        | Action::IssueCert(_)
+        | Action::RevokeCert(_)
+        | Action::ListCerts
+        | Action::GetCrl
+        | Action::Warnings
+        | Action::RewrapStatus
+        | Action::RenewLease(_)
+        | Action::RevokeLease(_)
+        | Action::ListApprovals
+        | Action::Approve(_)
+        | Action::DenyApproval(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use anyhow::{Result, bail};
+    use libsalus::{Action, BeginUpload, DeletePrefix, DiffRequest, Link, Store};
+
+    use super::{Namespaces, covers};
+    use crate::{config::ConfigSalusd, error::Error};
+
+    fn namespaces(toml: &str) -> Result<Namespaces> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(toml, ::config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        Ok(Namespaces::from(&config))
+    }
+
+    fn store(key: &str, value: &str, force: bool) -> Action {
+        Action::Store(Store::builder().key(key).value(value).force(force).build())
+    }
+
+    #[test]
+    fn namespaces_hold_the_keys_under_them() {
+        assert!(covers("payments", "payments/card", false));
+        assert!(covers("payments", "payments/eu/card", false));
+        assert!(!covers("payments", "payments", false));
+        assert!(!covers("payments", "paymentsx/card", false));
+        assert!(!covers("payments", "pay", false));
+        assert!(covers("payments", "pay", true));
+        assert!(covers("payments", "", true));
+        assert!(covers("payments", "payments/eu/", true));
+        assert!(!covers("payments", "web/", true));
+        assert!(!covers("", "payments/card", false));
+    }
+
+    #[test]
+    fn policies_refuse_what_they_name() -> Result<()> {
+        let namespaces = namespaces(
+            "[namespace.payments]\npolicies = [\"no-overwrite\", \"no-delete\"]\n\
+             [namespace.archive]\npolicies = [\"read-only\"]\n",
+        )?;
+        namespaces.admit(&store("payments/card", "1234", false), None, None)?;
+        namespaces.admit(&Action::Read("archive/2024".to_string()), None, None)?;
+        namespaces.admit(&store("web/card", "1234", true), None, None)?;
+        match namespaces.admit(&store("payments/card", "1234", true), None, None) {
+            Err(Error::NamespaceNoOverwrite(name)) if name == "payments" => {}
+            other => bail!("a forced store was not refused: {other:?}"),
+        }
+        match namespaces.admit(&Action::Delete("payments/card".to_string()), None, None) {
+            Err(Error::NamespaceNoDelete(name)) if name == "payments" => {}
+            other => bail!("a delete was not refused: {other:?}"),
+        }
+        let delete_prefix = |prefix: &str, dry_run| {
+            Action::DeletePrefix(
+                DeletePrefix::builder()
+                    .prefix(prefix)
+                    .dry_run(dry_run)
+                    .build(),
+            )
+        };
+        namespaces.admit(&delete_prefix("pay", true), None, None)?;
+        namespaces.admit(&delete_prefix("web/", false), None, None)?;
+        match namespaces.admit(&delete_prefix("pay", false), None, None) {
+            Err(Error::NamespaceNoDelete(name)) if name == "payments" => {}
+            other => bail!("a delete of a prefix was not refused: {other:?}"),
+        }
+        match namespaces.admit(&store("archive/2025", "x", false), None, None) {
+            Err(Error::NamespaceReadOnly(name)) if name == "archive" => {}
+            other => bail!("a store was not refused: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn values_over_the_namespace_limit_are_refused() -> Result<()> {
+        let namespaces = namespaces("[namespace.payments]\nmax_value_bytes = 4\n")?;
+        namespaces.admit(&store("payments/card", "1234", false), None, None)?;
+        namespaces.admit(&store("web/card", "12345", false), None, None)?;
+        match namespaces.admit(&store("payments/card", "12345", false), None, None) {
+            Err(Error::NamespaceValueTooLarge(_, 4)) => {}
+            other => bail!("a long value was not refused: {other:?}"),
+        }
+        let upload = BeginUpload::builder()
+            .key("payments/eu/blob")
+            .size(5)
+            .build();
+        if namespaces
+            .admit(&Action::BeginUpload(upload), None, None)
+            .is_ok()
+        {
+            bail!("a long upload was not refused");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn a_namespace_is_only_served_for_its_key_timeout() -> Result<()> {
+        let namespaces = namespaces("[namespace.payments]\nkey_timeout = 60\n")?;
+        let read = Action::Read("payments/card".to_string());
+        let prefix = Action::ReadPrefix(String::new());
+        let elsewhere = Action::Read("web/card".to_string());
+        if !namespaces.timed(&read, None)
+            || !namespaces.timed(&prefix, None)
+            || namespaces.timed(&elsewhere, None)
+        {
+            bail!("the wrong requests need the unlock time");
+        }
+        namespaces.admit(&read, None, Some(Duration::from_secs(59)))?;
+        namespaces.admit(&read, None, None)?;
+        namespaces.admit(&elsewhere, None, Some(Duration::from_secs(61)))?;
+        match namespaces.admit(&prefix, None, Some(Duration::from_secs(61))) {
+            Err(Error::NamespaceUnlockExpired(_, 60)) => Ok(()),
+            other => bail!("a read long after the unlock was not refused: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_read_through_an_alias_meets_its_targets_rules() -> Result<()> {
+        let namespaces = namespaces("[namespace.payments]\nkey_timeout = 60\n")?;
+        let read = Action::Read("web/card".to_string());
+        assert_eq!(namespaces.followed(&read), Some("web/card"));
+        assert!(
+            namespaces
+                .followed(&Action::ReadPrefix("web/".to_string()))
+                .is_none()
+        );
+        let stored = Some("payments/card");
+        if namespaces.timed(&read, None) || !namespaces.timed(&read, stored) {
+            bail!("only the read reaching the namespace needs the unlock time");
+        }
+        let late = Some(Duration::from_secs(61));
+        namespaces.admit(&read, None, late)?;
+        match namespaces.admit(&read, stored, late) {
+            Err(Error::NamespaceUnlockExpired(name, 60)) if name == "payments" => Ok(()),
+            other => bail!("a read through the alias was not refused: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn only_single_key_reads_are_held_for_approval() -> Result<()> {
+        let namespaces = namespaces(
+            "[namespace.payments]\napprovals = 1\n\
+             [namespace.\"payments/eu\"]\napprovals = 2\napprovers = [1001]\n",
+        )?;
+        let read = Action::Read("payments/eu/card".to_string());
+        let Some((key, rule)) = namespaces.approval(&read, None)? else {
+            bail!("a read in the namespace was not held");
+        };
+        assert_eq!(key, "payments/eu/card");
+        assert_eq!((rule.namespace.as_str(), rule.required), ("payments/eu", 2));
+        assert!(rule.admits(1001) && !rule.admits(1002));
+        let Some(outer) = namespaces.approval_rule("payments/card") else {
+            bail!("the outer namespace holds no reads");
+        };
+        assert!(outer.admits(1002));
+        assert_eq!(outer.window, 900);
+
+        assert!(
+            namespaces
+                .approval(&Action::Exists("payments/card".to_string()), None)?
+                .is_none()
+        );
+        assert!(
+            namespaces
+                .approval(&Action::Read("web/card".to_string()), None)?
+                .is_none()
+        );
+        assert!(
+            namespaces
+                .approval(&Action::History("payments/card".to_string()), None)?
+                .is_none()
+        );
+        // A diff through an alias still reads the values it reaches.
+        let diff = Action::Diff(DiffRequest::builder().key("web/card").from(1).to(2).build());
+        match namespaces.approval(&diff, Some("payments/card")) {
+            Err(Error::NamespaceNeedsApproval(name)) if name == "payments" => {}
+            other => bail!("a diff through an alias was not refused: {other:?}"),
+        }
+        assert!(
+            namespaces
+                .approval(&Action::ReadPrefix("web/".to_string()), None)?
+                .is_none()
+        );
+        let link = Action::Link(
+            Link::builder()
+                .alias("web/card")
+                .target("payments/card")
+                .build(),
+        );
+        for action in [Action::ReadPrefix(String::new()), link] {
+            match namespaces.approval(&action, None) {
+                Err(Error::NamespaceNeedsApproval(name)) if name == "payments" => {}
+                other => bail!("{action:?} was not refused: {other:?}"),
+            }
+        }
+        Ok(())
+    }
+}
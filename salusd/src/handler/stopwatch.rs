@@ -0,0 +1,93 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Where the time on a request that asked to be timed goes.
+
+use std::time::{Duration, Instant};
+
+use libsalus::Timing;
+
+/// One store call: when it started, once it held the store, how long it took,
+/// and how much of that was spent in the backend.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Spent {
+    pub(crate) started: Instant,
+    pub(crate) took: Duration,
+    pub(crate) storage: Duration,
+}
+
+/// The time spent on one request, from when its frame arrived.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Stopwatch {
+    received: Instant,
+    /// When the first store call started.
+    started: Option<Instant>,
+    storage: Duration,
+    crypto: Duration,
+}
+
+impl Stopwatch {
+    pub(crate) fn new(received: Instant) -> Self {
+        Self {
+            received,
+            started: None,
+            storage: Duration::ZERO,
+            crypto: Duration::ZERO,
+        }
+    }
+
+    /// Count a store call the request made.
+    pub(crate) fn record(&mut self, spent: Spent) {
+        let _started = self.started.get_or_insert(spent.started);
+        self.storage = self.storage.saturating_add(spent.storage);
+        self.crypto = self
+            .crypto
+            .saturating_add(spent.took.saturating_sub(spent.storage));
+    }
+
+    /// The timing so far, as the response reports it. A request that never
+    /// reached the store spent all its time queued.
+    pub(crate) fn timing(&self) -> Timing {
+        let now = Instant::now();
+        let queued = self
+            .started
+            .unwrap_or(now)
+            .saturating_duration_since(self.received);
+        Timing::builder()
+            .queued(queued)
+            .storage(self.storage)
+            .crypto(self.crypto)
+            .total(now.saturating_duration_since(self.received))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{Spent, Stopwatch};
+
+    #[test]
+    fn crypto_is_what_the_store_did_outside_the_backend() {
+        let received = Instant::now();
+        let mut stopwatch = Stopwatch::new(received);
+        for _ in 0..2 {
+            stopwatch.record(Spent {
+                started: received,
+                took: Duration::from_millis(5),
+                storage: Duration::from_millis(3),
+            });
+        }
+        let timing = stopwatch.timing();
+        assert_eq!(timing.queued(), Duration::ZERO);
+        assert_eq!(timing.storage(), Duration::from_millis(6));
+        assert_eq!(timing.crypto(), Duration::from_millis(4));
+        assert!(timing.total() >= timing.queued());
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Hooks: commands or webhooks, named in `[hooks.<name>]`, run after a value
+//! changes or a named key is rotated, so the services that depend on it can
+//! reload.
+//!
+//! The store reports each change it commits over a [`Changes`] channel, and
+//! [`deliver`] runs the hooks it calls for, with the hooks the daemon is
+//! configured with at the time, one change after another. A hook is told the
+//! key, the event and the value's new version, never the value: a command in
+//! its environment, and a webhook in the JSON body of a POST.
+//!
+//! ```text
+//! SALUS_HOOK=reload-app SALUS_EVENT=written SALUS_KEY=app/db SALUS_VERSION=3 SALUS_CHANGED_AT=1700000000
+//! {"hook":"reload-app","event":"written","key":"app/db","version":3,"at":1700000000}
+//! ```
+//!
+//! A hook that fails or runs out of time is logged, and not run again for
+//! that change.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    path::Path,
+    process::Stdio,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use getset::{CopyGetters, Getters};
+use serde::Serialize;
+use tokio::{
+    process::Command,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+        watch,
+    },
+    time::timeout,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::{ConfigSalusd, HookEvent, HookSettings, reload::Limits},
+    error::Error,
+};
+
+/// A change the store committed
+#[derive(Clone, CopyGetters, Debug, Eq, Getters, PartialEq, Serialize)]
+pub(crate) struct Change {
+    /// What happened
+    #[getset(get_copy = "pub(crate)")]
+    event: HookEvent,
+    /// The value's key, or the named key's name
+    #[getset(get = "pub(crate)")]
+    key: String,
+    /// The value's new version, or the named key's; none for a deletion
+    #[getset(get_copy = "pub(crate)")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u64>,
+    /// When it happened, in seconds since the Unix epoch
+    #[getset(get_copy = "pub(crate)")]
+    at: u64,
+}
+
+/// Where the store reports the changes it commits; nowhere until the daemon
+/// runs hooks
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Changes(Option<UnboundedSender<Change>>);
+
+impl Changes {
+    /// A place to report changes, and where they are received.
+    pub(crate) fn channel() -> (Self, UnboundedReceiver<Change>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self(Some(sender)), receiver)
+    }
+
+    /// Report that the value under `key` was written, as `version`.
+    pub(crate) fn written(&self, key: &str, version: u64) {
+        self.send(HookEvent::Written, key, Some(version));
+    }
+
+    /// Report that the value under `key` was deleted.
+    pub(crate) fn deleted(&self, key: &str) {
+        self.send(HookEvent::Deleted, key, None);
+    }
+
+    /// Report that the named key `name` was given `version`.
+    pub(crate) fn rotated(&self, name: &str, version: u32) {
+        self.send(HookEvent::Rotated, name, Some(u64::from(version)));
+    }
+
+    /// Report that the value under `key`, at `version`, is due to be
+    /// rotated.
+    pub(crate) fn overdue(&self, key: &str, version: Option<u64>) {
+        self.send(HookEvent::Overdue, key, version);
+    }
+
+    fn send(&self, event: HookEvent, key: &str, version: Option<u64>) {
+        if let Some(sender) = &self.0 {
+            let at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            // Gone only as the daemon stops.
+            let _sent = sender.send(Change {
+                event,
+                key: key.to_string(),
+                version,
+                at,
+            });
+        }
+    }
+}
+
+/// The hooks the daemon runs, by name
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Hooks(BTreeMap<String, HookSettings>);
+
+impl From<&ConfigSalusd> for Hooks {
+    fn from(config: &ConfigSalusd) -> Self {
+        Self(config.hooks().clone())
+    }
+}
+
+impl Hooks {
+    /// The hooks `change` calls for, by name.
+    pub(crate) fn for_change<'a>(
+        &'a self,
+        change: &'a Change,
+    ) -> impl Iterator<Item = (&'a str, &'a HookSettings)> {
+        self.0
+            .iter()
+            .filter(|(_, settings)| {
+                (settings.events().is_empty() || settings.events().contains(&change.event))
+                    && (settings.keys().is_empty()
+                        || settings.keys().iter().any(|key| covers(key, &change.key)))
+            })
+            .map(|(name, settings)| (name.as_str(), settings))
+    }
+}
+
+/// Whether a hook's `pattern`, a key or a prefix ending in `/`, covers `key`.
+pub(crate) fn covers(pattern: &str, key: &str) -> bool {
+    if pattern.ends_with('/') {
+        key.starts_with(pattern)
+    } else {
+        key == pattern
+    }
+}
+
+/// Run the hooks each change received on `changes` calls for, with the
+/// hooks in `limits` when it arrives, until the store is gone.
+pub(crate) async fn deliver(
+    mut changes: UnboundedReceiver<Change>,
+    limits: watch::Receiver<Limits>,
+) {
+    while let Some(change) = changes.recv().await {
+        let hooks = limits.borrow().hooks().clone();
+        for (name, settings) in hooks.for_change(&change) {
+            match run(name, settings, &change).await {
+                Ok(()) => info!(
+                    hook = name,
+                    event = change.event.name(),
+                    key = change.key.as_str(),
+                    version = change.version,
+                    "Hook ran"
+                ),
+                Err(e) => warn!(
+                    "Hook '{name}' for {} of {}: {e}",
+                    change.event.name(),
+                    change.key
+                ),
+            }
+        }
+    }
+}
+
+/// Run hook `name` after `change`: its command, then its webhook, each
+/// within its `timeout_ms`.
+///
+/// # Errors
+///
+/// * Returns an error if it has neither, or either fails or runs out of
+///   time.
+pub(crate) async fn run(name: &str, settings: &HookSettings, change: &Change) -> Result<()> {
+    if settings.command().is_none() && settings.url().is_none() {
+        return Err(Error::HookFailed(name.to_string(), "no command or url is set".into()).into());
+    }
+    let limit = Duration::from_millis(settings.timeout_ms());
+    let timed_out = || Error::HookTimeout(name.to_string(), settings.timeout_ms());
+    if let Some(command) = settings.command() {
+        timeout(limit, run_command(name, command, settings.args(), change))
+            .await
+            .map_err(|_| timed_out())??;
+    }
+    if let Some(url) = settings.url() {
+        timeout(limit, post(name, url, change))
+            .await
+            .map_err(|_| timed_out())??;
+    }
+    Ok(())
+}
+
+/// Run `command` with the change in its environment, and wait for it.
+async fn run_command(name: &str, command: &Path, args: &[String], change: &Change) -> Result<()> {
+    let failed = |problem: String| Error::HookFailed(name.to_string(), problem);
+    // Killed if it runs out of time.
+    let status = Command::new(command)
+        .args(args)
+        .env_clear()
+        .envs(env::var_os("PATH").map(|path| ("PATH", path)))
+        .env("SALUS_HOOK", name)
+        .env("SALUS_EVENT", change.event.name())
+        .env("SALUS_KEY", &change.key)
+        .envs(
+            change
+                .version
+                .map(|version| ("SALUS_VERSION", version.to_string())),
+        )
+        .env("SALUS_CHANGED_AT", change.at.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .map_err(|e| failed(format!("unable to run {}: {e}", command.display())))?;
+    if !status.success() {
+        return Err(failed(status.to_string()).into());
+    }
+    Ok(())
+}
+
+/// The JSON body of a webhook's POST.
+#[cfg(feature = "webhooks")]
+#[derive(Serialize)]
+struct Posted<'a> {
+    hook: &'a str,
+    #[serde(flatten)]
+    change: &'a Change,
+}
+
+/// POST the change to `url` as JSON, and check it was taken.
+#[cfg(feature = "webhooks")]
+async fn post(name: &str, url: &str, change: &Change) -> Result<()> {
+    let failed = |problem: String| Error::HookFailed(name.to_string(), problem);
+    let body = serde_json::to_vec(&Posted { hook: name, change })?;
+    let response = reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| failed(format!("unable to POST to {url}: {e}")))?;
+    if !response.status().is_success() {
+        return Err(failed(format!("{url} answered {}", response.status())).into());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "webhooks"))]
+#[allow(clippy::unused_async)]
+async fn post(name: &str, _url: &str, _change: &Change) -> Result<()> {
+    Err(Error::WebhooksUnsupported(name.to_string()).into())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{env, fs, os::unix::fs::PermissionsExt as _, path::PathBuf};
+
+    use anyhow::{Result, bail};
+
+    use super::{Change, Changes, Hooks, run};
+    use crate::{
+        config::{ConfigSalusd, HookEvent},
+        error::Error,
+    };
+
+    /// Write `script` as an executable hook, returning its path.
+    fn hook(name: &str, script: &str) -> Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("salusd-hook-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}"))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        Ok(path)
+    }
+
+    fn hooks(toml: &str) -> Result<Hooks> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(toml, ::config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        Ok(Hooks::from(&config))
+    }
+
+    fn change(event: HookEvent, key: &str, version: Option<u64>) -> Change {
+        Change {
+            event,
+            key: key.to_string(),
+            version,
+            at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn hooks_are_run_for_their_keys_and_events() -> Result<()> {
+        let hooks = hooks(
+            "[hooks.app]\ncommand = \"/bin/true\"\nkeys = [\"app/\", \"db\"]\n\
+             [hooks.rotations]\ncommand = \"/bin/true\"\nevents = [\"rotated\"]\n\
+             [hooks.everything]\ncommand = \"/bin/true\"\n",
+        )?;
+        let names = |change: &Change| {
+            hooks
+                .for_change(change)
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&change(HookEvent::Written, "app/web", Some(1))),
+            ["app", "everything"]
+        );
+        assert_eq!(
+            names(&change(HookEvent::Deleted, "db", None)),
+            ["app", "everything"]
+        );
+        assert_eq!(
+            names(&change(HookEvent::Written, "dbx", Some(2))),
+            ["everything"]
+        );
+        assert_eq!(
+            names(&change(HookEvent::Rotated, "payments", Some(3))),
+            ["everything", "rotations"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_command_is_told_the_change_but_not_the_value() -> Result<()> {
+        let out = env::temp_dir().join(format!("salusd-hook-{}.out", std::process::id()));
+        let path = hook(
+            "record",
+            &format!(
+                "echo \"$SALUS_HOOK $SALUS_EVENT $SALUS_KEY ${{SALUS_VERSION:-none}} $SALUS_CHANGED_AT $1\" > {:?}\n",
+                out.display()
+            ),
+        )?;
+        let hooks = hooks(&format!(
+            "[hooks.record]\ncommand = {:?}\nargs = [\"reload\"]\n",
+            path.display()
+        ))?;
+        let written = change(HookEvent::Written, "app/db", Some(3));
+        for (name, settings) in hooks.for_change(&written) {
+            run(name, settings, &written).await?;
+        }
+        assert_eq!(
+            fs::read_to_string(&out)?,
+            "record written app/db 3 1700000000 reload\n"
+        );
+        let deleted = change(HookEvent::Deleted, "app/db", None);
+        for (name, settings) in hooks.for_change(&deleted) {
+            run(name, settings, &deleted).await?;
+        }
+        assert_eq!(
+            fs::read_to_string(&out)?,
+            "record deleted app/db none 1700000000 reload\n"
+        );
+        fs::remove_file(out)?;
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hooks_that_misbehave_fail() -> Result<()> {
+        let slow = hook("slow", "sleep 5\n")?;
+        let failing = hook("failing", "exit 3\n")?;
+        let hooks = hooks(&format!(
+            "[hooks.slow]\ncommand = {:?}\ntimeout_ms = 100\n\
+             [hooks.failing]\ncommand = {:?}\n\
+             [hooks.missing]\ncommand = \"/nonexistent/hook\"\n\
+             [hooks.empty]\n",
+            slow.display(),
+            failing.display(),
+        ))?;
+        let written = change(HookEvent::Written, "app/db", Some(1));
+        let mut failures = Vec::new();
+        for (name, settings) in hooks.for_change(&written) {
+            if let Err(e) = run(name, settings, &written).await {
+                failures.push((name, e));
+            }
+        }
+        assert_eq!(failures.len(), 4);
+        for (name, e) in failures {
+            match (name, e.downcast_ref::<Error>()) {
+                ("slow", Some(Error::HookTimeout(_, 100)))
+                | ("failing" | "missing" | "empty", Some(Error::HookFailed(..))) => {}
+                (name, other) => bail!("unexpected failure of {name}: {other:?}"),
+            }
+        }
+        fs::remove_file(slow)?;
+        fs::remove_file(failing)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn changes_are_received_in_order() -> Result<()> {
+        let (changes, mut incoming) = Changes::channel();
+        changes.written("app/db", 1);
+        changes.deleted("app/db");
+        changes.rotated("payments", 2);
+        Changes::default().written("ignored", 1);
+        drop(changes);
+        let mut received = Vec::new();
+        while let Some(change) = incoming.recv().await {
+            received.push((change.event(), change.key().clone(), change.version()));
+        }
+        assert_eq!(
+            received,
+            [
+                (HookEvent::Written, "app/db".to_string(), Some(1)),
+                (HookEvent::Deleted, "app/db".to_string(), None),
+                (HookEvent::Rotated, "payments".to_string(), Some(2)),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -247,17 +247,32 @@
 #![cfg_attr(all(docsrs), feature(doc_cfg))]
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
+// criterion is only used by `benches/concurrency.rs`.
+#[cfg(test)]
+use criterion as _;
+// wat only builds the modules the WebAssembly plugin tests load.
+#[cfg(all(test, not(feature = "wasm-plugins")))]
+use wat as _;
+
 use crate::error::{clap_or_error, success};
 
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "cluster")]
+mod cluster;
 mod config;
 mod db;
 mod error;
 #[cfg(feature = "fuzzing")]
 pub mod fuzz;
 mod handler;
+mod hook;
 mod logging;
+mod plugin;
 mod runtime;
 mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod utils;
 
 /// Run the salus daemon to completion, returning the process exit code.
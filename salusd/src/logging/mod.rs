@@ -13,8 +13,12 @@ use std::{
 
 use anyhow::Result;
 use tracing::{Level, level_filters::LevelFilter};
-use tracing_subscriber::{EnvFilter, Layer, Registry, fmt::time::UtcTime};
-use tracing_subscriber_init::{Iso8601, TracingConfig, compact, try_init};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    fmt::time::UtcTime,
+    reload::{self, Handle},
+};
+use tracing_subscriber_init::{Iso8601, TracingConfig, compact, get_effective_level, try_init};
 
 use crate::{
     config::{ConfigSalusd, PathDefaults},
@@ -22,26 +26,45 @@ use crate::{
     utils::{ensure_parent_dir, to_path_buf},
 };
 
-/// Initialize tracing
+/// The filters of the layers [`initialize`] set up, which can be swapped
+/// while the daemon runs
+pub(crate) struct TracingReload {
+    handles: Vec<Handle<EnvFilter, Registry>>,
+    /// The `--log-filter` directives, kept across reloads
+    log_filter: Option<String>,
+}
+
+impl TracingReload {
+    /// Filter every layer by `config`'s verbosity and directives.
+    pub(crate) fn reload(&self, config: &ConfigSalusd) -> Result<()> {
+        for handle in &self.handles {
+            handle.reload(filter(config, self.log_filter.as_deref()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Initialize tracing, with `log_filter`'s directives added after the
+/// configured ones
 pub(crate) fn initialize<T, U>(
     tracing_config: &T,
     config: &ConfigSalusd,
     defaults: &U,
+    log_filter: Option<&str>,
     layers_opt: Option<Vec<Box<dyn Layer<Registry> + Send + Sync>>>,
-) -> Result<()>
+) -> Result<TracingReload>
 where
     T: TracingConfig,
     U: PathDefaults,
 {
     let mut layers = layers_opt.unwrap_or_default();
+    let mut handles = Vec::new();
 
     // Setup the stdout tracing layer if enabled
     if config.enable_std_output() {
-        let (layer, level_filter) = compact(tracing_config);
-        let directives = directives(config, level_filter);
-        let filter = EnvFilter::builder()
-            .with_default_directive(level_filter.into())
-            .parse_lossy(directives);
+        let (layer, _level_filter) = compact(tracing_config);
+        let (filter, handle) = reload::Layer::new(filter(config, log_filter));
+        handles.push(handle);
         let stdout_layer = layer
             .with_ansi(true)
             .with_ansi_sanitization(false)
@@ -54,11 +77,9 @@ where
     let tracing_absolute_path = tracing_absolute_path(defaults)?;
     ensure_parent_dir(&tracing_absolute_path)?;
     let tracing_file = File::create(&tracing_absolute_path)?;
-    let (layer, level_filter) = compact(tracing_config);
-    let directives = directives(config, level_filter);
-    let filter = EnvFilter::builder()
-        .with_default_directive(level_filter.into())
-        .parse_lossy(directives);
+    let (layer, _level_filter) = compact(tracing_config);
+    let (filter, handle) = reload::Layer::new(filter(config, log_filter));
+    handles.push(handle);
     let file_layer = layer
         .with_ansi_sanitization(false)
         .with_timer(UtcTime::new(Iso8601::DEFAULT))
@@ -67,10 +88,27 @@ where
     layers.push(file_layer.boxed());
 
     try_init(layers)?;
-    Ok(())
+    Ok(TracingReload {
+        handles,
+        log_filter: log_filter.map(str::to_string),
+    })
+}
+
+/// The filter for `config`'s verbosity and directives, then `log_filter`'s.
+fn filter(config: &ConfigSalusd, log_filter: Option<&str>) -> EnvFilter {
+    let level_filter = LevelFilter::from(get_effective_level(config.quiet(), config.verbose()));
+    EnvFilter::builder()
+        .with_default_directive(level_filter.into())
+        .parse_lossy(directives(config, level_filter, log_filter))
 }
 
-fn directives(config: &ConfigSalusd, level_filter: LevelFilter) -> String {
+/// The directives for the verbosity's level, then `config`'s, then
+/// `log_filter`'s, so a later directive for the same target wins.
+fn directives(
+    config: &ConfigSalusd,
+    level_filter: LevelFilter,
+    log_filter: Option<&str>,
+) -> String {
     let directives_base = match level_filter.into_level() {
         Some(level) => match level {
             Level::TRACE => "trace",
@@ -82,21 +120,25 @@ fn directives(config: &ConfigSalusd, level_filter: LevelFilter) -> String {
         None => "info",
     };
 
-    if let Some(directives) = config.tracing().directives() {
-        format!("{directives_base},{directives}")
-    } else {
-        directives_base.to_string()
-    }
+    [
+        Some(directives_base),
+        config.tracing().directives().as_deref(),
+        log_filter,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(",")
 }
 
-fn tracing_absolute_path<D>(defaults: &D) -> Result<PathBuf>
+pub(crate) fn tracing_absolute_path<D>(defaults: &D) -> Result<PathBuf>
 where
     D: PathDefaults,
 {
     let default_fn = || -> Result<PathBuf> { default_tracing_absolute_path(defaults) };
     defaults
         .tracing_absolute_path()
-        .as_ref()
+        .as_deref()
         .map_or_else(default_fn, to_path_buf)
 }
 
@@ -120,7 +162,40 @@ fn log_file_in(base: &Path, app: &str) -> PathBuf {
 mod test {
     use std::path::Path;
 
-    use super::log_file_in;
+    use anyhow::Result;
+    use tracing::level_filters::LevelFilter;
+
+    use super::{directives, log_file_in};
+    use crate::config::ConfigSalusd;
+
+    #[test]
+    fn the_log_filter_comes_after_the_configured_directives() -> Result<()> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .set_override("tracing.directives", "salusd=debug")?
+            .build()?
+            .try_deserialize()?;
+        assert_eq!(
+            directives(&config, LevelFilter::INFO, None),
+            "info,salusd=debug"
+        );
+        assert_eq!(
+            directives(
+                &config,
+                LevelFilter::WARN,
+                Some("salusd::store=trace,redb=warn")
+            ),
+            "warn,salusd=debug,salusd::store=trace,redb=warn"
+        );
+        assert_eq!(
+            directives(
+                &ConfigSalusd::default(),
+                LevelFilter::INFO,
+                Some("redb=warn")
+            ),
+            "info,redb=warn"
+        );
+        Ok(())
+    }
 
     #[test]
     fn log_file_in_composes_app_dir_and_extension() {
@@ -0,0 +1,563 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The database engine: short-lived database users, created when a client
+//! reads `database/creds/<role>` and dropped once their lease is up.
+//!
+//! A `[database_roles.<role>]` table names the plugin that reaches the
+//! database and the statements that create and drop a user. The daemon draws
+//! the user's name and password, fills them into the statements, and has the
+//! plugin run them with the `execute` op, so the engine works with any
+//! database a plugin can reach. Each user is recorded as a [`Lease`], and
+//! [`reap`] drops the users whose leases are up. A client may [`renew`] a
+//! lease, up to its plugin's `max_ttl` after it was minted, or [`revoke`] it
+//! to have the user dropped at once.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use aws_lc_rs::rand;
+use libsalus::{Charset, SecretSpec, generate_secret};
+use serde::Serialize;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::{debug, info, warn};
+use zeroize::Zeroizing;
+
+use super::{OnStore, Plugins, execute, stored_config};
+use crate::{
+    config::{ConfigSalusd, DatabaseRole},
+    error::Error,
+    store::lease::Lease,
+};
+
+/// The keys a read of mints a database user, followed by the role.
+pub(crate) const CREDS_PREFIX: &str = "database/creds/";
+
+/// How often the leases are checked for ones that are up.
+pub(crate) const REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many characters a minted password has. They are letters and digits,
+/// so it needs no escaping inside a SQL string.
+const PASSWORD_LENGTH: u32 = 32;
+
+/// The most characters of the role kept in a minted user's name, which
+/// Postgres caps at 63 characters and `MySQL` at 32.
+const MAX_ROLE_IN_NAME: usize = 16;
+
+/// The database roles users are minted for, by role
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct DatabaseRoles(BTreeMap<String, DatabaseRole>);
+
+impl From<&ConfigSalusd> for DatabaseRoles {
+    fn from(config: &ConfigSalusd) -> Self {
+        Self(config.database_roles().clone())
+    }
+}
+
+impl DatabaseRoles {
+    /// The role a read of `key` mints a user for, when it names one.
+    pub(crate) fn role_for<'k>(&self, key: &'k str) -> Option<&'k str> {
+        key.strip_prefix(CREDS_PREFIX)
+            .filter(|role| self.0.contains_key(*role))
+    }
+}
+
+/// What a read of `database/creds/<role>` answers with.
+#[derive(Debug, Serialize)]
+struct Minted<'a> {
+    username: &'a str,
+    password: &'a str,
+    lease_id: &'a str,
+    ttl: u64,
+    expires_at: u64,
+}
+
+/// Create a database user for `role`, recording its lease, and answer with
+/// the user's name and password as a JSON document.
+///
+/// # Errors
+///
+/// * Returns an error if there is no such role, the store is locked, its
+///   plugin refuses the role's ttl or fails to create the user, or the lease
+///   cannot be recorded.
+pub(crate) async fn mint(
+    store: &mut impl OnStore,
+    plugins: &Plugins,
+    roles: &DatabaseRoles,
+    role_name: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let role = roles
+        .0
+        .get(role_name)
+        .ok_or_else(|| Error::KeyNotFound(format!("{CREDS_PREFIX}{role_name}")))?;
+    let (settings, ttl) = plugins.admit(role.plugin(), role_name, Some(role.ttl()))?;
+    let stored = settings.clone();
+    let config = store
+        .on_store(move |store| stored_config(store, &stored))
+        .await?;
+
+    let username = format!(
+        "v_{}_{}",
+        name_part(role_name),
+        random(Charset::Hex, 8)?.as_str()
+    );
+    let password = random(Charset::Alnum, PASSWORD_LENGTH)?;
+    let lease_id = random(Charset::Hex, 32)?.to_string();
+    let issued_at = now();
+    let expires_at = issued_at.saturating_add(ttl);
+    let expiration = rfc3339(expires_at)?;
+    let creation = Zeroizing::new(render(
+        role.creation(),
+        &[
+            ("name", &username),
+            ("password", &password),
+            ("expiration", &expiration),
+        ],
+    ));
+    let revocation = render(role.revocation(), &[("name", &username)]);
+    execute(role.plugin(), settings, &creation, config.as_ref()).await?;
+
+    let lease = Lease::builder()
+        .id(lease_id.as_str())
+        .plugin(role.plugin().as_str())
+        .role(role_name)
+        .username(username.as_str())
+        .revocation(revocation)
+        .expires_at(expires_at)
+        .issued_at(issued_at)
+        .build();
+    let recorded = lease.clone();
+    if let Err(e) = store
+        .on_store(move |store| store.add_lease(&recorded))
+        .await
+    {
+        // A user nothing would drop is not handed out.
+        if let Err(dropped) =
+            execute(role.plugin(), settings, lease.revocation(), config.as_ref()).await
+        {
+            warn!(
+                role = role_name,
+                username, "Unable to drop an unrecorded user: {dropped}"
+            );
+        }
+        return Err(e);
+    }
+    info!(
+        target: "salusd::audit",
+        role = role_name,
+        lease = lease_id,
+        username,
+        ttl,
+        "Database user created"
+    );
+    let minted = Minted {
+        username: &username,
+        password: &password,
+        lease_id: &lease_id,
+        ttl,
+        expires_at,
+    };
+    Ok(Zeroizing::new(serde_json::to_vec(&minted)?))
+}
+
+/// Drop the users whose leases are up at `now`, in seconds since the Unix
+/// epoch, and forget their leases, returning how many were dropped.
+///
+/// Nothing is dropped while the store is locked, since a plugin's
+/// `config_key` cannot be read; a lease whose user could not be dropped is
+/// tried again on the next pass.
+///
+/// # Errors
+///
+/// * Returns an error if the leases cannot be read.
+pub(crate) async fn reap(store: &mut impl OnStore, plugins: &Plugins, now: u64) -> Result<usize> {
+    let expired = store
+        .on_store(move |store| store.expired_leases(now))
+        .await?;
+    let mut dropped = 0usize;
+    for lease in expired {
+        match drop_user(store, plugins, &lease).await {
+            Ok(()) => {}
+            Err(e) if matches!(e.downcast_ref(), Some(Error::StoreNotUnlocked)) => {
+                debug!("Expired leases wait for the store to be unlocked");
+                break;
+            }
+            Err(e) => {
+                warn!(
+                    lease = lease.id(),
+                    "Unable to drop the user of an expired lease: {e}"
+                );
+                continue;
+            }
+        }
+        let id = lease.id().clone();
+        store.on_store(move |store| store.remove_lease(&id)).await?;
+        info!(
+            target: "salusd::audit",
+            role = lease.role(),
+            lease = lease.id(),
+            username = lease.username(),
+            "Database user dropped"
+        );
+        dropped = dropped.saturating_add(1);
+    }
+    Ok(dropped)
+}
+
+/// Keep the user of lease `id` for `increment` seconds from `now` (its
+/// role's `ttl` when `None`), though no longer than its plugin's `max_ttl`
+/// after it was minted, running the role's `renewal` statements first.
+/// Answers with the lease as renewed, or `None` when it is unknown or up.
+///
+/// # Errors
+///
+/// * Returns an error if the store is locked, the lease's role is no longer
+///   configured, its plugin refuses the increment or fails to run the
+///   statements, or the lease cannot be rewritten.
+pub(crate) async fn renew(
+    store: &mut impl OnStore,
+    plugins: &Plugins,
+    roles: &DatabaseRoles,
+    id: &str,
+    increment: Option<u64>,
+    now: u64,
+) -> Result<Option<Lease>> {
+    let wanted = id.to_string();
+    let Some(lease) = store
+        .on_store(move |store| store.lease(&wanted))
+        .await?
+        .filter(|lease| lease.expires_at() > now)
+    else {
+        return Ok(None);
+    };
+    let role = roles
+        .0
+        .get(lease.role())
+        .ok_or_else(|| Error::KeyNotFound(format!("{CREDS_PREFIX}{}", lease.role())))?;
+    let (settings, ttl) = plugins.admit(
+        lease.plugin(),
+        lease.role(),
+        Some(increment.unwrap_or(role.ttl())),
+    )?;
+    let mut expires_at = now.saturating_add(ttl);
+    if let Some(issued_at) = lease.issued_at() {
+        expires_at = expires_at.min(issued_at.saturating_add(settings.max_ttl()));
+    }
+    let expires_at = expires_at.max(lease.expires_at());
+    if !role.renewal().is_empty() {
+        let stored = settings.clone();
+        let config = store
+            .on_store(move |store| stored_config(store, &stored))
+            .await?;
+        let expiration = rfc3339(expires_at)?;
+        let renewal = render(
+            role.renewal(),
+            &[("name", lease.username()), ("expiration", &expiration)],
+        );
+        execute(lease.plugin(), settings, &renewal, config.as_ref()).await?;
+    }
+    let wanted = id.to_string();
+    let renewed = store
+        .on_store(move |store| store.renew_lease(&wanted, expires_at, now))
+        .await?;
+    if let Some(renewed) = &renewed {
+        info!(
+            target: "salusd::audit",
+            role = renewed.role(),
+            lease = renewed.id(),
+            username = renewed.username(),
+            expires_at,
+            "Lease renewed"
+        );
+    }
+    Ok(renewed)
+}
+
+/// Drop the user of lease `id` now and forget the lease, answering whether
+/// there was one.
+///
+/// # Errors
+///
+/// * Returns an error if the store is locked, the lease's plugin fails to
+///   drop the user, or the lease cannot be read or removed. A lease whose
+///   user could not be dropped is kept.
+pub(crate) async fn revoke(store: &mut impl OnStore, plugins: &Plugins, id: &str) -> Result<bool> {
+    let wanted = id.to_string();
+    let Some(lease) = store.on_store(move |store| store.lease(&wanted)).await? else {
+        return Ok(false);
+    };
+    drop_user(store, plugins, &lease).await?;
+    let wanted = id.to_string();
+    store
+        .on_store(move |store| store.remove_lease(&wanted))
+        .await?;
+    info!(
+        target: "salusd::audit",
+        role = lease.role(),
+        lease = lease.id(),
+        username = lease.username(),
+        "Database user revoked"
+    );
+    Ok(true)
+}
+
+/// Have the plugin of `lease` run its `revocation` statements.
+async fn drop_user(store: &mut impl OnStore, plugins: &Plugins, lease: &Lease) -> Result<()> {
+    let settings = plugins.get(lease.plugin())?;
+    let stored = settings.clone();
+    let config = store
+        .on_store(move |store| stored_config(store, &stored))
+        .await?;
+    execute(
+        lease.plugin(),
+        settings,
+        lease.revocation(),
+        config.as_ref(),
+    )
+    .await
+}
+
+/// `secs` since the Unix epoch, as an RFC 3339 timestamp; empty if it cannot
+/// be one.
+fn rfc3339(secs: u64) -> Result<String> {
+    Ok(i64::try_from(secs)
+        .ok()
+        .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+        .map(|at| at.format(&Rfc3339))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// `statements` with every `{{name}}` of `values` filled in.
+fn render(statements: &[String], values: &[(&str, &str)]) -> Vec<String> {
+    statements
+        .iter()
+        .map(|statement| {
+            values
+                .iter()
+                .fold(statement.clone(), |statement, (name, value)| {
+                    statement.replace(&format!("{{{{{name}}}}}"), value)
+                })
+        })
+        .collect()
+}
+
+/// The part of a user's name that tells its role: the role's letters, digits
+/// and underscores, lowercased and cut short.
+fn name_part(role: &str) -> String {
+    role.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .map(|c| c.to_ascii_lowercase())
+        .take(MAX_ROLE_IN_NAME)
+        .collect()
+}
+
+/// `length` random characters from `charset`.
+fn random(charset: Charset, length: u32) -> Result<Zeroizing<String>> {
+    let spec = SecretSpec::Chars { length, charset };
+    Ok(Zeroizing::new(generate_secret(&spec, |buf| {
+        Ok(rand::fill(buf)?)
+    })?))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{
+        env, fs,
+        os::unix::fs::PermissionsExt as _,
+        sync::{Arc, RwLock},
+    };
+
+    use anyhow::{Result, bail};
+    use serde_json::Value;
+
+    use super::{DatabaseRoles, mint, name_part, reap, render, renew, revoke};
+    use crate::{config::ConfigSalusd, plugin::Plugins, store::test::unlocked_store};
+
+    #[test]
+    fn statements_are_filled_in() {
+        let statements = vec![
+            "CREATE ROLE \"{{name}}\" PASSWORD '{{password}}' VALID UNTIL '{{expiration}}'"
+                .to_string(),
+        ];
+        assert_eq!(
+            render(&statements, &[("name", "v_ro_1"), ("password", "pw")]),
+            vec!["CREATE ROLE \"v_ro_1\" PASSWORD 'pw' VALID UNTIL '{{expiration}}'"]
+        );
+        assert_eq!(name_part("Read-Only.Reports_2024"), "readonlyreports_");
+    }
+
+    #[tokio::test]
+    async fn a_read_creates_a_user_that_is_dropped_when_its_lease_is_up() -> Result<()> {
+        // The plugin appends each request it is sent to a log.
+        let dir = env::temp_dir().join(format!("salusd-database-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let log = dir.join("requests.log");
+        let path = dir.join("plugin");
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\nread request\necho \"$request\" >> {:?}\necho '{{\"protocol\":1}}'\n",
+                log.display()
+            ),
+        )?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                &format!(
+                    "[plugins.pg]\ncommand = {:?}\nconfig_key = \"admin/pg\"\n\
+                     [database_roles.ro]\nplugin = \"pg\"\nttl = 60\n\
+                     creation = [\"CREATE ROLE {{{{name}}}} PASSWORD '{{{{password}}}}'\"]\n\
+                     revocation = [\"DROP ROLE {{{{name}}}}\"]\n",
+                    path.display()
+                ),
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let (plugins, roles) = (Plugins::from(&config), DatabaseRoles::from(&config));
+        assert_eq!(roles.role_for("database/creds/ro"), Some("ro"));
+        assert_eq!(roles.role_for("database/creds/rw"), None);
+
+        let store = unlocked_store()?;
+        let _stored = store.store(
+            "admin/pg",
+            br#"{"dsn":"postgres://admin@db"}"#.to_vec(),
+            false,
+        )?;
+        let mut store = Arc::new(RwLock::new(store));
+
+        let minted: Value =
+            serde_json::from_slice(&mint(&mut store, &plugins, &roles, "ro").await?)?;
+        let (Some(username), Some(password)) = (
+            minted.get("username").and_then(Value::as_str),
+            minted.get("password").and_then(Value::as_str),
+        ) else {
+            bail!("expected a username and password, got {minted}");
+        };
+        assert!(username.starts_with("v_ro_"));
+        assert_eq!(password.len(), 32);
+        let (Some(60), Some(expires_at)) = (
+            minted.get("ttl").and_then(Value::as_u64),
+            minted.get("expires_at").and_then(Value::as_u64),
+        ) else {
+            bail!("expected a ttl of 60 and an expiry, got {minted}");
+        };
+
+        // Not up yet, then up.
+        assert_eq!(
+            reap(&mut store, &plugins, expires_at.saturating_sub(1)).await?,
+            0
+        );
+        assert_eq!(reap(&mut store, &plugins, expires_at).await?, 1);
+        assert_eq!(reap(&mut store, &plugins, expires_at).await?, 0);
+        let requests = fs::read_to_string(&log)?;
+        let requests = requests.lines().collect::<Vec<_>>();
+        match requests.as_slice() {
+            [create, drop] => {
+                assert!(create.contains(&format!("CREATE ROLE {username} PASSWORD '{password}'")));
+                assert!(create.contains("postgres://admin@db"));
+                assert!(drop.contains(&format!("\"DROP ROLE {username}\"")));
+            }
+            other => bail!("expected a create and a drop, got {other:?}"),
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_lease_can_be_renewed_up_to_the_max_ttl_and_revoked() -> Result<()> {
+        let dir = env::temp_dir().join(format!("salusd-lease-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let log = dir.join("requests.log");
+        let path = dir.join("plugin");
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\nread request\necho \"$request\" >> {:?}\necho '{{\"protocol\":1}}'\n",
+                log.display()
+            ),
+        )?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                &format!(
+                    "[plugins.pg]\ncommand = {:?}\nmax_ttl = 120\n\
+                     [database_roles.ro]\nplugin = \"pg\"\nttl = 60\n\
+                     creation = [\"CREATE ROLE {{{{name}}}}\"]\n\
+                     revocation = [\"DROP ROLE {{{{name}}}}\"]\n\
+                     renewal = [\"ALTER ROLE {{{{name}}}} VALID UNTIL '{{{{expiration}}}}'\"]\n",
+                    path.display()
+                ),
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        let (plugins, roles) = (Plugins::from(&config), DatabaseRoles::from(&config));
+        let mut store = Arc::new(RwLock::new(unlocked_store()?));
+
+        let minted: Value =
+            serde_json::from_slice(&mint(&mut store, &plugins, &roles, "ro").await?)?;
+        let (Some(lease_id), Some(username), Some(expires_at)) = (
+            minted.get("lease_id").and_then(Value::as_str),
+            minted.get("username").and_then(Value::as_str),
+            minted.get("expires_at").and_then(Value::as_u64),
+        ) else {
+            bail!("expected a lease, got {minted}");
+        };
+        let now = expires_at.saturating_sub(60);
+
+        // A renewal past the plugin's max_ttl is refused, and one within it
+        // is held to 120 seconds after the user was made.
+        assert!(
+            renew(&mut store, &plugins, &roles, lease_id, Some(121), now)
+                .await
+                .is_err()
+        );
+        let later = now.saturating_add(30);
+        let Some(renewed) = renew(&mut store, &plugins, &roles, lease_id, Some(100), later).await?
+        else {
+            bail!("the lease was not renewed");
+        };
+        assert_eq!(renewed.expires_at(), now.saturating_add(120));
+        assert_eq!(reap(&mut store, &plugins, expires_at).await?, 0);
+        assert!(
+            renew(&mut store, &plugins, &roles, "unknown", None, now)
+                .await?
+                .is_none()
+        );
+
+        assert!(revoke(&mut store, &plugins, lease_id).await?);
+        assert!(!revoke(&mut store, &plugins, lease_id).await?);
+        assert!(
+            renew(&mut store, &plugins, &roles, lease_id, None, now)
+                .await?
+                .is_none()
+        );
+        let requests = fs::read_to_string(&log)?;
+        let requests = requests.lines().collect::<Vec<_>>();
+        match requests.as_slice() {
+            [_create, alter, drop] => {
+                assert!(alter.contains(&format!("ALTER ROLE {username} VALID UNTIL")));
+                assert!(drop.contains(&format!("\"DROP ROLE {username}\"")));
+            }
+            other => bail!("expected a create, an alter and a drop, got {other:?}"),
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,743 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Plugins: programs, named in `[plugins.<name>]`, that mint short-lived
+//! credentials on demand, so engines for databases or clouds live outside
+//! the daemon.
+//!
+//! The daemon runs the program once per call and speaks newline-delimited
+//! JSON over its stdin and stdout: it writes one request and closes stdin,
+//! and reads one reply. Every request and reply carries
+//! [`PLUGIN_PROTOCOL`], and a reply of any other version is refused.
+//!
+//! ```text
+//! -> {"protocol":1,"op":"health"}
+//! <- {"protocol":1,"version":"1.2.0","roles":["readonly"]}
+//! -> {"protocol":1,"op":"mint","role":"readonly","ttl":600,"config":{...}}
+//! <- {"protocol":1,"credential":{"username":"v-ro-1","password":"..."},"ttl":600}
+//! <- {"protocol":1,"error":"role readonly is not set up"}
+//! -> {"protocol":1,"op":"execute","statements":["DROP ROLE ..."],"config":{...}}
+//! <- {"protocol":1}
+//! ```
+//!
+//! `execute` runs statements the daemon wrote, for the [`database`] engine.
+//! The program starts with an empty environment but for `PATH`; what it needs
+//! to reach the service it mints for comes in `config`, a JSON document read
+//! from the store under the plugin's `config_key`.
+//!
+//! A plugin may instead be a WebAssembly `module` the daemon runs in itself,
+//! sandboxed, with the `wasm-plugins` feature; it is handed the same requests
+//! and gives the same replies.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    io::ErrorKind,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use libsalus::{Credential, PluginInfo, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader},
+    process::Command,
+    task::{JoinSet, spawn_blocking},
+    time::timeout,
+};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    config::{ConfigSalusd, PluginSettings},
+    error::Error,
+    store::ShareStore,
+};
+
+pub(crate) mod database;
+#[cfg(feature = "wasm-plugins")]
+mod wasm;
+
+/// The version of the protocol the daemon speaks with its plugins.
+pub(crate) const PLUGIN_PROTOCOL: u32 = 1;
+
+/// The longest reply read from a plugin, in bytes.
+const MAX_REPLY_BYTES: u64 = 64 * 1024;
+
+/// The plugins the daemon may call, by name
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Plugins(BTreeMap<String, PluginSettings>);
+
+impl From<&ConfigSalusd> for Plugins {
+    fn from(config: &ConfigSalusd) -> Self {
+        Self(config.plugins().clone())
+    }
+}
+
+/// A request to a plugin.
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    protocol: u32,
+    op: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statements: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<&'a Value>,
+}
+
+/// A plugin's reply.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Reply {
+    protocol: u32,
+    error: Option<String>,
+    version: Option<String>,
+    credential: Option<Map<String, Value>>,
+    ttl: Option<u64>,
+}
+
+impl Plugins {
+    /// The plugin `name` and the seconds a credential for `role` it mints is
+    /// to be good for, when its settings allow the mint.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if there is no such plugin, it does not mint for
+    ///   `role`, or `ttl` is longer than its `max_ttl`.
+    pub(crate) fn admit(
+        &self,
+        name: &str,
+        role: &str,
+        ttl: Option<u64>,
+    ) -> Result<(&PluginSettings, u64), Error> {
+        let settings = self.get(name)?;
+        if !settings.roles().is_empty() && !settings.roles().iter().any(|r| r == role) {
+            return Err(Error::PluginRole(name.to_string(), role.to_string()));
+        }
+        match ttl {
+            Some(ttl) if ttl > settings.max_ttl() => {
+                Err(Error::PluginTtl(name.to_string(), settings.max_ttl()))
+            }
+            Some(0) => Err(Error::PluginTtl(name.to_string(), settings.max_ttl())),
+            Some(ttl) => Ok((settings, ttl)),
+            None => Ok((settings, settings.max_ttl())),
+        }
+    }
+
+    /// The settings of plugin `name`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if there is no such plugin.
+    pub(crate) fn get(&self, name: &str) -> Result<&PluginSettings, Error> {
+        self.0
+            .get(name)
+            .ok_or_else(|| Error::PluginNotFound(name.to_string()))
+    }
+
+    /// Check every plugin's health at once.
+    pub(crate) async fn health(&self) -> Vec<PluginInfo> {
+        let mut checks = JoinSet::new();
+        for (name, settings) in &self.0 {
+            let (name, settings) = (name.clone(), settings.clone());
+            let _abort = checks.spawn(async move { health(&name, &settings).await });
+        }
+        let mut plugins = checks.join_all().await;
+        plugins.sort_by(|a, b| a.name().cmp(b.name()));
+        plugins
+    }
+}
+
+/// Check the health of plugin `name`.
+async fn health(name: &str, settings: &PluginSettings) -> PluginInfo {
+    let request = Request {
+        protocol: PLUGIN_PROTOCOL,
+        op: "health",
+        role: None,
+        ttl: None,
+        statements: None,
+        config: None,
+    };
+    let reply = call(name, settings, &request).await;
+    if let Err(e) = &reply {
+        warn!(plugin = name, "Plugin failed its health check: {e}");
+    }
+    let (problem, version) = match reply {
+        Ok(reply) => (None, reply.version),
+        Err(e) => (Some(e.to_string()), None),
+    };
+    PluginInfo::builder()
+        .name(name)
+        .healthy(problem.is_none())
+        .maybe_problem(problem)
+        .maybe_version(version)
+        .roles(settings.roles().clone())
+        .max_ttl(settings.max_ttl())
+        .build()
+}
+
+/// Have plugin `name` mint a credential for `role`, good for `ttl` seconds
+/// or less, handing it `config`.
+///
+/// # Errors
+///
+/// * Returns an error if the plugin cannot be run, does not answer in time
+///   or in its protocol, or reports that it failed.
+pub(crate) async fn mint(
+    name: &str,
+    settings: &PluginSettings,
+    role: &str,
+    ttl: u64,
+    config: Option<&Value>,
+) -> Result<Credential> {
+    let request = Request {
+        protocol: PLUGIN_PROTOCOL,
+        op: "mint",
+        role: Some(role),
+        ttl: Some(ttl),
+        statements: None,
+        config,
+    };
+    let reply = call(name, settings, &request).await?;
+    let Some(credential) = reply.credential else {
+        return Err(
+            Error::PluginFailed(name.to_string(), "no credential in its reply".into()).into(),
+        );
+    };
+    // The plugin may cut the time short, never stretch it.
+    let ttl = reply.ttl.map_or(ttl, |granted| granted.min(ttl));
+    let fields = credential
+        .into_iter()
+        .map(|(field, value)| match value {
+            Value::String(value) => (field, value),
+            value => (field, value.to_string()),
+        })
+        .collect();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    info!(
+        target: "salusd::audit",
+        plugin = name,
+        role,
+        ttl,
+        "Credential minted"
+    );
+    Ok(Credential::builder()
+        .plugin(name)
+        .role(role)
+        .fields(fields)
+        .ttl(ttl)
+        .expires_at(now.saturating_add(ttl))
+        .build())
+}
+
+/// Have plugin `name` run `statements`, handing it `config`.
+///
+/// # Errors
+///
+/// * Returns an error if the plugin cannot be run, does not answer in time
+///   or in its protocol, or reports that it failed.
+pub(crate) async fn execute(
+    name: &str,
+    settings: &PluginSettings,
+    statements: &[String],
+    config: Option<&Value>,
+) -> Result<()> {
+    let request = Request {
+        protocol: PLUGIN_PROTOCOL,
+        op: "execute",
+        role: None,
+        ttl: None,
+        statements: Some(statements),
+        config,
+    };
+    let _reply = call(name, settings, &request).await?;
+    Ok(())
+}
+
+/// The JSON document stored under the plugin's `config_key`, if it has one.
+///
+/// # Errors
+///
+/// * Returns an error if the store is locked, which a plugin is never called
+///   while it is, or the key holds no JSON document.
+pub(crate) fn stored_config(
+    store: &ShareStore,
+    settings: &PluginSettings,
+) -> Result<Option<Value>> {
+    if store.unlocked_for().is_none() {
+        return Err(Error::StoreNotUnlocked.into());
+    }
+    let Some(key) = settings.config_key() else {
+        return Ok(None);
+    };
+    let Response::Value(Some(plaintext)) = store.read(key)? else {
+        return Err(Error::KeyNotFound(key.clone()).into());
+    };
+    let plaintext = Zeroizing::new(plaintext);
+    let config =
+        serde_json::from_slice::<Value>(&plaintext).map_err(|_e| Error::NotJson(key.clone()))?;
+    Ok(Some(config))
+}
+
+/// Where plugin work runs its store calls: a request's handler, which times
+/// them and stops them once the request is cancelled, or the store itself,
+/// for work no request asked for.
+pub(crate) trait OnStore {
+    /// Run `store_fn` on the store from the blocking pool.
+    async fn on_store<T>(
+        &mut self,
+        store_fn: impl FnOnce(&ShareStore) -> Result<T> + Send + 'static,
+    ) -> Result<T>
+    where
+        T: Send + 'static;
+}
+
+impl OnStore for Arc<RwLock<ShareStore>> {
+    async fn on_store<T>(
+        &mut self,
+        store_fn: impl FnOnce(&ShareStore) -> Result<T> + Send + 'static,
+    ) -> Result<T>
+    where
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        spawn_blocking(move || {
+            let store = match store.read() {
+                Ok(share_store) => share_store,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            store_fn(&store)
+        })
+        .await?
+    }
+}
+
+/// Run plugin `name` with `request`, and read its reply.
+async fn call(name: &str, settings: &PluginSettings, request: &Request<'_>) -> Result<Reply> {
+    let failed = |problem: String| Error::PluginFailed(name.to_string(), problem);
+    let request = serde_json::to_vec(request)?;
+    let (reply, status) = if let Some(module) = settings.module() {
+        (run_module(name, settings, module, request).await?, None)
+    } else {
+        let (status, reply) = run_program(name, settings, request).await?;
+        (reply, Some(status))
+    };
+    if reply.trim().is_empty() {
+        let problem = match status {
+            Some(status) => format!("it gave no reply and {status}"),
+            None => "it gave no reply".to_string(),
+        };
+        return Err(failed(problem).into());
+    }
+    let reply = serde_json::from_str::<Reply>(&reply)
+        .map_err(|e| failed(format!("its reply is not the protocol's JSON: {e}")))?;
+    if reply.protocol != PLUGIN_PROTOCOL {
+        return Err(
+            Error::PluginProtocol(name.to_string(), reply.protocol, PLUGIN_PROTOCOL).into(),
+        );
+    }
+    if let Some(error) = reply.error {
+        return Err(failed(error).into());
+    }
+    if let Some(status) = status
+        && !status.success()
+    {
+        return Err(failed(status.to_string()).into());
+    }
+    Ok(reply)
+}
+
+/// Run the module of plugin `name` with `request`, off the runtime: it is
+/// held to its fuel rather than a timeout.
+#[cfg(feature = "wasm-plugins")]
+async fn run_module(
+    name: &str,
+    settings: &PluginSettings,
+    module: &Path,
+    request: Vec<u8>,
+) -> Result<String> {
+    let (name, settings, module) = (name.to_string(), settings.clone(), module.to_path_buf());
+    spawn_blocking(move || wasm::run(&name, &settings, &module, &request)).await?
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+#[allow(clippy::unused_async)]
+async fn run_module(
+    name: &str,
+    _settings: &PluginSettings,
+    _module: &Path,
+    _request: Vec<u8>,
+) -> Result<String> {
+    let problem = "salusd was built without the wasm-plugins feature".to_string();
+    Err(Error::PluginFailed(name.to_string(), problem).into())
+}
+
+/// Run the program of plugin `name` with `request` on its stdin, answering
+/// with how it exited and the line it wrote.
+async fn run_program(
+    name: &str,
+    settings: &PluginSettings,
+    mut line: Vec<u8>,
+) -> Result<(ExitStatus, String)> {
+    let failed = |problem: String| Error::PluginFailed(name.to_string(), problem);
+    let command = settings
+        .command()
+        .as_ref()
+        .ok_or_else(|| Error::PluginCommand(name.to_string()))?;
+    line.push(b'\n');
+    // Dropped, and so killed, when the call times out.
+    let mut child = Command::new(command)
+        .args(settings.args())
+        .env_clear()
+        .envs(env::var_os("PATH").map(|path| ("PATH", path)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| failed(format!("unable to run {}: {e}", command.display())))?;
+    let exchange = async {
+        // A plugin may answer without reading its request.
+        if let Some(mut stdin) = child.stdin.take()
+            && let Err(e) = stdin.write_all(&line).await
+            && e.kind() != ErrorKind::BrokenPipe
+        {
+            return Err(e.into());
+        }
+        let mut reply = String::new();
+        if let Some(stdout) = child.stdout.take() {
+            let _read = BufReader::new(stdout.take(MAX_REPLY_BYTES))
+                .read_line(&mut reply)
+                .await?;
+        }
+        let status = child.wait().await?;
+        Ok::<_, anyhow::Error>((status, reply))
+    };
+    let limit = Duration::from_millis(settings.timeout_ms());
+    let ran = timeout(limit, exchange)
+        .await
+        .map_err(|_| Error::PluginTimeout(name.to_string(), settings.timeout_ms()))??;
+    Ok(ran)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{env, fs, os::unix::fs::PermissionsExt as _, path::PathBuf};
+
+    use anyhow::{Result, bail};
+    use serde_json::json;
+
+    use super::{Plugins, mint};
+    use crate::{config::ConfigSalusd, error::Error};
+
+    /// Write `script` as an executable plugin, returning its path.
+    fn plugin(name: &str, script: &str) -> Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("salusd-plugin-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}"))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        Ok(path)
+    }
+
+    /// Write the module in `wat` as a plugin, returning its path.
+    #[cfg(feature = "wasm-plugins")]
+    fn module(name: &str, wat: &str) -> Result<PathBuf> {
+        let path = plugin(&format!("{name}.wasm"), "")?;
+        fs::write(&path, wat::parse_str(wat)?)?;
+        Ok(path)
+    }
+
+    /// A module answering a health check with `health` and anything else with
+    /// `mint`, after running `setup`, which leaves an `i32` status; a status
+    /// other than 0 answers with an error.
+    #[cfg(feature = "wasm-plugins")]
+    fn replying(imports: &str, setup: &str, health: &str, mint: &str) -> String {
+        let quoted = |reply: &str| reply.replace('"', "\\\"");
+        format!(
+            "(module\n\
+             {imports}\n\
+             (memory (export \"memory\") 1)\n\
+             (data (i32.const 0) \"{health}\")\n\
+             (data (i32.const 1024) \"{mint}\")\n\
+             (data (i32.const 2048) \"{failed}\")\n\
+             (func (export \"salus_alloc\") (param i32) (result i32) (i32.const 4096))\n\
+             (func (export \"salus_call\") (param $at i32) (param $len i32) (result i64)\n\
+             (if (i32.ne {setup} (i32.const 0))\n\
+             (then (return (i64.const {failed_at}))))\n\
+             ;; The request starts {{\"protocol\":1,\"op\":\", so its op's first\n\
+             ;; letter is at 20.\n\
+             (if (result i64)\n\
+             (i32.eq (i32.load8_u (i32.add (local.get $at) (i32.const 20))) (i32.const 104))\n\
+             (then (i64.const {health_at}))\n\
+             (else (i64.const {mint_at})))))\n",
+            health = quoted(health),
+            mint = quoted(mint),
+            failed = quoted(FAILED),
+            health_at = health.len(),
+            mint_at = (1024_u64 << 32) | u64::try_from(mint.len()).unwrap_or(0),
+            failed_at = (2048_u64 << 32) | u64::try_from(FAILED.len()).unwrap_or(0),
+        )
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    const HEALTHY: &str = r#"{"protocol":1,"version":"0.1.0"}"#;
+
+    #[cfg(feature = "wasm-plugins")]
+    const FAILED: &str = r#"{"protocol":1,"error":"setup failed"}"#;
+
+    fn plugins(toml: &str) -> Result<Plugins> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(toml, ::config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        Ok(Plugins::from(&config))
+    }
+
+    #[test]
+    fn mints_are_held_to_the_plugin_settings() -> Result<()> {
+        let plugins =
+            plugins("[plugins.pg]\ncommand = \"/bin/true\"\nmax_ttl = 600\nroles = [\"ro\"]\n")?;
+        assert_eq!(plugins.admit("pg", "ro", None)?.1, 600);
+        assert_eq!(plugins.admit("pg", "ro", Some(60))?.1, 60);
+        assert!(matches!(
+            plugins.admit("pg", "admin", None),
+            Err(Error::PluginRole(..))
+        ));
+        assert!(matches!(
+            plugins.admit("pg", "ro", Some(601)),
+            Err(Error::PluginTtl(_, 600))
+        ));
+        assert!(matches!(
+            plugins.admit("mysql", "ro", None),
+            Err(Error::PluginNotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_plugin_mints_over_stdio() -> Result<()> {
+        // Echo the role and the config's user back as the credential.
+        let path = plugin(
+            "mint",
+            "read request\n\
+             case \"$request\" in\n\
+             *'\"op\":\"health\"'*) echo '{\"protocol\":1,\"version\":\"0.1.0\"}' ;;\n\
+             *'\"role\":\"ro\"'*'\"user\":\"admin\"'*) \
+             echo '{\"protocol\":1,\"credential\":{\"username\":\"v-ro\",\"port\":5432},\"ttl\":30}' ;;\n\
+             *) echo '{\"protocol\":1,\"error\":\"unexpected request\"}' ;;\n\
+             esac\n",
+        )?;
+        let plugins = plugins(&format!("[plugins.pg]\ncommand = {:?}\n", path.display()))?;
+        let health = plugins.health().await;
+        match health.as_slice() {
+            [info] => {
+                assert!(info.healthy(), "{:?}", info.problem());
+                assert_eq!(info.version().as_deref(), Some("0.1.0"));
+            }
+            other => bail!("expected one plugin, got {other:?}"),
+        }
+        let (settings, ttl) = plugins.admit("pg", "ro", Some(60))?;
+        let config = json!({"user": "admin"});
+        let credential = mint("pg", settings, "ro", ttl, Some(&config)).await?;
+        assert_eq!(
+            credential.fields(),
+            &[
+                ("port".to_string(), "5432".to_string()),
+                ("username".to_string(), "v-ro".to_string()),
+            ]
+        );
+        assert_eq!(credential.ttl(), 30);
+        let refused = mint("pg", settings, "rw", ttl, Some(&config)).await;
+        assert!(refused.is_err_and(|e| e.to_string().contains("unexpected request")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn plugins_that_misbehave_fail_their_calls() -> Result<()> {
+        let slow = plugin("slow", "sleep 5\n")?;
+        let old = plugin("old", "echo '{\"protocol\":0}'\n")?;
+        let silent = plugin("silent", "exit 3\n")?;
+        let plugins = plugins(&format!(
+            "[plugins.slow]\ncommand = {:?}\ntimeout_ms = 100\n\
+             [plugins.old]\ncommand = {:?}\n\
+             [plugins.silent]\ncommand = {:?}\n\
+             [plugins.missing]\ncommand = \"/nonexistent/plugin\"\n",
+            slow.display(),
+            old.display(),
+            silent.display(),
+        ))?;
+        let health = plugins.health().await;
+        assert_eq!(health.len(), 4);
+        assert!(health.iter().all(|info| !info.healthy()));
+        let problem = |name: &str| {
+            health
+                .iter()
+                .find(|info| info.name() == name)
+                .and_then(|info| info.problem().clone())
+                .unwrap_or_default()
+        };
+        let problem_slow = problem("slow");
+        assert!(problem_slow.contains("100 ms"), "{problem_slow}");
+        let problem_old = problem("old");
+        assert!(problem_old.contains("protocol 0"), "{problem_old}");
+        let problem_silent = problem("silent");
+        assert!(problem_silent.contains("no reply"), "{problem_silent}");
+        let problem_missing = problem("missing");
+        assert!(
+            problem_missing.contains("unable to run"),
+            "{problem_missing}"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[tokio::test]
+    async fn a_module_mints_in_its_sandbox() -> Result<()> {
+        let path = module(
+            "mint",
+            &replying(
+                "",
+                "(i32.const 0)",
+                HEALTHY,
+                r#"{"protocol":1,"credential":{"username":"v-ro"},"ttl":30}"#,
+            ),
+        )?;
+        let plugins = plugins(&format!("[plugins.pg]\nmodule = {:?}\n", path.display()))?;
+        let health = plugins.health().await;
+        match health.as_slice() {
+            [info] => {
+                assert!(info.healthy(), "{:?}", info.problem());
+                assert_eq!(info.version().as_deref(), Some("0.1.0"));
+            }
+            other => bail!("expected one plugin, got {other:?}"),
+        }
+        let (settings, ttl) = plugins.admit("pg", "ro", Some(60))?;
+        let credential = mint("pg", settings, "ro", ttl, None).await?;
+        assert_eq!(
+            credential.fields(),
+            &[("username".to_string(), "v-ro".to_string())]
+        );
+        assert_eq!(credential.ttl(), 30);
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[tokio::test]
+    async fn a_module_reaches_only_what_its_capabilities_grant() -> Result<()> {
+        let random = replying(
+            "(import \"salus\" \"random\" (func $random (param i32 i32) (result i32)))",
+            "(call $random (i32.const 3072) (i32.const 32))",
+            HEALTHY,
+            HEALTHY,
+        );
+        let wasi = replying(
+            "(import \"wasi_snapshot_preview1\" \"fd_write\" \
+             (func (param i32 i32 i32 i32) (result i32)))",
+            "(i32.const 0)",
+            HEALTHY,
+            HEALTHY,
+        );
+        let granted = module("granted", &random)?;
+        let ungranted = module("ungranted", &random)?;
+        let wasi = module("wasi", &wasi)?;
+        let plugins = plugins(&format!(
+            "[plugins.granted]\nmodule = {:?}\ncapabilities = [\"random\"]\n\
+             [plugins.ungranted]\nmodule = {:?}\ncapabilities = [\"log\", \"clock\"]\n\
+             [plugins.wasi]\nmodule = {:?}\ncapabilities = [\"log\", \"random\", \"clock\"]\n",
+            granted.display(),
+            ungranted.display(),
+            wasi.display(),
+        ))?;
+        let health = plugins.health().await;
+        let problem = |name: &str| {
+            health
+                .iter()
+                .find(|info| info.name() == name)
+                .map(|info| info.problem().clone())
+        };
+        assert_eq!(problem("granted"), Some(None));
+        let problem_ungranted = problem("ungranted").flatten().unwrap_or_default();
+        assert!(
+            problem_ungranted.contains("imports salus.random, which its capabilities do not grant"),
+            "{problem_ungranted}"
+        );
+        let problem_wasi = problem("wasi").flatten().unwrap_or_default();
+        assert!(
+            problem_wasi.contains("imports wasi_snapshot_preview1.fd_write"),
+            "{problem_wasi}"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[tokio::test]
+    async fn modules_that_run_away_are_stopped() -> Result<()> {
+        let spins = module(
+            "spins",
+            &replying(
+                "",
+                "(loop $spin (br $spin)) (i32.const 0)",
+                HEALTHY,
+                HEALTHY,
+            ),
+        )?;
+        let grows = module(
+            "grows",
+            &replying(
+                "",
+                "(i32.lt_s (memory.grow (i32.const 16)) (i32.const 0))",
+                HEALTHY,
+                HEALTHY,
+            ),
+        )?;
+        let big = module(
+            "big",
+            "(module (memory (export \"memory\") 4)\n\
+             (func (export \"salus_alloc\") (param i32) (result i32) (i32.const 0))\n\
+             (func (export \"salus_call\") (param i32 i32) (result i64) (i64.const 0)))\n",
+        )?;
+        let plugins = plugins(&format!(
+            "[plugins.spins]\nmodule = {:?}\nfuel = 100000\n\
+             [plugins.grows]\nmodule = {:?}\nmax_memory_bytes = 131072\n\
+             [plugins.big]\nmodule = {:?}\nmax_memory_bytes = 131072\n",
+            spins.display(),
+            grows.display(),
+            big.display(),
+        ))?;
+        let health = plugins.health().await;
+        assert!(health.iter().all(|info| !info.healthy()));
+        let problem = |name: &str| {
+            health
+                .iter()
+                .find(|info| info.name() == name)
+                .and_then(|info| info.problem().clone())
+                .unwrap_or_default()
+        };
+        let problem_spins = problem("spins");
+        assert!(
+            problem_spins.contains("ran out of its 100000 fuel"),
+            "{problem_spins}"
+        );
+        let problem_grows = problem("grows");
+        assert!(problem_grows.contains("setup failed"), "{problem_grows}");
+        let problem_big = problem("big");
+        assert!(problem_big.contains("trapped"), "{problem_big}");
+        Ok(())
+    }
+}
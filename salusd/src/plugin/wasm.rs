@@ -0,0 +1,208 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Plugins run in the daemon as WebAssembly modules, named by `module` in
+//! their `[plugins.<name>]` table.
+//!
+//! A module speaks the same JSON as a program, through its memory rather than
+//! stdio. It exports `memory`, `salus_alloc(len) -> ptr`, which the daemon
+//! calls for room to write a request into, and `salus_call(ptr, len) -> i64`,
+//! which answers with where its reply is: the reply's offset in the high 32
+//! bits and its length in the low 32.
+//!
+//! A module is sandboxed: it reaches nothing outside its memory but the host
+//! functions its `capabilities` grant, imported from `salus`, and a module
+//! importing anything else is refused before it runs. Each call loads the
+//! module afresh, with `fuel` to spend and at most `max_memory_bytes` of
+//! memory, so a module that loops or grows without end is stopped.
+
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use aws_lc_rs::rand;
+use tracing::info;
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    core::TrapCode,
+};
+
+use super::MAX_REPLY_BYTES;
+use crate::{
+    config::{PluginCapability, PluginSettings},
+    error::Error,
+};
+
+/// The module a plugin imports host functions from.
+const HOST_MODULE: &str = "salus";
+
+/// The most bytes of one line a module logs.
+const MAX_LOG_BYTES: usize = 1024;
+
+/// What a running module's store holds.
+struct Host {
+    plugin: String,
+    limits: StoreLimits,
+}
+
+/// The name of the host function `capability` grants.
+fn function(capability: PluginCapability) -> &'static str {
+    match capability {
+        PluginCapability::Log => "log",
+        PluginCapability::Random => "random",
+        PluginCapability::Clock => "now",
+    }
+}
+
+/// Run the module at `path` for plugin `name` with `request`, answering
+/// with its reply.
+///
+/// # Errors
+///
+/// * Returns an error if the module cannot be read or loaded, imports what
+///   its capabilities do not grant, traps, runs out of fuel, or leaves its
+///   reply out of its memory.
+pub(super) fn run(
+    name: &str,
+    settings: &PluginSettings,
+    path: &Path,
+    request: &[u8],
+) -> Result<String> {
+    let failed = |problem: String| Error::PluginFailed(name.to_string(), problem);
+    let trapped = |e: wasmi::Error| match e.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => failed(format!("it ran out of its {} fuel", settings.fuel())),
+        _ => failed(format!("it trapped: {e}")),
+    };
+    let wasm =
+        fs::read(path).map_err(|e| failed(format!("unable to read {}: {e}", path.display())))?;
+    let mut config = Config::default();
+    let _config = config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &wasm)
+        .map_err(|e| failed(format!("{} is not a module: {e}", path.display())))?;
+    for import in module.imports() {
+        let granted = import.module() == HOST_MODULE
+            && settings
+                .capabilities()
+                .iter()
+                .any(|capability| function(*capability) == import.name());
+        if !granted {
+            return Err(failed(format!(
+                "it imports {}.{}, which its capabilities do not grant",
+                import.module(),
+                import.name()
+            ))
+            .into());
+        }
+    }
+
+    let max_memory = usize::try_from(settings.max_memory_bytes()).unwrap_or(usize::MAX);
+    let host = Host {
+        plugin: name.to_string(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(max_memory)
+            .instances(1)
+            .build(),
+    };
+    let mut store = Store::new(&engine, host);
+    store.limiter(|host| &mut host.limits);
+    store
+        .set_fuel(settings.fuel())
+        .map_err(|e| failed(format!("unable to give it fuel: {e}")))?;
+    let mut linker = Linker::new(&engine);
+    for capability in settings.capabilities() {
+        let _linker = match capability {
+            PluginCapability::Log => linker.func_wrap(HOST_MODULE, "log", log)?,
+            PluginCapability::Random => linker.func_wrap(HOST_MODULE, "random", random)?,
+            PluginCapability::Clock => linker.func_wrap(HOST_MODULE, "now", now)?,
+        };
+    }
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(trapped)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or_else(|| failed("it exports no memory".into()))?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "salus_alloc")
+        .map_err(|_e| failed("it exports no salus_alloc(i32) -> i32".into()))?;
+    let call = instance
+        .get_typed_func::<(i32, i32), i64>(&store, "salus_call")
+        .map_err(|_e| failed("it exports no salus_call(i32, i32) -> i64".into()))?;
+
+    let len = i32::try_from(request.len())?;
+    let at = alloc.call(&mut store, len).map_err(trapped)?;
+    memory
+        .write(&mut store, usize::try_from(at)?, request)
+        .map_err(|_e| failed("it gave no room for the request".into()))?;
+    let packed = u64::from_ne_bytes(
+        call.call(&mut store, (at, len))
+            .map_err(trapped)?
+            .to_ne_bytes(),
+    );
+    let (reply_at, reply_len) = (packed >> 32, packed & u64::from(u32::MAX));
+    if reply_len > MAX_REPLY_BYTES {
+        return Err(failed(format!("its reply is over {MAX_REPLY_BYTES} bytes")).into());
+    }
+    let mut reply = vec![0; usize::try_from(reply_len)?];
+    memory
+        .read(&store, usize::try_from(reply_at)?, &mut reply)
+        .map_err(|_e| failed("its reply is outside its memory".into()))?;
+    String::from_utf8(reply).map_err(|_e| failed("its reply is not UTF-8".into()).into())
+}
+
+/// The memory of the module calling a host function.
+fn memory(caller: &Caller<'_, Host>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+/// `salus.log(ptr, len)`: log the text at `ptr`, cut to [`MAX_LOG_BYTES`].
+#[allow(
+    clippy::needless_pass_by_value,
+    reason = "wasmi hands a host function its caller by value"
+)]
+fn log(caller: Caller<'_, Host>, at: i32, len: i32) {
+    let (Ok(at), Ok(len), Some(memory)) =
+        (usize::try_from(at), usize::try_from(len), memory(&caller))
+    else {
+        return;
+    };
+    let end = at.saturating_add(len.min(MAX_LOG_BYTES));
+    if let Some(line) = memory.data(&caller).get(at..end) {
+        let plugin = caller.data().plugin.as_str();
+        info!(plugin, "{}", String::from_utf8_lossy(line));
+    }
+}
+
+/// `salus.random(ptr, len) -> i32`: fill the `len` bytes at `ptr` with random
+/// bytes, answering 0, or -1 if it cannot.
+fn random(mut caller: Caller<'_, Host>, at: i32, len: i32) -> i32 {
+    let (Ok(at), Ok(len), Some(memory)) =
+        (usize::try_from(at), usize::try_from(len), memory(&caller))
+    else {
+        return -1;
+    };
+    let filled = memory
+        .data_mut(&mut caller)
+        .get_mut(at..at.saturating_add(len))
+        .is_some_and(|bytes| rand::fill(bytes).is_ok());
+    if filled { 0 } else { -1 }
+}
+
+/// `salus.now() -> i64`: the time, in seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX)
+        })
+}
@@ -0,0 +1,351 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! `salusd bench`: store, read and unlock throughput and latency against a
+//! throwaway store.
+//!
+//! The store is generated and unlocked for the run, over a database file in
+//! the temp dir (removed afterwards) or in memory, and filled with `--keys`
+//! values first. Each operation then runs `--count` times at each
+//! `--concurrency`, split across that many threads sharing the store behind
+//! the same lock the daemon's connections share: stores and reads take it
+//! shared, an unlock takes it alone. Each call is timed on its own, and the
+//! report gives each run's throughput over its wall time and its latency
+//! percentiles.
+
+use std::{
+    env, fs,
+    io::{Write as _, stdout},
+    path::PathBuf,
+    process,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Result, anyhow, bail};
+use libsalus::{Response, Store};
+use serde::Serialize;
+
+use crate::{
+    db::{
+        Backend, SharedBackend,
+        backend::{GroupCommit, MemoryBackend, RedbBackend},
+    },
+    runtime::cli::{BenchArgs, BenchOp},
+    store::ShareStore,
+};
+
+/// What `salusd bench` prints.
+#[derive(Debug, Serialize)]
+struct Report {
+    backend: &'static str,
+    commit_window_ms: u64,
+    keys: u32,
+    value_size: usize,
+    results: Vec<Measured>,
+}
+
+/// One operation at one concurrency.
+#[derive(Debug, Serialize)]
+struct Measured {
+    op: &'static str,
+    concurrency: u16,
+    ops: usize,
+    seconds: f64,
+    ops_per_sec: f64,
+    latency_us: Latency,
+}
+
+/// Latency percentiles, in microseconds.
+#[derive(Debug, Serialize)]
+struct Latency {
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+/// A database file in the temp dir, removed when dropped.
+#[derive(Debug)]
+struct TempDatabase(PathBuf);
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        let _removed = fs::remove_file(&self.0);
+    }
+}
+
+/// The store under test, and the shares that unlock it.
+struct Bench {
+    store: RwLock<ShareStore>,
+    shares: Vec<String>,
+    value: Vec<u8>,
+    keys: u32,
+}
+
+/// Run the benchmark `args` describes and print its report.
+pub(crate) fn run(args: &BenchArgs) -> Result<()> {
+    let report = bench(args)?;
+    let mut out = stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn bench(args: &BenchArgs) -> Result<Report> {
+    let (backend, _temp) = if args.memory() {
+        let backend: Backend = Arc::new(SharedBackend::new(MemoryBackend::default()));
+        (backend, None)
+    } else {
+        let temp = TempDatabase(temp_path()?);
+        let redb = RedbBackend::create(&temp.0)?;
+        let window = Duration::from_millis(args.commit_window_ms());
+        let backend = if window.is_zero() {
+            Arc::new(SharedBackend::new(redb))
+        } else {
+            Arc::new(SharedBackend::new(GroupCommit::new(redb, window)))
+        };
+        (backend, Some(temp))
+    };
+    let bench = Bench::new(backend, args.keys(), args.value_size())?;
+    let mut results = vec![];
+    for op in args.ops() {
+        for concurrency in args.concurrency() {
+            results.push(bench.measure(*op, *concurrency, args.count())?);
+        }
+    }
+    Ok(Report {
+        backend: if args.memory() { "memory" } else { "file" },
+        commit_window_ms: args.commit_window_ms(),
+        keys: args.keys(),
+        value_size: args.value_size(),
+        results,
+    })
+}
+
+impl Bench {
+    /// A new store in `backend`, unlocked, with a `value_size` byte value
+    /// under each of `keys` keys.
+    fn new(backend: Backend, keys: u32, value_size: usize) -> Result<Self> {
+        let mut store = ShareStore::builder().backend(backend).build();
+        let Response::Shares(generated) = store.gen_shares()? else {
+            bail!("the benchmark store did not generate shares");
+        };
+        let shares = generated
+            .shares()
+            .iter()
+            .take(usize::from(store.get_threshold()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let bench = Self {
+            store: RwLock::new(store),
+            shares,
+            value: vec![b'x'; value_size],
+            keys,
+        };
+        bench.unlock()?;
+        let entries = (0..keys)
+            .map(|i| {
+                Store::builder()
+                    .key(key(i))
+                    .value("x".repeat(value_size))
+                    .force(true)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        match bench.read_store().store_batch(&entries, false)? {
+            Response::BatchStored(outcome) if outcome.applied() => {}
+            other => bail!("the benchmark store was not filled: {other:?}"),
+        }
+        Ok(bench)
+    }
+
+    /// Run `op` `count` times across `concurrency` threads.
+    fn measure(&self, op: BenchOp, concurrency: u16, count: u32) -> Result<Measured> {
+        let threads = u32::from(concurrency);
+        let started = Instant::now();
+        let latencies = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|thread| {
+                    let share = count
+                        .checked_div(threads)
+                        .unwrap_or_default()
+                        .saturating_add(u32::from(
+                            thread < count.checked_rem(threads).unwrap_or_default(),
+                        ));
+                    scope.spawn(move || self.work(op, thread, share))
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .map_err(|_| anyhow!("a benchmark thread panicked"))?
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let seconds = started.elapsed().as_secs_f64();
+        let mut latencies = latencies.into_iter().flatten().collect::<Vec<_>>();
+        latencies.sort_unstable();
+        let ops = latencies.len();
+        Ok(Measured {
+            op: match op {
+                BenchOp::Store => "store",
+                BenchOp::Read => "read",
+                BenchOp::Unlock => "unlock",
+            },
+            concurrency,
+            ops,
+            seconds,
+            ops_per_sec: if seconds > 0.0 {
+                f64::from(u32::try_from(ops)?) / seconds
+            } else {
+                0.0
+            },
+            latency_us: Latency {
+                p50: percentile(&latencies, 50),
+                p90: percentile(&latencies, 90),
+                p99: percentile(&latencies, 99),
+                max: latencies.last().map_or(0.0, micros),
+            },
+        })
+    }
+
+    /// One thread's share of a run: `op` `count` times, each timed.
+    fn work(&self, op: BenchOp, thread: u32, count: u32) -> Result<Vec<Duration>> {
+        let mut latencies = Vec::with_capacity(usize::try_from(count)?);
+        for i in 0..count {
+            let key = key(i
+                .wrapping_add(thread.wrapping_mul(count))
+                .checked_rem(self.keys)
+                .unwrap_or_default());
+            let started = Instant::now();
+            match op {
+                BenchOp::Store => self.store(&key)?,
+                BenchOp::Read => self.read(&key)?,
+                BenchOp::Unlock => self.unlock()?,
+            }
+            latencies.push(started.elapsed());
+        }
+        Ok(latencies)
+    }
+
+    fn store(&self, key: &str) -> Result<()> {
+        match self.read_store().store(key, self.value.clone(), true)? {
+            Response::Success => Ok(()),
+            other => bail!("expected {key} to be stored, got {other:?}"),
+        }
+    }
+
+    fn read(&self, key: &str) -> Result<()> {
+        match self.read_store().read(key)? {
+            Response::Value(Some(_)) => Ok(()),
+            other => bail!("expected a value under {key}, got {other:?}"),
+        }
+    }
+
+    /// Hand over the shares and unlock, as a client's `add-share` and
+    /// `unlock` requests do.
+    fn unlock(&self) -> Result<()> {
+        let mut store = self.write_store();
+        for share in &self.shares {
+            store.add_share(share.clone());
+        }
+        match store.unlock()? {
+            Response::Success => Ok(()),
+            other => bail!("expected the store to unlock, got {other:?}"),
+        }
+    }
+
+    fn read_store(&self) -> RwLockReadGuard<'_, ShareStore> {
+        match self.store.read() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn write_store(&self) -> RwLockWriteGuard<'_, ShareStore> {
+        match self.store.write() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// The `i`th of the keys the benchmark writes and reads.
+fn key(i: u32) -> String {
+    format!("bench/key-{i}")
+}
+
+/// A database file name in the temp dir no other run will pick.
+fn temp_path() -> Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    Ok(env::temp_dir().join(format!("salusd-bench-{}-{nanos}.redb", process::id())))
+}
+
+/// The nearest-rank `percent`th percentile of `sorted`, in microseconds.
+fn percentile(sorted: &[Duration], percent: usize) -> f64 {
+    let rank = sorted
+        .len()
+        .saturating_mul(percent)
+        .div_ceil(100)
+        .saturating_sub(1);
+    sorted.get(rank).map_or(0.0, micros)
+}
+
+fn micros(duration: &Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use anyhow::{Result, bail};
+    use clap::Parser as _;
+
+    use super::{bench, percentile};
+    use crate::runtime::cli::{Cli, Command};
+
+    #[test]
+    fn every_op_is_measured_at_every_concurrency() -> Result<()> {
+        for backend in ["--memory", "--commit-window-ms=1"] {
+            let cli = Cli::try_parse_from([
+                "salusd",
+                "bench",
+                "-n",
+                "9",
+                "--concurrency",
+                "1,4",
+                "--keys",
+                "4",
+                backend,
+            ])?;
+            let Some(Command::Bench(args)) = cli.command() else {
+                bail!("expected a bench");
+            };
+            let report = bench(args)?;
+            assert_eq!(report.results.len(), 6);
+            for measured in &report.results {
+                assert_eq!(measured.ops, 9);
+                assert!(measured.latency_us.p50 <= measured.latency_us.max);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn percentiles_take_the_nearest_rank() {
+        let sorted = (1..=10).map(Duration::from_micros).collect::<Vec<_>>();
+        assert!((percentile(&sorted, 50) - 5.0).abs() < 0.001);
+        assert!((percentile(&sorted, 99) - 10.0).abs() < 0.001);
+        assert!(percentile(&[], 50).abs() < 0.001);
+    }
+}
@@ -6,11 +6,15 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use clap::{ArgAction, Parser};
+use std::path::PathBuf;
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, value_parser};
 use config::{ConfigError, Map, Source, Value, ValueKind};
-use getset::Getters;
+use getset::{CopyGetters, Getters};
+
+use tracing_subscriber::EnvFilter;
 
-use crate::config::PathDefaults;
+use crate::config::{ConfigFormat, PathDefaults};
 
 #[derive(Clone, Debug, Getters, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -41,6 +45,13 @@ pub(crate) struct Cli {
     /// The absolute path to a non-standard config file
     #[clap(short, long, help = "Specify the absolute path to the config file")]
     config_absolute_path: Option<String>,
+    /// The config file's format, when its extension does not name it
+    #[clap(
+        long,
+        value_enum,
+        help = "Read the config file as toml, yaml or json, whatever its extension"
+    )]
+    config_format: Option<ConfigFormat>,
     /// The absolute path to a non-standard tracing output file
     #[clap(
         short,
@@ -48,6 +59,15 @@ pub(crate) struct Cli {
         help = "Specify the absolute path to the tracing output file"
     )]
     tracing_absolute_path: Option<String>,
+    /// Tracing directives for this run only, added after `[tracing]
+    /// directives` so they win for the targets they name
+    #[clap(
+        long,
+        value_name = "DIRECTIVES",
+        value_parser = log_filter,
+        help = "Add tracing directives for this run, e.g. salusd::store=trace,redb=warn"
+    )]
+    log_filter: Option<String>,
     /// The absolute path to a non-standard database file
     #[clap(short, long, help = "Specify the absolute path to the database file")]
     database_absolute_path: Option<String>,
@@ -55,6 +75,215 @@ pub(crate) struct Cli {
     /// or the platform default is used)
     #[clap(short, long, help = "Specify the path to the IPC socket")]
     socket_path: Option<String>,
+    /// Start read-only: reads are served, but changes to the store are refused
+    /// until a client lifts the mode
+    #[clap(long, help = "Refuse changes to the store until the mode is lifted")]
+    read_only: bool,
+    /// A maintenance command to run instead of the daemon
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Maintenance commands that run in place of the daemon.
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Work with the database directly, without a running daemon
+    Offline {
+        #[command(subcommand)]
+        action: OfflineAction,
+    },
+    /// Check a backup written by `salusc backup` against its manifest and
+    /// swap it in for the database
+    ///
+    /// The file's checksums and row counts must match the manifest, and the
+    /// backup must belong to the same share set as the database it replaces,
+    /// so the shares in hand still unlock it. The database is replaced in one
+    /// rename, and the one it replaces is kept beside it as
+    /// `<DB>.pre-restore-<SECONDS>`. Stop salusd first; it holds a lock on the
+    /// database.
+    Restore {
+        /// The backup file; its manifest is read from `<BACKUP>.manifest.json`
+        #[arg(value_name = "BACKUP")]
+        backup: PathBuf,
+        /// The database to replace (default: the daemon's database)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+        /// An age identity file that opens an encrypted backup
+        #[arg(short, long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Only check the backup, leaving the database alone
+        #[arg(long)]
+        verify_only: bool,
+        /// Restore a backup taken under a different share set than the
+        /// database's; unlocking it then needs that set's shares
+        #[arg(long)]
+        force: bool,
+    },
+    /// Measure store, read and unlock throughput and latency against a
+    /// throwaway store, printing the results as JSON
+    ///
+    /// The store is created for the run in a temporary database file (or in
+    /// memory with `--memory`) and removed afterwards, so neither the daemon's
+    /// database nor its config is touched. Each operation runs `--count` times
+    /// at each `--concurrency`, split across that many threads taking the
+    /// store's lock the way the daemon's connections do.
+    Bench(BenchArgs),
+    /// Change or inspect the membership of a cluster (needs the `cluster`
+    /// feature)
+    ///
+    /// The request goes to a running node over the cluster's own connections,
+    /// using this config's `[cluster]` key file, and follows the node to the
+    /// cluster's leader when it is not the leader itself.
+    Cluster {
+        #[command(subcommand)]
+        action: ClusterAction,
+    },
+    /// Load the configuration as the daemon would, print every setting with
+    /// where it came from, and check it, exiting non-zero on any problem
+    ///
+    /// Values must be in range, the tracing directives must parse, and the
+    /// database, log, socket and transport key paths must exist or be
+    /// creatable. The daemon is not started and nothing is written, so a
+    /// deployment can check a configuration before restarting salusd with it.
+    ValidateConfig,
+    /// Write a config file holding every setting at its default, commented
+    /// out, to edit from
+    ///
+    /// The file goes where the daemon looks for it (or to `-c`), in TOML.
+    /// The daemon starts without one, on its built-in defaults, so this is
+    /// only a starting point for changing them.
+    InitConfig {
+        /// Replace a config file that already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Work with the daemon's configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+/// The `salusd config` commands.
+#[derive(Clone, Copy, Debug, Subcommand)]
+pub(crate) enum ConfigAction {
+    /// Print the configuration the daemon would run with, merged from the
+    /// config file, the environment and the flags, each setting with where it
+    /// came from
+    ///
+    /// A setting named for a secret (a password, token, or the like), and the
+    /// credentials in a URL, are printed as `<redacted>`.
+    Show,
+}
+
+/// The `salusd cluster` commands.
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum ClusterAction {
+    /// Add this node, as `[cluster]` describes it, to the cluster the node at
+    /// ADDR belongs to
+    ///
+    /// Start this node's daemon first: it is sent the store as soon as it is
+    /// added, and votes once it has caught up.
+    Join {
+        /// The cluster address of any node of the cluster
+        #[arg(value_name = "ADDR")]
+        addr: String,
+    },
+    /// Remove a node from its cluster
+    Leave {
+        /// The node to remove (default: this node)
+        #[arg(long, value_name = "ID")]
+        node: Option<u64>,
+        /// The cluster address of a node to ask (default: this node's)
+        #[arg(long, value_name = "ADDR")]
+        addr: Option<String>,
+    },
+    /// Print a node's view of its cluster as JSON: its role and term, the
+    /// leader, how far its log is applied, and the members
+    Status {
+        /// The cluster address of the node to ask (default: this node's)
+        #[arg(long, value_name = "ADDR")]
+        addr: Option<String>,
+    },
+}
+
+/// The options of `salusd bench`.
+#[derive(Args, Clone, CopyGetters, Debug, Getters)]
+pub(crate) struct BenchArgs {
+    /// The operations to measure
+    #[arg(
+        long = "op",
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [BenchOp::Store, BenchOp::Read, BenchOp::Unlock],
+    )]
+    #[getset(get = "pub(crate)")]
+    ops: Vec<BenchOp>,
+    /// How many times to run each operation at each concurrency
+    #[arg(short = 'n', long, default_value_t = 1000, value_parser = value_parser!(u32).range(1..))]
+    #[getset(get_copy = "pub(crate)")]
+    count: u32,
+    /// The numbers of threads to run each operation on
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [1, 4, 16],
+        value_parser = value_parser!(u16).range(1..),
+    )]
+    #[getset(get = "pub(crate)")]
+    concurrency: Vec<u16>,
+    /// How many keys are stored before the run, and written and read during it
+    #[arg(long, default_value_t = 1024, value_parser = value_parser!(u32).range(1..))]
+    #[getset(get_copy = "pub(crate)")]
+    keys: u32,
+    /// The length of each value, in bytes
+    #[arg(long, default_value_t = 64)]
+    #[getset(get_copy = "pub(crate)")]
+    value_size: usize,
+    /// Keep the store in memory rather than in a database file
+    #[arg(long)]
+    #[getset(get_copy = "pub(crate)")]
+    memory: bool,
+    /// Group writes to the database file arriving within this many
+    /// milliseconds into one transaction, as `[storage] commit_window_ms` does
+    #[arg(long, default_value_t = 0, conflicts_with = "memory")]
+    #[getset(get_copy = "pub(crate)")]
+    commit_window_ms: u64,
+}
+
+/// An operation `salusd bench` measures.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum BenchOp {
+    /// Overwrite a value
+    Store,
+    /// Read a value
+    Read,
+    /// Hand over the threshold of shares and unlock the store
+    Unlock,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub(crate) enum OfflineAction {
+    /// Read values straight from the database, reconstructing the key from
+    /// shares entered here
+    ///
+    /// A disaster-recovery path for when the daemon or its socket is broken.
+    /// The shares are read from the terminal (or one per line from stdin), and
+    /// the key they reconstruct is held only in this process's memory: it is
+    /// never written anywhere. A single value is written to stdout exactly as
+    /// stored; with `--prefix`, every value under the prefix is printed as
+    /// `KEY<TAB>VALUE`. The database must not be held open by a running salusd.
+    Read {
+        /// The database file (default: the daemon's database)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+        /// Read every value whose key starts with KEY
+        #[arg(short, long)]
+        prefix: bool,
+        /// The key to read
+        #[arg(value_name = "KEY")]
+        key: String,
+    },
 }
 
 impl Source for Cli {
@@ -87,6 +316,12 @@ impl Source for Cli {
                 Value::new(Some(&origin), ValueKind::Boolean(true)),
             );
         }
+        if self.read_only {
+            let _old = map.insert(
+                "read_only".to_string(),
+                Value::new(Some(&origin), ValueKind::Boolean(true)),
+            );
+        }
         // The `*_absolute_path` config/tracing/database overrides are consumed
         // directly through `PathDefaults`, not the config struct, so they are
         // intentionally not emitted here. The socket path, however, lives in
@@ -101,6 +336,14 @@ impl Source for Cli {
     }
 }
 
+/// Check that `--log-filter` parses as tracing directives.
+fn log_filter(directives: &str) -> Result<String, String> {
+    EnvFilter::builder()
+        .parse(directives)
+        .map(|_filter| directives.to_string())
+        .map_err(|e| e.to_string())
+}
+
 impl PathDefaults for Cli {
     fn env_prefix(&self) -> String {
         env!("CARGO_PKG_NAME").to_ascii_uppercase()
@@ -114,6 +357,10 @@ impl PathDefaults for Cli {
         self.config_absolute_path.clone()
     }
 
+    fn config_format(&self) -> Option<ConfigFormat> {
+        self.config_format
+    }
+
     fn tracing_absolute_path(&self) -> Option<String> {
         self.tracing_absolute_path.clone()
     }
@@ -125,11 +372,13 @@ impl PathDefaults for Cli {
 
 #[cfg(test)]
 mod test {
-    use anyhow::Result;
+    use std::path::Path;
+
+    use anyhow::{Result, bail};
     use clap::Parser;
     use config::{Config, Map, Source};
 
-    use super::Cli;
+    use super::{BenchOp, Cli, ClusterAction, Command, OfflineAction};
     use crate::config::{ConfigSalusd, env_source};
 
     #[test]
@@ -143,17 +392,142 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn log_filter_must_parse() -> Result<()> {
+        let cli = Cli::try_parse_from(["salusd", "--log-filter", "salusd::store=trace,redb=warn"])?;
+        assert_eq!(
+            cli.log_filter().as_deref(),
+            Some("salusd::store=trace,redb=warn")
+        );
+        // It is for this run only, so it is not layered into the config.
+        assert!(cli.collect()?.is_empty());
+        assert!(Cli::try_parse_from(["salusd", "--log-filter", "salusd=loud"]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn collect_includes_set_flags() -> Result<()> {
-        let cli = Cli::try_parse_from(["salusd", "-vv", "-e", "-s", "/tmp/s.sock"])?;
+        let cli = Cli::try_parse_from(["salusd", "-vv", "-e", "-s", "/tmp/s.sock", "--read-only"])?;
         let map = cli.collect()?;
         assert!(map.contains_key("verbose"));
         assert!(map.contains_key("enable_std_output"));
         assert!(map.contains_key("socket_path"));
+        assert!(map.contains_key("read_only"));
         assert!(!map.contains_key("quiet"));
         Ok(())
     }
 
+    #[test]
+    fn offline_read_parses_after_the_daemon_flags() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusd",
+            "-d",
+            "/tmp/a.redb",
+            "offline",
+            "read",
+            "--db",
+            "/tmp/b.redb",
+            "-p",
+            "db/",
+        ])?;
+        let Some(Command::Offline {
+            action: OfflineAction::Read { db, prefix, key },
+        }) = cli.command()
+        else {
+            bail!("expected an offline read");
+        };
+        assert_eq!(db.as_deref(), Some(Path::new("/tmp/b.redb")));
+        assert!(*prefix);
+        assert_eq!(key, "db/");
+        assert!(Cli::try_parse_from(["salusd", "offline", "read"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn restore_takes_a_backup_and_its_options() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusd",
+            "restore",
+            "/backups/salus.redb.age",
+            "-i",
+            "/keys/backup.txt",
+            "--verify-only",
+        ])?;
+        let Some(Command::Restore {
+            backup,
+            db,
+            identity,
+            verify_only,
+            force,
+        }) = cli.command()
+        else {
+            bail!("expected a restore");
+        };
+        assert_eq!(backup, Path::new("/backups/salus.redb.age"));
+        assert_eq!(db, &None);
+        assert_eq!(identity.as_deref(), Some(Path::new("/keys/backup.txt")));
+        assert!(*verify_only);
+        assert!(!*force);
+        assert!(Cli::try_parse_from(["salusd", "restore"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bench_takes_lists_of_ops_and_concurrencies() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusd",
+            "bench",
+            "--op",
+            "read,unlock",
+            "-n",
+            "50",
+            "--concurrency",
+            "2,8",
+            "--memory",
+        ])?;
+        let Some(Command::Bench(args)) = cli.command() else {
+            bail!("expected a bench");
+        };
+        assert_eq!(args.ops(), &[BenchOp::Read, BenchOp::Unlock]);
+        assert_eq!(args.count(), 50);
+        assert_eq!(args.concurrency(), &[2, 8]);
+        assert_eq!(args.keys(), 1024);
+        assert!(args.memory());
+        let Some(Command::Bench(args)) = Cli::try_parse_from(["salusd", "bench"])?.command else {
+            bail!("expected a bench");
+        };
+        assert_eq!(args.ops().len(), 3);
+        assert!(Cli::try_parse_from(["salusd", "bench", "--concurrency", "0"]).is_err());
+        assert!(
+            Cli::try_parse_from(["salusd", "bench", "--memory", "--commit-window-ms", "5"])
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cluster_leave_names_a_node_and_where_to_ask() -> Result<()> {
+        let cli = Cli::try_parse_from([
+            "salusd",
+            "cluster",
+            "leave",
+            "--node",
+            "3",
+            "--addr",
+            "10.0.0.1:7400",
+        ])?;
+        let Some(Command::Cluster {
+            action: ClusterAction::Leave { node, addr },
+        }) = cli.command()
+        else {
+            bail!("expected a cluster leave");
+        };
+        assert_eq!(*node, Some(3));
+        assert_eq!(addr.as_deref(), Some("10.0.0.1:7400"));
+        assert!(Cli::try_parse_from(["salusd", "cluster", "join"]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn cli_default_does_not_clobber_env_verbose() -> Result<()> {
         let mut env = Map::new();
@@ -0,0 +1,144 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Wiping the key material as the daemon goes down, whether it panicked or
+//! was told to stop.
+//!
+//! A panic aborts the daemon rather than unwinding out of the request that
+//! raised it with the store half changed: the hook first zeroizes the store's
+//! key and shares, unless another thread holds the store at the time, then
+//! logs the panic. `SIGTERM`, `SIGINT` and `SIGQUIT` stop the daemon too, once
+//! it has zeroized them, waiting for the store if it must. The signals a
+//! process cannot safely handle (`SIGKILL`, `SIGSEGV` and the like) are left
+//! alone; keeping the key out of a core dump is up to the core dump settings.
+
+use std::{
+    panic, process,
+    sync::{Arc, RwLock, TryLockError, Weak},
+};
+
+use anyhow::Result;
+use tokio::task::spawn_blocking;
+#[cfg(unix)]
+use tokio::{
+    select,
+    signal::unix::{SignalKind, signal},
+};
+use tracing::error;
+
+use crate::store::ShareStore;
+
+/// Have a panic anywhere in the daemon wipe `share_store`, as far as it can,
+/// and abort.
+pub(super) fn wipe_on_panic(share_store: &Arc<RwLock<ShareStore>>) {
+    let share_store = Arc::downgrade(share_store);
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let wiped = try_wipe(&share_store);
+        report(info);
+        error!(wiped, "salusd panicked, and is aborting");
+        process::abort();
+    }));
+}
+
+/// Wipe `share_store` without waiting for it, answering whether it was: a
+/// thread holding it, maybe the one panicking, keeps its key in memory until
+/// the process is gone.
+fn try_wipe(share_store: &Weak<RwLock<ShareStore>>) -> bool {
+    let Some(share_store) = share_store.upgrade() else {
+        return true;
+    };
+    match share_store.try_write() {
+        Ok(mut store) => store.wipe(),
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().wipe(),
+        Err(TryLockError::WouldBlock) => return false,
+    }
+    true
+}
+
+/// Wipe `share_store`, once whatever holds it lets go.
+pub(super) async fn wipe(share_store: &Arc<RwLock<ShareStore>>) {
+    let share_store = share_store.clone();
+    let wiped = spawn_blocking(move || match share_store.write() {
+        Ok(mut store) => store.wipe(),
+        Err(poisoned) => poisoned.into_inner().wipe(),
+    });
+    if let Err(e) = wiped.await {
+        error!("Unable to wipe the key material: {e}");
+    }
+}
+
+/// Completes with the name of the signal that tells the daemon to stop.
+///
+/// # Errors
+///
+/// * Returns an error if the signals cannot be listened for.
+#[cfg(unix)]
+pub(super) fn terminated() -> Result<impl Future<Output = &'static str>> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut quit = signal(SignalKind::quit())?;
+    Ok(async move {
+        select! {
+            _ = terminate.recv() => "SIGTERM",
+            _ = interrupt.recv() => "SIGINT",
+            _ = quit.recv() => "SIGQUIT",
+        }
+    })
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+pub(super) fn terminated() -> Result<impl Future<Output = &'static str>> {
+    Ok(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        "Ctrl-C"
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, RwLock};
+
+    use anyhow::{Result, bail};
+
+    use super::{try_wipe, wipe};
+    use crate::store::{ShareStore, test::unlocked_store};
+
+    fn sealed(share_store: &Arc<RwLock<ShareStore>>) -> Result<bool> {
+        let Ok(store) = share_store.read() else {
+            bail!("the store was poisoned");
+        };
+        Ok(store.unlocked_for().is_none())
+    }
+
+    #[tokio::test]
+    async fn the_key_is_wiped_unless_the_store_is_held() -> Result<()> {
+        let share_store = Arc::new(RwLock::new(unlocked_store()?));
+        let weak = Arc::downgrade(&share_store);
+        {
+            let Ok(_held) = share_store.read() else {
+                bail!("the store was poisoned");
+            };
+            assert!(!try_wipe(&weak));
+        }
+        assert!(!sealed(&share_store)?);
+        assert!(try_wipe(&weak));
+        assert!(sealed(&share_store)?);
+        // Nothing is left to wipe once the store is gone.
+        drop(share_store);
+        assert!(try_wipe(&weak));
+
+        let share_store = Arc::new(RwLock::new(unlocked_store()?));
+        wipe(&share_store).await;
+        assert!(sealed(&share_store)?);
+        Ok(())
+    }
+}
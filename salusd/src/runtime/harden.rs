@@ -0,0 +1,121 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Keeping an unsealed daemon's memory from being read off it: no core file
+//! is written when it crashes, and on Linux it is not dumpable, so processes
+//! of its user can neither `ptrace` it nor read `/proc/<pid>/mem` without
+//! `CAP_SYS_PTRACE`. Root can still do either.
+//!
+//! On Windows a crash brings up no error dialog, and Windows Error Reporting
+//! leaves the daemon's heap, where the key is held, out of what it collects.
+//! Windows has nothing like the dumpable flag, though: a debugger run as the
+//! daemon's user can still attach to it, and a `LocalDumps` policy an
+//! administrator sets for full dumps still writes its memory out.
+
+use anyhow::Result;
+#[cfg(windows)]
+use anyhow::bail;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use rustix::process::{DumpableBehavior, set_dumpable_behavior};
+#[cfg(unix)]
+use rustix::process::{Resource, Rlimit, setrlimit};
+#[cfg(not(any(unix, windows)))]
+use tracing::warn;
+#[cfg(windows)]
+use windows_sys::Win32::System::{
+    Diagnostics::Debug::{GetErrorMode, SEM_NOGPFAULTERRORBOX, SetErrorMode},
+    ErrorReporting::{WER_FAULT_REPORTING_FLAG_NOHEAP, WerSetFlags},
+};
+
+/// Turn off core files for the daemon, soft and hard limit alike so it cannot
+/// turn them back on, and have it not be dumpable where it can.
+///
+/// # Errors
+///
+/// * Returns an error if the limit or the dumpable flag cannot be set.
+#[cfg(unix)]
+pub(super) fn harden() -> Result<()> {
+    setrlimit(
+        Resource::Core,
+        Rlimit {
+            current: Some(0),
+            maximum: Some(0),
+        },
+    )?;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    set_dumpable_behavior(DumpableBehavior::NotDumpable)?;
+    Ok(())
+}
+
+/// Have a crash raise no error dialog, and leave the heap out of what Windows
+/// Error Reporting collects of it.
+///
+/// # Errors
+///
+/// * Returns an error if Windows Error Reporting's flags cannot be set.
+#[cfg(windows)]
+#[allow(unsafe_code)]
+pub(super) fn harden() -> Result<()> {
+    // SAFETY: both only read and set flags of this process.
+    unsafe {
+        let _previous = SetErrorMode(GetErrorMode() | SEM_NOGPFAULTERRORBOX);
+    }
+    // SAFETY: this only sets a flag of this process.
+    let set = unsafe { WerSetFlags(WER_FAULT_REPORTING_FLAG_NOHEAP) };
+    if set < 0 {
+        bail!("WerSetFlags failed with {set:#010x}");
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+#[allow(clippy::unnecessary_wraps)]
+pub(super) fn harden() -> Result<()> {
+    warn!("Core dumps and debugger attachment cannot be turned off on this platform");
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use anyhow::Result;
+    use rustix::process::{Resource, getrlimit};
+
+    use super::harden;
+
+    #[test]
+    fn no_core_file_is_written_once_hardened() -> Result<()> {
+        harden()?;
+        let core = getrlimit(Resource::Core);
+        assert_eq!(core.current, Some(0));
+        assert_eq!(core.maximum, Some(0));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(
+            rustix::process::dumpable_behavior()?,
+            rustix::process::DumpableBehavior::NotDumpable
+        );
+        Ok(())
+    }
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use anyhow::Result;
+    use windows_sys::Win32::System::Diagnostics::Debug::{GetErrorMode, SEM_NOGPFAULTERRORBOX};
+
+    use super::harden;
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn a_crash_raises_no_dialog_once_hardened() -> Result<()> {
+        harden()?;
+        // SAFETY: this only reads a flag of this process.
+        let mode = unsafe { GetErrorMode() };
+        assert_ne!(mode & SEM_NOGPFAULTERRORBOX, 0);
+        Ok(())
+    }
+}
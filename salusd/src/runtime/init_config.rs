@@ -0,0 +1,313 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! `salusd init-config`: write a config file holding every setting at its
+//! default, commented out, to edit from.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context as _, Result};
+
+use crate::{
+    config::{ConfigFormat, config_file_path, config_format},
+    error::Error,
+    runtime::cli::Cli,
+};
+
+/// The config file `init-config` writes. Each setting is commented out at its
+/// built-in default, as `#key = value`, so the daemon's own defaults still
+/// apply until a line is uncommented; descriptions are `# `-prefixed.
+const TEMPLATE: &str = r#"# salusd configuration
+#
+# Every setting is shown at its built-in default and commented out; remove the
+# leading '#' to change one. Environment variables (SALUSD_KEY_TIMEOUT,
+# SALUSD_TRACING__DIRECTIVES, ...) override this file, and flags override both.
+# `salusd validate-config` checks a change before a restart.
+
+# Seconds the unlocked key is held before it is cleared from memory
+#key_timeout = 20
+# The most bytes one `salusc random` request may draw
+#max_random_bytes = 4096
+# The longest request, in bytes, read from a client
+#max_message_bytes = 1048576
+# The longest value, in bytes, stored by one request
+#max_value_bytes = 1048576
+# Start read-only, refusing changes until `salusc read-only off`
+#read_only = false
+# Keep the daemon's memory out of core files and away from debuggers
+#harden_process = true
+# Log to stdout/stderr as well as the trace file
+#enable_std_output = false
+# Turn logging up or down
+#verbose = 0
+#quiet = 0
+# Where the daemon listens (default: SALUS_SOCKET, then the platform default)
+#socket_path = "/run/user/1000/salusd.sock"
+# A second socket speaking newline-delimited JSON; off unless set
+#json_socket_path = "/run/user/1000/salusd-json.sock"
+# The key that encrypts connections on a socket in the shared temp directory
+#transport_key_path = "~/.config/salusd/transport.key"
+
+# The share count and threshold used when `salusc shares` does not choose them
+#[shares]
+#num_shares = 5
+#threshold = 3
+
+# How idle connections are checked on (seconds; 0 turns each off)
+#[keepalive]
+#interval = 30
+#timeout = 120
+
+# Recently read values kept decrypted in memory; a capacity of 0 turns it off
+#[read_cache]
+#capacity = 0
+#ttl = 30
+
+# Values stored in chunks with `salusc store-file`
+#[streaming]
+#max_bytes = 1073741824
+#max_uploads = 4
+#timeout = 300
+#dedup = true
+
+# zstd compression before sealing; a threshold of 0 turns it off
+#[compression]
+#threshold = 0
+#level = 3
+
+# Earlier versions of each value kept for `salusc history` and `salusc diff`;
+# 0 keeps none
+#[history]
+#keep = 0
+
+# Where the store is kept, when not in the local database file
+#[storage]
+#url = "s3://bucket/prefix"
+#commit_window_ms = 0
+
+# Clustered mode, off unless `listen` is set (needs the `cluster` feature)
+#[cluster]
+#node_id = 1
+#listen = "10.0.0.1:7100"
+#key_file = "/etc/salusd/cluster.key"
+#bootstrap = false
+#heartbeat_ms = 250
+#election_timeout_ms = 1000
+
+# What the trace file records
+#[tracing]
+#with_target = false
+#with_thread_ids = false
+#with_thread_names = false
+#with_line_number = false
+#with_level = false
+#directives = "salusd=info"
+
+# One more place to take requests on; repeat the table for each
+#[[listeners]]
+#path = "/run/salusd/ops.sock"
+#allow_gids = [1001]
+#read_only = true
+
+# Stricter rules for the keys under payments/
+#[namespace.payments]
+#key_timeout = 300
+#max_value_bytes = 4096
+#policies = ["no-overwrite", "no-delete"]
+#audit = "writes"
+# Hold each read until two other users approve it, for 15 minutes
+#approvals = 2
+#approvers = [1001, 1002, 1003]
+#approval_window = 900
+
+# A program that mints short-lived credentials with `salusc plugin mint`
+#[plugins.postgres]
+#command = "/usr/libexec/salus/salus-postgres"
+#args = ["--sslmode", "require"]
+#timeout_ms = 5000
+#max_ttl = 3600
+#roles = ["readonly", "readwrite"]
+#config_key = "plugins/postgres"
+# ... or, with the wasm-plugins feature, a sandboxed WebAssembly module
+#module = "/usr/lib/salus/salus-postgres.wasm"
+#capabilities = ["log", "random"]
+#fuel = 10000000
+#max_memory_bytes = 16777216
+
+# The longest an SSH certificate signed with `salusc ssh sign` is good for
+#[ssh]
+#max_ttl = 86400
+
+# The longest a certificate issued with `salusc pki issue` is good for
+#[pki]
+#max_ttl = 2592000
+
+# The database users a read of database/creds/readonly creates, with a plugin
+#[database_roles.readonly]
+#plugin = "postgres"
+#creation = ["CREATE ROLE \"{{name}}\" LOGIN PASSWORD '{{password}}' VALID UNTIL '{{expiration}}'", "GRANT SELECT ON ALL TABLES IN SCHEMA public TO \"{{name}}\""]
+#revocation = ["DROP ROLE IF EXISTS \"{{name}}\""]
+#renewal = ["ALTER ROLE \"{{name}}\" VALID UNTIL '{{expiration}}'"]
+#ttl = 3600
+
+# A command run after app/db, or any key under app/, changes, told the key and
+# its new version (never the value); a url is POSTed them as JSON, with the
+# webhooks feature
+#[hooks.reload-app]
+#keys = ["app/db", "app/"]
+#events = ["written", "deleted", "rotated"]
+#command = "/usr/local/bin/reload-app"
+#args = ["--graceful"]
+#url = "https://deploy.example.com/hooks/salus"
+#timeout_ms = 5000
+
+# What the values stored under ci/ must look like before they are stored
+#[validation.github-tokens]
+#keys = ["ci/"]
+#pattern = "^ghp_[A-Za-z0-9]{36}$"
+#min_bytes = 40
+#max_bytes = 40
+#min_entropy = 128
+
+# A JSON schema the document stored under app/db must meet
+#[validation.db-logins]
+#keys = ["app/db"]
+#schema = '{"type": "object", "required": ["user", "password"]}'
+"#;
+
+/// Write [`TEMPLATE`] to the config file the daemon would read, refusing to
+/// replace one that exists unless `force` is set.
+///
+/// # Errors
+///
+/// * Returns an error if the file exists and `force` is not set, is not to be
+///   written as TOML, or cannot be written.
+pub(crate) fn run(cli: &Cli, force: bool) -> Result<()> {
+    let path = config_file_path(cli)?;
+    write(&path, config_format(cli, &path), force)?;
+    eprintln!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn write(path: &Path, format: ConfigFormat, force: bool) -> Result<()> {
+    if format != ConfigFormat::Toml {
+        return Err(Error::ConfigTemplateFormat.into());
+    }
+    if path.exists() && !force {
+        return Err(Error::ConfigExists(path.display().to_string()).into());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("unable to create {}", parent.display()))?;
+    }
+    fs::write(path, TEMPLATE).with_context(|| format!("unable to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use anyhow::{Result, bail};
+    use config::{Config, File, FileFormat};
+
+    use super::{TEMPLATE, write};
+    use crate::{
+        config::{ConfigFormat, ConfigSalusd},
+        utils::test::temp_dir,
+    };
+
+    fn parse(toml: &str) -> Result<ConfigSalusd> {
+        Ok(Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()?
+            .try_deserialize()?)
+    }
+
+    #[test]
+    fn the_template_changes_nothing_as_written() -> Result<()> {
+        assert_eq!(parse(TEMPLATE)?, ConfigSalusd::default());
+        Ok(())
+    }
+
+    #[test]
+    fn the_template_shows_the_defaults() -> Result<()> {
+        // Uncomment the settings but for the examples of what has no default.
+        let examples = [
+            "socket_path",
+            "json_socket_path",
+            "transport_key_path",
+            "url",
+            "node_id",
+            "listen",
+            "key_file",
+            "directives",
+            "[[listeners]]",
+            "path",
+            "allow_gids",
+            "read_only = true",
+            "[namespace.payments]",
+            "key_timeout = 300",
+            "max_value_bytes = 4096",
+            "policies",
+            "audit",
+            "approvals",
+            "approvers",
+            "approval_window",
+            "[plugins.postgres]",
+            "command",
+            "args",
+            "roles",
+            "config_key",
+            "module",
+            "capabilities",
+            "[database_roles.readonly]",
+            "plugin",
+            "creation",
+            "revocation",
+            "renewal",
+            "[hooks.reload-app]",
+            "keys",
+            "events",
+            "[validation.github-tokens]",
+            "[validation.db-logins]",
+            "pattern",
+            "schema",
+            "min_bytes",
+            "max_bytes",
+            "min_entropy",
+        ];
+        let uncommented = TEMPLATE
+            .lines()
+            .filter_map(|line| line.strip_prefix('#'))
+            .filter(|line| !line.is_empty() && !line.starts_with(' '))
+            .filter(|line| !examples.iter().any(|example| line.starts_with(example)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let config = parse(&uncommented)?;
+        if config != ConfigSalusd::default() {
+            bail!("the template's values are not the defaults:\n{uncommented}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn an_existing_file_is_only_replaced_with_force() -> Result<()> {
+        let dir = temp_dir("init-config")?;
+        let path = dir.join("nested").join("salusd.toml");
+        write(&path, ConfigFormat::Toml, false)?;
+        assert_eq!(fs::read_to_string(&path)?, TEMPLATE);
+        fs::write(&path, "key_timeout = 5\n")?;
+        assert!(write(&path, ConfigFormat::Toml, false).is_err());
+        assert_eq!(fs::read_to_string(&path)?, "key_timeout = 5\n");
+        write(&path, ConfigFormat::Toml, true)?;
+        assert_eq!(fs::read_to_string(&path)?, TEMPLATE);
+        assert!(write(&dir.join("salusd.yaml"), ConfigFormat::Yaml, false).is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,588 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The places the daemon takes requests on, who may connect to each, and
+//! keeping each of them up.
+//!
+//! The daemon always listens on its socket, and on the JSON one when that is
+//! set; every `[[listeners]]` entry adds one more. Each is served on its own:
+//! when accepting on one keeps failing, it is bound again, with backoff,
+//! while the others carry on.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use bon::Builder;
+use interprocess::local_socket::{
+    PeerCreds,
+    tokio::{Listener as LocalSocketListener, Stream as LocalSocketStream},
+    traits::{
+        StreamCommon as _,
+        tokio::{Listener as _, Stream as _},
+    },
+};
+use libsalus::{Reader, TransportKey, Writer};
+use tokio::time::{Instant, sleep};
+use tracing::{error, info};
+
+use crate::{
+    config::{ListenerKind, ListenerSettings, reload::Reloader},
+    error::Error,
+    handler::Wire,
+    runtime::{listen, serve},
+    store::ShareStore,
+};
+
+/// How long a listener that gave up waits before it is bound again, at first
+const FIRST_BACKOFF: Duration = Duration::from_millis(250);
+/// The longest a listener waits between attempts to bind it again
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How many accepts in a row may fail before a listener is bound again
+pub(crate) const MAX_ACCEPT_FAILURES: u32 = 16;
+
+/// Who connected to a listener
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Peer {
+    /// A process on this host, as the platform reports it, when it does
+    Local(Option<PeerCreds>),
+    /// A client over TCP, whose certificate was verified; its address is
+    /// only logged
+    #[cfg(feature = "tls")]
+    #[allow(dead_code)]
+    Remote(std::net::SocketAddr),
+}
+
+impl Peer {
+    /// The user a local peer runs as, when the platform reports it.
+    #[cfg(unix)]
+    pub(crate) fn uid(&self) -> Option<u32> {
+        match self {
+            Self::Local(Some(creds)) => creds.euid(),
+            Self::Local(None) => None,
+            #[cfg(feature = "tls")]
+            Self::Remote(_) => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    #[allow(clippy::unused_self)]
+    pub(crate) fn uid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Who a listener lets in, and what they may do once they are
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Access {
+    /// The users that may connect, by uid
+    allow_uids: Vec<u32>,
+    /// The groups whose members may connect, by gid
+    allow_gids: Vec<u32>,
+    /// Whether changes to the store are refused
+    read_only: bool,
+}
+
+impl Access {
+    /// Whether changes to the store made through the listener are refused.
+    pub(crate) fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether `peer` may connect: anyone may when no users or groups are
+    /// named, and otherwise only a local peer the platform reports as one
+    /// of them.
+    pub(crate) fn admits(&self, peer: &Peer) -> bool {
+        if self.allow_uids.is_empty() && self.allow_gids.is_empty() {
+            return true;
+        }
+        match peer {
+            Peer::Local(Some(creds)) => self.admits_creds(creds),
+            Peer::Local(None) => false,
+            #[cfg(feature = "tls")]
+            Peer::Remote(_) => false,
+        }
+    }
+
+    #[cfg(unix)]
+    fn admits_creds(&self, creds: &PeerCreds) -> bool {
+        creds
+            .euid()
+            .is_some_and(|uid| self.allow_uids.contains(&uid))
+            || creds
+                .egid()
+                .is_some_and(|gid| self.allow_gids.contains(&gid))
+    }
+
+    #[cfg(not(unix))]
+    fn admits_creds(&self, _creds: &PeerCreds) -> bool {
+        false
+    }
+}
+
+impl From<&ListenerSettings> for Access {
+    fn from(settings: &ListenerSettings) -> Self {
+        Self {
+            allow_uids: settings.allow_uids().clone(),
+            allow_gids: settings.allow_gids().clone(),
+            read_only: settings.read_only(),
+        }
+    }
+}
+
+/// How the connections a listener accepts are served
+#[derive(Builder, Clone, Default)]
+pub(crate) struct Serving {
+    /// The protocol every connection speaks
+    #[builder(default)]
+    wire: Wire,
+    /// The key every connection must open with the handshake of, when there
+    /// is one
+    transport: Option<Arc<TransportKey>>,
+    /// Who may connect, and what they may do
+    #[builder(default)]
+    access: Access,
+}
+
+impl Serving {
+    pub(crate) fn wire(&self) -> Wire {
+        self.wire
+    }
+
+    pub(crate) fn transport(&self) -> Option<&Arc<TransportKey>> {
+        self.transport.as_ref()
+    }
+
+    pub(crate) fn access(&self) -> &Access {
+        &self.access
+    }
+}
+
+/// A place the daemon takes requests on, and how it serves them
+pub(crate) struct Endpoint {
+    bind: Bind,
+    serving: Serving,
+}
+
+/// What an [`Endpoint`] binds
+enum Bind {
+    /// The local socket `path` names, as `socket_name` resolves it, given to
+    /// `gid` when there is one
+    Socket {
+        path: Option<String>,
+        gid: Option<u32>,
+    },
+    /// A TCP address, each connection to which must complete a mutual TLS
+    /// handshake
+    #[cfg(feature = "tls")]
+    Tcp {
+        address: String,
+        acceptor: tokio_rustls::TlsAcceptor,
+    },
+}
+
+impl Endpoint {
+    /// The local socket `path` names, served as `serving` says.
+    pub(crate) fn socket(path: Option<String>, serving: Serving) -> Self {
+        Self {
+            bind: Bind::Socket { path, gid: None },
+            serving,
+        }
+    }
+
+    /// The endpoint a `[[listeners]]` entry describes.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if a setting its kind needs is missing, one is set
+    ///   that it cannot use, or, for a `tcp` listener, its certificates cannot
+    ///   be loaded.
+    pub(crate) fn from_settings(settings: &ListenerSettings) -> Result<Self> {
+        let serving = Serving::builder()
+            .wire(settings.wire())
+            .access(Access::from(settings))
+            .build();
+        let bind = match settings.kind() {
+            ListenerKind::Socket => {
+                let path = settings
+                    .path()
+                    .clone()
+                    .ok_or(Error::ListenerMissing("socket", "path"))?;
+                if settings.gid().is_some() && !(cfg!(unix) && Path::new(&path).is_absolute()) {
+                    return Err(Error::SocketGroup.into());
+                }
+                Bind::Socket {
+                    path: Some(path),
+                    gid: settings.gid(),
+                }
+            }
+            ListenerKind::Tcp => {
+                for (setting, set) in [
+                    ("gid", settings.gid().is_some()),
+                    ("allow_uids", !settings.allow_uids().is_empty()),
+                    ("allow_gids", !settings.allow_gids().is_empty()),
+                ] {
+                    if set {
+                        return Err(Error::ListenerSocketOnly(setting).into());
+                    }
+                }
+                tcp(settings)?
+            }
+        };
+        Ok(Self { bind, serving })
+    }
+
+    /// How the connections this endpoint accepts are served.
+    pub(crate) fn serving(&self) -> &Serving {
+        &self.serving
+    }
+
+    /// Start listening.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the socket or address cannot be listened on, or
+    ///   a socket cannot be given its group.
+    #[cfg_attr(
+        not(feature = "tls"),
+        allow(clippy::unused_async, reason = "only a TCP endpoint awaits")
+    )]
+    pub(crate) async fn bind(&self) -> Result<Bound> {
+        match &self.bind {
+            Bind::Socket { path, gid } => {
+                let listener = listen(path.as_deref())?;
+                if let (Some(path), Some(gid)) = (path, gid) {
+                    give_group(path, *gid)?;
+                }
+                Ok(Bound::Socket(listener))
+            }
+            #[cfg(feature = "tls")]
+            Bind::Tcp { address, acceptor } => Ok(Bound::Tcp(
+                tokio::net::TcpListener::bind(address).await?,
+                acceptor.clone(),
+            )),
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.bind {
+            Bind::Socket {
+                path: Some(path), ..
+            } => write!(f, "socket {path}"),
+            Bind::Socket { path: None, .. } => write!(f, "the default socket"),
+            #[cfg(feature = "tls")]
+            Bind::Tcp { address, .. } => write!(f, "tcp {address}"),
+        }
+    }
+}
+
+/// A listening [`Endpoint`]
+pub(crate) enum Bound {
+    Socket(LocalSocketListener),
+    #[cfg(feature = "tls")]
+    Tcp(tokio::net::TcpListener, tokio_rustls::TlsAcceptor),
+}
+
+impl Bound {
+    /// Wait for the next connection.
+    pub(crate) async fn accept(&self) -> io::Result<Conn> {
+        match self {
+            Self::Socket(listener) => listener.accept().await.map(Conn::Socket),
+            #[cfg(feature = "tls")]
+            Self::Tcp(listener, acceptor) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok(Conn::Tcp(stream, addr, acceptor.clone()))
+            }
+        }
+    }
+}
+
+/// A connection a [`Bound`] endpoint accepted, not yet opened
+pub(crate) enum Conn {
+    Socket(LocalSocketStream),
+    #[cfg(feature = "tls")]
+    Tcp(
+        tokio::net::TcpStream,
+        std::net::SocketAddr,
+        tokio_rustls::TlsAcceptor,
+    ),
+}
+
+impl Conn {
+    /// Who connected.
+    pub(crate) fn peer(&self) -> Peer {
+        match self {
+            // Not every platform reports a peer, and none is needed to serve
+            // it.
+            Self::Socket(stream) => Peer::Local(stream.peer_creds().ok()),
+            #[cfg(feature = "tls")]
+            Self::Tcp(_, addr, _) => Peer::Remote(*addr),
+        }
+    }
+
+    /// The connection's halves, once a TCP client has completed its TLS
+    /// handshake.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the TLS handshake fails.
+    #[cfg_attr(
+        not(feature = "tls"),
+        allow(clippy::unused_async, reason = "only a TCP endpoint awaits")
+    )]
+    pub(crate) async fn open(self) -> Result<(Reader, Writer)> {
+        match self {
+            Self::Socket(stream) => {
+                let (receiver, sender) = stream.split();
+                Ok((Box::new(receiver), Box::new(sender)))
+            }
+            #[cfg(feature = "tls")]
+            Self::Tcp(stream, _, acceptor) => {
+                let (receiver, sender) = tokio::io::split(acceptor.accept(stream).await?);
+                Ok((Box::new(receiver), Box::new(sender)))
+            }
+        }
+    }
+}
+
+/// Serve `endpoint`, already listening on `bound`, for as long as the daemon
+/// runs, binding it again whenever serving it gives up.
+///
+/// The wait before binding again doubles with each failure in a row, up to
+/// [`MAX_BACKOFF`], and starts over once the listener has stayed up longer
+/// than that.
+pub(crate) async fn supervise(
+    endpoint: Endpoint,
+    mut bound: Bound,
+    share_store: Arc<RwLock<ShareStore>>,
+    reloader: Arc<Reloader>,
+) {
+    let mut backoff = FIRST_BACKOFF;
+    loop {
+        let started = Instant::now();
+        if let Err(e) = serve(
+            bound,
+            endpoint.serving().clone(),
+            share_store.clone(),
+            reloader.limits(),
+            Some(reloader.clone()),
+        )
+        .await
+        {
+            error!(listener = %endpoint, "Restarting a listener that keeps failing: {e}");
+        }
+        if started.elapsed() > MAX_BACKOFF {
+            backoff = FIRST_BACKOFF;
+        }
+        bound = loop {
+            sleep(backoff).await;
+            backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+            match endpoint.bind().await {
+                Ok(bound) => {
+                    info!(listener = %endpoint, "Listening again");
+                    break bound;
+                }
+                Err(e) => error!(listener = %endpoint, "Unable to listen again: {e}"),
+            }
+        };
+    }
+}
+
+/// Give the socket file at `path` to the group `gid`, and let its members
+/// connect to it.
+#[cfg(unix)]
+fn give_group(path: &str, gid: u32) -> Result<()> {
+    use std::{
+        fs::{Permissions, set_permissions},
+        os::unix::fs::{PermissionsExt as _, chown},
+    };
+
+    chown(path, None, Some(gid))?;
+    set_permissions(path, Permissions::from_mode(0o660))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn give_group(_path: &str, _gid: u32) -> Result<()> {
+    Err(Error::SocketGroup.into())
+}
+
+/// A `tcp` listener's address, and the TLS acceptor that asks each client for
+/// a certificate chaining to `client_ca`.
+#[cfg(feature = "tls")]
+fn tcp(settings: &ListenerSettings) -> Result<Bind> {
+    use anyhow::Context as _;
+    use tokio_rustls::{
+        TlsAcceptor,
+        rustls::{
+            RootCertStore, ServerConfig,
+            crypto::ring,
+            pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject as _},
+            server::WebPkiClientVerifier,
+        },
+    };
+
+    let address = settings
+        .address()
+        .clone()
+        .ok_or(Error::ListenerMissing("tcp", "address"))?;
+    let required = |path: &Option<std::path::PathBuf>, setting| {
+        path.clone().ok_or(Error::ListenerMissing("tcp", setting))
+    };
+    let (cert, key, client_ca) = (
+        required(settings.cert(), "cert")?,
+        required(settings.key(), "key")?,
+        required(settings.client_ca(), "client_ca")?,
+    );
+    let certificates = |path: &Path| -> Result<Vec<CertificateDer<'static>>> {
+        let certificates = CertificateDer::pem_file_iter(path)
+            .and_then(Iterator::collect::<std::result::Result<Vec<_>, _>>)
+            .with_context(|| format!("unable to read {}", path.display()))?;
+        if certificates.is_empty() {
+            return Err(Error::NoCertificate(path.display().to_string()).into());
+        }
+        Ok(certificates)
+    };
+
+    let chain = certificates(&cert)?;
+    let key = PrivateKeyDer::from_pem_file(&key)
+        .with_context(|| format!("unable to read {}", key.display()))?;
+    let mut roots = RootCertStore::empty();
+    for ca in certificates(&client_ca)? {
+        roots.add(ca)?;
+    }
+    let provider = Arc::new(ring::default_provider());
+    let verifier =
+        WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?;
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(chain, key)?;
+    Ok(Bind::Tcp {
+        address,
+        acceptor: TlsAcceptor::from(Arc::new(config)),
+    })
+}
+
+#[cfg(not(feature = "tls"))]
+fn tcp(_settings: &ListenerSettings) -> Result<Bind> {
+    Err(Error::TlsUnsupported.into())
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(unix)]
+    use std::{fs, os::unix::fs::MetadataExt as _};
+
+    use anyhow::{Result, bail};
+    use config::{Config, File, FileFormat};
+    #[cfg(unix)]
+    use interprocess::local_socket::{
+        GenericFilePath, ToFsName as _,
+        tokio::{Stream, prelude::*},
+    };
+
+    use super::{Access, Endpoint, Peer, Serving};
+    use crate::config::ConfigSalusd;
+    #[cfg(unix)]
+    use crate::utils::test::temp_dir;
+
+    fn listeners(toml: &str) -> Result<ConfigSalusd> {
+        Ok(Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()?
+            .try_deserialize()?)
+    }
+
+    #[test]
+    fn an_entry_needs_what_its_kind_listens_on() -> Result<()> {
+        let config = listeners(
+            r#"
+            [[listeners]]
+            path = "/run/salusd/ops.sock"
+            wire = "json"
+            allow_uids = [1000]
+            read_only = true
+
+            [[listeners]]
+            kind = "socket"
+
+            [[listeners]]
+            kind = "tcp"
+            address = "127.0.0.1:7443"
+            allow_gids = [100]
+            "#,
+        )?;
+        let [ops, pathless, tcp] = config.listeners().as_slice() else {
+            bail!("expected three listeners");
+        };
+        let ops = Endpoint::from_settings(ops)?;
+        assert_eq!(ops.to_string(), "socket /run/salusd/ops.sock");
+        assert!(ops.serving().access().read_only());
+        assert!(Endpoint::from_settings(pathless).is_err());
+        assert!(Endpoint::from_settings(tcp).is_err());
+        assert!(listeners("[[listeners]]\nkind = \"http\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn anyone_is_let_in_unless_users_or_groups_are_named() {
+        let open = Access::default();
+        assert!(open.admits(&Peer::Local(None)));
+        let restricted = Access {
+            allow_uids: vec![1000],
+            ..Access::default()
+        };
+        assert!(!restricted.admits(&Peer::Local(None)));
+        #[cfg(feature = "tls")]
+        assert!(!restricted.admits(&Peer::Remote(([127, 0, 0, 1], 7443).into())));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_peer_is_let_in_by_its_uid_or_gid() -> Result<()> {
+        let dir = temp_dir("peer")?;
+        let me = fs::metadata(&dir)?;
+        let path = dir.join("peer.sock");
+        let Some(name) = path.to_str() else {
+            bail!("the socket path is not UTF-8");
+        };
+
+        let endpoint = Endpoint::socket(Some(name.to_string()), Serving::default());
+        let listener = endpoint.bind().await?;
+        let _client = Stream::connect(path.as_path().to_fs_name::<GenericFilePath>()?).await?;
+        let peer = listener.accept().await?.peer();
+
+        let by_user = Access {
+            allow_uids: vec![me.uid()],
+            ..Access::default()
+        };
+        let by_group = Access {
+            allow_gids: vec![me.gid()],
+            ..Access::default()
+        };
+        let other = Access {
+            allow_uids: vec![me.uid().wrapping_add(1)],
+            ..Access::default()
+        };
+        assert_eq!(peer.uid(), Some(me.uid()));
+        assert!(by_user.admits(&peer));
+        assert!(by_group.admits(&peer));
+        assert!(!other.admits(&peer));
+        drop(listener);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -8,35 +8,75 @@
 
 use std::{
     ffi::OsString,
+    future::pending,
     io::ErrorKind,
-    sync::{Arc, Mutex},
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use interprocess::local_socket::{
-    ListenerOptions,
-    traits::tokio::{Listener, RecvHalf, Stream as _},
+use interprocess::local_socket::{ListenerOptions, tokio::Listener as LocalSocketListener};
+use libsalus::{
+    Action, FrameMeta, Init, MAX_MESSAGE_SIZE, UnknownMessage, decode_frame_with_meta, decode_json,
+    frame_len, respond, socket_name,
 };
-use libsalus::{Action, decode, socket_name};
+#[cfg(unix)]
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::{
-    io::AsyncReadExt,
-    spawn,
-    sync::mpsc::{UnboundedSender, unbounded_channel},
+    io::{AsyncBufReadExt as _, AsyncRead, AsyncReadExt, AsyncWrite, BufReader},
+    join, select, spawn,
+    sync::{
+        OwnedSemaphorePermit, Semaphore,
+        mpsc::{UnboundedSender, unbounded_channel},
+        watch,
+    },
+    task::{JoinSet, spawn_blocking},
+    time::{Instant, Interval, MissedTickBehavior, interval, interval_at},
 };
-use tracing::{error, info, trace};
+use tracing::{error, info, trace, warn};
 
 use crate::{
-    config::{ConfigSalusd, load},
-    db::initialize_redb,
+    config::{
+        ConfigSalusd, load,
+        reload::{Limits, Reloader},
+    },
+    db::{Backend, database_absolute_path, initialize_backend, migrations::migrate},
     error::Error,
-    handler::ActionHandler,
+    handler::{ActionHandler, Wire},
+    hook::{self, Changes},
     logging::initialize,
-    runtime::cli::Cli,
-    store::ShareStore,
+    plugin::{Plugins, database},
+    runtime::{
+        cli::{Cli, ClusterAction, Command, ConfigAction},
+        listeners::{Bound, Endpoint, MAX_ACCEPT_FAILURES, Peer, Serving, supervise},
+        transport::transport_key,
+    },
+    store::{
+        ShareStore,
+        blob::{Uploads, sweep_chunks},
+        cache::ReadCache,
+        compress::Compression,
+        rotation::{self, Reminders},
+        rules::Rules,
+    },
 };
 
+mod bench;
 mod cli;
+mod crash;
+mod harden;
+mod init_config;
+pub(crate) mod listeners;
+mod offline;
+mod restore;
+mod transport;
+mod validate;
+
+/// The most requests one connection may have read but not yet answered; the
+/// daemon stops reading from a client that reaches it until one is answered.
+const MAX_IN_FLIGHT: usize = 64;
 
 #[allow(clippy::too_many_lines)]
 pub(crate) async fn run<I, T>(args: Option<I>) -> Result<()>
@@ -51,25 +91,317 @@ where
         Cli::try_parse()?
     };
 
+    // Maintenance commands run in place of the daemon.
+    match cli.command() {
+        Some(Command::Offline { action }) => return offline::run(&cli, action),
+        Some(Command::Restore {
+            backup,
+            db,
+            identity,
+            verify_only,
+            force,
+        }) => {
+            let target = match db {
+                Some(path) => path.clone(),
+                None => database_absolute_path(&cli)?,
+            };
+            return restore::run(backup, &target, identity.as_deref(), *verify_only, *force);
+        }
+        Some(Command::Bench(args)) => return bench::run(args),
+        Some(Command::ValidateConfig) => return validate::run(&cli),
+        Some(Command::InitConfig { force }) => return init_config::run(&cli, *force),
+        Some(Command::Config {
+            action: ConfigAction::Show,
+        }) => return validate::show(&cli).map(drop),
+        Some(Command::Cluster { .. }) | None => {}
+    }
+
     // Load the configuration
     let config = load::<Cli, ConfigSalusd, Cli>(&cli, &cli).with_context(|| Error::ConfigLoad)?;
+    Init::builder()
+        .num_shares(config.shares().num_shares())
+        .threshold(config.shares().threshold())
+        .build()
+        .validate()
+        .with_context(|| Error::InvalidShareDefaults)?;
+    let compression = Compression::new(
+        config.compression().threshold(),
+        config.compression().level(),
+    )?;
 
     // Initialize tracing
-    initialize(&config, &config, &cli, None).with_context(|| Error::TracingInit)?;
+    let tracing = initialize(&config, &config, &cli, cli.log_filter().as_deref(), None)
+        .with_context(|| Error::TracingInit)?;
 
     trace!("configuration loaded");
     trace!("tracing initialized");
 
+    // Keep the key, once it is unlocked, out of core files and debuggers.
+    if config.harden_process() {
+        harden::harden().with_context(|| Error::Harden)?;
+        trace!("process hardened");
+    }
+
+    // Cluster membership commands talk to a running node.
+    if let Some(Command::Cluster { action }) = cli.command() {
+        return cluster_command(&config, action).await;
+    }
+
     // Initialize the database
-    let redb = initialize_redb(&cli).with_context(|| Error::DatabaseInit)?;
+    let url = config.storage().url().as_deref();
+    let clustered = config.cluster().listen().as_deref();
+    let backend = if let Some(listen) = clustered {
+        if url.is_some() {
+            return Err(Error::ClusterObjectStore.into());
+        }
+        // A cluster node migrates as it starts, and leaves sweeping chunks
+        // to the single daemon: a sweep's deletes would be written through
+        // the log from whichever node ran it.
+        cluster_backend(&config, listen, &database_absolute_path(&cli)?)
+            .await
+            .with_context(|| Error::DatabaseInit)?
+    } else {
+        let commit_window = Duration::from_millis(config.storage().commit_window_ms());
+        let backend =
+            initialize_backend(&cli, url, commit_window).with_context(|| Error::DatabaseInit)?;
+        // The pre-migration copy goes beside the database file, or where it
+        // would be for an object store.
+        let _copy = migrate(&backend, Some(&database_absolute_path(&cli)?))?;
+        let _swept = sweep_chunks(&backend)?;
+        backend
+    };
+    let database_path = database_absolute_path(&cli).ok().filter(|_| url.is_none());
     trace!("database initialized");
 
-    // Setup the socket
-    let name = socket_name(config.socket_path().as_deref())?;
+    // Listen on the socket, the JSON one when it is asked for, and every
+    // other listener configured.
+    let transport = transport_key(
+        config.socket_path().as_deref(),
+        config.transport_key_path().as_deref(),
+    )?
+    .map(Arc::new);
+    let mut endpoints = vec![Endpoint::socket(
+        config.socket_path().clone(),
+        Serving::builder().maybe_transport(transport).build(),
+    )];
+    if let Some(path) = config.json_socket_path() {
+        endpoints.push(Endpoint::socket(
+            Some(path.clone()),
+            Serving::builder().wire(Wire::Json).build(),
+        ));
+    }
+    for settings in config.listeners() {
+        endpoints.push(Endpoint::from_settings(settings)?);
+    }
+    let mut bound = Vec::with_capacity(endpoints.len());
+    for endpoint in &endpoints {
+        bound.push(
+            endpoint
+                .bind()
+                .await
+                .with_context(|| format!("unable to listen on {endpoint}"))?,
+        );
+    }
     trace!("socket setup");
 
+    // The syncronization between the server and client, if any is used, goes here.
+    info!("salusd daemon is running");
+
+    // Set up our share store and the message handler for it.
+    let (changes, pending) = Changes::channel();
+    let share_store = Arc::new(RwLock::new(
+        ShareStore::builder()
+            .backend(backend.clone())
+            .maybe_database_path(database_path)
+            .default_num_shares(config.shares().num_shares())
+            .default_threshold(config.shares().threshold())
+            // A node of a cluster does not see the writes the log applies to
+            // it, so it could not tell when a cached value went stale.
+            .read_cache(ReadCache::new(
+                if clustered.is_some() {
+                    0
+                } else {
+                    config.read_cache().capacity()
+                },
+                Duration::from_secs(config.read_cache().ttl()),
+            ))
+            .uploads(Uploads::new(
+                config.streaming().max_bytes(),
+                config.streaming().max_uploads(),
+                Duration::from_secs(config.streaming().timeout()),
+                config.streaming().dedup(),
+            ))
+            .compression(compression)
+            .keep_versions(config.history().keep())
+            .read_only(config.read_only())
+            .changes(changes.clone())
+            .rules(Arc::new(Rules::from(&config)))
+            .build(),
+    ));
+    // Zeroize the key and shares however the daemon goes down, short of
+    // being killed outright.
+    crash::wipe_on_panic(&share_store);
+    let stopped = crash::terminated()?;
+
+    // Take up a changed configuration on SIGHUP, or when a client asks.
+    let reloader = Arc::new(Reloader::new(
+        config.clone(),
+        Box::new(move || load::<Cli, ConfigSalusd, Cli>(&cli, &cli)),
+        Some(tracing),
+    ));
+    reload_on_hangup(&reloader)?;
+
+    // Check every plugin once, so one that cannot run is logged from the
+    // start rather than at its first mint.
+    let plugins = Plugins::from(&config);
+    let _handle = spawn(async move {
+        let _checked = plugins.health().await;
+    });
+    reap_leases(&share_store, &reloader);
+    remind_rotations(&share_store, changes);
+    let _handle = spawn(hook::deliver(pending, reloader.limits()));
+    apply_rules(&share_store, &reloader);
+
+    // Serve every listener on its own, restarting any that fails.
+    let mut listeners = JoinSet::new();
+    for (endpoint, bound) in endpoints.into_iter().zip(bound) {
+        info!(listener = %endpoint, wire = ?endpoint.serving().wire(), "Listening");
+        let _abort = listeners.spawn(supervise(
+            endpoint,
+            bound,
+            share_store.clone(),
+            reloader.clone(),
+        ));
+    }
+    let served = async {
+        while let Some(supervised) = listeners.join_next().await {
+            supervised?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    select! {
+        served = served => served?,
+        signal = stopped => {
+            crash::wipe(&share_store).await;
+            info!(signal, "salusd daemon stopped, its key material wiped");
+        }
+    }
+    Ok(())
+}
+
+/// Drop the database users whose leases are up, every
+/// [`REAP_INTERVAL`](database::REAP_INTERVAL), with the plugins the daemon
+/// runs with at the time.
+fn reap_leases(share_store: &Arc<RwLock<ShareStore>>, reloader: &Arc<Reloader>) {
+    let mut share_store = share_store.clone();
+    let limits = reloader.limits();
+    let _handle = spawn(async move {
+        let mut ticks = interval(database::REAP_INTERVAL);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let _tick = ticks.tick().await;
+            let plugins = limits.borrow().plugins().clone();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            if let Err(e) = database::reap(&mut share_store, &plugins, now).await {
+                error!("Unable to check the leases: {e}");
+            }
+        }
+    });
+}
+
+/// Report the values due to be rotated, every
+/// [`CHECK_INTERVAL`](rotation::CHECK_INTERVAL), each once until it is
+/// written again.
+fn remind_rotations(share_store: &Arc<RwLock<ShareStore>>, changes: Changes) {
+    let share_store = share_store.clone();
+    let _handle = spawn(async move {
+        let mut ticks = interval(rotation::CHECK_INTERVAL);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut reminders = Reminders::default();
+        loop {
+            let _tick = ticks.tick().await;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            let share_store = share_store.clone();
+            let changes = changes.clone();
+            let checked = spawn_blocking(move || {
+                let store = match share_store.read() {
+                    Ok(store) => store,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let reminded = reminders.remind(&store, &changes, now);
+                (reminders, reminded)
+            })
+            .await;
+            match checked {
+                Ok((checked, reminded)) => {
+                    reminders = checked;
+                    if let Err(e) = reminded {
+                        error!("Unable to check the rotation ages: {e}");
+                    }
+                }
+                Err(e) => {
+                    error!("Unable to check the rotation ages: {e}");
+                    reminders = Reminders::default();
+                }
+            }
+        }
+    });
+}
+
+/// Give the store the validation rules of each configuration `reloader`
+/// takes up.
+fn apply_rules(share_store: &Arc<RwLock<ShareStore>>, reloader: &Arc<Reloader>) {
+    let share_store = share_store.clone();
+    let mut limits = reloader.limits();
+    let _handle = spawn(async move {
+        while limits.changed().await.is_ok() {
+            let rules = limits.borrow_and_update().rules().clone();
+            let share_store = share_store.clone();
+            let applied = spawn_blocking(move || match share_store.write() {
+                Ok(mut store) => store.set_rules(rules),
+                Err(poisoned) => poisoned.into_inner().set_rules(rules),
+            });
+            if let Err(e) = applied.await {
+                error!("Unable to take up the validation rules: {e}");
+            }
+        }
+    });
+}
+
+/// Reload the configuration with `reloader` whenever the daemon is sent
+/// `SIGHUP`.
+#[cfg(unix)]
+fn reload_on_hangup(reloader: &Arc<Reloader>) -> Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    let reloader = reloader.clone();
+    let _handle = spawn(async move {
+        while hangups.recv().await.is_some() {
+            let reloader = reloader.clone();
+            match spawn_blocking(move || reloader.reload()).await {
+                Ok(Ok(_report)) => {}
+                Ok(Err(e)) => error!("Unable to reload the configuration: {e}"),
+                Err(e) => error!("Unable to reload the configuration: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn reload_on_hangup(_reloader: &Arc<Reloader>) -> Result<()> {
+    Ok(())
+}
+
+/// Create the listener for the socket `override_path` names, as
+/// [`socket_name`] resolves it.
+fn listen(override_path: Option<&str>) -> Result<LocalSocketListener> {
     // Configure our listener...
-    let opts = ListenerOptions::new().name(name);
+    let opts = ListenerOptions::new().name(socket_name(override_path)?);
 
     // ...and create it.
     let listener = match opts.create_tokio() {
@@ -92,79 +424,613 @@ where
         }
         x => x?,
     };
+    Ok(listener)
+}
 
-    // The syncronization between the server and client, if any is used, goes here.
-    info!("salusd daemon is running");
-
-    // Set up our share store and the message handler for it.
-    let share_store = Arc::new(Mutex::new(ShareStore::builder().redb(redb.clone()).build()));
-
-    // Set up our loop boilerplate that processes our incoming connections.
+/// Answer every connection `listener` accepts from `share_store`, until the
+/// task serving them is dropped or accepting fails [`MAX_ACCEPT_FAILURES`]
+/// times in a row, which is returned as an error for the listener to be bound
+/// again.
+///
+/// A connection from a peer `serving`'s access does not let in is closed
+/// unanswered, and one on a read-only listener has its changes refused. A
+/// request longer than `max_message_bytes` (capped at `MAX_MESSAGE_SIZE`,
+/// past which it could not be decoded anyway) is answered with
+/// `Response::MessageTooLarge` and its connection closed. `wire` is the
+/// protocol every connection on `listener` speaks, as `serving` says: the
+/// requests on a bincode connection are answered concurrently, each response
+/// written as it completes and carrying its request's id, while JSON ones are
+/// answered in order. A connection whose client stays silent past
+/// `keepalive`'s timeout, with none of its requests in flight, is closed, and
+/// an idle JSON connection is pinged at its interval. With a transport key,
+/// every connection must open with the handshake it keys, and is encrypted
+/// from then on. A connection has at most [`MAX_IN_FLIGHT`] requests read and
+/// not yet answered.
+///
+/// Each connection is served with the `limits` current when it is accepted,
+/// so a reload of the configuration applies to the connections opened after
+/// it. `reloader`, when there is one, answers `Action::ReloadConfig`.
+#[allow(clippy::too_many_lines)]
+pub(crate) async fn serve(
+    listener: Bound,
+    serving: Serving,
+    share_store: Arc<RwLock<ShareStore>>,
+    limits: watch::Receiver<Limits>,
+    reloader: Option<Arc<Reloader>>,
+) -> Result<()> {
+    let wire = serving.wire();
+    let mut failures = 0;
     loop {
         let conn = match listener.accept().await {
-            Ok(c) => c,
+            Ok(c) => {
+                failures = 0;
+                c
+            }
+            Err(e) if failures >= MAX_ACCEPT_FAILURES => return Err(e.into()),
             Err(e) => {
+                failures = failures.saturating_add(1);
                 error!("There was an error with an incoming connection: {e}");
                 continue;
             }
         };
+        let peer = conn.peer();
+        if !serving.access().admits(&peer) {
+            warn!(
+                ?peer,
+                "Refused a connection from a peer this listener does not let in"
+            );
+            continue;
+        }
+        let limits = limits.borrow().clone();
+        let max_message_bytes = limits
+            .max_message_bytes()
+            .min(u32::try_from(MAX_MESSAGE_SIZE).unwrap_or(u32::MAX));
+        let timeout = Some(limits.keepalive().timeout())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+        let ping_every = Some(limits.keepalive().interval())
+            .filter(|secs| *secs > 0 && wire == Wire::Json)
+            .map(Duration::from_secs);
 
-        let (mut receiver, sender) = conn.split();
-        let (tx, mut rx) = unbounded_channel::<Incoming>();
+        let transport = serving.transport().cloned();
+        let read_only_listener = serving.access().read_only();
         let share_store_c = share_store.clone();
-        let kt = config.key_timeout();
-        let _client_recv_handle = spawn(async move {
-            let mut action_handler = ActionHandler::builder()
-                .sender(sender)
-                .store(share_store_c)
-                .key_timeout(kt)
-                .build();
-            while let Some(incoming) = rx.recv().await {
-                let result = match incoming {
-                    Incoming::Action(message) => action_handler.action_handler(message).await,
-                    Incoming::Undecodable => action_handler.decode_error().await,
+        let reloader = reloader.clone();
+        let _handle = spawn(async move {
+            let opening = async {
+                let (reader, sender) = conn.open().await?;
+                respond(reader, sender, transport.as_deref()).await
+            };
+            let Some(opened) = within(timeout, opening).await else {
+                warn!(?peer, "Closing a connection whose client has gone quiet");
+                return;
+            };
+            let (mut reader, sender) = match opened {
+                Ok(halves) => halves,
+                Err(e) => {
+                    warn!(?peer, "Refused a connection that failed its handshake: {e}");
+                    return;
+                }
+            };
+            let (tx, mut rx) = unbounded_channel::<Request>();
+            let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+            let answering = async move {
+                let mut action_handler = ActionHandler::builder()
+                    .sender(sender)
+                    .store(share_store_c)
+                    .key_timeout(limits.key_timeout())
+                    .max_random_bytes(limits.max_random_bytes())
+                    .max_value_bytes(limits.max_value_bytes())
+                    .wire(wire)
+                    .maybe_reloader(reloader)
+                    .read_only_listener(read_only_listener)
+                    .maybe_uid(peer.uid())
+                    .namespaces(limits.namespaces().clone())
+                    .plugins(limits.plugins().clone())
+                    .database(limits.database().clone())
+                    .ssh(limits.ssh())
+                    .pki(limits.pki())
+                    .build();
+                // An interval too long to count to never comes round, so it
+                // pings nothing.
+                let mut idle = ping_every.and_then(|period| {
+                    let mut idle = interval_at(Instant::now().checked_add(period)?, period);
+                    idle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    Some(idle)
+                });
+                // Requests being answered alongside one another, each into a
+                // buffer of its own.
+                let mut answering = JoinSet::new();
+                let mut reading = true;
+                while reading || !answering.is_empty() {
+                    select! {
+                        incoming = rx.recv(), if reading => {
+                            let Some(Request { meta, received, incoming, permit }) = incoming else {
+                                reading = false;
+                                continue;
+                            };
+                            if let Some(idle) = idle.as_mut() {
+                                idle.reset();
+                            }
+                            match wire {
+                                Wire::Bincode => {
+                                    let received = meta.verbose().then_some(received);
+                                    let mut handler =
+                                        action_handler.for_request(meta.id(), received);
+                                    let _abort = answering.spawn(async move {
+                                        let answered =
+                                            answer(&mut handler, incoming, max_message_bytes).await;
+                                        drop(permit);
+                                        let written = handler.into_written();
+                                        answered.map(|()| written)
+                                    });
+                                }
+                                Wire::Json => {
+                                    let result = answer(
+                                        &mut action_handler,
+                                        incoming,
+                                        max_message_bytes,
+                                    )
+                                    .await;
+                                    drop(permit);
+                                    if let Err(e) = result {
+                                        error!("Error handling client message: {e}");
+                                    }
+                                }
+                            }
+                        }
+                        Some(answered) = answering.join_next() => {
+                            let result = match answered {
+                                Ok(Ok(response)) => action_handler.forward(&response).await,
+                                Ok(Err(e)) => Err(e),
+                                Err(e) => Err(e.into()),
+                            };
+                            if let Err(e) = result {
+                                error!("Error handling client message: {e}");
+                            }
+                        }
+                        () = idle_for(idle.as_mut()) => {
+                            if let Err(e) = action_handler.ping().await {
+                                warn!(?peer, "Closing a connection whose client is gone: {e}");
+                                break;
+                            }
+                        }
+                    }
+                }
+            };
+            let reading = async move {
+                let handled = match wire {
+                    Wire::Bincode => {
+                        handle_conn(
+                            &mut reader,
+                            tx,
+                            &in_flight,
+                            max_message_bytes,
+                            timeout,
+                            peer,
+                        )
+                        .await
+                    }
+                    Wire::Json => {
+                        handle_json_conn(reader, tx, &in_flight, max_message_bytes, timeout, peer)
+                            .await
+                    }
                 };
-                if let Err(e) = result {
-                    error!("Error handling client message: {e}");
+                if let Err(e) = handled {
+                    error!("Error while handling connection: {e}");
                 }
-            }
+            };
+            let ((), ()) = join!(answering, reading);
         });
+    }
+}
 
-        let _handle = spawn(async move {
-            if let Err(e) = handle_conn(&mut receiver, tx).await {
-                error!("Error while handling connection: {e}");
-            }
-        });
+/// Open this node's database as a node of the cluster `[cluster]` describes,
+/// taking cluster traffic on `listen`.
+#[cfg(feature = "cluster")]
+async fn cluster_backend(config: &ConfigSalusd, listen: &str, database: &Path) -> Result<Backend> {
+    crate::cluster::start(config.cluster(), listen, database).await
+}
+
+#[cfg(not(feature = "cluster"))]
+#[allow(clippy::unused_async)]
+async fn cluster_backend(
+    _config: &ConfigSalusd,
+    _listen: &str,
+    _database: &Path,
+) -> Result<Backend> {
+    Err(Error::ClusterUnsupported.into())
+}
+
+/// Run a `salusd cluster` command.
+#[cfg(feature = "cluster")]
+async fn cluster_command(config: &ConfigSalusd, action: &ClusterAction) -> Result<()> {
+    let settings = config.cluster();
+    match action {
+        ClusterAction::Join { addr } => crate::cluster::join(settings, addr).await,
+        ClusterAction::Leave { node, addr } => {
+            crate::cluster::leave(settings, addr.as_deref(), *node).await
+        }
+        ClusterAction::Status { addr } => crate::cluster::status(settings, addr.as_deref()).await,
     }
 }
 
+#[cfg(not(feature = "cluster"))]
+#[allow(clippy::unused_async)]
+async fn cluster_command(_config: &ConfigSalusd, _action: &ClusterAction) -> Result<()> {
+    Err(Error::ClusterUnsupported.into())
+}
+
 /// A decoded request, or a signal that the request could not be decoded.
 ///
 /// The receive task decodes the incoming `Action`, but the send half lives in
 /// the handler task, so a decode failure is forwarded over the channel as
-/// [`Incoming::Undecodable`] for the handler to answer with a `Response::Error`
-/// rather than silently dropping the connection.
+/// [`Incoming::Undecodable`], carrying why, for the handler to answer with a
+/// `Response::Error` rather than silently dropping the connection. A request in
+/// a valid frame whose action this daemon does not know is forwarded as
+/// [`Incoming::Unknown`], and one past the size limit as
+/// [`Incoming::TooLarge`] without being read any further.
 enum Incoming {
     Action(Action),
-    Undecodable,
+    Undecodable(String),
+    Unknown(u32),
+    TooLarge,
 }
 
-async fn handle_conn<T: RecvHalf + Unpin>(
+/// An [`Incoming`] request, with what its frame carried beside it and when it
+/// arrived.
+struct Request {
+    meta: FrameMeta,
+    received: std::time::Instant,
+    incoming: Incoming,
+    /// Its place among the connection's [`MAX_IN_FLIGHT`] requests, given
+    /// back once it is answered
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Request {
+    fn new(meta: FrameMeta, incoming: Incoming) -> Self {
+        Self {
+            meta,
+            received: std::time::Instant::now(),
+            incoming,
+            permit: None,
+        }
+    }
+
+    /// The request, holding `permit` until it is answered.
+    fn holding(mut self, permit: OwnedSemaphorePermit) -> Self {
+        self.permit = Some(permit);
+        self
+    }
+
+    /// A request that answers no frame in particular: one that could not be
+    /// read far enough to find its id, or a JSON line.
+    fn unframed(incoming: Incoming) -> Self {
+        Self::new(FrameMeta::default(), incoming)
+    }
+}
+
+/// Answer `incoming` with `handler`, refusing a request past
+/// `max_message_bytes`.
+async fn answer<W: AsyncWrite + Unpin>(
+    handler: &mut ActionHandler<W>,
+    incoming: Incoming,
+    max_message_bytes: u32,
+) -> Result<()> {
+    match incoming {
+        Incoming::Action(message) => handler.action_handler(message).await,
+        Incoming::Undecodable(reason) => handler.decode_error(&reason).await,
+        Incoming::Unknown(tag) => handler.unknown_action(tag).await,
+        Incoming::TooLarge => handler.too_large(max_message_bytes).await,
+    }
+}
+
+/// Read bincode frames from `receiver` until the client closes its side,
+/// forwarding each request to the handler as soon as its frame is complete
+/// and one of `in_flight`'s permits is free, so a client that sends faster
+/// than it is answered waits rather than queueing without bound.
+///
+/// A frame longer than `max_message_bytes`, or one that is corrupt, closes the
+/// connection once it is answered: the frames after it cannot be trusted to
+/// start where it seems to end. Returning drops the receive half, and the
+/// handler task drops the send half once it has answered everything, which
+/// closes the connection. A client silent past `timeout` is only let go once
+/// none of its requests are in flight, so one waiting on a long request can
+/// still cancel it.
+async fn handle_conn<T: AsyncRead + Unpin>(
     receiver: &mut T,
-    txc: UnboundedSender<Incoming>,
+    txc: UnboundedSender<Request>,
+    in_flight: &Arc<Semaphore>,
+    max_message_bytes: u32,
+    timeout: Option<Duration>,
+    peer: Peer,
 ) -> Result<()> {
-    // Describe the receive operation as receiving a line into our big buffer.
+    let limit = usize::try_from(max_message_bytes)?;
+    // Nothing is in flight before the first frame is read.
+    let capacity = in_flight.available_permits();
     let mut msg_buf = Vec::new();
-    let _msg_size = receiver.read_to_end(&mut msg_buf).await?;
+    loop {
+        // A frame's header says how long it is, so an oversized request is
+        // refused without buffering the rest of it.
+        match frame_len(&msg_buf) {
+            Ok(Some(len)) if len > limit => return refuse_too_large(&txc, max_message_bytes, peer),
+            Ok(Some(len)) if len <= msg_buf.len() => {
+                let rest = msg_buf.split_off(len);
+                let frame = std::mem::replace(&mut msg_buf, rest);
+                let permit = in_flight.clone().acquire_owned().await?;
+                if !forward_frame(&frame, &txc, permit, peer)? {
+                    return Ok(());
+                }
+                continue;
+            }
+            Ok(_) if msg_buf.len() > limit => {
+                return refuse_too_large(&txc, max_message_bytes, peer);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(?peer, error = %e, "Unable to decode a request");
+                txc.send(Request::unframed(Incoming::Undecodable(e.to_string())))?;
+                return Ok(());
+            }
+        }
+
+        let want = limit.saturating_add(1).saturating_sub(msg_buf.len());
+        let mut request = (&mut *receiver).take(u64::try_from(want)?);
+        let read = request.read_buf(&mut msg_buf);
+        let Some(read) = unless_idle(timeout, in_flight, capacity, read).await else {
+            warn!(?peer, "Closing a connection whose client has gone quiet");
+            return Ok(());
+        };
+        if read? == 0 {
+            if !msg_buf.is_empty() {
+                warn!(?peer, "Unable to decode a truncated request");
+                let reason = "corrupt frame: the request was cut short".to_string();
+                txc.send(Request::unframed(Incoming::Undecodable(reason)))?;
+            }
+            return Ok(());
+        }
+    }
+}
 
-    // A forged length prefix cannot trigger an unbounded allocation here:
-    // `decode` enforces `MAX_MESSAGE_SIZE`. A request we cannot decode (for
-    // example, an action from a client newer than this daemon) is forwarded as
-    // `Undecodable` so the handler can reply with a clear error.
-    match decode::<Action>(&msg_buf) {
-        Ok(message) => txc.send(Incoming::Action(message))?,
-        Err(_) => txc.send(Incoming::Undecodable)?,
+/// Decode `frame` and forward its request to the handler, returning whether
+/// the connection can go on.
+///
+/// A truncated or corrupted request fails the frame's checksum before it is
+/// decoded, and a forged length prefix cannot trigger an unbounded allocation
+/// here: `decode` enforces `MAX_MESSAGE_SIZE`. A request that fails either
+/// check is forwarded as `Undecodable`, so the handler can reply with a clear
+/// error, and ends the connection. One in a valid frame that we cannot decode
+/// (for example, an action from a client newer than this daemon) is forwarded
+/// as `Unknown`, with its id, and the connection goes on.
+fn forward_frame(
+    frame: &[u8],
+    txc: &UnboundedSender<Request>,
+    permit: OwnedSemaphorePermit,
+    peer: Peer,
+) -> Result<bool> {
+    match decode_frame_with_meta::<Action>(frame) {
+        Ok((meta, message)) => {
+            txc.send(Request::new(meta, Incoming::Action(message)).holding(permit))?;
+        }
+        Err(e) => {
+            if let Some(unknown) = e.downcast_ref::<UnknownMessage>() {
+                warn!(?peer, %unknown, "Refused an unknown request");
+                let meta = FrameMeta::builder().id(unknown.id()).build();
+                txc.send(Request::new(meta, Incoming::Unknown(unknown.tag())).holding(permit))?;
+            } else {
+                warn!(?peer, error = %e, "Unable to decode a request");
+                txc.send(Request::unframed(Incoming::Undecodable(e.to_string())))?;
+                return Ok(false);
+            }
+        }
     }
+    Ok(true)
+}
 
+/// Refuse a request over `max_message_bytes`.
+fn refuse_too_large(
+    txc: &UnboundedSender<Request>,
+    max_message_bytes: u32,
+    peer: Peer,
+) -> Result<()> {
+    warn!(
+        ?peer,
+        limit = max_message_bytes,
+        "Refused a request over the message size limit"
+    );
+    txc.send(Request::unframed(Incoming::TooLarge))?;
     Ok(())
 }
+
+/// Read newline-delimited JSON requests from `receiver` until the client closes
+/// its side, forwarding each to the handler in turn as `in_flight` lets it.
+///
+/// Unlike a bincode connection, one that cannot be decoded is answered and the
+/// connection kept, so a script can correct itself. A line longer than
+/// `max_message_bytes` still closes the connection, unread.
+async fn handle_json_conn<T: AsyncRead + Unpin>(
+    receiver: T,
+    txc: UnboundedSender<Request>,
+    in_flight: &Arc<Semaphore>,
+    max_message_bytes: u32,
+    timeout: Option<Duration>,
+    peer: Peer,
+) -> Result<()> {
+    let limit = u64::from(max_message_bytes);
+    let capacity = in_flight.available_permits();
+    let mut receiver = BufReader::new(receiver);
+    loop {
+        let mut line = Vec::new();
+        let mut request = (&mut receiver).take(limit.saturating_add(1));
+        let read = request.read_until(b'\n', &mut line);
+        let Some(read) = unless_idle(timeout, in_flight, capacity, read).await else {
+            warn!(?peer, "Closing a connection whose client has gone quiet");
+            return Ok(());
+        };
+        if read? == 0 {
+            return Ok(());
+        }
+        if line.len() > usize::try_from(max_message_bytes)? && !line.ends_with(b"\n") {
+            return refuse_too_large(&txc, max_message_bytes, peer);
+        }
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let permit = in_flight.clone().acquire_owned().await?;
+        let incoming = match decode_json::<Action>(&line) {
+            Ok(message) => Incoming::Action(message),
+            Err(e) => {
+                warn!(?peer, error = %e, "Unable to decode a JSON request");
+                Incoming::Undecodable(e.to_string())
+            }
+        };
+        txc.send(Request::unframed(incoming).holding(permit))?;
+    }
+}
+
+/// Wait for `read`, giving up after `timeout` when there is one.
+async fn within<T>(timeout: Option<Duration>, read: impl Future<Output = T>) -> Option<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read).await.ok(),
+        None => Some(read.await),
+    }
+}
+
+/// Wait for `read` from a client, giving up once it has been silent for
+/// `timeout` with none of its requests in flight: all `capacity` of
+/// `in_flight`'s permits are free. A client waiting on a long request says
+/// nothing until it is answered, unless it cancels it.
+async fn unless_idle<T>(
+    timeout: Option<Duration>,
+    in_flight: &Semaphore,
+    capacity: usize,
+    read: impl Future<Output = T>,
+) -> Option<T> {
+    let Some(timeout) = timeout else {
+        return Some(read.await);
+    };
+    tokio::pin!(read);
+    loop {
+        if let Ok(read) = tokio::time::timeout(timeout, &mut read).await {
+            return Some(read);
+        }
+        if in_flight.available_permits() >= capacity {
+            return None;
+        }
+    }
+}
+
+/// Wait out `idle`'s next tick, or forever when there are no pings.
+async fn idle_for(idle: Option<&mut Interval>) {
+    match idle {
+        Some(idle) => {
+            let _tick = idle.tick().await;
+        }
+        None => pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use anyhow::{Result, bail};
+    use libsalus::{Action, encode_frame_with_id};
+    use tokio::{
+        io::{AsyncWriteExt as _, duplex},
+        sync::{Semaphore, mpsc::unbounded_channel},
+        time::timeout,
+    };
+
+    use super::{Incoming, handle_conn, listeners::Peer};
+
+    #[tokio::test]
+    async fn a_request_in_flight_can_be_cancelled_after_the_idle_timeout() -> Result<()> {
+        let (mut client, mut server) = duplex(1024);
+        let (tx, mut rx) = unbounded_channel();
+        let in_flight = Arc::new(Semaphore::new(2));
+        let idle = Duration::from_millis(50);
+        let reading = handle_conn(
+            &mut server,
+            tx,
+            &in_flight,
+            1024,
+            Some(idle),
+            Peer::Local(None),
+        );
+        tokio::pin!(reading);
+
+        client
+            .write_all(&encode_frame_with_id(1, Action::Lock)?)
+            .await?;
+        assert!(
+            timeout(Duration::from_millis(150), &mut reading)
+                .await
+                .is_err()
+        );
+        let Ok(held) = rx.try_recv() else {
+            bail!("the request was not read");
+        };
+
+        // Still read well past the timeout, while the request is in flight.
+        client
+            .write_all(&encode_frame_with_id(2, Action::Cancel(1))?)
+            .await?;
+        assert!(timeout(idle, &mut reading).await.is_err());
+        let Ok(cancel) = rx.try_recv() else {
+            bail!("the cancel was not read past the idle timeout");
+        };
+        assert!(matches!(
+            cancel.incoming,
+            Incoming::Action(Action::Cancel(1))
+        ));
+
+        // Once nothing is in flight, a silent client is let go.
+        drop((held, cancel));
+        timeout(Duration::from_millis(200), &mut reading).await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_connection_stops_being_read_at_its_in_flight_cap() -> Result<()> {
+        let mut frames = Vec::new();
+        for id in 0..5 {
+            frames.extend(encode_frame_with_id(id, Action::Lock)?);
+        }
+        let mut reader = frames.as_slice();
+        let (tx, mut rx) = unbounded_channel();
+        let in_flight = Arc::new(Semaphore::new(2));
+        let reading = handle_conn(&mut reader, tx, &in_flight, 1024, None, Peer::Local(None));
+        tokio::pin!(reading);
+
+        // Two requests are read, and the third waits for one of them.
+        assert!(
+            timeout(Duration::from_millis(50), &mut reading)
+                .await
+                .is_err()
+        );
+        let Ok(first) = rx.try_recv() else {
+            bail!("the first request was not read");
+        };
+        let Ok(_second) = rx.try_recv() else {
+            bail!("the second request was not read");
+        };
+        assert!(rx.try_recv().is_err());
+
+        // Answering one lets one more in.
+        drop(first);
+        assert!(
+            timeout(Duration::from_millis(50), &mut reading)
+                .await
+                .is_err()
+        );
+        let Ok(third) = rx.try_recv() else {
+            bail!("the third request was not read once the first was answered");
+        };
+        assert!(matches!(third.incoming, Incoming::Action(Action::Lock)));
+        assert_eq!(third.meta.id(), 2);
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Offline reads: a disaster-recovery path that needs neither a running daemon
+//! nor the socket.
+//!
+//! The database file is opened directly and the key reconstructed from shares
+//! entered here, through the same [`ShareStore`] unlock the daemon uses. The
+//! key lives only in that store, which is dropped (and the key zeroized) as
+//! soon as the read is done; nothing is written back. A database in an older
+//! schema is migrated in memory, and left as it is on disk.
+
+use std::{
+    io::{BufRead as _, IsTerminal as _, Write as _, stdin, stdout},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Context as _, Result, bail};
+use libsalus::{Response, normalize_share};
+use scanpw::scanpw;
+use zeroize::Zeroizing;
+
+use crate::{
+    db::{
+        Backend, SharedBackend,
+        backend::{MemoryBackend, RedbBackend, StorageBackend as _, Table, WriteOp},
+        database_absolute_path,
+        migrations::migrate,
+    },
+    error::Error,
+    runtime::cli::{Cli, OfflineAction},
+    store::ShareStore,
+};
+
+/// Run an offline action.
+pub(crate) fn run(cli: &Cli, action: &OfflineAction) -> Result<()> {
+    match action {
+        OfflineAction::Read { db, prefix, key } => {
+            let path = match db {
+                Some(path) => path.clone(),
+                None => database_absolute_path(cli)?,
+            };
+            let mut store = open_store(&path)?;
+            let threshold = store.get_threshold();
+            let shares = if stdin().is_terminal() {
+                prompt_shares(threshold)
+            } else {
+                read_shares(threshold)?
+            };
+            let values = read(&mut store, shares, key, *prefix)?;
+            drop(store);
+            write_values(&values, *prefix)
+        }
+    }
+}
+
+/// A store over a copy of the rows in the existing database at `path`, still
+/// sealed.
+fn open_store(path: &Path) -> Result<ShareStore> {
+    let db = RedbBackend::open(path)?;
+    let rows = MemoryBackend::default();
+    for table in Table::ALL {
+        rows.commit(
+            db.scan(table, "")?
+                .into_iter()
+                .map(|(key, value)| WriteOp::Put { table, key, value })
+                .collect(),
+        )?;
+    }
+    let backend: Backend = Arc::new(SharedBackend::new(rows));
+    let _copy = migrate(&backend, None)?;
+    Ok(ShareStore::builder()
+        .backend(backend)
+        .database_path(path.to_path_buf())
+        .build())
+}
+
+/// Unlock `store` with `shares` and read the value under `key`, or every value
+/// under it as a prefix.
+fn read(
+    store: &mut ShareStore,
+    shares: Vec<Zeroizing<String>>,
+    key: &str,
+    prefix: bool,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    for share in shares {
+        store.add_share(share.as_str());
+    }
+    if !matches!(store.unlock()?, Response::Success) {
+        return Err(Error::UnlockFailed.into());
+    }
+    let values = if prefix {
+        match store.read_prefix(key)? {
+            Response::Values(values) => values,
+            other => bail!("unexpected response to an offline read: {other:?}"),
+        }
+    } else {
+        match store.read(key)? {
+            Response::Value(Some(value)) => vec![(key.to_string(), value)],
+            _ => return Err(Error::KeyNotFound(key.to_string()).into()),
+        }
+    };
+    store.lock();
+    Ok(values)
+}
+
+/// Prompt for `threshold` shares on the terminal, asking again for any that
+/// do not parse.
+fn prompt_shares(threshold: u8) -> Vec<Zeroizing<String>> {
+    eprintln!("Enter {threshold} shares, one per prompt");
+    let mut shares = Vec::with_capacity(usize::from(threshold));
+    for i in 1..=threshold {
+        loop {
+            let input = Zeroizing::new(scanpw!("Enter share {i}/{threshold}: "));
+            match normalize_share(&input) {
+                Ok(share) => {
+                    shares.push(Zeroizing::new(share));
+                    break;
+                }
+                Err(e) => eprintln!("Share {i}: {e}; please enter it again"),
+            }
+        }
+    }
+    shares
+}
+
+/// Read `threshold` shares from stdin, one per non-empty line.
+fn read_shares(threshold: u8) -> Result<Vec<Zeroizing<String>>> {
+    let mut shares = Vec::with_capacity(usize::from(threshold));
+    for line in stdin().lock().lines() {
+        if shares.len() == usize::from(threshold) {
+            break;
+        }
+        let line = Zeroizing::new(line?);
+        if !line.trim().is_empty() {
+            shares.push(Zeroizing::new(normalize_share(&line)?));
+        }
+    }
+    if shares.len() < usize::from(threshold) {
+        bail!("expected {threshold} shares on stdin, got {}", shares.len());
+    }
+    Ok(shares)
+}
+
+/// Write a single value to stdout as stored, or each value under a prefix as
+/// `KEY<TAB>VALUE`.
+fn write_values(values: &[(String, Vec<u8>)], prefix: bool) -> Result<()> {
+    let mut out = stdout().lock();
+    if !prefix {
+        for (_, value) in values {
+            out.write_all(value)
+                .with_context(|| "Unable to write the value to stdout")?;
+        }
+        return Ok(out.flush()?);
+    }
+    for (key, value) in values {
+        match str::from_utf8(value) {
+            Ok(value) => writeln!(out, "{key}\t{value}")?,
+            Err(_) => eprintln!(
+                "Value for '{key}' is {} bytes of non-UTF-8 binary data; read it on its own",
+                value.len()
+            ),
+        }
+    }
+    Ok(out.flush()?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, sync::Arc};
+
+    use anyhow::{Result, bail};
+    use libsalus::Response;
+    use zeroize::Zeroizing;
+
+    use super::{open_store, read};
+    use crate::{
+        db::{SharedBackend, backend::RedbBackend},
+        error::Error,
+        store::ShareStore,
+        utils::test::temp_dir,
+    };
+
+    #[test]
+    fn offline_reads_need_the_shares() -> Result<()> {
+        let dir = temp_dir("offline")?;
+        let path = dir.join("salusd.redb");
+        let result = (|| -> Result<()> {
+            let shares = {
+                let backend = RedbBackend::create(&path)?;
+                let mut store = ShareStore::builder()
+                    .backend(Arc::new(SharedBackend::new(backend)))
+                    .build();
+                let Response::Shares(shares) = store.gen_shares()? else {
+                    bail!("expected shares");
+                };
+                for share in shares.shares().iter().take(3) {
+                    store.add_share(share.clone());
+                }
+                let _unlocked = store.unlock()?;
+                let _stored = store.store("db/user", b"admin".to_vec(), false)?;
+                let _stored = store.store("db/pass", b"hunter2".to_vec(), false)?;
+                let _stored = store.store("web/token", b"abc".to_vec(), false)?;
+                shares
+                    .shares()
+                    .iter()
+                    .map(|share| Zeroizing::new(share.clone()))
+                    .collect::<Vec<_>>()
+            };
+            let quorum = shares.get(1..).unwrap_or_default().to_vec();
+
+            let mut store = open_store(&path)?;
+            assert_eq!(
+                read(&mut store, quorum.clone(), "db/", true)?,
+                vec![
+                    ("db/pass".to_string(), b"hunter2".to_vec()),
+                    ("db/user".to_string(), b"admin".to_vec()),
+                ]
+            );
+            assert_eq!(
+                read(&mut store, quorum.clone(), "web/token", false)?,
+                vec![("web/token".to_string(), b"abc".to_vec())]
+            );
+            let missing = read(&mut store, quorum, "nope", false);
+            assert!(matches!(
+                missing.as_ref().map_err(|e| e.downcast_ref::<Error>()),
+                Err(Some(Error::KeyNotFound(_)))
+            ));
+            let short = shares.get(..2).unwrap_or_default().to_vec();
+            let refused = read(&mut store, short, "web/token", false);
+            assert!(matches!(
+                refused.as_ref().map_err(|e| e.downcast_ref::<Error>()),
+                Err(Some(Error::UnlockFailed))
+            ));
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+}
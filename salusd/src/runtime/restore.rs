@@ -0,0 +1,432 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Restores from a backup written by `salusc backup`.
+//!
+//! Nothing is replaced until the backup has been checked end to end: the file
+//! against the manifest's checksum, the database in it (staged beside the
+//! target, and decrypted on the way when it is encrypted) against the
+//! database checksum and row counts, and its share set against the database
+//! it would replace. The staged copy then takes the database's place in one
+//! rename, once the old database has been linked (or copied) aside.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use age::{Decryptor, IdentityFile};
+use anyhow::{Context as _, Result, bail};
+use libsalus::{Response, StoreStatus};
+
+use crate::{
+    db::{
+        SharedBackend,
+        backend::{RedbBackend, StorageBackend as _, Table},
+        migrations::check_supported,
+    },
+    error::Error,
+    store::{
+        ShareStore,
+        backup::{
+            AGE_ENCRYPTION, BackupManifest, MANIFEST_FORMAT, create_private, manifest_path,
+            sha256_file,
+        },
+    },
+    utils::ensure_parent_dir,
+};
+
+/// How a backup's share set compares with the database it would replace.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ShareSet {
+    /// The shares that unlock the database unlock the backup.
+    Same,
+    /// The backup needs the shares of another set.
+    Different,
+    /// There is no initialized database to compare with.
+    Unchecked,
+}
+
+/// What a restore checked, and where the replaced database went.
+#[derive(Debug)]
+struct Restored {
+    rows: u64,
+    share_epoch: u64,
+    fingerprint: Option<String>,
+    share_set: ShareSet,
+    replaced: bool,
+    preserved: Option<PathBuf>,
+}
+
+/// Check `backup` and, unless `verify_only`, swap it in for `target`.
+pub(crate) fn run(
+    backup: &Path,
+    target: &Path,
+    identity: Option<&Path>,
+    verify_only: bool,
+    force: bool,
+) -> Result<()> {
+    let restored = restore(backup, target, identity, verify_only, force)?;
+    println!(
+        "{} matches its manifest: {} rows, share epoch {}, fingerprint {}",
+        backup.display(),
+        restored.rows,
+        restored.share_epoch,
+        restored.fingerprint.as_deref().unwrap_or("none"),
+    );
+    match restored.share_set {
+        ShareSet::Same => println!("It belongs to the share set of {}", target.display()),
+        ShareSet::Different => println!(
+            "It belongs to a different share set than {}; unlock it with that set's shares",
+            target.display()
+        ),
+        ShareSet::Unchecked => println!(
+            "There is no initialized database at {} to compare its share set with",
+            target.display()
+        ),
+    }
+    if !restored.replaced {
+        println!("Verified only; {} was not changed", target.display());
+    } else if let Some(preserved) = &restored.preserved {
+        println!(
+            "Restored {}; the previous database is kept at {}",
+            target.display(),
+            preserved.display()
+        );
+    } else {
+        println!("Restored {}", target.display());
+    }
+    Ok(())
+}
+
+fn restore(
+    backup: &Path,
+    target: &Path,
+    identity: Option<&Path>,
+    verify_only: bool,
+    force: bool,
+) -> Result<Restored> {
+    let manifest_path = manifest_path(backup);
+    let manifest: BackupManifest = fs::read(&manifest_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?))
+        .with_context(|| Error::BackupManifest(manifest_path.clone()))?;
+    if manifest.format() != MANIFEST_FORMAT {
+        bail!(
+            "{} is in manifest format {}; this salusd reads format {MANIFEST_FORMAT}",
+            manifest_path.display(),
+            manifest.format()
+        );
+    }
+    if sha256_file(backup)? != (manifest.sha256().clone(), manifest.bytes()) {
+        return Err(mismatch(backup, "the file's SHA-256 or size differs"));
+    }
+
+    let file_name = target
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| Error::DatabaseOpen(target.to_path_buf()))?;
+    ensure_parent_dir(target)?;
+    let staged = target.with_file_name(format!(".{file_name}.restore-{}", process::id()));
+    drop(fs::remove_file(&staged));
+    let checked = check(backup, &staged, target, &manifest, identity, force);
+    let swapped = checked.and_then(|mut restored| {
+        if !verify_only {
+            restored.preserved = swap(&staged, target, file_name)?;
+            restored.replaced = true;
+        }
+        Ok(restored)
+    });
+    drop(fs::remove_file(&staged));
+    swapped
+}
+
+/// Stage the database in `backup` at `staged`, and check it against the
+/// manifest and the database at `target`.
+fn check(
+    backup: &Path,
+    staged: &Path,
+    target: &Path,
+    manifest: &BackupManifest,
+    identity: Option<&Path>,
+    force: bool,
+) -> Result<Restored> {
+    stage(backup, staged, manifest, identity)?;
+    if sha256_file(staged)?.0 != *manifest.database_sha256() {
+        return Err(mismatch(backup, "the database's SHA-256 differs"));
+    }
+
+    let db = RedbBackend::open(staged)?;
+    // An older backup is migrated when salusd next opens it.
+    let _version = check_supported(&db)?;
+    let mut rows = 0u64;
+    for table in Table::ALL {
+        let found = u64::try_from(db.scan(table, "")?.len())?;
+        let expected = manifest.rows().get(table.name()).copied().unwrap_or(0);
+        if found != expected {
+            return Err(mismatch(
+                backup,
+                &format!("{} has {found} rows, not {expected}", table.name()),
+            ));
+        }
+        rows = rows.saturating_add(found);
+    }
+    let status = status(db)?;
+    if status.fingerprint() != manifest.fingerprint()
+        || status.share_epoch() != manifest.share_epoch()
+    {
+        return Err(mismatch(
+            backup,
+            "the store's fingerprint or share epoch differs",
+        ));
+    }
+
+    let share_set = if target.exists() {
+        match status_at(target)?.fingerprint() {
+            None => ShareSet::Unchecked,
+            Some(current) if Some(current) == status.fingerprint().as_ref() => ShareSet::Same,
+            Some(_) => ShareSet::Different,
+        }
+    } else {
+        ShareSet::Unchecked
+    };
+    if share_set == ShareSet::Different && !force {
+        return Err(Error::BackupShareSet.into());
+    }
+    Ok(Restored {
+        rows,
+        share_epoch: status.share_epoch(),
+        fingerprint: status.fingerprint().clone(),
+        share_set,
+        replaced: false,
+        preserved: None,
+    })
+}
+
+/// Copy the database in `backup` to `staged`, decrypting it with the age
+/// identities in `identity` when the manifest says it is encrypted.
+fn stage(
+    backup: &Path,
+    staged: &Path,
+    manifest: &BackupManifest,
+    identity: Option<&Path>,
+) -> Result<()> {
+    let mut source = BufReader::new(File::open(backup)?);
+    let mut out = create_private(staged)?;
+    match manifest.encryption().as_deref() {
+        None => {
+            let _copied = io::copy(&mut source, &mut out)?;
+        }
+        Some(AGE_ENCRYPTION) => {
+            let identity = identity.ok_or(Error::BackupIdentityRequired)?;
+            let identities = IdentityFile::from_file(identity.display().to_string())
+                .with_context(|| format!("unable to read {}", identity.display()))?
+                .into_identities()?;
+            let mut reader =
+                Decryptor::new_buffered(source)?.decrypt(identities.iter().map(Box::as_ref))?;
+            let _copied = io::copy(&mut reader, &mut out)?;
+        }
+        Some(other) => bail!("the backup is encrypted with '{other}', which salusd cannot open"),
+    }
+    Ok(out.sync_all()?)
+}
+
+/// Link (or copy) the database at `target` aside, then rename `staged` over
+/// it. Returns where the old database was kept, if there was one.
+fn swap(staged: &Path, target: &Path, file_name: &str) -> Result<Option<PathBuf>> {
+    let preserved = if target.exists() {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let preserved = target.with_file_name(format!("{file_name}.pre-restore-{seconds}"));
+        match fs::hard_link(target, &preserved) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                bail!("{} already exists", preserved.display());
+            }
+            Err(_) => {
+                let mut out = create_private(&preserved)?;
+                let _copied = io::copy(&mut File::open(target)?, &mut out)?;
+                out.sync_all()?;
+            }
+        }
+        Some(preserved)
+    } else {
+        None
+    };
+    fs::rename(staged, target).with_context(|| {
+        format!(
+            "unable to move the restored database to {}",
+            target.display()
+        )
+    })?;
+    Ok(preserved)
+}
+
+/// The status of the store in `db`.
+fn status(db: RedbBackend) -> Result<StoreStatus> {
+    let store = ShareStore::builder()
+        .backend(Arc::new(SharedBackend::new(db)))
+        .build();
+    match store.status()? {
+        Response::Status(status) => Ok(status),
+        other => bail!("unexpected response to a status check: {other:?}"),
+    }
+}
+
+/// The status of the store in the database at `path`, which must not be held
+/// by a running salusd.
+fn status_at(path: &Path) -> Result<StoreStatus> {
+    status(RedbBackend::open(path)?)
+}
+
+fn mismatch(backup: &Path, reason: &str) -> anyhow::Error {
+    Error::BackupMismatch(backup.to_path_buf(), reason.to_string()).into()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path, sync::Arc};
+
+    use age::{secrecy::ExposeSecret as _, x25519};
+    use anyhow::{Result, bail};
+    use libsalus::{Backup, Response};
+
+    use super::{ShareSet, restore};
+    use crate::{
+        db::{
+            SharedBackend,
+            backend::{RedbBackend, StorageBackend as _, Table},
+        },
+        error::Error,
+        store::ShareStore,
+        utils::test::temp_dir,
+    };
+
+    /// An unlocked store over a new database at `path`.
+    fn unlocked_store_at(path: &Path) -> Result<ShareStore> {
+        let mut store = ShareStore::builder()
+            .backend(Arc::new(SharedBackend::new(RedbBackend::create(path)?)))
+            .build();
+        let Response::Shares(shares) = store.gen_shares()? else {
+            bail!("expected shares");
+        };
+        for share in shares.shares().iter().take(3) {
+            store.add_share(share.clone());
+        }
+        let _unlocked = store.unlock()?;
+        Ok(store)
+    }
+
+    fn has_value(db: &Path, key: &str) -> Result<bool> {
+        Ok(RedbBackend::open(db)?.get(Table::Values, key)?.is_some())
+    }
+
+    fn is_error(result: &Result<super::Restored>, check: fn(&Error) -> bool) -> bool {
+        result
+            .as_ref()
+            .is_err_and(|e| e.downcast_ref::<Error>().is_some_and(check))
+    }
+
+    #[test]
+    fn a_verified_backup_replaces_the_database_and_keeps_the_old_one() -> Result<()> {
+        let dir = temp_dir("restore")?;
+        let result = (|| -> Result<()> {
+            let db = dir.join("salusd.redb");
+            let backup = dir.join("backup.redb");
+            {
+                let store = unlocked_store_at(&db)?;
+                let _stored = store.store("before", b"1".to_vec(), false)?;
+                let request = Backup::builder().path(backup.display().to_string()).build();
+                let _backed_up = store.backup(&request)?;
+                let _stored = store.store("after", b"2".to_vec(), false)?;
+            }
+
+            let verified = restore(&backup, &db, None, true, false)?;
+            assert_eq!(verified.share_set, ShareSet::Same);
+            assert!(!verified.replaced);
+            assert!(has_value(&db, "after")?);
+
+            let restored = restore(&backup, &db, None, false, false)?;
+            assert!(restored.replaced);
+            assert!(has_value(&db, "before")?);
+            assert!(!has_value(&db, "after")?);
+            let Some(preserved) = restored.preserved else {
+                bail!("expected the old database to be kept");
+            };
+            assert!(has_value(&preserved, "after")?);
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+
+    #[test]
+    fn a_damaged_or_foreign_backup_is_refused() -> Result<()> {
+        let dir = temp_dir("restore-refused")?;
+        let result = (|| -> Result<()> {
+            let db = dir.join("salusd.redb");
+            let backup = dir.join("backup.redb");
+            {
+                let mut store = unlocked_store_at(&db)?;
+                let request = Backup::builder().path(backup.display().to_string()).build();
+                let _backed_up = store.backup(&request)?;
+                // New shares retire the set the backup was taken under.
+                let _refreshed = store.refresh_shares()?;
+            }
+            assert!(is_error(&restore(&backup, &db, None, true, false), |e| {
+                matches!(e, Error::BackupShareSet)
+            }));
+            assert!(restore(&backup, &db, None, true, true).is_ok());
+
+            let mut bytes = fs::read(&backup)?;
+            if let Some(byte) = bytes.last_mut() {
+                *byte ^= 1;
+            }
+            fs::write(&backup, bytes)?;
+            assert!(is_error(&restore(&backup, &db, None, true, true), |e| {
+                matches!(e, Error::BackupMismatch(..))
+            }));
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+
+    #[test]
+    fn an_encrypted_backup_needs_its_identity() -> Result<()> {
+        let dir = temp_dir("restore-age")?;
+        let result = (|| -> Result<()> {
+            let db = dir.join("salusd.redb");
+            let backup = dir.join("backup.redb.age");
+            let identity = x25519::Identity::generate();
+            let identity_file = dir.join("identity.txt");
+            fs::write(&identity_file, identity.to_string().expose_secret())?;
+            {
+                let store = unlocked_store_at(&db)?;
+                let request = Backup::builder()
+                    .path(backup.display().to_string())
+                    .recipient(identity.to_public().to_string())
+                    .build();
+                let _backed_up = store.backup(&request)?;
+            }
+            assert!(is_error(&restore(&backup, &db, None, true, false), |e| {
+                matches!(e, Error::BackupIdentityRequired)
+            }));
+            let verified = restore(&backup, &db, Some(&identity_file), true, false)?;
+            assert_eq!(verified.share_set, ShareSet::Same);
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The transport key connections on a shared socket are encrypted under.
+
+use std::{io::Write as _, path::Path};
+
+use anyhow::Result;
+use aws_lc_rs::rand;
+use libsalus::{TRANSPORT_KEY_LEN, TransportKey, socket_is_shared, transport_key_path};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::{store::backup::create_private, utils::ensure_parent_dir};
+
+/// The key connections to the socket at `socket_path` are encrypted under:
+/// the one at `key_path`, as `transport_key_path` resolves it, when the
+/// socket is shared, or else none. A missing key is created.
+pub(super) fn transport_key(
+    socket_path: Option<&str>,
+    key_path: Option<&str>,
+) -> Result<Option<TransportKey>> {
+    if !socket_is_shared(socket_path) {
+        return Ok(None);
+    }
+    let path = transport_key_path(key_path)?;
+    let key = load_or_create(&path)?;
+    info!(key = %path.display(), "Encrypting connections on the shared socket");
+    Ok(Some(key))
+}
+
+/// Read the key at `path`, creating it, readable by the daemon's user only,
+/// when there is none.
+fn load_or_create(path: &Path) -> Result<TransportKey> {
+    if !path.exists() {
+        let mut key = Zeroizing::new([0; TRANSPORT_KEY_LEN]);
+        rand::fill(key.as_mut_slice())?;
+        ensure_parent_dir(path)?;
+        let mut file = create_private(path)?;
+        file.write_all(key.as_slice())?;
+        file.sync_all()?;
+    }
+    TransportKey::read(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use anyhow::Result;
+
+    use super::{load_or_create, transport_key};
+    use crate::utils::test::temp_dir;
+
+    #[test]
+    fn a_key_is_created_once_and_kept() -> Result<()> {
+        let dir = temp_dir("transport")?;
+        let path = dir.join("transport.key");
+        let _key = load_or_create(&path)?;
+        let created = fs::read(&path)?;
+        let _key = load_or_create(&path)?;
+        assert_eq!(fs::read(&path)?, created);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn an_explicit_socket_needs_no_key() -> Result<()> {
+        assert!(
+            transport_key(Some("/tmp/salusd-explicit.sock"), Some("/nonexistent/key"))?.is_none()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,671 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! `salusd validate-config` and `salusd config show`: load the configuration
+//! the way the daemon would and print every setting with where it came from,
+//! then, to validate it, check it, without starting the daemon.
+
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use config::{Map, Source as _, Value, ValueKind};
+use libsalus::{Init, MAX_UNLOCK_SECONDS, TransportKey, socket_is_shared, transport_key_path};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    config::{ClusterSettings, ConfigSalusd, ListenerKind, config_file_path, layered},
+    db::database_absolute_path,
+    error::Error,
+    logging::tracing_absolute_path,
+    runtime::{cli::Cli, listeners::Endpoint},
+    store::{
+        compress::Compression,
+        rules::{self, Schema},
+    },
+};
+
+/// Print the merged configuration, then every problem with it.
+///
+/// # Errors
+///
+/// * Returns an error if the configuration cannot be loaded, or
+///   [`Error::InvalidConfig`] when it has problems.
+pub(crate) fn run(cli: &Cli) -> Result<()> {
+    let config = show(cli)?;
+    let problems = problems(&config, cli);
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    if problems.is_empty() {
+        eprintln!("The configuration is valid");
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(problems.len()).into())
+    }
+}
+
+/// Print the merged configuration, every setting as `key = value  # source`,
+/// with secrets redacted, and return it.
+///
+/// # Errors
+///
+/// * Returns an error if the configuration cannot be loaded.
+pub(crate) fn show(cli: &Cli) -> Result<ConfigSalusd> {
+    let file = config_file_path(cli)?;
+    let layered = layered(cli, cli)?;
+    let sources = layered.collect()?;
+    let config: ConfigSalusd = layered
+        .try_deserialize()
+        .with_context(|| Error::ConfigDeserialize)?;
+
+    let found = if file.exists() { "" } else { " (not found)" };
+    println!("# config file: {}{found}", file.display());
+    for (key, value) in settings(&config)? {
+        let source = match origin(&sources, &key) {
+            None => "default",
+            Some(source @ ("the environment" | "command line")) => source,
+            Some(_file) => "config file",
+        };
+        let value = redacted(&key, value);
+        println!("{key} = {value}  # {source}");
+    }
+    Ok(config)
+}
+
+/// What a secret is printed as.
+const REDACTED: &str = "<redacted>";
+
+/// The words that mark a setting as a secret, wherever they are in its name.
+const SECRET_NAMES: [&str; 5] = ["password", "passphrase", "secret", "token", "credential"];
+
+/// `value` as it may be printed for the setting `key`: redacted whole when
+/// the setting is named for a secret, and with any credentials in a URL
+/// redacted otherwise.
+fn redacted(key: &str, value: String) -> String {
+    let name = key.rsplit('.').next().unwrap_or(key).to_ascii_lowercase();
+    if SECRET_NAMES.iter().any(|secret| name.contains(secret)) {
+        return REDACTED.to_string();
+    }
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return value;
+    };
+    let (authority, path) = rest
+        .find('/')
+        .map_or((rest, ""), |slash| rest.split_at(slash));
+    match authority.rsplit_once('@') {
+        Some((_credentials, host)) => format!("{scheme}://{REDACTED}@{host}{path}"),
+        None => value,
+    }
+}
+
+/// Every setting of `config`, as `(dotted.key, value)`, sorted by key.
+fn settings(config: &ConfigSalusd) -> Result<Vec<(String, String)>> {
+    let mut settings = Vec::new();
+    flatten("", &serde_json::to_value(config)?, &mut settings);
+    Ok(settings)
+}
+
+fn flatten(key: &str, value: &serde_json::Value, settings: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(table) => {
+            for (name, value) in table {
+                let key = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{key}.{name}")
+                };
+                flatten(&key, value, settings);
+            }
+        }
+        serde_json::Value::Null => settings.push((key.to_string(), "unset".to_string())),
+        value => settings.push((key.to_string(), value.to_string())),
+    }
+}
+
+/// Where the setting at the dotted `key` came from: the config file, the
+/// environment, or the command line; `None` when no source set it.
+fn origin<'a>(sources: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    let (name, rest) = key
+        .split_once('.')
+        .map_or((key, None), |(name, rest)| (name, Some(rest)));
+    let value = sources.get(name)?;
+    match (rest, &value.kind) {
+        (None, _) => value.origin(),
+        (Some(rest), ValueKind::Table(table)) => origin(table, rest),
+        (Some(_), _) => None,
+    }
+}
+
+/// Everything wrong with `config` that would stop the daemon starting, or
+/// leave it unable to do its job, each prefixed with the setting at fault.
+#[allow(clippy::too_many_lines)]
+fn problems(config: &ConfigSalusd, cli: &Cli) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut check = |setting: &str, result: Result<()>| {
+        if let Err(e) = result {
+            problems.push(format!("{setting}: {e:#}"));
+        }
+    };
+
+    // Values in range
+    check(
+        "key_timeout",
+        within(config.key_timeout(), 1..=MAX_UNLOCK_SECONDS),
+    );
+    check(
+        "max_random_bytes",
+        within(config.max_random_bytes().into(), 1..=u32::MAX.into()),
+    );
+    check(
+        "max_message_bytes",
+        within(config.max_message_bytes().into(), 1..=u32::MAX.into()),
+    );
+    check(
+        "max_value_bytes",
+        within(config.max_value_bytes(), 1..=u64::MAX),
+    );
+    check(
+        "shares",
+        Init::builder()
+            .num_shares(config.shares().num_shares())
+            .threshold(config.shares().threshold())
+            .build()
+            .validate(),
+    );
+    check(
+        "compression",
+        Compression::new(
+            config.compression().threshold(),
+            config.compression().level(),
+        )
+        .map(drop),
+    );
+    check(
+        "streaming.max_bytes",
+        within(config.streaming().max_bytes(), 1..=u64::MAX),
+    );
+    check(
+        "streaming.max_uploads",
+        u64::try_from(config.streaming().max_uploads())
+            .map_err(Into::into)
+            .and_then(|uploads| within(uploads, 1..=u64::MAX)),
+    );
+    for (name, namespace) in config.namespace() {
+        let setting = format!("namespace.{name}");
+        if name.is_empty() || name.starts_with('/') || name.ends_with('/') {
+            check(&setting, Err(Error::NamespaceName.into()));
+        }
+        if let Some(timeout) = namespace.key_timeout() {
+            check(
+                &format!("{setting}.key_timeout"),
+                within(timeout, 1..=MAX_UNLOCK_SECONDS),
+            );
+        }
+        if let Some(bytes) = namespace.max_value_bytes() {
+            check(
+                &format!("{setting}.max_value_bytes"),
+                within(bytes, 1..=u64::MAX),
+            );
+        }
+        if let Some(approvals) = namespace.approvals() {
+            check(
+                &format!("{setting}.approvals"),
+                within(u64::from(approvals), 1..=u64::from(u32::MAX)),
+            );
+        }
+        check(
+            &format!("{setting}.approval_window"),
+            within(namespace.approval_window(), 1..=u64::MAX),
+        );
+    }
+    for (name, plugin) in config.plugins() {
+        let setting = format!("plugins.{name}");
+        if let Some(module) = plugin.module() {
+            check(&format!("{setting}.module"), wasm_module(module));
+            check(
+                &format!("{setting}.fuel"),
+                within(plugin.fuel(), 1..=u64::MAX),
+            );
+        } else {
+            check(
+                &format!("{setting}.command"),
+                absolute_command(plugin.command().as_ref()),
+            );
+        }
+        check(
+            &format!("{setting}.timeout_ms"),
+            within(plugin.timeout_ms(), 1..=u64::MAX),
+        );
+        check(
+            &format!("{setting}.max_ttl"),
+            within(plugin.max_ttl(), 1..=u64::MAX),
+        );
+    }
+    check("ssh.max_ttl", within(config.ssh().max_ttl(), 1..=u64::MAX));
+    check("pki.max_ttl", within(config.pki().max_ttl(), 1..=u64::MAX));
+    for (name, role) in config.database_roles() {
+        let setting = format!("database_roles.{name}");
+        match config.plugins().get(role.plugin()) {
+            Some(plugin) => check(
+                &format!("{setting}.ttl"),
+                within(role.ttl(), 1..=plugin.max_ttl()),
+            ),
+            None => check(
+                &format!("{setting}.plugin"),
+                Err(Error::PluginNotFound(role.plugin().clone()).into()),
+            ),
+        }
+        for (statements, listed) in [
+            ("creation", role.creation()),
+            ("revocation", role.revocation()),
+        ] {
+            if listed.is_empty() {
+                check(
+                    &format!("{setting}.{statements}"),
+                    Err(anyhow!("no statements are listed")),
+                );
+            }
+        }
+    }
+    for (name, hook) in config.hooks() {
+        let setting = format!("hooks.{name}");
+        if hook.command().is_none() && hook.url().is_none() {
+            check(&setting, Err(anyhow!("no command or url is set")));
+        }
+        if hook.command().is_some() {
+            check(
+                &format!("{setting}.command"),
+                absolute_command(hook.command().as_ref()),
+            );
+        }
+        if let Some(url) = hook.url() {
+            check(&format!("{setting}.url"), webhook_url(url));
+        }
+        check(
+            &format!("{setting}.timeout_ms"),
+            within(hook.timeout_ms(), 1..=u64::MAX),
+        );
+    }
+    for (name, rule) in config.validation() {
+        let setting = format!("validation.{name}");
+        if rule.pattern().is_none()
+            && rule.schema().is_none()
+            && rule.min_bytes().is_none()
+            && rule.max_bytes().is_none()
+            && rule.min_entropy().is_none()
+        {
+            check(&setting, Err(anyhow!("nothing is checked")));
+        }
+        if let Some(pattern) = rule.pattern() {
+            check(
+                &format!("{setting}.pattern"),
+                rules::pattern(pattern).map(drop),
+            );
+        }
+        if let Some(schema) = rule.schema() {
+            check(
+                &format!("{setting}.schema"),
+                Schema::parse(schema).map(drop),
+            );
+        }
+        if let (Some(min), Some(max)) = (rule.min_bytes(), rule.max_bytes()) {
+            check(&format!("{setting}.max_bytes"), within(max, min..=u64::MAX));
+        }
+    }
+    if let Some(directives) = config.tracing().directives() {
+        check(
+            "tracing.directives",
+            EnvFilter::builder()
+                .parse(directives)
+                .map(drop)
+                .map_err(|e| anyhow!("{e}")),
+        );
+    }
+    if let Some(url) = config.storage().url() {
+        check("storage.url", storage_url(url));
+    }
+    if config.cluster().listen().is_some() {
+        if config.storage().url().is_some() {
+            check("cluster", Err(Error::ClusterObjectStore.into()));
+        }
+        check("cluster", cluster(config.cluster()));
+    }
+
+    // Paths exist, or can be created
+    if config.storage().url().is_none() {
+        check(
+            "database",
+            database_absolute_path(cli).and_then(|path| creatable(&path)),
+        );
+    }
+    check(
+        "tracing",
+        tracing_absolute_path(cli).and_then(|path| creatable(&path)),
+    );
+    for (setting, path) in [
+        ("socket_path", config.socket_path()),
+        ("json_socket_path", config.json_socket_path()),
+    ] {
+        // Anything but an absolute path names a socket in a namespace or the
+        // default directory.
+        if let Some(path) = path.as_deref().map(Path::new).filter(|p| p.is_absolute()) {
+            check(setting, creatable(path));
+        }
+    }
+    for (index, listener) in config.listeners().iter().enumerate() {
+        let setting = format!("listeners[{index}]");
+        check(&setting, Endpoint::from_settings(listener).map(drop));
+        if let Some(path) = listener.path().as_deref().map(Path::new)
+            && path.is_absolute()
+            && listener.kind() == ListenerKind::Socket
+        {
+            check(&setting, creatable(path));
+        }
+    }
+    if socket_is_shared(config.socket_path().as_deref()) {
+        check(
+            "transport_key_path",
+            transport_key_path(config.transport_key_path().as_deref()).and_then(|path| {
+                if path.exists() {
+                    TransportKey::read(&path).map(drop)
+                } else {
+                    creatable(&path)
+                }
+            }),
+        );
+    }
+    problems
+}
+
+/// Check that a plugin's or hook's `command` names a file by its absolute
+/// path.
+fn absolute_command(command: Option<&PathBuf>) -> Result<()> {
+    let Some(command) = command else {
+        bail!("no command is set");
+    };
+    if !command.is_absolute() {
+        bail!("{} is not an absolute path", command.display());
+    }
+    if !command.is_file() {
+        bail!("{} is not a file", command.display());
+    }
+    Ok(())
+}
+
+/// Check that a plugin's `module` names a file by its absolute path, and can
+/// be run by this build.
+fn wasm_module(module: &PathBuf) -> Result<()> {
+    absolute_command(Some(module))?;
+    if cfg!(not(feature = "wasm-plugins")) {
+        bail!("salusd was built without the wasm-plugins feature");
+    }
+    Ok(())
+}
+
+/// Check that a hook's `url` can be sent a POST by this build.
+fn webhook_url(url: &str) -> Result<()> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("{url} is not an http or https URL");
+    }
+    if cfg!(not(feature = "webhooks")) {
+        bail!("salusd was built without the webhooks feature");
+    }
+    Ok(())
+}
+
+fn within(value: u64, range: RangeInclusive<u64>) -> Result<()> {
+    if !range.contains(&value) {
+        bail!(
+            "{value} is outside the range {} to {}",
+            range.start(),
+            range.end()
+        );
+    }
+    Ok(())
+}
+
+/// Check that the file at `path` can be written, or created along with any
+/// directory it needs.
+fn creatable(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        bail!("{} is a directory", path.display());
+    }
+    let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+        bail!("nothing of {} exists", path.display());
+    };
+    if existing != path && !existing.is_dir() {
+        bail!(
+            "{} cannot be created: {} is not a directory",
+            path.display(),
+            existing.display()
+        );
+    }
+    if existing.metadata()?.permissions().readonly() {
+        bail!(
+            "{} cannot be written: {} is read-only",
+            path.display(),
+            existing.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "s3")]
+fn storage_url(url: &str) -> Result<()> {
+    url.strip_prefix("s3://")
+        .filter(|rest| !rest.is_empty())
+        .map(drop)
+        .ok_or_else(|| Error::InvalidStorageUrl(url.to_string()).into())
+}
+
+#[cfg(not(feature = "s3"))]
+fn storage_url(url: &str) -> Result<()> {
+    Err(Error::ObjectStoreUnsupported(url.to_string()).into())
+}
+
+#[cfg(feature = "cluster")]
+fn cluster(settings: &ClusterSettings) -> Result<()> {
+    let _id = crate::cluster::node_id(settings)?;
+    let _key = crate::cluster::cluster_key(settings)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "cluster"))]
+fn cluster(_settings: &ClusterSettings) -> Result<()> {
+    Err(Error::ClusterUnsupported.into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{ffi::OsStr, fs};
+
+    use anyhow::Result;
+    use clap::Parser as _;
+    use config::{Config, Map, Source as _};
+
+    use super::{creatable, origin, problems, redacted, settings};
+    use crate::{
+        config::{ConfigSalusd, env_source},
+        runtime::cli::Cli,
+        utils::test::temp_dir,
+    };
+
+    fn from_env(vars: &[(&str, &str)]) -> Result<Config> {
+        let mut env = Map::new();
+        for (name, value) in vars {
+            let _old = env.insert((*name).to_string(), (*value).to_string());
+        }
+        Ok(Config::builder()
+            .add_source(env_source("SALUSD").source(Some(env)))
+            .build()?)
+    }
+
+    #[test]
+    fn settings_are_flattened_with_their_sources() -> Result<()> {
+        let layered = from_env(&[
+            ("SALUSD_KEY_TIMEOUT", "60"),
+            ("SALUSD_TRACING__DIRECTIVES", "salusd=debug"),
+        ])?;
+        let sources = layered.collect()?;
+        let config: ConfigSalusd = layered.try_deserialize()?;
+        let settings = settings(&config)?;
+        let value = |key: &str| {
+            settings
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("key_timeout"), Some("60"));
+        assert_eq!(value("keepalive.interval"), Some("30"));
+        assert_eq!(value("socket_path"), Some("unset"));
+        assert_eq!(value("tracing.directives"), Some("\"salusd=debug\""));
+        assert_eq!(origin(&sources, "key_timeout"), Some("the environment"));
+        assert_eq!(
+            origin(&sources, "tracing.directives"),
+            Some("the environment")
+        );
+        assert_eq!(origin(&sources, "tracing.with_target"), None);
+        assert_eq!(origin(&sources, "max_random_bytes"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        assert_eq!(
+            redacted(
+                "storage.url",
+                "\"s3://id:hunter2@bucket/prefix\"".to_string()
+            ),
+            "\"s3://<redacted>@bucket/prefix\""
+        );
+        assert_eq!(
+            redacted("storage.url", "\"s3://bucket/a@b\"".to_string()),
+            "\"s3://bucket/a@b\""
+        );
+        assert_eq!(
+            redacted("vault.api_token", "\"abc\"".to_string()),
+            "<redacted>"
+        );
+        assert_eq!(redacted("key_timeout", "20".to_string()), "20");
+        assert_eq!(
+            redacted(
+                "transport_key_path",
+                "\"/etc/salusd/transport.key\"".to_string()
+            ),
+            "\"/etc/salusd/transport.key\""
+        );
+    }
+
+    #[test]
+    fn a_sound_configuration_has_no_problems() -> Result<()> {
+        let dir = temp_dir("validate-sound")?;
+        let key = dir.join("transport.key");
+        let key = key.to_string_lossy();
+        let config: ConfigSalusd =
+            from_env(&[("SALUSD_TRANSPORT_KEY_PATH", &key)])?.try_deserialize()?;
+        let database = dir.join("db").join("salusd.redb");
+        let tracing = dir.join("log").join("salusd.log");
+        let cli = Cli::try_parse_from([
+            OsStr::new("salusd"),
+            OsStr::new("-d"),
+            database.as_os_str(),
+            OsStr::new("-t"),
+            tracing.as_os_str(),
+        ])?;
+        assert_eq!(problems(&config, &cli), Vec::<String>::new());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn every_problem_is_reported_with_its_setting() -> Result<()> {
+        let dir = temp_dir("validate-problems")?;
+        let blocker = dir.join("file");
+        fs::write(&blocker, b"")?;
+        let database = blocker.join("salusd.redb");
+        let config: ConfigSalusd = from_env(&[
+            ("SALUSD_KEY_TIMEOUT", "0"),
+            ("SALUSD_COMPRESSION__LEVEL", "1000"),
+            ("SALUSD_SHARES__THRESHOLD", "9"),
+            ("SALUSD_NAMESPACE__PAYMENTS__KEY_TIMEOUT", "0"),
+            ("SALUSD_NAMESPACE__PAYMENTS__APPROVALS", "0"),
+            ("SALUSD_PLUGINS__PG__TIMEOUT_MS", "0"),
+            ("SALUSD_PLUGINS__WASM__MODULE", "mint.wasm"),
+            ("SALUSD_PLUGINS__WASM__FUEL", "0"),
+            ("SALUSD_SSH__MAX_TTL", "0"),
+            ("SALUSD_PKI__MAX_TTL", "0"),
+            ("SALUSD_DATABASE_ROLES__RO__PLUGIN", "mysql"),
+            ("SALUSD_HOOKS__RELOAD__URL", "ftp://example.com/reload"),
+            ("SALUSD_HOOKS__RELOAD__TIMEOUT_MS", "0"),
+            ("SALUSD_VALIDATION__TOKENS__PATTERN", "ghp_("),
+            ("SALUSD_VALIDATION__TOKENS__MIN_BYTES", "40"),
+            ("SALUSD_VALIDATION__TOKENS__MAX_BYTES", "4"),
+            ("SALUSD_TRACING__DIRECTIVES", "salusd=loud"),
+            (
+                "SALUSD_SOCKET_PATH",
+                &dir.join("salusd.sock").to_string_lossy(),
+            ),
+        ])?
+        .try_deserialize()?;
+        let cli = Cli::try_parse_from([
+            OsStr::new("salusd"),
+            OsStr::new("-d"),
+            database.as_os_str(),
+            OsStr::new("-t"),
+            dir.as_os_str(),
+        ])?;
+        let problems = problems(&config, &cli);
+        let settings = problems
+            .iter()
+            .filter_map(|problem| problem.split_once(':').map(|(setting, _)| setting))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            settings,
+            [
+                "key_timeout",
+                "shares",
+                "compression",
+                "namespace.payments.key_timeout",
+                "namespace.payments.approvals",
+                "plugins.pg.command",
+                "plugins.pg.timeout_ms",
+                "plugins.wasm.module",
+                "plugins.wasm.fuel",
+                "ssh.max_ttl",
+                "pki.max_ttl",
+                "database_roles.ro.plugin",
+                "database_roles.ro.creation",
+                "database_roles.ro.revocation",
+                "hooks.reload.url",
+                "hooks.reload.timeout_ms",
+                "validation.tokens.pattern",
+                "validation.tokens.max_bytes",
+                "tracing.directives",
+                "database",
+                "tracing"
+            ]
+        );
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn creatable_walks_up_to_the_nearest_existing_directory() -> Result<()> {
+        let dir = temp_dir("validate-creatable")?;
+        assert!(creatable(&dir.join("a").join("b").join("file")).is_ok());
+        assert!(creatable(&dir).is_err());
+        fs::write(dir.join("file"), b"")?;
+        assert!(creatable(&dir.join("file")).is_ok());
+        assert!(creatable(&dir.join("file").join("below")).is_err());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Aliases: keys that send reads on to another key, so a value can move to a
+//! new path while readers of the old one keep working.
+//!
+//! An alias is a row of `salus_aliases` naming its target. A read of a key
+//! that holds no value follows the key's alias, and the target's in turn, as
+//! far as [`MAX_ALIAS_DEPTH`] links. A value stored under an alias shadows it,
+//! and deleting the alias removes only the alias.
+
+use anyhow::Result;
+use libsalus::Response;
+use tracing::info;
+
+use super::ShareStore;
+use crate::{
+    db::{
+        SALUS_ALIASES_TABLE_DEF, SALUS_VAL_TABLE_DEF,
+        backend::{StorageBackend, Table, WriteOp},
+        put, read_backend, read_value,
+        values::salus::SalusVal,
+        write_keys,
+    },
+    error::Error,
+};
+
+/// The most aliases a read follows before giving up.
+pub(crate) const MAX_ALIAS_DEPTH: usize = 8;
+
+/// The lock every change to an alias holds, so two links cannot form a loop
+/// between them.
+pub(super) const ALIASES_LOCK: &str = "aliases";
+
+impl ShareStore {
+    /// Make `alias` an alias of `target`, replacing any alias it already is.
+    ///
+    /// `KeyExists` is answered when `alias` holds a value of its own. The
+    /// target need not hold a value yet; a read of the alias finds nothing
+    /// until it does.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or if the alias would lead
+    ///   back to itself.
+    pub(crate) fn link(&self, alias: &str, target: &str) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut response = Response::Success;
+        let locks = [(Table::Values, alias), (Table::Aliases, ALIASES_LOCK)];
+        write_keys(&self.backend, locks, |db| -> Result<()> {
+            if read_value(db, SALUS_VAL_TABLE_DEF, alias)?.is_some() {
+                response = Response::KeyExists;
+                return Ok(());
+            }
+            let (path, _) = chain(db, target)?;
+            if path.iter().any(|key| key == alias) {
+                return Err(Error::AliasLoop(alias.to_string()).into());
+            }
+            db.commit(vec![put(
+                SALUS_ALIASES_TABLE_DEF,
+                alias,
+                &target.to_string(),
+            )])?;
+            info!("Linked {alias} to {target}");
+            Ok(())
+        })?;
+        Ok(response)
+    }
+
+    /// The key a read of `key` reaches through its aliases, where its value
+    /// is; `None` when `key` is no alias. The store need not be unlocked.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the aliases loop or go too deep, or the database
+    ///   cannot be read.
+    pub(crate) fn resolve(&self, key: &str) -> Result<Option<String>> {
+        let mut stored = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            let (mut path, _) = chain(db, key)?;
+            if path.len() > 1 {
+                stored = path.pop();
+            }
+            Ok(())
+        })?;
+        Ok(stored)
+    }
+}
+
+/// The keys a read of `key` passes through, `key` first and the key it reads
+/// last, and the value it finds there.
+///
+/// # Errors
+///
+/// * Returns an error if the aliases loop, or go more than
+///   [`MAX_ALIAS_DEPTH`] deep.
+pub(super) fn chain(db: &dyn StorageBackend, key: &str) -> Result<(Vec<String>, Option<SalusVal>)> {
+    let mut path = vec![key.to_string()];
+    let mut current = key.to_string();
+    for _ in 0..=MAX_ALIAS_DEPTH {
+        if let Some(value) = read_value(db, SALUS_VAL_TABLE_DEF, &current)? {
+            return Ok((path, Some(value)));
+        }
+        let Some(target) = read_value(db, SALUS_ALIASES_TABLE_DEF, &current)? else {
+            return Ok((path, None));
+        };
+        if path.contains(&target) {
+            return Err(Error::AliasLoop(key.to_string()).into());
+        }
+        path.push(target.clone());
+        current = target;
+    }
+    Err(Error::AliasTooDeep(key.to_string(), MAX_ALIAS_DEPTH).into())
+}
+
+/// Remove the alias `key`, if it is one, returning whether it was.
+///
+/// The caller holds the lock of `key` in `salus_store`, as [`ShareStore::link`]
+/// does.
+pub(super) fn unlink(db: &dyn StorageBackend, key: &str) -> Result<bool> {
+    if read_value(db, SALUS_ALIASES_TABLE_DEF, key)?.is_none() {
+        return Ok(false);
+    }
+    db.commit(vec![WriteOp::Delete {
+        table: Table::Aliases,
+        key: key.to_string(),
+    }])?;
+    info!("Removed alias: {key}");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::Response;
+
+    use super::MAX_ALIAS_DEPTH;
+    use crate::store::test::{temp_store, unlocked_store};
+
+    #[test]
+    fn reads_follow_aliases_to_the_target() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("new/db", b"s3cret".to_vec(), false)?;
+        assert!(matches!(store.link("old/db", "new/db")?, Response::Success));
+        assert!(matches!(
+            store.link("older/db", "old/db")?,
+            Response::Success
+        ));
+        for key in ["new/db", "old/db", "older/db"] {
+            match store.read(key)? {
+                Response::Value(Some(value)) => assert_eq!(value, b"s3cret"),
+                other => bail!("expected the value through {key}, got {other:?}"),
+            }
+        }
+        // A write to the target is seen through the alias.
+        let _stored = store.store("new/db", b"rotated".to_vec(), true)?;
+        match store.read("older/db")? {
+            Response::Value(Some(value)) => assert_eq!(value, b"rotated"),
+            other => bail!("expected the new value, got {other:?}"),
+        }
+        // Deleting an alias leaves the target alone.
+        assert!(matches!(store.delete("old/db")?, Response::Success));
+        assert!(matches!(store.read("older/db")?, Response::Value(None)));
+        assert!(matches!(store.read("new/db")?, Response::Value(Some(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn links_that_would_loop_or_shadow_a_value_are_refused() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("held", b"value".to_vec(), false)?;
+        assert!(matches!(store.link("held", "other")?, Response::KeyExists));
+        assert!(store.link("a", "a").is_err());
+        assert!(matches!(store.link("a", "b")?, Response::Success));
+        assert!(matches!(store.link("b", "c")?, Response::Success));
+        assert!(store.link("c", "a").is_err());
+        // A dangling alias reads as a missing key.
+        assert!(matches!(store.read("a")?, Response::Value(None)));
+        Ok(())
+    }
+
+    #[test]
+    fn chains_past_the_depth_limit_fail() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("k0", b"end".to_vec(), false)?;
+        let mut previous = "k0".to_string();
+        for depth in 1..=MAX_ALIAS_DEPTH.saturating_add(1) {
+            let alias = format!("k{depth}");
+            assert!(matches!(store.link(&alias, &previous)?, Response::Success));
+            previous = alias;
+        }
+        assert!(store.read(&format!("k{MAX_ALIAS_DEPTH}")).is_ok());
+        assert!(store.read(&previous).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn aliases_resolve_to_the_key_holding_the_value() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("c", b"value".to_vec(), false)?;
+        assert!(matches!(store.link("b", "c")?, Response::Success));
+        assert!(matches!(store.link("a", "b")?, Response::Success));
+        assert_eq!(store.resolve("a")?.as_deref(), Some("c"));
+        assert_eq!(store.resolve("c")?, None);
+        assert_eq!(store.resolve("missing")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn linking_needs_the_store_unlocked() {
+        let store = temp_store();
+        assert!(store.link("a", "b").is_err());
+    }
+}
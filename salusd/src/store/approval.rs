@@ -0,0 +1,361 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Approvals: reads of a key held until enough other users agree to them.
+//!
+//! An approval is a row of `salus_approvals`, a JSON document under the
+//! approval's id naming the key, the user who asked to read it and the users
+//! who approved, by uid. A request waits `window` seconds for its approvals;
+//! once `required` users have given theirs, the requester may read the key
+//! for `window` seconds more. Approvals name keys, which are only revealed to
+//! an unlocked client, so they are looked up, listed and given only while the
+//! store is unlocked. A read is still held while it is sealed: its requester
+//! named the key.
+
+use anyhow::Result;
+use aws_lc_rs::rand;
+use libsalus::ApprovalInfo;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::ShareStore;
+use crate::{
+    db::{
+        SALUS_APPROVALS_TABLE_DEF,
+        backend::{Table, WriteOp},
+        put, read_backend, read_value, scan_values, write_keys,
+    },
+    error::Error,
+};
+
+/// The lock every write to `salus_approvals` holds. Asking for an approval
+/// forgets the lapsed rows of others, so one lock over the whole table keeps
+/// it from racing with an approval given or removed under its id.
+const APPROVALS_LOCK: &str = "approvals";
+
+/// A read of a key held for approval
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Approval {
+    /// The approval's id
+    id: String,
+    /// The key the read is for
+    key: String,
+    /// The user who asked to read it
+    requester: u32,
+    /// The users who approved it so far
+    approved_by: Vec<u32>,
+    /// How many approvals grant it
+    required: u32,
+    /// How long it waits, and once granted lasts, in seconds
+    window: u64,
+    /// When it lapses, in seconds since the Unix epoch
+    expires_at: u64,
+}
+
+impl Approval {
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn requester(&self) -> u32 {
+        self.requester
+    }
+
+    /// Whether enough users approved it.
+    pub(crate) fn granted(&self) -> bool {
+        u32::try_from(self.approved_by.len()).unwrap_or(u32::MAX) >= self.required
+    }
+
+    /// The approval as a client is told of it.
+    pub(crate) fn info(&self) -> ApprovalInfo {
+        ApprovalInfo::builder()
+            .id(self.id.as_str())
+            .key(self.key.as_str())
+            .requester(self.requester)
+            .approved_by(self.approved_by.clone())
+            .required(self.required)
+            .granted(self.granted())
+            .expires_at(self.expires_at)
+            .build()
+    }
+}
+
+impl ShareStore {
+    /// The approval for `requester` to read `key` at `now`, in seconds since
+    /// the Unix epoch: the one already granted or waiting, or else a new one,
+    /// waiting `window` seconds for `required` approvals. Approvals that
+    /// lapsed are forgotten on the way.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the approvals cannot be read or written.
+    pub(crate) fn request_approval(
+        &self,
+        key: &str,
+        requester: u32,
+        required: u32,
+        window: u64,
+        now: u64,
+    ) -> Result<Approval> {
+        let mut id = [0u8; 8];
+        rand::fill(&mut id)?;
+        let fresh = Approval {
+            id: format!("{:016x}", u64::from_be_bytes(id)),
+            key: key.to_string(),
+            requester,
+            approved_by: Vec::new(),
+            required,
+            window,
+            expires_at: now.saturating_add(window),
+        };
+        let mut approval = fresh.clone();
+        // Held over the table, so one user's reads of a key share one
+        // approval.
+        write_keys(
+            &self.backend,
+            [(Table::Approvals, APPROVALS_LOCK)],
+            |db| -> Result<()> {
+                let mut ops = Vec::new();
+                let mut waiting = None;
+                for (id, row) in scan_values(db, SALUS_APPROVALS_TABLE_DEF, "")? {
+                    let held = serde_json::from_str::<Approval>(&row)?;
+                    if held.expires_at <= now {
+                        ops.push(WriteOp::Delete {
+                            table: Table::Approvals,
+                            key: id,
+                        });
+                    } else if held.key == key && held.requester == requester {
+                        waiting = Some(held);
+                    }
+                }
+                if let Some(held) = waiting {
+                    approval = held;
+                } else {
+                    let row = serde_json::to_string(&fresh)?;
+                    ops.push(put(SALUS_APPROVALS_TABLE_DEF, &fresh.id, &row));
+                }
+                if ops.is_empty() {
+                    Ok(())
+                } else {
+                    db.commit(ops)
+                }
+            },
+        )?;
+        if approval == fresh {
+            info!(
+                target: "salusd::audit",
+                key,
+                approval = fresh.id.as_str(),
+                requester,
+                required,
+                "Read held for approval"
+            );
+        }
+        Ok(approval)
+    }
+
+    /// The approval `id`, unless it lapsed by `now` or is unknown.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the approval cannot be
+    ///   read.
+    pub(crate) fn approval(&self, id: &str, now: u64) -> Result<Option<Approval>> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut approval = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            approval = read_value(db, SALUS_APPROVALS_TABLE_DEF, id)?
+                .map(|row| serde_json::from_str::<Approval>(&row))
+                .transpose()?
+                .filter(|approval| approval.expires_at > now);
+            Ok(())
+        })?;
+        Ok(approval)
+    }
+
+    /// The approvals that have not lapsed by `now`, soonest to lapse first.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the approvals cannot be
+    ///   read.
+    pub(crate) fn approvals(&self, now: u64) -> Result<Vec<Approval>> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut approvals = Vec::new();
+        read_backend(&self.backend, |db| -> Result<()> {
+            for (_, row) in scan_values(db, SALUS_APPROVALS_TABLE_DEF, "")? {
+                let approval = serde_json::from_str::<Approval>(&row)?;
+                if approval.expires_at > now {
+                    approvals.push(approval);
+                }
+            }
+            Ok(())
+        })?;
+        approvals.sort_by_key(|approval| approval.expires_at);
+        Ok(approvals)
+    }
+
+    /// Add `approver`'s approval to `id` at `now`, granting it for its
+    /// window once enough users have approved, and answering with it as it
+    /// stands; `None` when it lapsed or is unknown.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`Error::SelfApproval`] when `approver` asked for the read,
+    ///   or an error if the store is locked or the approval cannot be read or
+    ///   written.
+    pub(crate) fn approve(&self, id: &str, approver: u32, now: u64) -> Result<Option<Approval>> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut updated = None;
+        write_keys(
+            &self.backend,
+            [(Table::Approvals, APPROVALS_LOCK)],
+            |db| -> Result<()> {
+                let Some(row) = read_value(db, SALUS_APPROVALS_TABLE_DEF, id)? else {
+                    return Ok(());
+                };
+                let mut approval = serde_json::from_str::<Approval>(&row)?;
+                if approval.expires_at <= now {
+                    return Ok(());
+                }
+                if approval.requester == approver {
+                    return Err(Error::SelfApproval.into());
+                }
+                if approval.granted() || approval.approved_by.contains(&approver) {
+                    updated = Some(approval);
+                    return Ok(());
+                }
+                approval.approved_by.push(approver);
+                if approval.granted() {
+                    approval.expires_at = now.saturating_add(approval.window);
+                }
+                let row = serde_json::to_string(&approval)?;
+                db.commit(vec![put(SALUS_APPROVALS_TABLE_DEF, id, &row)])?;
+                info!(
+                    target: "salusd::audit",
+                    key = approval.key.as_str(),
+                    approval = id,
+                    requester = approval.requester,
+                    approver,
+                    granted = approval.granted(),
+                    "Read approved"
+                );
+                updated = Some(approval);
+                Ok(())
+            },
+        )?;
+        Ok(updated)
+    }
+
+    /// Forget the approval `id`, refusing the read it holds.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the approval cannot be removed.
+    pub(crate) fn remove_approval(&self, id: &str) -> Result<()> {
+        write_keys(&self.backend, [(Table::Approvals, APPROVALS_LOCK)], |db| {
+            db.commit(vec![WriteOp::Delete {
+                table: Table::Approvals,
+                key: id.to_string(),
+            }])
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use crate::{
+        error::Error,
+        store::test::{temp_store, unlocked_store},
+    };
+
+    #[test]
+    fn a_read_is_granted_once_enough_others_approve() -> Result<()> {
+        let store = unlocked_store()?;
+        let held = store.request_approval("payments/card", 1000, 2, 60, 100)?;
+        assert!(!held.granted());
+        let again = store.request_approval("payments/card", 1000, 2, 60, 110)?;
+        assert_eq!(again, held);
+        let other = store.request_approval("payments/card", 1001, 2, 60, 110)?;
+        assert_ne!(other.info().id(), held.info().id());
+
+        let id = held.info().id().clone();
+        match store.approve(&id, 1000, 120) {
+            Err(e) if matches!(e.downcast_ref(), Some(Error::SelfApproval)) => {}
+            other => bail!("the requester approved their own read: {other:?}"),
+        }
+        let Some(once) = store.approve(&id, 1001, 120)? else {
+            bail!("a waiting approval was not found");
+        };
+        assert!(!once.granted());
+        assert_eq!(store.approve(&id, 1001, 120)?, Some(once));
+        let Some(granted) = store.approve(&id, 1002, 130)? else {
+            bail!("a waiting approval was not found");
+        };
+        assert!(granted.granted());
+        assert_eq!(granted.info().expires_at(), 190);
+        assert_eq!(store.approvals(160)?.len(), 2);
+        assert_eq!(
+            store.request_approval("payments/card", 1000, 2, 60, 180)?,
+            granted
+        );
+        // 1001's request lapsed at 170, and was forgotten.
+        assert_eq!(store.approvals(0)?, vec![granted]);
+        Ok(())
+    }
+
+    #[test]
+    fn approvals_lapse_after_their_window() -> Result<()> {
+        let store = unlocked_store()?;
+        let held = store.request_approval("payments/card", 1000, 1, 60, 100)?;
+        let id = held.info().id().clone();
+        assert_eq!(store.approval(&id, 159)?, Some(held));
+        assert_eq!(store.approval(&id, 160)?, None);
+        assert_eq!(store.approve(&id, 1001, 160)?, None);
+        assert!(store.approvals(160)?.is_empty());
+
+        // Asking again after it lapsed starts a new one, and forgets the old.
+        let renewed = store.request_approval("payments/card", 1000, 1, 60, 160)?;
+        assert_ne!(renewed.info().id(), &id);
+        assert_eq!(store.approval(&id, 0)?, None);
+        store.remove_approval(renewed.info().id())?;
+        assert!(store.approvals(160)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn approvals_are_only_looked_up_while_unlocked() -> Result<()> {
+        let store = temp_store();
+        let held = store.request_approval("payments/card", 1000, 1, 60, 100)?;
+        let id = held.info().id().clone();
+        let sealed = [
+            store.approvals(100).err(),
+            store.approval(&id, 100).err(),
+            store.approve(&id, 1001, 100).err(),
+        ];
+        for refusal in sealed {
+            match refusal {
+                Some(e) if matches!(e.downcast_ref(), Some(Error::StoreNotUnlocked)) => {}
+                other => bail!("expected the sealed store to refuse, got {other:?}"),
+            }
+        }
+        Ok(())
+    }
+}
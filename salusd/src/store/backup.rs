@@ -0,0 +1,360 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Online backups.
+//!
+//! A backup is a redb database holding every row of the store, copied under
+//! the backend's lock so it is one point in time whichever backend the daemon
+//! runs on. The rows are already sealed, so taking one needs no unlock, and
+//! the file holds nothing the store does not. It can be encrypted once more to
+//! an age recipient for offsite copies. A JSON manifest written beside it
+//! records its checksums, the row counts, and the store it came from.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Read as _, Write},
+    iter,
+    path::{Path, PathBuf},
+    str::FromStr as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use age::{Encryptor, x25519};
+use anyhow::{Context as _, Result};
+use aws_lc_rs::digest;
+use getset::{CopyGetters, Getters};
+use libsalus::{Backup, BackupInfo, Response};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::ShareStore;
+use crate::{
+    db::{
+        SHARE_EPOCH_KEY,
+        backend::{RedbBackend, StorageBackend, Table, WriteOp},
+        unlock_backend,
+    },
+    error::Error,
+};
+
+/// The manifest format this daemon writes and reads.
+pub(crate) const MANIFEST_FORMAT: u32 = 1;
+/// The `encryption` a manifest records for a file encrypted to an age
+/// recipient.
+pub(crate) const AGE_ENCRYPTION: &str = "age";
+
+/// What a backup's manifest records.
+#[derive(Clone, CopyGetters, Debug, Deserialize, Getters, Serialize)]
+pub(crate) struct BackupManifest {
+    #[getset(get_copy = "pub(crate)")]
+    format: u32,
+    /// When the backup was taken, in seconds since the Unix epoch
+    created_at: u64,
+    daemon_version: String,
+    /// The store's fingerprint, as `status` and paper backups show it
+    #[getset(get = "pub(crate)")]
+    fingerprint: Option<String>,
+    #[getset(get_copy = "pub(crate)")]
+    share_epoch: u64,
+    /// The backup's file name, in the manifest's directory
+    file: String,
+    #[getset(get_copy = "pub(crate)")]
+    bytes: u64,
+    #[getset(get = "pub(crate)")]
+    sha256: String,
+    /// [`AGE_ENCRYPTION`] when the file is encrypted to an age recipient
+    #[getset(get = "pub(crate)")]
+    encryption: Option<String>,
+    /// The SHA-256 of the redb database itself: the same as `sha256` unless
+    /// the file is encrypted
+    #[getset(get = "pub(crate)")]
+    database_sha256: String,
+    /// The rows copied, by table
+    #[getset(get = "pub(crate)")]
+    rows: BTreeMap<String, u64>,
+}
+
+/// Where the manifest of the backup at `path` is written.
+pub(crate) fn manifest_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".manifest.json");
+    path.with_file_name(name)
+}
+
+impl ShareStore {
+    /// Copy the store to a new redb file at the requested path, encrypted to
+    /// the recipient if there is one, and write its manifest beside it.
+    pub(crate) fn backup(&self, request: &Backup) -> Result<Response> {
+        let recipient = match request
+            .recipient()
+            .as_deref()
+            .map(x25519::Recipient::from_str)
+        {
+            Some(Ok(recipient)) => Some(recipient),
+            Some(Err(_)) => return Ok(Response::InvalidPublicKey),
+            None => None,
+        };
+        let path = PathBuf::from(request.path());
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|_| path.is_absolute())
+            .ok_or_else(|| Error::BackupPath(path.clone()))?;
+        let manifest_path = manifest_path(&path);
+        if path.exists() || manifest_path.exists() {
+            return Err(Error::BackupExists(path).into());
+        }
+
+        let (ops, rows) = self.snapshot()?;
+        let partial = path.with_file_name(format!(".{file_name}.salusd-{}", std::process::id()));
+        drop(fs::remove_file(&partial));
+        let written = write_backup(&partial, &path, ops, recipient.as_ref());
+        drop(fs::remove_file(&partial));
+        let database_sha256 = written?;
+        let (sha256, bytes) = sha256_file(&path)?;
+
+        let manifest = BackupManifest {
+            format: MANIFEST_FORMAT,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            fingerprint: self.fingerprint()?,
+            share_epoch: self.config_value::<u64>(SHARE_EPOCH_KEY)?.unwrap_or(0),
+            file: file_name.to_string(),
+            bytes,
+            sha256: sha256.clone(),
+            encryption: recipient.is_some().then(|| AGE_ENCRYPTION.to_string()),
+            database_sha256,
+            rows,
+        };
+        let mut out = create_private(&manifest_path)?;
+        serde_json::to_writer_pretty(&mut out, &manifest)?;
+        writeln!(out)?;
+        out.sync_all()?;
+
+        let rows = manifest.rows.values().sum();
+        let encrypted = recipient.is_some();
+        info!(
+            target: "salusd::audit",
+            path = %path.display(), sha256 = sha256.as_str(), rows, encrypted, "Store backed up"
+        );
+        Ok(Response::BackedUp(
+            BackupInfo::builder()
+                .path(path.display().to_string())
+                .manifest(manifest_path.display().to_string())
+                .sha256(sha256)
+                .bytes(bytes)
+                .rows(rows)
+                .encrypted(encrypted)
+                .build(),
+        ))
+    }
+
+    /// Every row of every table, read while every write is held off, with the
+    /// count per table.
+    fn snapshot(&self) -> Result<(Vec<WriteOp>, BTreeMap<String, u64>)> {
+        let mut ops = Vec::new();
+        let mut rows = BTreeMap::new();
+        unlock_backend(&self.backend, |db: &dyn StorageBackend| -> Result<()> {
+            for table in Table::ALL {
+                let scanned = db.scan(table, "")?;
+                let _prev = rows.insert(table.name().to_string(), u64::try_from(scanned.len())?);
+                ops.extend(scanned.into_iter().map(|(key, value)| WriteOp::Put {
+                    table,
+                    key,
+                    value,
+                }));
+            }
+            Ok(())
+        })?;
+        Ok((ops, rows))
+    }
+}
+
+/// Write `ops` to a redb database at `partial`, then copy it to `path`,
+/// encrypting it to `recipient` on the way if there is one. Returns the
+/// database's SHA-256; `path` is removed again if the copy fails.
+fn write_backup(
+    partial: &Path,
+    path: &Path,
+    ops: Vec<WriteOp>,
+    recipient: Option<&x25519::Recipient>,
+) -> Result<String> {
+    RedbBackend::create(partial)?.commit(ops)?;
+    let (database_sha256, _bytes) = sha256_file(partial)?;
+    let mut source = File::open(partial)?;
+    let mut out = create_private(path)?;
+    let copied = (|| -> Result<()> {
+        if let Some(recipient) = recipient {
+            let recipient: &dyn age::Recipient = recipient;
+            let encryptor = Encryptor::with_recipients(iter::once(recipient))?;
+            let mut writer = encryptor.wrap_output(&mut out)?;
+            let _copied = io::copy(&mut source, &mut writer)?;
+            let _out = writer.finish()?;
+        } else {
+            let _copied = io::copy(&mut source, &mut out)?;
+        }
+        Ok(out.sync_all()?)
+    })();
+    if copied.is_err() {
+        drop(fs::remove_file(path));
+    }
+    copied.with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(database_sha256)
+}
+
+/// Create `path`, which must not exist yet, readable by the daemon's user
+/// only.
+pub(crate) fn create_private(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    let _ = options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        let _ = options.mode(0o600);
+    }
+    options
+        .open(path)
+        .with_context(|| format!("unable to create {}", path.display()))
+}
+
+/// The SHA-256 of the file at `path`, in hex, and its size in bytes.
+pub(crate) fn sha256_file(path: &Path) -> Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut context = digest::Context::new(&digest::SHA256);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut bytes = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        let Some(chunk) = buffer.get(..read).filter(|chunk| !chunk.is_empty()) else {
+            break;
+        };
+        context.update(chunk);
+        bytes = bytes.saturating_add(u64::try_from(read)?);
+    }
+    let hex = context
+        .finish()
+        .as_ref()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    Ok((hex, bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use age::x25519;
+    use anyhow::{Result, bail};
+    use libsalus::{Backup, Response};
+    use serde_json::Value;
+
+    use super::{super::test::unlocked_store, sha256_file};
+    use crate::{
+        db::{
+            backend::{RedbBackend, StorageBackend as _, Table},
+            unlock_backend,
+        },
+        utils::test::temp_dir,
+    };
+
+    fn field(manifest: &Value, pointer: &str) -> Value {
+        manifest.pointer(pointer).cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn a_backup_holds_every_row_and_matches_its_manifest() -> Result<()> {
+        let dir = temp_dir("backup")?;
+        let result = (|| -> Result<()> {
+            let store = unlocked_store()?;
+            let _stored = store.store("db/pass", b"hunter2".to_vec(), false)?;
+            let path = dir.join("salus.redb");
+            let request = Backup::builder().path(path.display().to_string()).build();
+            let Response::BackedUp(info) = store.backup(&request)? else {
+                bail!("expected a backup");
+            };
+            assert!(!info.encrypted());
+            assert_eq!(sha256_file(&path)?, (info.sha256().clone(), info.bytes()));
+
+            let manifest: Value = serde_json::from_slice(&fs::read(info.manifest())?)?;
+            assert_eq!(field(&manifest, "/sha256"), info.sha256().as_str());
+            assert_eq!(field(&manifest, "/database_sha256"), info.sha256().as_str());
+            assert_eq!(field(&manifest, "/file"), "salus.redb");
+            assert_eq!(field(&manifest, "/rows/salus_store"), 2);
+
+            let copy = RedbBackend::open(&path)?;
+            let mut original = None;
+            unlock_backend(&store.backend, |db| -> Result<()> {
+                original = db.get(Table::Values, "db/pass")?;
+                Ok(())
+            })?;
+            assert!(original.is_some());
+            assert_eq!(copy.get(Table::Values, "db/pass")?, original);
+
+            // A second backup to the same place is refused.
+            assert!(store.backup(&request).is_err());
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+
+    #[test]
+    fn an_encrypted_backup_opens_with_the_identity() -> Result<()> {
+        let dir = temp_dir("backup-age")?;
+        let result = (|| -> Result<()> {
+            let store = unlocked_store()?;
+            let identity = x25519::Identity::generate();
+            let path = dir.join("salus.redb.age");
+            let request = Backup::builder()
+                .path(path.display().to_string())
+                .recipient(identity.to_public().to_string())
+                .build();
+            let Response::BackedUp(info) = store.backup(&request)? else {
+                bail!("expected a backup");
+            };
+            assert!(info.encrypted());
+
+            let manifest: Value = serde_json::from_slice(&fs::read(info.manifest())?)?;
+            assert_eq!(field(&manifest, "/encryption"), "age");
+            let database = age::decrypt(&identity, &fs::read(&path)?)?;
+            let decrypted = dir.join("salus.redb");
+            fs::write(&decrypted, &database)?;
+            assert_eq!(
+                field(&manifest, "/database_sha256"),
+                sha256_file(&decrypted)?.0
+            );
+            assert!(RedbBackend::open(&decrypted).is_ok());
+            Ok(())
+        })();
+        drop(fs::remove_dir_all(&dir));
+        result
+    }
+
+    #[test]
+    fn a_bad_recipient_or_relative_path_is_refused() -> Result<()> {
+        let store = unlocked_store()?;
+        let request = Backup::builder()
+            .path("/nonexistent/salus.redb")
+            .recipient("age1nope")
+            .build();
+        assert!(matches!(
+            store.backup(&request)?,
+            Response::InvalidPublicKey
+        ));
+        let relative = Backup::builder().path("salus.redb").build();
+        assert!(store.backup(&relative).is_err());
+        Ok(())
+    }
+}
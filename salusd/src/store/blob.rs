@@ -0,0 +1,1118 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Values too large for one message, stored and read in chunks.
+//!
+//! An upload names its key and length up front, then sends its chunks, each
+//! sealed on arrival as its own `salus_blobs` row under the store key, bound to
+//! a `blob:` AAD naming the upload and the chunk's position. Only one chunk is
+//! ever held in memory. Finishing the upload writes the key's `salus_store`
+//! row: the upload's id in the clear, then a sealed manifest of the id and the
+//! length, so a chunk cannot be dropped, reordered, or borrowed from another
+//! value without the read failing. Reads hand back the id and length, and the
+//! client asks for the chunks one at a time.
+//!
+//! Each chunk is also tagged on arrival with an HMAC under a key derived from
+//! the store key, and the manifest records a digest of the tags. A finished
+//! upload whose digest matches a value already stored in chunks is not kept:
+//! its row names the chunks already stored, and `salus_blob_refs` counts the
+//! rows naming them, so they are removed only with the last of those rows.
+//! Turning `[streaming] dedup` off stores every upload's chunks on its own.
+//!
+//! An upload that is not finished within the timeout is dropped with its
+//! chunks. Chunks that no row names (an upload cut short by a restart, or a
+//! streamed value replaced by a batch or an import) are removed, and the
+//! counts of shared chunks recounted, when the daemon starts.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write as _,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use aws_lc_rs::{hkdf, hmac, rand};
+use libsalus::{
+    BeginUpload, ReadChunk, Response, StreamedValue, UploadChunk, chunk_count, chunk_len,
+};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use super::{KeyLen, ShareStore, now, open, seal};
+use crate::{
+    db::{
+        Backend, Row as _, SALUS_BLOB_REFS_TABLE_DEF, SALUS_BLOBS_TABLE_DEF, SALUS_VAL_TABLE_DEF,
+        backend::{StorageBackend, Table, WriteOp},
+        put, read_backend, read_value, unlock_backend,
+        values::{
+            blob_ref::BlobRef,
+            salus::{BLOB_ID_LEN, SalusVal},
+        },
+        write_keys, write_value,
+    },
+    error::Error,
+};
+
+/// The longest value stored in chunks, by default (1 GiB).
+pub(crate) const DEFAULT_MAX_STREAM_BYTES: u64 = 1024 * 1024 * 1024;
+/// How many uploads may be in progress at once, by default.
+pub(crate) const DEFAULT_MAX_UPLOADS: usize = 4;
+/// How long an upload may go without a chunk before it is dropped, by default.
+pub(crate) const DEFAULT_UPLOAD_TIMEOUT: u64 = 300;
+
+/// The HKDF salt for the key chunks are tagged under.
+const DIGEST_SALT: &[u8] = b"salus blob digest";
+/// The HKDF info for the key chunks are tagged under.
+const DIGEST_INFO: &[u8] = b"salus blob digest v1";
+/// The length of a chunk's tag and of a value's digest, in bytes.
+const DIGEST_LEN: usize = 32;
+/// The lock every change to `salus_blob_refs` holds, so one writer at a time
+/// counts the rows naming shared chunks.
+pub(super) const REFS_LOCK: &str = "refs";
+
+/// Uploads in progress, and the limits they are held to.
+#[derive(Debug)]
+pub(crate) struct Uploads {
+    max_bytes: u64,
+    max_pending: usize,
+    timeout: Duration,
+    dedup: bool,
+    pending: Mutex<HashMap<String, Upload>>,
+}
+
+#[derive(Debug)]
+struct Upload {
+    id: [u8; BLOB_ID_LEN],
+    key: String,
+    len: u64,
+    force: bool,
+    /// The tag of each chunk received, by position.
+    received: BTreeMap<u32, [u8; DIGEST_LEN]>,
+    expires_at: Instant,
+}
+
+/// What the manifest of a value stored in chunks records.
+#[derive(Debug)]
+struct Manifest {
+    id: [u8; BLOB_ID_LEN],
+    len: u64,
+    /// The digest of the value, unless it was stored without deduplication.
+    digest: Option<[u8; DIGEST_LEN]>,
+}
+
+impl Default for Uploads {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_STREAM_BYTES,
+            DEFAULT_MAX_UPLOADS,
+            Duration::from_secs(DEFAULT_UPLOAD_TIMEOUT),
+            true,
+        )
+    }
+}
+
+impl Uploads {
+    /// Accept values of up to `max_bytes`, from up to `max_uploads` uploads at
+    /// once, each dropped after `timeout` without a chunk, sharing the chunks
+    /// of equal values when `dedup` is set.
+    pub(crate) fn new(max_bytes: u64, max_uploads: usize, timeout: Duration, dedup: bool) -> Self {
+        Self {
+            max_bytes,
+            max_pending: max_uploads,
+            timeout,
+            dedup,
+            pending: Mutex::default(),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<String, Upload>> {
+        match self.pending.lock() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn expiry(&self) -> Instant {
+        let now = Instant::now();
+        now.checked_add(self.timeout).unwrap_or(now)
+    }
+
+    /// Take out every upload that has timed out, returning their ids.
+    fn take_expired(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut pending = self.pending();
+        let expired = pending
+            .iter()
+            .filter(|(_, upload)| upload.expires_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for id in &expired {
+            let _dropped = pending.remove(id);
+        }
+        expired
+    }
+}
+
+impl ShareStore {
+    /// Start uploading a value of `begin.size()` bytes under `begin.key()`.
+    pub(crate) fn begin_upload(&self, begin: &BeginUpload) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let limits = &self.uploads;
+        if begin.size() > limits.max_bytes {
+            info!("Refusing a {} byte upload", begin.size());
+            return Ok(Response::ValueTooLarge(limits.max_bytes));
+        }
+        if let Err(failure) = self.rules.check_upload(begin.key(), begin.size()) {
+            info!(
+                "Refusing an upload to {} that breaks rule {}",
+                begin.key(),
+                failure.rule()
+            );
+            return Ok(Response::ValidationFailed(failure));
+        }
+        let _chunks = u32::try_from(chunk_count(begin.size()))?;
+        if !begin.force() {
+            let mut exists = false;
+            read_backend(&self.backend, |db| -> Result<()> {
+                exists = read_value(db, SALUS_VAL_TABLE_DEF, begin.key())?.is_some();
+                Ok(())
+            })?;
+            if exists {
+                info!(
+                    "Refusing to overwrite existing key without force: {}",
+                    begin.key()
+                );
+                return Ok(Response::KeyExists);
+            }
+        }
+        for expired in limits.take_expired() {
+            info!("Upload {expired} timed out");
+            self.delete_chunks(&expired)?;
+        }
+        let mut id = [0u8; BLOB_ID_LEN];
+        rand::fill(&mut id)?;
+        let name = hex(&id);
+        let mut pending = limits.pending();
+        if pending.len() >= limits.max_pending {
+            return Err(Error::TooManyUploads(limits.max_pending).into());
+        }
+        let upload = Upload {
+            id,
+            key: begin.key().clone(),
+            len: begin.size(),
+            force: begin.force(),
+            received: BTreeMap::new(),
+            expires_at: limits.expiry(),
+        };
+        let _old = pending.insert(name.clone(), upload);
+        info!("Started upload {name} of {} bytes", begin.size());
+        Ok(Response::UploadStarted(name))
+    }
+
+    /// Seal and store one chunk of an upload.
+    pub(crate) fn upload_chunk(&self, chunk: &UploadChunk) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let Some(len) = self.live_upload(chunk.upload(), |upload| upload.len) else {
+            return Ok(Response::UploadNotFound);
+        };
+        let index = chunk.index();
+        let expected = chunk_len(len, index);
+        if expected != Some(chunk.bytes().len()) {
+            let expected = expected.unwrap_or(0);
+            return Err(Error::ChunkSize(index, chunk.bytes().len(), expected).into());
+        }
+        let row = chunk_row(chunk.upload(), index);
+        let tag = tag(&digest_key(enc_key)?, chunk.bytes())?;
+        let mut bytes = chunk.bytes().clone();
+        let sealed = seal(enc_key, &chunk_aad(&row), &mut bytes)?;
+        write_keys(&self.backend, [(Table::Blobs, row.as_str())], |db| {
+            write_value(db, SALUS_BLOBS_TABLE_DEF, &row, &sealed)
+        })?;
+        let expires_at = self.uploads.expiry();
+        let recorded = self.live_upload(chunk.upload(), |upload| {
+            let _old = upload.received.insert(index, tag);
+            upload.expires_at = expires_at;
+        });
+        if recorded.is_none() {
+            // Timed out while this chunk was written.
+            self.delete_chunks(chunk.upload())?;
+            return Ok(Response::UploadNotFound);
+        }
+        Ok(Response::Success)
+    }
+
+    /// Store an upload whose every chunk has arrived under its key.
+    pub(crate) fn finish_upload(&self, name: &str) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let upload = {
+            let mut pending = self.uploads.pending();
+            let Some(upload) = pending
+                .get(name)
+                .filter(|upload| upload.expires_at > Instant::now())
+            else {
+                return Ok(Response::UploadNotFound);
+            };
+            if let Some(missing) =
+                (0..chunk_count(upload.len)).find(|index| match u32::try_from(*index) {
+                    Ok(index) => !upload.received.contains_key(&index),
+                    Err(_) => true,
+                })
+            {
+                return Err(Error::ChunkMissing(missing).into());
+            }
+            pending
+                .remove(name)
+                .ok_or_else(|| anyhow!("the upload vanished while held"))?
+        };
+        let digest = if self.uploads.dedup {
+            Some(upload.digest(enc_key)?)
+        } else {
+            None
+        };
+        let key = upload.key.as_str();
+        let (mut exists, mut shared, mut version) = (false, false, 0);
+        let locks = [(Table::Values, key), (Table::BlobRefs, REFS_LOCK)];
+        write_keys(&self.backend, locks, |db| -> Result<()> {
+            let existing = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+            exists = !upload.force && existing.is_some();
+            if exists {
+                return Ok(());
+            }
+            let (id, mut ops) =
+                upload.chunk_writes(db, name, enc_key, existing.as_ref(), digest)?;
+            shared = id != upload.id;
+            let manifest = Manifest {
+                id,
+                len: upload.len,
+                digest,
+            };
+            let mut sealed_manifest = manifest.to_bytes();
+            let sealed = seal(enc_key, key, &mut sealed_manifest)?;
+            let row = SalusVal::from_blob_parts(id, sealed.nonce()?, sealed.ciphertext()?);
+            ops.push(put(SALUS_VAL_TABLE_DEF, key, &row));
+            let (revised, next) = self.revised(db, key, existing.as_ref(), Some(now()))?;
+            ops.extend(revised);
+            db.commit(ops)?;
+            version = next;
+            self.read_cache.invalidate([key]);
+            Ok(())
+        })?;
+        if exists {
+            info!("Refusing to overwrite existing key without force: {key}");
+            self.delete_chunks(name)?;
+            return Ok(Response::KeyExists);
+        }
+        self.changes.written(key, version);
+        if shared {
+            info!(
+                "Stored {} bytes under key {key} in the chunks of an equal value",
+                upload.len
+            );
+        } else {
+            info!("Stored {} bytes in chunks under key: {key}", upload.len);
+        }
+        Ok(Response::Success)
+    }
+
+    /// Run `write_fn` with the value under `key` while holding its write lock,
+    /// and the lock of `salus_blob_refs` too when that value is stored in
+    /// chunks, so [`ShareStore::release_chunks`] may count its chunks' rows.
+    pub(super) fn write_value_row(
+        &self,
+        key: &str,
+        mut write_fn: impl FnMut(&dyn StorageBackend, Option<SalusVal>) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            let mut streamed = false;
+            read_backend(&self.backend, |db| -> Result<()> {
+                streamed = is_streamed(read_value(db, SALUS_VAL_TABLE_DEF, key)?.as_ref())?;
+                Ok(())
+            })?;
+            let mut locks = vec![(Table::Values, key)];
+            if streamed {
+                locks.push((Table::BlobRefs, REFS_LOCK));
+            }
+            let mut retry = false;
+            write_keys(&self.backend, locks, |db| -> Result<()> {
+                let existing = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+                // Stored in chunks since it was looked at, so take the refs
+                // lock and look again.
+                retry = !streamed && is_streamed(existing.as_ref())?;
+                if retry {
+                    return Ok(());
+                }
+                write_fn(db, existing)
+            })?;
+            if !retry {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The writes that let go of the chunks of `replaced`, the value under
+    /// `key`, if it is stored in chunks: their deletes, unless other values
+    /// share them, when one fewer row is counted as naming them instead.
+    ///
+    /// The caller holds the lock of `salus_blob_refs`. Chunks whose manifest
+    /// no longer opens are left for the sweep when the daemon starts, as they
+    /// may be shared.
+    pub(super) fn release_chunks(
+        db: &dyn StorageBackend,
+        enc_key: &[u8],
+        key: &str,
+        replaced: Option<&SalusVal>,
+    ) -> Result<Vec<WriteOp>> {
+        Self::release_all_chunks(db, enc_key, replaced.map(|replaced| (key, replaced)))
+    }
+
+    /// The writes that let go of the chunks of each of `replaced`, as
+    /// [`ShareStore::release_chunks`] does for one, with the values that share
+    /// chunks counted off together.
+    pub(super) fn release_all_chunks<'a>(
+        db: &dyn StorageBackend,
+        enc_key: &[u8],
+        replaced: impl IntoIterator<Item = (&'a str, &'a SalusVal)>,
+    ) -> Result<Vec<WriteOp>> {
+        // The number of values let go of, by the digest naming their chunks.
+        let mut released = BTreeMap::<[u8; DIGEST_LEN], ([u8; BLOB_ID_LEN], u64)>::new();
+        let mut ids = BTreeSet::new();
+        for (key, replaced) in replaced {
+            let Some(id) = replaced.blob_id()? else {
+                continue;
+            };
+            let manifest = match Self::manifest_of(enc_key, key, replaced) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Leaving the chunks of '{key}' to the sweep: {e}");
+                    continue;
+                }
+            };
+            match manifest.digest {
+                Some(digest) => {
+                    let (_, count) = released.entry(digest).or_insert((id, 0));
+                    *count = count.saturating_add(1);
+                }
+                None => {
+                    let _ = ids.insert(id);
+                }
+            }
+        }
+        let mut ops = Vec::new();
+        for (digest, (id, count)) in released {
+            let refs_key = hex(&digest);
+            match read_value(db, SALUS_BLOB_REFS_TABLE_DEF, &refs_key)? {
+                Some(mut blob_ref) if blob_ref.id == id && blob_ref.count > count => {
+                    blob_ref.count = blob_ref.count.saturating_sub(count);
+                    ops.push(put(SALUS_BLOB_REFS_TABLE_DEF, &refs_key, &blob_ref));
+                }
+                Some(blob_ref) if blob_ref.id == id => {
+                    let _ = ids.insert(id);
+                    ops.push(WriteOp::Delete {
+                        table: Table::BlobRefs,
+                        key: refs_key,
+                    });
+                }
+                _ => {
+                    let _ = ids.insert(id);
+                }
+            }
+        }
+        for id in ids {
+            ops.extend(deletes(db.keys(Table::Blobs, &format!("{}/", hex(&id)))?));
+        }
+        Ok(ops)
+    }
+
+    pub(crate) fn read_chunk(&self, request: &ReadChunk) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if !is_id(request.id()) {
+            return Ok(Response::KeyNotFound);
+        }
+        let row = chunk_row(request.id(), request.index());
+        let mut sealed = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = read_value(db, SALUS_BLOBS_TABLE_DEF, &row)?;
+            Ok(())
+        })?;
+        let Some(sealed) = sealed else {
+            return Ok(Response::KeyNotFound);
+        };
+        Ok(Response::Chunk(open(enc_key, &chunk_aad(&row), &sealed)?))
+    }
+
+    /// Run `upload_fn` on the upload `name`, unless it is unknown or has timed
+    /// out.
+    fn live_upload<T>(&self, name: &str, upload_fn: impl FnOnce(&mut Upload) -> T) -> Option<T> {
+        self.uploads
+            .pending()
+            .get_mut(name)
+            .filter(|upload| upload.expires_at > Instant::now())
+            .map(upload_fn)
+    }
+
+    /// Remove every chunk stored under the id `name`.
+    fn delete_chunks(&self, name: &str) -> Result<()> {
+        let mut rows = vec![];
+        read_backend(&self.backend, |db| -> Result<()> {
+            rows = db.keys(Table::Blobs, &format!("{name}/"))?;
+            Ok(())
+        })?;
+        write_keys(
+            &self.backend,
+            rows.iter().map(|row| (Table::Blobs, row.as_str())),
+            |db| db.commit(deletes(rows.clone())),
+        )
+    }
+
+    /// What a value stored in chunks holds: its id and length.
+    pub(super) fn open_manifest(
+        enc_key: &[u8],
+        key: &str,
+        sealed: &SalusVal,
+    ) -> Result<StreamedValue> {
+        let manifest = Self::manifest_of(enc_key, key, sealed)?;
+        Ok(StreamedValue::builder()
+            .id(hex(&manifest.id))
+            .size(manifest.len)
+            .build())
+    }
+
+    fn manifest_of(enc_key: &[u8], key: &str, sealed: &SalusVal) -> Result<Manifest> {
+        let id = sealed
+            .blob_id()?
+            .ok_or_else(|| anyhow!("the value under '{key}' is not stored in chunks"))?;
+        let opened = open(enc_key, key, sealed)?;
+        let manifest = Manifest::from_bytes(&opened)
+            .ok_or_else(|| anyhow!("the manifest of '{key}' is malformed"))?;
+        if manifest.id != id {
+            return Err(anyhow!("the manifest of '{key}' names other chunks"));
+        }
+        Ok(manifest)
+    }
+
+    /// Check that every chunk of the streamed value under `key` is present
+    /// and opens.
+    pub(super) fn check_chunks(&self, enc_key: &[u8], key: &str, sealed: &SalusVal) -> Result<()> {
+        let manifest = Self::open_manifest(enc_key, key, sealed)?;
+        for index in 0..chunk_count(manifest.size()) {
+            let index = u32::try_from(index)?;
+            let row = chunk_row(manifest.id(), index);
+            let (mut chunk, mut current) = (None, None);
+            read_backend(&self.backend, |db| -> Result<()> {
+                chunk = read_value(db, SALUS_BLOBS_TABLE_DEF, &row)?;
+                if chunk.is_none() {
+                    current = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+                }
+                Ok(())
+            })?;
+            let Some(chunk) = chunk else {
+                // Replaced since the check read it.
+                if current.and_then(|row| row.blob_id().ok().flatten()) != sealed.blob_id()? {
+                    return Ok(());
+                }
+                return Err(Error::ChunkMissing(u64::from(index)).into());
+            };
+            let expected = chunk_len(manifest.size(), index).unwrap_or(0);
+            let opened = Zeroizing::new(open(enc_key, &chunk_aad(&row), &chunk)?);
+            if opened.len() != expected {
+                return Err(Error::ChunkSize(index, opened.len(), expected).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Upload {
+    /// The digest of the uploaded value: the tag of its length and its
+    /// chunks' tags, in order.
+    fn digest(&self, enc_key: &[u8]) -> Result<[u8; DIGEST_LEN]> {
+        let mut tags = self.len.to_be_bytes().to_vec();
+        for chunk_tag in self.received.values() {
+            tags.extend_from_slice(chunk_tag);
+        }
+        tag(&digest_key(enc_key)?, &tags)
+    }
+
+    /// The id of the chunks the finished upload `name` is kept in, and the
+    /// writes that keep it there and let go of the chunks of `replaced`: its
+    /// own chunks, unless a value with the same `digest` already has some.
+    fn chunk_writes(
+        &self,
+        db: &dyn StorageBackend,
+        name: &str,
+        enc_key: &[u8],
+        replaced: Option<&SalusVal>,
+        digest: Option<[u8; DIGEST_LEN]>,
+    ) -> Result<([u8; BLOB_ID_LEN], Vec<WriteOp>)> {
+        let release = || ShareStore::release_chunks(db, enc_key, &self.key, replaced);
+        let Some(digest) = digest else {
+            return Ok((self.id, release()?));
+        };
+        let refs_key = hex(&digest);
+        // Replacing a value with itself leaves its count alone.
+        let replaced_same = replaced
+            .and_then(|replaced| ShareStore::manifest_of(enc_key, &self.key, replaced).ok())
+            .and_then(|manifest| manifest.digest)
+            == Some(digest);
+        let mut ops = if replaced_same {
+            Vec::new()
+        } else {
+            release()?
+        };
+        let Some(mut blob_ref) = read_value(db, SALUS_BLOB_REFS_TABLE_DEF, &refs_key)? else {
+            let blob_ref = BlobRef {
+                id: self.id,
+                count: 1,
+            };
+            ops.push(put(SALUS_BLOB_REFS_TABLE_DEF, &refs_key, &blob_ref));
+            return Ok((self.id, ops));
+        };
+        if !replaced_same {
+            blob_ref.count = blob_ref.count.saturating_add(1);
+            ops.push(put(SALUS_BLOB_REFS_TABLE_DEF, &refs_key, &blob_ref));
+        }
+        ops.extend(deletes(db.keys(Table::Blobs, &format!("{name}/"))?));
+        Ok((blob_ref.id, ops))
+    }
+}
+
+impl Manifest {
+    /// The sealed bytes: the id, the length (8 bytes, big-endian), then the
+    /// digest when there is one.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.id.to_vec();
+        bytes.extend_from_slice(&self.len.to_be_bytes());
+        if let Some(digest) = &self.digest {
+            bytes.extend_from_slice(digest);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (id, rest) = bytes.split_first_chunk::<BLOB_ID_LEN>()?;
+        let (len, rest) = rest.split_first_chunk::<8>()?;
+        let digest = if rest.is_empty() {
+            None
+        } else {
+            Some(<[u8; DIGEST_LEN]>::try_from(rest).ok()?)
+        };
+        Some(Self {
+            id: *id,
+            len: u64::from_be_bytes(*len),
+            digest,
+        })
+    }
+}
+
+/// Remove the chunks no `salus_store` row names, returning how many there
+/// were, and recount the rows naming each set of shared chunks.
+///
+/// Uploads in progress are not named by any row, so this runs only before the
+/// daemon takes requests.
+pub(crate) fn sweep_chunks(backend: &Backend) -> Result<usize> {
+    let (mut swept, mut recounted) = (0, 0);
+    unlock_backend(backend, |db: &dyn StorageBackend| -> Result<()> {
+        let mut named = BTreeMap::<String, u64>::new();
+        for (_, row) in db.scan(Table::Values, "")? {
+            if let Ok(Some(id)) = SalusVal::from_raw_bytes(&row).blob_id() {
+                let count = named.entry(hex(&id)).or_default();
+                *count = count.saturating_add(1);
+            }
+        }
+        let mut ops = db
+            .keys(Table::Blobs, "")?
+            .into_iter()
+            .filter(|row| {
+                row.split_once('/')
+                    .is_none_or(|(id, _)| !named.contains_key(id))
+            })
+            .map(|key| WriteOp::Delete {
+                table: Table::Blobs,
+                key,
+            })
+            .collect::<Vec<_>>();
+        swept = ops.len();
+        for (refs_key, row) in db.scan(Table::BlobRefs, "")? {
+            let blob_ref = BlobRef::from_row(&row).ok();
+            let count = blob_ref
+                .and_then(|blob_ref| named.get(&hex(&blob_ref.id)))
+                .copied()
+                .unwrap_or(0);
+            match blob_ref {
+                Some(mut blob_ref) if count > 0 => {
+                    if blob_ref.count != count {
+                        blob_ref.count = count;
+                        ops.push(put(SALUS_BLOB_REFS_TABLE_DEF, &refs_key, &blob_ref));
+                    }
+                }
+                _ => ops.push(WriteOp::Delete {
+                    table: Table::BlobRefs,
+                    key: refs_key,
+                }),
+            }
+        }
+        recounted = ops.len().saturating_sub(swept);
+        if !ops.is_empty() {
+            db.commit(ops)?;
+        }
+        Ok(())
+    })?;
+    if swept > 0 {
+        info!("Removed {swept} chunks no value names");
+    }
+    if recounted > 0 {
+        info!("Recounted the values sharing {recounted} sets of chunks");
+    }
+    Ok(swept)
+}
+
+/// Whether `sealed` is a value stored in chunks.
+fn is_streamed(sealed: Option<&SalusVal>) -> Result<bool> {
+    Ok(sealed
+        .map(SalusVal::blob_id)
+        .transpose()?
+        .flatten()
+        .is_some())
+}
+
+/// The key chunks are tagged under, derived from the store key.
+fn digest_key(enc_key: &[u8]) -> Result<hmac::Key> {
+    let mut key = Zeroizing::new([0u8; DIGEST_LEN]);
+    hkdf::Salt::new(hkdf::HKDF_SHA256, DIGEST_SALT)
+        .extract(enc_key)
+        .expand(&[DIGEST_INFO], KeyLen(DIGEST_LEN))?
+        .fill(key.as_mut_slice())?;
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, key.as_slice()))
+}
+
+/// The HMAC-SHA256 tag of `bytes`.
+fn tag(key: &hmac::Key, bytes: &[u8]) -> Result<[u8; DIGEST_LEN]> {
+    Ok(hmac::sign(key, bytes).as_ref().try_into()?)
+}
+
+fn deletes(rows: Vec<String>) -> Vec<WriteOp> {
+    rows.into_iter()
+        .map(|key| WriteOp::Delete {
+            table: Table::Blobs,
+            key,
+        })
+        .collect()
+}
+
+/// The bytes the chunks stored under `id` take, as sealed.
+pub(super) fn chunk_bytes(db: &dyn StorageBackend, id: &[u8; BLOB_ID_LEN]) -> Result<u64> {
+    Ok(db
+        .scan(Table::Blobs, &format!("{}/", hex(id)))?
+        .iter()
+        .map(|(_, row)| u64::try_from(row.len()).unwrap_or(u64::MAX))
+        .fold(0, u64::saturating_add))
+}
+
+/// The `salus_blobs` row of chunk `index` of the value stored under `id`.
+fn chunk_row(id: &str, index: u32) -> String {
+    format!("{id}/{index:010}")
+}
+
+/// The AAD a chunk is sealed under, so it opens only in its own row.
+fn chunk_aad(row: &str) -> String {
+    format!("blob:{row}")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Whether `id` could name a value's chunks.
+fn is_id(id: &str) -> bool {
+    Some(id.len()) == BLOB_ID_LEN.checked_mul(2)
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use anyhow::{Result, bail};
+    use libsalus::{
+        BeginUpload, CHUNK_SIZE, ReadChunk, Response, StreamedValue, UploadChunk, chunk_count,
+        chunk_len,
+    };
+
+    use super::{Uploads, deletes, sweep_chunks};
+    use crate::{
+        db::{
+            Row as _, SALUS_VAL_TABLE_DEF,
+            backend::{StorageBackend, Table, WriteOp},
+            read_backend, read_value, unlock_backend,
+            values::{blob_ref::BlobRef, salus::SalusVal},
+        },
+        error::Error,
+        store::{ShareStore, test::unlocked_store},
+    };
+
+    fn begin(store: &ShareStore, key: &str, len: u64) -> Result<String> {
+        match store.begin_upload(
+            &BeginUpload::builder()
+                .key(key)
+                .size(len)
+                .force(false)
+                .build(),
+        )? {
+            Response::UploadStarted(upload) => Ok(upload),
+            other => bail!("expected an upload, got {other:?}"),
+        }
+    }
+
+    fn send(store: &ShareStore, upload: &str, value: &[u8]) -> Result<()> {
+        for (index, bytes) in value.chunks(CHUNK_SIZE).enumerate() {
+            let chunk = UploadChunk::builder()
+                .upload(upload)
+                .index(u32::try_from(index)?)
+                .bytes(bytes.to_vec())
+                .build();
+            if !matches!(store.upload_chunk(&chunk)?, Response::Success) {
+                bail!("expected chunk {index} to be stored");
+            }
+        }
+        Ok(())
+    }
+
+    fn upload(store: &ShareStore, key: &str, value: &[u8]) -> Result<()> {
+        upload_with(store, key, value, false)
+    }
+
+    fn upload_with(store: &ShareStore, key: &str, value: &[u8], force: bool) -> Result<()> {
+        let begin = BeginUpload::builder()
+            .key(key)
+            .size(u64::try_from(value.len())?)
+            .force(force)
+            .build();
+        let Response::UploadStarted(upload) = store.begin_upload(&begin)? else {
+            bail!("expected an upload");
+        };
+        send(store, &upload, value)?;
+        match store.finish_upload(&upload)? {
+            Response::Success => Ok(()),
+            other => bail!("expected the upload to finish, got {other:?}"),
+        }
+    }
+
+    fn download(store: &ShareStore, key: &str) -> Result<Vec<u8>> {
+        let Response::Streamed(streamed) = store.read(key)? else {
+            bail!("expected a streamed value");
+        };
+        let mut value = vec![];
+        for index in 0..chunk_count(streamed.size()) {
+            let index = u32::try_from(index)?;
+            let request = ReadChunk::builder().id(streamed.id()).index(index).build();
+            let Response::Chunk(chunk) = store.read_chunk(&request)? else {
+                bail!("expected chunk {index}");
+            };
+            assert_eq!(Some(chunk.len()), chunk_len(streamed.size(), index));
+            value.extend_from_slice(&chunk);
+        }
+        Ok(value)
+    }
+
+    fn chunk_rows(store: &ShareStore) -> Result<usize> {
+        let mut rows = 0;
+        read_backend(&store.backend, |db| -> Result<()> {
+            rows = db.keys(Table::Blobs, "")?.len();
+            Ok(())
+        })?;
+        Ok(rows)
+    }
+
+    fn blob_refs(store: &ShareStore) -> Result<Vec<(String, BlobRef)>> {
+        let mut rows = vec![];
+        read_backend(&store.backend, |db| -> Result<()> {
+            rows = db.scan(Table::BlobRefs, "")?;
+            Ok(())
+        })?;
+        rows.into_iter()
+            .map(|(key, row)| Ok((key, BlobRef::from_row(&row)?)))
+            .collect()
+    }
+
+    fn sealed_row(store: &ShareStore, key: &str) -> Result<Option<SalusVal>> {
+        let mut sealed = None;
+        read_backend(&store.backend, |db| -> Result<()> {
+            sealed = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+            Ok(())
+        })?;
+        Ok(sealed)
+    }
+
+    fn value(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| u8::try_from(i % 251).unwrap_or(0))
+            .collect()
+    }
+
+    #[test]
+    fn an_uploaded_value_reads_back_in_chunks() -> Result<()> {
+        let store = unlocked_store()?;
+        let value = value(CHUNK_SIZE.saturating_mul(2).saturating_add(7));
+        upload(&store, "big", &value)?;
+        assert_eq!(chunk_rows(&store)?, 3);
+        assert_eq!(download(&store, "big")?, value);
+        match store.exists("big")? {
+            Response::KeyStat(stat) => {
+                assert!(stat.chunked());
+                assert!(stat.stored_bytes() > u64::try_from(value.len())?);
+            }
+            other => bail!("expected a stat of 'big', got {other:?}"),
+        }
+        let (Some(enc_key), Some(sealed)) = (&store.key, sealed_row(&store, "big")?) else {
+            bail!("expected an unlocked store holding 'big'");
+        };
+        let Some(error) = store.open_value(enc_key, "big", &sealed).err() else {
+            bail!("a streamed value must not be opened whole");
+        };
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::StreamedValue(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_of_the_wrong_size_or_a_missing_chunk_are_refused() -> Result<()> {
+        let store = unlocked_store()?;
+        let value = value(CHUNK_SIZE.saturating_add(1));
+        let upload = begin(&store, "big", u64::try_from(value.len())?)?;
+        let short = UploadChunk::builder()
+            .upload(&upload)
+            .index(0)
+            .bytes(vec![0; 10])
+            .build();
+        assert!(store.upload_chunk(&short).is_err());
+        let beyond = UploadChunk::builder()
+            .upload(&upload)
+            .index(2)
+            .bytes(vec![0; 1])
+            .build();
+        assert!(store.upload_chunk(&beyond).is_err());
+        let Some(first) = value.get(..CHUNK_SIZE) else {
+            bail!("expected a full chunk");
+        };
+        send(&store, &upload, first)?;
+        let Some(error) = store.finish_upload(&upload).err() else {
+            bail!("an upload with a missing chunk must not finish");
+        };
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::ChunkMissing(1))
+        ));
+        assert!(matches!(store.read("big")?, Response::Value(None)));
+        Ok(())
+    }
+
+    #[test]
+    fn uploads_are_held_to_their_limits() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.uploads = Uploads::new(100, 1, Duration::from_secs(30), true);
+        let too_big = BeginUpload::builder()
+            .key("big")
+            .size(101)
+            .force(false)
+            .build();
+        assert!(matches!(
+            store.begin_upload(&too_big)?,
+            Response::ValueTooLarge(100)
+        ));
+        let _upload = begin(&store, "one", 10)?;
+        assert!(begin(&store, "two", 10).is_err());
+        let unknown = UploadChunk::builder()
+            .upload("nope")
+            .index(0)
+            .bytes(vec![0; 10])
+            .build();
+        assert!(matches!(
+            store.upload_chunk(&unknown)?,
+            Response::UploadNotFound
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn replacing_or_deleting_a_value_removes_its_chunks() -> Result<()> {
+        let store = unlocked_store()?;
+        upload(&store, "big", &value(CHUNK_SIZE.saturating_add(1)))?;
+        let Response::Streamed(first) = store.read("big")? else {
+            bail!("expected a streamed value");
+        };
+        assert_eq!(chunk_rows(&store)?, 2);
+        let again = BeginUpload::builder()
+            .key("big")
+            .size(3)
+            .force(true)
+            .build();
+        let Response::UploadStarted(upload) = store.begin_upload(&again)? else {
+            bail!("expected an upload");
+        };
+        send(&store, &upload, b"abc")?;
+        assert!(matches!(store.finish_upload(&upload)?, Response::Success));
+        assert_eq!(chunk_rows(&store)?, 1);
+        let stale = ReadChunk::builder().id(first.id()).index(0).build();
+        assert!(matches!(store.read_chunk(&stale)?, Response::KeyNotFound));
+        assert_eq!(download(&store, "big")?, b"abc");
+        let _deleted = store.delete("big")?;
+        assert_eq!(chunk_rows(&store)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn equal_values_share_their_chunks_until_the_last_is_deleted() -> Result<()> {
+        let store = unlocked_store()?;
+        let value = value(CHUNK_SIZE.saturating_add(1));
+        upload(&store, "a", &value)?;
+        upload(&store, "b", &value)?;
+        upload_with(&store, "b", &value, true)?;
+        assert_eq!(chunk_rows(&store)?, 2);
+        let (Response::Streamed(a), Response::Streamed(b)) = (store.read("a")?, store.read("b")?)
+        else {
+            bail!("expected streamed values");
+        };
+        assert_eq!(a, b);
+        let refs = blob_refs(&store)?;
+        assert_eq!(refs.len(), 1);
+        assert!(refs.iter().all(|(_, blob_ref)| blob_ref.count == 2));
+        let Response::StoreChecked(report) = store.check_integrity()? else {
+            bail!("expected an integrity report");
+        };
+        assert!(report.problems().is_empty());
+        let _deleted = store.delete("a")?;
+        assert_eq!(chunk_rows(&store)?, 2);
+        assert_eq!(download(&store, "b")?, value);
+        upload_with(&store, "b", b"other", true)?;
+        assert_eq!(chunk_rows(&store)?, 1);
+        assert_eq!(blob_refs(&store)?.len(), 1);
+        let _deleted = store.delete("b")?;
+        assert_eq!(chunk_rows(&store)?, 0);
+        assert!(blob_refs(&store)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn values_sharing_chunks_can_be_deleted_together() -> Result<()> {
+        let store = unlocked_store()?;
+        let value = value(CHUNK_SIZE.saturating_add(1));
+        for key in ["tmp/a", "tmp/b", "keep"] {
+            upload(&store, key, &value)?;
+        }
+        let _deleted = store.delete_prefix("tmp/", false)?;
+        assert_eq!(chunk_rows(&store)?, 2);
+        assert!(blob_refs(&store)?.iter().all(|(_, r)| r.count == 1));
+        assert_eq!(download(&store, "keep")?, value);
+        let _deleted = store.delete_prefix("k", false)?;
+        assert_eq!(chunk_rows(&store)?, 0);
+        assert!(blob_refs(&store)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn without_dedup_equal_values_keep_their_own_chunks() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.uploads = Uploads::new(1024, 1, Duration::from_secs(30), false);
+        upload(&store, "a", b"same")?;
+        upload(&store, "b", b"same")?;
+        assert_eq!(chunk_rows(&store)?, 2);
+        assert!(blob_refs(&store)?.is_empty());
+        let _deleted = store.delete("a")?;
+        assert_eq!(download(&store, "b")?, b"same");
+        Ok(())
+    }
+
+    #[test]
+    fn a_missing_chunk_fails_the_integrity_check() -> Result<()> {
+        let store = unlocked_store()?;
+        upload(&store, "big", &value(CHUNK_SIZE.saturating_add(1)))?;
+        let Response::StoreChecked(report) = store.check_integrity()? else {
+            bail!("expected an integrity report");
+        };
+        assert!(report.problems().is_empty());
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            let rows = db.keys(Table::Blobs, "")?;
+            db.commit(deletes(rows.into_iter().skip(1).collect()))
+        })?;
+        let Response::StoreChecked(report) = store.check_integrity()? else {
+            bail!("expected an integrity report");
+        };
+        assert_eq!(report.problems().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn unfinished_uploads_are_swept() -> Result<()> {
+        let store = unlocked_store()?;
+        upload(&store, "kept", b"kept")?;
+        let upload = begin(&store, "dropped", 4)?;
+        send(&store, &upload, b"lost")?;
+        assert_eq!(chunk_rows(&store)?, 2);
+        assert_eq!(sweep_chunks(&store.backend)?, 1);
+        let Response::Streamed(kept) = store.read("kept")? else {
+            bail!("expected a streamed value");
+        };
+        assert_eq!(kept, StreamedValue::builder().id(kept.id()).size(4).build());
+        assert_eq!(download(&store, "kept")?, b"kept");
+        Ok(())
+    }
+
+    #[test]
+    fn the_sweep_recounts_shared_chunks() -> Result<()> {
+        let store = unlocked_store()?;
+        upload(&store, "a", b"same")?;
+        upload(&store, "b", b"same")?;
+        let Some((refs_key, mut blob_ref)) = blob_refs(&store)?.into_iter().next() else {
+            bail!("expected the chunks to be shared");
+        };
+        blob_ref.count = 5;
+        let stale = BlobRef {
+            id: [9; 16],
+            count: 1,
+        };
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            db.commit(vec![
+                WriteOp::Put {
+                    table: Table::BlobRefs,
+                    key: refs_key.clone(),
+                    value: blob_ref.to_row(),
+                },
+                WriteOp::Put {
+                    table: Table::BlobRefs,
+                    key: "stale".to_string(),
+                    value: stale.to_row(),
+                },
+                WriteOp::Delete {
+                    table: Table::Values,
+                    key: "a".to_string(),
+                },
+            ])
+        })?;
+        assert_eq!(sweep_chunks(&store.backend)?, 0);
+        let refs = blob_refs(&store)?;
+        assert_eq!(refs.len(), 1);
+        assert!(refs.iter().all(|(_, blob_ref)| blob_ref.count == 1));
+        let _deleted = store.delete("b")?;
+        assert_eq!(chunk_rows(&store)?, 0);
+        Ok(())
+    }
+}
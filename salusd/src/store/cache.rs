@@ -0,0 +1,177 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! An optional cache of decrypted values, for readers that ask for the same
+//! keys over and over.
+//!
+//! Off unless `[read_cache] capacity` is set. Entries live for the configured
+//! TTL at most, the least recently read go first once the cache is full, and a
+//! store or delete of a key drops its entry. Locking the store, by hand or by
+//! the key timeout, empties the cache, so no plaintext outlives the key.
+//!
+//! A write from outside this daemon (another daemon sharing an object-store
+//! bucket) is only seen once the entry expires.
+
+use std::{
+    num::NonZeroUsize,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use zeroize::Zeroizing;
+
+/// Decrypted values, by key. Deliberately not `Debug`.
+#[derive(Default)]
+pub(crate) struct ReadCache {
+    /// `None` when the cache is off.
+    inner: Option<Mutex<Entries>>,
+}
+
+struct Entries {
+    values: LruCache<String, Entry>,
+    ttl: Duration,
+    /// Bumped by every invalidation, so a read that raced a write does not
+    /// cache the value it decrypted before the write.
+    epoch: u64,
+}
+
+struct Entry {
+    value: Zeroizing<Vec<u8>>,
+    expires_at: Instant,
+}
+
+impl ReadCache {
+    /// A cache of up to `capacity` values, each kept for up to `ttl`; off when
+    /// either is zero.
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        let inner = NonZeroUsize::new(capacity)
+            .filter(|_| !ttl.is_zero())
+            .map(|capacity| {
+                Mutex::new(Entries {
+                    values: LruCache::new(capacity),
+                    ttl,
+                    epoch: 0,
+                })
+            });
+        Self { inner }
+    }
+
+    /// The cached value of `key`, if there is a live one.
+    pub(crate) fn get(&self, key: &str) -> Option<Zeroizing<Vec<u8>>> {
+        let mut entries = self.entries()?;
+        let entry = entries.values.get(key)?;
+        if entry.expires_at > Instant::now() {
+            return Some(entry.value.clone());
+        }
+        let _expired = entries.values.pop(key);
+        None
+    }
+
+    /// The invalidation count to hand to [`ReadCache::insert`], taken before
+    /// the value is read.
+    pub(crate) fn epoch(&self) -> u64 {
+        self.entries().map_or(0, |entries| entries.epoch)
+    }
+
+    /// Cache `value` under `key`, unless the cache was invalidated since
+    /// `epoch` was taken.
+    pub(crate) fn insert(&self, epoch: u64, key: &str, value: &[u8]) {
+        let Some(mut entries) = self.entries() else {
+            return;
+        };
+        if entries.epoch != epoch {
+            return;
+        }
+        let Some(expires_at) = Instant::now().checked_add(entries.ttl) else {
+            return;
+        };
+        let entry = Entry {
+            value: Zeroizing::new(value.to_vec()),
+            expires_at,
+        };
+        let _evicted = entries.values.put(key.to_string(), entry);
+    }
+
+    /// Drop the entries of `keys`.
+    pub(crate) fn invalidate<'k>(&self, keys: impl IntoIterator<Item = &'k str>) {
+        if let Some(mut entries) = self.entries() {
+            entries.epoch = entries.epoch.wrapping_add(1);
+            for key in keys {
+                let _dropped = entries.values.pop(key);
+            }
+        }
+    }
+
+    /// Drop every entry.
+    pub(crate) fn clear(&self) {
+        if let Some(mut entries) = self.entries() {
+            entries.epoch = entries.epoch.wrapping_add(1);
+            entries.values.clear();
+        }
+    }
+
+    fn entries(&self) -> Option<MutexGuard<'_, Entries>> {
+        self.inner.as_ref().map(|inner| match inner.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use super::ReadCache;
+
+    #[test]
+    fn off_by_default() {
+        let cache = ReadCache::default();
+        cache.insert(cache.epoch(), "db/pass", b"hunter2");
+        assert!(cache.get("db/pass").is_none());
+        assert!(
+            ReadCache::new(0, Duration::from_secs(30))
+                .get("db/pass")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn entries_expire_evict_and_invalidate() {
+        let cache = ReadCache::new(2, Duration::from_millis(500));
+        cache.insert(cache.epoch(), "a", b"1");
+        cache.insert(cache.epoch(), "b", b"2");
+        assert_eq!(
+            cache.get("a").as_deref().map(Vec::as_slice),
+            Some(&b"1"[..])
+        );
+        // "b" is now the least recently read.
+        cache.insert(cache.epoch(), "c", b"3");
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+
+        cache.invalidate(["c"]);
+        assert!(cache.get("c").is_none());
+        assert!(cache.get("a").is_some());
+
+        thread::sleep(Duration::from_millis(600));
+        assert!(cache.get("a").is_none());
+    }
+
+    #[test]
+    fn a_read_that_raced_a_write_is_not_cached() {
+        let cache = ReadCache::new(4, Duration::from_secs(30));
+        let epoch = cache.epoch();
+        cache.invalidate(["a"]);
+        cache.insert(epoch, "a", b"stale");
+        assert!(cache.get("a").is_none());
+        cache.insert(cache.epoch(), "a", b"fresh");
+        assert!(cache.get("a").is_some());
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Optional zstd compression of values before they are sealed.
+//!
+//! Off unless `[compression] threshold` is set. A value at least that long is
+//! compressed, and kept compressed only if that makes it smaller; its row is
+//! flagged (see [`SalusVal`](crate::db::values::salus::SalusVal)) and sealed
+//! under [`compressed_aad`], so the flag cannot be added or stripped without
+//! the value failing to open. Reads decompress transparently.
+//!
+//! Compressing before encrypting lets a value's stored length depend on its
+//! contents. Someone who can both plant text in a value and watch the size of
+//! its row can learn the rest of it a guess at a time, so leave compression
+//! off for values that mix secrets with input from elsewhere.
+
+use std::{ops::RangeInclusive, sync::LazyLock};
+
+use anyhow::{Result, anyhow};
+use libsalus::MAX_MESSAGE_SIZE;
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+
+/// The documented default for `[compression] level`.
+pub(crate) const DEFAULT_LEVEL: i32 = 3;
+
+/// The levels zstd accepts.
+static LEVELS: LazyLock<RangeInclusive<i32>> = LazyLock::new(zstd::compression_level_range);
+
+/// When, and how hard, values are compressed before they are sealed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Compression {
+    /// The shortest value to compress; 0 turns compression off.
+    threshold: usize,
+    level: i32,
+}
+
+impl Compression {
+    /// Compress values of at least `threshold` bytes at zstd `level`; off when
+    /// `threshold` is zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CompressionLevel`] for a level zstd does not accept.
+    pub(crate) fn new(threshold: usize, level: i32) -> Result<Self> {
+        if !LEVELS.contains(&level) {
+            return Err(Error::CompressionLevel(level, *LEVELS.start(), *LEVELS.end()).into());
+        }
+        Ok(Self { threshold, level })
+    }
+
+    /// `value` compressed, if it is long enough to try and compressing it
+    /// saves space.
+    pub(crate) fn compress(&self, value: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.threshold == 0 || value.len() < self.threshold {
+            return Ok(None);
+        }
+        let compressed = zstd::bulk::compress(value, self.level)?;
+        Ok((compressed.len() < value.len()).then_some(compressed))
+    }
+}
+
+/// Decompress a value [`Compression::compress`] shrank.
+///
+/// A value is never stored larger than a message can carry, so anything that
+/// would decompress past [`MAX_MESSAGE_SIZE`] is refused rather than expanded.
+pub(crate) fn decompress(compressed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    zstd::bulk::decompress(compressed, MAX_MESSAGE_SIZE)
+        .map(Zeroizing::new)
+        .map_err(|e| anyhow!("the value does not decompress: {e}"))
+}
+
+/// The AAD a compressed value is sealed under, so it opens only as one.
+pub(crate) fn compressed_aad(key: &str) -> String {
+    format!("zstd:{key}")
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::{Compression, DEFAULT_LEVEL, decompress};
+
+    #[test]
+    fn only_long_compressible_values_are_compressed() -> Result<()> {
+        let pem = "-----BEGIN CERTIFICATE-----\n".repeat(40);
+        assert_eq!(Compression::default().compress(pem.as_bytes())?, None);
+        let compression = Compression::new(64, DEFAULT_LEVEL)?;
+        assert_eq!(compression.compress(b"short")?, None);
+        let noise = (0u8..=255).collect::<Vec<_>>();
+        assert_eq!(compression.compress(&noise)?, None);
+        let Some(compressed) = compression.compress(pem.as_bytes())? else {
+            bail!("expected a repetitive value to compress");
+        };
+        assert!(compressed.len() < pem.len());
+        assert_eq!(decompress(&compressed)?.as_slice(), pem.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn levels_zstd_refuses_are_refused() {
+        assert!(Compression::new(64, 1000).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Data keys for envelope encryption.
+//!
+//! `GenerateDataKey` hands out a random key together with the same key sealed
+//! under the store key, so an application can encrypt locally, keep only the
+//! wrapped key beside its data, and ask the daemon to unwrap it later. Nothing
+//! is written to the database. The store key survives share refreshes, so a
+//! wrapped data key stays usable across them.
+
+use anyhow::Result;
+use aws_lc_rs::rand;
+use libsalus::{DataKey, MAX_DATA_KEY_BITS, MIN_DATA_KEY_BITS, Response};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::{ShareStore, open, seal};
+use crate::{
+    db::{Row as _, values::salus::SalusVal},
+    error::Error,
+};
+
+/// The AAD binding a wrapped data key to its purpose.
+const DATA_KEY_AAD: &str = "DATA_KEY";
+/// The wrapped-key layout this version writes: the version byte, then the
+/// nonce and ciphertext.
+const DATA_KEY_VERSION: u8 = 1;
+
+impl ShareStore {
+    /// Generate a `bits`-bit data key, returning it in the clear and wrapped.
+    ///
+    /// `bits` must be a whole number of bytes from [`MIN_DATA_KEY_BITS`] to
+    /// [`MAX_DATA_KEY_BITS`].
+    pub(crate) fn generate_data_key(&self, bits: u16) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if !(MIN_DATA_KEY_BITS..=MAX_DATA_KEY_BITS).contains(&bits) || !bits.is_multiple_of(8) {
+            return Ok(Response::InvalidDataKeyBits(bits));
+        }
+        let mut plaintext = Zeroizing::new(vec![0u8; usize::from(bits / 8)]);
+        rand::fill(&mut plaintext)?;
+        let mut sealed = plaintext.to_vec();
+        let wrapped = seal(enc_key, DATA_KEY_AAD, &mut sealed)?;
+        let mut ciphertext = vec![DATA_KEY_VERSION];
+        ciphertext.extend_from_slice(&wrapped.to_row());
+        info!(target: "salusd::audit", bits, "Data key generated");
+        Ok(Response::DataKey(
+            DataKey::builder()
+                .plaintext(plaintext.to_vec())
+                .ciphertext(ciphertext)
+                .build(),
+        ))
+    }
+
+    /// Unwrap a data key produced by [`ShareStore::generate_data_key`].
+    pub(crate) fn decrypt_data_key(&self, ciphertext: &[u8]) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let Some((&DATA_KEY_VERSION, wrapped)) = ciphertext.split_first() else {
+            return Ok(Response::InvalidDataKey);
+        };
+        // A key wrapped by another store, or tampered with, fails to open.
+        let Ok(plaintext) = open(enc_key, DATA_KEY_AAD, &SalusVal::from_raw_bytes(wrapped)) else {
+            info!("Refusing to unwrap a data key this store did not wrap");
+            return Ok(Response::InvalidDataKey);
+        };
+        info!(target: "salusd::audit", "Data key decrypted");
+        Ok(Response::DataKeyPlaintext(plaintext))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::Response;
+
+    use super::super::test::unlocked_store;
+
+    #[test]
+    fn data_keys_unwrap_only_in_their_store() -> Result<()> {
+        let store = unlocked_store()?;
+        let Response::DataKey(data_key) = store.generate_data_key(256)? else {
+            bail!("expected a data key");
+        };
+        assert_eq!(data_key.plaintext().len(), 32);
+        assert!(matches!(
+            store.decrypt_data_key(data_key.ciphertext())?,
+            Response::DataKeyPlaintext(ref plaintext) if plaintext == data_key.plaintext()
+        ));
+
+        let mut tampered = data_key.ciphertext().clone();
+        if let Some(last) = tampered.last_mut() {
+            *last ^= 1;
+        }
+        assert!(matches!(
+            store.decrypt_data_key(&tampered)?,
+            Response::InvalidDataKey
+        ));
+        assert!(matches!(
+            unlocked_store()?.decrypt_data_key(data_key.ciphertext())?,
+            Response::InvalidDataKey
+        ));
+        assert!(matches!(
+            store.decrypt_data_key(&[])?,
+            Response::InvalidDataKey
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn data_key_sizes_are_bounded() -> Result<()> {
+        let store = unlocked_store()?;
+        for bits in [0, 64, 130, 1024] {
+            assert!(matches!(
+                store.generate_data_key(bits)?,
+                Response::InvalidDataKeyBits(refused) if refused == bits
+            ));
+        }
+        assert!(matches!(
+            store.generate_data_key(128)?,
+            Response::DataKey(ref key) if key.plaintext().len() == 16
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Encryption as a service: values encrypted and decrypted under a per-context
+//! key without being stored.
+//!
+//! Each context's key is derived from the store key with HKDF, so nothing is
+//! written to the database and the keys survive share refreshes. Values are
+//! sealed with AES-256-GCM-SIV. A randomized ciphertext uses a random nonce;
+//! a deterministic one uses a synthetic nonce, an HMAC-SHA256 of the value
+//! under a second per-context key, so equal values give equal ciphertexts and
+//! can be matched without decrypting. That leaks equality, so it is opt-in
+//! and flagged in the ciphertext's first byte.
+//!
+//! Under a named key, the context keys are derived from the key's newest
+//! version instead, and the ciphertext records the key's name and version
+//! after the first byte so it still decrypts after the key is rotated.
+
+use anyhow::{Result, bail};
+use aws_lc_rs::{
+    aead::{AES_256_GCM_SIV, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    constant_time, hkdf, hmac, rand,
+};
+use libsalus::{DecryptRequest, EncryptRequest, Response};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::{KeyLen, ShareStore};
+use crate::error::Error;
+
+/// The first byte of a randomized ciphertext.
+const RANDOMIZED: u8 = 1;
+/// The first byte of a deterministic ciphertext.
+const DETERMINISTIC: u8 = 2;
+/// The first byte of a randomized ciphertext under a named key.
+const RANDOMIZED_NAMED: u8 = 3;
+/// The first byte of a deterministic ciphertext under a named key.
+const DETERMINISTIC_NAMED: u8 = 4;
+/// The HKDF salt for context keys.
+const CONTEXT_SALT: &[u8] = b"salus encrypt";
+/// The HKDF info prefix for context keys, followed by the key's purpose and
+/// the context.
+const CONTEXT_INFO: &[u8] = b"salus encrypt v1";
+/// The length of each derived key, in bytes.
+const CONTEXT_KEY_LEN: usize = 32;
+
+impl ShareStore {
+    /// Encrypt a value under its context's key.
+    pub(crate) fn encrypt(&self, request: &EncryptRequest) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let context = request.context();
+        let deterministic = request.deterministic();
+        // The first byte, and for a named key its name and version, which
+        // together head the ciphertext and its AAD.
+        let (base_key, mut ciphertext) = if let Some(name) = request.key() {
+            let Some((version, material)) = self.named_key_for_write(enc_key, name)? else {
+                return Ok(Response::NamedKeyNotFound(name.clone()));
+            };
+            let mode = if deterministic {
+                DETERMINISTIC_NAMED
+            } else {
+                RANDOMIZED_NAMED
+            };
+            (material, named_header(mode, name, version)?)
+        } else {
+            let mode = if deterministic {
+                DETERMINISTIC
+            } else {
+                RANDOMIZED
+            };
+            (enc_key.clone(), vec![mode])
+        };
+        let nonce = if deterministic {
+            let mac_key = context_key(&base_key, b"siv", context)?;
+            synthetic_nonce(&mac_key, request.plaintext())?
+        } else {
+            let mut nonce = [0u8; NONCE_LEN];
+            rand::fill(&mut nonce)?;
+            nonce
+        };
+        let key = aead_key(&context_key(&base_key, b"enc", context)?)?;
+        let aad = aad(&ciphertext, context);
+        ciphertext.extend_from_slice(&nonce);
+        let mut sealed = Zeroizing::new(request.plaintext().clone());
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(aad),
+            &mut *sealed,
+        )?;
+        ciphertext.extend_from_slice(&sealed);
+        info!(
+            target: "salusd::audit",
+            context = context.as_str(), deterministic, key = request.key().as_deref(), "Value encrypted"
+        );
+        Ok(Response::Ciphertext(ciphertext))
+    }
+
+    /// Decrypt a value produced by [`ShareStore::encrypt`] under the same
+    /// context.
+    pub(crate) fn decrypt(&self, request: &DecryptRequest) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let context = request.context();
+        let ciphertext = request.ciphertext();
+        let Some((&mode, rest)) = ciphertext.split_first() else {
+            return Ok(Response::InvalidCiphertext);
+        };
+        let (base_key, rest) = match mode {
+            RANDOMIZED | DETERMINISTIC => (enc_key.clone(), rest),
+            RANDOMIZED_NAMED | DETERMINISTIC_NAMED => {
+                let Some((name, version, rest)) = split_named(rest) else {
+                    return Ok(Response::InvalidCiphertext);
+                };
+                let Some(material) = self.named_key_version(enc_key, name, version)? else {
+                    info!("Refusing a ciphertext under missing named key '{name}' v{version}");
+                    return Ok(Response::NamedKeyNotFound(name.to_string()));
+                };
+                (material, rest)
+            }
+            _ => return Ok(Response::InvalidCiphertext),
+        };
+        let header_len = ciphertext.len().saturating_sub(rest.len());
+        let Some((header, _)) = ciphertext.split_at_checked(header_len) else {
+            return Ok(Response::InvalidCiphertext);
+        };
+        let Some((nonce, sealed)) = rest.split_at_checked(NONCE_LEN) else {
+            return Ok(Response::InvalidCiphertext);
+        };
+        let key = aead_key(&context_key(&base_key, b"enc", context)?)?;
+        let mut plaintext = Zeroizing::new(sealed.to_vec());
+        let Ok(opened) = key.open_in_place(
+            Nonce::try_assume_unique_for_key(nonce)?,
+            Aad::from(aad(header, context)),
+            &mut plaintext,
+        ) else {
+            info!("Refusing a ciphertext this store did not encrypt under '{context}'");
+            return Ok(Response::InvalidCiphertext);
+        };
+        let len = opened.len();
+        plaintext.truncate(len);
+        // A deterministic nonce must be the one the value itself yields.
+        if mode == DETERMINISTIC || mode == DETERMINISTIC_NAMED {
+            let mac_key = context_key(&base_key, b"siv", context)?;
+            let expected = synthetic_nonce(&mac_key, &plaintext)?;
+            if constant_time::verify_slices_are_equal(&expected, nonce).is_err() {
+                return Ok(Response::InvalidCiphertext);
+            }
+        }
+        info!(target: "salusd::audit", context = context.as_str(), "Value decrypted");
+        Ok(Response::Plaintext(plaintext.to_vec()))
+    }
+}
+
+/// The `purpose` key for `context`, derived from the store key.
+fn context_key(enc_key: &[u8], purpose: &[u8], context: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let mut key = Zeroizing::new(vec![0u8; CONTEXT_KEY_LEN]);
+    hkdf::Salt::new(hkdf::HKDF_SHA256, CONTEXT_SALT)
+        .extract(enc_key)
+        .expand(
+            &[CONTEXT_INFO, purpose, context.as_bytes()],
+            KeyLen(CONTEXT_KEY_LEN),
+        )?
+        .fill(&mut key)?;
+    Ok(key)
+}
+
+/// The nonce a deterministic ciphertext of `plaintext` uses: the leading bytes
+/// of its HMAC-SHA256.
+fn synthetic_nonce(mac_key: &[u8], plaintext: &[u8]) -> Result<[u8; NONCE_LEN]> {
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, mac_key), plaintext);
+    let Some(nonce) = tag.as_ref().get(..NONCE_LEN) else {
+        bail!("an HMAC-SHA256 tag is shorter than a nonce");
+    };
+    Ok(nonce.try_into()?)
+}
+
+/// The head of a ciphertext under a named key: its first byte, then the key's
+/// name (length-prefixed) and version.
+fn named_header(mode: u8, name: &str, version: u32) -> Result<Vec<u8>> {
+    let mut header = vec![mode, u8::try_from(name.len())?];
+    header.extend_from_slice(name.as_bytes());
+    header.extend_from_slice(&version.to_be_bytes());
+    Ok(header)
+}
+
+/// Split the key name and version off the rest of a named-key ciphertext.
+fn split_named(rest: &[u8]) -> Option<(&str, u32, &[u8])> {
+    let (&name_len, rest) = rest.split_first()?;
+    let (name, rest) = rest.split_at_checked(usize::from(name_len))?;
+    let (version, rest) = rest.split_first_chunk::<4>()?;
+    Some((
+        str::from_utf8(name).ok()?,
+        u32::from_be_bytes(*version),
+        rest,
+    ))
+}
+
+/// The AAD binding a ciphertext to its head (mode, and any named key) and
+/// context.
+fn aad(header: &[u8], context: &str) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend_from_slice(context.as_bytes());
+    aad
+}
+
+fn aead_key(key: &[u8]) -> Result<LessSafeKey> {
+    Ok(LessSafeKey::new(UnboundKey::new(&AES_256_GCM_SIV, key)?))
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{DecryptRequest, EncryptRequest, NewNamedKey, Response};
+
+    use super::super::{ShareStore, test::unlocked_store};
+
+    fn encrypt(
+        store: &ShareStore,
+        context: &str,
+        value: &[u8],
+        deterministic: bool,
+    ) -> Result<Vec<u8>> {
+        let request = EncryptRequest::builder()
+            .context(context)
+            .plaintext(value.to_vec())
+            .deterministic(deterministic)
+            .build();
+        let Response::Ciphertext(ciphertext) = store.encrypt(&request)? else {
+            bail!("expected a ciphertext");
+        };
+        Ok(ciphertext)
+    }
+
+    fn decrypt(store: &ShareStore, context: &str, ciphertext: &[u8]) -> Result<Response> {
+        let request = DecryptRequest::builder()
+            .context(context)
+            .ciphertext(ciphertext.to_vec())
+            .build();
+        store.decrypt(&request)
+    }
+
+    #[test]
+    fn deterministic_ciphertexts_match_only_for_equal_values() -> Result<()> {
+        let store = unlocked_store()?;
+        let first = encrypt(&store, "users/email", b"a@example.com", true)?;
+        assert_eq!(
+            first,
+            encrypt(&store, "users/email", b"a@example.com", true)?
+        );
+        assert_ne!(
+            first,
+            encrypt(&store, "users/email", b"b@example.com", true)?
+        );
+        assert_ne!(
+            first,
+            encrypt(&store, "users/phone", b"a@example.com", true)?
+        );
+        assert_eq!(first.first(), Some(&2));
+        assert!(matches!(
+            decrypt(&store, "users/email", &first)?,
+            Response::Plaintext(ref value) if value == b"a@example.com"
+        ));
+        assert!(matches!(
+            decrypt(&store, "users/phone", &first)?,
+            Response::InvalidCiphertext
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn named_key_ciphertexts_decrypt_after_rotation() -> Result<()> {
+        let store = unlocked_store()?;
+        let encrypt = |deterministic| {
+            store.encrypt(
+                &EncryptRequest::builder()
+                    .context("billing")
+                    .plaintext(b"4111".to_vec())
+                    .deterministic(deterministic)
+                    .key("app-a")
+                    .build(),
+            )
+        };
+        assert!(matches!(
+            encrypt(false)?,
+            Response::NamedKeyNotFound(ref name) if name == "app-a"
+        ));
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app-a").build())?;
+        let Response::Ciphertext(first) = encrypt(true)? else {
+            bail!("expected a ciphertext");
+        };
+        assert_eq!(first.first(), Some(&4));
+        // Not the store key's ciphertext for the same value and context.
+        assert_ne!(first, self::encrypt(&store, "billing", b"4111", true)?);
+        let _rotated = store.rotate_named_key("app-a")?;
+        let Response::Ciphertext(second) = encrypt(true)? else {
+            bail!("expected a ciphertext");
+        };
+        assert_ne!(first, second);
+        for ciphertext in [&first, &second] {
+            assert!(matches!(
+                decrypt(&store, "billing", ciphertext)?,
+                Response::Plaintext(ref value) if value == b"4111"
+            ));
+        }
+        // The name and version are authenticated: relabel it as version 1.
+        let mut relabelled = second.clone();
+        if let Some(version) = relabelled.get_mut(10) {
+            *version = 1;
+        }
+        assert!(matches!(
+            decrypt(&store, "billing", &relabelled)?,
+            Response::InvalidCiphertext
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn randomized_ciphertexts_differ_and_tampering_is_caught() -> Result<()> {
+        let store = unlocked_store()?;
+        let first = encrypt(&store, "notes", b"hello", false)?;
+        assert_ne!(first, encrypt(&store, "notes", b"hello", false)?);
+        assert!(matches!(
+            decrypt(&store, "notes", &first)?,
+            Response::Plaintext(ref value) if value == b"hello"
+        ));
+        // Relabelling a randomized ciphertext as deterministic fails the AAD.
+        let mut relabelled = first.clone();
+        if let Some(mode) = relabelled.first_mut() {
+            *mode = 2;
+        }
+        assert!(matches!(
+            decrypt(&store, "notes", &relabelled)?,
+            Response::InvalidCiphertext
+        ));
+        assert!(matches!(
+            decrypt(&store, "notes", &[1, 2, 3])?,
+            Response::InvalidCiphertext
+        ));
+        assert!(matches!(
+            decrypt(&unlocked_store()?, "notes", &first)?,
+            Response::InvalidCiphertext
+        ));
+        Ok(())
+    }
+}
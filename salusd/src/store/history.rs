@@ -0,0 +1,523 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Value history: the versions a value had before its current one, and what
+//! changed between two of them.
+//!
+//! Every write of a value records its version, its write time and, when the
+//! daemon knows it, the uid of the user who wrote it. With `[history] keep`
+//! set, the write also keeps the value it replaces in `salus_history`, sealed
+//! as it was stored, with that version's record, and drops the oldest beyond
+//! `keep`. Deleting a value drops its history; a backup copies it with every
+//! other table. A value stored in chunks is not kept, since its chunks go when
+//! it is replaced.
+
+use std::{cell::Cell, collections::BTreeSet};
+
+use anyhow::Result;
+use libsalus::{DiffRequest, FieldChange, FieldChangeKind, Response, ValueVersion};
+use serde_json::Value;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::{ShareStore, alias};
+use crate::{
+    db::{
+        SALUS_AUTHORS_TABLE_DEF, SALUS_HISTORY_TABLE_DEF, SALUS_VERSIONS_TABLE_DEF,
+        SALUS_WRITTEN_TABLE_DEF,
+        backend::{StorageBackend, Table, WriteOp},
+        put, read_backend, read_value, scan_keys, scan_values,
+        values::{revision::Revision, salus::SalusVal},
+    },
+    error::Error,
+};
+
+/// The digits of the version in a `salus_history` key, zero-padded so the
+/// versions of a key sort in order.
+const VERSION_DIGITS: usize = 20;
+
+thread_local! {
+    /// The user the store call running on this thread writes for.
+    static AUTHOR: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Run `f`, recording the user `uid` as the author of the values it writes.
+pub(crate) fn written_by<T>(uid: Option<u32>, f: impl FnOnce() -> T) -> T {
+    let outer = AUTHOR.replace(uid);
+    let value = f();
+    AUTHOR.set(outer);
+    value
+}
+
+impl ShareStore {
+    /// The writes recording a new version of the value under `key`, which
+    /// replaces `existing`, and that version's number.
+    ///
+    /// The version's write time is `written`, and its author the user the
+    /// calling thread writes for. `existing` is kept for history when
+    /// versions are kept, and the oldest kept beyond `[history] keep` go.
+    pub(super) fn revised(
+        &self,
+        db: &dyn StorageBackend,
+        key: &str,
+        existing: Option<&SalusVal>,
+        written: Option<u64>,
+    ) -> Result<(Vec<WriteOp>, u64)> {
+        let current = read_value(db, SALUS_VERSIONS_TABLE_DEF, key)?.unwrap_or(0);
+        let version = current.saturating_add(1);
+        let mut ops = vec![put(SALUS_VERSIONS_TABLE_DEF, key, &version)];
+        ops.push(match written {
+            Some(written) => put(SALUS_WRITTEN_TABLE_DEF, key, &written),
+            None => WriteOp::Delete {
+                table: Table::Written,
+                key: key.to_string(),
+            },
+        });
+        ops.push(match AUTHOR.get() {
+            Some(uid) => put(SALUS_AUTHORS_TABLE_DEF, key, &uid),
+            None => WriteOp::Delete {
+                table: Table::Authors,
+                key: key.to_string(),
+            },
+        });
+
+        let mut kept = revision_keys(db, key)?;
+        if self.keep_versions > 0
+            && current > 0
+            && let Some(existing) = existing
+            && existing.blob_id()?.is_none()
+        {
+            let revision = Revision {
+                version: current,
+                written: read_value(db, SALUS_WRITTEN_TABLE_DEF, key)?,
+                author: read_value(db, SALUS_AUTHORS_TABLE_DEF, key)?,
+                sealed: existing.clone(),
+            };
+            let row = revision_key(key, current);
+            ops.push(put(SALUS_HISTORY_TABLE_DEF, &row, &revision));
+            kept.push(row);
+        }
+        let excess = kept
+            .len()
+            .saturating_sub(usize::try_from(self.keep_versions)?);
+        ops.extend(kept.into_iter().take(excess).map(|row| WriteOp::Delete {
+            table: Table::History,
+            key: row,
+        }));
+        Ok((ops, version))
+    }
+
+    /// List the versions of the value under `key` kept for history, oldest
+    /// first and the current one last, following its aliases. The values are
+    /// not opened, so the store need not be unlocked.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the aliases loop or go too deep, or the database
+    ///   cannot be read.
+    pub(crate) fn history(&self, key: &str) -> Result<Response> {
+        let mut response = Response::KeyNotFound;
+        read_backend(&self.backend, |db| -> Result<()> {
+            let (path, Some(_)) = alias::chain(db, key)? else {
+                return Ok(());
+            };
+            let stored = path.last().map_or(key, String::as_str);
+            let mut versions = revisions(db, stored)?
+                .into_iter()
+                .map(|revision| {
+                    ValueVersion::builder()
+                        .version(revision.version)
+                        .maybe_written(revision.written)
+                        .maybe_author(revision.author)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            versions.push(
+                ValueVersion::builder()
+                    .version(read_value(db, SALUS_VERSIONS_TABLE_DEF, stored)?.unwrap_or(0))
+                    .maybe_written(read_value(db, SALUS_WRITTEN_TABLE_DEF, stored)?)
+                    .maybe_author(read_value(db, SALUS_AUTHORS_TABLE_DEF, stored)?)
+                    .current(true)
+                    .build(),
+            );
+            response = Response::History(versions);
+            Ok(())
+        })?;
+        Ok(response)
+    }
+
+    /// Compare two versions of the value under `key`, following its aliases:
+    /// field by field when both are JSON documents, otherwise as a whole. The
+    /// changed fields' values are left out unless the request shows them.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, either version is stored in
+    ///   chunks or cannot be opened, or the database cannot be read.
+    pub(crate) fn diff(&self, request: &DiffRequest) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let key = request.key().as_str();
+        let mut found = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            let (path, Some(current)) = alias::chain(db, key)? else {
+                return Ok(());
+            };
+            let stored = path.last().map_or(key, String::as_str).to_string();
+            let current_version = read_value(db, SALUS_VERSIONS_TABLE_DEF, &stored)?.unwrap_or(0);
+            let sealed = |version: u64| -> Result<Option<SalusVal>> {
+                if version == current_version {
+                    return Ok(Some(current.clone()));
+                }
+                let row = revision_key(&stored, version);
+                Ok(read_value(db, SALUS_HISTORY_TABLE_DEF, &row)?.map(|revision| revision.sealed))
+            };
+            let (from, to) = (sealed(request.from())?, sealed(request.to())?);
+            found = Some((stored, from, to));
+            Ok(())
+        })?;
+        let Some((stored, from, to)) = found else {
+            return Ok(Response::KeyNotFound);
+        };
+        let (from, to) = match (from, to) {
+            (Some(from), Some(to)) => (from, to),
+            (None, _) => return Ok(Response::VersionNotFound(request.from())),
+            (_, None) => return Ok(Response::VersionNotFound(request.to())),
+        };
+        let before = Zeroizing::new(self.open_value(enc_key, &stored, &from)?);
+        let after = Zeroizing::new(self.open_value(enc_key, &stored, &to)?);
+        info!(
+            from = request.from(),
+            to = request.to(),
+            show_values = request.show_values(),
+            "Compared two versions of the value under key: {stored}"
+        );
+        Ok(Response::Diff(field_changes(
+            &before,
+            &after,
+            request.show_values(),
+        )))
+    }
+}
+
+/// The `salus_history` key of version `version` of the value under `key`.
+fn revision_key(key: &str, version: u64) -> String {
+    format!("{key}\0{version:0VERSION_DIGITS$}")
+}
+
+/// The `salus_history` keys of the value under `key`, oldest first.
+///
+/// Keys may hold any character, so a row under the key's prefix is only one
+/// of its versions when just the version follows.
+pub(super) fn revision_keys(db: &dyn StorageBackend, key: &str) -> Result<Vec<String>> {
+    let prefix = format!("{key}\0");
+    let mut keys = scan_keys(db, SALUS_HISTORY_TABLE_DEF, &prefix)?;
+    keys.retain(|row| row.strip_prefix(&prefix).is_some_and(is_version));
+    Ok(keys)
+}
+
+/// The versions of the value under `key` kept for history, oldest first.
+fn revisions(db: &dyn StorageBackend, key: &str) -> Result<Vec<Revision>> {
+    let prefix = format!("{key}\0");
+    Ok(scan_values(db, SALUS_HISTORY_TABLE_DEF, &prefix)?
+        .into_iter()
+        .filter(|(row, _)| row.strip_prefix(&prefix).is_some_and(is_version))
+        .map(|(_, revision)| revision)
+        .collect())
+}
+
+/// Whether `rest` is the version a `salus_history` key ends in.
+fn is_version(rest: &str) -> bool {
+    rest.len() == VERSION_DIGITS && rest.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// The fields that differ between `before` and `after`, with their values
+/// when `show_values` is set.
+fn field_changes(before: &[u8], after: &[u8], show_values: bool) -> Vec<FieldChange> {
+    let documents = (
+        serde_json::from_slice::<Value>(before).ok(),
+        serde_json::from_slice::<Value>(after).ok(),
+    );
+    let mut changes = vec![];
+    match &documents {
+        (Some(before), Some(after)) => compare("", Some(before), Some(after), &mut changes),
+        _ if before != after => changes.push((
+            String::new(),
+            FieldChangeKind::Changed,
+            Some(Shown::Bytes(before)),
+            Some(Shown::Bytes(after)),
+        )),
+        _ => {}
+    }
+    changes
+        .into_iter()
+        .map(|(path, kind, before, after)| {
+            FieldChange::builder()
+                .path(path)
+                .kind(kind)
+                .maybe_before(before.filter(|_| show_values).map(Shown::text))
+                .maybe_after(after.filter(|_| show_values).map(Shown::text))
+                .build()
+        })
+        .collect()
+}
+
+/// A changed field: its path, how it changed, and its values before and after.
+type Changed<'a> = (
+    String,
+    FieldChangeKind,
+    Option<Shown<'a>>,
+    Option<Shown<'a>>,
+);
+
+/// A value a diff may show.
+enum Shown<'a> {
+    Json(&'a Value),
+    Bytes(&'a [u8]),
+}
+
+impl Shown<'_> {
+    /// The value as text: a string as itself, other JSON as JSON, and bytes
+    /// that are not UTF-8 as their length.
+    fn text(self) -> String {
+        match self {
+            Shown::Json(Value::String(text)) => text.clone(),
+            Shown::Json(other) => other.to_string(),
+            Shown::Bytes(bytes) => std::str::from_utf8(bytes)
+                .map_or_else(|_| format!("<{} bytes>", bytes.len()), ToString::to_string),
+        }
+    }
+}
+
+/// Record the fields under `path` that differ between `before` and `after`,
+/// descending into objects and arrays both sides hold.
+fn compare<'a>(
+    path: &str,
+    before: Option<&'a Value>,
+    after: Option<&'a Value>,
+    changes: &mut Vec<Changed<'a>>,
+) {
+    match (before, after) {
+        (Some(Value::Object(before)), Some(Value::Object(after))) => {
+            let names = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+            for name in names {
+                let escaped = name.replace('~', "~0").replace('/', "~1");
+                let child = format!("{path}/{escaped}");
+                compare(&child, before.get(name), after.get(name), changes);
+            }
+        }
+        (Some(Value::Array(before)), Some(Value::Array(after))) => {
+            for index in 0..before.len().max(after.len()) {
+                let child = format!("{path}/{index}");
+                compare(&child, before.get(index), after.get(index), changes);
+            }
+        }
+        (Some(before), Some(after)) if before != after => changes.push((
+            path.to_string(),
+            FieldChangeKind::Changed,
+            Some(Shown::Json(before)),
+            Some(Shown::Json(after)),
+        )),
+        (Some(before), None) => changes.push((
+            path.to_string(),
+            FieldChangeKind::Removed,
+            Some(Shown::Json(before)),
+            None,
+        )),
+        (None, Some(after)) => changes.push((
+            path.to_string(),
+            FieldChangeKind::Added,
+            None,
+            Some(Shown::Json(after)),
+        )),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{DiffRequest, FieldChangeKind, Response, ValueVersion};
+
+    use super::written_by;
+    use crate::store::{
+        ShareStore,
+        test::{temp_store, unlocked_store},
+    };
+
+    /// A store with its key unlocked that keeps `keep` earlier versions.
+    fn keeping(keep: u32) -> Result<ShareStore> {
+        let mut store = unlocked_store()?;
+        store.keep_versions = keep;
+        Ok(store)
+    }
+
+    fn history(store: &ShareStore, key: &str) -> Result<Vec<ValueVersion>> {
+        match store.history(key)? {
+            Response::History(versions) => Ok(versions),
+            other => bail!("expected the history of {key}, got {other:?}"),
+        }
+    }
+
+    fn diff(store: &ShareStore, from: u64, to: u64, show_values: bool) -> Result<Response> {
+        store.diff(
+            &DiffRequest::builder()
+                .key("app/db")
+                .from(from)
+                .to(to)
+                .show_values(show_values)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn earlier_versions_are_kept_with_their_author_up_to_keep() -> Result<()> {
+        let store = keeping(2)?;
+        for (uid, value) in [(Some(1000), "one"), (Some(1001), "two"), (None, "three")] {
+            let _stored = written_by(uid, || store.store("app/db", value.into(), true))?;
+        }
+        let _stored = written_by(Some(1002), || store.store("app/db", b"four".to_vec(), true))?;
+        match history(&store, "app/db")?.as_slice() {
+            [two, three, four] => {
+                assert_eq!((two.version(), two.author()), (2, Some(1001)));
+                assert_eq!((three.version(), three.author()), (3, None));
+                assert_eq!((four.version(), four.author()), (4, Some(1002)));
+                assert!(two.written().is_some() && four.written().is_some());
+                assert!(!two.current() && !three.current() && four.current());
+            }
+            other => bail!("expected versions 2 to 4, got {other:?}"),
+        }
+        // The oldest version went, and the kept ones open as they were.
+        assert!(matches!(
+            diff(&store, 1, 4, false)?,
+            Response::VersionNotFound(1)
+        ));
+        match diff(&store, 2, 4, true)? {
+            Response::Diff(changes) => match changes.as_slice() {
+                [change] => {
+                    assert_eq!(change.before().as_deref(), Some("two"));
+                    assert_eq!(change.after().as_deref(), Some("four"));
+                }
+                other => bail!("expected one change, got {other:?}"),
+            },
+            other => bail!("expected a diff, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn no_versions_are_kept_by_default() -> Result<()> {
+        let store = unlocked_store()?;
+        for value in ["one", "two"] {
+            let _stored = store.store("app/db", value.into(), true)?;
+        }
+        match history(&store, "app/db")?.as_slice() {
+            [current] => assert_eq!((current.version(), current.current()), (2, true)),
+            other => bail!("expected only the current version, got {other:?}"),
+        }
+        assert!(matches!(
+            diff(&store, 1, 2, false)?,
+            Response::VersionNotFound(1)
+        ));
+        assert!(matches!(store.history("app/none")?, Response::KeyNotFound));
+        Ok(())
+    }
+
+    #[test]
+    fn deleting_a_value_drops_its_history() -> Result<()> {
+        let store = keeping(5)?;
+        for value in ["one", "two", "three"] {
+            let _stored = store.store("app/db", value.into(), true)?;
+        }
+        assert_eq!(history(&store, "app/db")?.len(), 3);
+        assert!(matches!(store.delete("app/db")?, Response::Success));
+        assert!(matches!(store.history("app/db")?, Response::KeyNotFound));
+        let _stored = store.store("app/db", b"again".to_vec(), false)?;
+        match history(&store, "app/db")?.as_slice() {
+            [current] => assert_eq!(current.version(), 1),
+            other => bail!("expected a fresh history, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn a_diff_names_the_changed_fields_and_masks_their_values() -> Result<()> {
+        let store = keeping(1)?;
+        let creds = br#"{"user":"app","password":"hunter2","hosts":["a"]}"#;
+        let _stored = store.store("app/db", creds.to_vec(), false)?;
+        let patched = store.patch(
+            "app/db",
+            r#"{"password":"rotated","hosts":["a","b"],"port":5432}"#,
+        )?;
+        assert!(matches!(patched, Response::Success));
+        let masked = match diff(&store, 1, 2, false)? {
+            Response::Diff(changes) => changes,
+            other => bail!("expected a diff, got {other:?}"),
+        };
+        let fields = masked
+            .iter()
+            .map(|change| (change.path().as_str(), change.kind()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("/hosts/1", FieldChangeKind::Added),
+                ("/password", FieldChangeKind::Changed),
+                ("/port", FieldChangeKind::Added),
+            ]
+        );
+        assert!(
+            masked
+                .iter()
+                .all(|change| change.before().is_none() && change.after().is_none())
+        );
+        match diff(&store, 1, 2, true)? {
+            Response::Diff(changes) => {
+                let password = changes.iter().find(|change| change.path() == "/password");
+                let Some(password) = password else {
+                    bail!("expected the password to change, got {changes:?}");
+                };
+                assert_eq!(password.before().as_deref(), Some("hunter2"));
+                assert_eq!(password.after().as_deref(), Some("rotated"));
+            }
+            other => bail!("expected a diff, got {other:?}"),
+        }
+        match diff(&store, 2, 2, true)? {
+            Response::Diff(changes) => assert!(changes.is_empty()),
+            other => bail!("expected an empty diff, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn values_that_are_not_json_differ_as_a_whole() -> Result<()> {
+        let store = keeping(1)?;
+        let _stored = store.store("app/db", b"plain".to_vec(), false)?;
+        let _stored = store.store("app/db", vec![0xff, 0xfe], true)?;
+        match diff(&store, 1, 2, true)? {
+            Response::Diff(changes) => match changes.as_slice() {
+                [change] => {
+                    assert_eq!(change.path(), "");
+                    assert_eq!(change.kind(), FieldChangeKind::Changed);
+                    assert_eq!(change.before().as_deref(), Some("plain"));
+                    assert_eq!(change.after().as_deref(), Some("<2 bytes>"));
+                }
+                other => bail!("expected one change, got {other:?}"),
+            },
+            other => bail!("expected a diff, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn a_diff_needs_the_store_unlocked() {
+        let store = temp_store();
+        assert!(diff(&store, 1, 2, false).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! An integrity check of the sealed store, to find bit rot and partial writes
+//! before a read trips over them.
+//!
+//! Every value, signing key, and named keyring is opened under the key and
+//! AAD it should be sealed under, so a damaged row, a truncated one, or one
+//! copied under another key's name all fail to open. A value sealed under a
+//! named key must name a version that exists, and every signing key must have
+//! exactly one use counter. The rows that fail are reported; the check itself
+//! only fails if the store cannot be read at all.
+
+use anyhow::Result;
+use libsalus::{IntegrityProblem, IntegrityReport, Response};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use super::{ShareStore, named_key::check_keyring, signing::open_signing_key};
+use crate::{
+    db::{
+        CHECK_KEY_KEY, Row as _,
+        backend::{StorageBackend, Table},
+        unlock_backend,
+        values::salus::SalusVal,
+    },
+    error::Error,
+};
+
+/// Rows of one table, as stored.
+type Rows = Vec<(String, Vec<u8>)>;
+
+impl ShareStore {
+    /// Open every sealed row and check what it records, reporting each row
+    /// that fails rather than stopping at the first.
+    pub(crate) fn check_integrity(&self) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let (mut values, mut signing_keys, mut signing_uses, mut named_keys) =
+            (Rows::new(), Rows::new(), Rows::new(), Rows::new());
+        // Scanned with writes held off, so a signing key and its use counter
+        // are seen together.
+        unlock_backend(&self.backend, |db: &dyn StorageBackend| -> Result<()> {
+            values = db.scan(Table::Values, "")?;
+            signing_keys = db.scan(Table::SigningKeys, "")?;
+            signing_uses = db.scan(Table::SigningUses, "")?;
+            named_keys = db.scan(Table::NamedKeys, "")?;
+            Ok(())
+        })?;
+        // The sentinel is sealed under the key the shares unlock, which the
+        // unlock itself has just checked.
+        values.retain(|(key, _)| key != CHECK_KEY_KEY);
+
+        let mut problems = Vec::new();
+        let mut report = |table: Table, key: &str, reason: String| {
+            warn!("Integrity check: {} '{key}' {reason}", table.name());
+            problems.push(
+                IntegrityProblem::builder()
+                    .table(table.name())
+                    .key(key)
+                    .reason(reason)
+                    .build(),
+            );
+        };
+        // Opened once the database is released: a value under a named key
+        // reads its keyring.
+        for (key, row) in &values {
+            let sealed = SalusVal::from_row(row)?;
+            let checked = if sealed.blob_id()?.is_some() {
+                self.check_chunks(enc_key, key, &sealed)
+            } else {
+                self.open_value(enc_key, key, &sealed)
+                    .map(Zeroizing::new)
+                    .map(drop)
+            };
+            if let Err(e) = checked {
+                report(Table::Values, key, open_failure(&sealed, &e));
+            }
+        }
+        for (name, row) in &signing_keys {
+            let sealed = SalusVal::from_row(row)?;
+            if let Err(e) = open_signing_key(enc_key, name, &sealed) {
+                report(Table::SigningKeys, name, open_failure(&sealed, &e));
+            }
+            if !signing_uses.iter().any(|(uses, _)| uses == name) {
+                report(Table::SigningKeys, name, "has no use counter".to_string());
+            }
+        }
+        for (name, row) in &signing_uses {
+            if let Err(e) = u64::from_row(row) {
+                report(Table::SigningUses, name, format!("is malformed: {e}"));
+            }
+            if !signing_keys.iter().any(|(key, _)| key == name) {
+                report(
+                    Table::SigningUses,
+                    name,
+                    "counts uses of a signing key that does not exist".to_string(),
+                );
+            }
+        }
+        for (name, row) in &named_keys {
+            let sealed = SalusVal::from_row(row)?;
+            if let Err(e) = check_keyring(enc_key, name, &sealed) {
+                report(Table::NamedKeys, name, open_failure(&sealed, &e));
+            }
+        }
+
+        let checked = [&values, &signing_keys, &signing_uses, &named_keys]
+            .iter()
+            .try_fold(0u64, |checked, rows| {
+                Ok::<_, anyhow::Error>(checked.saturating_add(u64::try_from(rows.len())?))
+            })?;
+        info!(
+            target: "salusd::audit",
+            checked, problems = problems.len(), "Store integrity checked"
+        );
+        Ok(Response::StoreChecked(
+            IntegrityReport::builder()
+                .checked(checked)
+                .problems(problems)
+                .build(),
+        ))
+    }
+}
+
+/// Why a sealed row failed to open, in words an operator can act on.
+fn open_failure(sealed: &SalusVal, error: &anyhow::Error) -> String {
+    if let Err(e) = sealed.nonce() {
+        format!("is malformed: {e}")
+    } else if let Some(
+        missing @ (Error::NamedKeyMissing(..) | Error::ChunkMissing(..) | Error::ChunkSize(..)),
+    ) = error.downcast_ref::<Error>()
+    {
+        format!("cannot be opened: {missing}")
+    } else {
+        "does not decrypt: it is damaged, or was copied from another key".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::{Result, bail};
+    use libsalus::{NewNamedKey, NewSigningKey, Response, SigningAlgorithm, Store};
+
+    use super::super::{ShareStore, test::unlocked_store};
+    use crate::db::{
+        SharedBackend,
+        backend::{MemoryBackend, StorageBackend, Table, WriteOp},
+        unlock_backend,
+    };
+
+    fn problems(store: &ShareStore) -> Result<Vec<(String, String)>> {
+        let Response::StoreChecked(report) = store.check_integrity()? else {
+            bail!("expected an integrity report");
+        };
+        Ok(report
+            .problems()
+            .iter()
+            .map(|problem| (problem.table().clone(), problem.key().clone()))
+            .collect())
+    }
+
+    #[test]
+    fn a_healthy_store_has_no_problems() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("db/pass", b"hunter2".to_vec(), false)?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app").build())?;
+        let store_request = Store::builder().key("app/token").value("abc").build();
+        let _stored = store.store_with_key("app", &store_request)?;
+        let _created = store.create_signing_key(
+            &NewSigningKey::builder()
+                .name("release")
+                .algorithm(SigningAlgorithm::Ed25519)
+                .build(),
+        )?;
+        let Response::StoreChecked(report) = store.check_integrity()? else {
+            bail!("expected an integrity report");
+        };
+        assert_eq!(report.checked(), 5);
+        assert!(report.problems().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn damaged_moved_and_orphaned_rows_are_reported() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("a", b"one".to_vec(), false)?;
+        let _stored = store.store("b", b"two".to_vec(), false)?;
+        let _created = store.create_signing_key(
+            &NewSigningKey::builder()
+                .name("release")
+                .algorithm(SigningAlgorithm::HmacSha256)
+                .build(),
+        )?;
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            let Some(mut flipped) = db.get(Table::Values, "a")? else {
+                bail!("expected a row");
+            };
+            if let Some(byte) = flipped.last_mut() {
+                *byte ^= 1;
+            }
+            let Some(moved) = db.get(Table::Values, "b")? else {
+                bail!("expected a row");
+            };
+            let put = |key: &str, value: Vec<u8>| WriteOp::Put {
+                table: Table::Values,
+                key: key.to_string(),
+                value,
+            };
+            db.commit(vec![
+                put("a", flipped),
+                put("c", moved),
+                put("d", vec![1, 2, 3]),
+                WriteOp::Delete {
+                    table: Table::SigningUses,
+                    key: "release".to_string(),
+                },
+            ])
+        })?;
+        assert_eq!(
+            problems(&store)?,
+            [
+                ("salus_store", "a"),
+                ("salus_store", "c"),
+                ("salus_store", "d"),
+                ("salus_signing_keys", "release"),
+            ]
+            .map(|(table, key)| (table.to_string(), key.to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn checking_needs_the_key() {
+        let store = ShareStore::builder()
+            .backend(Arc::new(SharedBackend::new(MemoryBackend::default())))
+            .build();
+        assert!(store.check_integrity().is_err());
+    }
+}
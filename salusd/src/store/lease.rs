@@ -0,0 +1,231 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Leases: the credentials the daemon minted for a while, kept so each is
+//! taken back at its source once the while is up.
+//!
+//! A lease is a row of `salus_leases`, a JSON document under the lease's id
+//! naming the plugin and role it was minted with, the user it created, and the
+//! statements that drop that user. Nothing in it is secret: the password is
+//! only ever in the reply to the read that minted it. Renewing a lease
+//! rewrites its row, under the row's lock, with the later expiry.
+
+use anyhow::Result;
+use bon::Builder;
+use getset::{CopyGetters, Getters};
+use libsalus::LeaseInfo;
+use serde::{Deserialize, Serialize};
+
+use super::ShareStore;
+use crate::{
+    db::{
+        SALUS_LEASES_TABLE_DEF,
+        backend::{Table, WriteOp},
+        put, read_backend, read_value, scan_values, write_keys,
+    },
+    error::Error,
+};
+
+/// A credential minted for a while
+#[derive(Builder, Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize)]
+pub(crate) struct Lease {
+    /// The lease's id
+    #[builder(into)]
+    #[getset(get = "pub(crate)")]
+    id: String,
+    /// The plugin that minted it, and takes it back
+    #[builder(into)]
+    #[getset(get = "pub(crate)")]
+    plugin: String,
+    /// The role it was minted for
+    #[builder(into)]
+    #[getset(get = "pub(crate)")]
+    role: String,
+    /// The user it created
+    #[builder(into)]
+    #[getset(get = "pub(crate)")]
+    username: String,
+    /// The statements that drop the user
+    #[getset(get = "pub(crate)")]
+    revocation: Vec<String>,
+    /// When it is up, in seconds since the Unix epoch
+    #[getset(get_copy = "pub(crate)")]
+    expires_at: u64,
+    /// When it was minted, in seconds since the Unix epoch; `None` for a
+    /// lease recorded before mint times were
+    #[serde(default)]
+    #[getset(get_copy = "pub(crate)")]
+    issued_at: Option<u64>,
+}
+
+impl Lease {
+    /// The lease as a client is told of it.
+    pub(crate) fn info(&self) -> LeaseInfo {
+        LeaseInfo::builder()
+            .lease_id(self.id.as_str())
+            .role(self.role.as_str())
+            .username(self.username.as_str())
+            .expires_at(self.expires_at)
+            .build()
+    }
+}
+
+impl ShareStore {
+    /// Record `lease`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the lease cannot be
+    ///   written.
+    pub(crate) fn add_lease(&self, lease: &Lease) -> Result<()> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let row = serde_json::to_string(lease)?;
+        write_keys(
+            &self.backend,
+            [(Table::Leases, lease.id().as_str())],
+            |db| db.commit(vec![put(SALUS_LEASES_TABLE_DEF, lease.id(), &row)]),
+        )
+    }
+
+    /// The lease `id`, if it is recorded.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the lease cannot be read.
+    pub(crate) fn lease(&self, id: &str) -> Result<Option<Lease>> {
+        let mut lease = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            lease = read_value(db, SALUS_LEASES_TABLE_DEF, id)?
+                .map(|row| serde_json::from_str::<Lease>(&row))
+                .transpose()?;
+            Ok(())
+        })?;
+        Ok(lease)
+    }
+
+    /// Have the lease `id` be up at `expires_at`, unless it is already up at
+    /// `now` or gone, answering with the lease as renewed.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the lease cannot be
+    ///   read or written.
+    pub(crate) fn renew_lease(&self, id: &str, expires_at: u64, now: u64) -> Result<Option<Lease>> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut renewed = None;
+        write_keys(&self.backend, [(Table::Leases, id)], |db| -> Result<()> {
+            let Some(row) = read_value(db, SALUS_LEASES_TABLE_DEF, id)? else {
+                return Ok(());
+            };
+            let mut lease = serde_json::from_str::<Lease>(&row)?;
+            if lease.expires_at <= now {
+                return Ok(());
+            }
+            lease.expires_at = expires_at;
+            let row = serde_json::to_string(&lease)?;
+            db.commit(vec![put(SALUS_LEASES_TABLE_DEF, id, &row)])?;
+            renewed = Some(lease);
+            Ok(())
+        })?;
+        Ok(renewed)
+    }
+
+    /// The leases that are up at `now`, in seconds since the Unix epoch,
+    /// soonest first.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the leases cannot be read.
+    pub(crate) fn expired_leases(&self, now: u64) -> Result<Vec<Lease>> {
+        let mut expired = Vec::new();
+        read_backend(&self.backend, |db| -> Result<()> {
+            for (_, row) in scan_values(db, SALUS_LEASES_TABLE_DEF, "")? {
+                let lease = serde_json::from_str::<Lease>(&row)?;
+                if lease.expires_at() <= now {
+                    expired.push(lease);
+                }
+            }
+            Ok(())
+        })?;
+        expired.sort_by_key(Lease::expires_at);
+        Ok(expired)
+    }
+
+    /// Forget the lease `id`, once it has been taken back.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the lease cannot be removed.
+    pub(crate) fn remove_lease(&self, id: &str) -> Result<()> {
+        write_keys(&self.backend, [(Table::Leases, id)], |db| {
+            db.commit(vec![WriteOp::Delete {
+                table: Table::Leases,
+                key: id.to_string(),
+            }])
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::Lease;
+    use crate::store::test::{temp_store, unlocked_store};
+
+    fn lease(id: &str, expires_at: u64) -> Lease {
+        Lease::builder()
+            .id(id)
+            .plugin("pg")
+            .role("ro")
+            .username(format!("v_ro_{id}"))
+            .revocation(vec![format!("DROP ROLE v_ro_{id}")])
+            .expires_at(expires_at)
+            .issued_at(0)
+            .build()
+    }
+
+    #[test]
+    fn leases_are_found_once_they_are_up() -> Result<()> {
+        let store = unlocked_store()?;
+        store.add_lease(&lease("b", 200))?;
+        store.add_lease(&lease("a", 100))?;
+        store.add_lease(&lease("c", 300))?;
+        assert!(store.expired_leases(99)?.is_empty());
+        assert_eq!(
+            store.expired_leases(200)?,
+            vec![lease("a", 100), lease("b", 200)]
+        );
+        store.remove_lease("a")?;
+        assert_eq!(store.expired_leases(200)?, vec![lease("b", 200)]);
+        assert!(temp_store().add_lease(&lease("d", 100)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn only_leases_not_yet_up_are_renewed() -> Result<()> {
+        let store = unlocked_store()?;
+        store.add_lease(&lease("a", 100))?;
+        assert_eq!(store.renew_lease("a", 500, 100)?, None);
+        assert_eq!(store.renew_lease("gone", 500, 50)?, None);
+        let Some(renewed) = store.renew_lease("a", 500, 50)? else {
+            bail!("a lease not yet up was not renewed");
+        };
+        assert_eq!(renewed.expires_at(), 500);
+        assert_eq!(store.lease("a")?, Some(renewed));
+        assert!(store.expired_leases(499)?.is_empty());
+        // Recorded before mint times were.
+        let old = r#"{"id":"b","plugin":"pg","role":"ro","username":"v_ro_b","revocation":[],"expires_at":9}"#;
+        assert_eq!(serde_json::from_str::<Lease>(old)?.issued_at(), None);
+        Ok(())
+    }
+}
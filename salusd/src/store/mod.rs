@@ -6,41 +6,130 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, Result};
 use aws_lc_rs::{
-    aead::{AES_256_GCM, Aad, Nonce, RandomizedNonceKey},
-    rand,
+    aead::{AES_128_GCM, AES_256_GCM, Aad, Nonce, RandomizedNonceKey},
+    constant_time, digest, hkdf, rand,
 };
+use bincode_next::Decode;
 use bon::Builder;
-use libsalus::{Init, Response, Shares, SsssConfig, fuzzy_rank, gen_shares, unlock_key};
-use redb::{Database, ReadableDatabase, ReadableTable};
+use libsalus::{
+    BatchOutcome, GenerateSecret, Init, KeyAlgorithm, KeyStat, Response, Shares, SsssConfig, Store,
+    StoreStatus, WrappingKey, fuzzy_rank, gen_shares, generate_secret, share_parts, unlock_key,
+};
 use regex::Regex;
 use tracing::{error, info, trace};
 use zeroize::{Zeroize, Zeroizing};
 
 use crate::{
+    config::{DEFAULT_NUM_SHARES, DEFAULT_THRESHOLD},
     db::{
-        CHECK_KEY_KEY, INITIALIZED_KEY, NUM_SHARES_KEY, SALUS_CONFIG_TABLE_DEF,
-        SALUS_VAL_TABLE_DEF, THRESHOLD_KEY, delete_value, read_value, unlock_redb,
+        Backend, CHECK_KEY_KEY, INITIALIZED_KEY, KDF_SALT_KEY, KEY_ALGORITHM_KEY, NUM_SHARES_KEY,
+        SALUS_ALIASES_TABLE_DEF, SALUS_CONFIG_TABLE_DEF, SALUS_VAL_TABLE_DEF,
+        SALUS_VERSIONS_TABLE_DEF, SALUS_WRITTEN_TABLE_DEF, SCHEMA_VERSION_KEY, SHARE_DIGESTS_KEY,
+        SHARE_EPOCH_KEY, THRESHOLD_KEY, WRAPPED_KEY_KEY,
+        backend::{StorageBackend, Table, WriteOp},
+        migrations::SCHEMA_VERSION,
+        put, read_backend, read_value, scan_keys, scan_values, unlock_backend,
         values::{config::ConfigVal, salus::SalusVal},
-        write_value,
+        write_keys, write_share_set, write_value,
     },
     error::Error,
+    hook::Changes,
+};
+
+use self::{
+    blob::Uploads,
+    cache::ReadCache,
+    compress::{Compression, compressed_aad, decompress},
+    named_key::Rewraps,
+    rules::Rules,
 };
 
+mod alias;
+pub(crate) mod approval;
+pub(crate) mod backup;
+pub(crate) mod blob;
+pub(crate) mod cache;
+pub(crate) mod compress;
+mod data_key;
+mod encrypt;
+pub(crate) mod history;
+mod integrity;
+pub(crate) mod lease;
+mod named_key;
+mod pki;
+pub(crate) mod rotation;
+pub(crate) mod rules;
+mod signing;
+mod ssh;
+mod sync;
+mod wrap;
+
 #[derive(Builder)]
 pub(crate) struct ShareStore {
     #[builder(default)]
     shares: Vec<String>,
     #[allow(dead_code)]
     key: Option<Zeroizing<Vec<u8>>>,
-    redb: Arc<Mutex<Database>>,
+    backend: Backend,
     /// Incremented on every successful unlock so that stale key-clear timers
     /// (from earlier unlocks) become no-ops. See `clear_key_if_generation`.
     #[builder(default)]
     key_generation: u64,
+    /// When the auto-clear timer for the current key fires; `None` while
+    /// sealed or when the key is held until an explicit lock.
+    #[builder(skip)]
+    key_expires_at: Option<Instant>,
+    /// When the current key was unlocked; `None` while sealed.
+    #[builder(skip)]
+    key_unlocked_at: Option<Instant>,
+    /// The database file backing this store, reported by `status`.
+    database_path: Option<PathBuf>,
+    /// The share count used when none has been recorded (`[shares]` in the
+    /// daemon config).
+    #[builder(default = DEFAULT_NUM_SHARES)]
+    default_num_shares: u8,
+    /// The threshold used when none has been recorded.
+    #[builder(default = DEFAULT_THRESHOLD)]
+    default_threshold: u8,
+    /// The key pair the next wrapped import is sealed to, if one was issued.
+    #[builder(skip)]
+    wrapping_key: Option<WrappingKey>,
+    /// Recently read values, when the daemon is configured to cache them.
+    #[builder(default)]
+    read_cache: ReadCache,
+    /// Values being uploaded in chunks.
+    #[builder(default)]
+    uploads: Uploads,
+    /// Whether values are compressed before they are sealed.
+    #[builder(default)]
+    compression: Compression,
+    /// Whether changes to the store are refused (`read_only` in the daemon
+    /// config, or `salus read-only`).
+    #[builder(default)]
+    read_only: bool,
+    /// Where committed changes are reported, for the hooks.
+    #[builder(default)]
+    changes: Changes,
+    /// The rewraps begun since the daemon started, and how far each has got.
+    #[builder(default)]
+    rewraps: Rewraps,
+    /// How many versions before its current one each value keeps for
+    /// history (`[history] keep` in the daemon config).
+    #[builder(default)]
+    keep_versions: u32,
+    /// What values must look like before they are stored (`[validation]` in
+    /// the daemon config).
+    #[builder(default)]
+    rules: Arc<Rules>,
 }
 
 impl ShareStore {
@@ -50,6 +139,27 @@ impl ShareStore {
 
     pub(crate) fn clear_key(&mut self) {
         self.key = None;
+        self.key_expires_at = None;
+        self.key_unlocked_at = None;
+        self.wrapping_key = None;
+        self.read_cache.clear();
+    }
+
+    /// Record how long the freshly unlocked key will be held, so `status` can
+    /// report the time remaining. `None` means until an explicit lock.
+    pub(crate) fn set_key_expiry(&mut self, hold: Option<Duration>) {
+        self.key_expires_at = hold.and_then(|hold| Instant::now().checked_add(hold));
+    }
+
+    /// Take up the validation rules of a reloaded configuration.
+    pub(crate) fn set_rules(&mut self, rules: Arc<Rules>) {
+        self.rules = rules;
+    }
+
+    /// How long ago the key held now was unlocked; `None` while sealed.
+    pub(crate) fn unlocked_for(&self) -> Option<Duration> {
+        self.key_unlocked_at
+            .map(|unlocked_at| unlocked_at.elapsed())
     }
 
     /// Clear the unlocked key only if the store has not been unlocked again
@@ -72,29 +182,90 @@ impl ShareStore {
         self.key_generation = self.key_generation.wrapping_add(1);
     }
 
+    /// Zeroize every secret the store holds in memory, the shares collected
+    /// towards an unlock as well as the key, as the daemon goes down.
+    pub(crate) fn wipe(&mut self) {
+        self.clear_shares();
+        self.lock();
+    }
+
+    /// Whether changes to the store are being refused.
+    pub(crate) fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Enter or lift read-only mode. Anyone may enter it, but lifting it
+    /// needs the store unlocked, so a client that cannot read the values
+    /// cannot reopen the store to writes either.
+    pub(crate) fn set_read_only(&mut self, read_only: bool) -> Result<Response> {
+        if !read_only && self.read_only && self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        if self.read_only != read_only {
+            self.read_only = read_only;
+            info!(target: "salusd::audit", read_only, "Read-only mode changed");
+        }
+        Ok(Response::Success)
+    }
+
     pub(crate) fn add_share<S: Into<String>>(&mut self, share: S) {
         self.shares.push(share.into());
     }
 
+    /// Record the share count and threshold for the share set about to be
+    /// generated.
+    ///
+    /// Unusable parameters are refused with
+    /// [`Response::InvalidShareParameters`], and the parameters of a store
+    /// whose shares already exist are never rewritten.
     pub(crate) fn initialize(&mut self, init: Init) -> Result<Response> {
         trace!(
             "Initializing share store with {} shares and threshold {}",
             init.num_shares(),
             init.threshold()
         );
-        unlock_redb(&self.redb, |db| -> Result<()> {
-            write_value::<&str, ConfigVal>(
+        if let Err(e) = init.validate() {
+            info!("Refusing share parameters: {e}");
+            return Ok(Response::InvalidShareParameters(e.to_string()));
+        }
+        if self.config_value::<bool>(INITIALIZED_KEY)?.unwrap_or(false) {
+            return Ok(Response::AlreadyInitialiazed);
+        }
+        unlock_backend(&self.backend, |db| -> Result<()> {
+            write_value(
+                db,
+                SALUS_CONFIG_TABLE_DEF,
+                SCHEMA_VERSION_KEY,
+                &ConfigVal::from_value(SCHEMA_VERSION)?,
+            )?;
+            write_value(
                 db,
                 SALUS_CONFIG_TABLE_DEF,
                 NUM_SHARES_KEY,
-                ConfigVal::from_value(init.num_shares())?,
+                &ConfigVal::from_value(init.num_shares())?,
             )?;
-            write_value::<&str, ConfigVal>(
+            write_value(
                 db,
                 SALUS_CONFIG_TABLE_DEF,
                 THRESHOLD_KEY,
-                ConfigVal::from_value(init.threshold())?,
+                &ConfigVal::from_value(init.threshold())?,
             )?;
+            write_value(
+                db,
+                SALUS_CONFIG_TABLE_DEF,
+                KEY_ALGORITHM_KEY,
+                &ConfigVal::from_value(init.algorithm())?,
+            )?;
+            if init.kdf() {
+                let mut salt = [0u8; 32];
+                rand::fill(&mut salt)?;
+                write_value(
+                    db,
+                    SALUS_CONFIG_TABLE_DEF,
+                    KDF_SALT_KEY,
+                    &ConfigVal::from_value(salt)?,
+                )?;
+            }
             Ok(())
         })?;
         Ok(Response::Success)
@@ -103,12 +274,11 @@ impl ShareStore {
     pub(crate) fn gen_shares(&mut self) -> Result<Response> {
         trace!("Generating shares for share store");
         let mut initialized = false;
-        unlock_redb(&self.redb, |db| -> Result<()> {
-            if let Ok(init_opt) =
-                read_value::<&str, ConfigVal>(db, SALUS_CONFIG_TABLE_DEF, INITIALIZED_KEY)
+        read_backend(&self.backend, |db| -> Result<()> {
+            if let Ok(init_opt) = read_value(db, SALUS_CONFIG_TABLE_DEF, INITIALIZED_KEY)
                 && let Some(init) = init_opt
             {
-                initialized = init.value().to_value::<bool>()?;
+                initialized = init.to_value::<bool>()?;
             }
             Ok(())
         })?;
@@ -116,23 +286,25 @@ impl ShareStore {
         if initialized {
             Ok(Response::AlreadyInitialiazed)
         } else {
-            let mut key = Zeroizing::new([0u8; 32]);
-            rand::fill(&mut *key)?;
-            let mut num_shares = 5;
-            let mut threshold = 3;
-
-            unlock_redb(&self.redb, |db| -> Result<()> {
-                if let Ok(num_shares_opt) =
-                    read_value::<&str, ConfigVal>(db, SALUS_CONFIG_TABLE_DEF, NUM_SHARES_KEY)
+            let algorithm = self
+                .config_value::<KeyAlgorithm>(KEY_ALGORITHM_KEY)?
+                .unwrap_or_default();
+            let mut secret = Zeroizing::new(vec![0u8; algorithm.key_len()]);
+            rand::fill(&mut secret)?;
+            let key = self.derive_key(&secret)?;
+            let mut num_shares = self.default_num_shares;
+            let mut threshold = self.default_threshold;
+
+            read_backend(&self.backend, |db| -> Result<()> {
+                if let Ok(num_shares_opt) = read_value(db, SALUS_CONFIG_TABLE_DEF, NUM_SHARES_KEY)
                     && let Some(num_shares_ag) = num_shares_opt
                 {
-                    num_shares = num_shares_ag.value().to_value::<u8>()?;
+                    num_shares = num_shares_ag.to_value::<u8>()?;
                 }
-                if let Ok(threshold_opt) =
-                    read_value::<&str, ConfigVal>(db, SALUS_CONFIG_TABLE_DEF, THRESHOLD_KEY)
+                if let Ok(threshold_opt) = read_value(db, SALUS_CONFIG_TABLE_DEF, THRESHOLD_KEY)
                     && let Some(threshold_ag) = threshold_opt
                 {
-                    threshold = threshold_ag.value().to_value::<u8>()?;
+                    threshold = threshold_ag.to_value::<u8>()?;
                 }
                 Ok(())
             })?;
@@ -143,29 +315,34 @@ impl ShareStore {
                     .num_shares(num_shares)
                     .threshold(threshold)
                     .build(),
-                &key,
+                &secret,
             ) {
                 Ok(shares) => {
-                    let rnkey = RandomizedNonceKey::new(&AES_256_GCM, key.as_slice())
-                        .with_context(|| Error::NonceKeyGen)?;
+                    let digests = shares
+                        .iter()
+                        .map(String::as_str)
+                        .map(share_digest)
+                        .collect::<Result<Vec<_>>>()?;
+                    let rnkey = aead_key(&key)?;
                     let mut check_key = CHECK_KEY_KEY.as_bytes().to_vec();
                     let nonce = rnkey.seal_in_place_append_tag(
                         Aad::from(CHECK_KEY_KEY.as_bytes()),
                         &mut check_key,
                     )?;
-                    unlock_redb(&self.redb, |db| -> Result<()> {
+                    unlock_backend(&self.backend, |db| -> Result<()> {
                         let salus_val = SalusVal::from_parts(*nonce.as_ref(), &check_key);
-                        write_value::<String, SalusVal>(
+                        write_value(db, SALUS_VAL_TABLE_DEF, CHECK_KEY_KEY, &salus_val)?;
+                        write_value(
                             db,
-                            SALUS_VAL_TABLE_DEF,
-                            CHECK_KEY_KEY.to_string(),
-                            salus_val,
+                            SALUS_CONFIG_TABLE_DEF,
+                            SHARE_DIGESTS_KEY,
+                            &ConfigVal::from_value(&digests)?,
                         )?;
-                        write_value::<&str, ConfigVal>(
+                        write_value(
                             db,
                             SALUS_CONFIG_TABLE_DEF,
                             INITIALIZED_KEY,
-                            ConfigVal::from_value(true)?,
+                            &ConfigVal::from_value(true)?,
                         )?;
                         Ok(())
                     })?;
@@ -177,29 +354,217 @@ impl ShareStore {
     }
 
     pub(crate) fn get_threshold(&self) -> u8 {
-        let mut threshold = 3;
-        if let Ok(()) = unlock_redb(&self.redb, |db| -> Result<()> {
-            if let Ok(threshold_opt) =
-                read_value::<&str, ConfigVal>(db, SALUS_CONFIG_TABLE_DEF, THRESHOLD_KEY)
+        let mut threshold = self.default_threshold;
+        if let Ok(()) = read_backend(&self.backend, |db| -> Result<()> {
+            if let Ok(threshold_opt) = read_value(db, SALUS_CONFIG_TABLE_DEF, THRESHOLD_KEY)
                 && let Some(threshold_ag) = threshold_opt
             {
-                threshold = threshold_ag.value().to_value::<u8>()?;
+                threshold = threshold_ag.to_value::<u8>()?;
             }
             Ok(())
         }) {}
         threshold
     }
 
+    /// Report the store's state. Available while sealed: nothing here needs
+    /// (or reveals) the key.
+    pub(crate) fn status(&self) -> Result<Response> {
+        let mut initialized = false;
+        let mut num_shares = self.default_num_shares;
+        let mut threshold = self.default_threshold;
+        // A fresh database has no config table yet; that reads as the defaults.
+        read_backend(&self.backend, |db| -> Result<()> {
+            if let Ok(Some(init)) = read_value(db, SALUS_CONFIG_TABLE_DEF, INITIALIZED_KEY) {
+                initialized = init.to_value::<bool>()?;
+            }
+            if let Ok(Some(num_shares_ag)) = read_value(db, SALUS_CONFIG_TABLE_DEF, NUM_SHARES_KEY)
+            {
+                num_shares = num_shares_ag.to_value::<u8>()?;
+            }
+            if let Ok(Some(threshold_ag)) = read_value(db, SALUS_CONFIG_TABLE_DEF, THRESHOLD_KEY) {
+                threshold = threshold_ag.to_value::<u8>()?;
+            }
+            Ok(())
+        })?;
+        let sealed = self.key.is_none();
+        let key_timeout_remaining = self
+            .key_expires_at
+            .filter(|_| !sealed)
+            .map(|expires| expires.saturating_duration_since(Instant::now()).as_secs());
+        Ok(Response::Status(
+            StoreStatus::builder()
+                .initialized(initialized)
+                .sealed(sealed)
+                .threshold(threshold)
+                .num_shares(num_shares)
+                .shares_collected(self.shares.len())
+                .maybe_key_timeout_remaining(key_timeout_remaining)
+                .daemon_version(env!("CARGO_PKG_VERSION"))
+                .maybe_database_path(
+                    self.database_path
+                        .as_ref()
+                        .map(|path| path.display().to_string()),
+                )
+                .maybe_fingerprint(self.fingerprint()?)
+                .share_epoch(self.config_value::<u64>(SHARE_EPOCH_KEY)?.unwrap_or(0))
+                .key_algorithm(
+                    self.config_value::<KeyAlgorithm>(KEY_ALGORITHM_KEY)?
+                        .unwrap_or_default(),
+                )
+                .kdf(self.config_value::<[u8; 32]>(KDF_SALT_KEY)?.is_some())
+                .read_only(self.read_only)
+                .build(),
+        ))
+    }
+
+    /// A fingerprint of the sealed check value written when the shares were
+    /// generated: it differs for every key, so it ties printed shares to their
+    /// store without revealing anything about the key.
+    fn fingerprint(&self) -> Result<Option<String>> {
+        let mut fingerprint = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            if let Ok(Some(check)) = read_value(db, SALUS_VAL_TABLE_DEF, CHECK_KEY_KEY) {
+                let mut context = digest::Context::new(&digest::SHA256);
+                context.update(&check.nonce()?);
+                context.update(check.ciphertext()?);
+                let hex: Vec<String> = context
+                    .finish()
+                    .as_ref()
+                    .iter()
+                    .take(8)
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect();
+                fingerprint = Some(
+                    hex.chunks(2)
+                        .map(<[String]>::concat)
+                        .collect::<Vec<_>>()
+                        .join("-"),
+                );
+            }
+            Ok(())
+        })?;
+        Ok(fingerprint)
+    }
+
+    /// Check `share` against the digests recorded when the shares were
+    /// generated. Available while sealed: the digests identify the shares
+    /// without revealing them, so a custodian can confirm theirs is still
+    /// good without convening a quorum.
+    pub(crate) fn verify_share(&self, share: &str) -> Result<Response> {
+        let (index, submitted) = share_digest(share)?;
+        let digests = self
+            .config_value::<Vec<(u8, Vec<u8>)>>(SHARE_DIGESTS_KEY)?
+            .ok_or(Error::ShareDigestsMissing)?;
+        let known = digests.iter().any(|(idx, digest)| {
+            *idx == index && constant_time::verify_slices_are_equal(digest, &submitted).is_ok()
+        });
+        Ok(if known {
+            Response::ShareVerified(index)
+        } else {
+            Response::ShareNotRecognized
+        })
+    }
+
+    /// Issue a new share set for the held key and retire the old one.
+    ///
+    /// The new shares split a fresh share key rather than the store key, and
+    /// the store key is sealed under it (`WRAPPED_KEY`). Re-sealing `CHECK_KEY`
+    /// under the share key is what retires the previous shares: they still
+    /// reconstruct their own share key, but it no longer opens the check
+    /// value. Stored values are untouched.
+    pub(crate) fn refresh_shares(&mut self) -> Result<Response> {
+        let Some(store_key) = self.key.clone() else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let num_shares = self
+            .config_value::<u8>(NUM_SHARES_KEY)?
+            .unwrap_or(self.default_num_shares);
+        let threshold = self
+            .config_value::<u8>(THRESHOLD_KEY)?
+            .unwrap_or(self.default_threshold);
+        let epoch = self
+            .config_value::<u64>(SHARE_EPOCH_KEY)?
+            .unwrap_or(0)
+            .wrapping_add(1);
+
+        let mut secret = Zeroizing::new(vec![0u8; store_key.len()]);
+        rand::fill(&mut secret)?;
+        let share_key = self.derive_key(&secret)?;
+        let shares = gen_shares(
+            &SsssConfig::builder()
+                .num_shares(num_shares)
+                .threshold(threshold)
+                .build(),
+            &secret,
+        )
+        .map_err(|_| Error::ShareGeneration)?;
+        let digests = shares
+            .iter()
+            .map(String::as_str)
+            .map(share_digest)
+            .collect::<Result<Vec<_>>>()?;
+
+        let rnkey = aead_key(&share_key)?;
+        let mut check_key = CHECK_KEY_KEY.as_bytes().to_vec();
+        let check_nonce =
+            rnkey.seal_in_place_append_tag(Aad::from(CHECK_KEY_KEY.as_bytes()), &mut check_key)?;
+        let mut wrapped = store_key.to_vec();
+        let wrap_nonce =
+            rnkey.seal_in_place_append_tag(Aad::from(WRAPPED_KEY_KEY.as_bytes()), &mut wrapped)?;
+        let config = vec![
+            (
+                WRAPPED_KEY_KEY,
+                ConfigVal::from_value((*wrap_nonce.as_ref(), &wrapped))?,
+            ),
+            (SHARE_DIGESTS_KEY, ConfigVal::from_value(&digests)?),
+            (SHARE_EPOCH_KEY, ConfigVal::from_value(epoch)?),
+        ];
+        let check = SalusVal::from_parts(*check_nonce.as_ref(), &check_key);
+        unlock_backend(&self.backend, |db| -> Result<()> {
+            write_share_set(db, &check, &config)
+        })?;
+        // Shares collected towards an unlock belong to the retired set.
+        self.clear_shares();
+        info!(
+            target: "salusd::audit",
+            epoch, num_shares, threshold, "Share set refreshed; the previous shares are retired"
+        );
+        Ok(Response::Shares(Shares::builder().shares(shares).build()))
+    }
+
+    /// The key a share set's secret stands for: the secret itself, or, when
+    /// the store was initialized with a KDF, HKDF-SHA256 of it under the stored
+    /// salt.
+    fn derive_key(&self, secret: &Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>> {
+        let Some(salt) = self.config_value::<[u8; 32]>(KDF_SALT_KEY)? else {
+            return Ok(secret.clone());
+        };
+        let mut key = Zeroizing::new(vec![0u8; secret.len()]);
+        hkdf::Salt::new(hkdf::HKDF_SHA256, &salt)
+            .extract(secret)
+            .expand(&[KDF_INFO], KeyLen(secret.len()))?
+            .fill(&mut key)?;
+        Ok(key)
+    }
+
+    /// Read and decode one `salus_config` row; `None` when it is absent.
+    fn config_value<T: Decode<()>>(&self, key: &'static str) -> Result<Option<T>> {
+        let mut value = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            if let Ok(Some(stored)) = read_value(db, SALUS_CONFIG_TABLE_DEF, key) {
+                value = Some(stored.to_value()?);
+            }
+            Ok(())
+        })?;
+        Ok(value)
+    }
+
     pub(crate) fn unlock(&mut self) -> Result<Response> {
         let mut unlocked = false;
-        match unlock_key(&self.shares) {
+        match unlock_key(&self.shares).and_then(|secret| self.derive_key(&secret)) {
             Ok(key) => {
-                unlock_redb(&self.redb, |redb_c| -> Result<()> {
-                    match read_value::<String, SalusVal>(
-                        redb_c,
-                        SALUS_VAL_TABLE_DEF,
-                        CHECK_KEY_KEY.to_string(),
-                    ) {
+                read_backend(&self.backend, |db| -> Result<()> {
+                    match read_value(db, SALUS_VAL_TABLE_DEF, CHECK_KEY_KEY) {
                         Err(e) => {
                             error!("Error reading CHECK_KEY from database: {e}");
                             return Err(e);
@@ -209,10 +574,9 @@ impl ShareStore {
                             return Err(Error::CheckKeyNotFound.into());
                         }
                         Ok(Some(svag)) => {
-                            let sv = svag.value();
+                            let sv = svag;
                             let nonce = Nonce::from(&sv.nonce()?);
-                            let rnkey = RandomizedNonceKey::new(&AES_256_GCM, &key)
-                                .with_context(|| Error::NonceKeyGen)?;
+                            let rnkey = aead_key(&key)?;
                             let mut ciphertext = sv.ciphertext()?.to_vec();
                             // A failed open here means the reconstructed key (and
                             // therefore the supplied shares) is wrong. That is a
@@ -224,7 +588,7 @@ impl ShareStore {
                             ) {
                                 Ok(plaintext_b) if plaintext_b == CHECK_KEY_KEY.as_bytes() => {
                                     info!("Key successfully unlocked and verified.");
-                                    self.key = Some(key.clone());
+                                    self.key = Some(unwrap_store_key(db, &rnkey, &key)?);
                                     unlocked = true;
                                 }
                                 Ok(_) | Err(_) => {
@@ -241,23 +605,51 @@ impl ShareStore {
         self.clear_shares();
         if unlocked {
             self.key_generation = self.key_generation.wrapping_add(1);
+            self.key_unlocked_at = Some(Instant::now());
             Ok(Response::Success)
         } else {
             Ok(Response::UnlockFailed)
         }
     }
 
-    pub(crate) fn store(&self, key: &str, mut value: Vec<u8>, force: bool) -> Result<Response> {
+    pub(crate) fn store(&self, key: &str, value: Vec<u8>, force: bool) -> Result<Response> {
+        self.store_sealed(key, value, force, None)
+    }
+
+    /// Store a value sealed under the named key's newest version rather than
+    /// the store key.
+    pub(crate) fn store_with_key(&self, name: &str, request: &Store) -> Result<Response> {
+        self.store_sealed(
+            request.key(),
+            request.value().as_bytes().to_vec(),
+            request.force(),
+            Some(name),
+        )
+    }
+
+    fn store_sealed(
+        &self,
+        key: &str,
+        mut value: Vec<u8>,
+        force: bool,
+        named_key: Option<&str>,
+    ) -> Result<Response> {
         if let Some(enc_key) = &self.key {
+            if let Err(failure) = self.rules.check(key, &value) {
+                value.zeroize();
+                info!(
+                    "Refusing a value for {key} that breaks rule {}",
+                    failure.rule()
+                );
+                return Ok(Response::ValidationFailed(failure));
+            }
             // Collision protection: unless the caller forces the write, refuse to
             // overwrite an existing key. Checked before sealing so a refused
             // overwrite does no needless encryption.
             if !force {
                 let mut exists = false;
-                unlock_redb(&self.redb, |db| -> Result<()> {
-                    exists =
-                        read_value::<String, SalusVal>(db, SALUS_VAL_TABLE_DEF, key.to_string())?
-                            .is_some();
+                read_backend(&self.backend, |db| -> Result<()> {
+                    exists = read_value(db, SALUS_VAL_TABLE_DEF, key)?.is_some();
                     Ok(())
                 })?;
                 if exists {
@@ -265,85 +657,484 @@ impl ShareStore {
                     return Ok(Response::KeyExists);
                 }
             }
-            let rnkey = RandomizedNonceKey::new(&AES_256_GCM, enc_key)
-                .with_context(|| Error::NonceKeyGen)?;
-            let nonce = rnkey.seal_in_place_append_tag(Aad::from(key.as_bytes()), &mut value)?;
-            unlock_redb(&self.redb, |db| -> Result<()> {
-                let salus_val = SalusVal::from_parts(*nonce.as_ref(), &value);
-                match write_value::<String, SalusVal>(
-                    db,
-                    SALUS_VAL_TABLE_DEF,
-                    key.to_string(),
-                    salus_val,
-                ) {
-                    Err(e) => {
-                        error!("Error writing value to database: {e}");
-                        return Err(e);
-                    }
-                    Ok(()) => {
-                        info!("Stored value under key: {key}");
-                    }
+            let Some(salus_val) = self.seal_for(enc_key, key, value, named_key)? else {
+                return Ok(Response::NamedKeyNotFound(
+                    named_key.unwrap_or_default().to_string(),
+                ));
+            };
+            let (mut exists, mut version) = (false, 0);
+            self.write_value_row(key, |db, existing| -> Result<()> {
+                // Checked again under the key's write lock, in case another
+                // store of it landed while this one was sealing.
+                exists = !force && existing.is_some();
+                if exists {
+                    return Ok(());
+                }
+                let mut ops = Self::release_chunks(db, enc_key, key, existing.as_ref())?;
+                ops.push(put(SALUS_VAL_TABLE_DEF, key, &salus_val));
+                let (revised, next) = self.revised(db, key, existing.as_ref(), Some(now()))?;
+                ops.extend(revised);
+                if let Err(e) = db.commit(ops) {
+                    error!("Error writing value to database: {e}");
+                    return Err(e);
                 }
+                version = next;
+                self.read_cache.invalidate([key]);
+                info!("Stored value under key: {key}");
                 Ok(())
             })?;
+            if exists {
+                info!("Refusing to overwrite existing key without force: {key}");
+                return Ok(Response::KeyExists);
+            }
+            self.changes.written(key, version);
             Ok(Response::Success)
         } else {
             Err(Error::StoreNotUnlocked.into())
         }
     }
 
+    /// Generate a secret from the daemon's RNG and store it.
+    ///
+    /// The secret is returned only when the request asks to `show` it; an
+    /// existing key is left alone (and `KeyExists` returned) unless `force` is
+    /// set.
+    pub(crate) fn generate(&self, request: &GenerateSecret) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut secret = generate_secret(request.spec(), |buf| Ok(rand::fill(buf)?))?;
+        let shown = request.show().then(|| secret.clone());
+        let stored = self.store(request.key(), secret.as_bytes().to_vec(), request.force());
+        secret.zeroize();
+        match stored? {
+            Response::Success => Ok(Response::Generated(shown)),
+            other => {
+                if let Some(mut shown) = shown {
+                    shown.zeroize();
+                }
+                Ok(other)
+            }
+        }
+    }
+
+    /// Store several values in one transaction.
+    ///
+    /// Existing keys are looked up first: if any entry would overwrite one
+    /// without `force`, or this is a dry run, nothing is written and the
+    /// outcome only describes the plan.
+    pub(crate) fn store_batch(&self, entries: &[Store], dry_run: bool) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        // One value that breaks a rule refuses the batch, as one conflict
+        // does.
+        for entry in entries {
+            if let Err(failure) = self.rules.check(entry.key(), entry.value().as_bytes()) {
+                info!(
+                    "Refusing a batch store: the value for {} breaks rule {}",
+                    entry.key(),
+                    failure.rule()
+                );
+                return Ok(Response::ValidationFailed(failure));
+            }
+        }
+        let keys = entries
+            .iter()
+            .map(|entry| entry.key().to_string())
+            .collect::<Vec<_>>();
+        let (mut overwritten, mut conflicts) = (vec![], vec![]);
+        let classify = |db: &dyn StorageBackend| -> Result<(Vec<String>, Vec<String>)> {
+            let (mut overwritten, mut conflicts) = (vec![], vec![]);
+            for entry in entries {
+                // A missing table (nothing stored yet) means no key exists.
+                let exists = read_value(db, SALUS_VAL_TABLE_DEF, entry.key())?.is_some();
+                if exists && entry.force() {
+                    overwritten.push(entry.key().to_string());
+                } else if exists {
+                    conflicts.push(entry.key().to_string());
+                }
+            }
+            Ok((overwritten, conflicts))
+        };
+
+        let (mut applied, mut versions) = (false, vec![]);
+        if dry_run {
+            read_backend(&self.backend, |db| -> Result<()> {
+                (overwritten, conflicts) = classify(db)?;
+                Ok(())
+            })?;
+        } else {
+            // Sealed up front so the lookup and the write share one hold of
+            // the backend, and nothing can land between them.
+            let mut sealed = Vec::with_capacity(entries.len());
+            for entry in entries {
+                let value = entry.value().as_bytes().to_vec();
+                sealed.push((
+                    entry.key().to_string(),
+                    self.seal_value(enc_key, entry.key(), value)?,
+                ));
+            }
+            let locked = sealed.iter().map(|(key, _)| (Table::Values, key.as_str()));
+            write_keys(&self.backend, locked, |db| -> Result<()> {
+                (overwritten, conflicts) = classify(db)?;
+                applied = conflicts.is_empty();
+                if applied {
+                    let mut ops = sealed
+                        .iter()
+                        .map(|(key, value)| put(SALUS_VAL_TABLE_DEF, key, value))
+                        .collect::<Vec<_>>();
+                    for (key, _) in &sealed {
+                        let existing = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+                        let (revised, version) =
+                            self.revised(db, key, existing.as_ref(), Some(now()))?;
+                        ops.extend(revised);
+                        versions.push((key.clone(), version));
+                    }
+                    db.commit(ops)?;
+                    self.read_cache
+                        .invalidate(sealed.iter().map(|(key, _)| key.as_str()));
+                }
+                Ok(())
+            })?;
+        }
+        for (key, version) in &versions {
+            self.changes.written(key, *version);
+        }
+        if applied {
+            info!("Stored {} values in one batch", keys.len());
+        } else if !conflicts.is_empty() {
+            info!(
+                "Refusing batch store: {} existing key(s) without force",
+                conflicts.len()
+            );
+        }
+        Ok(Response::BatchStored(
+            BatchOutcome::builder()
+                .keys(keys)
+                .overwritten(overwritten)
+                .conflicts(conflicts)
+                .applied(applied)
+                .build(),
+        ))
+    }
+
     pub(crate) fn read(&self, key: &str) -> Result<Response> {
         if let Some(enc_key) = &self.key {
-            let mut response = Response::KeyNotFound;
-            unlock_redb(&self.redb, |db| -> Result<()> {
-                match read_value::<String, SalusVal>(db, SALUS_VAL_TABLE_DEF, key.to_string()) {
+            if let Some(plaintext) = self.read_cache.get(key) {
+                trace!("Read value for key {key} from the read cache");
+                return Ok(Response::Value(Some(plaintext.to_vec())));
+            }
+            let epoch = self.read_cache.epoch();
+            let mut found = None;
+            read_backend(&self.backend, |db| -> Result<()> {
+                match alias::chain(db, key) {
                     Err(e) => {
                         error!("Error reading value from database: {e}");
                         return Err(e);
                     }
-                    Ok(None) => {
-                        info!("Key not found: {key}");
-                        response = Response::Value(None);
-                    }
-                    Ok(Some(svag)) => {
-                        let sv = svag.value();
-                        let nonce = Nonce::from(&sv.nonce()?);
-                        let rnkey = RandomizedNonceKey::new(&AES_256_GCM, enc_key)
-                            .with_context(|| Error::NonceKeyGen)?;
-                        let mut ciphertext = sv.ciphertext()?.to_vec();
-                        match rnkey.open_in_place(nonce, Aad::from(key.as_bytes()), &mut ciphertext)
-                        {
-                            Err(e) => {
-                                error!("Error decrypting value: {e}");
-                                return Err(e.into());
-                            }
-                            Ok(plaintext_b) => {
-                                trace!("Read and decrypted value for key {key}");
-                                response = Response::Value(Some(plaintext_b.to_vec()));
-                            }
-                        }
-                    }
+                    Ok((_, None)) => info!("Key not found: {key}"),
+                    Ok((path, Some(value))) => found = Some((path, value)),
                 }
                 Ok(())
             })?;
-            Ok(response)
+            let Some((path, sealed)) = found else {
+                return Ok(Response::Value(None));
+            };
+            // The value is sealed to the key it is stored under, at the end
+            // of any aliases followed, and cached under it too.
+            let stored = path.last().map_or(key, String::as_str);
+            if sealed.blob_id()?.is_some() {
+                return Ok(Response::Streamed(Self::open_manifest(
+                    enc_key, stored, &sealed,
+                )?));
+            }
+            match self.open_value(enc_key, stored, &sealed) {
+                Err(e) => {
+                    error!("Error decrypting value: {e}");
+                    Err(e)
+                }
+                Ok(plaintext) => {
+                    trace!("Read and decrypted value for key {stored}");
+                    self.read_cache.insert(epoch, stored, &plaintext);
+                    Ok(Response::Value(Some(plaintext)))
+                }
+            }
         } else {
             Err(Error::StoreNotUnlocked.into())
         }
     }
 
-    pub(crate) fn delete(&self, key: &str) -> Result<Response> {
-        if self.key.is_none() {
+    /// Report what is stored under `key`, following its aliases, without
+    /// opening it, so the store need not be unlocked.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the aliases loop or go too deep, or the database
+    ///   cannot be read.
+    pub(crate) fn exists(&self, key: &str) -> Result<Response> {
+        let mut response = Response::KeyNotFound;
+        read_backend(&self.backend, |db| -> Result<()> {
+            let (path, Some(sealed)) = alias::chain(db, key)? else {
+                return Ok(());
+            };
+            let stored = path.last().map_or(key, String::as_str);
+            let chunks = match sealed.blob_id()? {
+                Some(id) => Some(blob::chunk_bytes(db, &id)?),
+                None => None,
+            };
+            let row = u64::try_from(sealed.stored_len()).unwrap_or(u64::MAX);
+            response = Response::KeyStat(
+                KeyStat::builder()
+                    .key(stored)
+                    .stored_bytes(row.saturating_add(chunks.unwrap_or(0)))
+                    .chunked(chunks.is_some())
+                    .versions(read_value(db, SALUS_VERSIONS_TABLE_DEF, stored)?.unwrap_or(0))
+                    .maybe_written(read_value(db, SALUS_WRITTEN_TABLE_DEF, stored)?)
+                    .build(),
+            );
+            Ok(())
+        })?;
+        Ok(response)
+    }
+
+    /// Read the JSON document under `key` and answer with only the field at
+    /// `pointer`: a string as its text, anything else as JSON. A missing or
+    /// streamed value is answered as [`read`](Self::read) answers it.
+    pub(crate) fn read_field(&self, key: &str, pointer: &str) -> Result<Response> {
+        let plaintext = match self.read(key)? {
+            Response::Value(Some(plaintext)) => Zeroizing::new(plaintext),
+            response => return Ok(response),
+        };
+        let document: serde_json::Value =
+            serde_json::from_slice(&plaintext).map_err(|_e| Error::NotJson(key.to_string()))?;
+        let Some(field) = document.pointer(pointer) else {
+            return Err(Error::NoSuchField(key.to_string(), pointer.to_string()).into());
+        };
+        let field = match field {
+            serde_json::Value::String(text) => text.as_bytes().to_vec(),
+            other => serde_json::to_vec(other)?,
+        };
+        trace!("Read field {pointer} of the value for key {key}");
+        Ok(Response::Value(Some(field)))
+    }
+
+    /// Apply the JSON merge patch `patch` (RFC 7396) to the document under
+    /// `key` and store the result. The document is read, patched and written
+    /// under the key's write lock, so no other write lands in between; a value
+    /// sealed under a named key is sealed again under its newest version.
+    ///
+    /// That named key is looked up, and rotated when it is due, before the
+    /// value's lock is taken: rotating takes the keyring's lock, which may
+    /// share a stripe with the value's, and the stripes are not reentrant.
+    pub(crate) fn patch(&self, key: &str, patch: &str) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
             return Err(Error::StoreNotUnlocked.into());
+        };
+        let patch: serde_json::Value =
+            serde_json::from_str(patch).map_err(|_e| Error::PatchNotJson)?;
+        let mut current = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            current = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+            Ok(())
+        })?;
+        let resolved = match current.map(|current| current.named_key()).transpose()? {
+            Some(Some((name, _))) => self
+                .named_key_for_write(enc_key, &name)?
+                .map(|newest| (name, newest)),
+            _ => None,
+        };
+        let mut patch = Some(patch);
+        let (mut response, mut version) = (Response::Success, 0);
+        self.write_value_row(key, |db, existing| -> Result<()> {
+            let (Some(existing), Some(patch)) = (existing, patch.take()) else {
+                response = Response::KeyNotFound;
+                return Ok(());
+            };
+            let plaintext = Zeroizing::new(self.open_value(enc_key, key, &existing)?);
+            let mut document: serde_json::Value =
+                serde_json::from_slice(&plaintext).map_err(|_e| Error::NotJson(key.to_string()))?;
+            merge_patch(&mut document, patch);
+            let mut value = serde_json::to_vec(&document)?;
+            if let Err(failure) = self.rules.check(key, &value) {
+                value.zeroize();
+                info!(
+                    "Refusing a patch of {key} that breaks rule {}",
+                    failure.rule()
+                );
+                response = Response::ValidationFailed(failure);
+                return Ok(());
+            }
+            let sealed = match existing.named_key()? {
+                None => self.seal_value(enc_key, key, value)?,
+                Some((name, _)) => {
+                    // Looked at again under the lock: a write since may have
+                    // sealed the value under another named key, which is then
+                    // only read here, and rotates on its next write instead.
+                    let newest = match &resolved {
+                        Some((resolved, newest)) if *resolved == name => Some(newest.clone()),
+                        _ => self.newest_named_key(enc_key, &name)?,
+                    };
+                    let Some((newest, material)) = newest else {
+                        value.zeroize();
+                        response = Response::NamedKeyNotFound(name);
+                        return Ok(());
+                    };
+                    self.seal_named(&name, newest, &material, key, value)?
+                }
+            };
+            let mut ops = vec![put(SALUS_VAL_TABLE_DEF, key, &sealed)];
+            let (revised, next) = self.revised(db, key, Some(&existing), Some(now()))?;
+            ops.extend(revised);
+            if let Err(e) = db.commit(ops) {
+                error!("Error writing value to database: {e}");
+                return Err(e);
+            }
+            version = next;
+            self.read_cache.invalidate([key]);
+            info!("Patched value under key: {key}");
+            Ok(())
+        })?;
+        if matches!(response, Response::Success) {
+            self.changes.written(key, version);
+        }
+        Ok(response)
+    }
+
+    /// Decrypt a stored value under the store key, or under the named key
+    /// version it records.
+    fn open_value(&self, enc_key: &[u8], key: &str, sealed: &SalusVal) -> Result<Vec<u8>> {
+        if sealed.blob_id()?.is_some() {
+            return Err(Error::StreamedValue(key.to_string()).into());
         }
+        let Some((name, version)) = sealed.named_key()? else {
+            return open_stored(enc_key, key, sealed);
+        };
+        let material = self
+            .named_key_version(enc_key, &name, version)?
+            .ok_or(Error::NamedKeyMissing(name, version))?;
+        open_stored(&material, key, sealed)
+    }
+
+    /// Seal `value` under `key` with the newest version of the named key when
+    /// one is given, or else with `enc_key`. `None` when there is no such named
+    /// key.
+    fn seal_for(
+        &self,
+        enc_key: &[u8],
+        key: &str,
+        mut value: Vec<u8>,
+        named_key: Option<&str>,
+    ) -> Result<Option<SalusVal>> {
+        let Some(name) = named_key else {
+            return Ok(Some(self.seal_value(enc_key, key, value)?));
+        };
+        let Some((version, material)) = self.named_key_for_write(enc_key, name)? else {
+            value.zeroize();
+            return Ok(None);
+        };
+        Ok(Some(self.seal_named(name, version, &material, key, value)?))
+    }
+
+    /// Seal `value` under `key` with version `version` of the named key
+    /// `name`, whose key is `material`, recording the version that sealed it.
+    fn seal_named(
+        &self,
+        name: &str,
+        version: u32,
+        material: &[u8],
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<SalusVal> {
+        let sealed = self.seal_value(material, key, value)?;
+        let named =
+            SalusVal::from_named_parts(name, version, sealed.nonce()?, sealed.ciphertext()?)?;
+        Ok(if sealed.is_compressed() {
+            named.into_compressed()
+        } else {
+            named
+        })
+    }
+
+    /// Seal `value` under `key` with `enc_key`, compressing it first when the
+    /// compression settings call for it.
+    fn seal_value(&self, enc_key: &[u8], key: &str, mut value: Vec<u8>) -> Result<SalusVal> {
+        let Some(mut compressed) = self.compression.compress(&value)? else {
+            return seal(enc_key, key, &mut value);
+        };
+        value.zeroize();
+        Ok(seal(enc_key, &compressed_aad(key), &mut compressed)?.into_compressed())
+    }
+
+    /// Decrypt every value whose key starts with `prefix`, in key order.
+    ///
+    /// The `CHECK_KEY` sentinel row is never returned. Any value that fails to
+    /// decrypt fails the whole request rather than being silently skipped.
+    pub(crate) fn read_prefix(&self, prefix: &str) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        trace!("Reading values under prefix: {prefix}");
+        let mut sealed = vec![];
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = scan_values(db, SALUS_VAL_TABLE_DEF, prefix)?;
+            sealed.retain(|(key, _)| key != CHECK_KEY_KEY);
+            Ok(())
+        })?;
+        // Opened once the database is released: a value under a named key
+        // reads its keyring.
+        let values = sealed
+            .into_iter()
+            .map(|(key, value)| Ok((key.clone(), self.open_value(enc_key, &key, &value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Response::Values(values))
+    }
+
+    pub(crate) fn delete(&self, key: &str) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
         let mut removed = false;
-        unlock_redb(&self.redb, |db| -> Result<()> {
-            match delete_value::<String, SalusVal>(db, SALUS_VAL_TABLE_DEF, key.to_string()) {
+        self.write_value_row(key, |db, existing| -> Result<()> {
+            // A value stored in chunks goes with its chunks, unless other
+            // values share them. A key with no value may be an alias, which
+            // goes on its own.
+            let deleted = existing.as_ref().map_or_else(
+                || alias::unlink(db, key),
+                |existing| {
+                    let mut ops = Self::release_chunks(db, enc_key, key, Some(existing))?;
+                    ops.push(WriteOp::Delete {
+                        table: Table::Values,
+                        key: key.to_string(),
+                    });
+                    for table in [
+                        Table::Written,
+                        Table::Versions,
+                        Table::RotateAfter,
+                        Table::Authors,
+                    ] {
+                        ops.push(WriteOp::Delete {
+                            table,
+                            key: key.to_string(),
+                        });
+                    }
+                    ops.extend(history::revision_keys(db, key)?.into_iter().map(|row| {
+                        WriteOp::Delete {
+                            table: Table::History,
+                            key: row,
+                        }
+                    }));
+                    db.commit(ops)?;
+                    Ok(true)
+                },
+            );
+            match deleted {
                 Err(e) => {
                     error!("Error deleting value from database: {e}");
                     return Err(e);
                 }
                 Ok(existed) => {
+                    self.read_cache.invalidate([key]);
                     removed = existed;
                     if existed {
                         info!("Deleted value under key: {key}");
@@ -355,12 +1146,97 @@ impl ShareStore {
             Ok(())
         })?;
         if removed {
+            self.changes.deleted(key);
             Ok(Response::Success)
         } else {
             Ok(Response::KeyNotFound)
         }
     }
 
+    /// Delete every value and alias whose key starts with `prefix` in one
+    /// write, or only list their keys for a `dry_run`.
+    ///
+    /// The keys are those under the prefix when the request is served; one
+    /// stored under it meanwhile is left alone.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the database cannot be
+    ///   read or written.
+    pub(crate) fn delete_prefix(&self, prefix: &str, dry_run: bool) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let mut keys = BTreeSet::new();
+        read_backend(&self.backend, |db| -> Result<()> {
+            keys.extend(scan_keys(db, SALUS_VAL_TABLE_DEF, prefix)?);
+            keys.extend(scan_keys(db, SALUS_ALIASES_TABLE_DEF, prefix)?);
+            let _ = keys.remove(CHECK_KEY_KEY);
+            Ok(())
+        })?;
+        if dry_run || keys.is_empty() {
+            return Ok(Response::Deleted(keys.into_iter().collect()));
+        }
+        let mut locks = keys
+            .iter()
+            .map(|key| (Table::Values, key.as_str()))
+            .collect::<Vec<_>>();
+        locks.push((Table::BlobRefs, blob::REFS_LOCK));
+        locks.push((Table::Aliases, alias::ALIASES_LOCK));
+        let mut deleted = vec![];
+        write_keys(&self.backend, locks, |db| -> Result<()> {
+            let (mut values, mut ops) = (vec![], vec![]);
+            for key in &keys {
+                let value = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+                let aliased = read_value(db, SALUS_ALIASES_TABLE_DEF, key)?.is_some();
+                if let Some(value) = value {
+                    values.push((key.as_str(), value));
+                    for table in [
+                        Table::Values,
+                        Table::Written,
+                        Table::Versions,
+                        Table::RotateAfter,
+                        Table::Authors,
+                    ] {
+                        ops.push(WriteOp::Delete {
+                            table,
+                            key: key.clone(),
+                        });
+                    }
+                    ops.extend(history::revision_keys(db, key)?.into_iter().map(|row| {
+                        WriteOp::Delete {
+                            table: Table::History,
+                            key: row,
+                        }
+                    }));
+                } else if !aliased {
+                    continue;
+                }
+                if aliased {
+                    ops.push(WriteOp::Delete {
+                        table: Table::Aliases,
+                        key: key.clone(),
+                    });
+                }
+                deleted.push(key.clone());
+            }
+            // Values that share chunks may fall under the prefix together.
+            let released = values.iter().map(|(key, value)| (*key, value));
+            ops.extend(Self::release_all_chunks(db, enc_key, released)?);
+            if !ops.is_empty() {
+                db.commit(ops)?;
+            }
+            self.read_cache
+                .invalidate(deleted.iter().map(String::as_str));
+            Ok(())
+        })?;
+        for key in &deleted {
+            self.changes.deleted(key);
+        }
+        info!("Deleted {} keys under prefix: {prefix}", deleted.len());
+        Ok(Response::Deleted(deleted))
+    }
+
     pub(crate) fn find(&self, regex: &str) -> Result<Response> {
         // Key names are only revealed to an unlocked client: the less exposed
         // while locked, the better.
@@ -370,15 +1246,14 @@ impl ShareStore {
         let mut matches = vec![];
         trace!("Finding keys matching regex: {regex}");
         let re = Regex::new(regex).with_context(|| Error::InvalidRegex)?;
+        // Only the keys that could match are read: all of them unless the
+        // regex is anchored to a literal start.
+        let prefix = literal_prefix(regex);
 
-        unlock_redb(&self.redb, |db| -> Result<()> {
-            let read_txn = db.begin_read()?;
-            let table = read_txn.open_table(SALUS_VAL_TABLE_DEF)?;
-            for iter_res in table.iter()? {
-                let (key_bytes, _) = iter_res.with_context(|| Error::TableIterRead)?;
-                let key_str = key_bytes.value();
-                if re.is_match(&key_str) {
-                    matches.push(key_str.clone());
+        read_backend(&self.backend, |db| -> Result<()> {
+            for key in scan_keys(db, SALUS_VAL_TABLE_DEF, &prefix)? {
+                if re.is_match(&key) {
+                    matches.push(key);
                 }
             }
             Ok(())
@@ -397,14 +1272,10 @@ impl ShareStore {
         }
         trace!("Searching keys for query: {query}");
         let mut keys = vec![];
-        unlock_redb(&self.redb, |db| -> Result<()> {
-            let read_txn = db.begin_read()?;
-            let table = read_txn.open_table(SALUS_VAL_TABLE_DEF)?;
-            for iter_res in table.iter()? {
-                let (key_bytes, _) = iter_res.with_context(|| Error::TableIterRead)?;
-                let key_str = key_bytes.value();
-                if key_str != CHECK_KEY_KEY {
-                    keys.push(key_str.clone());
+        read_backend(&self.backend, |db| -> Result<()> {
+            for key in scan_keys(db, SALUS_VAL_TABLE_DEF, "")? {
+                if key != CHECK_KEY_KEY {
+                    keys.push(key);
                 }
             }
             Ok(())
@@ -413,25 +1284,203 @@ impl ShareStore {
     }
 }
 
+/// The literal text every match of `regex` starts with, when it is anchored
+/// to the start of the key: `^app/db` gives `app/db`. The prefix ends at the
+/// first thing it cannot be sure of, and is empty for an unanchored regex.
+fn literal_prefix(regex: &str) -> String {
+    // An alternation can match without the prefix.
+    let Some(rest) = regex.strip_prefix('^').filter(|_| !regex.contains('|')) else {
+        return String::new();
+    };
+    let mut prefix = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => escaped,
+                _ => break,
+            },
+            '.' | '[' | ']' | '(' | ')' | '{' | '}' | '*' | '+' | '?' | '^' | '$' => break,
+            c => c,
+        };
+        // The character may not be there at all.
+        if matches!(chars.peek(), Some('?' | '*' | '{')) {
+            break;
+        }
+        prefix.push(literal);
+    }
+    prefix
+}
+
+/// Decrypt a stored value, checking it was sealed for `key`.
+/// The HKDF `info` binding a derived key to its purpose.
+const KDF_INFO: &[u8] = b"salus store key v1";
+
+/// An HKDF output length.
+struct KeyLen(usize);
+
+impl hkdf::KeyType for KeyLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// The AES-GCM key for `key`, sized by its length: 16 bytes is AES-128,
+/// anything else AES-256 (which refuses a wrong length).
+fn aead_key(key: &[u8]) -> Result<RandomizedNonceKey> {
+    let algorithm = if key.len() == KeyAlgorithm::Aes128Gcm.key_len() {
+        &AES_128_GCM
+    } else {
+        &AES_256_GCM
+    };
+    RandomizedNonceKey::new(algorithm, key).with_context(|| Error::NonceKeyGen)
+}
+
+/// The store key, given the key the shares reconstructed.
+///
+/// Until the shares are first refreshed they split the store key itself; after
+/// that they split a share key that unseals the `WRAPPED_KEY` record.
+fn unwrap_store_key(
+    db: &dyn StorageBackend,
+    share_key: &RandomizedNonceKey,
+    reconstructed: &Zeroizing<Vec<u8>>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let Some(wrapped) = read_value(db, SALUS_CONFIG_TABLE_DEF, WRAPPED_KEY_KEY)? else {
+        return Ok(reconstructed.clone());
+    };
+    let (nonce, mut sealed) = wrapped.to_value::<([u8; 12], Vec<u8>)>()?;
+    let store_key = share_key
+        .open_in_place(
+            Nonce::from(&nonce),
+            Aad::from(WRAPPED_KEY_KEY.as_bytes()),
+            &mut sealed,
+        )
+        .map(|plaintext| Zeroizing::new(plaintext.to_vec()));
+    sealed.zeroize();
+    Ok(store_key?)
+}
+
+/// The index of `share` and a SHA-256 digest of its index and bytes.
+///
+/// The digest covers the decoded share rather than its string, which carries a
+/// random nonce, so any form of the same share verifies.
+fn share_digest(share: &str) -> Result<(u8, Vec<u8>)> {
+    let (index, data) = share_parts(share)?;
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(SHARE_DIGESTS_KEY.as_bytes());
+    context.update(&[index]);
+    context.update(&data);
+    Ok((index, context.finish().as_ref().to_vec()))
+}
+
+fn open(enc_key: &[u8], key: &str, sv: &SalusVal) -> Result<Vec<u8>> {
+    let nonce = Nonce::from(&sv.nonce()?);
+    let rnkey = aead_key(enc_key)?;
+    let mut ciphertext = sv.ciphertext()?.to_vec();
+    let plaintext = rnkey
+        .open_in_place(nonce, Aad::from(key.as_bytes()), &mut ciphertext)?
+        .to_vec();
+    ciphertext.zeroize();
+    Ok(plaintext)
+}
+
+/// Open a stored value, decompressing it if it was compressed before it was
+/// sealed.
+fn open_stored(enc_key: &[u8], key: &str, sealed: &SalusVal) -> Result<Vec<u8>> {
+    if !sealed.is_compressed() {
+        return open(enc_key, key, sealed);
+    }
+    let compressed = Zeroizing::new(open(enc_key, &compressed_aad(key), sealed)?);
+    let mut value = decompress(&compressed)?;
+    Ok(std::mem::take(&mut *value))
+}
+
+/// Encrypt `value` in place under `enc_key`, binding it to `key` as AAD.
+fn seal(enc_key: &[u8], key: &str, value: &mut Vec<u8>) -> Result<SalusVal> {
+    let rnkey = aead_key(enc_key)?;
+    let nonce = rnkey.seal_in_place_append_tag(Aad::from(key.as_bytes()), value)?;
+    Ok(SalusVal::from_parts(*nonce.as_ref(), value))
+}
+
+/// Apply the JSON merge patch `patch` to `target` (RFC 7396): an object's
+/// members are merged in one by one, `null` removing a member, and anything
+/// else replaces the target whole.
+fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    let serde_json::Value::Object(members) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let serde_json::Value::Object(document) = target {
+        for (name, value) in members {
+            if value.is_null() {
+                let _removed = document.remove(&name);
+            } else {
+                merge_patch(
+                    document.entry(name).or_insert(serde_json::Value::Null),
+                    value,
+                );
+            }
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
 #[cfg(test)]
-mod test {
-    use std::sync::{Arc, Mutex};
+pub(crate) mod test {
+    use std::{
+        sync::{Arc, mpsc},
+        thread,
+        time::Duration,
+    };
 
     use anyhow::{Result, anyhow, bail};
-    use libsalus::Response;
-    use redb::Database;
+    use libsalus::{
+        Charset, GenerateSecret, Init, KeyAlgorithm, NewNamedKey, Response, SecretSpec, Store,
+        wrap_share,
+    };
 
-    use super::ShareStore;
-    use crate::db::{
-        SALUS_VAL_TABLE_DEF, read_value, unlock_redb, values::salus::SalusVal, write_value,
+    use super::{
+        ShareStore,
+        cache::ReadCache,
+        compress::{Compression, DEFAULT_LEVEL},
+        literal_prefix, merge_patch,
+        rules::Rules,
     };
+    use crate::{
+        config::{ConfigSalusd, HookEvent},
+        db::{
+            SALUS_VAL_TABLE_DEF, SharedBackend,
+            backend::{MemoryBackend, StorageBackend, Table, WriteOp},
+            key_sharing_stripe, read_backend, read_value, unlock_backend, write_value,
+        },
+        hook::Changes,
+    };
+
+    pub(crate) fn temp_store() -> ShareStore {
+        // Each test gets its own in-memory backend. This avoids the filesystem
+        // entirely, so parallel tests can never collide on a shared path.
+        ShareStore::builder()
+            .backend(Arc::new(SharedBackend::new(MemoryBackend::default())))
+            .build()
+    }
 
-    fn temp_store() -> Result<ShareStore> {
-        // Each test gets its own isolated in-memory database. This avoids the
-        // filesystem entirely, so parallel tests can never collide on a shared
-        // path and trigger redb's `DatabaseAlreadyOpen`.
-        let db = Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?;
-        Ok(ShareStore::builder().redb(Arc::new(Mutex::new(db))).build())
+    /// A store with its shares generated and its key unlocked.
+    pub(crate) fn unlocked_store() -> Result<ShareStore> {
+        let mut store = temp_store();
+        let shares = gen_and_collect(&mut store)?;
+        match unlock_with(&mut store, &shares)? {
+            Response::Success => Ok(store),
+            other => bail!("expected an unlock, got {other:?}"),
+        }
     }
 
     fn gen_and_collect(store: &mut ShareStore) -> Result<Vec<String>> {
@@ -443,7 +1492,7 @@ mod test {
 
     #[test]
     fn unlock_with_correct_shares_succeeds() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         // Default threshold is 3.
         for share in shares.iter().take(3) {
@@ -455,14 +1504,25 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn wipe_clears_the_key_and_collected_shares() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.add_share("a share collected towards the next unlock");
+        store.wipe();
+        assert!(store.key.is_none());
+        assert!(store.shares.is_empty());
+        assert_eq!(store.key_generation(), 2);
+        Ok(())
+    }
+
     #[test]
     fn unlock_with_wrong_shares_fails_without_panic() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let _shares = gen_and_collect(&mut store)?;
 
         // Shares from a *different* store reconstruct a different (wrong) key, so
         // the sentinel GCM open fails. This used to `.unwrap()`-panic (H2).
-        let mut other = temp_store()?;
+        let mut other = temp_store();
         let wrong = gen_and_collect(&mut other)?;
         for share in wrong.iter().take(3) {
             store.add_share(share.clone());
@@ -473,9 +1533,204 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn verify_share_recognizes_only_the_current_set() -> Result<()> {
+        let mut other = temp_store();
+        let foreign = gen_and_collect(&mut other)?;
+        let Some(first_foreign) = foreign.first() else {
+            bail!("no shares");
+        };
+        // A store without shares has nothing to verify against.
+        let mut store = temp_store();
+        let Err(e) = store.verify_share(first_foreign) else {
+            bail!("a share was checked before any were generated");
+        };
+        assert!(e.to_string().contains("cannot be verified"));
+
+        let shares = gen_and_collect(&mut store)?;
+        for (share, index) in shares.iter().zip(1u8..) {
+            assert!(matches!(
+                store.verify_share(share)?,
+                Response::ShareVerified(idx) if idx == index
+            ));
+        }
+        // Any form of the share verifies, and the store stays sealed.
+        let Some(first) = shares.first() else {
+            bail!("no shares");
+        };
+        assert!(matches!(
+            store.verify_share(&wrap_share(first)?)?,
+            Response::ShareVerified(1)
+        ));
+        assert!(store.key.is_none());
+
+        for share in &foreign {
+            assert!(matches!(
+                store.verify_share(share)?,
+                Response::ShareNotRecognized
+            ));
+        }
+        assert!(store.verify_share("not a share").is_err());
+        Ok(())
+    }
+
+    fn unlock_with(store: &mut ShareStore, shares: &[String]) -> Result<Response> {
+        for share in shares.iter().take(3) {
+            store.add_share(share.clone());
+        }
+        store.unlock()
+    }
+
+    #[test]
+    fn initialize_refuses_unusable_or_late_parameters() -> Result<()> {
+        let mut store = temp_store();
+        for (num_shares, threshold) in [(5, 1), (3, 4), (0, 0)] {
+            let init = Init::builder()
+                .num_shares(num_shares)
+                .threshold(threshold)
+                .build();
+            assert!(matches!(
+                store.initialize(init)?,
+                Response::InvalidShareParameters(_)
+            ));
+        }
+        let init = Init::builder().num_shares(4).threshold(2).build();
+        assert!(matches!(store.initialize(init)?, Response::Success));
+        assert_eq!(gen_and_collect(&mut store)?.len(), 4);
+        // Once the shares exist their parameters stay as recorded.
+        let init = Init::builder().num_shares(9).threshold(9).build();
+        assert!(matches!(
+            store.initialize(init)?,
+            Response::AlreadyInitialiazed
+        ));
+        assert_eq!(store.get_threshold(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn configured_defaults_apply_until_the_shares_are_recorded() -> Result<()> {
+        let mut store = ShareStore::builder()
+            .backend(Arc::new(SharedBackend::new(MemoryBackend::default())))
+            .default_num_shares(7)
+            .default_threshold(4)
+            .build();
+        match store.status()? {
+            Response::Status(status) => {
+                assert_eq!(status.num_shares(), 7);
+                assert_eq!(status.threshold(), 4);
+            }
+            other => bail!("expected status, got {other:?}"),
+        }
+        assert_eq!(store.get_threshold(), 4);
+        assert_eq!(gen_and_collect(&mut store)?.len(), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn refresh_retires_the_old_shares_and_keeps_the_values() -> Result<()> {
+        let mut store = temp_store();
+        let original = gen_and_collect(&mut store)?;
+        assert!(store.refresh_shares().is_err());
+        assert!(matches!(
+            unlock_with(&mut store, &original)?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.store("alpha", b"kept".to_vec(), false)?,
+            Response::Success
+        ));
+        let before = store.fingerprint()?;
+
+        let mut current = original.clone();
+        for epoch in 1..=2u64 {
+            let previous = current;
+            current = match store.refresh_shares()? {
+                Response::Shares(shares) => shares.shares().to_vec(),
+                other => bail!("expected shares, got {other:?}"),
+            };
+            assert_eq!(current.len(), 5);
+            store.lock();
+            assert!(matches!(
+                unlock_with(&mut store, &previous)?,
+                Response::UnlockFailed
+            ));
+            for share in &previous {
+                assert!(matches!(
+                    store.verify_share(share)?,
+                    Response::ShareNotRecognized
+                ));
+            }
+            assert!(matches!(
+                unlock_with(&mut store, &current)?,
+                Response::Success
+            ));
+            assert!(matches!(
+                store.read("alpha")?,
+                Response::Value(Some(ref value)) if value == b"kept"
+            ));
+            match store.status()? {
+                Response::Status(status) => {
+                    assert_eq!(status.share_epoch(), epoch);
+                    assert_ne!(status.fingerprint(), &before);
+                }
+                other => bail!("expected status, got {other:?}"),
+            }
+        }
+        assert!(matches!(
+            unlock_with(&mut store, &original)?,
+            Response::UnlockFailed
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn aes_128_with_a_kdf_unlocks_and_refreshes() -> Result<()> {
+        let mut store = temp_store();
+        let init = Init::builder()
+            .num_shares(5)
+            .threshold(3)
+            .algorithm(KeyAlgorithm::Aes128Gcm)
+            .kdf(true)
+            .build();
+        assert!(matches!(store.initialize(init)?, Response::Success));
+        let shares = gen_and_collect(&mut store)?;
+        // A 16-byte secret makes shorter shares than the default 32.
+        let (_, secret) = libsalus::share_parts(shares.first().map_or("", String::as_str))?;
+        assert_eq!(secret.len(), 16);
+        assert!(matches!(
+            unlock_with(&mut store, &shares)?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.store("alpha", b"short key".to_vec(), false)?,
+            Response::Success
+        ));
+        let refreshed = match store.refresh_shares()? {
+            Response::Shares(shares) => shares.shares().to_vec(),
+            other => bail!("expected shares, got {other:?}"),
+        };
+        store.lock();
+        assert!(matches!(
+            unlock_with(&mut store, &refreshed)?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.read("alpha")?,
+            Response::Value(Some(ref value)) if value == b"short key"
+        ));
+        match store.status()? {
+            Response::Status(status) => {
+                assert_eq!(status.key_algorithm(), KeyAlgorithm::Aes128Gcm);
+                assert!(status.kdf());
+            }
+            other => bail!("expected status, got {other:?}"),
+        }
+        Ok(())
+    }
+
     #[test]
     fn delete_removes_stored_value() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         for share in shares.iter().take(3) {
             store.add_share(share.clone());
@@ -495,9 +1750,107 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn committed_changes_are_reported_with_their_versions() -> Result<()> {
+        let mut store = unlocked_store()?;
+        let (changes, mut reported) = Changes::channel();
+        store.changes = changes;
+        let _stored = store.store("app/db", br#"{"user":"a"}"#.to_vec(), false)?;
+        let _refused = store.store("app/db", b"b".to_vec(), false)?;
+        let _patched = store.patch("app/db", r#"{"user":"b"}"#)?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app").build())?;
+        let _rotated = store.rotate_named_key("app")?;
+        let _deleted = store.delete("app/db")?;
+        let _stored = store.store("app/db", b"c".to_vec(), false)?;
+        let mut received = vec![];
+        while let Ok(change) = reported.try_recv() {
+            received.push((change.event(), change.key().clone(), change.version()));
+        }
+        let change = |event, key: &str, version| (event, key.to_string(), version);
+        assert_eq!(
+            received,
+            [
+                change(HookEvent::Written, "app/db", Some(1)),
+                change(HookEvent::Written, "app/db", Some(2)),
+                change(HookEvent::Rotated, "app", Some(2)),
+                change(HookEvent::Deleted, "app/db", None),
+                change(HookEvent::Written, "app/db", Some(1)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn values_that_break_a_rule_are_not_stored() -> Result<()> {
+        let mut store = unlocked_store()?;
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(
+                "[validation.db]\nkeys = [\"app/\"]\n\
+                 schema = '{\"type\": \"object\", \"required\": [\"password\"]}'\n",
+                ::config::FileFormat::Toml,
+            ))
+            .build()?
+            .try_deserialize()?;
+        store.set_rules(Arc::new(Rules::from(&config)));
+        let refused = |response| match response {
+            Response::ValidationFailed(failure) => failure.rule() == "db",
+            _ => false,
+        };
+        assert!(refused(store.store(
+            "app/db",
+            b"hunter2".to_vec(),
+            false
+        )?));
+        assert!(matches!(store.read("app/db")?, Response::Value(None)));
+        let document = br#"{"password":"hunter2"}"#.to_vec();
+        assert!(matches!(
+            store.store("app/db", document, false)?,
+            Response::Success
+        ));
+        assert!(refused(store.patch("app/db", r#"{"password":null}"#)?));
+        let batch = [
+            Store::builder().key("web/a").value("1").build(),
+            Store::builder().key("app/b").value("2").build(),
+        ];
+        assert!(refused(store.store_batch(&batch, false)?));
+        assert!(matches!(store.read("web/a")?, Response::Value(None)));
+        assert!(matches!(
+            store.store("web/a", b"1".to_vec(), false)?,
+            Response::Success
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_stores_of_one_key_write_it_once() -> Result<()> {
+        let store = unlocked_store()?;
+        let stored = thread::scope(|scope| {
+            (0..8u8)
+                .map(|i| {
+                    let store = &store;
+                    scope.spawn(move || store.store("alpha", vec![i], false))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|write| write.join().map_err(|_| anyhow!("a store panicked"))?)
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let written = stored
+            .iter()
+            .filter(|response| matches!(response, Response::Success))
+            .count();
+        assert_eq!(written, 1);
+        assert!(
+            stored
+                .iter()
+                .all(|response| matches!(response, Response::Success | Response::KeyExists))
+        );
+        Ok(())
+    }
+
     #[test]
     fn store_refuses_overwrite_without_force() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         for share in shares.iter().take(3) {
             store.add_share(share.clone());
@@ -533,24 +1886,450 @@ mod test {
     }
 
     #[test]
-    fn delete_before_unlock_errors() -> Result<()> {
-        let store = temp_store()?;
-        assert!(store.delete("alpha").is_err());
+    fn cached_reads_follow_writes_deletes_and_lock() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.read_cache = ReadCache::new(8, Duration::from_secs(30));
+        let read = |store: &ShareStore| -> Result<Option<Vec<u8>>> {
+            match store.read("alpha")? {
+                Response::Value(value) => Ok(value),
+                other => bail!("expected a value, got {other:?}"),
+            }
+        };
+        let _stored = store.store("alpha", b"first".to_vec(), false)?;
+        assert_eq!(read(&store)?.as_deref(), Some(&b"first"[..]));
+
+        // Removed behind the store's back, the value is still served cached.
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            db.commit(vec![WriteOp::Delete {
+                table: Table::Values,
+                key: "alpha".to_string(),
+            }])
+        })?;
+        assert_eq!(read(&store)?.as_deref(), Some(&b"first"[..]));
+
+        let _stored = store.store("alpha", b"second".to_vec(), true)?;
+        assert_eq!(read(&store)?.as_deref(), Some(&b"second"[..]));
+        let _deleted = store.delete("alpha")?;
+        assert_eq!(read(&store)?, None);
+
+        let _stored = store.store("alpha", b"third".to_vec(), false)?;
+        assert_eq!(read(&store)?.as_deref(), Some(&b"third"[..]));
+        store.clear_key();
+        assert!(store.read_cache.get("alpha").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_values_read_back_whole() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.compression = Compression::new(64, DEFAULT_LEVEL)?;
+        let json = r#"{"user":"svc","password":"hunter2"}"#.repeat(20);
+        let _stored = store.store("app/config", json.as_bytes().to_vec(), false)?;
+        let _stored = store.store("app/short", b"hunter2".to_vec(), false)?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app").build())?;
+        let request = Store::builder()
+            .key("app/named")
+            .value(json.clone())
+            .build();
+        let _stored = store.store_with_key("app", &request)?;
+        for (key, compressed) in [
+            ("app/config", true),
+            ("app/short", false),
+            ("app/named", true),
+        ] {
+            let mut row = None;
+            read_backend(&store.backend, |db| -> Result<()> {
+                row = read_value(db, SALUS_VAL_TABLE_DEF, key)?;
+                Ok(())
+            })?;
+            let Some(row) = row else {
+                bail!("expected a row for {key}");
+            };
+            assert_eq!(row.is_compressed(), compressed);
+            if compressed {
+                assert!(row.ciphertext()?.len() < json.len());
+            }
+        }
+        for key in ["app/config", "app/named"] {
+            let Response::Value(Some(value)) = store.read(key)? else {
+                bail!("expected a value for {key}");
+            };
+            assert_eq!(value, json.as_bytes());
+        }
         Ok(())
     }
 
     #[test]
-    fn find_and_search_before_unlock_error() -> Result<()> {
+    fn a_stripped_compression_flag_fails_to_open() -> Result<()> {
+        let mut store = unlocked_store()?;
+        store.compression = Compression::new(64, DEFAULT_LEVEL)?;
+        let _stored = store.store("app/config", "a".repeat(512).into_bytes(), false)?;
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            let Some(row) = db.get(Table::Values, "app/config")? else {
+                bail!("expected a row");
+            };
+            let Some(stripped) = row.get(8..) else {
+                bail!("expected a flagged row");
+            };
+            db.commit(vec![WriteOp::Put {
+                table: Table::Values,
+                key: "app/config".to_string(),
+                value: stripped.to_vec(),
+            }])
+        })?;
+        assert!(store.read("app/config").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn store_batch_is_all_or_nothing() -> Result<()> {
+        let mut store = temp_store();
+        let shares = gen_and_collect(&mut store)?;
+        for share in shares.iter().take(3) {
+            store.add_share(share.clone());
+        }
+        assert!(matches!(store.unlock()?, Response::Success));
+        assert!(matches!(
+            store.store("app/a", b"old".to_vec(), false)?,
+            Response::Success
+        ));
+        let batch = |force: bool| {
+            vec![
+                Store::builder()
+                    .key("app/a")
+                    .value("new")
+                    .force(force)
+                    .build(),
+                Store::builder()
+                    .key("app/b")
+                    .value("b")
+                    .force(force)
+                    .build(),
+            ]
+        };
+
+        // A dry run reports the plan and writes nothing.
+        match store.store_batch(&batch(true), true)? {
+            Response::BatchStored(outcome) => {
+                assert!(!outcome.applied());
+                assert_eq!(outcome.overwritten(), &["app/a".to_string()]);
+            }
+            other => bail!("expected a batch outcome, got {other:?}"),
+        }
+        assert!(matches!(store.read("app/b")?, Response::Value(None)));
+
+        // An unforced overwrite conflicts, so neither entry is written.
+        match store.store_batch(&batch(false), false)? {
+            Response::BatchStored(outcome) => {
+                assert!(!outcome.applied());
+                assert_eq!(outcome.conflicts(), &["app/a".to_string()]);
+            }
+            other => bail!("expected a batch outcome, got {other:?}"),
+        }
+        assert!(matches!(store.read("app/b")?, Response::Value(None)));
+
+        // Forced, both land.
+        match store.store_batch(&batch(true), false)? {
+            Response::BatchStored(outcome) => assert!(outcome.applied()),
+            other => bail!("expected a batch outcome, got {other:?}"),
+        }
+        match store.read("app/a")? {
+            Response::Value(Some(value)) => assert_eq!(value, b"new"),
+            other => bail!("expected the overwritten value, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn generate_stores_without_returning_unless_shown() -> Result<()> {
+        let spec = SecretSpec::Chars {
+            length: 32,
+            charset: Charset::Hex,
+        };
+        let request = |show: bool, force: bool| {
+            GenerateSecret::builder()
+                .key("db/password")
+                .spec(spec.clone())
+                .show(show)
+                .force(force)
+                .build()
+        };
+        let mut store = temp_store();
+        assert!(store.generate(&request(false, false)).is_err());
+        let shares = gen_and_collect(&mut store)?;
+        for share in shares.iter().take(3) {
+            store.add_share(share.clone());
+        }
+        assert!(matches!(store.unlock()?, Response::Success));
+
+        assert!(matches!(
+            store.generate(&request(false, false))?,
+            Response::Generated(None)
+        ));
+        let Response::Value(Some(first)) = store.read("db/password")? else {
+            bail!("generated value was not stored");
+        };
+        assert_eq!(first.len(), 32);
+        assert!(first.iter().all(u8::is_ascii_hexdigit));
+
+        assert!(matches!(
+            store.generate(&request(true, false))?,
+            Response::KeyExists
+        ));
+        let Response::Generated(Some(shown)) = store.generate(&request(true, true))? else {
+            bail!("expected the generated value to be shown");
+        };
+        assert!(
+            matches!(store.read("db/password")?, Response::Value(Some(v)) if v == shown.as_bytes())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_prefix_returns_only_matching_keys() -> Result<()> {
+        let mut store = temp_store();
+        assert!(store.read_prefix("").is_err());
+        let shares = gen_and_collect(&mut store)?;
+        for share in shares.iter().take(3) {
+            store.add_share(share.clone());
+        }
+        assert!(matches!(store.unlock()?, Response::Success));
+        for key in ["app/a", "app/b", "apq", "other"] {
+            let _stored = store.store(key, key.as_bytes().to_vec(), false)?;
+        }
+        match store.read_prefix("app/")? {
+            Response::Values(values) => assert_eq!(
+                values,
+                vec![
+                    ("app/a".to_string(), b"app/a".to_vec()),
+                    ("app/b".to_string(), b"app/b".to_vec()),
+                ]
+            ),
+            other => bail!("expected values, got {other:?}"),
+        }
+        // The empty prefix lists everything except the internal sentinel.
+        match store.read_prefix("")? {
+            Response::Values(values) => assert_eq!(values.len(), 4),
+            other => bail!("expected values, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn read_field_returns_one_field_of_a_json_value() -> Result<()> {
+        let store = unlocked_store()?;
+        let creds = br#"{"user":"app","password":"hunter2","port":5432,"tls":{"on":true}}"#;
+        let _stored = store.store("db/creds", creds.to_vec(), false)?;
+        let _stored = store.store("plain", b"not json".to_vec(), false)?;
+        for (pointer, expected) in [
+            ("/password", &b"hunter2"[..]),
+            ("/port", b"5432"),
+            ("/tls", br#"{"on":true}"#),
+        ] {
+            match store.read_field("db/creds", pointer)? {
+                Response::Value(Some(value)) => assert_eq!(value, expected),
+                other => bail!("expected a value, got {other:?}"),
+            }
+        }
+        assert!(store.read_field("db/creds", "/missing").is_err());
+        assert!(store.read_field("plain", "/password").is_err());
+        assert!(matches!(
+            store.read_field("absent", "/password")?,
+            Response::Value(None)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7396() -> Result<()> {
+        // Examples from RFC 7396, appendix A.
+        for (target, patch, expected) in [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a":{"b":"d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            ("[1,2]", r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+            ("{}", r#"{"a":{"bb":{"ccc":null}}}"#, r#"{"a":{"bb":{}}}"#),
+        ] {
+            let mut document: serde_json::Value = serde_json::from_str(target)?;
+            merge_patch(&mut document, serde_json::from_str(patch)?);
+            assert_eq!(
+                document,
+                serde_json::from_str::<serde_json::Value>(expected)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn patch_rewrites_a_stored_document() -> Result<()> {
+        let store = unlocked_store()?;
+        let creds = br#"{"user":"app","password":"hunter2","old":true}"#;
+        let _stored = store.store("db/creds", creds.to_vec(), false)?;
+        assert!(matches!(
+            store.patch("db/creds", r#"{"password":"rotated","old":null}"#)?,
+            Response::Success
+        ));
+        match store.read("db/creds")? {
+            Response::Value(Some(value)) => assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(&value)?,
+                serde_json::json!({"user": "app", "password": "rotated"})
+            ),
+            other => bail!("expected a value, got {other:?}"),
+        }
+        assert!(matches!(
+            store.patch("absent", "{}")?,
+            Response::KeyNotFound
+        ));
+        let _stored = store.store("plain", b"not json".to_vec(), false)?;
+        assert!(store.patch("plain", "{}").is_err());
+        assert!(store.patch("db/creds", "{not json").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn patch_rotates_a_named_key_that_is_due() -> Result<()> {
+        let store = unlocked_store()?;
+        let create = NewNamedKey::builder().name("app-a").rotate_after(1).build();
+        let _created = store.create_named_key(&create)?;
+        // The value shares its lock's stripe with the keyring, so rotating the
+        // key under the value's lock would wait on itself.
+        let key = key_sharing_stripe(Table::Values, "db/", (Table::NamedKeys, "app-a"))?;
+        let document = Store::builder().key(&key).value(r#"{"user":"a"}"#).build();
+        assert!(matches!(
+            store.store_with_key("app-a", &document)?,
+            Response::Success
+        ));
+        let sealed_with = |store: &ShareStore| -> Result<Option<(String, u32)>> {
+            let mut sealed_with = None;
+            read_backend(&store.backend, |db| -> Result<()> {
+                if let Some(value) = read_value(db, SALUS_VAL_TABLE_DEF, &key)? {
+                    sealed_with = value.named_key()?;
+                }
+                Ok(())
+            })?;
+            Ok(sealed_with)
+        };
+        let Some((_, stored)) = sealed_with(&store)? else {
+            bail!("expected the value under the named key");
+        };
+        thread::sleep(Duration::from_millis(1_100));
+
+        let (done, patched) = mpsc::channel();
+        let patching = {
+            let key = key.clone();
+            thread::spawn(move || {
+                let patched = store.patch(&key, r#"{"user":"b"}"#);
+                let _sent = done.send(patched.map(|response| (store, response)));
+            })
+        };
+        let Ok(patched) = patched.recv_timeout(Duration::from_secs(5)) else {
+            bail!("the patch did not finish");
+        };
+        let (store, response) = patched?;
+        patching
+            .join()
+            .map_err(|_| anyhow!("the patching thread panicked"))?;
+        assert!(
+            matches!(response, Response::Success),
+            "expected the patch to land, got {response:?}"
+        );
+        let Some((name, patched)) = sealed_with(&store)? else {
+            bail!("expected the patch to stay under the named key");
+        };
+        assert_eq!(name, "app-a");
+        assert!(
+            patched > stored,
+            "expected a rotation past {stored}, got {patched}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn delete_before_unlock_errors() {
+        let store = temp_store();
+        assert!(store.delete("alpha").is_err());
+    }
+
+    #[test]
+    fn find_and_search_before_unlock_error() {
         // Key names must not be enumerable without the key.
-        let store = temp_store()?;
+        let store = temp_store();
         assert!(store.find(".*").is_err());
         assert!(store.search("", None).is_err());
+    }
+
+    #[test]
+    fn exists_reports_a_value_while_the_store_is_sealed() -> Result<()> {
+        let mut store = unlocked_store()?;
+        let _stored = store.store("db/creds", b"first".to_vec(), false)?;
+        let _refused = store.store("db/creds", b"kept".to_vec(), false)?;
+        let _second = store.store("db/creds", b"second".to_vec(), true)?;
+        let _third = store.store("db/creds", b"s3cret".to_vec(), true)?;
+        assert!(matches!(
+            store.link("old/creds", "db/creds")?,
+            Response::Success
+        ));
+        store.lock();
+        for key in ["db/creds", "old/creds"] {
+            match store.exists(key)? {
+                Response::KeyStat(stat) => {
+                    assert_eq!(stat.key(), "db/creds");
+                    assert!(stat.stored_bytes() > 6);
+                    assert!(!stat.chunked());
+                    // The refused write is not a version.
+                    assert_eq!(stat.versions(), 3);
+                    assert!(stat.written().is_some());
+                }
+                other => bail!("expected a stat of {key}, got {other:?}"),
+            }
+        }
+        assert!(matches!(store.exists("nope")?, Response::KeyNotFound));
+        Ok(())
+    }
+
+    #[test]
+    fn delete_prefix_removes_every_key_under_it_at_once() -> Result<()> {
+        let store = unlocked_store()?;
+        for key in ["tmp/a", "tmp/b", "tmpfile", "app/a"] {
+            let _stored = store.store(key, b"v".to_vec(), false)?;
+        }
+        assert!(matches!(store.link("tmp/old", "app/a")?, Response::Success));
+        let expected = ["tmp/a", "tmp/b", "tmp/old"];
+        match store.delete_prefix("tmp/", true)? {
+            Response::Deleted(keys) => assert_eq!(keys, expected),
+            other => bail!("expected the keys to delete, got {other:?}"),
+        }
+        assert!(matches!(store.read("tmp/a")?, Response::Value(Some(_))));
+        match store.delete_prefix("tmp/", false)? {
+            Response::Deleted(keys) => assert_eq!(keys, expected),
+            other => bail!("expected the keys deleted, got {other:?}"),
+        }
+        for key in expected {
+            assert!(matches!(store.read(key)?, Response::Value(None)));
+        }
+        assert!(matches!(store.read("tmpfile")?, Response::Value(Some(_))));
+        assert!(matches!(store.read("app/a")?, Response::Value(Some(_))));
+        // The sentinel row is never the store's to delete.
+        match store.delete_prefix("", false)? {
+            Response::Deleted(keys) => assert_eq!(keys, ["app/a", "tmpfile"]),
+            other => bail!("expected the keys deleted, got {other:?}"),
+        }
+        assert!(matches!(store.read("app/a")?, Response::Value(None)));
         Ok(())
     }
 
     #[test]
     fn search_returns_ranked_matches() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         for share in shares.iter().take(3) {
             store.add_share(share.clone());
@@ -588,7 +2367,7 @@ mod test {
 
     #[test]
     fn find_returns_regex_matches() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         for share in shares.iter().take(3) {
             store.add_share(share.clone());
@@ -621,9 +2400,21 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn literal_prefix_stops_where_a_match_could_differ() {
+        assert_eq!(literal_prefix("^app/db"), "app/db");
+        assert_eq!(literal_prefix(r"^app\.db\-"), "app.db-");
+        assert_eq!(literal_prefix("^app/d?b"), "app/");
+        assert_eq!(literal_prefix("^ap+x.*"), "ap");
+        assert_eq!(literal_prefix(r"^app\d"), "app");
+        assert_eq!(literal_prefix("^app|db"), "");
+        assert_eq!(literal_prefix("app/db"), "");
+        assert_eq!(literal_prefix("(?i)^app"), "");
+    }
+
     #[test]
     fn relocated_ciphertext_fails_to_decrypt() -> Result<()> {
-        let mut store = temp_store()?;
+        let mut store = temp_store();
         let shares = gen_and_collect(&mut store)?;
         for share in shares.iter().take(3) {
             store.add_share(share.clone());
@@ -635,11 +2426,10 @@ mod test {
         ));
 
         // Copy alpha's sealed blob verbatim under a different key name.
-        unlock_redb(&store.redb, |db| {
-            let sv = read_value::<String, SalusVal>(db, SALUS_VAL_TABLE_DEF, "alpha".to_string())?
-                .ok_or_else(|| anyhow!("alpha present"))?
-                .value();
-            write_value::<String, SalusVal>(db, SALUS_VAL_TABLE_DEF, "beta".to_string(), sv)
+        unlock_backend(&store.backend, |db| {
+            let sv = read_value(db, SALUS_VAL_TABLE_DEF, "alpha")?
+                .ok_or_else(|| anyhow!("alpha present"))?;
+            write_value(db, SALUS_VAL_TABLE_DEF, "beta", &sv)
         })?;
 
         // Reading under "beta" must fail: the key name is bound as AAD (H1).
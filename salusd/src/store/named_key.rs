@@ -0,0 +1,792 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Named keys: versioned keys that values and transit operations can use in
+//! place of the store key, each rotated on its own schedule.
+//!
+//! A key's versions live together in one keyring row of `salus_named_keys`,
+//! sealed under the store key and bound to a `named:` AAD. Rotating adds a
+//! version that later writes use; every value and ciphertext records the
+//! version that sealed it, so older ones keep opening. A key with a rotation
+//! period rotates itself on its first write after the period has passed.
+//! A rewrap ([`begin_rewrap`](ShareStore::begin_rewrap)) seals the values
+//! under a prefix again under their key's newest version, a batch at a time,
+//! so the versions before it seal nothing stored.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::{Context as _, Result, bail};
+use aws_lc_rs::rand;
+use libsalus::{NamedKeyInfo, NewNamedKey, Response, RewrapProgress, decode, encode};
+use tracing::{info, warn};
+use zeroize::{Zeroize as _, Zeroizing};
+
+use super::{ShareStore, now, open, open_stored, seal};
+use crate::{
+    db::{
+        SALUS_NAMED_KEYS_TABLE_DEF, SALUS_VAL_TABLE_DEF, backend::Table, put, read_backend,
+        read_value, scan_values, values::salus::SalusVal, write_keys, write_value,
+    },
+    error::Error,
+};
+
+/// The length of each key version, in bytes (AES-256).
+const NAMED_KEY_LEN: usize = 32;
+
+/// How many values one write of a rewrap seals again.
+const REWRAP_BATCH: usize = 64;
+
+/// The rewraps begun since the daemon started, and how far each has got, by
+/// prefix.
+#[derive(Debug, Default)]
+pub(crate) struct Rewraps(Mutex<BTreeMap<String, RewrapProgress>>);
+
+impl Rewraps {
+    fn progress(&self) -> MutexGuard<'_, BTreeMap<String, RewrapProgress>> {
+        match self.0.lock() {
+            Ok(progress) => progress,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// The progress of the rewrap of `prefix`, while it runs.
+    fn running(&self, prefix: &str) -> Option<RewrapProgress> {
+        self.progress()
+            .get(prefix)
+            .filter(|progress| !progress.finished())
+            .cloned()
+    }
+
+    /// Record that `progress`'s rewrap has begun; the progress of the rewrap
+    /// of its prefix already running, if there is one, in its place.
+    fn begin(&self, progress: &RewrapProgress) -> Option<RewrapProgress> {
+        let mut rewraps = self.progress();
+        if let Some(running) = rewraps
+            .get(progress.prefix())
+            .filter(|running| !running.finished())
+        {
+            return Some(running.clone());
+        }
+        let _previous = rewraps.insert(progress.prefix().clone(), progress.clone());
+        None
+    }
+
+    /// Count a batch of `checked` values of the rewrap of `prefix`, of which
+    /// `resealed` were sealed again, or which stopped it.
+    fn advance(&self, prefix: &str, checked: usize, resealed: Result<u64>) -> RewrapProgress {
+        let mut rewraps = self.progress();
+        let before = rewraps.get(prefix);
+        let stale = before.map_or(0, RewrapProgress::stale);
+        let checked_before = before.map_or(0, RewrapProgress::checked);
+        let rewrapped = before.map_or(0, RewrapProgress::rewrapped);
+        let batches = before.map_or(0, RewrapProgress::batches);
+        let checked = u64::try_from(checked).unwrap_or(u64::MAX);
+        let progress = match resealed {
+            Ok(count) => RewrapProgress::builder()
+                .prefix(prefix)
+                .stale(stale)
+                .checked(checked_before.saturating_add(checked))
+                .rewrapped(rewrapped.saturating_add(count))
+                .batches(batches.saturating_add(1))
+                .build(),
+            Err(e) => RewrapProgress::builder()
+                .prefix(prefix)
+                .stale(stale)
+                .checked(checked_before)
+                .rewrapped(rewrapped)
+                .batches(batches)
+                .error(format!("{e:#}"))
+                .build(),
+        };
+        let _previous = rewraps.insert(prefix.to_string(), progress.clone());
+        progress
+    }
+
+    /// Every rewrap's progress, by prefix.
+    fn all(&self) -> Vec<RewrapProgress> {
+        self.progress().values().cloned().collect()
+    }
+}
+
+/// A named key's rotation period and versions, oldest first.
+struct Keyring {
+    /// Seconds after the newest version's creation that the key rotates.
+    rotate_after: Option<u64>,
+    /// Each version's creation time, in seconds since the Unix epoch, and key.
+    versions: Vec<(u64, Zeroizing<Vec<u8>>)>,
+}
+
+impl Keyring {
+    /// A keyring holding a single fresh version.
+    fn new(rotate_after: Option<u64>, now: u64) -> Result<Self> {
+        let mut keyring = Self {
+            rotate_after,
+            versions: vec![],
+        };
+        let _version = keyring.rotate(now)?;
+        Ok(keyring)
+    }
+
+    /// Add a fresh version, returning its number.
+    fn rotate(&mut self, now: u64) -> Result<u32> {
+        let mut material = Zeroizing::new(vec![0u8; NAMED_KEY_LEN]);
+        rand::fill(&mut material)?;
+        self.versions.push((now, material));
+        Ok(u32::try_from(self.versions.len())?)
+    }
+
+    /// The newest version's number, creation time, and key.
+    fn newest(&self) -> Result<(u32, u64, &Zeroizing<Vec<u8>>)> {
+        let (created, material) = self
+            .versions
+            .last()
+            .context("a named key has no versions")?;
+        Ok((u32::try_from(self.versions.len())?, *created, material))
+    }
+
+    /// Version `version`'s key; versions are numbered from 1.
+    fn version(&self, version: u32) -> Option<&Zeroizing<Vec<u8>>> {
+        let index = usize::try_from(version).ok()?.checked_sub(1)?;
+        self.versions.get(index).map(|(_, material)| material)
+    }
+
+    /// Whether the rotation period has passed since the newest version.
+    fn rotation_due(&self, now: u64) -> Result<bool> {
+        let (_, created, _) = self.newest()?;
+        Ok(self
+            .rotate_after
+            .is_some_and(|period| now.saturating_sub(created) >= period))
+    }
+
+    fn info(&self, name: &str, now: u64) -> Result<NamedKeyInfo> {
+        let (version, rotated_at, _) = self.newest()?;
+        Ok(NamedKeyInfo::builder()
+            .name(name)
+            .version(version)
+            .maybe_rotate_after(self.rotate_after)
+            .rotated_at(rotated_at)
+            .rotation_due(self.rotation_due(now)?)
+            .build())
+    }
+
+    fn encode(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let mut versions = self
+            .versions
+            .iter()
+            .map(|(created, material)| (*created, material.to_vec()))
+            .collect::<Vec<_>>();
+        let encoded = encode((self.rotate_after, &versions));
+        for (_, material) in &mut versions {
+            material.zeroize();
+        }
+        Ok(Zeroizing::new(encoded?))
+    }
+
+    fn decode(plaintext: &[u8]) -> Result<Self> {
+        let (rotate_after, versions): (Option<u64>, Vec<(u64, Vec<u8>)>) = decode(plaintext)?;
+        Ok(Self {
+            rotate_after,
+            versions: versions
+                .into_iter()
+                .map(|(created, material)| (created, Zeroizing::new(material)))
+                .collect(),
+        })
+    }
+}
+
+impl ShareStore {
+    /// Create a named key with a single version.
+    pub(crate) fn create_named_key(&self, request: &NewNamedKey) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if let Err(e) = request.validate() {
+            return Ok(Response::InvalidKeyName(e.to_string()));
+        }
+        let name = request.name();
+        let created = self.update_keyring(enc_key, name, |existing| {
+            Ok(match existing {
+                Some(_) => (None, false),
+                None => (Some(Keyring::new(request.rotate_after(), now())?), true),
+            })
+        })?;
+        if !created {
+            info!("Refusing to replace existing named key: {name}");
+            return Ok(Response::KeyExists);
+        }
+        info!(
+            target: "salusd::audit",
+            key = name.as_str(), rotate_after = request.rotate_after(), "Named key created"
+        );
+        Ok(Response::Success)
+    }
+
+    /// Add a new version to a named key.
+    pub(crate) fn rotate_named_key(&self, name: &str) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let rotated = self.update_keyring(enc_key, name, |keyring| {
+            let Some(mut keyring) = keyring else {
+                return Ok((None, None));
+            };
+            let version = keyring.rotate(now())?;
+            Ok((Some(keyring), Some(version)))
+        })?;
+        let Some(version) = rotated else {
+            return Ok(Response::NamedKeyNotFound(name.to_string()));
+        };
+        info!(target: "salusd::audit", key = name, version, "Named key rotated");
+        self.changes.rotated(name, version);
+        Ok(Response::KeyRotated(version))
+    }
+
+    /// Describe every named key, sorted by name.
+    pub(crate) fn list_named_keys(&self) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let mut sealed = vec![];
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = scan_values(db, SALUS_NAMED_KEYS_TABLE_DEF, "")?;
+            Ok(())
+        })?;
+        let now = now();
+        let keys = sealed
+            .iter()
+            .map(|(name, value)| {
+                let plaintext = Zeroizing::new(open(enc_key, &named_aad(name), value)?);
+                Keyring::decode(&plaintext)?.info(name, now)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Response::NamedKeys(keys))
+    }
+
+    /// The newest version of a named key for a write, rotating the key first
+    /// when its rotation period has passed. `None` when there is no such key.
+    pub(super) fn named_key_for_write(
+        &self,
+        enc_key: &[u8],
+        name: &str,
+    ) -> Result<Option<(u32, Zeroizing<Vec<u8>>)>> {
+        let Some(keyring) = self.keyring(enc_key, name)? else {
+            return Ok(None);
+        };
+        let now = now();
+        if !keyring.rotation_due(now)? {
+            let (version, _, material) = keyring.newest()?;
+            return Ok(Some((version, material.clone())));
+        }
+        // Checked again under the backend's lock: another write may have
+        // rotated it first.
+        let mut rotated = None;
+        let newest = self.update_keyring(enc_key, name, |keyring| {
+            let Some(mut keyring) = keyring else {
+                return Ok((None, None));
+            };
+            if keyring.rotation_due(now)? {
+                let version = keyring.rotate(now)?;
+                info!(target: "salusd::audit", key = name, version, "Named key rotated on schedule");
+                rotated = Some(version);
+            }
+            let (version, _, material) = keyring.newest()?;
+            let newest = Some((version, material.clone()));
+            Ok((rotated.is_some().then_some(keyring), newest))
+        })?;
+        if let Some(version) = rotated {
+            self.changes.rotated(name, version);
+        }
+        Ok(newest)
+    }
+
+    /// Begin a rewrap of `prefix`: find every value under it that an older
+    /// version of its named key sealed, answering with the rewrap's progress
+    /// and those values' keys, [`REWRAP_BATCH`] to a batch, for
+    /// [`rewrap_batch`](Self::rewrap_batch) to seal again. A rewrap of
+    /// `prefix` already running is answered with its progress and no batches.
+    /// Values under the store key are left alone.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, a keyring cannot be opened,
+    ///   or the database cannot be read.
+    pub(crate) fn begin_rewrap(&self, prefix: &str) -> Result<(RewrapProgress, Vec<Vec<String>>)> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if let Some(running) = self.rewraps.running(prefix) {
+            return Ok((running, vec![]));
+        }
+        let mut sealed = vec![];
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = scan_values(db, SALUS_VAL_TABLE_DEF, prefix)?;
+            Ok(())
+        })?;
+        let mut newest = BTreeMap::new();
+        let mut stale = vec![];
+        for (key, value) in sealed {
+            let Some((name, version)) = value.named_key()? else {
+                continue;
+            };
+            let newest = match newest.entry(name) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let keyring = self.keyring(enc_key, entry.key())?;
+                    let version =
+                        keyring.map(|keyring| keyring.newest().map(|(version, ..)| version));
+                    *entry.insert(version.transpose()?)
+                }
+            };
+            if newest.is_some_and(|newest| version < newest) {
+                stale.push(key);
+            }
+        }
+
+        let progress = RewrapProgress::builder()
+            .prefix(prefix)
+            .stale(u64::try_from(stale.len())?)
+            .build();
+        // Another request may have begun the same rewrap during the scan.
+        if let Some(running) = self.rewraps.begin(&progress) {
+            return Ok((running, vec![]));
+        }
+        info!(target: "salusd::audit", prefix, stale = stale.len(), "Rewrap begun");
+        let batches = stale.chunks(REWRAP_BATCH).map(<[String]>::to_vec);
+        Ok((progress, batches.collect()))
+    }
+
+    /// Seal one batch of the rewrap of `prefix` again under the newest
+    /// version of each value's named key, answering with how far the rewrap
+    /// has got.
+    ///
+    /// The batch is written under its keys' write locks and lands whole; a
+    /// value written or deleted since the rewrap began is passed over. A
+    /// batch that fails finishes the rewrap with the error in its progress.
+    /// The batches before it stay sealed anew, and beginning the rewrap again
+    /// carries on from there.
+    pub(crate) fn rewrap_batch(&self, prefix: &str, batch: &[String]) -> RewrapProgress {
+        let resealed = self.reseal_batch(batch);
+        match &resealed {
+            Ok(count) => info!(prefix, rewrapped = count, "Rewrapped a batch of values"),
+            Err(e) => warn!(prefix, "Rewrap stopped: {e:#}"),
+        }
+        let progress = self.rewraps.advance(prefix, batch.len(), resealed);
+        if progress.finished() {
+            info!(
+                target: "salusd::audit",
+                prefix,
+                rewrapped = progress.rewrapped(),
+                "Values rewrapped"
+            );
+        }
+        progress
+    }
+
+    /// How far each rewrap begun since the daemon started has got.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked.
+    pub(crate) fn rewraps(&self) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        Ok(Response::Rewraps(self.rewraps.all()))
+    }
+
+    /// Seal the values under `batch` again, answering with how many were.
+    fn reseal_batch(&self, batch: &[String]) -> Result<u64> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if self.read_only {
+            bail!("the store was made read-only");
+        }
+        let mut names = BTreeSet::new();
+        read_backend(&self.backend, |db| -> Result<()> {
+            for key in batch {
+                if let Some(value) = read_value(db, SALUS_VAL_TABLE_DEF, key)?
+                    && let Some((name, _)) = value.named_key()?
+                {
+                    let _new = names.insert(name);
+                }
+            }
+            Ok(())
+        })?;
+        // Each keyring is read before any value is written: taking its lock
+        // while holding the values' could wait on itself.
+        let mut keyrings = BTreeMap::new();
+        for name in names {
+            let keyring = self.keyring(enc_key, &name)?;
+            let _previous = keyrings.insert(name, keyring);
+        }
+
+        let mut rewrapped = 0;
+        let locks = batch.iter().map(|key| (Table::Values, key.as_str()));
+        write_keys(&self.backend, locks, |db| -> Result<()> {
+            let mut ops = vec![];
+            for key in batch {
+                // Looked at again under the lock: it may have been written or
+                // deleted since the rewrap began.
+                let Some(existing) = read_value(db, SALUS_VAL_TABLE_DEF, key)? else {
+                    continue;
+                };
+                if let Some(resealed) = self.resealed(&keyrings, key, &existing)? {
+                    ops.push(put(SALUS_VAL_TABLE_DEF, key, &resealed));
+                }
+            }
+            rewrapped = ops.len();
+            if rewrapped > 0 {
+                db.commit(ops)?;
+            }
+            Ok(())
+        })?;
+        self.read_cache.invalidate(batch.iter().map(String::as_str));
+        Ok(u64::try_from(rewrapped)?)
+    }
+
+    /// `existing`, the value under `key`, sealed again under the newest
+    /// version of its named key in `keyrings`; `None` when that version, or a
+    /// newer one, already sealed it.
+    fn resealed(
+        &self,
+        keyrings: &BTreeMap<String, Option<Keyring>>,
+        key: &str,
+        existing: &SalusVal,
+    ) -> Result<Option<SalusVal>> {
+        let Some((name, version)) = existing.named_key()? else {
+            return Ok(None);
+        };
+        let Some(Some(keyring)) = keyrings.get(&name) else {
+            return Ok(None);
+        };
+        let (newest, _, material) = keyring.newest()?;
+        if version >= newest {
+            return Ok(None);
+        }
+        let sealed_with = keyring
+            .version(version)
+            .ok_or_else(|| Error::NamedKeyMissing(name.clone(), version))?;
+        let plaintext = open_stored(sealed_with, key, existing)?;
+        Ok(Some(
+            self.seal_named(&name, newest, material, key, plaintext)?,
+        ))
+    }
+
+    /// The newest version of a named key, without rotating it. `None` when
+    /// there is no such key.
+    pub(super) fn newest_named_key(
+        &self,
+        enc_key: &[u8],
+        name: &str,
+    ) -> Result<Option<(u32, Zeroizing<Vec<u8>>)>> {
+        self.keyring(enc_key, name)?
+            .map(|keyring| {
+                let (version, _, material) = keyring.newest()?;
+                Ok((version, material.clone()))
+            })
+            .transpose()
+    }
+
+    /// One version of a named key, for a read. `None` when there is no such
+    /// key or version.
+    pub(super) fn named_key_version(
+        &self,
+        enc_key: &[u8],
+        name: &str,
+        version: u32,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        Ok(self
+            .keyring(enc_key, name)?
+            .and_then(|keyring| keyring.version(version).cloned()))
+    }
+
+    fn keyring(&self, enc_key: &[u8], name: &str) -> Result<Option<Keyring>> {
+        let mut sealed = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = read_value(db, SALUS_NAMED_KEYS_TABLE_DEF, name)?;
+            Ok(())
+        })?;
+        sealed
+            .map(|sealed| open_keyring(enc_key, name, &sealed))
+            .transpose()
+    }
+
+    /// Read the keyring of `name`, and write back the one `change` returns,
+    /// under the name's write lock, so two changes to a key cannot lose a
+    /// version. `change` gets `None` when there is no such key.
+    fn update_keyring<T>(
+        &self,
+        enc_key: &[u8],
+        name: &str,
+        change: impl FnOnce(Option<Keyring>) -> Result<(Option<Keyring>, T)>,
+    ) -> Result<T> {
+        let mut change = Some(change);
+        let mut result = None;
+        write_keys(
+            &self.backend,
+            [(Table::NamedKeys, name)],
+            |db| -> Result<()> {
+                let change = change.take().context("the keyring change already ran")?;
+                let keyring = read_value(db, SALUS_NAMED_KEYS_TABLE_DEF, name)?
+                    .map(|sealed| open_keyring(enc_key, name, &sealed))
+                    .transpose()?;
+                let (keyring, changed) = change(keyring)?;
+                if let Some(keyring) = keyring {
+                    let mut plaintext = keyring.encode()?;
+                    let salus_val = seal(enc_key, &named_aad(name), &mut plaintext)?;
+                    write_value(db, SALUS_NAMED_KEYS_TABLE_DEF, name, &salus_val)?;
+                }
+                result = Some(changed);
+                Ok(())
+            },
+        )?;
+        result.context("the keyring change did not run")
+    }
+}
+
+/// Open the sealed keyring of the named key `name`.
+fn open_keyring(enc_key: &[u8], name: &str, sealed: &SalusVal) -> Result<Keyring> {
+    let plaintext = Zeroizing::new(open(enc_key, &named_aad(name), sealed)?);
+    Keyring::decode(&plaintext)
+}
+
+/// Check that the sealed keyring of `name` opens and holds a version.
+pub(super) fn check_keyring(enc_key: &[u8], name: &str, sealed: &SalusVal) -> Result<()> {
+    let _newest = open_keyring(enc_key, name, sealed)?.newest()?;
+    Ok(())
+}
+
+/// The AAD a keyring is sealed under, distinct from any value key's.
+fn named_aad(name: &str) -> String {
+    format!("named:{name}")
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use anyhow::{Result, anyhow, bail};
+    use libsalus::{NewNamedKey, Response, Store};
+
+    use super::{
+        super::test::{temp_store, unlocked_store},
+        Keyring, REWRAP_BATCH, now,
+    };
+    use crate::{
+        db::{SALUS_VAL_TABLE_DEF, read_backend, read_value},
+        error::Error,
+    };
+
+    #[test]
+    fn values_keep_opening_across_rotations() -> Result<()> {
+        let store = unlocked_store()?;
+        let create = NewNamedKey::builder().name("app-a").build();
+        assert!(matches!(
+            store.create_named_key(&create)?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.create_named_key(&create)?,
+            Response::KeyExists
+        ));
+        let value = |key: &str, value: &str| Store::builder().key(key).value(value).build();
+        assert!(matches!(
+            store.store_with_key("app-a", &value("db/one", "first"))?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.rotate_named_key("app-a")?,
+            Response::KeyRotated(2)
+        ));
+        assert!(matches!(
+            store.store_with_key("app-a", &value("db/two", "second"))?,
+            Response::Success
+        ));
+        assert!(matches!(
+            store.read("db/one")?,
+            Response::Value(Some(ref v)) if v == b"first"
+        ));
+        assert!(matches!(
+            store.read_prefix("db/")?,
+            Response::Values(ref values) if values.len() == 2
+        ));
+        assert!(matches!(
+            store.store_with_key("nope", &value("db/three", "x"))?,
+            Response::NamedKeyNotFound(ref name) if name == "nope"
+        ));
+        // The keyring is not a value, so it cannot be read back.
+        assert!(matches!(store.read("app-a")?, Response::Value(None)));
+        Ok(())
+    }
+
+    #[test]
+    fn rewrap_seals_values_under_the_newest_version() -> Result<()> {
+        let store = unlocked_store()?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app-a").build())?;
+        let value = |key: &str, value: &str| Store::builder().key(key).value(value).build();
+        for (key, plain) in [
+            ("db/one", "first"),
+            ("db/two", "second"),
+            ("web/one", "third"),
+        ] {
+            let _stored = store.store_with_key("app-a", &value(key, plain))?;
+        }
+        let _stored = store.store("db/plain", b"fourth".to_vec(), false)?;
+        let _rotated = store.rotate_named_key("app-a")?;
+        let _rotated = store.rotate_named_key("app-a")?;
+
+        let (begun, batches) = store.begin_rewrap("db/")?;
+        assert_eq!(begun.stale(), 2);
+        for batch in &batches {
+            let _progress = store.rewrap_batch("db/", batch);
+        }
+        let version = |key: &str| -> Result<Option<u32>> {
+            let mut version = None;
+            read_backend(&store.backend, |db| -> Result<()> {
+                if let Some(value) = read_value(db, SALUS_VAL_TABLE_DEF, key)? {
+                    version = value.named_key()?.map(|(_, version)| version);
+                }
+                Ok(())
+            })?;
+            Ok(version)
+        };
+        assert_eq!(version("db/one")?, Some(3));
+        assert_eq!(version("db/two")?, Some(3));
+        assert_eq!(version("web/one")?, Some(1));
+        assert_eq!(version("db/plain")?, None);
+        assert!(matches!(
+            store.read("db/two")?,
+            Response::Value(Some(ref v)) if v == b"second"
+        ));
+        // Nothing under the prefix is left for a second run to do.
+        let (begun, batches) = store.begin_rewrap("db/")?;
+        assert!(begun.finished());
+        assert_eq!(begun.stale(), 0);
+        assert!(batches.is_empty());
+
+        match temp_store().begin_rewrap("db/") {
+            Err(e) if matches!(e.downcast_ref(), Some(Error::StoreNotUnlocked)) => {}
+            other => bail!("expected the sealed store to refuse, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rewrap_reports_progress_batch_by_batch() -> Result<()> {
+        let mut store = unlocked_store()?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app-a").build())?;
+        let count = REWRAP_BATCH.saturating_mul(2).saturating_add(3);
+        for index in 0..count {
+            let value = Store::builder()
+                .key(format!("db/{index:03}"))
+                .value("secret")
+                .build();
+            let _stored = store.store_with_key("app-a", &value)?;
+        }
+        let _rotated = store.rotate_named_key("app-a")?;
+
+        let (begun, batches) = store.begin_rewrap("db/")?;
+        assert_eq!(begun.stale(), u64::try_from(count)?);
+        assert_eq!(batches.len(), 3);
+        // Asked again while it runs, the rewrap is reported, not begun twice.
+        let (again, none) = store.begin_rewrap("db/")?;
+        assert_eq!(again, begun);
+        assert!(none.is_empty());
+
+        let mut checked = 0u64;
+        for (written, batch) in (1u64..).zip(&batches) {
+            let progress = store.rewrap_batch("db/", batch);
+            checked = checked.saturating_add(u64::try_from(batch.len())?);
+            assert_eq!(progress.batches(), written);
+            assert_eq!(progress.checked(), checked);
+            assert_eq!(progress.rewrapped(), checked);
+            assert_eq!(progress.finished(), written == 3);
+            match store.rewraps()? {
+                Response::Rewraps(rewraps) => assert_eq!(rewraps, vec![progress]),
+                other => bail!("expected Response::Rewraps, got {other:?}"),
+            }
+        }
+
+        // A batch the store cannot write stops the rewrap, saying why.
+        let _rotated = store.rotate_named_key("app-a")?;
+        let (_begun, batches) = store.begin_rewrap("db/")?;
+        store.clear_key();
+        let Some(batch) = batches.first() else {
+            bail!("expected a batch to rewrap");
+        };
+        let stopped = store.rewrap_batch("db/", batch);
+        assert!(stopped.finished());
+        assert!(stopped.error().is_some());
+        assert_eq!(stopped.rewrapped(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_rotations_each_add_a_version() -> Result<()> {
+        let store = unlocked_store()?;
+        let _created = store.create_named_key(&NewNamedKey::builder().name("app-a").build())?;
+        let mut versions = thread::scope(|scope| {
+            (0..8)
+                .map(|_| scope.spawn(|| store.rotate_named_key("app-a")))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|rotation| match rotation.join() {
+                    Ok(Ok(Response::KeyRotated(version))) => Ok(version),
+                    Ok(other) => Err(anyhow!("expected a rotation, got {other:?}")),
+                    Err(_) => Err(anyhow!("a rotation panicked")),
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        versions.sort_unstable();
+        assert_eq!(versions, (2..=9).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_list_with_their_rotation_state() -> Result<()> {
+        let store = unlocked_store()?;
+        assert!(matches!(
+            store.list_named_keys()?,
+            Response::NamedKeys(ref keys) if keys.is_empty()
+        ));
+        let create = |name: &str, rotate_after| {
+            NewNamedKey::builder()
+                .name(name)
+                .maybe_rotate_after(rotate_after)
+                .build()
+        };
+        assert!(matches!(
+            store.create_named_key(&create("bad/name", None))?,
+            Response::InvalidKeyName(_)
+        ));
+        let _created = store.create_named_key(&create("backups", Some(3600)))?;
+        let _created = store.create_named_key(&create("app-a", None))?;
+        let Response::NamedKeys(keys) = store.list_named_keys()? else {
+            bail!("expected the named keys");
+        };
+        let names = keys
+            .iter()
+            .map(|key| key.name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["app-a", "backups"]);
+        assert!(
+            keys.iter()
+                .all(|key| key.version() == 1 && !key.rotation_due())
+        );
+
+        let keyring = Keyring::new(Some(60), 1_000)?;
+        assert!(!keyring.rotation_due(1_059)?);
+        assert!(keyring.rotation_due(1_060)?);
+        assert!(!Keyring::new(None, 0)?.rotation_due(now())?);
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Rotation reminders: how long the value under a key may go unwritten
+//! before it is due to be rotated.
+//!
+//! A key's `rotate_after` is a row of `salus_rotate_after`, in seconds,
+//! measured from the write time `salus_written` records; writing the value
+//! again is what rotates it. Neither is secret, so the daemon finds the
+//! values due while the store is sealed, and [`Reminders`] reports each one
+//! once, to the audit log and to the hooks, until it is written again. A
+//! client is only told of them once the store is unlocked, since they name
+//! keys.
+
+use std::{collections::BTreeSet, time::Duration};
+
+use anyhow::Result;
+use libsalus::{Response, RotationWarning};
+use tracing::{info, warn};
+
+use super::ShareStore;
+use crate::{
+    db::{
+        SALUS_ROTATE_AFTER_TABLE_DEF, SALUS_VAL_TABLE_DEF, SALUS_VERSIONS_TABLE_DEF,
+        SALUS_WRITTEN_TABLE_DEF,
+        backend::{Table, WriteOp},
+        put, read_backend, read_value, scan_values, write_keys,
+    },
+    error::Error,
+    hook::Changes,
+};
+
+/// How often the daemon looks for values due to be rotated.
+pub(crate) const CHECK_INTERVAL: Duration = Duration::from_mins(10);
+
+impl ShareStore {
+    /// Have the value under `key` fall due `rotate_after` seconds after each
+    /// write, or stop tracking it when `None`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the database cannot be
+    ///   read or written.
+    pub(crate) fn set_rotation(&self, key: &str, rotate_after: Option<u64>) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let mut response = Response::Success;
+        write_keys(&self.backend, [(Table::Values, key)], |db| -> Result<()> {
+            if read_value(db, SALUS_VAL_TABLE_DEF, key)?.is_none() {
+                response = Response::KeyNotFound;
+                return Ok(());
+            }
+            let op = match rotate_after {
+                Some(secs) => put(SALUS_ROTATE_AFTER_TABLE_DEF, key, &secs),
+                None => WriteOp::Delete {
+                    table: Table::RotateAfter,
+                    key: key.to_string(),
+                },
+            };
+            db.commit(vec![op])
+        })?;
+        if matches!(response, Response::Success) {
+            info!(
+                target: "salusd::audit",
+                key,
+                rotate_after,
+                "Rotation age set"
+            );
+        }
+        Ok(response)
+    }
+
+    /// The values due to be rotated at `now`, in seconds since the Unix
+    /// epoch, longest overdue first. A value whose write time was never
+    /// recorded counts as due.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the database cannot be read.
+    pub(crate) fn overdue(&self, now: u64) -> Result<Vec<RotationWarning>> {
+        let mut overdue = Vec::new();
+        read_backend(&self.backend, |db| -> Result<()> {
+            for (key, rotate_after) in scan_values(db, SALUS_ROTATE_AFTER_TABLE_DEF, "")? {
+                let written = read_value(db, SALUS_WRITTEN_TABLE_DEF, &key)?;
+                let due_at = written.map(|written| written.saturating_add(rotate_after));
+                if due_at.is_some_and(|due_at| due_at > now) {
+                    continue;
+                }
+                overdue.push(
+                    RotationWarning::builder()
+                        .key(key)
+                        .rotate_after(rotate_after)
+                        .maybe_written(written)
+                        .maybe_due_at(due_at)
+                        .build(),
+                );
+            }
+            Ok(())
+        })?;
+        overdue.sort_by_key(RotationWarning::due_at);
+        Ok(overdue)
+    }
+
+    /// The values due to be rotated now, for a client; see
+    /// [`overdue`](Self::overdue).
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the store is locked, or the database cannot be
+    ///   read.
+    pub(crate) fn warnings(&self, now: u64) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        Ok(Response::Warnings(self.overdue(now)?))
+    }
+
+    /// The version of the value under `key`, if one was recorded.
+    fn version(&self, key: &str) -> Result<Option<u64>> {
+        let mut version = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            version = read_value(db, SALUS_VERSIONS_TABLE_DEF, key)?;
+            Ok(())
+        })?;
+        Ok(version)
+    }
+}
+
+/// The values already reported as due, by key and the write they are due
+/// after, so each is reported once
+#[derive(Debug, Default)]
+pub(crate) struct Reminders {
+    reported: BTreeSet<(String, Option<u64>)>,
+}
+
+impl Reminders {
+    /// Report each value of `store` due at `now` that was not already: as an
+    /// audit event, and to the hooks through `changes`. Returns how many were
+    /// reported.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the database cannot be read.
+    pub(crate) fn remind(
+        &mut self,
+        store: &ShareStore,
+        changes: &Changes,
+        now: u64,
+    ) -> Result<usize> {
+        let overdue = store.overdue(now)?;
+        let due = overdue
+            .iter()
+            .map(|warning| (warning.key().clone(), warning.written()))
+            .collect::<BTreeSet<_>>();
+        // Written again, or no longer tracked, since it was reported.
+        self.reported.retain(|reported| due.contains(reported));
+        let mut reminded = 0usize;
+        for warning in &overdue {
+            if !self
+                .reported
+                .insert((warning.key().clone(), warning.written()))
+            {
+                continue;
+            }
+            warn!(
+                target: "salusd::audit",
+                key = warning.key().as_str(),
+                rotate_after = warning.rotate_after(),
+                written = warning.written(),
+                "Value is due to be rotated"
+            );
+            changes.overdue(warning.key(), store.version(warning.key())?);
+            reminded = reminded.saturating_add(1);
+        }
+        Ok(reminded)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{Response, RotationWarning};
+
+    use super::Reminders;
+    use crate::{
+        config::HookEvent,
+        hook::Changes,
+        store::test::{temp_store, unlocked_store},
+    };
+
+    #[test]
+    fn values_fall_due_after_their_rotation_age() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("app/db", b"hunter2".to_vec(), false)?;
+        let _stored = store.store("app/api", b"token".to_vec(), false)?;
+        assert!(matches!(
+            store.set_rotation("app/missing", Some(60))?,
+            Response::KeyNotFound
+        ));
+        assert!(matches!(
+            store.set_rotation("app/db", Some(60))?,
+            Response::Success
+        ));
+        let Some(written) = store
+            .overdue(u64::MAX)?
+            .first()
+            .and_then(RotationWarning::written)
+        else {
+            bail!("the value's write time was not recorded");
+        };
+        assert!(store.overdue(written.saturating_add(59))?.is_empty());
+        let overdue = store.overdue(written.saturating_add(60))?;
+        assert_eq!(
+            overdue
+                .iter()
+                .map(|warning| (warning.key().as_str(), warning.due_at()))
+                .collect::<Vec<_>>(),
+            vec![("app/db", Some(written.saturating_add(60)))]
+        );
+        let _cleared = store.set_rotation("app/db", None)?;
+        assert!(store.overdue(u64::MAX)?.is_empty());
+        assert!(temp_store().set_rotation("app/db", Some(60)).is_err());
+        // They name keys, so a client is not told of them while sealed.
+        assert!(matches!(store.warnings(u64::MAX)?, Response::Warnings(_)));
+        assert!(temp_store().warnings(u64::MAX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn each_overdue_value_is_reported_once_per_write() -> Result<()> {
+        let store = unlocked_store()?;
+        let _stored = store.store("app/db", b"hunter2".to_vec(), false)?;
+        let _set = store.set_rotation("app/db", Some(0))?;
+        let (changes, mut received) = Changes::channel();
+        let mut reminders = Reminders::default();
+        assert_eq!(reminders.remind(&store, &changes, u64::MAX)?, 1);
+        assert_eq!(reminders.remind(&store, &changes, u64::MAX)?, 0);
+        let change = received.try_recv()?;
+        assert_eq!(change.event(), HookEvent::Overdue);
+        assert_eq!(change.key(), "app/db");
+        assert_eq!(change.version(), Some(1));
+        assert!(received.try_recv().is_err());
+
+        // Deleting it stops the reminders, and storing it again starts them
+        // over.
+        let _deleted = store.delete("app/db")?;
+        assert_eq!(reminders.remind(&store, &changes, u64::MAX)?, 0);
+        let _stored = store.store("app/db", b"hunter3".to_vec(), false)?;
+        let _set = store.set_rotation("app/db", Some(0))?;
+        assert_eq!(reminders.remind(&store, &changes, u64::MAX)?, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The `[validation.<name>]` rules, checked on each value before it is sealed
+//! and stored, so a malformed credential is refused when it is written rather
+//! than found out when it is used.
+//!
+//! A value stored under a key several rules hold for must meet each of them.
+//! A rule whose pattern or schema cannot be read refuses every value it holds
+//! for, rather than letting them through unchecked; `salusd validate-config`
+//! reports it.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context as _, Result};
+use libsalus::ValidationFailure;
+use regex::Regex;
+
+use crate::{
+    config::{ConfigSalusd, ValidationSettings},
+    hook::covers,
+};
+
+pub(crate) use self::schema::Schema;
+
+mod schema;
+
+/// The validation rules, by name
+#[derive(Debug, Default)]
+pub(crate) struct Rules {
+    /// The rules as configured, which say whether two sets of rules are the
+    /// same
+    settings: BTreeMap<String, ValidationSettings>,
+    /// The rules as read, or why one could not be
+    checks: BTreeMap<String, Result<Checks, String>>,
+}
+
+impl PartialEq for Rules {
+    fn eq(&self, other: &Self) -> bool {
+        self.settings == other.settings
+    }
+}
+
+impl Eq for Rules {}
+
+impl From<&ConfigSalusd> for Rules {
+    fn from(config: &ConfigSalusd) -> Self {
+        let checks = config
+            .validation()
+            .iter()
+            .map(|(name, settings)| {
+                let checks = Checks::read(settings).map_err(|e| format!("{e:#}"));
+                (name.clone(), checks)
+            })
+            .collect();
+        Self {
+            settings: config.validation().clone(),
+            checks,
+        }
+    }
+}
+
+impl Rules {
+    /// Check `value`, to be stored under `key`, against every rule that holds
+    /// for the key.
+    ///
+    /// # Errors
+    ///
+    /// * Returns the first rule `value` breaks, and how.
+    pub(crate) fn check(&self, key: &str, value: &[u8]) -> Result<(), ValidationFailure> {
+        self.each(key, |checks| checks.check(value))
+    }
+
+    /// Check a value of `len` bytes, to be uploaded in chunks under `key`,
+    /// against every rule that holds for the key. Only a rule that checks
+    /// nothing but the length lets it through: the whole value is never held
+    /// at once to check otherwise.
+    ///
+    /// # Errors
+    ///
+    /// * Returns the first rule the upload breaks, and how.
+    pub(crate) fn check_upload(&self, key: &str, len: u64) -> Result<(), ValidationFailure> {
+        self.each(key, |checks| {
+            checks.check_len(len)?;
+            if checks.reads_value() {
+                return Err("values it checks cannot be uploaded in chunks".to_string());
+            }
+            Ok(())
+        })
+    }
+
+    fn each(
+        &self,
+        key: &str,
+        check: impl Fn(&Checks) -> Result<(), String>,
+    ) -> Result<(), ValidationFailure> {
+        for (name, checks) in &self.checks {
+            let holds = self.settings.get(name).is_some_and(|settings| {
+                settings.keys().is_empty()
+                    || settings.keys().iter().any(|pattern| covers(pattern, key))
+            });
+            if !holds {
+                continue;
+            }
+            let checked = match checks {
+                Ok(checks) => check(checks),
+                Err(problem) => Err(format!("the rule cannot be used: {problem}")),
+            };
+            if let Err(reason) = checked {
+                return Err(ValidationFailure::builder()
+                    .key(key)
+                    .rule(name)
+                    .reason(reason)
+                    .build());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What one rule checks
+#[derive(Debug)]
+struct Checks {
+    pattern: Option<Regex>,
+    schema: Option<Schema>,
+    min_bytes: Option<u64>,
+    max_bytes: Option<u64>,
+    min_entropy: Option<u64>,
+}
+
+impl Checks {
+    fn read(settings: &ValidationSettings) -> Result<Self> {
+        Ok(Self {
+            pattern: settings.pattern().as_deref().map(pattern).transpose()?,
+            schema: settings
+                .schema()
+                .as_deref()
+                .map(Schema::parse)
+                .transpose()
+                .context("the schema cannot be used")?,
+            min_bytes: settings.min_bytes(),
+            max_bytes: settings.max_bytes(),
+            min_entropy: settings.min_entropy(),
+        })
+    }
+
+    /// Whether it checks more of a value than its length.
+    fn reads_value(&self) -> bool {
+        self.pattern.is_some() || self.schema.is_some() || self.min_entropy.is_some()
+    }
+
+    fn check_len(&self, len: u64) -> Result<(), String> {
+        if let Some(min) = self.min_bytes.filter(|min| len < *min) {
+            return Err(format!("it is shorter than {min} bytes"));
+        }
+        if let Some(max) = self.max_bytes.filter(|max| len > *max) {
+            return Err(format!("it is longer than {max} bytes"));
+        }
+        Ok(())
+    }
+
+    fn check(&self, value: &[u8]) -> Result<(), String> {
+        self.check_len(u64::try_from(value.len()).unwrap_or(u64::MAX))?;
+        if let Some(min) = self.min_entropy
+            && entropy(value) < f64::from(u32::try_from(min).unwrap_or(u32::MAX))
+        {
+            return Err(format!("it has fewer than {min} bits of entropy"));
+        }
+        if self.pattern.is_none() && self.schema.is_none() {
+            return Ok(());
+        }
+        let text = str::from_utf8(value).map_err(|_e| "it is not UTF-8 text".to_string())?;
+        if let Some(pattern) = self
+            .pattern
+            .as_ref()
+            .filter(|pattern| !pattern.is_match(text))
+        {
+            return Err(format!("it does not match the pattern `{pattern}`"));
+        }
+        if let Some(schema) = &self.schema {
+            let document =
+                serde_json::from_str(text).map_err(|_e| "it is not a JSON document".to_string())?;
+            schema.check(&document)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compile a rule's `pattern`.
+///
+/// # Errors
+///
+/// * Returns an error if it is not a regular expression.
+pub(crate) fn pattern(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).context("the pattern does not compile")
+}
+
+/// The bits of entropy in `value`, estimated from how often each byte appears
+/// in it: its length times the Shannon entropy of those frequencies.
+fn entropy(value: &[u8]) -> f64 {
+    let mut counts = [0u32; 256];
+    for byte in value {
+        if let Some(count) = counts.get_mut(usize::from(*byte)) {
+            *count = count.saturating_add(1);
+        }
+    }
+    let len = f64::from(u32::try_from(value.len()).unwrap_or(u32::MAX));
+    let per_byte: f64 = counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let share = f64::from(*count) / len;
+            -share * share.log2()
+        })
+        .sum();
+    per_byte * len
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+
+    use super::{Rules, entropy};
+    use crate::config::ConfigSalusd;
+
+    fn rules(toml: &str) -> Result<Rules> {
+        let config: ConfigSalusd = ::config::Config::builder()
+            .add_source(::config::File::from_str(toml, ::config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        Ok(Rules::from(&config))
+    }
+
+    /// The rule `value` breaks under `key`, and how.
+    fn broken(rules: &Rules, key: &str, value: &str) -> Option<(String, String)> {
+        rules
+            .check(key, value.as_bytes())
+            .err()
+            .map(|failure| (failure.rule().clone(), failure.reason().clone()))
+    }
+
+    #[test]
+    fn values_are_checked_by_the_rules_for_their_keys() -> Result<()> {
+        let rules = rules(
+            "[validation.tokens]\nkeys = [\"ci/\"]\npattern = \"^ghp_[A-Za-z0-9]{36}$\"\n\
+             [validation.db]\nkeys = [\"app/db\"]\nmin_bytes = 2\nmax_bytes = 512\n\
+             schema = '{\"type\": \"object\", \"required\": [\"password\"]}'\n\
+             [validation.strong]\nkeys = [\"app/\"]\nmin_entropy = 64\n",
+        )?;
+        let token = format!("ghp_{}", "aZ09".repeat(9));
+        assert!(rules.check("ci/github", token.as_bytes()).is_ok());
+        assert!(rules.check("web/anything", b"x").is_ok());
+        assert_eq!(
+            broken(&rules, "ci/github", "ghp_short"),
+            Some((
+                "tokens".to_string(),
+                "it does not match the pattern `^ghp_[A-Za-z0-9]{36}$`".to_string()
+            ))
+        );
+        assert_eq!(
+            broken(&rules, "app/db", r#"{"user":"app"}"#),
+            Some((
+                "db".to_string(),
+                "the document has no 'password'".to_string()
+            ))
+        );
+        assert_eq!(
+            broken(&rules, "app/db", "password"),
+            Some(("db".to_string(), "it is not a JSON document".to_string()))
+        );
+        let strong = r#"{"password":"Xq7#mP2$vL9@wK4!"}"#;
+        assert!(rules.check("app/db", strong.as_bytes()).is_ok());
+        assert_eq!(
+            broken(
+                &rules,
+                "app/api",
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            ),
+            Some((
+                "strong".to_string(),
+                "it has fewer than 64 bits of entropy".to_string()
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn uploads_only_pass_rules_on_their_length() -> Result<()> {
+        let rules = rules(
+            "[validation.sized]\nkeys = [\"blobs/\"]\nmax_bytes = 1024\n\
+             [validation.documents]\nkeys = [\"docs/\"]\nschema = '{\"type\": \"object\"}'\n",
+        )?;
+        rules
+            .check_upload("blobs/a", 1024)
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        if rules.check_upload("blobs/a", 1025).is_ok() {
+            bail!("a long upload was let through");
+        }
+        if rules.check_upload("docs/a", 10).is_ok() {
+            bail!("an upload a schema checks was let through");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn a_rule_that_cannot_be_read_refuses_everything() -> Result<()> {
+        let rules = rules("[validation.broken]\nkeys = [\"app/\"]\npattern = \"(\"\n")?;
+        match rules.check("app/db", b"anything") {
+            Err(failure) if failure.reason().starts_with("the rule cannot be used") => Ok(()),
+            other => bail!("a broken rule let a value through: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn entropy_grows_with_variety_and_length() {
+        assert!(entropy(b"").abs() < f64::EPSILON);
+        assert!(entropy(b"aaaaaaaa").abs() < f64::EPSILON);
+        assert!((entropy(b"abababab") - 8.0).abs() < 1e-9);
+        assert!((entropy(b"abcdefgh") - 24.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! The part of JSON Schema a validation rule may use: `type`, `enum`,
+//! `const`, `required`, `properties`, `additionalProperties`, `items`,
+//! `minItems`, `maxItems`, `minLength`, `maxLength`, `pattern`, `minimum` and
+//! `maximum`, and the annotations (`$schema`, `title`, `description`, …).
+//!
+//! A schema using any other keyword is refused when it is read, rather than
+//! the keyword being ignored, so a rule never seems to check more than it
+//! does. A reason a document is refused names where in it the problem is,
+//! never what is there.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Keywords that describe a schema without checking anything.
+const ANNOTATIONS: [&str; 7] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+
+/// A JSON type a schema may ask for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "null" => Self::Null,
+            "boolean" => Self::Boolean,
+            "integer" => Self::Integer,
+            "number" => Self::Number,
+            "string" => Self::String,
+            "array" => Self::Array,
+            "object" => Self::Object,
+            other => bail!("'{other}' is not a JSON type"),
+        })
+    }
+
+    fn of(self, value: &Value) -> bool {
+        match self {
+            Self::Null => value.is_null(),
+            Self::Boolean => value.is_boolean(),
+            Self::Integer => {
+                value.is_i64()
+                    || value.is_u64()
+                    || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+            }
+            Self::Number => value.is_number(),
+            Self::String => value.is_string(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+}
+
+/// What an object may hold beside its `properties`
+#[derive(Debug, Default)]
+enum Additional {
+    #[default]
+    Any,
+    None,
+    Matching(Box<Schema>),
+}
+
+/// A JSON schema, read and ready to check documents with
+#[derive(Debug, Default)]
+pub(crate) struct Schema {
+    /// The `false` schema, which nothing meets
+    never: bool,
+    kinds: Option<Vec<Kind>>,
+    allowed: Option<Vec<Value>>,
+    required: Vec<String>,
+    properties: BTreeMap<String, Schema>,
+    additional: Additional,
+    items: Option<Box<Schema>>,
+    min_items: Option<u64>,
+    max_items: Option<u64>,
+    min_length: Option<u64>,
+    max_length: Option<u64>,
+    pattern: Option<Regex>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+}
+
+impl Schema {
+    /// Read the schema in the JSON document `text`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if `text` is not JSON, or not a schema this module
+    ///   can check with.
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        let schema: Value = serde_json::from_str(text).context("it is not a JSON document")?;
+        Self::from_value(&schema, "")
+    }
+
+    fn from_value(schema: &Value, at: &str) -> Result<Self> {
+        let keywords = match schema {
+            Value::Bool(accepts) => {
+                return Ok(Self {
+                    never: !accepts,
+                    ..Self::default()
+                });
+            }
+            Value::Object(keywords) => keywords,
+            _ => bail!("the schema at '{at}' is not an object or a boolean"),
+        };
+        let mut read = Self::default();
+        for (keyword, value) in keywords {
+            let bad = || anyhow!("'{keyword}' at '{at}' is malformed");
+            match keyword.as_str() {
+                "type" => {
+                    let names = match value {
+                        Value::String(name) => vec![name.as_str()],
+                        Value::Array(names) => names
+                            .iter()
+                            .map(|name| name.as_str().ok_or_else(bad))
+                            .collect::<Result<_>>()?,
+                        _ => return Err(bad()),
+                    };
+                    read.kinds = Some(names.into_iter().map(Kind::parse).collect::<Result<_>>()?);
+                }
+                "enum" => read.allowed = Some(value.as_array().ok_or_else(bad)?.clone()),
+                "const" => read.allowed = Some(vec![value.clone()]),
+                "required" => {
+                    read.required = value
+                        .as_array()
+                        .ok_or_else(bad)?
+                        .iter()
+                        .map(|name| name.as_str().map(str::to_string).ok_or_else(bad))
+                        .collect::<Result<_>>()?;
+                }
+                "properties" => {
+                    for (name, property) in value.as_object().ok_or_else(bad)? {
+                        let inner = Self::from_value(property, &pointer(at, name))?;
+                        let _old = read.properties.insert(name.clone(), inner);
+                    }
+                }
+                "additionalProperties" => {
+                    read.additional = match value {
+                        Value::Bool(true) => Additional::Any,
+                        Value::Bool(false) => Additional::None,
+                        schema => Additional::Matching(Box::new(Self::from_value(schema, at)?)),
+                    };
+                }
+                "items" => {
+                    read.items = Some(Box::new(Self::from_value(value, &pointer(at, "*"))?));
+                }
+                "minItems" => read.min_items = Some(value.as_u64().ok_or_else(bad)?),
+                "maxItems" => read.max_items = Some(value.as_u64().ok_or_else(bad)?),
+                "minLength" => read.min_length = Some(value.as_u64().ok_or_else(bad)?),
+                "maxLength" => read.max_length = Some(value.as_u64().ok_or_else(bad)?),
+                "pattern" => {
+                    let pattern = value.as_str().ok_or_else(bad)?;
+                    read.pattern = Some(
+                        Regex::new(pattern)
+                            .with_context(|| format!("'pattern' at '{at}' does not compile"))?,
+                    );
+                }
+                "minimum" => read.minimum = Some(value.as_f64().ok_or_else(bad)?),
+                "maximum" => read.maximum = Some(value.as_f64().ok_or_else(bad)?),
+                annotation if ANNOTATIONS.contains(&annotation) => {}
+                other => bail!("'{other}' at '{at}' is not a keyword salusd checks"),
+            }
+        }
+        Ok(read)
+    }
+
+    /// Check `document` against the schema.
+    ///
+    /// # Errors
+    ///
+    /// * Returns the first way `document` falls short, naming where.
+    pub(crate) fn check(&self, document: &Value) -> Result<(), String> {
+        self.check_at(document, "")
+    }
+
+    fn check_at(&self, value: &Value, at: &str) -> Result<(), String> {
+        let here = if at.is_empty() { "the document" } else { at };
+        if self.never {
+            return Err(format!("{here} is not allowed"));
+        }
+        if let Some(kinds) = &self.kinds
+            && !kinds.iter().any(|kind| kind.of(value))
+        {
+            return Err(format!("{here} is not of the type the schema asks for"));
+        }
+        if let Some(allowed) = &self.allowed
+            && !allowed.contains(value)
+        {
+            return Err(format!("{here} is not one of the values the schema allows"));
+        }
+        match value {
+            Value::Object(members) => self.check_object(members, at, here),
+            Value::Array(items) => self.check_array(items, at, here),
+            Value::String(text) => self.check_string(text, here),
+            Value::Number(number) => match number.as_f64() {
+                Some(number) if self.minimum.is_some_and(|minimum| number < minimum) => {
+                    Err(format!("{here} is below the minimum"))
+                }
+                Some(number) if self.maximum.is_some_and(|maximum| number > maximum) => {
+                    Err(format!("{here} is above the maximum"))
+                }
+                _ => Ok(()),
+            },
+            Value::Null | Value::Bool(_) => Ok(()),
+        }
+    }
+
+    fn check_object(
+        &self,
+        members: &Map<String, Value>,
+        at: &str,
+        here: &str,
+    ) -> Result<(), String> {
+        if let Some(missing) = self
+            .required
+            .iter()
+            .find(|name| !members.contains_key(name.as_str()))
+        {
+            return Err(format!("{here} has no '{missing}'"));
+        }
+        for (name, member) in members {
+            let at = pointer(at, name);
+            match (self.properties.get(name), &self.additional) {
+                (Some(property), _) => property.check_at(member, &at)?,
+                (None, Additional::Any) => {}
+                (None, Additional::None) => return Err(format!("{at} is not allowed")),
+                (None, Additional::Matching(schema)) => schema.check_at(member, &at)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn check_array(&self, items: &[Value], at: &str, here: &str) -> Result<(), String> {
+        let count = u64::try_from(items.len()).unwrap_or(u64::MAX);
+        if let Some(min) = self.min_items.filter(|min| count < *min) {
+            return Err(format!("{here} has fewer than {min} items"));
+        }
+        if let Some(max) = self.max_items.filter(|max| count > *max) {
+            return Err(format!("{here} has more than {max} items"));
+        }
+        if let Some(schema) = &self.items {
+            for (index, item) in items.iter().enumerate() {
+                schema.check_at(item, &pointer(at, &index.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_string(&self, text: &str, here: &str) -> Result<(), String> {
+        let length = u64::try_from(text.chars().count()).unwrap_or(u64::MAX);
+        if let Some(min) = self.min_length.filter(|min| length < *min) {
+            return Err(format!("{here} is shorter than {min} characters"));
+        }
+        if let Some(max) = self.max_length.filter(|max| length > *max) {
+            return Err(format!("{here} is longer than {max} characters"));
+        }
+        if let Some(pattern) = self
+            .pattern
+            .as_ref()
+            .filter(|pattern| !pattern.is_match(text))
+        {
+            return Err(format!("{here} does not match the pattern `{pattern}`"));
+        }
+        Ok(())
+    }
+}
+
+/// The JSON pointer to member `name` of the value at `at`.
+fn pointer(at: &str, name: &str) -> String {
+    format!("{at}/{}", name.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::Schema;
+
+    #[test]
+    fn documents_are_checked_against_the_schema() -> Result<()> {
+        let schema = Schema::parse(
+            r#"{
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "required": ["user", "password"],
+                "properties": {
+                    "user": {"type": "string", "pattern": "^[a-z]+$"},
+                    "password": {"type": "string", "minLength": 16},
+                    "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                    "hosts": {"type": "array", "minItems": 1, "items": {"type": "string"}},
+                    "mode": {"enum": ["ro", "rw"]}
+                },
+                "additionalProperties": false
+            }"#,
+        )?;
+        let password = "correct-horse-battery";
+        assert_eq!(
+            schema.check(&json!({"user": "app", "password": password, "port": 5432})),
+            Ok(())
+        );
+        let refused = |document| schema.check(&document).err();
+        assert_eq!(
+            refused(json!({"user": "app"})),
+            Some("the document has no 'password'".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "App", "password": password})),
+            Some("/user does not match the pattern `^[a-z]+$`".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "app", "password": "hunter2"})),
+            Some("/password is shorter than 16 characters".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "app", "password": password, "port": 70000})),
+            Some("/port is above the maximum".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "app", "password": password, "hosts": ["a", 1]})),
+            Some("/hosts/1 is not of the type the schema asks for".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "app", "password": password, "mode": "admin"})),
+            Some("/mode is not one of the values the schema allows".to_string())
+        );
+        assert_eq!(
+            refused(json!({"user": "app", "password": password, "extra": true})),
+            Some("/extra is not allowed".to_string())
+        );
+        assert_eq!(
+            refused(json!(["app"])),
+            Some("the document is not of the type the schema asks for".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn schemas_salusd_cannot_check_are_refused() {
+        assert!(Schema::parse("true").is_ok());
+        assert!(Schema::parse("not json").is_err());
+        assert!(Schema::parse(r#"{"type": "text"}"#).is_err());
+        assert!(Schema::parse(r#"{"oneOf": [{"type": "string"}]}"#).is_err());
+        assert!(Schema::parse(r#"{"properties": {"a": {"format": "email"}}}"#).is_err());
+        assert!(Schema::parse(r#"{"pattern": "("}"#).is_err());
+        assert!(Schema::parse(r#"{"minLength": -1}"#).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Named signing keys: Ed25519 and HMAC-SHA256 keys generated and held by the
+//! daemon, so callers get signatures and tags without ever holding the key.
+//!
+//! The key material lives in its own `salus_signing_keys` table, sealed under
+//! the store key like any value but bound to a `signing:` AAD, so it can never
+//! be read back through `read` or `read-prefix`. Every use bumps a per-key
+//! counter in `salus_signing_uses`, which the audit log reports.
+
+use anyhow::Result;
+use aws_lc_rs::{
+    hmac, rand,
+    signature::{self, Ed25519KeyPair, KeyPair as _},
+};
+use libsalus::{
+    NewSigningKey, Response, SignRequest, SigningAlgorithm, VerifyRequest, decode, encode,
+};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::{ShareStore, open, seal};
+use crate::{
+    db::{
+        SALUS_SIGNING_TABLE_DEF, SALUS_SIGNING_USES_TABLE_DEF, backend::Table, read_backend,
+        read_value, values::salus::SalusVal, write_keys, write_value,
+    },
+    error::Error,
+};
+
+/// The length of a generated HMAC-SHA256 key, in bytes.
+const HMAC_KEY_LEN: usize = 32;
+
+/// A signing key as it is sealed: its algorithm and key material (a PKCS#8
+/// document for Ed25519, the raw key for HMAC).
+type SigningKey = (SigningAlgorithm, Zeroizing<Vec<u8>>);
+
+impl ShareStore {
+    /// Generate and store a named signing key.
+    ///
+    /// An existing key of the same name is left alone (and `KeyExists`
+    /// returned) unless `force` is set; a replaced key's use counter restarts.
+    pub(crate) fn create_signing_key(&self, request: &NewSigningKey) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let name = request.name();
+        if !request.force() && self.signing_key(enc_key, name)?.is_some() {
+            info!("Refusing to overwrite existing signing key without force: {name}");
+            return Ok(Response::KeyExists);
+        }
+        let (material, public_key) = match request.algorithm() {
+            SigningAlgorithm::Ed25519 => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rand::SystemRandom::new())?;
+                let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())?;
+                (
+                    Zeroizing::new(pkcs8.as_ref().to_vec()),
+                    Some(pair.public_key().as_ref().to_vec()),
+                )
+            }
+            SigningAlgorithm::HmacSha256 => {
+                let mut material = Zeroizing::new(vec![0u8; HMAC_KEY_LEN]);
+                rand::fill(&mut material)?;
+                (material, None)
+            }
+            unsupported => return Ok(Response::WrongSigningAlgorithm(unsupported)),
+        };
+        let mut sealed = Zeroizing::new(encode((request.algorithm(), material.to_vec()))?);
+        let salus_val = seal(enc_key, &signing_aad(name), &mut sealed)?;
+        let mut exists = false;
+        let locked = [
+            (Table::SigningKeys, name.as_str()),
+            (Table::SigningUses, name.as_str()),
+        ];
+        write_keys(&self.backend, locked, |db| -> Result<()> {
+            // Checked again under the name's write locks, in case another
+            // create of it landed first.
+            exists = !request.force() && read_value(db, SALUS_SIGNING_TABLE_DEF, name)?.is_some();
+            if exists {
+                return Ok(());
+            }
+            write_value(db, SALUS_SIGNING_TABLE_DEF, name, &salus_val)?;
+            write_value(db, SALUS_SIGNING_USES_TABLE_DEF, name, &0)
+        })?;
+        if exists {
+            info!("Refusing to overwrite existing signing key without force: {name}");
+            return Ok(Response::KeyExists);
+        }
+        info!(
+            target: "salusd::audit",
+            key = name.as_str(), algorithm = %request.algorithm(), "Signing key created"
+        );
+        Ok(Response::SigningKeyCreated(public_key))
+    }
+
+    /// Sign a message with a named Ed25519 key.
+    pub(crate) fn sign(&self, request: &SignRequest) -> Result<Response> {
+        self.with_signing_key(request.key(), "sign", |algorithm, material| {
+            let SigningAlgorithm::Ed25519 = algorithm else {
+                return Ok(Response::WrongSigningAlgorithm(algorithm));
+            };
+            let pair = Ed25519KeyPair::from_pkcs8(material)?;
+            Ok(Response::Signature(
+                pair.sign(request.message()).as_ref().to_vec(),
+            ))
+        })
+    }
+
+    /// Compute an HMAC-SHA256 tag with a named HMAC key.
+    pub(crate) fn hmac(&self, request: &SignRequest) -> Result<Response> {
+        self.with_signing_key(request.key(), "hmac", |algorithm, material| {
+            let SigningAlgorithm::HmacSha256 = algorithm else {
+                return Ok(Response::WrongSigningAlgorithm(algorithm));
+            };
+            let key = hmac::Key::new(hmac::HMAC_SHA256, material);
+            Ok(Response::Signature(
+                hmac::sign(&key, request.message()).as_ref().to_vec(),
+            ))
+        })
+    }
+
+    /// Check an Ed25519 signature or an HMAC tag against a named key; tags are
+    /// compared in constant time.
+    pub(crate) fn verify_signature(&self, request: &VerifyRequest) -> Result<Response> {
+        self.with_signing_key(request.key(), "verify", |algorithm, material| {
+            let valid = match algorithm {
+                SigningAlgorithm::Ed25519 => {
+                    let pair = Ed25519KeyPair::from_pkcs8(material)?;
+                    signature::UnparsedPublicKey::new(&signature::ED25519, pair.public_key())
+                        .verify(request.message(), request.signature())
+                        .is_ok()
+                }
+                SigningAlgorithm::HmacSha256 => {
+                    let key = hmac::Key::new(hmac::HMAC_SHA256, material);
+                    hmac::verify(&key, request.message(), request.signature()).is_ok()
+                }
+                unsupported => return Ok(Response::WrongSigningAlgorithm(unsupported)),
+            };
+            Ok(Response::SignatureValid(valid))
+        })
+    }
+
+    /// Run `operation` with the named key, counting the use when it succeeds.
+    pub(super) fn with_signing_key<F>(&self, name: &str, operation: &str, f: F) -> Result<Response>
+    where
+        F: FnOnce(SigningAlgorithm, &[u8]) -> Result<Response>,
+    {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        let Some((algorithm, material)) = self.signing_key(enc_key, name)? else {
+            info!("Signing key not found: {name}");
+            return Ok(Response::SigningKeyNotFound);
+        };
+        let response = f(algorithm, &material)?;
+        if !matches!(response, Response::WrongSigningAlgorithm(_)) {
+            let uses = self.count_signing_use(name)?;
+            info!(target: "salusd::audit", key = name, operation, uses, "Signing key used");
+        }
+        Ok(response)
+    }
+
+    /// The named signing key, if there is one.
+    fn signing_key(&self, enc_key: &[u8], name: &str) -> Result<Option<SigningKey>> {
+        let mut sealed = None;
+        read_backend(&self.backend, |db| -> Result<()> {
+            sealed = read_value(db, SALUS_SIGNING_TABLE_DEF, name)?;
+            Ok(())
+        })?;
+        sealed
+            .map(|sealed| open_signing_key(enc_key, name, &sealed))
+            .transpose()
+    }
+
+    /// Add one to the named key's use counter, returning the new count.
+    fn count_signing_use(&self, name: &str) -> Result<u64> {
+        let mut uses = 0;
+        write_keys(
+            &self.backend,
+            [(Table::SigningUses, name)],
+            |db| -> Result<()> {
+                let previous = read_value(db, SALUS_SIGNING_USES_TABLE_DEF, name)?.unwrap_or(0);
+                uses = previous.saturating_add(1);
+                write_value(db, SALUS_SIGNING_USES_TABLE_DEF, name, &uses)
+            },
+        )?;
+        Ok(uses)
+    }
+}
+
+/// Open the sealed signing key `name`.
+pub(super) fn open_signing_key(
+    enc_key: &[u8],
+    name: &str,
+    sealed: &SalusVal,
+) -> Result<SigningKey> {
+    let plaintext = Zeroizing::new(open(enc_key, &signing_aad(name), sealed)?);
+    let (algorithm, material): (SigningAlgorithm, Vec<u8>) = decode(&plaintext)?;
+    Ok((algorithm, Zeroizing::new(material)))
+}
+
+/// The AAD a signing key is sealed under, distinct from any value key's.
+fn signing_aad(name: &str) -> String {
+    format!("signing:{name}")
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{NewSigningKey, Response, SignRequest, SigningAlgorithm, VerifyRequest};
+
+    use super::super::test::unlocked_store;
+
+    #[test]
+    fn ed25519_signatures_verify_and_the_key_stays_inside() -> Result<()> {
+        let store = unlocked_store()?;
+        let create = NewSigningKey::builder()
+            .name("release")
+            .algorithm(SigningAlgorithm::Ed25519)
+            .build();
+        let Response::SigningKeyCreated(Some(public_key)) = store.create_signing_key(&create)?
+        else {
+            bail!("expected a public key");
+        };
+        assert_eq!(public_key.len(), 32);
+        assert!(matches!(
+            store.create_signing_key(&create)?,
+            Response::KeyExists
+        ));
+        // The key is not a value, so it cannot be read back.
+        assert!(matches!(store.read("release")?, Response::Value(None)));
+
+        let sign = SignRequest::builder()
+            .key("release")
+            .message(b"v1.0.0".to_vec())
+            .build();
+        let Response::Signature(signature) = store.sign(&sign)? else {
+            bail!("expected a signature");
+        };
+        let verify = |message: &[u8]| {
+            VerifyRequest::builder()
+                .key("release")
+                .message(message.to_vec())
+                .signature(signature.clone())
+                .build()
+        };
+        assert!(matches!(
+            store.verify_signature(&verify(b"v1.0.0"))?,
+            Response::SignatureValid(true)
+        ));
+        assert!(matches!(
+            store.verify_signature(&verify(b"v1.0.1"))?,
+            Response::SignatureValid(false)
+        ));
+        assert_eq!(store.count_signing_use("release")?, 4);
+        assert!(matches!(
+            store.hmac(&sign)?,
+            Response::WrongSigningAlgorithm(SigningAlgorithm::Ed25519)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn hmac_tags_verify_and_unknown_keys_are_reported() -> Result<()> {
+        let store = unlocked_store()?;
+        let create = NewSigningKey::builder()
+            .name("webhook")
+            .algorithm(SigningAlgorithm::HmacSha256)
+            .build();
+        assert!(matches!(
+            store.create_signing_key(&create)?,
+            Response::SigningKeyCreated(None)
+        ));
+        let request = SignRequest::builder()
+            .key("webhook")
+            .message(b"payload".to_vec())
+            .build();
+        let Response::Signature(tag) = store.hmac(&request)? else {
+            bail!("expected a tag");
+        };
+        assert_eq!(tag.len(), 32);
+        let verify = VerifyRequest::builder()
+            .key("webhook")
+            .message(b"payload".to_vec())
+            .signature(tag)
+            .build();
+        assert!(matches!(
+            store.verify_signature(&verify)?,
+            Response::SignatureValid(true)
+        ));
+        let missing = SignRequest::builder().key("nope").message(vec![]).build();
+        assert!(matches!(
+            store.sign(&missing)?,
+            Response::SigningKeyNotFound
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Copying values between two daemons.
+//!
+//! The destination issues a wrapping key (see [`super::wrap`]); the source
+//! opens each value under the requested prefixes and seals it to that key; the
+//! destination unwraps every value before writing any, then seals each under
+//! its own store key. Whoever drives the sync only ever holds values sealed to
+//! the destination. Each value carries the time it was last written, which the
+//! destination records with it, so `newest-wins` can compare the two copies.
+//! Values stored in chunks are listed rather than copied.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use libsalus::{
+    ExportSync, ImportSync, Response, SyncBundle, SyncEntry, SyncOutcome, SyncStrategy,
+    WRAP_PUBLIC_KEY_LEN, wrap_key,
+};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::ShareStore;
+use crate::{
+    db::{
+        CHECK_KEY_KEY, SALUS_VAL_TABLE_DEF, SALUS_WRITTEN_TABLE_DEF, put, read_backend, read_value,
+        scan_values,
+    },
+    error::Error,
+};
+
+/// What a sync does with one value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Verdict {
+    /// The destination has no such key.
+    Copy,
+    /// The destination's value is replaced.
+    Overwrite,
+    /// The destination's value is kept.
+    Skip,
+}
+
+impl ShareStore {
+    /// Seal every value under the requested prefixes to the destination's
+    /// wrapping key, with the time each was last written.
+    pub(crate) fn export_sync(&self, request: &ExportSync) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        if !request.dry_run() && request.recipient().len() != WRAP_PUBLIC_KEY_LEN {
+            return Ok(Response::InvalidPublicKey);
+        }
+        let (mut rows, mut written) = (BTreeMap::new(), BTreeMap::new());
+        read_backend(&self.backend, |db| -> Result<()> {
+            for prefix in request.prefixes() {
+                rows.extend(scan_values(db, SALUS_VAL_TABLE_DEF, prefix)?);
+                written.extend(scan_values(db, SALUS_WRITTEN_TABLE_DEF, prefix)?);
+            }
+            Ok(())
+        })?;
+        let _check = rows.remove(CHECK_KEY_KEY);
+        let (mut entries, mut streamed) = (vec![], vec![]);
+        for (key, sealed) in rows {
+            if sealed.blob_id()?.is_some() {
+                streamed.push(key);
+                continue;
+            }
+            let wrapped = if request.dry_run() {
+                vec![]
+            } else {
+                let value = Zeroizing::new(self.open_value(enc_key, &key, &sealed)?);
+                let Ok(wrapped) = wrap_key(request.recipient(), &value) else {
+                    return Ok(Response::InvalidPublicKey);
+                };
+                wrapped
+            };
+            let written = written.get(&key).copied();
+            entries.push(
+                SyncEntry::builder()
+                    .key(key)
+                    .wrapped(wrapped)
+                    .maybe_written(written)
+                    .build(),
+            );
+        }
+        info!(
+            target: "salusd::audit",
+            peer = request.peer().as_str(),
+            prefixes = ?request.prefixes(),
+            values = entries.len(),
+            dry_run = request.dry_run(),
+            "Sync exported"
+        );
+        Ok(Response::SyncBundle(
+            SyncBundle::builder()
+                .entries(entries)
+                .streamed(streamed)
+                .build(),
+        ))
+    }
+
+    /// Store the values of a sync under the requested strategy.
+    ///
+    /// Every value is unwrapped before any is written, so a bundle sealed to
+    /// another key writes nothing. The wrapping key is spent once the values
+    /// are stored; a dry run needs none and leaves it in place.
+    pub(crate) fn import_sync(&mut self, request: &ImportSync) -> Result<Response> {
+        let Some(enc_key) = &self.key else {
+            return Err(Error::StoreNotUnlocked.into());
+        };
+        // The sentinel is never a value, whatever the source sent.
+        let (entries, sentinel): (Vec<_>, Vec<_>) = request
+            .entries()
+            .iter()
+            .partition(|entry| entry.key() != CHECK_KEY_KEY);
+        let mut tally = Tally {
+            skipped: sentinel.iter().map(|entry| entry.key().clone()).collect(),
+            ..Tally::default()
+        };
+        if request.dry_run() {
+            read_backend(&self.backend, |db| -> Result<()> {
+                for entry in &entries {
+                    let exists = read_value(db, SALUS_VAL_TABLE_DEF, entry.key())?.is_some();
+                    let ours = read_value(db, SALUS_WRITTEN_TABLE_DEF, entry.key())?;
+                    let verdict = verdict(request.strategy(), exists, ours, entry.written());
+                    tally.record(verdict, entry.key());
+                }
+                Ok(())
+            })?;
+        } else {
+            let Some(wrapping_key) = &self.wrapping_key else {
+                info!("Refusing a sync with no wrapping key issued");
+                return Ok(Response::InvalidWrappedKey);
+            };
+            let mut values = Vec::with_capacity(entries.len());
+            for entry in &entries {
+                let Ok(value) = wrapping_key.unwrap_key(entry.wrapped()) else {
+                    info!("Refusing a sync not wrapped to the current wrapping key");
+                    return Ok(Response::InvalidWrappedKey);
+                };
+                values.push(value);
+            }
+            // Nothing is imported when one value breaks a rule here.
+            for (entry, value) in entries.iter().zip(&values) {
+                if let Err(failure) = self.rules.check(entry.key(), value) {
+                    info!(
+                        "Refusing a sync: the value for {} breaks rule {}",
+                        entry.key(),
+                        failure.rule()
+                    );
+                    return Ok(Response::ValidationFailed(failure));
+                }
+            }
+            for (entry, value) in entries.iter().zip(values) {
+                let key = entry.key().as_str();
+                let sealed = self.seal_value(enc_key, key, value.to_vec())?;
+                let (mut decided, mut version) = (Verdict::Skip, None);
+                self.write_value_row(key, |db, existing| -> Result<()> {
+                    let ours = read_value(db, SALUS_WRITTEN_TABLE_DEF, key)?;
+                    decided = verdict(
+                        request.strategy(),
+                        existing.is_some(),
+                        ours,
+                        entry.written(),
+                    );
+                    if decided == Verdict::Skip {
+                        return Ok(());
+                    }
+                    let mut ops = Self::release_chunks(db, enc_key, key, existing.as_ref())?;
+                    ops.push(put(SALUS_VAL_TABLE_DEF, key, &sealed));
+                    // The write time is the source's, so a later sync can
+                    // tell which side wrote last.
+                    let (revised, next) =
+                        self.revised(db, key, existing.as_ref(), entry.written())?;
+                    ops.extend(revised);
+                    db.commit(ops)?;
+                    version = Some(next);
+                    self.read_cache.invalidate([key]);
+                    Ok(())
+                })?;
+                if let Some(version) = version {
+                    self.changes.written(key, version);
+                }
+                tally.record(decided, key);
+            }
+            self.wrapping_key = None;
+        }
+        let outcome = tally.outcome(!request.dry_run());
+        info!(
+            target: "salusd::audit",
+            peer = request.peer().as_str(),
+            strategy = ?request.strategy(),
+            copied = outcome.copied().len(),
+            overwritten = outcome.overwritten().len(),
+            skipped = outcome.skipped().len(),
+            dry_run = request.dry_run(),
+            "Sync imported"
+        );
+        Ok(Response::Synced(outcome))
+    }
+}
+
+/// The keys of a sync, by what became of them.
+#[derive(Debug, Default)]
+struct Tally {
+    copied: Vec<String>,
+    overwritten: Vec<String>,
+    skipped: Vec<String>,
+}
+
+impl Tally {
+    fn record(&mut self, verdict: Verdict, key: &str) {
+        let keys = match verdict {
+            Verdict::Copy => &mut self.copied,
+            Verdict::Overwrite => &mut self.overwritten,
+            Verdict::Skip => &mut self.skipped,
+        };
+        keys.push(key.to_string());
+    }
+
+    fn outcome(self, applied: bool) -> SyncOutcome {
+        SyncOutcome::builder()
+            .copied(self.copied)
+            .overwritten(self.overwritten)
+            .skipped(self.skipped)
+            .applied(applied)
+            .build()
+    }
+}
+
+/// What to do with a value the destination may already hold, last written at
+/// `ours`, given the source's copy was last written at `theirs`.
+fn verdict(
+    strategy: SyncStrategy,
+    exists: bool,
+    ours: Option<u64>,
+    theirs: Option<u64>,
+) -> Verdict {
+    if !exists {
+        return Verdict::Copy;
+    }
+    match strategy {
+        SyncStrategy::Overwrite => Verdict::Overwrite,
+        // No recorded time is older than any time.
+        SyncStrategy::NewestWins if theirs > ours => Verdict::Overwrite,
+        SyncStrategy::Skip | SyncStrategy::NewestWins => Verdict::Skip,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{ExportSync, ImportSync, Response, SyncOutcome, SyncStrategy};
+
+    use crate::{
+        db::{SALUS_WRITTEN_TABLE_DEF, backend::StorageBackend, unlock_backend, write_value},
+        store::{ShareStore, test::unlocked_store},
+    };
+
+    fn stored(store: &ShareStore, key: &str, value: &[u8], written: u64) -> Result<()> {
+        let Response::Success = store.store(key, value.to_vec(), true)? else {
+            bail!("expected '{key}' to be stored");
+        };
+        unlock_backend(&store.backend, |db: &dyn StorageBackend| -> Result<()> {
+            write_value(db, SALUS_WRITTEN_TABLE_DEF, key, &written)
+        })
+    }
+
+    fn sync(
+        source: &ShareStore,
+        destination: &mut ShareStore,
+        strategy: SyncStrategy,
+        dry_run: bool,
+    ) -> Result<SyncOutcome> {
+        let recipient = if dry_run {
+            vec![]
+        } else {
+            let Response::WrappingKey(recipient) = destination.new_wrapping_key()? else {
+                bail!("expected a wrapping key");
+            };
+            recipient
+        };
+        let export = ExportSync::builder()
+            .prefixes(vec!["app/".to_string()])
+            .recipient(recipient)
+            .peer("destination")
+            .dry_run(dry_run)
+            .build();
+        let Response::SyncBundle(bundle) = source.export_sync(&export)? else {
+            bail!("expected a sync bundle");
+        };
+        assert!(
+            bundle
+                .entries()
+                .iter()
+                .all(|entry| entry.wrapped().is_empty() == dry_run)
+        );
+        let import = ImportSync::builder()
+            .entries(bundle.entries().clone())
+            .strategy(strategy)
+            .peer("source")
+            .dry_run(dry_run)
+            .build();
+        match destination.import_sync(&import)? {
+            Response::Synced(outcome) => Ok(outcome),
+            other => bail!("expected a sync outcome, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_sync_copies_only_its_prefixes_and_spends_the_wrapping_key() -> Result<()> {
+        let source = unlocked_store()?;
+        let mut destination = unlocked_store()?;
+        stored(&source, "app/db", b"new", 2)?;
+        stored(&source, "app/api", b"token", 2)?;
+        stored(&source, "web/x", b"elsewhere", 2)?;
+        stored(&destination, "app/db", b"old", 1)?;
+
+        let planned = sync(&source, &mut destination, SyncStrategy::Skip, true)?;
+        assert_eq!(planned.copied(), &["app/api"]);
+        assert_eq!(planned.skipped(), &["app/db"]);
+        assert!(!planned.applied());
+        assert!(matches!(
+            destination.read("app/api")?,
+            Response::Value(None)
+        ));
+
+        let synced = sync(&source, &mut destination, SyncStrategy::Overwrite, false)?;
+        assert_eq!(synced.copied(), &["app/api"]);
+        assert_eq!(synced.overwritten(), &["app/db"]);
+        assert!(synced.applied());
+        assert!(matches!(
+            destination.read("app/db")?,
+            Response::Value(Some(ref value)) if value == b"new"
+        ));
+        assert!(matches!(destination.read("web/x")?, Response::Value(None)));
+
+        // The wrapping key is spent, and nothing is written without one.
+        let replay = ImportSync::builder().entries(vec![]).peer("source").build();
+        assert!(matches!(
+            destination.import_sync(&replay)?,
+            Response::InvalidWrappedKey
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn newest_wins_keeps_the_later_write() -> Result<()> {
+        let source = unlocked_store()?;
+        let mut destination = unlocked_store()?;
+        stored(&source, "app/a", b"source a", 200)?;
+        stored(&source, "app/b", b"source b", 100)?;
+        stored(&destination, "app/a", b"destination a", 100)?;
+        stored(&destination, "app/b", b"destination b", 200)?;
+        destination.keep_versions = 1;
+
+        let synced = sync(&source, &mut destination, SyncStrategy::NewestWins, false)?;
+        assert_eq!(synced.overwritten(), &["app/a"]);
+        assert_eq!(synced.skipped(), &["app/b"]);
+        assert!(matches!(
+            destination.read("app/a")?,
+            Response::Value(Some(ref value)) if value == b"source a"
+        ));
+        assert!(matches!(
+            destination.read("app/b")?,
+            Response::Value(Some(ref value)) if value == b"destination b"
+        ));
+        // The value it replaced is kept, and the copy keeps the source's write
+        // time, so syncing again is a no-op.
+        match destination.history("app/a")? {
+            Response::History(versions) => {
+                let written = versions
+                    .iter()
+                    .map(|version| (version.version(), version.written()))
+                    .collect::<Vec<_>>();
+                assert_eq!(written, [(1, Some(100)), (2, Some(200))]);
+            }
+            other => bail!("expected the history of app/a, got {other:?}"),
+        }
+        let again = sync(&source, &mut destination, SyncStrategy::NewestWins, false)?;
+        assert_eq!(again.skipped(), &["app/a", "app/b"]);
+        Ok(())
+    }
+}
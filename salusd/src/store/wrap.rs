@@ -0,0 +1,151 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Wrapped key import and export, for custody transfers.
+//!
+//! To import, a client asks for a wrapping key: an X25519 key pair generated
+//! in memory, whose public half is handed out and whose private half unwraps
+//! exactly one import before it is discarded (and is dropped on lock). To
+//! export, a stored value is sealed to the recipient's X25519 public key. The
+//! format is [`libsalus::wrap_key`]'s, so the key is never in the clear on
+//! the socket in either direction.
+
+use anyhow::Result;
+use libsalus::{
+    ExportWrapped, ImportWrapped, Response, WRAP_PUBLIC_KEY_LEN, WrappingKey, wrap_key,
+};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use super::ShareStore;
+use crate::error::Error;
+
+impl ShareStore {
+    /// Generate a wrapping key for the next import, replacing any earlier one,
+    /// and return its public half.
+    pub(crate) fn new_wrapping_key(&mut self) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let wrapping_key = WrappingKey::generate()?;
+        let public_key = wrapping_key.public_key()?;
+        self.wrapping_key = Some(wrapping_key);
+        info!(target: "salusd::audit", "Wrapping key issued");
+        Ok(Response::WrappingKey(public_key))
+    }
+
+    /// Unwrap a value sealed to the current wrapping key and store it.
+    ///
+    /// The wrapping key is discarded once the value is stored; a refused or
+    /// failed import leaves it in place for a retry.
+    pub(crate) fn import_wrapped(&mut self, request: &ImportWrapped) -> Result<Response> {
+        if self.key.is_none() {
+            return Err(Error::StoreNotUnlocked.into());
+        }
+        let Some(wrapping_key) = &self.wrapping_key else {
+            info!("Refusing a wrapped import with no wrapping key issued");
+            return Ok(Response::InvalidWrappedKey);
+        };
+        let Ok(value) = wrapping_key.unwrap_key(request.wrapped()) else {
+            info!("Refusing a value not wrapped to the current wrapping key");
+            return Ok(Response::InvalidWrappedKey);
+        };
+        let response = self.store(request.key(), value.to_vec(), request.force())?;
+        if matches!(response, Response::Success) {
+            self.wrapping_key = None;
+            info!(target: "salusd::audit", key = request.key().as_str(), "Wrapped key imported");
+        }
+        Ok(response)
+    }
+
+    /// Seal the value stored under a key to a recipient's public key.
+    pub(crate) fn export_wrapped(&self, request: &ExportWrapped) -> Result<Response> {
+        if request.recipient().len() != WRAP_PUBLIC_KEY_LEN {
+            return Ok(Response::InvalidPublicKey);
+        }
+        let Response::Value(Some(value)) = self.read(request.key())? else {
+            return Ok(Response::KeyNotFound);
+        };
+        let value = Zeroizing::new(value);
+        let Ok(wrapped) = wrap_key(request.recipient(), &value) else {
+            return Ok(Response::InvalidPublicKey);
+        };
+        info!(target: "salusd::audit", key = request.key().as_str(), "Wrapped key exported");
+        Ok(Response::Wrapped(wrapped))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use libsalus::{ExportWrapped, ImportWrapped, Response, WrappingKey, wrap_key};
+
+    use super::super::test::unlocked_store;
+
+    #[test]
+    fn a_wrapping_key_imports_once() -> Result<()> {
+        let mut store = unlocked_store()?;
+        let stray = ImportWrapped::builder()
+            .key("hsm/key")
+            .wrapped(vec![1, 2, 3])
+            .build();
+        assert!(matches!(
+            store.import_wrapped(&stray)?,
+            Response::InvalidWrappedKey
+        ));
+        let Response::WrappingKey(public_key) = store.new_wrapping_key()? else {
+            bail!("expected a wrapping key");
+        };
+        let request = ImportWrapped::builder()
+            .key("hsm/key")
+            .wrapped(wrap_key(&public_key, b"key material")?)
+            .build();
+        assert!(matches!(store.import_wrapped(&request)?, Response::Success));
+        assert!(matches!(
+            store.read("hsm/key")?,
+            Response::Value(Some(ref value)) if value == b"key material"
+        ));
+        // The wrapping key is spent.
+        assert!(matches!(
+            store.import_wrapped(&request)?,
+            Response::InvalidWrappedKey
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn exports_open_only_for_the_recipient() -> Result<()> {
+        let store = unlocked_store()?;
+        assert!(matches!(
+            store.store("db/key", b"custody".to_vec(), false)?,
+            Response::Success
+        ));
+        let recipient = WrappingKey::generate()?;
+        let export = |key: &str, recipient: Vec<u8>| {
+            ExportWrapped::builder()
+                .key(key)
+                .recipient(recipient)
+                .build()
+        };
+        let Response::Wrapped(wrapped) =
+            store.export_wrapped(&export("db/key", recipient.public_key()?))?
+        else {
+            bail!("expected a wrapped value");
+        };
+        assert_eq!(recipient.unwrap_key(&wrapped)?.as_slice(), b"custody");
+        assert!(matches!(
+            store.export_wrapped(&export("nope", recipient.public_key()?))?,
+            Response::KeyNotFound
+        ));
+        assert!(matches!(
+            store.export_wrapped(&export("db/key", vec![0; 5]))?,
+            Response::InvalidPublicKey
+        ));
+        Ok(())
+    }
+}
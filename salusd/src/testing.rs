@@ -0,0 +1,276 @@
+// Copyright (c) 2025 salus developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! A real daemon for integration tests, built with the `testing` feature.
+//!
+//! [`TestDaemon::start`] initializes and unlocks a store in memory, then
+//! serves it on a socket of its own from a background thread, answering
+//! requests exactly as `salusd` does. Dropping the daemon stops it and
+//! removes the socket, so every test can start its own.
+
+use std::{
+    io, process,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context as _, Result, bail};
+use interprocess::local_socket::ListenerOptions;
+use libsalus::{Client, Response, socket_name};
+use tokio::{
+    runtime::Builder,
+    select,
+    sync::{oneshot, watch},
+};
+
+use crate::{
+    config::{ConfigSalusd, reload::Limits},
+    db::{SharedBackend, backend::MemoryBackend},
+    runtime::{
+        listeners::{Bound, Serving},
+        serve,
+    },
+    store::ShareStore,
+};
+
+/// Counts the daemons started by this process, to name their sockets apart.
+static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+/// An unlocked, in-memory daemon serving a socket of its own until dropped.
+#[derive(Debug)]
+pub struct TestDaemon {
+    /// The path of the socket the daemon listens on.
+    socket: String,
+    /// The shares the store was initialized with.
+    shares: Vec<String>,
+    /// Stops the daemon when sent to or dropped.
+    stop: Option<oneshot::Sender<()>>,
+    /// The thread serving the socket.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestDaemon {
+    /// Initialize and unlock an empty in-memory store, and serve it on a new
+    /// socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be initialized or unlocked, or
+    /// the socket cannot be listened on.
+    pub fn start() -> Result<Self> {
+        let (store, shares) = unlocked_store()?;
+        let socket = unique_socket();
+        let name = socket_name(Some(&socket))?;
+        let (stop, stopped) = oneshot::channel();
+        let (ready, listening) = mpsc::channel::<io::Result<()>>();
+        let thread = thread::Builder::new()
+            .name("salusd-test".to_string())
+            .spawn(move || {
+                let runtime = match Builder::new_multi_thread()
+                    .worker_threads(2)
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _sent = ready.send(Err(e));
+                        return;
+                    }
+                };
+                runtime.block_on(async move {
+                    let listener = match ListenerOptions::new().name(name).create_tokio() {
+                        Ok(listener) => listener,
+                        Err(e) => {
+                            let _sent = ready.send(Err(e));
+                            return;
+                        }
+                    };
+                    let _sent = ready.send(Ok(()));
+                    let (_limits, limits) = watch::channel(Limits::from(&ConfigSalusd::default()));
+                    select! {
+                        _ = serve(Bound::Socket(listener), Serving::default(), store, limits, None) => {}
+                        _ = stopped => {}
+                    }
+                });
+            })?;
+        let daemon = Self {
+            socket,
+            shares,
+            stop: Some(stop),
+            thread: Some(thread),
+        };
+        listening
+            .recv()
+            .context("the test daemon stopped before listening")??;
+        Ok(daemon)
+    }
+
+    /// The path of the socket the daemon listens on, to pass as the socket
+    /// override to a client.
+    #[must_use]
+    pub fn socket(&self) -> &str {
+        &self.socket
+    }
+
+    /// The shares the store was initialized with, to unlock it again after a
+    /// lock.
+    #[must_use]
+    pub fn shares(&self) -> &[String] {
+        &self.shares
+    }
+
+    /// A blocking client for the daemon.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon cannot be reached.
+    pub fn client(&self) -> Result<Client> {
+        Client::connect(Some(&self.socket))
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        // Dropping the listener with the runtime removes the socket file.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _joined = thread.join();
+        }
+    }
+}
+
+/// An empty in-memory store, initialized and unlocked, with its shares.
+fn unlocked_store() -> Result<(Arc<RwLock<ShareStore>>, Vec<String>)> {
+    let backend = Arc::new(SharedBackend::new(MemoryBackend::default()));
+    let mut store = ShareStore::builder().backend(backend).build();
+    let Response::Shares(shares) = store.gen_shares()? else {
+        bail!("expected shares");
+    };
+    let shares = shares.shares().to_vec();
+    for share in shares.iter().take(usize::from(store.get_threshold())) {
+        store.add_share(share.clone());
+    }
+    if !matches!(store.unlock()?, Response::Success) {
+        bail!("expected the store to unlock");
+    }
+    Ok((Arc::new(RwLock::new(store)), shares))
+}
+
+/// A socket path no other test daemon, in this process or another, uses.
+fn unique_socket() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    let name = format!(
+        "salusd-test-{}-{}-{nanos}.sock",
+        process::id(),
+        STARTED.fetch_add(1, Ordering::Relaxed)
+    );
+    if cfg!(windows) {
+        format!(r"\\.\pipe\{name}")
+    } else {
+        std::env::temp_dir().join(name).display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use anyhow::{Context as _, Result, bail};
+    use libsalus::{Action, ClientApi as _, Refusal, Response, Share, UnlockTimeout};
+
+    use super::TestDaemon;
+
+    #[test]
+    fn values_round_trip_through_a_real_daemon() -> Result<()> {
+        let daemon = TestDaemon::start()?;
+        let client = daemon.client()?;
+        assert!(client.store("db/password", "s3cret", false)?);
+        assert!(!client.store("db/password", "other", false)?);
+        assert_eq!(
+            client
+                .read("db/password")?
+                .as_ref()
+                .map(|secret| secret.expose().as_slice()),
+            Some(&b"s3cret"[..])
+        );
+        assert!(client.read("missing")?.is_none());
+        assert!(client.delete("db/password")?);
+        assert!(!client.delete("db/password")?);
+
+        let Response::Status(status) = client.send(Action::Status)? else {
+            bail!("expected a status");
+        };
+        assert!(status.initialized());
+        assert!(!status.sealed());
+        Ok(())
+    }
+
+    #[test]
+    fn pipelined_requests_are_matched_to_their_responses() -> Result<()> {
+        let daemon = TestDaemon::start()?;
+        let client = daemon.client()?;
+        assert!(client.store("a", "1", false)?);
+        assert!(client.store("b", "2", false)?);
+        let responses = client.pipeline(vec![
+            Action::Read("a".to_string()),
+            Action::Ping,
+            Action::Read("missing".to_string()),
+            Action::Read("b".to_string()),
+        ])?;
+        match responses.as_slice() {
+            [
+                Response::Value(Some(a)),
+                Response::Pong,
+                Response::Value(None),
+                Response::Value(Some(b)),
+            ] => assert_eq!((a.as_slice(), b.as_slice()), (&b"1"[..], &b"2"[..])),
+            other => bail!("expected the responses in request order, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn a_locked_daemon_unlocks_with_its_shares_and_stops_when_dropped() -> Result<()> {
+        let daemon = TestDaemon::start()?;
+        let client = daemon.client()?;
+        assert!(client.store("k", "v", false)?);
+        let _locked = client.send(Action::Lock)?;
+        let refusal = client.read("k").err().context("read while locked")?;
+        assert!(matches!(
+            refusal.downcast_ref::<Refusal>(),
+            Some(Refusal::Error(_))
+        ));
+
+        let Response::Status(status) = client.send(Action::Status)? else {
+            bail!("expected a status");
+        };
+        assert!(status.sealed());
+        for share in daemon.shares().iter().take(usize::from(status.threshold())) {
+            let share = Share::builder().share(share.as_str()).build();
+            let _added = client.send(Action::Share(share))?;
+        }
+        assert!(matches!(
+            client.send(Action::Unlock(UnlockTimeout::Default))?,
+            Response::Success
+        ));
+        assert!(client.read("k")?.is_some());
+
+        let socket = daemon.socket().to_string();
+        drop(daemon);
+        assert!(client.read("k").is_err());
+        assert!(!Path::new(&socket).exists());
+        Ok(())
+    }
+}
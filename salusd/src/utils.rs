@@ -7,6 +7,7 @@
 // modified, or distributed except according to those terms.
 
 use std::{
+    env,
     fs::create_dir_all,
     path::{Path, PathBuf},
 };
@@ -15,9 +16,47 @@ use anyhow::{Context, Result};
 
 use crate::error::Error;
 
-#[allow(clippy::unnecessary_wraps)]
-pub(crate) fn to_path_buf(path: &String) -> Result<PathBuf> {
-    Ok(PathBuf::from(path))
+/// `path`, with a leading `~` and any `${VAR}` in it expanded.
+pub(crate) fn to_path_buf(path: &str) -> Result<PathBuf> {
+    Ok(PathBuf::from(expand(path)?))
+}
+
+/// Expand a leading `~` in `path` to the home directory, and every `${VAR}`
+/// to the value of that environment variable, so one config file can serve
+/// several users and environments.
+///
+/// # Errors
+///
+/// * Returns an error if `path` names a variable that is not set, leaves a
+///   `${` unclosed, or starts with `~` where there is no home directory.
+pub(crate) fn expand(path: &str) -> Result<String> {
+    expand_with(path, dirs2::home_dir(), |name| env::var(name).ok())
+}
+
+fn expand_with(
+    path: &str,
+    home: Option<PathBuf>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        let home = home.ok_or_else(|| Error::HomeDir(path.to_string()))?;
+        expanded.push_str(&home.to_string_lossy());
+        rest = rest.strip_prefix('~').unwrap_or(rest);
+    }
+    while let Some((before, after)) = rest.split_once("${") {
+        let (name, after) = after
+            .split_once('}')
+            .ok_or_else(|| Error::UnclosedPathVar(path.to_string()))?;
+        let value =
+            var(name).ok_or_else(|| Error::UnsetPathVar(name.to_string(), path.to_string()))?;
+        expanded.push_str(before);
+        expanded.push_str(&value);
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 /// Create the parent directory of `path` if it does not already exist.
@@ -31,3 +70,69 @@ pub(crate) fn ensure_parent_dir(path: &Path) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use anyhow::Result;
+
+    use super::expand_with;
+
+    /// A new directory under the temp dir for a test, its name made unique
+    /// with the process id and the time.
+    pub(crate) fn temp_dir(name: &str) -> Result<PathBuf> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let dir = env::temp_dir().join(format!("salusd-{name}-{}-{nanos}", process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn expand(path: &str) -> Result<String> {
+        expand_with(
+            path,
+            Some(PathBuf::from("/home/salus")),
+            |name| match name {
+                "HOME" => Some("/home/salus".to_string()),
+                "ENV" => Some("prod".to_string()),
+                _ => None,
+            },
+        )
+    }
+
+    #[test]
+    fn home_and_variables_are_expanded() -> Result<()> {
+        assert_eq!(expand("~")?, "/home/salus");
+        assert_eq!(
+            expand("~/salusd/salusd.redb")?,
+            "/home/salus/salusd/salusd.redb"
+        );
+        assert_eq!(
+            expand("${HOME}/salusd/${ENV}.redb")?,
+            "/home/salus/salusd/prod.redb"
+        );
+        assert_eq!(
+            expand("~/${ENV}/${ENV}.sock")?,
+            "/home/salus/prod/prod.sock"
+        );
+        // Only a leading `~` is the home directory, and `~user` is left alone.
+        assert_eq!(expand("/srv/~/salusd")?, "/srv/~/salusd");
+        assert_eq!(expand("~other/salusd")?, "~other/salusd");
+        assert_eq!(expand("/srv/$ENV")?, "/srv/$ENV");
+        Ok(())
+    }
+
+    #[test]
+    fn unset_and_unclosed_variables_are_errors() {
+        assert!(expand("/srv/${MISSING}/salusd.redb").is_err());
+        assert!(expand("/srv/${ENV/salusd.redb").is_err());
+        assert!(expand_with("~/salusd", None, |_| None).is_err());
+    }
+}
@@ -13,12 +13,16 @@
 //! copies the systemd user unit and the example config. Each binary's output is
 //! written to `dist/<binary>/`.
 //!
+//! `cargo xtask header` regenerates `libsalus/include/salus.h`, the C header
+//! for the `ffi` feature's `extern "C"` functions.
+//!
 //! # Usage
 //!
 //! ```text
 //! cargo xtask dist salusd
 //! cargo xtask dist salusc
 //! cargo xtask dist salus-agent
+//! cargo xtask header
 //! ```
 
 // no-panic restriction lints: handle every error, never panic (see CLAUDE.md)
@@ -44,6 +48,7 @@ use std::{
 };
 
 use anyhow::{Context as _, Result, bail};
+use cbindgen::{Builder, Config, EnumConfig, Language, RenameRule, Style};
 use clap::{Arg, ArgAction, Command};
 use clap_complete::{Shell, generate_to};
 use clap_mangen::Man;
@@ -60,6 +65,9 @@ fn main() -> Result<()> {
                         .help("Binary to generate artifacts for (salusd, salusc, salus-agent)"),
                 ),
         )
+        .subcommand(
+            Command::new("header").about("Generate the C header for libsalus's ffi feature"),
+        )
         .get_matches();
 
     match matches.subcommand() {
@@ -69,6 +77,7 @@ fn main() -> Result<()> {
                 .context("missing required `binary` argument")?;
             dist(binary)
         }
+        Some(("header", _)) => header(),
         _ => bail!("unknown subcommand"),
     }
 }
@@ -95,6 +104,45 @@ fn dist(binary: &str) -> Result<()> {
     Ok(())
 }
 
+/// The header's leading comment.
+const HEADER_PREAMBLE: &str = "\
+/*
+ * salus.h - the C interface to salusd, from libsalus built with the `ffi`
+ * feature. Generated by `cargo xtask header`; do not edit.
+ */";
+
+fn header() -> Result<()> {
+    let config = Config {
+        language: Language::C,
+        cpp_compat: true,
+        usize_is_size_t: true,
+        include_guard: Some("SALUS_H".to_string()),
+        header: Some(HEADER_PREAMBLE.to_string()),
+        style: Style::Type,
+        documentation: true,
+        enumeration: EnumConfig {
+            rename_variants: RenameRule::ScreamingSnakeCase,
+            prefix_with_name: true,
+            ..EnumConfig::default()
+        },
+        ..Config::default()
+    };
+    let out = PathBuf::from("libsalus/include/salus.h");
+    let written = Builder::new()
+        .with_config(config)
+        .with_src("libsalus/src/ffi.rs")
+        .generate()
+        .context("failed to generate the C header")?
+        .write_to_file(&out);
+    let state = if written {
+        "written to"
+    } else {
+        "unchanged at"
+    };
+    println!("Header {state} {}", out.display());
+    Ok(())
+}
+
 fn copy_licenses(out_dir: &Path) -> Result<()> {
     for name in ["LICENSE-MIT", "LICENSE-APACHE"] {
         fs::copy(name, out_dir.join(name))
@@ -185,6 +233,7 @@ fn salusd_command() -> Command {
                 .help("Enable logging to stdout/stderr"),
         )
         .arg(config_absolute_path_arg())
+        .arg(config_format_arg())
         .arg(
             Arg::new("tracing-absolute-path")
                 .short('t')
@@ -192,6 +241,12 @@ fn salusd_command() -> Command {
                 .value_name("PATH")
                 .help("Specify the absolute path to the tracing output file"),
         )
+        .arg(
+            Arg::new("log-filter")
+                .long("log-filter")
+                .value_name("DIRECTIVES")
+                .help("Add tracing directives for this run, e.g. salusd::store=trace,redb=warn"),
+        )
         .arg(
             Arg::new("database-absolute-path")
                 .short('d')
@@ -217,6 +272,7 @@ fn salusc_command() -> Command {
                 .value_name("PATH")
                 .help("Specify a path to the config file"),
         )
+        .arg(config_format_arg())
         .arg(socket_path_arg())
         .arg(
             Arg::new("agent-socket-path")
@@ -369,6 +425,7 @@ fn salus_agent_command() -> Command {
                 .help("Enable logging to stdout/stderr"),
         )
         .arg(config_absolute_path_arg())
+        .arg(config_format_arg())
         .arg(
             Arg::new("tracing-absolute-path")
                 .short('t')
@@ -413,6 +470,14 @@ fn config_absolute_path_arg() -> Arg {
         .help("Specify the absolute path to the config file")
 }
 
+fn config_format_arg() -> Arg {
+    Arg::new("config-format")
+        .long("config-format")
+        .value_name("FORMAT")
+        .value_parser(["toml", "yaml", "json"])
+        .help("Read the config file as toml, yaml or json, whatever its extension")
+}
+
 fn socket_path_arg() -> Arg {
     Arg::new("socket-path")
         .short('s')